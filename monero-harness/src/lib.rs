@@ -24,9 +24,13 @@ pub mod image;
 
 use crate::image::{MONEROD_DAEMON_CONTAINER_NAME, MONEROD_DEFAULT_NETWORK, RPC_PORT};
 use anyhow::{anyhow, bail, Context, Result};
+use futures::future::try_join_all;
 use monero_rpc::monerod;
 use monero_rpc::monerod::MonerodRpc as _;
-use monero_rpc::wallet::{self, GetAddress, MoneroWalletRpc as _, Refreshed, Transfer};
+use monero_rpc::wallet::{
+    self, Destination, GetAddress, MoneroWalletRpc as _, Refreshed, Transfer,
+};
+use std::convert::TryFrom;
 use std::time::Duration;
 use testcontainers::clients::Cli;
 use testcontainers::{Container, Docker, RunArgs};
@@ -38,6 +42,13 @@ const BLOCK_TIME_SECS: u64 = 1;
 /// Poll interval when checking if the wallet has synced with monerod.
 const WAIT_WALLET_SYNC_MILLIS: u64 = 1000;
 
+/// Blocks mined to the miner wallet up front. Coinbase outputs unlock after 60
+/// blocks, the miner can fund the other wallets from the rest without waiting.
+const INITIAL_BLOCKS: u32 = 120;
+
+/// Confirmations after which the outputs of a transaction are unlocked.
+pub const UNLOCK_BLOCKS: u32 = 10;
+
 #[derive(Clone, Debug)]
 pub struct Monero {
     monerod: Monerod,
@@ -47,7 +58,7 @@ impl<'c> Monero {
     /// Starts a new regtest monero container setup consisting out of 1 monerod
     /// node and n wallets. The docker container and network will be prefixed
     /// with a randomly generated `prefix`. One miner wallet is started
-    /// automatically.
+    /// automatically, the wallets are started concurrently.
     /// monerod container name is: `prefix`_`monerod`
     /// network is: `prefix`_`monero`
     /// miner wallet container name is: `miner`
@@ -65,36 +76,14 @@ impl<'c> Monero {
 
         tracing::info!("Starting monerod: {}", monerod_name);
         let (monerod, monerod_container) = Monerod::new(cli, monerod_name, network)?;
-        let mut containers = vec![];
-        let mut wallets = vec![];
-
-        let miner = "miner";
-        tracing::info!("Starting miner wallet: {}", miner);
-        let (miner_wallet, miner_container) =
-            MoneroWalletRpc::new(cli, &miner, &monerod, prefix.clone()).await?;
-
-        wallets.push(miner_wallet);
-        containers.push(miner_container);
-        for wallet in additional_wallets.iter() {
-            tracing::info!("Starting wallet: {}", wallet);
-
-            // Create new wallet, the RPC sometimes has startup problems so we allow retries
-            // (drop the container that failed and try again) Times out after
-            // trying for 5 minutes
-            let (wallet, container) = tokio::time::timeout(Duration::from_secs(300), async {
-                loop {
-                    let result = MoneroWalletRpc::new(cli, &wallet, &monerod, prefix.clone()).await;
-
-                    match result {
-                        Ok(tuple) => { return tuple; }
-                        Err(e) => { tracing::warn!("Monero wallet RPC emitted error {} - retrying to create wallet in 2 seconds...", e); }
-                    }
-                }
-            }).await.context("All retry attempts for creating a wallet exhausted")?;
 
-            wallets.push(wallet);
-            containers.push(container);
-        }
+        let names = std::iter::once("miner").chain(additional_wallets);
+        let (wallets, containers) = try_join_all(
+            names.map(|name| MoneroWalletRpc::new_with_retry(cli, name, &monerod, &prefix)),
+        )
+        .await?
+        .into_iter()
+        .unzip();
 
         Ok((Self { monerod, wallets }, monerod_container, containers))
     }
@@ -117,11 +106,10 @@ impl<'c> Monero {
         let miner_wallet = self.wallet("miner")?;
         let miner_address = miner_wallet.address().await?.address;
 
-        // generate the first 70 as bulk
         let monerod = &self.monerod;
         let res = monerod
             .client()
-            .generateblocks(70, miner_address.clone())
+            .generateblocks(INITIAL_BLOCKS, miner_address.clone())
             .await?;
         tracing::info!("Generated {:?} blocks", res.blocks.len());
         miner_wallet.refresh().await?;
@@ -129,6 +117,8 @@ impl<'c> Monero {
         Ok(())
     }
 
+    /// Funds the wallet with one output per amount, all sent in a single
+    /// transaction.
     pub async fn init_wallet(&self, name: &str, amount_in_outputs: Vec<u64>) -> Result<()> {
         let miner_wallet = self.wallet("miner")?;
         let miner_address = miner_wallet.address().await?.address;
@@ -137,18 +127,61 @@ impl<'c> Monero {
         let wallet = self.wallet(name)?;
         let address = wallet.address().await?.address;
 
-        for amount in amount_in_outputs {
-            if amount > 0 {
-                miner_wallet.transfer(&address, amount).await?;
-                tracing::info!("Funded {} wallet with {}", wallet.name, amount);
-                monerod
-                    .client()
-                    .generateblocks(10, miner_address.clone())
-                    .await?;
-                wallet.refresh().await?;
-            }
+        let destinations = amount_in_outputs
+            .into_iter()
+            .filter(|amount| *amount > 0)
+            .map(|amount| Destination {
+                amount,
+                address: address.clone(),
+            })
+            .collect::<Vec<_>>();
+
+        if destinations.is_empty() {
+            return Ok(());
         }
 
+        let outputs = destinations.len();
+        miner_wallet
+            .client()
+            .transfer(0, destinations, false)
+            .await?;
+        tracing::info!("Funded {} wallet with {} outputs", wallet.name, outputs);
+
+        monerod
+            .client()
+            .generateblocks(UNLOCK_BLOCKS, miner_address)
+            .await?;
+        wallet.refresh().await?;
+
+        Ok(())
+    }
+
+    /// Mines the blocks missing until the outputs `transfer` sent to the
+    /// wallet `name` are unlocked, without waiting for the miner.
+    pub async fn mine_to_unlock(&self, name: &str, transfer: &Transfer) -> Result<()> {
+        let miner_address = self.wallet("miner")?.address().await?.address;
+        let wallet = self.wallet(name)?;
+        let address = wallet.address().await?.address;
+        let tx_key = transfer
+            .tx_key
+            .context("Transfer was made without returning the tx key")?;
+
+        let confirmations = wallet
+            .client()
+            .check_tx_key(transfer.tx_hash.clone(), tx_key.to_string(), address)
+            .await?
+            .confirmations;
+        let missing = u64::from(UNLOCK_BLOCKS).saturating_sub(confirmations);
+
+        if missing > 0 {
+            self.monerod
+                .client()
+                .generateblocks(u32::try_from(missing)?, miner_address)
+                .await?;
+            tracing::info!("Mined {} blocks to unlock {}", missing, transfer.tx_hash);
+        }
+        wallet.refresh().await?;
+
         Ok(())
     }
 
@@ -243,16 +276,45 @@ impl<'c> Monerod {
 }
 
 impl<'c> MoneroWalletRpc {
+    /// Starts a new wallet container, retrying for up to 5 minutes because
+    /// the RPC sometimes has startup problems. The container that failed is
+    /// dropped before trying again.
+    async fn new_with_retry(
+        cli: &'c Cli,
+        name: &str,
+        monerod: &Monerod,
+        prefix: &str,
+    ) -> Result<(Self, Container<'c, Cli, image::MoneroWalletRpc>)> {
+        tracing::info!("Starting wallet: {}", name);
+
+        time::timeout(Duration::from_secs(300), async {
+            loop {
+                match MoneroWalletRpc::new(cli, name, monerod, prefix).await {
+                    Ok(tuple) => return tuple,
+                    Err(e) => {
+                        tracing::warn!(
+                            "Monero wallet RPC emitted error {} - retrying to create wallet in 2 seconds...",
+                            e
+                        );
+                        time::sleep(Duration::from_secs(2)).await;
+                    }
+                }
+            }
+        })
+        .await
+        .context("All retry attempts for creating a wallet exhausted")
+    }
+
     /// Starts a new wallet container which is attached to
     /// MONEROD_DEFAULT_NETWORK and MONEROD_DAEMON_CONTAINER_NAME
     async fn new(
         cli: &'c Cli,
         name: &str,
         monerod: &Monerod,
-        prefix: String,
+        prefix: &str,
     ) -> Result<(Self, Container<'c, Cli, image::MoneroWalletRpc>)> {
         let daemon_address = format!("{}:{}", monerod.name, RPC_PORT);
-        let image = image::MoneroWalletRpc::new(&name, daemon_address);
+        let image = image::MoneroWalletRpc::new(name, daemon_address);
 
         let network = monerod.network.clone();
         let run_args = RunArgs::default()
@@ -315,6 +377,35 @@ impl<'c> MoneroWalletRpc {
         Ok(balance)
     }
 
+    pub async fn unlocked_balance(&self) -> Result<u64> {
+        self.client().refresh().await?;
+        let balance = self.client().get_balance(0).await?.unlocked_balance;
+
+        Ok(balance)
+    }
+
+    /// Waits for the unlocked balance to reach `expected`, failing with the
+    /// last unlocked balance after ~30 seconds.
+    pub async fn wait_for_unlocked_balance(&self, expected: u64) -> Result<()> {
+        let mut retry: u8 = 0;
+        loop {
+            let unlocked = self.unlocked_balance().await?;
+            if unlocked == expected {
+                return Ok(());
+            }
+            if retry >= 30 {
+                bail!(
+                    "Unlocked balance of wallet {} is {} instead of {} after 30 retries",
+                    self.name,
+                    unlocked,
+                    expected
+                )
+            }
+            time::sleep(Duration::from_millis(WAIT_WALLET_SYNC_MILLIS)).await;
+            retry += 1;
+        }
+    }
+
     pub async fn refresh(&self) -> Result<Refreshed> {
         Ok(self.client().refresh().await?)
     }
@@ -1,3 +1,5 @@
+#![cfg(feature = "docker-tests")]
+
 use monero_harness::Monero;
 use monero_rpc::monerod::MonerodRpc as _;
 use spectral::prelude::*;
@@ -1,4 +1,7 @@
+#![cfg(feature = "docker-tests")]
+
 use monero_harness::{Monero, MoneroWalletRpc};
+use monero_rpc::monerod::MonerodRpc as _;
 use monero_rpc::wallet::MoneroWalletRpc as _;
 use spectral::prelude::*;
 use std::time::Duration;
@@ -55,6 +58,56 @@ async fn fund_transfer_and_check_tx_key() {
     assert_that!(res.received).is_equal_to(send_to_bob);
 }
 
+#[tokio::test]
+async fn transfer_is_unlocked_after_mining_to_unlock() {
+    let _guard = tracing_subscriber::fmt()
+        .with_env_filter("warn,test=debug,monero_harness=debug,monero_rpc=debug")
+        .set_default();
+
+    let fund_alice: u64 = 1_000_000_000_000;
+    let send_to_bob = 5_000_000_000;
+
+    let tc = Cli::default();
+    let (monero, _monerod_container, _wallet_containers) =
+        Monero::new(&tc, vec!["alice", "bob"]).await.unwrap();
+    let alice_wallet = monero.wallet("alice").unwrap();
+    let bob_wallet = monero.wallet("bob").unwrap();
+
+    // no miner is started, only the harness mines blocks
+    monero.init_miner().await.unwrap();
+    monero.init_wallet("alice", vec![fund_alice]).await.unwrap();
+
+    let bob_address = bob_wallet.address().await.unwrap().address;
+    let transfer = alice_wallet
+        .transfer(&bob_address, send_to_bob)
+        .await
+        .unwrap();
+
+    let miner_address = monero
+        .wallet("miner")
+        .unwrap()
+        .address()
+        .await
+        .unwrap()
+        .address;
+    monero
+        .monerod()
+        .client()
+        .generateblocks(1, miner_address)
+        .await
+        .unwrap();
+
+    assert_that!(bob_wallet.balance().await.unwrap()).is_equal_to(send_to_bob);
+    assert_that!(bob_wallet.unlocked_balance().await.unwrap()).is_equal_to(0);
+
+    monero.mine_to_unlock("bob", &transfer).await.unwrap();
+
+    bob_wallet
+        .wait_for_unlocked_balance(send_to_bob)
+        .await
+        .unwrap();
+}
+
 async fn wait_for_wallet_to_catch_up(wallet: &MoneroWalletRpc, expected_balance: u64) {
     let max_retry = 15;
     let mut retry = 0;
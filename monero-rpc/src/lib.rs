@@ -12,6 +12,7 @@
 )]
 #![forbid(unsafe_code)]
 
+pub mod lws;
 pub mod monerod;
 pub mod wallet;
 
@@ -0,0 +1,213 @@
+//! Client of a light wallet server speaking the REST API of monero-lws and
+//! OpenMonero.
+//!
+//! The server scans the blockchain for the outputs of the accounts it knows
+//! the view key of, it never learns a spend key.
+
+use anyhow::{Context, Result};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize};
+
+#[derive(Debug, Clone)]
+pub struct Client {
+    inner: reqwest::Client,
+    base_url: reqwest::Url,
+}
+
+impl Client {
+    pub fn new(mut base_url: reqwest::Url) -> Result<Self> {
+        // The endpoints are relative to the path of the server
+        if !base_url.path().ends_with('/') {
+            let path = format!("{}/", base_url.path());
+            base_url.set_path(&path);
+        }
+
+        Ok(Self {
+            inner: reqwest::ClientBuilder::new()
+                .connection_verbose(true)
+                .build()?,
+            base_url,
+        })
+    }
+
+    /// Logs into the account of the address, creating it if it does not
+    /// exist yet.
+    pub async fn login(&self, account: &Account) -> Result<Login> {
+        self.request("login", &LoginPayload {
+            address: &account.address,
+            view_key: &account.view_key,
+            create_account: true,
+            generated_locally: false,
+        })
+        .await
+    }
+
+    /// Asks the server to scan the blockchain for the account again, starting
+    /// at `from_height`. Servers may only do so after an approval or a fee.
+    pub async fn import_request(&self, account: &Account, from_height: u64) -> Result<Import> {
+        self.request("import_request", &ImportPayload {
+            address: &account.address,
+            view_key: &account.view_key,
+            from_height,
+        })
+        .await
+    }
+
+    pub async fn get_address_txs(&self, account: &Account) -> Result<AddressTxs> {
+        self.request("get_address_txs", account).await
+    }
+
+    async fn request<Req, Res>(&self, endpoint: &str, request: &Req) -> Result<Res>
+    where
+        Req: Serialize,
+        Res: DeserializeOwned,
+    {
+        let url = self.base_url.join(endpoint).context("url is well formed")?;
+        let response = self.inner.post(url).json(request).send().await?;
+
+        if !response.status().is_success() {
+            anyhow::bail!(
+                "Request to {} failed with status code {}",
+                endpoint,
+                response.status()
+            )
+        }
+
+        Ok(response.json().await?)
+    }
+}
+
+/// An address together with its private view key, in hex.
+#[derive(Clone, Debug, Serialize)]
+pub struct Account {
+    pub address: String,
+    pub view_key: String,
+}
+
+#[derive(Serialize, Debug)]
+struct LoginPayload<'a> {
+    address: &'a str,
+    view_key: &'a str,
+    create_account: bool,
+    generated_locally: bool,
+}
+
+#[derive(Serialize, Debug)]
+struct ImportPayload<'a> {
+    address: &'a str,
+    view_key: &'a str,
+    from_height: u64,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct Login {
+    pub new_address: bool,
+    /// The height the server started scanning at for a new account.
+    pub start_height: Option<u64>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Import {
+    pub request_fulfilled: bool,
+    pub status: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct AddressTxs {
+    pub scanned_height: u64,
+    pub blockchain_height: u64,
+    #[serde(default)]
+    pub transactions: Vec<AddressTx>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct AddressTx {
+    pub hash: String,
+    /// The piconero the transaction sends to the account.
+    #[serde(deserialize_with = "u64_from_string")]
+    pub total_received: u64,
+    #[serde(default)]
+    pub unlock_time: u64,
+    /// Not set while the transaction is in the mempool.
+    pub height: Option<u64>,
+    #[serde(default)]
+    pub mempool: bool,
+}
+
+impl AddressTx {
+    pub fn confirmations(&self, blockchain_height: u64) -> u64 {
+        match self.height {
+            Some(height) if !self.mempool => blockchain_height.saturating_sub(height),
+            _ => 0,
+        }
+    }
+}
+
+/// The servers send amounts as strings so they are not rounded by
+/// JavaScript clients.
+fn u64_from_string<'de, D>(deserializer: D) -> Result<u64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    String::deserialize(deserializer)?
+        .parse()
+        .map_err(serde::de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn endpoints_are_relative_to_the_path_of_the_server() {
+        let client = Client::new("https://lws.example.com/api".parse().unwrap()).unwrap();
+
+        assert_eq!(
+            client.base_url.join("login").unwrap().as_str(),
+            "https://lws.example.com/api/login"
+        );
+    }
+
+    #[test]
+    fn can_deserialize_address_txs() {
+        let response = r#"{
+          "total_received": "1000000000000",
+          "scanned_height": 2500010,
+          "scanned_block_height": 2500010,
+          "start_height": 2499990,
+          "blockchain_height": 2500010,
+          "transactions": [
+            {
+              "id": 1,
+              "hash": "a8ccc5bc71e9ba8c8a4f0a4e8e1b7c3a4d3b3f4e5a6b7c8d9e0f1a2b3c4d5e6f",
+              "timestamp": "2021-12-01T12:00:00Z",
+              "total_received": "1000000000000",
+              "total_sent": "0",
+              "unlock_time": 0,
+              "height": 2500000,
+              "coinbase": false,
+              "mempool": false,
+              "mixin": 10
+            },
+            {
+              "id": 2,
+              "hash": "0f1a2b3c4d5e6fa8ccc5bc71e9ba8c8a4f0a4e8e1b7c3a4d3b3f4e5a6b7c8d9e",
+              "timestamp": "2021-12-01T12:20:00Z",
+              "total_received": "5",
+              "total_sent": "0",
+              "unlock_time": 0,
+              "coinbase": false,
+              "mempool": true,
+              "mixin": 10
+            }
+          ]
+        }"#;
+
+        let txs = serde_json::from_str::<AddressTxs>(response).unwrap();
+
+        assert_eq!(txs.transactions.len(), 2);
+        assert_eq!(txs.transactions[0].total_received, 1_000_000_000_000);
+        assert_eq!(txs.transactions[0].confirmations(txs.blockchain_height), 10);
+        assert_eq!(txs.transactions[1].confirmations(txs.blockchain_height), 0);
+    }
+}
@@ -21,6 +21,7 @@ pub struct Client {
     base_url: reqwest::Url,
     get_o_indexes_bin_url: reqwest::Url,
     get_outs_bin_url: reqwest::Url,
+    get_transactions_url: reqwest::Url,
 }
 
 impl Client {
@@ -29,6 +30,18 @@ impl Client {
         Self::new("127.0.0.1".to_owned(), port)
     }
 
+    /// New monerod RPC client for a daemon address given as `<host>:<port>`.
+    pub fn from_daemon_address(daemon_address: &str) -> Result<Self> {
+        let (host, port) = daemon_address
+            .rsplit_once(':')
+            .with_context(|| format!("Daemon address {} is missing a port", daemon_address))?;
+        let port = port.parse().with_context(|| {
+            format!("Failed to parse port of daemon address {}", daemon_address)
+        })?;
+
+        Self::new(host.to_owned(), port)
+    }
+
     fn new(host: String, port: u16) -> Result<Self> {
         Ok(Self {
             inner: reqwest::ClientBuilder::new()
@@ -43,6 +56,9 @@ impl Client {
             get_outs_bin_url: format!("http://{}:{}/get_outs.bin", host, port)
                 .parse()
                 .context("url is well formed")?,
+            get_transactions_url: format!("http://{}:{}/get_transactions", host, port)
+                .parse()
+                .context("url is well formed")?,
         })
     }
 
@@ -58,6 +74,21 @@ impl Client {
             .await
     }
 
+    pub async fn get_transactions(&self, txs_hashes: Vec<String>) -> Result<GetTransactions> {
+        let response = self
+            .inner
+            .post(self.get_transactions_url.clone())
+            .json(&GetTransactionsPayload { txs_hashes })
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            anyhow::bail!("Request failed with status code {}", response.status())
+        }
+
+        Ok(response.json().await?)
+    }
+
     async fn binary_request<Req, Res>(&self, url: reqwest::Url, request: Req) -> Result<Res>
     where
         Req: Serialize,
@@ -132,6 +163,38 @@ struct GetOutsPayload {
     outputs: Vec<GetOutputsOut>,
 }
 
+#[derive(Clone, Debug, Serialize)]
+struct GetTransactionsPayload {
+    txs_hashes: Vec<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct GetTransactions {
+    pub status: String,
+    #[serde(default)]
+    pub txs: Vec<GetTransactionsTx>,
+    #[serde(default)]
+    pub missed_tx: Vec<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct GetTransactionsTx {
+    pub tx_hash: String,
+    pub as_hex: String,
+    pub in_pool: bool,
+}
+
+impl GetTransactionsTx {
+    /// Decodes the transaction from the hex encoding returned by monerod.
+    pub fn transaction(&self) -> Result<monero::Transaction> {
+        let bytes = hex::decode(&self.as_hex).context("Transaction is not valid hex")?;
+        let transaction =
+            monero::consensus::deserialize(&bytes).context("Failed to decode transaction")?;
+
+        Ok(transaction)
+    }
+}
+
 #[derive(Clone, Copy, Debug, Serialize)]
 pub struct GetOutputsOut {
     pub amount: u64,
@@ -19,6 +19,7 @@ pub trait MoneroWalletRpc {
     ) -> Transfer;
     async fn get_height(&self) -> BlockHeight;
     async fn check_tx_key(&self, txid: String, tx_key: String, address: String) -> CheckTxKey;
+    async fn get_transfer_by_txid(&self, txid: String) -> GetTransferByTxid;
     #[allow(clippy::too_many_arguments)]
     async fn generate_from_keys(
         &self,
@@ -31,8 +32,10 @@ pub trait MoneroWalletRpc {
         autosave_current: bool,
     ) -> GenerateFromKeys;
     async fn refresh(&self) -> Refreshed;
+    async fn rescan_blockchain(&self) -> WalletRescanned;
     async fn sweep_all(&self, address: String) -> SweepAll;
     async fn get_version(&self) -> Version;
+    async fn query_key(&self, key_type: String) -> QueryKey;
 }
 
 #[jsonrpc_client::implement(MoneroWalletRpc)]
@@ -170,6 +173,18 @@ impl From<CheckTxKeyResponse> for CheckTxKey {
     }
 }
 
+/// A transfer of the loaded wallet, only the fields we need are decoded.
+#[derive(Clone, Debug, Deserialize)]
+pub struct GetTransferByTxid {
+    pub transfer: TransferEntry,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct TransferEntry {
+    pub txid: String,
+    pub unlock_time: u64,
+}
+
 #[derive(Clone, Debug, Deserialize)]
 pub struct GenerateFromKeys {
     pub address: String,
@@ -192,6 +207,17 @@ pub struct SweepAll {
     weight_list: Vec<u32>,
 }
 
+#[derive(Clone, Deserialize)]
+pub struct QueryKey {
+    pub key: String,
+}
+
+impl std::fmt::Debug for QueryKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "QueryKey {{ key: [*****] }}")
+    }
+}
+
 #[derive(Debug, Copy, Clone, Deserialize)]
 pub struct Version {
     pub version: u32,
@@ -200,6 +226,7 @@ pub struct Version {
 pub type WalletCreated = Empty;
 pub type WalletClosed = Empty;
 pub type WalletOpened = Empty;
+pub type WalletRescanned = Empty;
 
 /// Zero-sized struct to allow serde to deserialize an empty JSON object.
 ///
@@ -244,7 +271,7 @@ mod tests {
           }
         }"#;
 
-        let _: Response<SweepAll> = serde_json::from_str(&response).unwrap();
+        let _: Response<SweepAll> = serde_json::from_str(response).unwrap();
     }
 
     #[test]
@@ -256,6 +283,37 @@ mod tests {
           }
         }"#;
 
-        let _: Response<WalletCreated> = serde_json::from_str(&response).unwrap();
+        let _: Response<WalletCreated> = serde_json::from_str(response).unwrap();
+    }
+
+    #[test]
+    fn can_deserialize_get_transfer_by_txid() {
+        let response = r#"{
+          "id": "0",
+          "jsonrpc": "2.0",
+          "result": {
+            "transfer": {
+              "address": "55LTR8KniP4LQGJSPtbYDacR7dz8RBFnsfAKMaMuwUNYX6aQbBcovzDPyrQF9KXF9tVU6Xk3K8no1BywnJX6GvZX8yJsXvt",
+              "amount": 300000000000,
+              "confirmations": 1,
+              "double_spend_seen": false,
+              "fee": 21650200000,
+              "height": 153624,
+              "note": "",
+              "payment_id": "0000000000000000",
+              "subaddr_index": {"major": 0, "minor": 0},
+              "suggested_confirmations_threshold": 1,
+              "timestamp": 1535918400,
+              "txid": "c36258a276018c3a4bc1f195a7fb530f50cd63a4fa765fb7c6f7f49fc051762a",
+              "type": "out",
+              "unlock_time": 0
+            }
+          }
+        }"#;
+
+        let response: Response<GetTransferByTxid> = serde_json::from_str(response).unwrap();
+        let result: Result<GetTransferByTxid, _> = response.payload.into();
+
+        assert_eq!(result.unwrap().transfer.unlock_time, 0);
     }
 }
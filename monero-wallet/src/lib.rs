@@ -55,7 +55,7 @@ impl Wallet {
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "docker-tests"))]
 mod tests {
     use super::*;
     use monero_harness::image::Monerod;
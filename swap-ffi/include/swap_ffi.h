@@ -0,0 +1,156 @@
+#ifndef SWAP_FFI_H
+#define SWAP_FFI_H
+
+/* Generated with cbindgen from swap-ffi, do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * A taker with its wallets and running swaps.
+ */
+typedef struct SwapTaker SwapTaker;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Opens the taker described by `config_json`, a JSON object with at least
+ * the `data_dir`.
+ *
+ * Returns `NULL` on failure, e.g. if another process uses the data
+ * directory.
+ *
+ * # Safety
+ *
+ * `config_json` has to be `NULL` or a NUL-terminated string.
+ */
+SwapTaker *swap_taker_new(const char *config_json);
+
+/**
+ * Releases the taker and stops the swaps it runs, they can be resumed later
+ * on.
+ *
+ * # Safety
+ *
+ * `taker` has to be `NULL` or returned by [`swap_taker_new`], it must not
+ * be used afterwards.
+ */
+void swap_taker_free(SwapTaker *taker);
+
+/**
+ * Starts a swap described by `request_json` and returns its id.
+ *
+ * The request is a JSON object with the `seller` address including its peer
+ * id, the `bitcoin_change_address`, the `monero_receive_address` and the
+ * `btc_amount` in satoshi. The call returns once the swap is set up, the
+ * swap itself runs in the background.
+ *
+ * # Safety
+ *
+ * `taker` has to be returned by [`swap_taker_new`], `request_json` has to be
+ * `NULL` or a NUL-terminated string.
+ */
+char *swap_start(const SwapTaker *taker, const char *request_json);
+
+/**
+ * Resumes a swap in the background, returns `0` on success and `-1` on
+ * failure.
+ *
+ * # Safety
+ *
+ * `taker` has to be returned by [`swap_taker_new`], `swap_id` has to be
+ * `NULL` or a NUL-terminated string.
+ */
+int32_t swap_resume(const SwapTaker *taker, const char *swap_id);
+
+/**
+ * Publishes the cancel transaction of a swap that is not running and
+ * returns its id.
+ *
+ * # Safety
+ *
+ * `taker` has to be returned by [`swap_taker_new`], `swap_id` has to be
+ * `NULL` or a NUL-terminated string.
+ */
+char *swap_cancel(const SwapTaker *taker, const char *swap_id);
+
+/**
+ * Publishes the refund transaction of a swap that is not running and
+ * returns its id.
+ *
+ * # Safety
+ *
+ * `taker` has to be returned by [`swap_taker_new`], `swap_id` has to be
+ * `NULL` or a NUL-terminated string.
+ */
+char *swap_refund(const SwapTaker *taker, const char *swap_id);
+
+/**
+ * Stops a running swap in its last saved state, returns `0` on success and
+ * `-1` on failure. It can be resumed later on.
+ *
+ * # Safety
+ *
+ * `taker` has to be returned by [`swap_taker_new`], `swap_id` has to be
+ * `NULL` or a NUL-terminated string.
+ */
+int32_t swap_stop(const SwapTaker *taker, const char *swap_id);
+
+/**
+ * Returns the status of a swap as a JSON object with its `state`, whether it
+ * is `finished` and whether it is `running` in this taker.
+ *
+ * # Safety
+ *
+ * `taker` has to be returned by [`swap_taker_new`], `swap_id` has to be
+ * `NULL` or a NUL-terminated string.
+ */
+char *swap_status(const SwapTaker *taker, const char *swap_id);
+
+/**
+ * Returns everything known about a swap as a JSON object: its `state`, the
+ * amounts, the lock transactions, the expired `timelock`, its `history` and
+ * the `errors` that stopped it.
+ *
+ * # Safety
+ *
+ * `taker` has to be returned by [`swap_taker_new`], `swap_id` has to be
+ * `NULL` or a NUL-terminated string.
+ */
+char *swap_info(const SwapTaker *taker, const char *swap_id);
+
+/**
+ * Returns the oldest event that was not polled yet as a JSON object with its
+ * `type`, or `NULL` if there is none.
+ *
+ * # Safety
+ *
+ * `taker` has to be returned by [`swap_taker_new`].
+ */
+char *swap_poll_event(const SwapTaker *taker);
+
+/**
+ * Returns the message of the last error on the calling thread, or `NULL` if
+ * the last call succeeded.
+ */
+char *swap_last_error(void);
+
+/**
+ * Releases a string returned by one of the functions.
+ *
+ * # Safety
+ *
+ * `string` has to be `NULL` or returned by one of the functions, it must
+ * not be used afterwards.
+ */
+void swap_string_free(char *string);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* SWAP_FFI_H */
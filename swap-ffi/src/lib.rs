@@ -0,0 +1,356 @@
+#![warn(
+    unused_extern_crates,
+    rust_2018_idioms,
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    clippy::fallible_impl_from,
+    clippy::cast_precision_loss,
+    clippy::cast_possible_wrap,
+    clippy::dbg_macro
+)]
+#![cfg_attr(not(test), warn(clippy::unwrap_used))]
+#![allow(missing_debug_implementations)]
+
+//! C bindings to embed the taker in an application, e.g. a mobile wallet
+//! calling them from Kotlin or Swift, instead of running the `swap` binary.
+//!
+//! The header is `include/swap_ffi.h`, generated with `cbindgen --config
+//! cbindgen.toml --output include/swap_ffi.h` from this directory.
+//!
+//! All functions take and return NUL-terminated UTF-8 strings, structured
+//! values are JSON, see [`taker`] for their fields. Strings returned by the
+//! functions belong to the caller and have to be released with
+//! [`swap_string_free`]. A function that fails returns `NULL` or `-1` and
+//! leaves a message for [`swap_last_error`] on the calling thread. Panics do
+//! not cross the boundary, they are reported like any other error.
+//!
+//! A [`SwapTaker`] is an opaque handle that has to be released with
+//! [`swap_taker_free`], which also stops the swaps it runs. It can be used
+//! from several threads at once.
+
+pub mod taker;
+
+use anyhow::{anyhow, bail, Context, Result};
+use std::any::Any;
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use taker::{Config, StartRequest, Taker};
+use uuid::Uuid;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<String>> = RefCell::new(None);
+}
+
+/// A taker with its wallets and running swaps.
+pub struct SwapTaker(Taker);
+
+/// Opens the taker described by `config_json`, a JSON object with at least
+/// the `data_dir`.
+///
+/// Returns `NULL` on failure, e.g. if another process uses the data
+/// directory.
+///
+/// # Safety
+///
+/// `config_json` has to be `NULL` or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn swap_taker_new(config_json: *const c_char) -> *mut SwapTaker {
+    call(|| {
+        let config = serde_json::from_str::<Config>(str_arg(config_json, "config")?)
+            .context("Invalid config")?;
+
+        Ok(Box::into_raw(Box::new(SwapTaker(Taker::new(config)?))))
+    })
+    .unwrap_or(ptr::null_mut())
+}
+
+/// Releases the taker and stops the swaps it runs, they can be resumed later
+/// on.
+///
+/// # Safety
+///
+/// `taker` has to be `NULL` or returned by [`swap_taker_new`], it must not
+/// be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn swap_taker_free(taker: *mut SwapTaker) {
+    if taker.is_null() {
+        return;
+    }
+
+    let _ = call(|| {
+        drop(Box::from_raw(taker));
+        Ok(())
+    });
+}
+
+/// Starts a swap described by `request_json` and returns its id.
+///
+/// The request is a JSON object with the `seller` address including its peer
+/// id, the `bitcoin_change_address`, the `monero_receive_address` and the
+/// `btc_amount` in satoshi. The call returns once the swap is set up, the
+/// swap itself runs in the background.
+///
+/// # Safety
+///
+/// `taker` has to be returned by [`swap_taker_new`], `request_json` has to be
+/// `NULL` or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn swap_start(
+    taker: *const SwapTaker,
+    request_json: *const c_char,
+) -> *mut c_char {
+    call(|| {
+        let request = serde_json::from_str::<StartRequest>(str_arg(request_json, "request")?)
+            .context("Invalid request")?;
+        let swap_id = taker_arg(taker)?.start(request)?;
+
+        string_out(swap_id.to_string())
+    })
+    .unwrap_or(ptr::null_mut())
+}
+
+/// Resumes a swap in the background, returns `0` on success and `-1` on
+/// failure.
+///
+/// # Safety
+///
+/// `taker` has to be returned by [`swap_taker_new`], `swap_id` has to be
+/// `NULL` or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn swap_resume(taker: *const SwapTaker, swap_id: *const c_char) -> i32 {
+    call(|| taker_arg(taker)?.resume(swap_id_arg(swap_id)?))
+        .map(|()| 0)
+        .unwrap_or(-1)
+}
+
+/// Publishes the cancel transaction of a swap that is not running and
+/// returns its id.
+///
+/// # Safety
+///
+/// `taker` has to be returned by [`swap_taker_new`], `swap_id` has to be
+/// `NULL` or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn swap_cancel(
+    taker: *const SwapTaker,
+    swap_id: *const c_char,
+) -> *mut c_char {
+    call(|| {
+        let txid = taker_arg(taker)?.cancel(swap_id_arg(swap_id)?)?;
+
+        string_out(txid.to_string())
+    })
+    .unwrap_or(ptr::null_mut())
+}
+
+/// Publishes the refund transaction of a swap that is not running and
+/// returns its id.
+///
+/// # Safety
+///
+/// `taker` has to be returned by [`swap_taker_new`], `swap_id` has to be
+/// `NULL` or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn swap_refund(
+    taker: *const SwapTaker,
+    swap_id: *const c_char,
+) -> *mut c_char {
+    call(|| {
+        let txid = taker_arg(taker)?.refund(swap_id_arg(swap_id)?)?;
+
+        string_out(txid.to_string())
+    })
+    .unwrap_or(ptr::null_mut())
+}
+
+/// Stops a running swap in its last saved state, returns `0` on success and
+/// `-1` on failure. It can be resumed later on.
+///
+/// # Safety
+///
+/// `taker` has to be returned by [`swap_taker_new`], `swap_id` has to be
+/// `NULL` or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn swap_stop(taker: *const SwapTaker, swap_id: *const c_char) -> i32 {
+    call(|| taker_arg(taker)?.stop(swap_id_arg(swap_id)?))
+        .map(|()| 0)
+        .unwrap_or(-1)
+}
+
+/// Returns the status of a swap as a JSON object with its `state`, whether it
+/// is `finished` and whether it is `running` in this taker.
+///
+/// # Safety
+///
+/// `taker` has to be returned by [`swap_taker_new`], `swap_id` has to be
+/// `NULL` or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn swap_status(
+    taker: *const SwapTaker,
+    swap_id: *const c_char,
+) -> *mut c_char {
+    call(|| {
+        let status = taker_arg(taker)?.status(swap_id_arg(swap_id)?)?;
+
+        string_out(serde_json::to_string(&status)?)
+    })
+    .unwrap_or(ptr::null_mut())
+}
+
+/// Returns everything known about a swap as a JSON object: its `state`, the
+/// amounts, the lock transactions, the expired `timelock`, its `history` and
+/// the `errors` that stopped it.
+///
+/// # Safety
+///
+/// `taker` has to be returned by [`swap_taker_new`], `swap_id` has to be
+/// `NULL` or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn swap_info(taker: *const SwapTaker, swap_id: *const c_char) -> *mut c_char {
+    call(|| {
+        let info = taker_arg(taker)?.info(swap_id_arg(swap_id)?)?;
+
+        string_out(serde_json::to_string(&info)?)
+    })
+    .unwrap_or(ptr::null_mut())
+}
+
+/// Returns the oldest event that was not polled yet as a JSON object with its
+/// `type`, or `NULL` if there is none.
+///
+/// # Safety
+///
+/// `taker` has to be returned by [`swap_taker_new`].
+#[no_mangle]
+pub unsafe extern "C" fn swap_poll_event(taker: *const SwapTaker) -> *mut c_char {
+    call(|| match taker_arg(taker)?.poll_event() {
+        Some(event) => string_out(serde_json::to_string(&event)?),
+        None => Ok(ptr::null_mut()),
+    })
+    .unwrap_or(ptr::null_mut())
+}
+
+/// Returns the message of the last error on the calling thread, or `NULL` if
+/// the last call succeeded.
+#[no_mangle]
+pub extern "C" fn swap_last_error() -> *mut c_char {
+    LAST_ERROR
+        .with(|last_error| last_error.borrow().clone())
+        .and_then(|message| string_out(message).ok())
+        .unwrap_or(ptr::null_mut())
+}
+
+/// Releases a string returned by one of the functions.
+///
+/// # Safety
+///
+/// `string` has to be `NULL` or returned by one of the functions, it must
+/// not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn swap_string_free(string: *mut c_char) {
+    if !string.is_null() {
+        drop(CString::from_raw(string));
+    }
+}
+
+/// Runs `f`, records its error or panic as the last error.
+fn call<T>(f: impl FnOnce() -> Result<T>) -> Option<T> {
+    let result = panic::catch_unwind(AssertUnwindSafe(f))
+        .unwrap_or_else(|payload| Err(anyhow!("Panicked: {}", panic_message(payload.as_ref()))));
+
+    LAST_ERROR.with(|last_error| {
+        *last_error.borrow_mut() = result.as_ref().err().map(|error| format!("{:#}", error))
+    });
+
+    result.ok()
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown cause")
+}
+
+unsafe fn str_arg<'a>(value: *const c_char, name: &str) -> Result<&'a str> {
+    if value.is_null() {
+        bail!("The {} is NULL", name)
+    }
+
+    CStr::from_ptr(value)
+        .to_str()
+        .with_context(|| format!("The {} is not UTF-8", name))
+}
+
+unsafe fn swap_id_arg(swap_id: *const c_char) -> Result<Uuid> {
+    str_arg(swap_id, "swap id")?
+        .parse()
+        .context("Invalid swap id")
+}
+
+unsafe fn taker_arg<'a>(taker: *const SwapTaker) -> Result<&'a Taker> {
+    match taker.as_ref() {
+        Some(SwapTaker(taker)) => Ok(taker),
+        None => bail!("The taker is NULL"),
+    }
+}
+
+fn string_out(value: String) -> Result<*mut c_char> {
+    Ok(CString::new(value)
+        .context("The string contains a NUL byte")?
+        .into_raw())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn last_error() -> Option<String> {
+        let message = swap_last_error();
+        if message.is_null() {
+            return None;
+        }
+
+        let owned = unsafe { CStr::from_ptr(message) }
+            .to_str()
+            .unwrap()
+            .to_owned();
+        unsafe { swap_string_free(message) };
+
+        Some(owned)
+    }
+
+    #[test]
+    fn invalid_arguments_are_reported_as_last_error() {
+        let config = CString::new(r#"{"testnet": true}"#).unwrap();
+
+        let taker = unsafe { swap_taker_new(config.as_ptr()) };
+        assert!(taker.is_null());
+        assert!(last_error().unwrap().starts_with("Invalid config"));
+
+        let swap_id = CString::new("not a swap id").unwrap();
+        assert_eq!(unsafe { swap_resume(ptr::null(), swap_id.as_ptr()) }, -1);
+        assert_eq!(last_error().unwrap(), "The taker is NULL");
+        assert!(unsafe { swap_status(ptr::null(), ptr::null()) }.is_null());
+        assert!(unsafe { swap_info(ptr::null(), ptr::null()) }.is_null());
+        assert_eq!(last_error().unwrap(), "The taker is NULL");
+
+        unsafe {
+            swap_taker_free(ptr::null_mut());
+            swap_string_free(ptr::null_mut());
+        }
+    }
+
+    #[test]
+    fn panics_do_not_cross_the_boundary() {
+        assert_eq!(call(|| -> Result<()> { panic!("boom") }), None);
+        assert_eq!(last_error().unwrap(), "Panicked: boom");
+
+        assert_eq!(call(|| Ok(42)), Some(42));
+        assert_eq!(last_error(), None);
+    }
+}
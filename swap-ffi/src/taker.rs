@@ -0,0 +1,757 @@
+//! The taker behind the C functions.
+//!
+//! A [`Taker`] opens the database and the wallets once and runs the swaps on
+//! a runtime of its own, next to each other like the swaps of a
+//! [`SwapFactory`]. Every call blocks until its work is done, a started or
+//! resumed swap keeps running in the background until it finishes, is
+//! stopped or the taker is dropped, its progress is reported as [`Event`]s.
+//!
+//! A stopped swap is left in its last saved state, like one whose process
+//! was killed, and can be resumed later on.
+
+use anyhow::{bail, Context, Result};
+use libp2p::Multiaddr;
+use monero_rpc::monerod;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::convert::TryInto;
+use std::path::PathBuf;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+use swap::cli::command::{
+    validate_bitcoin_address, validate_monero_address, DEFAULT_BITCOIN_CONFIRMATION_TARGET,
+    DEFAULT_BITCOIN_CONFIRMATION_TARGET_TESTNET, DEFAULT_ELECTRUM_RPC_URL,
+    DEFAULT_ELECTRUM_RPC_URL_TESTNET, DEFAULT_MONERO_DAEMON_ADDRESS,
+    DEFAULT_MONERO_DAEMON_ADDRESS_STAGENET,
+};
+use swap::cli::SwapFactory;
+use swap::data::DataDir;
+use swap::database::{self, open_db, SwapSummary};
+use swap::env::{self, GetConfig, Mainnet, Testnet};
+use swap::fs::{lock_data_dir, DataDirLock};
+use swap::libp2p_ext::MultiAddrExt;
+use swap::network::watchtower;
+use swap::progress::Progress;
+use swap::protocol::bob::{self, BobState};
+use swap::seed::Seed;
+use swap::tor::DEFAULT_SOCKS5_PORT;
+use swap::{bitcoin, cli, monero};
+use tokio::runtime::Runtime;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::task::JoinHandle;
+use url::Url;
+use uuid::Uuid;
+
+/// The same wallet the CLI uses, so both can work on the same data
+/// directory.
+const MONERO_WALLET_NAME: &str = "swap-tool-blockchain-monitoring-wallet";
+
+/// How often the state of a running swap is checked for changes.
+const STATE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Events that are not polled are dropped, the oldest first.
+const MAX_EVENTS: usize = 1000;
+
+/// How to open the taker, passed as JSON. Only the data directory is
+/// required, everything else defaults to what the CLI uses.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Mainnet and testnet swaps are kept in sub-directories of it, like the
+    /// data directory of the CLI.
+    pub data_dir: PathBuf,
+    #[serde(default)]
+    pub testnet: bool,
+    #[serde(default)]
+    pub electrum_rpc_urls: Vec<Url>,
+    pub bitcoin_target_block: Option<usize>,
+    pub monero_daemon_address: Option<String>,
+    /// Watches for the Monero through this light wallet server instead of a
+    /// monero-wallet-rpc, see [`monero::LightWallet`].
+    pub monero_light_wallet_server: Option<Url>,
+    pub tor_socks5_port: Option<u16>,
+    /// Registers the presigned cancel transaction of the swaps with this
+    /// watchtower, has to contain its peer id.
+    pub watchtower: Option<Multiaddr>,
+}
+
+/// A new swap, passed as JSON.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StartRequest {
+    /// Has to contain the peer id of the seller.
+    pub seller: Multiaddr,
+    pub bitcoin_change_address: bitcoin::Address,
+    pub monero_receive_address: monero::Address,
+    /// The Bitcoin to swap, in satoshi.
+    pub btc_amount: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Status {
+    pub swap_id: Uuid,
+    /// The phase as printed by the `history` command of the CLI.
+    pub state: String,
+    pub finished: bool,
+    /// Whether the swap is running in this taker.
+    pub running: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    State {
+        swap_id: Uuid,
+        state: String,
+    },
+    /// A wallet saw a new confirmation of a transaction it waits for.
+    Confirmations {
+        chain: String,
+        txid: String,
+        seen: u64,
+        needed: u64,
+    },
+    Finished {
+        swap_id: Uuid,
+        state: String,
+    },
+    Failed {
+        swap_id: Uuid,
+        error: String,
+    },
+    /// The swap was stopped before it finished.
+    Stopped {
+        swap_id: Uuid,
+    },
+}
+
+#[derive(Debug, Clone, Default)]
+struct Events(Arc<(Mutex<VecDeque<Event>>, Condvar)>);
+
+impl Events {
+    fn push(&self, event: Event) {
+        let (events, pushed) = &*self.0;
+        let mut events = events.lock().expect("events lock not poisoned");
+
+        if events.len() >= MAX_EVENTS {
+            events.pop_front();
+        }
+        events.push_back(event);
+        pushed.notify_all();
+    }
+
+    fn pop(&self) -> Option<Event> {
+        let (events, _) = &*self.0;
+        events.lock().expect("events lock not poisoned").pop_front()
+    }
+
+    fn wait(&self, timeout: Duration) -> Option<Event> {
+        let (events, pushed) = &*self.0;
+        let events = events.lock().expect("events lock not poisoned");
+        let (mut events, _) = pushed
+            .wait_timeout_while(events, timeout, |events| events.is_empty())
+            .expect("events lock not poisoned");
+
+        events.pop_front()
+    }
+}
+
+/// Aborts the tasks next to a swap, e.g. its event loop, once the swap
+/// finished or was stopped.
+struct AbortOnDrop(Vec<JoinHandle<()>>);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        for task in &self.0 {
+            task.abort();
+        }
+    }
+}
+
+pub struct Taker {
+    runtime: Runtime,
+    factory: SwapFactory,
+    is_testnet: bool,
+    running: Arc<Mutex<HashSet<Uuid>>>,
+    tasks: Mutex<HashMap<Uuid, JoinHandle<()>>>,
+    events: Events,
+    watchtower: Option<watchtower::Client>,
+    _monero_wallet_rpc: Option<monero::WalletRpcProcess>,
+    _data_dir_lock: DataDirLock,
+}
+
+impl Taker {
+    pub fn new(config: Config) -> Result<Self> {
+        let is_testnet = config.testnet;
+        let env_config = if is_testnet {
+            Testnet::get_config()
+        } else {
+            Mainnet::get_config()
+        };
+        let network = if is_testnet { "testnet" } else { "mainnet" };
+        let data_dir = DataDir::new(config.data_dir.join(network));
+        let data_dir_lock = lock_data_dir(data_dir.root())?;
+
+        // Only the first taker of the process gets to install the logger
+        let _ = cli::tracing::init_file_only(false, data_dir.logs(), "swap-ffi.log");
+
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .context("Failed to start the runtime")?;
+        let (factory, monero_wallet_rpc, watchtower) =
+            runtime.block_on(open_wallets(&config, &data_dir, env_config))?;
+
+        let events = Events::default();
+        runtime.spawn(report_confirmations(
+            factory.bitcoin_wallet().progress(),
+            factory.monero_wallet().progress(),
+            events.clone(),
+        ));
+
+        Ok(Self {
+            runtime,
+            factory,
+            is_testnet,
+            running: Default::default(),
+            tasks: Default::default(),
+            events,
+            watchtower,
+            _monero_wallet_rpc: monero_wallet_rpc,
+            _data_dir_lock: data_dir_lock,
+        })
+    }
+
+    /// Sets up a new swap with the seller and runs it in the background.
+    pub fn start(&self, request: StartRequest) -> Result<Uuid> {
+        let seller = request.seller;
+        let seller_peer_id = seller
+            .extract_peer_id()
+            .context("The seller address must contain the peer id of the seller")?;
+        let bitcoin_change_address =
+            validate_bitcoin_address(request.bitcoin_change_address, self.is_testnet)?;
+        let monero_receive_address =
+            validate_monero_address(request.monero_receive_address, self.is_testnet)?;
+        let btc_amount = bitcoin::Amount::from_sat(request.btc_amount);
+
+        let swap_id = Uuid::new_v4();
+        let db = self.factory.db();
+
+        let (swap, event_loop) = self.runtime.block_on(async {
+            database::prune_dead_addresses(db.as_ref()).await;
+            db.insert_address(seller_peer_id, seller).await?;
+            let seller_addresses = db.get_addresses(seller_peer_id).await?;
+
+            let (event_loop, mut event_loop_handle) = self
+                .factory
+                .event_loop(swap_id, seller_peer_id, seller_addresses)
+                .await?;
+            let event_loop = tokio::spawn(event_loop.run());
+
+            let swap = async {
+                let quote = event_loop_handle.request_quote().await?;
+                if btc_amount < quote.min_quantity || btc_amount > quote.max_quantity {
+                    bail!(
+                        "The seller only buys between {} and {}",
+                        quote.min_quantity,
+                        quote.max_quantity
+                    )
+                }
+
+                self.factory
+                    .new_swap(
+                        swap_id,
+                        seller_peer_id,
+                        event_loop_handle,
+                        monero_receive_address,
+                        bitcoin_change_address,
+                        btc_amount,
+                        None,
+                    )
+                    .await
+            };
+
+            match swap.await {
+                Ok(swap) => Ok((swap, event_loop)),
+                Err(error) => {
+                    event_loop.abort();
+                    Err(error)
+                }
+            }
+        })?;
+
+        self.running
+            .lock()
+            .expect("running lock not poisoned")
+            .insert(swap_id);
+        self.spawn(swap_id, self.with_watchtower(swap), event_loop);
+
+        Ok(swap_id)
+    }
+
+    /// Resumes a swap stored in the database in the background.
+    pub fn resume(&self, swap_id: Uuid) -> Result<()> {
+        if !self
+            .running
+            .lock()
+            .expect("running lock not poisoned")
+            .insert(swap_id)
+        {
+            bail!("Swap {} is running already", swap_id)
+        }
+
+        let db = self.factory.db();
+        let resumed = self.runtime.block_on(async {
+            cli::export::ensure_not_exported(swap_id, db.as_ref()).await?;
+
+            let seller_peer_id = db.get_peer_id(swap_id).await?;
+            database::prune_dead_addresses(db.as_ref()).await;
+            let seller_addresses = db.get_addresses(seller_peer_id).await?;
+            if seller_addresses.is_empty() {
+                bail!("No address of the seller {} is known", seller_peer_id)
+            }
+
+            let (event_loop, event_loop_handle) = self
+                .factory
+                .event_loop(swap_id, seller_peer_id, seller_addresses)
+                .await?;
+            let event_loop = tokio::spawn(event_loop.run());
+
+            let swap = async {
+                let swap = self.factory.resume_swap(swap_id, event_loop_handle).await?;
+
+                // Swaps that locked the Bitcoin already are registered right away
+                if let Some(watchtower) = &self.watchtower {
+                    let presigned = db.get_presigned_transactions(swap_id).await?;
+                    if !presigned.is_empty() {
+                        watchtower.spawn_register(swap_id, watchtower::of_bob(presigned));
+                    }
+                }
+
+                Ok(self.with_watchtower(swap))
+            };
+
+            match swap.await {
+                Ok(swap) => Ok((swap, event_loop)),
+                Err(error) => {
+                    event_loop.abort();
+                    Err(error)
+                }
+            }
+        });
+
+        match resumed {
+            Ok((swap, event_loop)) => {
+                self.spawn(swap_id, swap, event_loop);
+                Ok(())
+            }
+            Err(error) => {
+                self.running
+                    .lock()
+                    .expect("running lock not poisoned")
+                    .remove(&swap_id);
+                Err(error)
+            }
+        }
+    }
+
+    /// Publishes the cancel transaction of a swap that is not running, once
+    /// the cancel timelock expired.
+    pub fn cancel(&self, swap_id: Uuid) -> Result<bitcoin::Txid> {
+        if self.is_running(swap_id) {
+            bail!(
+                "Swap {} is running, it cancels by itself once the cancel timelock expired",
+                swap_id
+            )
+        }
+
+        let (txid, _) = self.runtime.block_on(cli::cancel(
+            swap_id,
+            self.factory.bitcoin_wallet(),
+            self.factory.db(),
+            false,
+        ))?;
+
+        Ok(txid)
+    }
+
+    /// Publishes the refund transaction of a swap that is not running, once
+    /// the cancel timelock expired, and returns its id.
+    pub fn refund(&self, swap_id: Uuid) -> Result<bitcoin::Txid> {
+        if self.is_running(swap_id) {
+            bail!(
+                "Swap {} is running, it refunds by itself once the cancel timelock expired",
+                swap_id
+            )
+        }
+
+        match self.runtime.block_on(cli::refund(
+            swap_id,
+            self.factory.bitcoin_wallet(),
+            self.factory.db(),
+            false,
+        ))? {
+            BobState::BtcRefunded(state6) => Ok(state6.tx_refund_id()),
+            state => bail!(
+                "Swap {} ended up in state {} after the refund",
+                swap_id,
+                state
+            ),
+        }
+    }
+
+    /// Stops a running swap in its last saved state.
+    pub fn stop(&self, swap_id: Uuid) -> Result<()> {
+        if !self
+            .running
+            .lock()
+            .expect("running lock not poisoned")
+            .remove(&swap_id)
+        {
+            bail!("Swap {} is not running", swap_id)
+        }
+
+        if let Some(task) = self
+            .tasks
+            .lock()
+            .expect("tasks lock not poisoned")
+            .remove(&swap_id)
+        {
+            task.abort();
+        }
+        self.events.push(Event::Stopped { swap_id });
+
+        Ok(())
+    }
+
+    /// Stops all running swaps and returns their ids, e.g. before the
+    /// application is suspended.
+    pub fn stop_all(&self) -> Vec<Uuid> {
+        self.running_swaps()
+            .into_iter()
+            .filter(|swap_id| self.stop(*swap_id).is_ok())
+            .collect()
+    }
+
+    pub fn running_swaps(&self) -> Vec<Uuid> {
+        self.running
+            .lock()
+            .expect("running lock not poisoned")
+            .iter()
+            .copied()
+            .collect()
+    }
+
+    pub fn status(&self, swap_id: Uuid) -> Result<Status> {
+        let state = self
+            .runtime
+            .block_on(self.factory.db().get_state(swap_id))?;
+        let (_, phase, finished, _) = SwapSummary::describe(&state);
+
+        Ok(Status {
+            swap_id,
+            state: phase,
+            finished,
+            running: self.is_running(swap_id),
+        })
+    }
+
+    /// Everything known about the swap, including which timelock expired if
+    /// it is not finished.
+    pub fn info(&self, swap_id: Uuid) -> Result<database::SwapInfo> {
+        let db = self.factory.db();
+        let bitcoin_wallet = self.factory.bitcoin_wallet();
+
+        self.runtime.block_on(database::swap_info(
+            db.as_ref(),
+            swap_id,
+            Some(bitcoin_wallet.as_ref()),
+        ))
+    }
+
+    /// The oldest event that was not polled yet.
+    pub fn poll_event(&self) -> Option<Event> {
+        self.events.pop()
+    }
+
+    /// Like [`Taker::poll_event`] but waits up to `timeout` for an event if
+    /// there is none.
+    pub fn next_event(&self, timeout: Duration) -> Option<Event> {
+        self.events.wait(timeout)
+    }
+
+    fn is_running(&self, swap_id: Uuid) -> bool {
+        self.running
+            .lock()
+            .expect("running lock not poisoned")
+            .contains(&swap_id)
+    }
+
+    fn with_watchtower(&self, swap: bob::Swap) -> bob::Swap {
+        match &self.watchtower {
+            Some(watchtower) => swap.with_watchtower(watchtower.clone()),
+            None => swap,
+        }
+    }
+
+    fn spawn(&self, swap_id: Uuid, swap: bob::Swap, event_loop: JoinHandle<()>) {
+        let db = self.factory.db();
+        let events = self.events.clone();
+        let running = self.running.clone();
+
+        let task = self.runtime.spawn(async move {
+            let tasks = AbortOnDrop(vec![
+                tokio::spawn(report_states(db, swap_id, events.clone())),
+                event_loop,
+            ]);
+            let result = bob::run(swap).await;
+            drop(tasks);
+
+            running
+                .lock()
+                .expect("running lock not poisoned")
+                .remove(&swap_id);
+            events.push(match result {
+                Ok(state) => Event::Finished {
+                    swap_id,
+                    state: state.to_string(),
+                },
+                Err(error) => Event::Failed {
+                    swap_id,
+                    error: format!("{:#}", error),
+                },
+            });
+        });
+        self.tasks
+            .lock()
+            .expect("tasks lock not poisoned")
+            .insert(swap_id, task);
+    }
+}
+
+async fn open_wallets(
+    config: &Config,
+    data_dir: &DataDir,
+    env_config: env::Config,
+) -> Result<(
+    SwapFactory,
+    Option<monero::WalletRpcProcess>,
+    Option<watchtower::Client>,
+)> {
+    let (default_electrum_rpc_url, default_target_block, default_monero_daemon_address) =
+        if config.testnet {
+            (
+                DEFAULT_ELECTRUM_RPC_URL_TESTNET,
+                DEFAULT_BITCOIN_CONFIRMATION_TARGET_TESTNET,
+                DEFAULT_MONERO_DAEMON_ADDRESS_STAGENET,
+            )
+        } else {
+            (
+                DEFAULT_ELECTRUM_RPC_URL,
+                DEFAULT_BITCOIN_CONFIRMATION_TARGET,
+                DEFAULT_MONERO_DAEMON_ADDRESS,
+            )
+        };
+    let electrum_rpc_urls = if config.electrum_rpc_urls.is_empty() {
+        vec![Url::parse(default_electrum_rpc_url)?]
+    } else {
+        config.electrum_rpc_urls.clone()
+    };
+    let monero_daemon_address = config
+        .monero_daemon_address
+        .clone()
+        .unwrap_or_else(|| default_monero_daemon_address.to_owned());
+
+    let db = open_db(data_dir.database()).await?;
+    let seed =
+        Seed::from_file_or_generate(data_dir.root()).context("Failed to read in seed file")?;
+
+    let bitcoin_wallet = bitcoin::Wallet::new(
+        electrum_rpc_urls,
+        &data_dir.bitcoin_wallet(),
+        seed.derive_extended_private_key(env_config.bitcoin_network)?,
+        env_config,
+        config.bitcoin_target_block.unwrap_or(default_target_block),
+    )
+    .await
+    .context("Failed to initialize Bitcoin wallet")?;
+    bitcoin_wallet.sync().await?;
+
+    let monerod = monerod::Client::from_daemon_address(monero_daemon_address.as_str())?;
+    let (monero_wallet, monero_wallet_rpc_process): (Arc<dyn monero::MoneroWallet>, _) =
+        match config.monero_light_wallet_server.clone() {
+            // Until the Monero can be swept without monero-wallet-rpc
+            Some(url) => bail!(
+                "The light wallet server at {} cannot sweep the Monero of a swap yet, configure no `monero_light_wallet_server`",
+                url
+            ),
+            None => {
+                let process = monero::WalletRpc::new(data_dir.monero_wallets())
+                    .await?
+                    .run(env_config.monero_network, monero_daemon_address.as_str())
+                    .await?;
+                let wallet = monero::Wallet::open_or_create(
+                    process.endpoint(),
+                    MONERO_WALLET_NAME.to_string(),
+                    env_config,
+                )
+                .await?
+                .with_monerod(monerod);
+
+                (Arc::new(wallet), Some(process))
+            }
+        };
+
+    let tor_socks5_port = config.tor_socks5_port.unwrap_or(DEFAULT_SOCKS5_PORT);
+    let watchtower = config
+        .watchtower
+        .clone()
+        .map(|address| {
+            watchtower::Client::new(address, seed.derive_libp2p_identity())
+                .map(|watchtower| watchtower.with_tor(tor_socks5_port))
+        })
+        .transpose()?;
+
+    let factory = SwapFactory::new(
+        seed.derive_libp2p_identity(),
+        db,
+        Arc::new(bitcoin_wallet),
+        monero_wallet,
+        env_config,
+        tor_socks5_port,
+    );
+
+    Ok((factory, monero_wallet_rpc_process, watchtower))
+}
+
+/// Reports the state of a swap whenever it changed.
+async fn report_states(
+    db: Arc<dyn swap::protocol::Database + Send + Sync>,
+    swap_id: Uuid,
+    events: Events,
+) {
+    let mut reported = None;
+
+    loop {
+        let state = db
+            .get_state(swap_id)
+            .await
+            .ok()
+            .and_then(|state| TryInto::<BobState>::try_into(state).ok())
+            .map(|state| state.to_string());
+
+        if state.is_some() && state != reported {
+            reported = state.clone();
+            events.push(Event::State {
+                swap_id,
+                state: state.unwrap_or_default(),
+            });
+        }
+
+        tokio::time::sleep(STATE_INTERVAL).await;
+    }
+}
+
+async fn report_confirmations(bitcoin: Progress, monero: Progress, events: Events) {
+    let mut bitcoin = bitcoin.subscribe();
+    let mut monero = monero.subscribe();
+
+    loop {
+        let received = tokio::select! {
+            received = bitcoin.recv() => received,
+            received = monero.recv() => received,
+        };
+
+        match received {
+            Ok(confirmations) => events.push(Event::Confirmations {
+                chain: confirmations.chain.to_string(),
+                txid: confirmations.txid,
+                seen: confirmations.seen,
+                needed: confirmations.needed,
+            }),
+            Err(RecvError::Lagged(_)) => continue,
+            Err(RecvError::Closed) => return,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_only_requires_the_data_dir() {
+        let config = serde_json::from_str::<Config>(r#"{"data_dir": "/data/swap"}"#).unwrap();
+
+        assert_eq!(config, Config {
+            data_dir: PathBuf::from("/data/swap"),
+            testnet: false,
+            electrum_rpc_urls: vec![],
+            bitcoin_target_block: None,
+            monero_daemon_address: None,
+            monero_light_wallet_server: None,
+            tor_socks5_port: None,
+            watchtower: None,
+        });
+        assert!(serde_json::from_str::<Config>(r#"{"data_dir": "/data", "test": true}"#).is_err());
+    }
+
+    #[test]
+    fn events_are_polled_oldest_first_and_dropped_when_too_many() {
+        let events = Events::default();
+        let swap_id = Uuid::new_v4();
+
+        for seen in 0..=MAX_EVENTS as u64 {
+            events.push(Event::Confirmations {
+                chain: "Bitcoin".to_owned(),
+                txid: "abcd".to_owned(),
+                seen,
+                needed: 1,
+            });
+        }
+        events.push(Event::Failed {
+            swap_id,
+            error: "seller unreachable".to_owned(),
+        });
+
+        assert_eq!(
+            serde_json::to_value(events.pop().unwrap()).unwrap(),
+            serde_json::json!({
+                "type": "confirmations",
+                "chain": "Bitcoin",
+                "txid": "abcd",
+                "seen": 2,
+                "needed": 1,
+            })
+        );
+        assert_eq!(
+            std::iter::from_fn(|| events.pop()).last(),
+            Some(Event::Failed {
+                swap_id,
+                error: "seller unreachable".to_owned(),
+            })
+        );
+    }
+
+    #[test]
+    fn waiting_for_an_event_returns_once_one_is_pushed() {
+        let events = Events::default();
+        let swap_id = Uuid::new_v4();
+
+        assert_eq!(events.wait(Duration::from_millis(10)), None);
+
+        let pusher = {
+            let events = events.clone();
+            std::thread::spawn(move || {
+                std::thread::sleep(Duration::from_millis(50));
+                events.push(Event::Stopped { swap_id });
+            })
+        };
+
+        assert_eq!(
+            events.wait(Duration::from_secs(10)),
+            Some(Event::Stopped { swap_id })
+        );
+        pusher.join().unwrap();
+    }
+}
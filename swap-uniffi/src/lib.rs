@@ -0,0 +1,422 @@
+#![warn(
+    unused_extern_crates,
+    rust_2018_idioms,
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    clippy::fallible_impl_from,
+    clippy::cast_precision_loss,
+    clippy::cast_possible_wrap,
+    clippy::dbg_macro
+)]
+#![cfg_attr(not(test), warn(clippy::unwrap_used))]
+#![allow(missing_debug_implementations)]
+
+//! Kotlin and Swift bindings to embed the taker in Android and iOS wallets,
+//! on top of the same [`swap_ffi::taker::Taker`] as the C functions.
+//!
+//! The interface is declared in `src/swap.udl`, the bindings are generated
+//! from it with `uniffi-bindgen generate src/swap.udl --language kotlin` or
+//! `--language swift` and load the library built from this crate.
+//!
+//! Every method blocks until its work is done and must not be called on the
+//! main thread of the application. Events are delivered to the
+//! [`EventListener`] on a thread of the library, the listener should hand
+//! them over to the application instead of doing any work itself.
+//!
+//! Mobile operating systems suspend applications in the background, which
+//! stops their swaps as well. A stopped swap is safe as long as it is resumed
+//! before its timelocks expire: applications should stop the swaps with
+//! [`Taker::stop_all`] when they are sent to the background, resume them when
+//! they come back and configure a watchtower that cancels the swap in the
+//! meantime, the refund needs the application to run again before the punish
+//! timelock expires.
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+use std::thread;
+use std::time::Duration;
+use swap_ffi::taker::{self, Config, StartRequest};
+use uuid::Uuid;
+
+/// The FFI functions generated from `src/swap.udl`, the generated code casts
+/// and unwraps where the crate's lints do not allow it.
+#[allow(clippy::cast_sign_loss, clippy::unwrap_used)]
+mod scaffolding {
+    use super::*;
+
+    uniffi_macros::include_scaffolding!("swap");
+}
+
+/// How long the thread delivering the events waits for one before it checks
+/// whether the taker was dropped.
+const EVENT_TIMEOUT: Duration = Duration::from_millis(500);
+
+#[derive(Debug, thiserror::Error)]
+pub enum TakerError {
+    #[error("{0}")]
+    InvalidArgument(String),
+    #[error("{0}")]
+    Failed(String),
+}
+
+impl From<anyhow::Error> for TakerError {
+    fn from(error: anyhow::Error) -> Self {
+        Self::Failed(format!("{:#}", error))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SwapStatus {
+    pub swap_id: String,
+    /// The phase as printed by the `history` command of the CLI.
+    pub state: String,
+    pub finished: bool,
+    /// Whether the swap is running in this taker.
+    pub running: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SwapEvent {
+    State {
+        swap_id: String,
+        state: String,
+    },
+    /// A wallet saw a new confirmation of a transaction it waits for.
+    Confirmations {
+        chain: String,
+        txid: String,
+        seen: u64,
+        needed: u64,
+    },
+    Finished {
+        swap_id: String,
+        state: String,
+    },
+    Failed {
+        swap_id: String,
+        error: String,
+    },
+    /// The swap was stopped before it finished.
+    Stopped {
+        swap_id: String,
+    },
+}
+
+impl From<taker::Event> for SwapEvent {
+    fn from(event: taker::Event) -> Self {
+        match event {
+            taker::Event::State { swap_id, state } => Self::State {
+                swap_id: swap_id.to_string(),
+                state,
+            },
+            taker::Event::Confirmations {
+                chain,
+                txid,
+                seen,
+                needed,
+            } => Self::Confirmations {
+                chain,
+                txid,
+                seen,
+                needed,
+            },
+            taker::Event::Finished { swap_id, state } => Self::Finished {
+                swap_id: swap_id.to_string(),
+                state,
+            },
+            taker::Event::Failed { swap_id, error } => Self::Failed {
+                swap_id: swap_id.to_string(),
+                error,
+            },
+            taker::Event::Stopped { swap_id } => Self::Stopped {
+                swap_id: swap_id.to_string(),
+            },
+        }
+    }
+}
+
+/// Implemented by the application to receive the events of the running
+/// swaps.
+pub trait EventListener: Send + Sync {
+    fn on_event(&self, event: SwapEvent);
+}
+
+/// Collects the options of a [`Taker`], everything but the data directory
+/// defaults to what the CLI uses. The options are checked once the taker is
+/// built.
+pub struct TakerBuilder {
+    data_dir: PathBuf,
+    options: Mutex<Options>,
+}
+
+#[derive(Debug, Default)]
+struct Options {
+    testnet: bool,
+    electrum_rpc_urls: Vec<String>,
+    bitcoin_target_block: Option<u32>,
+    monero_daemon_address: Option<String>,
+    monero_light_wallet_server: Option<String>,
+    tor_socks5_port: Option<u16>,
+    watchtower: Option<String>,
+}
+
+impl TakerBuilder {
+    pub fn new(data_dir: String) -> Self {
+        Self {
+            data_dir: PathBuf::from(data_dir),
+            options: Default::default(),
+        }
+    }
+
+    pub fn testnet(&self, testnet: bool) {
+        self.options().testnet = testnet;
+    }
+
+    /// Adds an Electrum server, the first one that can be reached is used.
+    pub fn electrum_rpc_url(&self, url: String) {
+        self.options().electrum_rpc_urls.push(url);
+    }
+
+    pub fn bitcoin_target_block(&self, blocks: u32) {
+        self.options().bitcoin_target_block = Some(blocks);
+    }
+
+    pub fn monero_daemon_address(&self, address: String) {
+        self.options().monero_daemon_address = Some(address);
+    }
+
+    /// Not supported yet: a light wallet server cannot sweep the Monero, the
+    /// taker refuses to run swaps with one.
+    pub fn monero_light_wallet_server(&self, url: String) {
+        self.options().monero_light_wallet_server = Some(url);
+    }
+
+    pub fn tor_socks5_port(&self, port: u16) {
+        self.options().tor_socks5_port = Some(port);
+    }
+
+    /// The watchtower the presigned cancel transaction is registered with, has
+    /// to contain its peer id.
+    pub fn watchtower(&self, address: String) {
+        self.options().watchtower = Some(address);
+    }
+
+    fn options(&self) -> MutexGuard<'_, Options> {
+        self.options.lock().expect("options lock not poisoned")
+    }
+
+    fn config(&self) -> Result<Config, TakerError> {
+        let options = self.options();
+
+        Ok(Config {
+            data_dir: self.data_dir.clone(),
+            testnet: options.testnet,
+            electrum_rpc_urls: options
+                .electrum_rpc_urls
+                .iter()
+                .map(|url| url.parse().map_err(|e| invalid("Electrum RPC URL", e)))
+                .collect::<Result<_, _>>()?,
+            bitcoin_target_block: options
+                .bitcoin_target_block
+                .map(usize::try_from)
+                .transpose()
+                .map_err(|e| invalid("Bitcoin target block", e))?,
+            monero_daemon_address: options.monero_daemon_address.clone(),
+            monero_light_wallet_server: options
+                .monero_light_wallet_server
+                .as_deref()
+                .map(str::parse)
+                .transpose()
+                .map_err(|e| invalid("light wallet server URL", e))?,
+            tor_socks5_port: options.tor_socks5_port,
+            watchtower: options
+                .watchtower
+                .as_deref()
+                .map(str::parse)
+                .transpose()
+                .map_err(|e| invalid("watchtower address", e))?,
+        })
+    }
+}
+
+type Listener = Arc<Mutex<Option<Arc<dyn EventListener>>>>;
+
+/// A taker with its wallets and running swaps, see
+/// [`swap_ffi::taker::Taker`].
+pub struct Taker {
+    core: Arc<taker::Taker>,
+    listener: Listener,
+}
+
+impl Taker {
+    /// Opens the wallets, which takes a while on the first start. Fails if
+    /// another taker or process uses the data directory.
+    pub fn new(builder: Arc<TakerBuilder>) -> Result<Self, TakerError> {
+        let core = Arc::new(taker::Taker::new(builder.config()?)?);
+        let listener = Listener::default();
+
+        let delivering = Arc::downgrade(&core);
+        let delivered = listener.clone();
+        thread::Builder::new()
+            .name("swap-events".to_owned())
+            .spawn(move || deliver_events(delivering, delivered))
+            .map_err(|e| TakerError::Failed(format!("Failed to deliver events: {}", e)))?;
+
+        Ok(Self { core, listener })
+    }
+
+    /// Sets up a new swap with the seller and runs it in the background,
+    /// returns its id.
+    pub fn start(
+        &self,
+        seller: String,
+        bitcoin_change_address: String,
+        monero_receive_address: String,
+        btc_amount: u64,
+    ) -> Result<String, TakerError> {
+        let request = StartRequest {
+            seller: seller.parse().map_err(|e| invalid("seller address", e))?,
+            bitcoin_change_address: bitcoin_change_address
+                .parse()
+                .map_err(|e| invalid("Bitcoin change address", e))?,
+            monero_receive_address: monero_receive_address
+                .parse()
+                .map_err(|e| invalid("Monero receive address", e))?,
+            btc_amount,
+        };
+
+        Ok(self.core.start(request)?.to_string())
+    }
+
+    /// Resumes a stopped or interrupted swap in the background.
+    pub fn resume(&self, swap_id: String) -> Result<(), TakerError> {
+        Ok(self.core.resume(parse_swap_id(&swap_id)?)?)
+    }
+
+    /// Stops a running swap in its last saved state.
+    pub fn stop(&self, swap_id: String) -> Result<(), TakerError> {
+        Ok(self.core.stop(parse_swap_id(&swap_id)?)?)
+    }
+
+    /// Stops all running swaps and returns their ids to resume them later
+    /// on.
+    pub fn stop_all(&self) -> Vec<String> {
+        self.core.stop_all().iter().map(Uuid::to_string).collect()
+    }
+
+    pub fn running_swaps(&self) -> Vec<String> {
+        self.core
+            .running_swaps()
+            .iter()
+            .map(Uuid::to_string)
+            .collect()
+    }
+
+    /// Publishes the cancel transaction of a swap that is not running, once
+    /// the cancel timelock expired, and returns its id.
+    pub fn cancel(&self, swap_id: String) -> Result<String, TakerError> {
+        Ok(self.core.cancel(parse_swap_id(&swap_id)?)?.to_string())
+    }
+
+    /// Publishes the refund transaction of a cancelled swap that is not
+    /// running and returns its id.
+    pub fn refund(&self, swap_id: String) -> Result<String, TakerError> {
+        Ok(self.core.refund(parse_swap_id(&swap_id)?)?.to_string())
+    }
+
+    pub fn status(&self, swap_id: String) -> Result<SwapStatus, TakerError> {
+        let status = self.core.status(parse_swap_id(&swap_id)?)?;
+
+        Ok(SwapStatus {
+            swap_id: status.swap_id.to_string(),
+            state: status.state,
+            finished: status.finished,
+            running: status.running,
+        })
+    }
+
+    /// Everything known about the swap as a JSON object, see `swap_info` of
+    /// the C bindings.
+    pub fn info(&self, swap_id: String) -> Result<String, TakerError> {
+        let info = self.core.info(parse_swap_id(&swap_id)?)?;
+
+        Ok(serde_json::to_string(&info).map_err(anyhow::Error::from)?)
+    }
+
+    /// Replaces the listener of the events. Events that happened while there
+    /// was no listener are delivered to the first one.
+    pub fn set_event_listener(&self, listener: Box<dyn EventListener>) {
+        *self.listener.lock().expect("listener lock not poisoned") = Some(Arc::from(listener));
+    }
+}
+
+/// Hands the events of the taker to the listener until the taker is dropped.
+fn deliver_events(taker: Weak<taker::Taker>, listener: Listener) {
+    while let Some(taker) = taker.upgrade() {
+        let current = listener.lock().expect("listener lock not poisoned").clone();
+
+        // Without a listener the events are kept in the taker
+        let listener = match current {
+            Some(listener) => listener,
+            None => {
+                drop(taker);
+                thread::sleep(EVENT_TIMEOUT);
+                continue;
+            }
+        };
+
+        let event = taker.next_event(EVENT_TIMEOUT);
+        drop(taker);
+
+        if let Some(event) = event {
+            listener.on_event(event.into());
+        }
+    }
+}
+
+fn parse_swap_id(swap_id: &str) -> Result<Uuid, TakerError> {
+    swap_id.parse().map_err(|e| invalid("swap id", e))
+}
+
+fn invalid(name: &str, error: impl fmt::Display) -> TakerError {
+    TakerError::InvalidArgument(format!("Invalid {}: {}", name, error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builder_checks_the_options_when_the_taker_is_built() {
+        let builder = TakerBuilder::new("/data/swap".to_owned());
+        builder.testnet(true);
+        builder.electrum_rpc_url("ssl://electrum.blockstream.info:60002".to_owned());
+        builder.tor_socks5_port(9150);
+
+        let config = builder.config().unwrap();
+        assert!(config.testnet);
+        assert_eq!(config.electrum_rpc_urls.len(), 1);
+        assert_eq!(config.tor_socks5_port, Some(9150));
+        assert_eq!(config.watchtower, None);
+
+        builder.watchtower("/dns4/watchtower.example.com/tcp/9939".to_owned());
+        assert!(builder.config().is_ok());
+
+        builder.watchtower("not an address".to_owned());
+        assert!(matches!(
+            builder.config(),
+            Err(TakerError::InvalidArgument(message)) if message.starts_with("Invalid watchtower address")
+        ));
+    }
+
+    #[test]
+    fn invalid_swap_ids_are_invalid_arguments() {
+        assert!(matches!(
+            parse_swap_id("not a swap id"),
+            Err(TakerError::InvalidArgument(_))
+        ));
+        assert!(parse_swap_id(&Uuid::new_v4().to_string()).is_ok());
+    }
+}
@@ -0,0 +1,198 @@
+//! Benchmarks of the cryptography of the swap setup: creating the initial
+//! states, which proves the DLEQ relation, receiving and verifying the
+//! messages of the other party and building the transactions spending the
+//! lock output.
+//!
+//! Compare against the numbers of another revision by saving them as a
+//! baseline first:
+//!
+//! ```shell
+//! cargo bench --package swap --features test-utils -- --save-baseline master
+//! cargo bench --package swap --features test-utils -- --baseline master
+//! ```
+
+#![allow(non_snake_case)]
+
+use bdk::database::MemoryDatabase;
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use rand::rngs::OsRng;
+use swap::bitcoin::wallet::StaticFeeRate;
+use swap::bitcoin::{self, TxCancel, TxLock, TxPunish, TxRedeem, TxRefund, WalletBuilder};
+use swap::env::{GetConfig, Regtest};
+use swap::monero;
+use swap::protocol::{alice, bob, CROSS_CURVE_PROOF_SYSTEM};
+use tokio::runtime::Runtime;
+use uuid::Uuid;
+
+type Wallet = bitcoin::Wallet<(), MemoryDatabase, StaticFeeRate>;
+
+fn fee() -> bitcoin::Amount {
+    bitcoin::Amount::from_sat(1_000)
+}
+
+fn btc() -> bitcoin::Amount {
+    bitcoin::Amount::from_sat(500_000)
+}
+
+fn xmr() -> monero::Amount {
+    monero::Amount::from_piconero(10_000)
+}
+
+async fn alice_state0(wallet: &Wallet) -> alice::State0 {
+    alice::State0::new(
+        btc(),
+        xmr(),
+        Regtest::get_config(),
+        wallet.new_address().await.unwrap(),
+        wallet.new_address().await.unwrap(),
+        fee(),
+        fee(),
+        &mut OsRng,
+    )
+}
+
+async fn bob_state0(wallet: &Wallet) -> bob::State0 {
+    let config = Regtest::get_config();
+
+    bob::State0::new(
+        Uuid::new_v4(),
+        &mut OsRng,
+        btc(),
+        xmr(),
+        config.bitcoin_cancel_timelock,
+        config.bitcoin_punish_timelock,
+        wallet.new_address().await.unwrap(),
+        config.monero_finality_confirmations,
+        fee(),
+        fee(),
+    )
+}
+
+fn initial_states(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let wallet = &WalletBuilder::new(bitcoin::Amount::ONE_BTC.as_sat()).build();
+    let alice_state0 = rt.block_on(alice_state0(wallet));
+    let bob_state0 = rt.block_on(bob_state0(wallet));
+
+    c.bench_function("alice_state0_new", |b| {
+        b.to_async(&rt).iter(|| alice_state0(wallet))
+    });
+    c.bench_function("bob_state0_new", |b| {
+        b.to_async(&rt).iter(|| bob_state0(wallet))
+    });
+
+    let s = monero::Scalar::random(&mut OsRng);
+    let (proof, points) = CROSS_CURVE_PROOF_SYSTEM.prove(&s, &mut OsRng);
+    c.bench_function("dleq_prove", |b| {
+        b.iter(|| CROSS_CURVE_PROOF_SYSTEM.prove(&s, &mut OsRng))
+    });
+    c.bench_function("dleq_verify", |b| {
+        b.iter(|| assert!(CROSS_CURVE_PROOF_SYSTEM.verify(&proof, points)))
+    });
+
+    let message1 = alice_state0.next_message();
+    let message0 = bob_state0.next_message();
+    c.bench_function("alice_receive_message0", |b| {
+        b.iter_batched(
+            || alice_state0.clone(),
+            |state0| state0.receive(message0.clone()).unwrap(),
+            BatchSize::SmallInput,
+        )
+    });
+    c.bench_function("bob_receive_message1", |b| {
+        b.to_async(&rt).iter_batched(
+            || (bob_state0.clone(), message1.clone()),
+            |(state0, message1)| async move { state0.receive(wallet, message1).await.unwrap() },
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+fn signatures(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let wallet = &WalletBuilder::new(bitcoin::Amount::ONE_BTC.as_sat()).build();
+    let alice_state0 = rt.block_on(alice_state0(wallet));
+    let bob_state0 = rt.block_on(bob_state0(wallet));
+
+    // Bob's state1 cannot be cloned, it is built again for every iteration
+    let (_, alice_state1) = alice_state0.receive(bob_state0.next_message()).unwrap();
+    let bob_state1 = || {
+        rt.block_on(
+            bob_state0
+                .clone()
+                .receive(wallet, alice_state1.next_message()),
+        )
+        .unwrap()
+    };
+    let message2 = bob_state1().next_message();
+    let alice_state2 = alice_state1.clone().receive(message2.clone()).unwrap();
+    let message3 = alice_state2.next_message();
+    let bob_state2 = bob_state1().receive(message3.clone()).unwrap();
+    let message4 = bob_state2.next_message();
+
+    c.bench_function("alice_receive_message2", |b| {
+        b.iter_batched(
+            || alice_state1.clone(),
+            |state1| state1.receive(message2.clone()).unwrap(),
+            BatchSize::SmallInput,
+        )
+    });
+    c.bench_function("alice_next_message3", |b| {
+        b.iter(|| alice_state2.next_message())
+    });
+    c.bench_function("bob_receive_message3", |b| {
+        b.iter_batched(
+            &bob_state1,
+            |state1| state1.receive(message3.clone()).unwrap(),
+            BatchSize::SmallInput,
+        )
+    });
+    c.bench_function("bob_next_message4", |b| {
+        b.iter(|| bob_state2.next_message())
+    });
+    c.bench_function("alice_receive_message4", |b| {
+        b.iter_batched(
+            || alice_state2.clone(),
+            |state2| state2.receive(message4.clone()).unwrap(),
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+fn transactions(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let wallet = &WalletBuilder::new(bitcoin::Amount::ONE_BTC.as_sat()).build();
+    let config = Regtest::get_config();
+    let A = bitcoin::SecretKey::new_random(&mut OsRng).public();
+    let B = bitcoin::SecretKey::new_random(&mut OsRng).public();
+    let address = rt.block_on(wallet.new_address()).unwrap();
+
+    c.bench_function("tx_lock_new", |b| {
+        b.to_async(&rt).iter_batched(
+            || address.clone(),
+            |change| async move { TxLock::new(wallet, btc(), A, B, change).await.unwrap() },
+            BatchSize::SmallInput,
+        )
+    });
+
+    let tx_lock = rt
+        .block_on(TxLock::new(wallet, btc(), A, B, address.clone()))
+        .unwrap();
+    let tx_cancel = TxCancel::new(&tx_lock, config.bitcoin_cancel_timelock, A, B, fee());
+
+    c.bench_function("tx_cancel_new", |b| {
+        b.iter(|| TxCancel::new(&tx_lock, config.bitcoin_cancel_timelock, A, B, fee()))
+    });
+    c.bench_function("tx_redeem_new", |b| {
+        b.iter(|| TxRedeem::new(&tx_lock, &address, fee()))
+    });
+    c.bench_function("tx_refund_new", |b| {
+        b.iter(|| TxRefund::new(&tx_cancel, &address, fee()))
+    });
+    c.bench_function("tx_punish_new", |b| {
+        b.iter(|| TxPunish::new(&tx_cancel, &address, config.bitcoin_punish_timelock, fee()))
+    });
+}
+
+criterion_group!(benches, initial_states, signatures, transactions);
+criterion_main!(benches);
@@ -1,9 +1,32 @@
 use anyhow::Result;
+use std::env;
 use vergen::{vergen, Config, SemverKind};
 
 fn main() -> Result<()> {
     let mut config = Config::default();
     *config.git_mut().semver_kind_mut() = SemverKind::Lightweight;
 
-    vergen(config)
+    vergen(config)?;
+
+    println!("cargo:rustc-env=SWAP_TARGET={}", env::var("TARGET")?);
+    println!(
+        "cargo:rustc-env=SWAP_FEATURES={}",
+        enabled_features().join(",")
+    );
+
+    Ok(())
+}
+
+/// Cargo sets `CARGO_FEATURE_<NAME>` for every feature the crate is built
+/// with.
+fn enabled_features() -> Vec<String> {
+    let mut features = env::vars()
+        .filter_map(|(key, _)| {
+            key.strip_prefix("CARGO_FEATURE_")
+                .map(|feature| feature.to_lowercase().replace('_', "-"))
+        })
+        .collect::<Vec<_>>();
+    features.sort();
+
+    features
 }
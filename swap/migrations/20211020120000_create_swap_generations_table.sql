@@ -0,0 +1,6 @@
+CREATE TABLE if NOT EXISTS swap_generations
+(
+    swap_id     TEXT    PRIMARY KEY NOT NULL,
+    generation  INTEGER             NOT NULL,
+    exported    BOOLEAN             NOT NULL
+);
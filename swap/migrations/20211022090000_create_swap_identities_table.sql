@@ -0,0 +1,5 @@
+CREATE TABLE if NOT EXISTS swap_identities
+(
+    swap_id     TEXT    PRIMARY KEY NOT NULL,
+    identity    TEXT                NOT NULL
+);
@@ -0,0 +1,7 @@
+CREATE TABLE if NOT EXISTS swap_fiat_values
+(
+    swap_id     TEXT    PRIMARY KEY NOT NULL,
+    currency    TEXT                NOT NULL,
+    btc_price   TEXT                NOT NULL,
+    value       TEXT                NOT NULL
+);
@@ -0,0 +1,11 @@
+CREATE TABLE if NOT EXISTS quotes
+(
+    id          INTEGER PRIMARY KEY autoincrement NOT NULL,
+    identity    TEXT                NOT NULL,
+    peer_id     TEXT                NOT NULL,
+    issued_at   TEXT                NOT NULL,
+    price       INTEGER             NOT NULL,
+    outcome     TEXT                NOT NULL,
+    reason      TEXT,
+    swap_id     TEXT
+);
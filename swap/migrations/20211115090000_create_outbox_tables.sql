@@ -0,0 +1,17 @@
+CREATE TABLE if NOT EXISTS outbox
+(
+    id          INTEGER PRIMARY KEY autoincrement NOT NULL,
+    swap_id     TEXT                NOT NULL,
+    peer_id     TEXT                NOT NULL,
+    kind        TEXT                NOT NULL,
+    message     TEXT                NOT NULL,
+    UNIQUE (swap_id, kind)
+);
+
+CREATE TABLE if NOT EXISTS received_messages
+(
+    swap_id     TEXT                NOT NULL,
+    kind        TEXT                NOT NULL,
+    nonce       TEXT                NOT NULL,
+    PRIMARY KEY (swap_id, kind, nonce)
+);
@@ -0,0 +1,7 @@
+CREATE TABLE if NOT EXISTS signed_quotes
+(
+    id          INTEGER PRIMARY KEY autoincrement NOT NULL,
+    swap_id     TEXT                NOT NULL,
+    peer_id     TEXT                NOT NULL,
+    quote       TEXT                NOT NULL
+);
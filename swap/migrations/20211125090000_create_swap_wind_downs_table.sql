@@ -0,0 +1,5 @@
+CREATE TABLE if NOT EXISTS swap_wind_downs
+(
+    swap_id     TEXT    PRIMARY KEY NOT NULL,
+    reason      TEXT                NOT NULL
+);
@@ -0,0 +1,15 @@
+CREATE TABLE if NOT EXISTS swap_summaries
+(
+    swap_id     TEXT    PRIMARY KEY NOT NULL,
+    role        TEXT                NOT NULL,
+    started_at  INTEGER             NOT NULL,
+    state       TEXT                NOT NULL,
+    finished    BOOLEAN             NOT NULL,
+    btc_amount  INTEGER
+);
+
+CREATE INDEX if NOT EXISTS swap_summaries_started_at ON swap_summaries (started_at, swap_id);
+CREATE INDEX if NOT EXISTS swap_summaries_finished ON swap_summaries (finished, started_at, swap_id);
+CREATE INDEX if NOT EXISTS swap_summaries_state ON swap_summaries (state);
+CREATE INDEX if NOT EXISTS swap_summaries_btc_amount ON swap_summaries (btc_amount);
+CREATE INDEX if NOT EXISTS peers_peer_id ON peers (peer_id);
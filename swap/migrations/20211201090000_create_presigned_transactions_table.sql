@@ -0,0 +1,7 @@
+CREATE TABLE if NOT EXISTS presigned_transactions
+(
+    swap_id     TEXT    NOT NULL,
+    kind        TEXT    NOT NULL,
+    presigned   TEXT    NOT NULL,
+    PRIMARY KEY (swap_id, kind)
+);
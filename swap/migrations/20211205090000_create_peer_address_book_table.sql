@@ -0,0 +1,24 @@
+CREATE TABLE if NOT EXISTS peer_address_book
+(
+    peer_id                 TEXT    NOT NULL,
+    address                 TEXT    NOT NULL,
+    added_at                INTEGER NOT NULL,
+    last_success            INTEGER,
+    successes               INTEGER NOT NULL DEFAULT 0,
+    failures                INTEGER NOT NULL DEFAULT 0,
+    consecutive_failures    INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (peer_id, address)
+);
+
+-- The addresses are stored without their `/p2p/` part, the peer id is stored next to them
+INSERT OR IGNORE INTO peer_address_book (peer_id, address, added_at)
+SELECT peer_id,
+       CASE
+           WHEN instr(address, '/p2p/') > 0 THEN substr(address, 1, instr(address, '/p2p/') - 1)
+           ELSE address
+       END,
+       CAST(strftime('%s', 'now') AS INTEGER)
+FROM peer_addresses
+WHERE instr(address, '/p2p/') != 1;
+
+DROP TABLE peer_addresses;
@@ -0,0 +1,5 @@
+CREATE TABLE if NOT EXISTS receipts
+(
+    swap_id     TEXT    PRIMARY KEY NOT NULL,
+    receipt     TEXT                NOT NULL
+);
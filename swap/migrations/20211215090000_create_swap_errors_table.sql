@@ -0,0 +1,7 @@
+CREATE TABLE if NOT EXISTS swap_errors
+(
+    id          INTEGER PRIMARY KEY autoincrement NOT NULL,
+    swap_id     TEXT                NOT NULL,
+    failed_at   TEXT                NOT NULL,
+    error       TEXT                NOT NULL
+);
@@ -0,0 +1,701 @@
+//! Amounts of Bitcoin and Monero, the price of one XMR in BTC and checked
+//! arithmetic on amounts.
+//!
+//! Amounts are an [`Amount`] of [`Btc`] or [`Xmr`], known as
+//! [`bitcoin::Amount`] and [`monero::Amount`]. They are serialized as
+//! satoshis and piconeros, in protocol messages, the database and the
+//! transcript of a swap alike, and displayed with their unit. Only the config
+//! file and the price feeds write Bitcoin amounts in BTC, see
+//! [`bitcoin::as_btc`]. Amounts are converted from and into the amounts of
+//! the `bitcoin` crate where transactions are built.
+//!
+//! A [`Quote`] and the price limits use a [`Price`]. The [`Rate`] of
+//! the ASB applies its spread at a finer precision than a satoshi and only
+//! becomes a price when it is quoted.
+//!
+//! Arithmetic on amounts fails with an error instead of wrapping around or
+//! panicking. Amounts received from the other party of a swap, e.g. the fees
+//! of the transactions it signs, are checked with these before any
+//! transaction is built from them.
+
+use crate::{bitcoin, monero};
+use anyhow::{bail, Context, Result};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt::{self, Display};
+use std::iter::Sum;
+use std::marker::PhantomData;
+use std::ops::{Add, AddAssign, Div, Mul, Sub, SubAssign};
+
+/// A currency amounts are kept in.
+pub trait Currency {
+    /// The unit an amount is displayed in, e.g. `BTC`.
+    const UNIT: &'static str;
+    /// The number of decimal places of the smallest unit, e.g. 8 for a
+    /// satoshi.
+    const DECIMALS: u32;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Btc {}
+
+impl Currency for Btc {
+    const UNIT: &'static str = "BTC";
+    const DECIMALS: u32 = 8;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Xmr {}
+
+impl Currency for Xmr {
+    const UNIT: &'static str = "XMR";
+    const DECIMALS: u32 = 12;
+}
+
+/// An amount of `C` in its smallest unit, satoshis for Bitcoin and piconeros
+/// for Monero.
+#[derive(PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Amount<C>(u64, PhantomData<C>);
+
+// Written out, deriving them requires `C` to be `Clone` and `Copy` as well
+impl<C> Clone for Amount<C> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<C> Copy for Amount<C> {}
+
+impl<C> Amount<C> {
+    pub const ZERO: Self = Self::from_atomic(0);
+    pub const MAX: Self = Self::from_atomic(u64::MAX);
+
+    /// The amount of `units` satoshis or piconeros.
+    pub const fn from_atomic(units: u64) -> Self {
+        Self(units, PhantomData)
+    }
+
+    pub const fn as_atomic(self) -> u64 {
+        self.0
+    }
+
+    pub fn checked_add(self, rhs: Self) -> Option<Self> {
+        self.0.checked_add(rhs.0).map(Self::from_atomic)
+    }
+
+    pub fn checked_sub(self, rhs: Self) -> Option<Self> {
+        self.0.checked_sub(rhs.0).map(Self::from_atomic)
+    }
+
+    pub fn checked_mul(self, rhs: u64) -> Option<Self> {
+        self.0.checked_mul(rhs).map(Self::from_atomic)
+    }
+
+    pub fn checked_div(self, rhs: u64) -> Option<Self> {
+        self.0.checked_div(rhs).map(Self::from_atomic)
+    }
+}
+
+impl<C> Default for Amount<C> {
+    fn default() -> Self {
+        Self::ZERO
+    }
+}
+
+impl<C: Currency> Display for Amount<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut decimal = Decimal::from(self.0);
+        decimal
+            .set_scale(C::DECIMALS)
+            .expect("decimals to be smaller than the max precision of 28");
+        write!(f, "{} {}", decimal, C::UNIT)
+    }
+}
+
+impl<C: Currency> fmt::Debug for Amount<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Amount({})", self)
+    }
+}
+
+impl<C> Serialize for Amount<C> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_u64(self.0)
+    }
+}
+
+impl<'de, C> Deserialize<'de> for Amount<C> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        u64::deserialize(deserializer).map(Self::from_atomic)
+    }
+}
+
+// The operators panic on overflow like the ones on integers, amounts that
+// come from the other party are checked with `CheckedArithmetic` instead
+impl<C> Add for Amount<C> {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        self.checked_add(rhs)
+            .expect("amount addition to not overflow")
+    }
+}
+
+impl<C> Sub for Amount<C> {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        self.checked_sub(rhs)
+            .expect("amount subtraction to not underflow")
+    }
+}
+
+impl<C> Mul<u64> for Amount<C> {
+    type Output = Self;
+
+    fn mul(self, rhs: u64) -> Self {
+        self.checked_mul(rhs)
+            .expect("amount multiplication to not overflow")
+    }
+}
+
+impl<C> Div<u64> for Amount<C> {
+    type Output = Self;
+
+    fn div(self, rhs: u64) -> Self {
+        self.checked_div(rhs)
+            .expect("amount to not be divided by zero")
+    }
+}
+
+impl<C> AddAssign for Amount<C> {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+impl<C> SubAssign for Amount<C> {
+    fn sub_assign(&mut self, rhs: Self) {
+        *self = *self - rhs;
+    }
+}
+
+impl<C> Sum for Amount<C> {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::ZERO, Add::add)
+    }
+}
+
+impl<C> From<Amount<C>> for u64 {
+    fn from(amount: Amount<C>) -> u64 {
+        amount.0
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, thiserror::Error)]
+#[non_exhaustive]
+pub enum Error {
+    #[error("{lhs} + {rhs} overflows")]
+    AdditionOverflow { lhs: String, rhs: String },
+    #[error("{lhs} - {rhs} is negative")]
+    SubtractionUnderflow { lhs: String, rhs: String },
+    #[error("{lhs} * {rhs} overflows")]
+    MultiplicationOverflow { lhs: String, rhs: u64 },
+}
+
+pub trait CheckedArithmetic: Copy + Display + Sized {
+    fn checked_add(self, rhs: Self) -> Option<Self>;
+    fn checked_sub(self, rhs: Self) -> Option<Self>;
+    fn checked_mul(self, rhs: u64) -> Option<Self>;
+
+    fn try_add(self, rhs: Self) -> Result<Self, Error> {
+        self.checked_add(rhs)
+            .ok_or_else(|| Error::AdditionOverflow {
+                lhs: self.to_string(),
+                rhs: rhs.to_string(),
+            })
+    }
+
+    fn try_sub(self, rhs: Self) -> Result<Self, Error> {
+        self.checked_sub(rhs)
+            .ok_or_else(|| Error::SubtractionUnderflow {
+                lhs: self.to_string(),
+                rhs: rhs.to_string(),
+            })
+    }
+
+    fn try_mul(self, rhs: u64) -> Result<Self, Error> {
+        self.checked_mul(rhs)
+            .ok_or_else(|| Error::MultiplicationOverflow {
+                lhs: self.to_string(),
+                rhs,
+            })
+    }
+}
+
+impl<C: Currency> CheckedArithmetic for Amount<C> {
+    fn checked_add(self, rhs: Self) -> Option<Self> {
+        Amount::checked_add(self, rhs)
+    }
+
+    fn checked_sub(self, rhs: Self) -> Option<Self> {
+        Amount::checked_sub(self, rhs)
+    }
+
+    fn checked_mul(self, rhs: u64) -> Option<Self> {
+        Amount::checked_mul(self, rhs)
+    }
+}
+
+/// The price of one XMR in BTC.
+///
+/// Serialized as the satoshis one XMR costs, as quotes always carried it.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct Price(bitcoin::Amount);
+
+impl Price {
+    pub const ZERO: Price = Price(bitcoin::Amount::ZERO);
+
+    pub fn new(btc_per_xmr: bitcoin::Amount) -> Self {
+        Self(btc_per_xmr)
+    }
+
+    /// The price paid when buying `xmr` for `btc`, rounded up to the next
+    /// satoshi.
+    pub fn of(btc: bitcoin::Amount, xmr: monero::Amount) -> Result<Self> {
+        let sats = Decimal::from(btc.as_sat())
+            .checked_mul(monero::Amount::ONE_XMR.as_piconero_decimal())
+            .and_then(|amount| amount.checked_div(xmr.as_piconero_decimal()))
+            .with_context(|| format!("Failed to compute the price of {} for {}", xmr, btc))?
+            .ceil()
+            .to_u64()
+            .context("Price does not fit into a bitcoin amount")?;
+
+        Ok(Self(bitcoin::Amount::from_sat(sats)))
+    }
+
+    pub fn btc_per_xmr(self) -> bitcoin::Amount {
+        self.0
+    }
+
+    /// The Bitcoin needed to buy `xmr` at this price, rounded up to the next
+    /// satoshi so the seller never sells less than `xmr` for it.
+    pub fn btc_for(self, xmr: monero::Amount) -> Result<bitcoin::Amount> {
+        let sats = xmr
+            .as_piconero_decimal()
+            .checked_mul(Decimal::from(self.0.as_sat()))
+            .and_then(|amount| amount.checked_div(monero::Amount::ONE_XMR.as_piconero_decimal()))
+            .with_context(|| format!("Failed to compute the price of {}", xmr))?
+            .ceil()
+            .to_u64()
+            .with_context(|| format!("Price of {} does not fit into a bitcoin amount", xmr))?;
+
+        Ok(bitcoin::Amount::from_sat(sats))
+    }
+
+    /// The price `percent` percent above this one, rounded down to a whole
+    /// satoshi.
+    pub fn markup(self, percent: Decimal) -> Result<Self> {
+        let sats = Decimal::from(self.0.as_sat())
+            .checked_mul(Decimal::ONE_HUNDRED + percent)
+            .and_then(|amount| amount.checked_div(Decimal::ONE_HUNDRED))
+            .with_context(|| format!("Failed to add {}% to {}", percent, self))?
+            .floor()
+            .to_u64()
+            .with_context(|| format!("{} plus {}% is not a valid price", self, percent))?;
+
+        Ok(Self(bitcoin::Amount::from_sat(sats)))
+    }
+}
+
+impl Display for Price {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/XMR", self.0)
+    }
+}
+
+/// Represents a quote for buying XMR.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq, Hash, Ord, PartialOrd)]
+pub struct Quote {
+    /// The price at which the maker is willing to buy at.
+    pub price: Price,
+    /// The minimum quantity the maker is willing to buy.
+    pub min_quantity: bitcoin::Amount,
+    /// The maximum quantity the maker is willing to buy.
+    pub max_quantity: bitcoin::Amount,
+}
+
+impl Display for Quote {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} for {} to {}",
+            self.price, self.min_quantity, self.max_quantity
+        )
+    }
+}
+
+/// Represents the rate at which we are willing to trade 1 XMR.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Rate {
+    /// Represents the asking price from the market.
+    ask: Price,
+    /// The spread which should be applied to the market asking price.
+    ask_spread: Decimal,
+}
+
+const ZERO_SPREAD: Decimal = Decimal::from_parts(0, 0, 0, false, 0);
+
+/// Decimal places of the asking price in BTC. Spreads are applied at four
+/// more places than a satoshi, so quotes for small amounts are not distorted
+/// by a price rounded to whole satoshis.
+const ASK_PRECISION: u32 = 12;
+
+impl Rate {
+    pub const ZERO: Rate = Rate {
+        ask: Price::ZERO,
+        ask_spread: ZERO_SPREAD,
+    };
+
+    pub fn new(ask: Price, ask_spread: Decimal) -> Self {
+        Self { ask, ask_spread }
+    }
+
+    /// The same market asking price with a different spread.
+    pub fn with_spread(self, ask_spread: Decimal) -> Self {
+        Self { ask_spread, ..self }
+    }
+
+    /// Computes the asking price at which we are willing to sell 1 XMR.
+    ///
+    /// This applies the spread to the market asking price and rounds up to a
+    /// whole satoshi.
+    pub fn ask(&self) -> Result<Price> {
+        let ask_in_sats = self
+            .precise_ask()?
+            .checked_mul(Decimal::from(bitcoin::Amount::ONE_BTC.as_sat()))
+            .context("Multiplication overflow")?
+            .round_dp_with_strategy(0, RoundingStrategy::AwayFromZero);
+
+        Ok(Price::new(bitcoin::Amount::from_sat(
+            ask_in_sats
+                .to_u64()
+                .context("Failed to fit asking price into u64")?,
+        )))
+    }
+
+    /// Calculate a sell quote for a given BTC amount.
+    pub fn sell_quote(&self, quote: bitcoin::Amount) -> Result<monero::Amount> {
+        Self::quote(self.precise_ask()?, quote)
+    }
+
+    /// The asking price in BTC with the spread applied, rounded up to
+    /// [`ASK_PRECISION`] decimal places.
+    fn precise_ask(&self) -> Result<Decimal> {
+        let ask_in_btc = Decimal::from(self.ask.btc_per_xmr().as_sat())
+            .checked_div(Decimal::from(bitcoin::Amount::ONE_BTC.as_sat()))
+            .context("Division overflow")?;
+        let spread_in_btc = ask_in_btc
+            .checked_mul(self.ask_spread)
+            .context("Multiplication overflow")?;
+        let ask_in_btc = ask_in_btc
+            .checked_add(spread_in_btc)
+            .context("Addition overflow")?;
+
+        Ok(ask_in_btc.round_dp_with_strategy(ASK_PRECISION, RoundingStrategy::AwayFromZero))
+    }
+
+    fn quote(rate: Decimal, quote: bitcoin::Amount) -> Result<monero::Amount> {
+        // quote (btc) = rate * base (xmr)
+        // base = quote / rate
+
+        if rate.is_zero() {
+            bail!("Cannot quote at a rate of zero")
+        }
+
+        let quote_in_btc = Decimal::from(quote.as_sat())
+            .checked_div(Decimal::from(bitcoin::Amount::ONE_BTC.as_sat()))
+            .context("Division overflow")?;
+
+        let scaled_quote = quote_in_btc
+            .checked_mul(Decimal::from(monero::Amount::ONE_XMR.as_piconero()))
+            .context("Multiplication overflow")?;
+
+        // Rounds down to a whole piconero, the quote never exceeds the rate.
+        // Dividing without the remainder keeps the division exact, rounding
+        // its result could otherwise carry over into the next piconero.
+        let remainder = scaled_quote
+            .checked_rem(rate)
+            .context("Division overflow")?;
+        let base_in_piconero = (scaled_quote - remainder)
+            .checked_div(rate)
+            .context("Division overflow")?
+            .to_u64()
+            .context("Failed to fit piconero amount into a u64")?;
+
+        Ok(monero::Amount::from_piconero(base_in_piconero))
+    }
+}
+
+impl Display for Rate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.ask)
+    }
+}
+
+/// What is left of `amount` after paying each of `fees` in turn, e.g. of the
+/// lock amount after the cancel and the refund transaction.
+pub fn remaining_after_fees(
+    amount: bitcoin::Amount,
+    fees: &[bitcoin::Amount],
+) -> Result<bitcoin::Amount, Error> {
+    fees.iter()
+        .try_fold(amount, |remaining, fee| remaining.try_sub(*fee))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    const TWO_PERCENT: Decimal = Decimal::from_parts(2, 0, 0, false, 2);
+    const ONE: Decimal = Decimal::from_parts(1, 0, 0, false, 0);
+
+    #[test]
+    fn sell_quote() {
+        let asking_price = Price::new(bitcoin::Amount::from_btc(0.002_500).unwrap());
+        let rate = Rate::new(asking_price, ZERO_SPREAD);
+
+        let btc_amount = bitcoin::Amount::from_btc(2.5).unwrap();
+
+        let xmr_amount = rate.sell_quote(btc_amount).unwrap();
+
+        assert_eq!(xmr_amount, monero::Amount::from_monero(1000.0).unwrap())
+    }
+
+    #[test]
+    fn applies_spread_to_asking_price() {
+        let asking_price = Price::new(bitcoin::Amount::from_sat(100));
+        let rate = Rate::new(asking_price, TWO_PERCENT);
+
+        let price = rate.ask().unwrap();
+
+        assert_eq!(price.btc_per_xmr().as_sat(), 102);
+    }
+
+    #[test]
+    fn given_spread_of_two_percent_when_caluclating_sell_quote_factor_between_should_be_two_percent(
+    ) {
+        let asking_price = Price::new(bitcoin::Amount::from_btc(0.004).unwrap());
+
+        let rate_no_spread = Rate::new(asking_price, ZERO_SPREAD);
+        let rate_with_spread = Rate::new(asking_price, TWO_PERCENT);
+
+        let xmr_no_spread = rate_no_spread.sell_quote(bitcoin::Amount::ONE_BTC).unwrap();
+        let xmr_with_spread = rate_with_spread
+            .sell_quote(bitcoin::Amount::ONE_BTC)
+            .unwrap();
+
+        let xmr_factor =
+            xmr_no_spread.as_piconero_decimal() / xmr_with_spread.as_piconero_decimal() - ONE;
+
+        assert!(xmr_with_spread < xmr_no_spread);
+        assert_eq!(xmr_factor.round_dp(8), TWO_PERCENT); // round to 8 decimal
+                                                         // places to show that
+                                                         // it is really close
+                                                         // to two percent
+    }
+
+    #[test]
+    fn sell_quote_rounds_down_to_a_whole_piconero() {
+        let rate = Rate::new(Price::new(bitcoin::Amount::from_sat(300)), ZERO_SPREAD);
+
+        let xmr_amount = rate.sell_quote(bitcoin::Amount::from_sat(100)).unwrap();
+
+        assert_eq!(xmr_amount, monero::Amount::from_piconero(333_333_333_333));
+    }
+
+    #[test]
+    fn sell_quote_applies_spread_below_a_satoshi() {
+        let one_percent = Decimal::new(1, 2);
+        let rate = Rate::new(Price::new(bitcoin::Amount::from_sat(333)), one_percent);
+
+        let xmr_amount = rate.sell_quote(bitcoin::Amount::from_sat(33_633)).unwrap();
+
+        assert_eq!(
+            rate.ask().unwrap(),
+            Price::new(bitcoin::Amount::from_sat(337))
+        );
+        assert_eq!(xmr_amount, monero::Amount::from_monero(100.0).unwrap());
+    }
+
+    #[test]
+    fn sell_quote_that_does_not_fit_fails() {
+        let rate = Rate::new(Price::new(bitcoin::Amount::ONE_SAT), ZERO_SPREAD);
+
+        assert!(rate.sell_quote(bitcoin::Amount::ONE_BTC).is_err());
+        assert!(Rate::ZERO.sell_quote(bitcoin::Amount::ONE_BTC).is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn sell_quote_does_not_overflow_and_never_exceeds_the_asking_price(
+            ask in 1_000u64..100_000_000,
+            spread in 0u32..10_000,
+            quote in 0u64..10_000_000_000,
+        ) {
+            let rate = Rate::new(Price::new(bitcoin::Amount::from_sat(ask)), Decimal::new(spread.into(), 4));
+            let quote = bitcoin::Amount::from_sat(quote);
+
+            let xmr_amount = rate.sell_quote(quote).unwrap();
+
+            let precise_ask = rate.precise_ask().unwrap();
+            let one_xmr = Decimal::from(monero::Amount::ONE_XMR.as_piconero());
+            let quote_in_btc = Decimal::from(quote.as_sat()) / Decimal::from(bitcoin::Amount::ONE_BTC.as_sat());
+            let worth = |piconero: Decimal| piconero / one_xmr * precise_ask;
+
+            prop_assert!(worth(xmr_amount.as_piconero_decimal()) <= quote_in_btc);
+            prop_assert!(worth(xmr_amount.as_piconero_decimal() + Decimal::from(1)) > quote_in_btc);
+        }
+    }
+
+    #[test]
+    fn fees_exceeding_the_amount_are_an_error() {
+        let amount = bitcoin::Amount::from_sat(10_000);
+        let fees = [
+            bitcoin::Amount::from_sat(6_000),
+            bitcoin::Amount::from_sat(5_000),
+        ];
+
+        let error = remaining_after_fees(amount, &fees).unwrap_err();
+
+        assert_eq!(error, Error::SubtractionUnderflow {
+            lhs: bitcoin::Amount::from_sat(4_000).to_string(),
+            rhs: bitcoin::Amount::from_sat(5_000).to_string(),
+        });
+    }
+
+    #[test]
+    fn price_of_whole_monero() {
+        let price = Price::new(bitcoin::Amount::from_sat(700_000));
+
+        let btc = price
+            .btc_for(monero::Amount::parse_monero("10").unwrap())
+            .unwrap();
+
+        assert_eq!(btc, bitcoin::Amount::from_sat(7_000_000));
+    }
+
+    #[test]
+    fn price_of_fractions_is_rounded_up() {
+        let price = Price::new(bitcoin::Amount::from_sat(700_000));
+
+        let btc = price.btc_for(monero::Amount::from_piconero(1)).unwrap();
+
+        assert_eq!(btc, bitcoin::Amount::from_sat(1));
+    }
+
+    #[test]
+    fn price_paid_is_rounded_up() {
+        let btc = bitcoin::Amount::from_sat(7_000_000);
+
+        let exact = Price::of(btc, monero::Amount::parse_monero("10").unwrap()).unwrap();
+        let rounded = Price::of(btc, monero::Amount::parse_monero("9.99").unwrap()).unwrap();
+
+        assert_eq!(exact, Price::new(bitcoin::Amount::from_sat(700_000)));
+        assert_eq!(rounded, Price::new(bitcoin::Amount::from_sat(700_701)));
+        assert_eq!(rounded.to_string(), "0.00700701 BTC/XMR");
+    }
+
+    #[test]
+    fn markup_is_rounded_down() {
+        let price = Price::new(bitcoin::Amount::from_sat(600_001));
+
+        let marked_up = price.markup(Decimal::from(5)).unwrap();
+
+        assert_eq!(marked_up, Price::new(bitcoin::Amount::from_sat(630_001)));
+        assert!(price.markup(Decimal::from(-200)).is_err());
+    }
+
+    #[test]
+    fn amounts_are_displayed_with_their_unit() {
+        assert_eq!(bitcoin::Amount::from_sat(700_701).to_string(), "0.00700701 BTC");
+        assert_eq!(monero::Amount::from_piconero(1).to_string(), "0.000000000001 XMR");
+        assert_eq!(monero::Amount::ZERO.to_string(), "0.000000000000 XMR");
+    }
+
+    #[test]
+    fn amounts_are_serialized_in_their_smallest_unit() {
+        let btc = bitcoin::Amount::from_sat(700_701);
+        let xmr = monero::Amount::from_piconero(1_000_000);
+
+        assert_eq!(serde_json::to_string(&btc).unwrap(), "700701");
+        assert_eq!(serde_json::to_string(&xmr).unwrap(), "1000000");
+        assert_eq!(serde_json::from_str::<bitcoin::Amount>("700701").unwrap(), btc);
+        assert_eq!(serde_json::from_str::<monero::Amount>("1000000").unwrap(), xmr);
+    }
+
+    #[test]
+    fn quote_is_displayed_with_units() {
+        let quote = Quote {
+            price: Price::new(bitcoin::Amount::from_sat(700_000)),
+            min_quantity: bitcoin::Amount::from_sat(10_000),
+            max_quantity: bitcoin::Amount::ONE_BTC,
+        };
+
+        assert_eq!(
+            quote.to_string(),
+            "0.00700000 BTC/XMR for 0.00010000 BTC to 1.00000000 BTC"
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn monero_arithmetic_fails_exactly_when_u64_does(lhs: u64, rhs: u64) {
+            let a = monero::Amount::from_piconero(lhs);
+            let b = monero::Amount::from_piconero(rhs);
+
+            prop_assert_eq!(a.try_add(b).ok().map(u64::from), lhs.checked_add(rhs));
+            prop_assert_eq!(a.try_sub(b).ok().map(u64::from), lhs.checked_sub(rhs));
+            prop_assert_eq!(a.try_mul(rhs).ok().map(u64::from), lhs.checked_mul(rhs));
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn bitcoin_arithmetic_fails_exactly_when_u64_does(lhs: u64, rhs: u64) {
+            let a = bitcoin::Amount::from_sat(lhs);
+            let b = bitcoin::Amount::from_sat(rhs);
+
+            prop_assert_eq!(a.try_add(b).ok().map(|amount| amount.as_sat()), lhs.checked_add(rhs));
+            prop_assert_eq!(a.try_sub(b).ok().map(|amount| amount.as_sat()), lhs.checked_sub(rhs));
+            prop_assert_eq!(a.try_mul(rhs).ok().map(|amount| amount.as_sat()), lhs.checked_mul(rhs));
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn remaining_after_fees_never_panics(amount: u64, fees: Vec<u64>) {
+            let fees = fees.into_iter().map(bitcoin::Amount::from_sat).collect::<Vec<_>>();
+
+            let remaining = remaining_after_fees(bitcoin::Amount::from_sat(amount), &fees);
+
+            let expected = fees
+                .iter()
+                .try_fold(0u64, |total, fee| total.checked_add(fee.as_sat()))
+                .and_then(|total| amount.checked_sub(total))
+                .map(bitcoin::Amount::from_sat);
+            prop_assert_eq!(remaining.ok(), expected);
+        }
+    }
+}
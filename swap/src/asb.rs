@@ -1,21 +1,44 @@
+pub mod admin;
+pub mod alert;
+mod approvals;
+pub mod backend_health;
 pub mod command;
 pub mod config;
+mod controls;
 mod event_loop;
+pub mod load;
+pub mod maker_key;
 mod network;
-mod rate;
+pub mod notifications;
+pub mod peer_identity;
+pub mod policy;
+pub mod port_mapping;
+pub mod price_feeds;
+pub mod quotes;
+pub mod rebalance;
 mod recovery;
+pub mod reload;
+pub mod service;
+mod supervisor;
 pub mod tracing;
 
-pub use event_loop::{EventLoop, EventLoopHandle, FixedRate, KrakenRate, LatestRate};
+pub use approvals::{Approvals, Decision, PendingApproval};
+pub use backend_health::BackendHealth;
+pub use controls::{TradingControls, TradingParameters};
+pub use event_loop::{
+    EventLoop, EventLoopHandle, FixedRate, KrakenRate, LatestRate, DEFAULT_IDENTITY,
+};
+pub use load::Load;
 pub use network::behaviour::{Behaviour, OutEvent};
 pub use network::transport;
-pub use rate::Rate;
 pub use recovery::cancel::cancel;
+pub use recovery::presigned::{presigned, publish_presigned};
 pub use recovery::punish::punish;
 pub use recovery::redeem::{redeem, Finality};
 pub use recovery::refund::refund;
 pub use recovery::safely_abort::safely_abort;
 pub use recovery::{cancel, refund};
+pub use supervisor::{catch_panic, Supervisor};
 
 #[cfg(test)]
 pub use network::rendezous;
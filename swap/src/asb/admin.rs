@@ -0,0 +1,623 @@
+//! A control channel that allows operators to adjust a running ASB.
+//!
+//! The ASB listens on a unix socket in the `admin` directory of its data
+//! directory. Access is restricted to the user running the ASB through the
+//! permissions of that directory, which is private before the socket is
+//! bound. Each request is a single line of JSON which is answered with a
+//! single line of JSON.
+
+use crate::amounts::Rate;
+use crate::asb::backend_health::{Backend, BackendStatus};
+use crate::asb::price_feeds::FeedStatus;
+use crate::asb::quotes::QuoteStats;
+use crate::asb::rebalance::{self, Rebalancing};
+use crate::asb::reload::Reloader;
+use crate::asb::tracing::{Levels, LogLevel, Subsystem};
+use crate::asb::{
+    Approvals, Decision, KrakenRate, LatestRate, PendingApproval, TradingControls,
+    TradingParameters, DEFAULT_IDENTITY,
+};
+use crate::bitcoin;
+use crate::database::{SwapFilter, SwapInfo, SwapPage};
+use crate::network::connection::{ConnectionCounts, ConnectionStats};
+use crate::protocol::Database;
+use anyhow::{bail, Context, Result};
+use libp2p::PeerId;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DisplayFromStr};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tracing_subscriber::filter::LevelFilter;
+use uuid::Uuid;
+
+const SOCKET_DIR_NAME: &str = "admin";
+const SOCKET_FILE_NAME: &str = "admin.sock";
+
+/// Where the ASB with the given data directory listens for admin requests.
+pub fn socket_path(data_dir: &Path) -> PathBuf {
+    data_dir.join(SOCKET_DIR_NAME).join(SOCKET_FILE_NAME)
+}
+
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum Request {
+    Status,
+    Pause,
+    Resume,
+    SetSpread {
+        ask_spread: Decimal,
+    },
+    SetLimits {
+        min_buy: bitcoin::Amount,
+        max_buy: bitcoin::Amount,
+    },
+    /// The swaps matching `filter`, all swaps if not given.
+    ListSwaps {
+        #[serde(default)]
+        filter: SwapFilter,
+        #[serde(default)]
+        page: SwapPage,
+    },
+    QuoteStats,
+    ConnectionStats,
+    SyncStats,
+    PriceFeeds,
+    RefusePeer {
+        #[serde_as(as = "DisplayFromStr")]
+        peer_id: PeerId,
+    },
+    Reload,
+    Approvals,
+    Approve {
+        swap_id: Uuid,
+    },
+    Reject {
+        swap_id: Uuid,
+    },
+    Rebalancing,
+    ApproveRebalance,
+    RejectRebalance,
+    LogLevels,
+    /// Sets the level of the ASB, or of `subsystem` if given.
+    SetLogLevel {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        subsystem: Option<Subsystem>,
+        #[serde_as(as = "DisplayFromStr")]
+        level: LevelFilter,
+    },
+    /// Lets `subsystem` follow the level of the ASB again.
+    ResetLogLevel {
+        subsystem: Subsystem,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "result", rename_all = "snake_case")]
+pub enum Response {
+    Status {
+        parameters: TradingParameters,
+        refused_peers: Vec<String>,
+        /// The backends that were checked, trading is suspended while one of
+        /// them has a problem.
+        #[serde(default)]
+        backends: BTreeMap<Backend, BackendStatus>,
+    },
+    Swaps {
+        swaps: Vec<SwapInfo>,
+        /// The swap to list the next page after, if there are more swaps.
+        #[serde(default)]
+        next: Option<Uuid>,
+    },
+    QuoteStats {
+        stats: QuoteStats,
+    },
+    /// The connection counts per maker identity.
+    ConnectionStats {
+        identities: BTreeMap<String, ConnectionCounts>,
+    },
+    SyncStats {
+        bitcoin: bitcoin::SyncCounts,
+    },
+    PriceFeeds {
+        feeds: Vec<FeedStatus>,
+        /// The median of the feeds with the configured spread, missing while
+        /// the feeds do not agree on a price.
+        #[serde(default)]
+        rate: Option<Rate>,
+    },
+    Reloaded {
+        changes: Vec<String>,
+    },
+    Approvals {
+        approvals: Vec<PendingApproval>,
+    },
+    Rebalancing {
+        report: rebalance::Report,
+    },
+    LogLevels {
+        levels: Levels,
+    },
+    Error {
+        message: String,
+    },
+}
+
+#[derive(Clone)]
+#[allow(missing_debug_implementations)]
+pub struct Admin {
+    controls: TradingControls,
+    approvals: Approvals,
+    db: Arc<dyn Database + Send + Sync>,
+    reloader: Arc<Reloader>,
+    log_level: LogLevel,
+    connection_stats: BTreeMap<String, ConnectionStats>,
+    sync_stats: bitcoin::SyncStats,
+    rate: KrakenRate,
+    rebalancing: Option<Rebalancing>,
+}
+
+impl Admin {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        controls: TradingControls,
+        approvals: Approvals,
+        db: Arc<dyn Database + Send + Sync>,
+        reloader: Arc<Reloader>,
+        log_level: LogLevel,
+        connection_stats: BTreeMap<String, ConnectionStats>,
+        sync_stats: bitcoin::SyncStats,
+        rate: KrakenRate,
+        rebalancing: Option<Rebalancing>,
+    ) -> Self {
+        Self {
+            controls,
+            approvals,
+            db,
+            reloader,
+            log_level,
+            connection_stats,
+            sync_stats,
+            rate,
+            rebalancing,
+        }
+    }
+
+    pub async fn handle(&self, request: Request) -> Response {
+        match self.try_handle(request).await {
+            Ok(response) => response,
+            Err(error) => Response::Error {
+                message: format!("{:#}", error),
+            },
+        }
+    }
+
+    async fn try_handle(&self, request: Request) -> Result<Response> {
+        match request {
+            Request::Status => {}
+            Request::Pause => {
+                self.controls.set_paused(true);
+                tracing::info!("Trading paused, no new swaps will be accepted");
+            }
+            Request::Resume => {
+                self.controls.set_paused(false);
+                tracing::info!("Trading resumed");
+            }
+            Request::SetSpread { ask_spread } => {
+                self.controls.set_ask_spread(ask_spread)?;
+                tracing::info!(%ask_spread, "Ask spread changed");
+            }
+            Request::SetLimits { min_buy, max_buy } => {
+                self.controls.set_limits(min_buy, max_buy)?;
+                tracing::info!(%min_buy, %max_buy, "Buy limits changed");
+            }
+            Request::ListSwaps { filter, page } => return self.list_swaps(&filter, &page).await,
+            Request::QuoteStats => {
+                return Ok(Response::QuoteStats {
+                    stats: QuoteStats::new(&self.db.get_quotes().await?),
+                })
+            }
+            Request::ConnectionStats => {
+                return Ok(Response::ConnectionStats {
+                    identities: self
+                        .connection_stats
+                        .iter()
+                        .map(|(identity, stats)| (identity.clone(), stats.counts()))
+                        .collect(),
+                })
+            }
+            Request::SyncStats => {
+                return Ok(Response::SyncStats {
+                    bitcoin: self.sync_stats.counts(),
+                })
+            }
+            Request::PriceFeeds => {
+                return Ok(Response::PriceFeeds {
+                    feeds: self.rate.feed_statuses(),
+                    rate: self.rate.clone().latest_rate().ok(),
+                })
+            }
+            Request::RefusePeer { peer_id } => {
+                self.controls.refuse_peer(peer_id);
+                tracing::info!(peer = %peer_id, "Refusing connections from peer");
+            }
+            Request::Reload => {
+                let changes = self.reloader.reload()?;
+
+                return Ok(Response::Reloaded {
+                    changes: changes.iter().map(ToString::to_string).collect(),
+                });
+            }
+            Request::Approvals => {
+                return Ok(self.pending_approvals());
+            }
+            Request::Approve { swap_id } => {
+                self.approvals.decide(swap_id, Decision::Approve)?;
+                tracing::info!(%swap_id, "Approved locking the Monero");
+
+                return Ok(self.pending_approvals());
+            }
+            Request::Reject { swap_id } => {
+                self.approvals.decide(swap_id, Decision::Reject)?;
+                tracing::info!(%swap_id, "Rejected locking the Monero");
+
+                return Ok(self.pending_approvals());
+            }
+            Request::Rebalancing => return self.rebalancing_report(),
+            Request::ApproveRebalance => {
+                self.rebalancing()?.decide(Decision::Approve)?;
+                tracing::info!("Approved rebalancing order");
+
+                return self.rebalancing_report();
+            }
+            Request::RejectRebalance => {
+                self.rebalancing()?.decide(Decision::Reject)?;
+                tracing::info!("Rejected rebalancing order");
+
+                return self.rebalancing_report();
+            }
+            Request::LogLevels => return Ok(self.log_levels()),
+            Request::SetLogLevel {
+                subsystem: None,
+                level,
+            } => {
+                self.log_level.set(level)?;
+                tracing::info!(%level, "Log level changed");
+
+                return Ok(self.log_levels());
+            }
+            Request::SetLogLevel {
+                subsystem: Some(subsystem),
+                level,
+            } => {
+                self.log_level.set_subsystem(subsystem, Some(level))?;
+                tracing::info!(%subsystem, %level, "Log level of subsystem changed");
+
+                return Ok(self.log_levels());
+            }
+            Request::ResetLogLevel { subsystem } => {
+                self.log_level.set_subsystem(subsystem, None)?;
+                tracing::info!(%subsystem, "Log level of subsystem reset");
+
+                return Ok(self.log_levels());
+            }
+        }
+
+        Ok(self.status())
+    }
+
+    fn status(&self) -> Response {
+        let mut refused_peers = self
+            .controls
+            .refused_peers()
+            .iter()
+            .map(PeerId::to_string)
+            .collect::<Vec<_>>();
+        refused_peers.sort();
+
+        Response::Status {
+            parameters: self.controls.parameters(),
+            refused_peers,
+            backends: self.controls.backend_health().statuses(),
+        }
+    }
+
+    fn log_levels(&self) -> Response {
+        Response::LogLevels {
+            levels: self.log_level.levels(),
+        }
+    }
+
+    fn pending_approvals(&self) -> Response {
+        Response::Approvals {
+            approvals: self.approvals.pending(),
+        }
+    }
+
+    fn rebalancing(&self) -> Result<&Rebalancing> {
+        self.rebalancing
+            .as_ref()
+            .context("Rebalancing is not configured")
+    }
+
+    fn rebalancing_report(&self) -> Result<Response> {
+        Ok(Response::Rebalancing {
+            report: self.rebalancing()?.report(),
+        })
+    }
+
+    async fn list_swaps(&self, filter: &SwapFilter, page: &SwapPage) -> Result<Response> {
+        let summaries = self.db.list_swaps(filter, page).await?;
+        let mut swaps = Vec::new();
+
+        for summary in &summaries {
+            let mut swap = SwapInfo::load(self.db.as_ref(), summary, None).await?;
+            swap.identity.get_or_insert_with(|| DEFAULT_IDENTITY.to_owned());
+
+            swaps.push(swap);
+        }
+
+        Ok(Response::Swaps {
+            swaps,
+            next: page.next(&summaries),
+        })
+    }
+}
+
+/// Serves admin requests on the unix socket at `socket_path` until an error
+/// occurs.
+///
+/// A stale socket left behind by a previous run is removed.
+#[cfg(unix)]
+pub async fn serve(socket_path: &Path, admin: Admin) -> Result<()> {
+    use std::io::ErrorKind;
+    use tokio::net::UnixListener;
+
+    let socket_dir = socket_path
+        .parent()
+        .context("Admin socket path has no parent directory")?;
+    create_private_dir(socket_dir)?;
+
+    match std::fs::remove_file(socket_path) {
+        Ok(()) => {}
+        Err(e) if e.kind() == ErrorKind::NotFound => {}
+        Err(e) => {
+            return Err(e).with_context(|| {
+                format!("Failed to remove stale socket {}", socket_path.display())
+            })
+        }
+    }
+
+    let listener = UnixListener::bind(socket_path)
+        .with_context(|| format!("Failed to bind admin socket {}", socket_path.display()))?;
+
+    tracing::info!(path = %socket_path.display(), "Listening for admin requests");
+
+    loop {
+        let (stream, _) = listener
+            .accept()
+            .await
+            .context("Failed to accept admin connection")?;
+
+        let admin = admin.clone();
+        tokio::spawn(async move {
+            if let Err(error) = handle_connection(stream, admin).await {
+                tracing::warn!("Failed to handle admin connection: {:#}", error);
+            }
+        });
+    }
+}
+
+/// Creates `dir` accessible only by the current user, or restricts it if it
+/// exists already, so that nobody else can connect to a socket bound in it.
+#[cfg(unix)]
+fn create_private_dir(dir: &Path) -> Result<()> {
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+
+    std::fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(dir)
+        .with_context(|| format!("Failed to create admin socket directory {}", dir.display()))?;
+    std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o700)).with_context(|| {
+        format!(
+            "Failed to restrict permissions of admin socket directory {}",
+            dir.display()
+        )
+    })?;
+
+    Ok(())
+}
+
+#[cfg(unix)]
+async fn handle_connection(stream: tokio::net::UnixStream, admin: Admin) -> Result<()> {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    let (read, mut write) = stream.into_split();
+    let mut lines = BufReader::new(read).lines();
+
+    while let Some(line) = lines.next_line().await? {
+        let response = match serde_json::from_str::<Request>(&line) {
+            Ok(request) => admin.handle(request).await,
+            Err(error) => Response::Error {
+                message: format!("Invalid request: {}", error),
+            },
+        };
+
+        let mut response = serde_json::to_string(&response)?;
+        response.push('\n');
+        write.write_all(response.as_bytes()).await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub async fn serve(_: &Path, _: Admin) -> Result<()> {
+    bail!("The admin socket is only supported on unix systems")
+}
+
+/// Sends a single request to the ASB listening on `socket_path` and returns
+/// its response.
+#[cfg(unix)]
+pub async fn send(socket_path: &Path, request: Request) -> Result<Response> {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::UnixStream;
+
+    let stream = UnixStream::connect(socket_path).await.with_context(|| {
+        format!(
+            "Failed to connect to admin socket {}, is the ASB running?",
+            socket_path.display()
+        )
+    })?;
+    let (read, mut write) = stream.into_split();
+
+    let mut request = serde_json::to_string(&request)?;
+    request.push('\n');
+    write.write_all(request.as_bytes()).await?;
+
+    let response = BufReader::new(read)
+        .lines()
+        .next_line()
+        .await?
+        .context("ASB closed the admin connection without responding")?;
+
+    let response = serde_json::from_str(&response).context("Failed to parse admin response")?;
+
+    if let Response::Error { message } = response {
+        bail!(message)
+    }
+
+    Ok(response)
+}
+
+#[cfg(not(unix))]
+pub async fn send(_: &Path, _: Request) -> Result<Response> {
+    bail!("The admin socket is only supported on unix systems")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asb::quotes::Outcome;
+    use crate::database::{SwapOrder, SwapStatus};
+    use rust_decimal_macros::dec;
+
+    #[cfg(unix)]
+    #[test]
+    fn socket_dir_is_only_accessible_by_owner() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let data_dir = tempfile::tempdir().unwrap();
+        let socket_dir = socket_path(data_dir.path()).parent().unwrap().to_owned();
+        let mode = |dir: &Path| std::fs::metadata(dir).unwrap().permissions().mode() & 0o777;
+
+        create_private_dir(&socket_dir).unwrap();
+        assert_eq!(mode(&socket_dir), 0o700);
+
+        std::fs::set_permissions(&socket_dir, std::fs::Permissions::from_mode(0o755)).unwrap();
+        create_private_dir(&socket_dir).unwrap();
+        assert_eq!(mode(&socket_dir), 0o700);
+    }
+
+    #[test]
+    fn requests_are_tagged_with_command() {
+        let peer_id = PeerId::random();
+
+        assert_eq!(
+            serde_json::to_string(&Request::Pause).unwrap(),
+            r#"{"command":"pause"}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&Request::SetLimits {
+                min_buy: bitcoin::Amount::from_sat(10_000),
+                max_buy: bitcoin::Amount::from_sat(20_000),
+            })
+            .unwrap(),
+            r#"{"command":"set_limits","min_buy":10000,"max_buy":20000}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<Request>(&format!(
+                r#"{{"command":"refuse_peer","peer_id":"{}"}}"#,
+                peer_id
+            ))
+            .unwrap(),
+            Request::RefusePeer { peer_id }
+        );
+        assert_eq!(
+            serde_json::from_str::<Request>(r#"{"command":"set_spread","ask_spread":0.03}"#)
+                .unwrap(),
+            Request::SetSpread {
+                ask_spread: dec!(0.03)
+            }
+        );
+        assert_eq!(
+            serde_json::to_string(&Request::Approve {
+                swap_id: Uuid::nil()
+            })
+            .unwrap(),
+            r#"{"command":"approve","swap_id":"00000000-0000-0000-0000-000000000000"}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<Request>(
+                r#"{"command":"set_log_level","subsystem":"network","level":"trace"}"#
+            )
+            .unwrap(),
+            Request::SetLogLevel {
+                subsystem: Some(Subsystem::Network),
+                level: LevelFilter::TRACE
+            }
+        );
+    }
+
+    #[test]
+    fn quote_stats_are_keyed_by_outcome() {
+        let stats = QuoteStats {
+            total: 3,
+            outcomes: vec![(Outcome::Converted, 1), (Outcome::PeerDisconnected, 2)]
+                .into_iter()
+                .collect(),
+        };
+
+        assert_eq!(
+            serde_json::to_string(&Response::QuoteStats { stats }).unwrap(),
+            r#"{"result":"quote_stats","stats":{"total":3,"outcomes":{"converted":1,"peer_disconnected":2}}}"#
+        );
+    }
+
+    #[test]
+    fn list_swaps_without_filter_lists_all_swaps() {
+        assert_eq!(
+            serde_json::from_str::<Request>(r#"{"command":"list_swaps"}"#).unwrap(),
+            Request::ListSwaps {
+                filter: SwapFilter::default(),
+                page: SwapPage::default(),
+            }
+        );
+        assert_eq!(
+            serde_json::from_str::<Request>(
+                r#"{"command":"list_swaps","filter":{"status":"open","min_btc":10000},"page":{"limit":10,"order":"newest_first"}}"#
+            )
+            .unwrap(),
+            Request::ListSwaps {
+                filter: SwapFilter {
+                    status: Some(SwapStatus::Open),
+                    min_btc: Some(bitcoin::Amount::from_sat(10_000)),
+                    ..SwapFilter::default()
+                },
+                page: SwapPage {
+                    limit: Some(10),
+                    after: None,
+                    order: SwapOrder::NewestFirst,
+                },
+            }
+        );
+    }
+
+    #[test]
+    fn unknown_command_is_rejected() {
+        assert!(serde_json::from_str::<Request>(r#"{"command":"shutdown"}"#).is_err());
+    }
+}
@@ -0,0 +1,224 @@
+//! Alerts about failures that need the attention of the operator.
+//!
+//! Most failures of the ASB are logged and resolved by resuming the swap. A
+//! swap that fails after the Monero was locked however puts the funds of the
+//! maker at risk: if it is not resumed before its timelocks expire the Monero
+//! may be lost. Such failures are classified as [`Severity::Critical`] and
+//! sent to every configured [`Sink`], e.g. by email or Telegram.
+
+use crate::protocol::alice::AliceState;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use futures::future;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+pub use self::smtp::Smtp;
+pub use self::telegram::Telegram;
+
+mod smtp;
+mod telegram;
+
+/// Delivery of an alert is retried for this long.
+const MAX_RETRY_DURATION: Duration = Duration::from_secs(2 * 60);
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize, strum::Display,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "UPPERCASE")]
+pub enum Severity {
+    /// Something failed, but the funds of the maker are not at risk.
+    Warning,
+    /// The funds of the maker are at risk until the operator intervenes.
+    Critical,
+}
+
+impl Default for Severity {
+    fn default() -> Self {
+        Severity::Warning
+    }
+}
+
+impl Severity {
+    /// Classifies the failure of a swap by the state it failed in.
+    ///
+    /// Once the Monero is locked it can only be redeemed by the taker or
+    /// refunded by the maker, a swap that stops in between has to be looked
+    /// after.
+    pub fn of_swap_failure(state: &AliceState) -> Self {
+        match state {
+            AliceState::XmrLockTransactionSent { .. }
+            | AliceState::XmrLocked { .. }
+            | AliceState::XmrLockTransferProofSent { .. }
+            | AliceState::EncSigLearned { .. }
+            | AliceState::BtcRedeemTransactionPublished { .. }
+            | AliceState::CancelTimelockExpired { .. }
+            | AliceState::BtcCancelled { .. }
+            | AliceState::BtcRefunded { .. }
+            | AliceState::BtcPunishable { .. } => Severity::Critical,
+            AliceState::Started { .. }
+            | AliceState::BtcLockTransactionSeen { .. }
+            | AliceState::BtcLocked { .. }
+            | AliceState::BtcRedeemed
+            | AliceState::XmrRefunded
+            | AliceState::BtcPunished { .. }
+            | AliceState::SafelyAborted => Severity::Warning,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Alert {
+    pub severity: Severity,
+    pub swap_id: Option<Uuid>,
+    pub message: String,
+}
+
+impl Alert {
+    pub fn new(severity: Severity, message: impl Into<String>) -> Self {
+        Self {
+            severity,
+            swap_id: None,
+            message: message.into(),
+        }
+    }
+
+    pub fn swap(severity: Severity, swap_id: Uuid, message: impl Into<String>) -> Self {
+        Self {
+            swap_id: Some(swap_id),
+            ..Self::new(severity, message)
+        }
+    }
+
+    pub fn subject(&self) -> String {
+        match self.swap_id {
+            Some(swap_id) => format!("[{}] ASB: swap {} needs attention", self.severity, swap_id),
+            None => format!("[{}] ASB needs attention", self.severity),
+        }
+    }
+}
+
+impl fmt::Display for Alert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}\n\n{}", self.subject(), self.message)
+    }
+}
+
+#[async_trait]
+pub trait Sink: Send + Sync {
+    async fn send(&self, alert: &Alert) -> Result<()>;
+}
+
+/// Sends alerts of at least the configured severity to all sinks.
+#[derive(Clone, Default)]
+#[allow(missing_debug_implementations)]
+pub struct Alerter {
+    min_severity: Severity,
+    sinks: Vec<Arc<dyn Sink>>,
+}
+
+impl Alerter {
+    pub fn new(min_severity: Severity, sinks: Vec<Arc<dyn Sink>>) -> Self {
+        Self {
+            min_severity,
+            sinks,
+        }
+    }
+
+    /// Sends the alert to all sinks and returns once it was delivered or
+    /// delivery was given up.
+    ///
+    /// Alerts are logged regardless of whether any sink is configured.
+    pub async fn alert(&self, alert: Alert) {
+        tracing::error!(swap_id = ?alert.swap_id, severity = %alert.severity, "{}", alert.message);
+
+        if alert.severity < self.min_severity {
+            return;
+        }
+
+        future::join_all(self.sinks.iter().map(|sink| deliver(sink.as_ref(), &alert))).await;
+    }
+}
+
+async fn deliver(sink: &dyn Sink, alert: &Alert) {
+    let backoff = backoff::ExponentialBackoff {
+        max_elapsed_time: Some(MAX_RETRY_DURATION),
+        ..backoff::ExponentialBackoff::default()
+    };
+
+    let result = backoff::future::retry_notify(
+        backoff,
+        || async { Ok(sink.send(alert).await?) },
+        |error, next: Duration| {
+            tracing::debug!(
+                "Failed to send alert, retrying in {}s: {:#}",
+                next.as_secs(),
+                error
+            );
+        },
+    )
+    .await
+    .context("Failed to send alert");
+
+    if let Err(error) = result {
+        tracing::warn!("{:#}", error);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<Alert>>);
+
+    #[async_trait]
+    impl Sink for Recorder {
+        async fn send(&self, alert: &Alert) -> Result<()> {
+            self.0.lock().unwrap().push(alert.clone());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn finished_swaps_do_not_put_funds_at_risk() {
+        assert_eq!(
+            Severity::of_swap_failure(&AliceState::BtcRedeemed),
+            Severity::Warning
+        );
+        assert_eq!(
+            Severity::of_swap_failure(&AliceState::XmrRefunded),
+            Severity::Warning
+        );
+        assert_eq!(
+            Severity::of_swap_failure(&AliceState::SafelyAborted),
+            Severity::Warning
+        );
+    }
+
+    #[tokio::test]
+    async fn alerts_below_min_severity_are_not_sent() {
+        let recorder = Arc::new(Recorder::default());
+        let alerter = Alerter::new(Severity::Critical, vec![recorder.clone()]);
+        let swap_id = Uuid::new_v4();
+
+        alerter
+            .alert(Alert::swap(Severity::Warning, swap_id, "Swap failed"))
+            .await;
+        alerter
+            .alert(Alert::swap(Severity::Critical, swap_id, "Swap failed"))
+            .await;
+
+        let sent = recorder.0.lock().unwrap();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(
+            sent[0].subject(),
+            format!("[CRITICAL] ASB: swap {} needs attention", swap_id)
+        );
+    }
+}
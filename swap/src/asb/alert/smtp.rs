@@ -0,0 +1,200 @@
+use crate::asb::alert::{Alert, Sink};
+use anyhow::{anyhow, bail, Context, Result};
+use async_trait::async_trait;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio_rustls::rustls::ClientConfig;
+use tokio_rustls::webpki::DNSNameRef;
+use tokio_rustls::TlsConnector;
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Sends alerts by email.
+///
+/// Only SMTP over TLS ("SMTPS", usually port 465) with `AUTH PLAIN` is
+/// supported, plain text connections and `STARTTLS` are not.
+#[derive(Debug, Clone)]
+pub struct Smtp {
+    pub host: String,
+    pub port: u16,
+    pub username: String,
+    pub password: String,
+    pub from: String,
+    pub to: Vec<String>,
+}
+
+#[async_trait]
+impl Sink for Smtp {
+    async fn send(&self, alert: &Alert) -> Result<()> {
+        tokio::time::timeout(TIMEOUT, self.send_mail(alert))
+            .await
+            .with_context(|| format!("No response from SMTP server {}", self.host))?
+    }
+}
+
+impl Smtp {
+    async fn send_mail(&self, alert: &Alert) -> Result<()> {
+        let tcp = TcpStream::connect((self.host.as_str(), self.port))
+            .await
+            .with_context(|| format!("Failed to connect to {}:{}", self.host, self.port))?;
+
+        let mut config = ClientConfig::new();
+        config
+            .root_store
+            .add_server_trust_anchors(&webpki_roots::TLS_SERVER_ROOTS);
+        let domain = DNSNameRef::try_from_ascii_str(&self.host)
+            .map_err(|_| anyhow!("Invalid SMTP host {}", self.host))?;
+        let tls = TlsConnector::from(Arc::new(config))
+            .connect(domain, tcp)
+            .await
+            .context("TLS handshake with SMTP server failed")?;
+
+        let mut session = Session(BufReader::new(tls));
+        session.reply(220).await?;
+        session.command("EHLO localhost", 250).await?;
+        session
+            .command(
+                &format!("AUTH PLAIN {}", auth_plain(&self.username, &self.password)),
+                235,
+            )
+            .await
+            .context("SMTP authentication failed")?;
+        session
+            .command(&format!("MAIL FROM:<{}>", self.from), 250)
+            .await?;
+        for to in &self.to {
+            session.command(&format!("RCPT TO:<{}>", to), 250).await?;
+        }
+        session.command("DATA", 354).await?;
+        session.command(&self.message(alert), 250).await?;
+        session.command("QUIT", 221).await?;
+
+        Ok(())
+    }
+
+    /// The mail including the terminating `.` line of the `DATA` command.
+    fn message(&self, alert: &Alert) -> String {
+        let mut message = format!(
+            "From: <{}>\r\nTo: {}\r\nSubject: {}\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n",
+            self.from,
+            self.to
+                .iter()
+                .map(|to| format!("<{}>", to))
+                .collect::<Vec<_>>()
+                .join(", "),
+            alert.subject()
+        );
+
+        for line in alert.message.lines() {
+            // Lines starting with a dot are escaped ("dot stuffing")
+            if line.starts_with('.') {
+                message.push('.');
+            }
+            message.push_str(line);
+            message.push_str("\r\n");
+        }
+        message.push('.');
+
+        message
+    }
+}
+
+fn auth_plain(username: &str, password: &str) -> String {
+    base64::encode(format!("\0{}\0{}", username, password))
+}
+
+struct Session<S>(BufReader<S>);
+
+impl<S> Session<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    async fn command(&mut self, command: &str, expected: u16) -> Result<()> {
+        self.0.get_mut().write_all(command.as_bytes()).await?;
+        self.0.get_mut().write_all(b"\r\n").await?;
+        self.0.get_mut().flush().await?;
+
+        self.reply(expected).await
+    }
+
+    /// Reads a possibly multi-line reply and fails if it does not have the
+    /// expected code.
+    async fn reply(&mut self, expected: u16) -> Result<()> {
+        loop {
+            let mut line = String::new();
+            if self.0.read_line(&mut line).await? == 0 {
+                bail!("SMTP server closed the connection");
+            }
+
+            let (code, last) = parse_reply_line(&line)?;
+            if code != expected {
+                bail!("Unexpected SMTP reply: {}", line.trim_end());
+            }
+            if last {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Returns the code of a reply line and whether it is the last line of the
+/// reply, e.g. `250-SIZE` is followed by more lines, `250 OK` is not.
+fn parse_reply_line(line: &str) -> Result<(u16, bool)> {
+    let code = line
+        .get(..3)
+        .and_then(|code| code.parse().ok())
+        .with_context(|| format!("Invalid SMTP reply: {}", line.trim_end()))?;
+    let last = line.as_bytes().get(3) != Some(&b'-');
+
+    Ok((code, last))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asb::alert::Severity;
+
+    #[test]
+    fn parses_multi_line_replies() {
+        assert_eq!(
+            parse_reply_line("250-smtp.example.com\r\n").unwrap(),
+            (250, false)
+        );
+        assert_eq!(parse_reply_line("250 SMTPUTF8\r\n").unwrap(), (250, true));
+        assert_eq!(parse_reply_line("221\r\n").unwrap(), (221, true));
+        assert!(parse_reply_line("OK\r\n").is_err());
+    }
+
+    #[test]
+    fn message_lines_starting_with_a_dot_are_escaped() {
+        let smtp = Smtp {
+            host: "smtp.example.com".to_owned(),
+            port: 465,
+            username: "asb".to_owned(),
+            password: "secret".to_owned(),
+            from: "asb@example.com".to_owned(),
+            to: vec!["operator@example.com".to_owned()],
+        };
+        let alert = Alert::new(Severity::Critical, "Event loop failed\n.\nbye");
+
+        assert_eq!(
+            smtp.message(&alert),
+            "From: <asb@example.com>\r\n\
+             To: <operator@example.com>\r\n\
+             Subject: [CRITICAL] ASB needs attention\r\n\
+             Content-Type: text/plain; charset=utf-8\r\n\
+             \r\n\
+             Event loop failed\r\n\
+             ..\r\n\
+             bye\r\n\
+             ."
+        );
+    }
+
+    #[test]
+    fn auth_plain_encodes_username_and_password() {
+        assert_eq!(auth_plain("asb", "secret"), "AGFzYgBzZWNyZXQ=");
+    }
+}
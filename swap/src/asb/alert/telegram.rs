@@ -0,0 +1,84 @@
+use crate::asb::alert::{Alert, Sink};
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use serde::Serialize;
+use std::time::Duration;
+use url::Url;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Sends alerts as messages of a Telegram bot.
+///
+/// The bot has to be a member of the chat, for a private chat the operator
+/// has to start a conversation with the bot first.
+#[derive(Debug, Clone)]
+pub struct Telegram {
+    url: Url,
+    chat_id: String,
+    client: reqwest::Client,
+}
+
+#[derive(Debug, Serialize)]
+struct SendMessage<'a> {
+    chat_id: &'a str,
+    text: String,
+}
+
+impl Telegram {
+    pub fn new(bot_token: &str, chat_id: String) -> Result<Self> {
+        let url = Url::parse(&format!(
+            "https://api.telegram.org/bot{}/sendMessage",
+            bot_token
+        ))
+        .context("Invalid Telegram bot token")?;
+        let client = reqwest::Client::builder().timeout(TIMEOUT).build()?;
+
+        Ok(Self {
+            url,
+            chat_id,
+            client,
+        })
+    }
+}
+
+#[async_trait]
+impl Sink for Telegram {
+    async fn send(&self, alert: &Alert) -> Result<()> {
+        let body = serde_json::to_string(&SendMessage {
+            chat_id: &self.chat_id,
+            text: alert.to_string(),
+        })?;
+
+        // The url contains the bot token, errors must not include it
+        self.client
+            .post(self.url.clone())
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body)
+            .send()
+            .await
+            .map_err(without_url)
+            .context("Failed to send Telegram message")?
+            .error_for_status()
+            .map_err(without_url)
+            .context("Telegram rejected message")?;
+
+        Ok(())
+    }
+}
+
+/// Describes the error without the url of the request, reqwest 0.11.7 has no
+/// way to remove it from the error itself.
+fn without_url(error: reqwest::Error) -> anyhow::Error {
+    if let Some(status) = error.status() {
+        return anyhow!("HTTP status {}", status);
+    }
+
+    if error.is_timeout() {
+        return anyhow!("Request timed out");
+    }
+
+    match std::error::Error::source(&error) {
+        Some(source) => anyhow!("{}", source),
+        None => anyhow!("Request failed"),
+    }
+}
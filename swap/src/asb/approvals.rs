@@ -0,0 +1,153 @@
+//! Holding back high-value swaps until the operator approves them.
+//!
+//! Locking the Monero is the step after which the ASB can no longer walk away
+//! from a swap on its own. With `min_btc` set in the `[approvals]` section of
+//! the config, swaps of at least that amount wait in front of this step until
+//! they are approved or rejected through the admin socket. A swap that is not
+//! decided before the cancel timelock expires is aborted.
+
+use crate::{bitcoin, monero};
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use tokio::sync::oneshot;
+use uuid::Uuid;
+
+/// A swap waiting for the operator, with the details to decide about it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PendingApproval {
+    pub swap_id: Uuid,
+    pub peer_id: Option<String>,
+    /// The Bitcoin locked by the taker.
+    pub btc: bitcoin::Amount,
+    /// The Monero that is locked once the swap is approved.
+    pub xmr: monero::Amount,
+    /// The fee of the Monero lock transaction as estimated during swap setup.
+    pub xmr_lock_fee: monero::Amount,
+    /// The fee deducted from the Bitcoin when redeeming it.
+    pub btc_redeem_fee: bitcoin::Amount,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    Approve,
+    Reject,
+}
+
+type Pending = HashMap<Uuid, (PendingApproval, oneshot::Sender<Decision>)>;
+
+/// A cheaply cloneable handle to the swaps waiting for the operator, shared
+/// between the swaps and the admin socket.
+#[derive(Debug, Clone, Default)]
+pub struct Approvals {
+    min_btc: Option<bitcoin::Amount>,
+    pending: Arc<Mutex<Pending>>,
+}
+
+impl Approvals {
+    /// Swaps of at least `min_btc` require approval, none do if it is `None`.
+    pub fn new(min_btc: Option<bitcoin::Amount>) -> Self {
+        Self {
+            min_btc,
+            pending: Default::default(),
+        }
+    }
+
+    pub fn required(&self, btc: bitcoin::Amount) -> bool {
+        self.min_btc.map_or(false, |min_btc| btc >= min_btc)
+    }
+
+    /// Waits until the operator decided about `swap`.
+    ///
+    /// The swap stays listed until then, callers that stop waiting early have
+    /// to [`withdraw`](Self::withdraw) it.
+    pub async fn request(&self, swap: PendingApproval) -> Decision {
+        let (sender, receiver) = oneshot::channel();
+        self.lock().insert(swap.swap_id, (swap, sender));
+
+        receiver.await.unwrap_or(Decision::Reject)
+    }
+
+    pub fn withdraw(&self, swap_id: Uuid) {
+        self.lock().remove(&swap_id);
+    }
+
+    pub fn pending(&self) -> Vec<PendingApproval> {
+        let mut pending = self
+            .lock()
+            .values()
+            .map(|(swap, _)| swap.clone())
+            .collect::<Vec<_>>();
+        pending.sort_by_key(|swap| swap.swap_id);
+
+        pending
+    }
+
+    pub fn decide(&self, swap_id: Uuid, decision: Decision) -> Result<()> {
+        let (_, sender) = match self.lock().remove(&swap_id) {
+            Some(pending) => pending,
+            None => bail!("Swap {} is not waiting for approval", swap_id),
+        };
+
+        // The swap stopped waiting in the meantime, there is nothing to decide
+        let _ = sender.send(decision);
+
+        Ok(())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Pending> {
+        self.pending.lock().expect("approvals lock not poisoned")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pending_approval(btc: bitcoin::Amount) -> PendingApproval {
+        PendingApproval {
+            swap_id: Uuid::new_v4(),
+            peer_id: None,
+            btc,
+            xmr: monero::Amount::from_monero(1.0).unwrap(),
+            xmr_lock_fee: monero::MONERO_FEE,
+            btc_redeem_fee: bitcoin::Amount::from_sat(1_000),
+        }
+    }
+
+    #[test]
+    fn approval_is_required_from_the_threshold() {
+        let approvals = Approvals::new(Some(bitcoin::Amount::from_sat(100_000)));
+
+        assert!(!approvals.required(bitcoin::Amount::from_sat(99_999)));
+        assert!(approvals.required(bitcoin::Amount::from_sat(100_000)));
+        assert!(!Approvals::new(None).required(bitcoin::Amount::MAX));
+    }
+
+    #[tokio::test]
+    async fn waiting_swap_receives_the_decision() {
+        let approvals = Approvals::new(Some(bitcoin::Amount::ZERO));
+        let swap = pending_approval(bitcoin::Amount::from_sat(100_000));
+        let swap_id = swap.swap_id;
+
+        let decision = tokio::spawn({
+            let approvals = approvals.clone();
+            async move { approvals.request(swap).await }
+        });
+        while approvals.pending().is_empty() {
+            tokio::task::yield_now().await;
+        }
+        approvals.decide(swap_id, Decision::Approve).unwrap();
+
+        assert_eq!(decision.await.unwrap(), Decision::Approve);
+        assert!(approvals.pending().is_empty());
+    }
+
+    #[test]
+    fn deciding_about_unknown_swap_fails() {
+        let approvals = Approvals::new(Some(bitcoin::Amount::ZERO));
+
+        assert!(approvals.decide(Uuid::new_v4(), Decision::Reject).is_err());
+    }
+}
@@ -0,0 +1,273 @@
+//! A dead man's switch that stops quoting while a backend is degraded.
+//!
+//! Quotes are only as good as the rate and the view of both blockchains they
+//! are based on. Every [`CHECK_INTERVAL`] the ASB checks that
+//! - the price tickers sent a message within [`Thresholds::max_rate_age`] and
+//!   agree on the price, see [`crate::asb::price_feeds`],
+//! - the Electrum server answers,
+//! - the Monero wallet RPC answers and its block height advanced within
+//!   [`Thresholds::max_monero_stall`].
+//!
+//! While one of the checks fails, quotes are handed out with a maximum of zero
+//! and new swaps are refused, as if trading was paused. Quoting resumes by
+//! itself once all checks pass again. Swaps that are already set up are not
+//! affected.
+
+use crate::asb::alert::{Alert, Alerter, Severity};
+use crate::asb::KrakenRate;
+use crate::{bitcoin, monero};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use time::OffsetDateTime;
+
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(15);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Thresholds {
+    /// How long the price ticker may stay silent, it sends heartbeats while
+    /// the price does not change.
+    pub max_rate_age: Duration,
+    /// How long the Monero block height may stay the same, blocks are mined
+    /// every two minutes on average.
+    pub max_monero_stall: Duration,
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Self {
+            max_rate_age: Duration::from_secs(60),
+            max_monero_stall: Duration::from_secs(30 * 60),
+        }
+    }
+}
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, strum::Display,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Backend {
+    #[strum(serialize = "price ticker")]
+    PriceTicker,
+    #[strum(serialize = "Electrum server")]
+    Electrum,
+    #[strum(serialize = "Monero wallet RPC")]
+    MoneroWallet,
+}
+
+/// The health of a backend as of its last check.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BackendStatus {
+    /// Why the backend is degraded, `None` while it is healthy.
+    #[serde(default)]
+    pub problem: Option<String>,
+    /// Seconds since the unix epoch of the last check that passed.
+    #[serde(default)]
+    pub last_healthy_at: Option<i64>,
+    /// How often the backend became degraded since the ASB started.
+    #[serde(default)]
+    pub outages: u64,
+}
+
+/// A cheaply cloneable handle to the health of the backends, shared between
+/// the checks, the network layer of all identities and the admin socket.
+///
+/// Backends that were not checked yet count as healthy.
+#[derive(Debug, Clone, Default)]
+pub struct BackendHealth {
+    inner: Arc<Mutex<BTreeMap<Backend, BackendStatus>>>,
+}
+
+impl BackendHealth {
+    /// Records the outcome of checking `backend`, returns whether the backend
+    /// became degraded or recovered with it.
+    pub fn record(&self, backend: Backend, result: Result<(), String>, now: i64) -> bool {
+        let mut statuses = self.lock();
+        let status = statuses.entry(backend).or_default();
+        let was_healthy = status.problem.is_none();
+
+        match result {
+            Ok(()) => {
+                status.problem = None;
+                status.last_healthy_at = Some(now);
+            }
+            Err(problem) => {
+                if was_healthy {
+                    status.outages += 1;
+                }
+                status.problem = Some(problem);
+            }
+        }
+
+        was_healthy != status.problem.is_none()
+    }
+
+    /// Why quoting is suspended, `None` if all backends are healthy.
+    pub fn degraded(&self) -> Option<String> {
+        let problems = self
+            .lock()
+            .iter()
+            .filter_map(|(backend, status)| {
+                let problem = status.problem.as_ref()?;
+                Some(format!("{}: {}", backend, problem))
+            })
+            .collect::<Vec<_>>();
+
+        if problems.is_empty() {
+            None
+        } else {
+            Some(problems.join(", "))
+        }
+    }
+
+    pub fn statuses(&self) -> BTreeMap<Backend, BackendStatus> {
+        self.lock().clone()
+    }
+
+    /// Checks the backends every [`CHECK_INTERVAL`], never returns.
+    pub async fn run(
+        self,
+        thresholds: Thresholds,
+        rate: KrakenRate,
+        bitcoin_wallet: Arc<bitcoin::Wallet>,
+        monero_wallet: Arc<monero::Wallet>,
+        alerter: Alerter,
+    ) {
+        let mut monero_progress = None;
+
+        loop {
+            tokio::time::sleep(CHECK_INTERVAL).await;
+
+            let price_ticker = check_rate_age(rate.last_message_age(), thresholds.max_rate_age)
+                .and_then(|()| rate.cross_check().map_err(|error| error.to_string()));
+            let electrum = bitcoin_wallet
+                .latest_block_height()
+                .await
+                .map(|_| ())
+                .map_err(|error| format!("{:#}", error));
+            let monero_wallet = match monero_wallet.block_height().await {
+                Ok(height) => check_progress(
+                    &mut monero_progress,
+                    height.height,
+                    Instant::now(),
+                    thresholds.max_monero_stall,
+                ),
+                Err(error) => Err(format!("{:#}", error)),
+            };
+
+            let now = OffsetDateTime::now_utc().unix_timestamp();
+            for (backend, result) in [
+                (Backend::PriceTicker, price_ticker),
+                (Backend::Electrum, electrum),
+                (Backend::MoneroWallet, monero_wallet),
+            ] {
+                if !self.record(backend, result, now) {
+                    continue;
+                }
+
+                let problem = self.lock()[&backend].problem.clone();
+                match problem {
+                    Some(problem) => {
+                        let message = format!(
+                            "Stopped quoting because the {} is degraded: {}",
+                            backend, problem
+                        );
+                        alerter.alert(Alert::new(Severity::Warning, message)).await;
+                    }
+                    None => tracing::info!(%backend, "Backend recovered"),
+                }
+            }
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, BTreeMap<Backend, BackendStatus>> {
+        self.inner.lock().expect("backend health lock not poisoned")
+    }
+}
+
+fn check_rate_age(age: Option<Duration>, max_rate_age: Duration) -> Result<(), String> {
+    match age {
+        Some(age) if age <= max_rate_age => Ok(()),
+        Some(age) => Err(format!("no message for {}s", age.as_secs())),
+        None => Err("no message received yet".to_owned()),
+    }
+}
+
+/// Fails once `height` did not advance for longer than `max_stall`.
+fn check_progress(
+    progress: &mut Option<(u32, Instant)>,
+    height: u32,
+    now: Instant,
+    max_stall: Duration,
+) -> Result<(), String> {
+    match *progress {
+        Some((previous, since)) if previous >= height => {
+            let stalled = now.saturating_duration_since(since);
+            if stalled > max_stall {
+                return Err(format!(
+                    "block height {} did not advance for {} minutes",
+                    height,
+                    stalled.as_secs() / 60
+                ));
+            }
+        }
+        _ => *progress = Some((height, now)),
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_transitions_count_as_outages() {
+        let health = BackendHealth::default();
+
+        assert!(!health.record(Backend::Electrum, Ok(()), 1));
+        assert!(health.record(Backend::Electrum, Err("unreachable".to_owned()), 2));
+        assert!(!health.record(Backend::Electrum, Err("unreachable".to_owned()), 3));
+        assert_eq!(
+            health.degraded(),
+            Some("Electrum server: unreachable".to_owned())
+        );
+
+        assert!(health.record(Backend::Electrum, Ok(()), 4));
+        assert_eq!(health.degraded(), None);
+        assert_eq!(health.statuses()[&Backend::Electrum], BackendStatus {
+            problem: None,
+            last_healthy_at: Some(4),
+            outages: 1,
+        });
+    }
+
+    #[test]
+    fn stale_or_missing_rate_is_degraded() {
+        let max = Duration::from_secs(60);
+
+        assert!(check_rate_age(Some(Duration::from_secs(5)), max).is_ok());
+        assert!(check_rate_age(Some(Duration::from_secs(61)), max).is_err());
+        assert!(check_rate_age(None, max).is_err());
+    }
+
+    #[test]
+    fn stalled_height_is_degraded_until_it_advances() {
+        let max = Duration::from_secs(30 * 60);
+        let start = Instant::now();
+        let mut progress = None;
+
+        assert!(check_progress(&mut progress, 100, start, max).is_ok());
+        assert!(check_progress(&mut progress, 100, start + max, max).is_ok());
+        assert!(check_progress(
+            &mut progress,
+            100,
+            start + max + Duration::from_secs(1),
+            max
+        )
+        .is_err());
+        assert!(check_progress(&mut progress, 101, start + 2 * max, max).is_ok());
+        assert!(check_progress(&mut progress, 101, start + 2 * max + max / 2, max).is_ok());
+    }
+}
@@ -1,13 +1,21 @@
+use crate::asb::admin;
 use crate::asb::config::GetDefaults;
-use crate::bitcoin::Amount;
+use crate::asb::tracing::Subsystem;
+use crate::bitcoin::{parse_amount, Amount};
+use crate::database::SwapQuery;
 use crate::env;
 use crate::env::GetConfig;
+use crate::protocol::presigned;
 use anyhow::{bail, Result};
 use bitcoin::Address;
+use libp2p::PeerId;
+use rust_decimal::Decimal;
 use serde::Serialize;
 use std::ffi::OsString;
 use std::path::PathBuf;
 use structopt::StructOpt;
+use tracing_subscriber::filter::LevelFilter;
+use url::Url;
 use uuid::Uuid;
 
 pub fn parse_args<I, T>(raw_args: I) -> Result<Arguments>
@@ -20,23 +28,43 @@ where
 
     let json = args.json;
     let disable_timestamp = args.disable_timestamp;
+    let otlp_endpoint = args.otlp_endpoint;
     let testnet = args.testnet;
     let config = args.config;
     let command: RawCommand = args.cmd;
 
     let arguments = match command {
-        RawCommand::Start { resume_only } => Arguments {
+        RawCommand::Start {
+            resume_only,
+            regenerate_identity,
+            rotate_maker_key,
+        } => Arguments {
             testnet,
             json,
             disable_timestamp,
+            otlp_endpoint,
             config_path: config_path(config, testnet)?,
             env_config: env_config(testnet),
-            cmd: Command::Start { resume_only },
+            cmd: Command::Start {
+                resume_only,
+                regenerate_identity,
+                rotate_maker_key,
+            },
+        },
+        RawCommand::Init { restore } => Arguments {
+            testnet,
+            json,
+            disable_timestamp,
+            otlp_endpoint,
+            config_path: config_path(config, testnet)?,
+            env_config: env_config(testnet),
+            cmd: Command::Init { restore },
         },
         RawCommand::History => Arguments {
             testnet,
             json,
             disable_timestamp,
+            otlp_endpoint,
             config_path: config_path(config, testnet)?,
             env_config: env_config(testnet),
             cmd: Command::History,
@@ -45,6 +73,7 @@ where
             testnet,
             json,
             disable_timestamp,
+            otlp_endpoint,
             config_path: config_path(config, testnet)?,
             env_config: env_config(testnet),
             cmd: Command::WithdrawBtc {
@@ -56,6 +85,7 @@ where
             testnet,
             json,
             disable_timestamp,
+            otlp_endpoint,
             config_path: config_path(config, testnet)?,
             env_config: env_config(testnet),
             cmd: Command::Balance,
@@ -64,6 +94,7 @@ where
             testnet,
             json,
             disable_timestamp,
+            otlp_endpoint,
             config_path: config_path(config, testnet)?,
             env_config: env_config(testnet),
             cmd: Command::Config,
@@ -72,10 +103,58 @@ where
             testnet,
             json,
             disable_timestamp,
+            otlp_endpoint,
             config_path: config_path(config, testnet)?,
             env_config: env_config(testnet),
             cmd: Command::ExportBitcoinWallet,
         },
+        RawCommand::Admin(admin_command) => Arguments {
+            testnet,
+            json,
+            disable_timestamp,
+            otlp_endpoint,
+            config_path: config_path(config, testnet)?,
+            env_config: env_config(testnet),
+            cmd: Command::Admin {
+                request: admin_command.into(),
+            },
+        },
+        RawCommand::Db(Db::Prune { retention_days }) => Arguments {
+            testnet,
+            json,
+            disable_timestamp,
+            otlp_endpoint,
+            config_path: config_path(config, testnet)?,
+            env_config: env_config(testnet),
+            cmd: Command::PruneDatabase { retention_days },
+        },
+        RawCommand::Db(Db::Compact) => Arguments {
+            testnet,
+            json,
+            disable_timestamp,
+            otlp_endpoint,
+            config_path: config_path(config, testnet)?,
+            env_config: env_config(testnet),
+            cmd: Command::CompactDatabase,
+        },
+        RawCommand::Report(Report::Quotes) => Arguments {
+            testnet,
+            json,
+            disable_timestamp,
+            otlp_endpoint,
+            config_path: config_path(config, testnet)?,
+            env_config: env_config(testnet),
+            cmd: Command::ReportQuotes,
+        },
+        RawCommand::Version => Arguments {
+            testnet,
+            json,
+            disable_timestamp,
+            otlp_endpoint,
+            config_path: config_path(config, testnet)?,
+            env_config: env_config(testnet),
+            cmd: Command::Version,
+        },
         RawCommand::ManualRecovery(ManualRecovery::Redeem {
             redeem_params: RecoverCommandParams { swap_id },
             do_not_await_finality,
@@ -83,6 +162,7 @@ where
             testnet,
             json,
             disable_timestamp,
+            otlp_endpoint,
             config_path: config_path(config, testnet)?,
             env_config: env_config(testnet),
             cmd: Command::Redeem {
@@ -97,6 +177,7 @@ where
             testnet,
             json,
             disable_timestamp,
+            otlp_endpoint,
             config_path: config_path(config, testnet)?,
             env_config: env_config(testnet),
             cmd: Command::Cancel { swap_id },
@@ -107,6 +188,7 @@ where
             testnet,
             json,
             disable_timestamp,
+            otlp_endpoint,
             config_path: config_path(config, testnet)?,
             env_config: env_config(testnet),
             cmd: Command::Refund { swap_id },
@@ -117,14 +199,28 @@ where
             testnet,
             json,
             disable_timestamp,
+            otlp_endpoint,
             config_path: config_path(config, testnet)?,
             env_config: env_config(testnet),
             cmd: Command::Punish { swap_id },
         },
+        RawCommand::ManualRecovery(ManualRecovery::Presigned {
+            params: RecoverCommandParams { swap_id },
+            publish,
+        }) => Arguments {
+            testnet,
+            json,
+            disable_timestamp,
+            otlp_endpoint,
+            config_path: config_path(config, testnet)?,
+            env_config: env_config(testnet),
+            cmd: Command::Presigned { swap_id, publish },
+        },
         RawCommand::ManualRecovery(ManualRecovery::SafelyAbort { swap_id }) => Arguments {
             testnet,
             json,
             disable_timestamp,
+            otlp_endpoint,
             config_path: config_path(config, testnet)?,
             env_config: env_config(testnet),
             cmd: Command::SafelyAbort { swap_id },
@@ -185,6 +281,7 @@ pub struct Arguments {
     pub testnet: bool,
     pub json: bool,
     pub disable_timestamp: bool,
+    pub otlp_endpoint: Option<Url>,
     pub config_path: PathBuf,
     pub env_config: env::Config,
     pub cmd: Command,
@@ -192,8 +289,13 @@ pub struct Arguments {
 
 #[derive(Debug, PartialEq)]
 pub enum Command {
+    Init {
+        restore: bool,
+    },
     Start {
         resume_only: bool,
+        regenerate_identity: bool,
+        rotate_maker_key: bool,
     },
     History,
     Config,
@@ -218,7 +320,20 @@ pub enum Command {
     SafelyAbort {
         swap_id: Uuid,
     },
+    Presigned {
+        swap_id: Uuid,
+        publish: Option<presigned::Kind>,
+    },
     ExportBitcoinWallet,
+    Admin {
+        request: admin::Request,
+    },
+    PruneDatabase {
+        retention_days: Option<u32>,
+    },
+    CompactDatabase,
+    ReportQuotes,
+    Version,
 }
 
 #[derive(structopt::StructOpt, Debug)]
@@ -246,6 +361,12 @@ pub struct RawArguments {
     )]
     pub disable_timestamp: bool,
 
+    #[structopt(
+        long = "otlp-endpoint",
+        help = "Export the tracing spans to this OpenTelemetry collector using OTLP over gRPC, e.g. http://localhost:4317"
+    )]
+    pub otlp_endpoint: Option<Url>,
+
     #[structopt(
         long = "config",
         help = "Provide a custom path to the configuration file. The configuration file must be a toml file.",
@@ -260,6 +381,16 @@ pub struct RawArguments {
 #[derive(structopt::StructOpt, Debug)]
 #[structopt(name = "xmr_btc-swap", about = "XMR BTC atomic swap")]
 pub enum RawCommand {
+    #[structopt(
+        about = "Sets up a new ASB: creates the config, the seed, the peer identity and the wallets, prints the mnemonic to back them up and checks that the backends are reachable."
+    )]
+    Init {
+        #[structopt(
+            long = "restore",
+            help = "Restore the seed from a previously printed mnemonic instead of generating a new one."
+        )]
+        restore: bool,
+    },
     #[structopt(about = "Main command to run the ASB.")]
     Start {
         #[structopt(
@@ -267,6 +398,16 @@ pub enum RawCommand {
             help = "For maintenance only. When set, no new swap requests will be accepted, but existing unfinished swaps will be resumed."
         )]
         resume_only: bool,
+        #[structopt(
+            long = "regenerate-identity",
+            help = "Replace the peer id of the ASB with a new random one. Takers that know the previous peer id will no longer find the ASB under it."
+        )]
+        regenerate_identity: bool,
+        #[structopt(
+            long = "rotate-maker-key",
+            help = "Replace the maker key that signs the quotes with a new random one. The previous key signs the rotation, so takers keep recognizing the ASB."
+        )]
+        rotate_maker_key: bool,
     },
     #[structopt(about = "Prints swap-id and the state of each swap ever made.")]
     History,
@@ -276,7 +417,8 @@ pub enum RawCommand {
     WithdrawBtc {
         #[structopt(
             long = "amount",
-            help = "Optionally specify the amount of Bitcoin to be withdrawn. If not specified the wallet will be drained."
+            help = "Optionally specify the amount of Bitcoin to be withdrawn, e.g. '0.5 BTC'. If not specified the wallet will be drained.",
+            parse(try_from_str = parse_amount)
         )]
         amount: Option<Amount>,
         #[structopt(long = "address", help = "The address to receive the Bitcoin.")]
@@ -290,6 +432,196 @@ pub enum RawCommand {
     ExportBitcoinWallet,
     #[structopt(about = "Contains sub-commands for recovering a swap manually.")]
     ManualRecovery(ManualRecovery),
+    #[structopt(
+        about = "Contains sub-commands for controlling a running ASB through its admin socket."
+    )]
+    Admin(AdminCommand),
+    #[structopt(about = "Contains sub-commands for maintaining the database.")]
+    Db(Db),
+    #[structopt(about = "Contains sub-commands for reporting on past trading.")]
+    Report(Report),
+    #[structopt(
+        about = "Prints the version, git commit, target, features and protocols of this build."
+    )]
+    Version,
+}
+
+#[derive(structopt::StructOpt, Debug)]
+pub enum AdminCommand {
+    #[structopt(about = "Prints the current trading parameters and refused peers.")]
+    Status,
+    #[structopt(
+        about = "Stops accepting new swaps and quotes a maximum of zero. Unfinished swaps are still resumed."
+    )]
+    Pause,
+    #[structopt(about = "Starts accepting new swaps again.")]
+    Resume,
+    #[structopt(about = "Changes the spread that is applied on top of the Kraken ask price.")]
+    SetSpread {
+        #[structopt(
+            long = "ask-spread",
+            help = "The new spread as a fraction, e.g. 0.02 for 2%."
+        )]
+        ask_spread: Decimal,
+    },
+    #[structopt(about = "Changes the minimum and maximum amount of Bitcoin accepted per swap.")]
+    SetLimits {
+        #[structopt(
+            long = "min-buy",
+            help = "The minimum amount, e.g. '0.002 BTC'.",
+            parse(try_from_str = parse_amount)
+        )]
+        min_buy: Amount,
+        #[structopt(
+            long = "max-buy",
+            help = "The maximum amount, e.g. '0.02 BTC'.",
+            parse(try_from_str = parse_amount)
+        )]
+        max_buy: Amount,
+    },
+    #[structopt(
+        about = "Prints swap-id, peer-id and the current phase of the swaps, e.g. of the unfinished ones with --status open."
+    )]
+    ListSwaps {
+        #[structopt(flatten)]
+        query: SwapQuery,
+    },
+    #[structopt(
+        about = "Prints how many quotes converted into a swap and why the others did not."
+    )]
+    QuoteStats,
+    #[structopt(
+        about = "Prints per identity how many connections were established, lost and re-established since the ASB started."
+    )]
+    ConnectionStats,
+    #[structopt(
+        about = "Prints how often the Bitcoin wallet was synced since the ASB started and how long the syncs took."
+    )]
+    SyncStats,
+    #[structopt(
+        about = "Prints the ask of each price ticker, how long ago it sent its last message and how far it is from the median."
+    )]
+    PriceFeeds,
+    #[structopt(
+        about = "Closes all connections to the given peer and refuses new ones until the ASB is restarted."
+    )]
+    RefusePeer {
+        #[structopt(long = "peer-id")]
+        peer_id: PeerId,
+    },
+    #[structopt(
+        about = "Re-reads the config file and applies changes to the [maker] and [logging] sections. The ASB also does this on SIGHUP."
+    )]
+    Reload,
+    #[structopt(
+        about = "Prints the swaps waiting for approval to lock the Monero, with their amounts and fees."
+    )]
+    Approvals,
+    #[structopt(about = "Approves locking the Monero of a swap waiting for approval.")]
+    Approve {
+        #[structopt(long = "swap-id")]
+        swap_id: Uuid,
+    },
+    #[structopt(
+        about = "Rejects a swap waiting for approval, it is aborted without locking the Monero."
+    )]
+    Reject {
+        #[structopt(long = "swap-id")]
+        swap_id: Uuid,
+    },
+    #[structopt(
+        about = "Prints the rebalancing mode, the order waiting for approval and the last order."
+    )]
+    Rebalancing,
+    #[structopt(about = "Approves the rebalancing order waiting for approval.")]
+    ApproveRebalance,
+    #[structopt(about = "Rejects the rebalancing order waiting for approval.")]
+    RejectRebalance,
+    #[structopt(about = "Prints the log level and the subsystems logged at a different level.")]
+    LogLevels,
+    #[structopt(
+        about = "Changes the log level of the ASB or of a subsystem until the ASB is restarted."
+    )]
+    SetLogLevel {
+        #[structopt(
+            long = "subsystem",
+            help = "One of protocol, network, bitcoin, monero or db. Changes the level of the ASB if not given."
+        )]
+        subsystem: Option<Subsystem>,
+        #[structopt(
+            long = "level",
+            help = "One of off, error, warn, info, debug or trace."
+        )]
+        level: LevelFilter,
+    },
+    #[structopt(about = "Lets a subsystem follow the log level of the ASB again.")]
+    ResetLogLevel {
+        #[structopt(long = "subsystem")]
+        subsystem: Subsystem,
+    },
+}
+
+impl From<AdminCommand> for admin::Request {
+    fn from(command: AdminCommand) -> Self {
+        match command {
+            AdminCommand::Status => admin::Request::Status,
+            AdminCommand::Pause => admin::Request::Pause,
+            AdminCommand::Resume => admin::Request::Resume,
+            AdminCommand::SetSpread { ask_spread } => admin::Request::SetSpread { ask_spread },
+            AdminCommand::SetLimits { min_buy, max_buy } => {
+                admin::Request::SetLimits { min_buy, max_buy }
+            }
+            AdminCommand::ListSwaps { query } => {
+                let (filter, page) = query.into_filter_and_page();
+                admin::Request::ListSwaps { filter, page }
+            }
+            AdminCommand::QuoteStats => admin::Request::QuoteStats,
+            AdminCommand::ConnectionStats => admin::Request::ConnectionStats,
+            AdminCommand::SyncStats => admin::Request::SyncStats,
+            AdminCommand::PriceFeeds => admin::Request::PriceFeeds,
+            AdminCommand::RefusePeer { peer_id } => admin::Request::RefusePeer { peer_id },
+            AdminCommand::Reload => admin::Request::Reload,
+            AdminCommand::Approvals => admin::Request::Approvals,
+            AdminCommand::Approve { swap_id } => admin::Request::Approve { swap_id },
+            AdminCommand::Reject { swap_id } => admin::Request::Reject { swap_id },
+            AdminCommand::Rebalancing => admin::Request::Rebalancing,
+            AdminCommand::ApproveRebalance => admin::Request::ApproveRebalance,
+            AdminCommand::RejectRebalance => admin::Request::RejectRebalance,
+            AdminCommand::LogLevels => admin::Request::LogLevels,
+            AdminCommand::SetLogLevel { subsystem, level } => {
+                admin::Request::SetLogLevel { subsystem, level }
+            }
+            AdminCommand::ResetLogLevel { subsystem } => {
+                admin::Request::ResetLogLevel { subsystem }
+            }
+        }
+    }
+}
+
+#[derive(structopt::StructOpt, Debug)]
+pub enum Db {
+    #[structopt(
+        about = "Reduces swaps that finished before the retention period to their final state. Unfinished swaps are never pruned because their states are needed for cancel, refund and punish."
+    )]
+    Prune {
+        #[structopt(
+            long = "retention-days",
+            help = "Keep the full history of swaps that finished within this many days. Defaults to `retention_days` in the [data] section of the config or 30 days."
+        )]
+        retention_days: Option<u32>,
+    },
+    #[structopt(
+        about = "Rebuilds the database file to reclaim the space freed by pruning. Must not be run while the ASB is running."
+    )]
+    Compact,
+}
+
+#[derive(structopt::StructOpt, Debug)]
+pub enum Report {
+    #[structopt(
+        about = "Prints what became of the quotes handed out, per identity: converted into a swap, expired, the taker disconnected or the swap was rejected."
+    )]
+    Quotes,
 }
 
 #[derive(structopt::StructOpt, Debug)]
@@ -328,6 +660,19 @@ pub enum ManualRecovery {
         #[structopt(flatten)]
         punish_params: RecoverCommandParams,
     },
+    #[structopt(
+        about = "Prints the cancel and punish transactions that were signed when the swap was set up, as raw transactions any Bitcoin node can publish. Does not depend on the state of the swap."
+    )]
+    Presigned {
+        #[structopt(flatten)]
+        params: RecoverCommandParams,
+
+        #[structopt(
+            long = "publish",
+            help = "Publish the presigned transaction of this kind instead, either cancel or punish. Fails if its timelock did not expire yet."
+        )]
+        publish: Option<presigned::Kind>,
+    },
     #[structopt(about = "Safely Abort requires the swap to be in a state prior to locking XMR.")]
     SafelyAbort {
         #[structopt(
@@ -367,9 +712,88 @@ mod tests {
             testnet: false,
             json: false,
             disable_timestamp: false,
+            otlp_endpoint: None,
+            config_path: default_mainnet_conf_path,
+            env_config: mainnet_env_config,
+            cmd: Command::Start {
+                resume_only: false,
+                regenerate_identity: false,
+                rotate_maker_key: false,
+            },
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+    }
+
+    #[test]
+    fn ensure_start_regenerate_identity_mapping_mainnet() {
+        let default_mainnet_conf_path = env::Mainnet::getConfigFileDefaults().unwrap().config_path;
+        let mainnet_env_config = env::Mainnet::get_config();
+
+        let raw_ars = vec![BINARY_NAME, "start", "--regenerate-identity"];
+        let expected_args = Arguments {
+            testnet: false,
+            json: false,
+            disable_timestamp: false,
+            otlp_endpoint: None,
+            config_path: default_mainnet_conf_path,
+            env_config: mainnet_env_config,
+            cmd: Command::Start {
+                resume_only: false,
+                regenerate_identity: true,
+                rotate_maker_key: false,
+            },
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+    }
+
+    #[test]
+    fn ensure_start_rotate_maker_key_mapping_mainnet() {
+        let default_mainnet_conf_path = env::Mainnet::getConfigFileDefaults().unwrap().config_path;
+        let mainnet_env_config = env::Mainnet::get_config();
+
+        let raw_ars = vec![BINARY_NAME, "start", "--rotate-maker-key"];
+        let expected_args = Arguments {
+            testnet: false,
+            json: false,
+            disable_timestamp: false,
+            otlp_endpoint: None,
             config_path: default_mainnet_conf_path,
             env_config: mainnet_env_config,
-            cmd: Command::Start { resume_only: false },
+            cmd: Command::Start {
+                resume_only: false,
+                regenerate_identity: false,
+                rotate_maker_key: true,
+            },
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+    }
+
+    #[test]
+    fn ensure_start_otlp_endpoint_mapping_mainnet() {
+        let default_mainnet_conf_path = env::Mainnet::getConfigFileDefaults().unwrap().config_path;
+        let mainnet_env_config = env::Mainnet::get_config();
+
+        let raw_ars = vec![
+            BINARY_NAME,
+            "--otlp-endpoint",
+            "http://localhost:4317",
+            "start",
+        ];
+        let expected_args = Arguments {
+            testnet: false,
+            json: false,
+            disable_timestamp: false,
+            otlp_endpoint: Some(Url::parse("http://localhost:4317").unwrap()),
+            config_path: default_mainnet_conf_path,
+            env_config: mainnet_env_config,
+            cmd: Command::Start {
+                resume_only: false,
+                regenerate_identity: false,
+                rotate_maker_key: false,
+            },
         };
         let args = parse_args(raw_ars).unwrap();
         assert_eq!(expected_args, args);
@@ -385,6 +809,7 @@ mod tests {
             testnet: false,
             json: false,
             disable_timestamp: false,
+            otlp_endpoint: None,
             config_path: default_mainnet_conf_path,
             env_config: mainnet_env_config,
             cmd: Command::History,
@@ -403,6 +828,7 @@ mod tests {
             testnet: false,
             json: false,
             disable_timestamp: false,
+            otlp_endpoint: None,
             config_path: default_mainnet_conf_path,
             env_config: mainnet_env_config,
             cmd: Command::Balance,
@@ -411,6 +837,25 @@ mod tests {
         assert_eq!(expected_args, args);
     }
 
+    #[test]
+    fn ensure_init_command_mapping_mainnet() {
+        let default_mainnet_conf_path = env::Mainnet::getConfigFileDefaults().unwrap().config_path;
+        let mainnet_env_config = env::Mainnet::get_config();
+
+        let raw_ars = vec![BINARY_NAME, "init", "--restore"];
+        let expected_args = Arguments {
+            testnet: false,
+            json: false,
+            disable_timestamp: false,
+            otlp_endpoint: None,
+            config_path: default_mainnet_conf_path,
+            env_config: mainnet_env_config,
+            cmd: Command::Init { restore: true },
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+    }
+
     #[test]
     fn ensure_withdraw_command_mapping_mainnet() {
         let default_mainnet_conf_path = env::Mainnet::getConfigFileDefaults().unwrap().config_path;
@@ -425,6 +870,7 @@ mod tests {
             testnet: false,
             json: false,
             disable_timestamp: false,
+            otlp_endpoint: None,
             config_path: default_mainnet_conf_path,
             env_config: mainnet_env_config,
             cmd: Command::WithdrawBtc {
@@ -452,6 +898,7 @@ mod tests {
             testnet: false,
             json: false,
             disable_timestamp: false,
+            otlp_endpoint: None,
             config_path: default_mainnet_conf_path,
             env_config: mainnet_env_config,
             cmd: Command::Cancel {
@@ -478,6 +925,7 @@ mod tests {
             testnet: false,
             json: false,
             disable_timestamp: false,
+            otlp_endpoint: None,
             config_path: default_mainnet_conf_path,
             env_config: mainnet_env_config,
             cmd: Command::Refund {
@@ -504,6 +952,7 @@ mod tests {
             testnet: false,
             json: false,
             disable_timestamp: false,
+            otlp_endpoint: None,
             config_path: default_mainnet_conf_path,
             env_config: mainnet_env_config,
             cmd: Command::Punish {
@@ -530,6 +979,7 @@ mod tests {
             testnet: false,
             json: false,
             disable_timestamp: false,
+            otlp_endpoint: None,
             config_path: default_mainnet_conf_path,
             env_config: mainnet_env_config,
             cmd: Command::SafelyAbort {
@@ -540,6 +990,36 @@ mod tests {
         assert_eq!(expected_args, args);
     }
 
+    #[test]
+    fn ensure_presigned_command_mapping_mainnet() {
+        let default_mainnet_conf_path = env::Mainnet::getConfigFileDefaults().unwrap().config_path;
+        let mainnet_env_config = env::Mainnet::get_config();
+
+        let raw_ars = vec![
+            BINARY_NAME,
+            "manual-recovery",
+            "presigned",
+            "--swap-id",
+            SWAP_ID,
+            "--publish",
+            "punish",
+        ];
+        let expected_args = Arguments {
+            testnet: false,
+            json: false,
+            disable_timestamp: false,
+            otlp_endpoint: None,
+            config_path: default_mainnet_conf_path,
+            env_config: mainnet_env_config,
+            cmd: Command::Presigned {
+                swap_id: Uuid::parse_str(SWAP_ID).unwrap(),
+                publish: Some(presigned::Kind::Punish),
+            },
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+    }
+
     #[test]
     fn ensure_start_command_mapping_for_testnet() {
         let default_testnet_conf_path = env::Testnet::getConfigFileDefaults().unwrap().config_path;
@@ -550,9 +1030,14 @@ mod tests {
             testnet: true,
             json: false,
             disable_timestamp: false,
+            otlp_endpoint: None,
             config_path: default_testnet_conf_path,
             env_config: testnet_env_config,
-            cmd: Command::Start { resume_only: false },
+            cmd: Command::Start {
+                resume_only: false,
+                regenerate_identity: false,
+                rotate_maker_key: false,
+            },
         };
         let args = parse_args(raw_ars).unwrap();
         assert_eq!(expected_args, args);
@@ -568,6 +1053,7 @@ mod tests {
             testnet: true,
             json: false,
             disable_timestamp: false,
+            otlp_endpoint: None,
             config_path: default_testnet_conf_path,
             env_config: testnet_env_config,
             cmd: Command::History,
@@ -586,6 +1072,7 @@ mod tests {
             testnet: true,
             json: false,
             disable_timestamp: false,
+            otlp_endpoint: None,
             config_path: default_testnet_conf_path,
             env_config: testnet_env_config,
             cmd: Command::Balance,
@@ -610,6 +1097,7 @@ mod tests {
             testnet: true,
             json: false,
             disable_timestamp: false,
+            otlp_endpoint: None,
             config_path: default_testnet_conf_path,
             env_config: testnet_env_config,
             cmd: Command::WithdrawBtc {
@@ -637,6 +1125,7 @@ mod tests {
             testnet: true,
             json: false,
             disable_timestamp: false,
+            otlp_endpoint: None,
             config_path: default_testnet_conf_path,
             env_config: testnet_env_config,
             cmd: Command::Cancel {
@@ -664,6 +1153,7 @@ mod tests {
             testnet: true,
             json: false,
             disable_timestamp: false,
+            otlp_endpoint: None,
             config_path: default_testnet_conf_path,
             env_config: testnet_env_config,
             cmd: Command::Refund {
@@ -691,6 +1181,7 @@ mod tests {
             testnet: true,
             json: false,
             disable_timestamp: false,
+            otlp_endpoint: None,
             config_path: default_testnet_conf_path,
             env_config: testnet_env_config,
             cmd: Command::Punish {
@@ -718,6 +1209,7 @@ mod tests {
             testnet: true,
             json: false,
             disable_timestamp: false,
+            otlp_endpoint: None,
             config_path: default_testnet_conf_path,
             env_config: testnet_env_config,
             cmd: Command::SafelyAbort {
@@ -728,6 +1220,171 @@ mod tests {
         assert_eq!(expected_args, args);
     }
 
+    #[test]
+    fn ensure_admin_pause_command_mapping_mainnet() {
+        let default_mainnet_conf_path = env::Mainnet::getConfigFileDefaults().unwrap().config_path;
+        let mainnet_env_config = env::Mainnet::get_config();
+
+        let raw_ars = vec![BINARY_NAME, "admin", "pause"];
+        let expected_args = Arguments {
+            testnet: false,
+            json: false,
+            disable_timestamp: false,
+            otlp_endpoint: None,
+            config_path: default_mainnet_conf_path,
+            env_config: mainnet_env_config,
+            cmd: Command::Admin {
+                request: admin::Request::Pause,
+            },
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+    }
+
+    #[test]
+    fn ensure_admin_set_limits_command_mapping_testnet() {
+        let default_testnet_conf_path = env::Testnet::getConfigFileDefaults().unwrap().config_path;
+        let testnet_env_config = env::Testnet::get_config();
+
+        let raw_ars = vec![
+            BINARY_NAME,
+            "--testnet",
+            "admin",
+            "set-limits",
+            "--min-buy",
+            "0.002 BTC",
+            "--max-buy",
+            "0.02 BTC",
+        ];
+        let expected_args = Arguments {
+            testnet: true,
+            json: false,
+            disable_timestamp: false,
+            otlp_endpoint: None,
+            config_path: default_testnet_conf_path,
+            env_config: testnet_env_config,
+            cmd: Command::Admin {
+                request: admin::Request::SetLimits {
+                    min_buy: Amount::from_btc(0.002).unwrap(),
+                    max_buy: Amount::from_btc(0.02).unwrap(),
+                },
+            },
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+    }
+
+    #[test]
+    fn ensure_admin_set_log_level_command_mapping_testnet() {
+        let default_testnet_conf_path = env::Testnet::getConfigFileDefaults().unwrap().config_path;
+        let testnet_env_config = env::Testnet::get_config();
+
+        let raw_ars = vec![
+            BINARY_NAME,
+            "--testnet",
+            "admin",
+            "set-log-level",
+            "--subsystem",
+            "db",
+            "--level",
+            "trace",
+        ];
+        let expected_args = Arguments {
+            testnet: true,
+            json: false,
+            disable_timestamp: false,
+            otlp_endpoint: None,
+            config_path: default_testnet_conf_path,
+            env_config: testnet_env_config,
+            cmd: Command::Admin {
+                request: admin::Request::SetLogLevel {
+                    subsystem: Some(Subsystem::Db),
+                    level: LevelFilter::TRACE,
+                },
+            },
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+    }
+
+    #[test]
+    fn ensure_db_prune_command_mapping_mainnet() {
+        let default_mainnet_conf_path = env::Mainnet::getConfigFileDefaults().unwrap().config_path;
+        let mainnet_env_config = env::Mainnet::get_config();
+
+        let raw_ars = vec![BINARY_NAME, "db", "prune", "--retention-days", "7"];
+        let expected_args = Arguments {
+            testnet: false,
+            json: false,
+            disable_timestamp: false,
+            otlp_endpoint: None,
+            config_path: default_mainnet_conf_path,
+            env_config: mainnet_env_config,
+            cmd: Command::PruneDatabase {
+                retention_days: Some(7),
+            },
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+    }
+
+    #[test]
+    fn ensure_db_compact_command_mapping_testnet() {
+        let default_testnet_conf_path = env::Testnet::getConfigFileDefaults().unwrap().config_path;
+        let testnet_env_config = env::Testnet::get_config();
+
+        let raw_ars = vec![BINARY_NAME, "--testnet", "db", "compact"];
+        let expected_args = Arguments {
+            testnet: true,
+            json: false,
+            disable_timestamp: false,
+            otlp_endpoint: None,
+            config_path: default_testnet_conf_path,
+            env_config: testnet_env_config,
+            cmd: Command::CompactDatabase,
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+    }
+
+    #[test]
+    fn ensure_report_quotes_command_mapping_mainnet() {
+        let default_mainnet_conf_path = env::Mainnet::getConfigFileDefaults().unwrap().config_path;
+        let mainnet_env_config = env::Mainnet::get_config();
+
+        let raw_ars = vec![BINARY_NAME, "report", "quotes"];
+        let expected_args = Arguments {
+            testnet: false,
+            json: false,
+            disable_timestamp: false,
+            otlp_endpoint: None,
+            config_path: default_mainnet_conf_path,
+            env_config: mainnet_env_config,
+            cmd: Command::ReportQuotes,
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+    }
+
+    #[test]
+    fn ensure_version_command_mapping_mainnet() {
+        let default_mainnet_conf_path = env::Mainnet::getConfigFileDefaults().unwrap().config_path;
+        let mainnet_env_config = env::Mainnet::get_config();
+
+        let raw_ars = vec![BINARY_NAME, "version"];
+        let expected_args = Arguments {
+            testnet: false,
+            json: false,
+            disable_timestamp: false,
+            otlp_endpoint: None,
+            config_path: default_mainnet_conf_path,
+            env_config: mainnet_env_config,
+            cmd: Command::Version,
+        };
+        let args = parse_args(raw_ars).unwrap();
+        assert_eq!(expected_args, args);
+    }
+
     #[test]
     fn ensure_disable_timestamp_mapping() {
         let default_mainnet_conf_path = env::Mainnet::getConfigFileDefaults().unwrap().config_path;
@@ -738,9 +1395,14 @@ mod tests {
             testnet: false,
             json: false,
             disable_timestamp: true,
+            otlp_endpoint: None,
             config_path: default_mainnet_conf_path,
             env_config: mainnet_env_config,
-            cmd: Command::Start { resume_only: false },
+            cmd: Command::Start {
+                resume_only: false,
+                regenerate_identity: false,
+                rotate_maker_key: false,
+            },
         };
         let args = parse_args(raw_ars).unwrap();
         assert_eq!(expected_args, args);
@@ -1,5 +1,16 @@
+use crate::asb::alert::Severity;
+use crate::asb::backend_health;
+use crate::asb::load;
+use crate::asb::price_feeds;
+use crate::asb::rebalance;
+use crate::asb::tracing::{Levels, Subsystem};
+use crate::asb::DEFAULT_IDENTITY;
+use crate::bitcoin;
+use crate::data::{system_config_dir, system_data_dir};
 use crate::env::{Mainnet, Testnet};
-use crate::fs::{ensure_directory_exists, system_config_dir, system_data_dir};
+use crate::fs::ensure_directory_exists;
+use crate::monero;
+use crate::network::address_family::{self, AddressFamily};
 use crate::tor::{DEFAULT_CONTROL_PORT, DEFAULT_SOCKS5_PORT};
 use anyhow::{bail, Context, Result};
 use config::ConfigError;
@@ -9,10 +20,16 @@ use libp2p::core::Multiaddr;
 use rust_decimal::prelude::FromPrimitive;
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DisplayFromStr};
+use std::collections::{BTreeMap, HashSet};
+use std::convert::TryFrom;
 use std::ffi::OsStr;
 use std::fs;
+use std::net::SocketAddr;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::time::Duration;
+use tracing_subscriber::filter::LevelFilter;
 use url::Url;
 
 pub trait GetDefaults {
@@ -22,8 +39,7 @@ pub trait GetDefaults {
 pub struct Defaults {
     pub config_path: PathBuf,
     data_dir: PathBuf,
-    listen_address_tcp: Multiaddr,
-    listen_address_ws: Multiaddr,
+    listen_addresses: Vec<Multiaddr>,
     electrum_rpc_url: Url,
     monero_wallet_rpc_url: Url,
     price_ticker_ws_url: Url,
@@ -37,8 +53,7 @@ impl GetDefaults for Testnet {
                 .join("testnet")
                 .join("config.toml"),
             data_dir: default_asb_data_dir()?.join("testnet"),
-            listen_address_tcp: Multiaddr::from_str("/ip4/0.0.0.0/tcp/9939")?,
-            listen_address_ws: Multiaddr::from_str("/ip4/0.0.0.0/tcp/9940/ws")?,
+            listen_addresses: default_listen_addresses()?,
             electrum_rpc_url: Url::parse("ssl://electrum.blockstream.info:60002")?,
             monero_wallet_rpc_url: Url::parse("http://127.0.0.1:38083/json_rpc")?,
             price_ticker_ws_url: Url::parse("wss://ws.kraken.com")?,
@@ -56,8 +71,7 @@ impl GetDefaults for Mainnet {
                 .join("mainnet")
                 .join("config.toml"),
             data_dir: default_asb_data_dir()?.join("mainnet"),
-            listen_address_tcp: Multiaddr::from_str("/ip4/0.0.0.0/tcp/9939")?,
-            listen_address_ws: Multiaddr::from_str("/ip4/0.0.0.0/tcp/9940/ws")?,
+            listen_addresses: default_listen_addresses()?,
             electrum_rpc_url: Url::parse("ssl://blockstream.info:700")?,
             monero_wallet_rpc_url: Url::parse("http://127.0.0.1:18083/json_rpc")?,
             price_ticker_ws_url: Url::parse("wss://ws.kraken.com")?,
@@ -68,6 +82,16 @@ impl GetDefaults for Mainnet {
     }
 }
 
+/// Listens on all interfaces, using IPv4 and IPv6.
+fn default_listen_addresses() -> Result<Vec<Multiaddr>> {
+    Ok(vec![
+        Multiaddr::from_str("/ip4/0.0.0.0/tcp/9939")?,
+        Multiaddr::from_str("/ip6/::/tcp/9939")?,
+        Multiaddr::from_str("/ip4/0.0.0.0/tcp/9940/ws")?,
+        Multiaddr::from_str("/ip6/::/tcp/9940/ws")?,
+    ])
+}
+
 fn default_asb_config_dir() -> Result<PathBuf> {
     system_config_dir()
         .map(|dir| Path::join(&dir, "asb"))
@@ -93,6 +117,28 @@ pub struct Config {
     pub monero: Monero,
     pub tor: TorConf,
     pub maker: Maker,
+    #[serde(default)]
+    pub logging: Logging,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub identities: Vec<Identity>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub policy: Option<PolicyConf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub webhook: Option<WebhookConf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alerts: Option<Alerts>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub approvals: Option<ApprovalsConf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub health: Option<HealthConf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rebalance: Option<RebalanceConf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backend_health: Option<BackendHealthConf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub load: Option<LoadConf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub watchtower: Option<WatchtowerConf>,
 }
 
 impl Config {
@@ -106,12 +152,64 @@ impl Config {
         config.merge(config::File::from(config_file))?;
         config.try_into()
     }
+
+    /// Fails if two identities share a name or a listen address, or if an
+    /// identity does not listen on an address of the selected address family.
+    pub fn validate_identities(&self) -> Result<()> {
+        let mut names = HashSet::new();
+        let mut listen = self.network.listen.iter().collect::<HashSet<_>>();
+
+        if let Some(family) = self.network.address_family {
+            if self.network.listen().is_empty() {
+                bail!(
+                    "The [network] section does not listen on any {} address",
+                    family
+                )
+            }
+        }
+
+        for identity in &self.identities {
+            if identity.name.is_empty() || identity.name == DEFAULT_IDENTITY {
+                bail!(
+                    "Identity name must not be empty or `{}`, got `{}`",
+                    DEFAULT_IDENTITY,
+                    identity.name
+                )
+            }
+            if !names.insert(&identity.name) {
+                bail!("Identity `{}` is configured more than once", identity.name)
+            }
+            if address_family::select(identity.listen.clone(), self.network.address_family)
+                .is_empty()
+            {
+                bail!(
+                    "Identity `{}` does not listen on any address",
+                    identity.name
+                )
+            }
+            for address in &identity.listen {
+                if !listen.insert(address) {
+                    bail!(
+                        "Listen address {} of identity `{}` is already in use by another identity",
+                        address,
+                        identity.name
+                    )
+                }
+            }
+        }
+
+        Ok(())
+    }
 }
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 #[serde(deny_unknown_fields)]
 pub struct Data {
     pub dir: PathBuf,
+    /// When set, finished swaps are reduced to their final state once they
+    /// are older than this many days.
+    #[serde(default)]
+    pub retention_days: Option<u32>,
 }
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
@@ -122,16 +220,93 @@ pub struct Network {
     pub rendezvous_point: Option<Multiaddr>,
     #[serde(default)]
     pub external_addresses: Vec<Multiaddr>,
+    /// Only listen on and advertise addresses of this family, applies to all
+    /// identities. Addresses of both families are used if not set.
+    #[serde(default)]
+    pub address_family: Option<AddressFamily>,
+    /// Forward the ports of the IPv4 TCP listen addresses on the router with
+    /// UPnP, see [`crate::asb::port_mapping`].
+    #[serde(default)]
+    pub port_mapping: bool,
+    /// Circuit relays to listen through and advertise, for hosts that cannot
+    /// accept inbound connections. Each address must end with the peer ID of
+    /// the relay. Applies to all identities.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub relays: Vec<Multiaddr>,
+}
+
+impl Network {
+    pub fn listen(&self) -> Vec<Multiaddr> {
+        address_family::select(self.listen.clone(), self.address_family)
+    }
+
+    pub fn external_addresses(&self) -> Vec<Multiaddr> {
+        address_family::select(self.external_addresses.clone(), self.address_family)
+    }
 }
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 #[serde(deny_unknown_fields)]
 pub struct Bitcoin {
     pub electrum_rpc_url: Url,
+    /// Take over if the server above is unavailable or lags behind.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub electrum_rpc_fallback_urls: Vec<Url>,
     pub target_block: usize,
     pub finality_confirmations: Option<u32>,
     #[serde(with = "crate::bitcoin::network")]
     pub network: bitcoin::Network,
+    /// Replaces the default block explorer in the swap history.
+    #[serde(default)]
+    pub explorer_url: Option<Url>,
+    /// Bump redeem transactions that do not confirm in time with a child
+    /// paying for them.
+    #[serde(default)]
+    pub cpfp: bool,
+    /// Use this bitcoind node instead of the Electrum servers above.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bitcoind: Option<Bitcoind>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Bitcoind {
+    /// The JSON-RPC interface of the node, only plain HTTP is supported.
+    pub rpc_url: Url,
+    /// The `.cookie` file in the data directory of the node, instead of
+    /// `rpc_user` and `rpc_password`.
+    #[serde(default)]
+    pub cookie_file: Option<PathBuf>,
+    #[serde(default)]
+    pub rpc_user: Option<String>,
+    #[serde(default)]
+    pub rpc_password: Option<String>,
+    /// The watch-only wallet of the node the ASB imports its scripts into.
+    #[serde(default = "default_bitcoind_wallet_name")]
+    pub wallet_name: String,
+}
+
+fn default_bitcoind_wallet_name() -> String {
+    "asb-watch-only".to_owned()
+}
+
+impl Bitcoind {
+    pub fn config(&self) -> Result<bitcoin::bitcoind::Config> {
+        let auth = match (&self.cookie_file, &self.rpc_user, &self.rpc_password) {
+            (Some(cookie_file), None, None) => bitcoin::bitcoind::Auth::Cookie(cookie_file.clone()),
+            (None, Some(user), Some(password)) => bitcoin::bitcoind::Auth::UserPass {
+                user: user.clone(),
+                password: password.clone(),
+            },
+            _ => bail!("Set either cookie_file or rpc_user and rpc_password in [bitcoin.bitcoind]"),
+        };
+
+        Ok(bitcoin::bitcoind::Config {
+            url: self.rpc_url.clone(),
+            auth,
+            wallet_name: self.wallet_name.clone(),
+        })
+    }
 }
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
@@ -141,6 +316,9 @@ pub struct Monero {
     pub finality_confirmations: Option<u64>,
     #[serde(with = "crate::monero::network")]
     pub network: monero::Network,
+    /// Replaces the default block explorer in the swap history.
+    #[serde(default)]
+    pub explorer_url: Option<Url>,
 }
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
@@ -153,12 +331,279 @@ pub struct TorConf {
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 #[serde(deny_unknown_fields)]
 pub struct Maker {
-    #[serde(with = "::bitcoin::util::amount::serde::as_btc")]
+    #[serde(with = "crate::bitcoin::as_btc")]
     pub min_buy_btc: bitcoin::Amount,
-    #[serde(with = "::bitcoin::util::amount::serde::as_btc")]
+    #[serde(with = "crate::bitcoin::as_btc")]
     pub max_buy_btc: bitcoin::Amount,
     pub ask_spread: Decimal,
     pub price_ticker_ws_url: Url,
+    /// Further price tickers to cross-check the price with, see
+    /// [`crate::asb::price_feeds`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub additional_price_ticker_ws_urls: Vec<Url>,
+    #[serde(default = "default_max_price_deviation")]
+    pub max_price_deviation: Decimal,
+    /// When set, tickers that did not update their ask for this many seconds
+    /// are left out of the price.
+    #[serde(default)]
+    pub max_price_age_secs: Option<u64>,
+    /// When set, swaps still running this many hours after they were set up
+    /// stop waiting for the taker, see [`crate::protocol::deadline`].
+    #[serde(default)]
+    pub max_swap_duration_hours: Option<u64>,
+}
+
+fn default_max_price_deviation() -> Decimal {
+    price_feeds::Tolerance::default().max_deviation
+}
+
+impl Maker {
+    /// All price tickers, the first one is [`Maker::price_ticker_ws_url`].
+    pub fn price_ticker_ws_urls(&self) -> Vec<Url> {
+        std::iter::once(self.price_ticker_ws_url.clone())
+            .chain(self.additional_price_ticker_ws_urls.iter().cloned())
+            .collect()
+    }
+
+    pub fn max_price_age(&self) -> Option<Duration> {
+        self.max_price_age_secs.map(Duration::from_secs)
+    }
+}
+
+/// An external service that decides about every quote and swap request of
+/// all identities, see [`crate::asb::policy::Webhook`].
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct PolicyConf {
+    pub webhook_url: Url,
+}
+
+/// Swaps that wait for the operator before the Monero is locked, see
+/// [`crate::asb::Approvals`].
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ApprovalsConf {
+    #[serde(with = "crate::bitcoin::as_btc")]
+    pub min_btc: bitcoin::Amount,
+}
+
+/// Selling Bitcoin for Monero once the balances cross the thresholds, see
+/// [`crate::asb::rebalance`].
+#[serde_as]
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct RebalanceConf {
+    #[serde(default)]
+    pub mode: rebalance::Mode,
+    #[serde_as(as = "DisplayFromStr")]
+    pub min_xmr: monero::Amount,
+    #[serde_as(as = "DisplayFromStr")]
+    pub target_xmr: monero::Amount,
+    #[serde(
+        default,
+        with = "crate::bitcoin::as_btc::opt",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub max_btc: Option<bitcoin::Amount>,
+    #[serde(default, with = "crate::bitcoin::as_btc")]
+    pub reserve_btc: bitcoin::Amount,
+    #[serde(default = "default_rebalance_cooldown_hours")]
+    pub cooldown_hours: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kraken: Option<KrakenRebalancerConf>,
+}
+
+fn default_rebalance_cooldown_hours() -> u64 {
+    6
+}
+
+impl RebalanceConf {
+    pub fn thresholds(&self) -> rebalance::Thresholds {
+        rebalance::Thresholds {
+            min_xmr: self.min_xmr,
+            target_xmr: self.target_xmr,
+            max_btc: self.max_btc,
+            reserve_btc: self.reserve_btc,
+        }
+    }
+}
+
+/// The credentials of the Kraken rebalancer, which requires the
+/// `kraken-rebalancer` feature.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct KrakenRebalancerConf {
+    pub api_key: String,
+    pub api_secret: String,
+    pub xmr_withdrawal_key: String,
+}
+
+/// The watchtower the presigned cancel and punish transactions of every swap
+/// are registered with, see [`crate::network::watchtower`].
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct WatchtowerConf {
+    /// Has to include the peer id of the watchtower.
+    pub address: Multiaddr,
+}
+
+/// The HTTP endpoints reporting liveness and readiness to a service manager,
+/// see [`crate::asb::service`].
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct HealthConf {
+    pub listen: SocketAddr,
+}
+
+/// How fresh the backends have to be for the ASB to keep quoting, see
+/// [`crate::asb::backend_health`].
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct BackendHealthConf {
+    #[serde(default = "default_max_rate_age_secs")]
+    pub max_rate_age_secs: u64,
+    #[serde(default = "default_max_monero_stall_mins")]
+    pub max_monero_stall_mins: u64,
+}
+
+fn default_max_rate_age_secs() -> u64 {
+    backend_health::Thresholds::default().max_rate_age.as_secs()
+}
+
+fn default_max_monero_stall_mins() -> u64 {
+    backend_health::Thresholds::default()
+        .max_monero_stall
+        .as_secs()
+        / 60
+}
+
+impl BackendHealthConf {
+    pub fn thresholds(&self) -> backend_health::Thresholds {
+        backend_health::Thresholds {
+            max_rate_age: Duration::from_secs(self.max_rate_age_secs),
+            max_monero_stall: Duration::from_secs(self.max_monero_stall_mins * 60),
+        }
+    }
+}
+
+/// When the ASB counts as saturated and sheds new quotes and swaps, see
+/// [`crate::asb::load`].
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct LoadConf {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_active_swaps: Option<usize>,
+    #[serde(default = "default_max_lag_ms")]
+    pub max_lag_ms: u64,
+}
+
+fn default_max_lag_ms() -> u64 {
+    u64::try_from(load::Limits::default().max_lag.as_millis()).unwrap_or(u64::MAX)
+}
+
+impl LoadConf {
+    pub fn limits(&self) -> load::Limits {
+        load::Limits {
+            max_active_swaps: self.max_active_swaps,
+            max_lag: Duration::from_millis(self.max_lag_ms),
+        }
+    }
+}
+
+/// Receiver of the notifications about the progress of swaps, see
+/// [`crate::asb::notifications`].
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct WebhookConf {
+    pub url: Url,
+    /// Key of the HMAC-SHA256 signature of every notification.
+    pub secret: String,
+}
+
+/// Where to send alerts about failures that need the attention of the
+/// operator, see [`crate::asb::alert`].
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Alerts {
+    #[serde(default)]
+    pub min_severity: Severity,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub telegram: Option<TelegramConf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub smtp: Option<SmtpConf>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct TelegramConf {
+    pub bot_token: String,
+    pub chat_id: String,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct SmtpConf {
+    pub host: String,
+    #[serde(default = "default_smtp_port")]
+    pub port: u16,
+    pub username: String,
+    pub password: String,
+    pub from: String,
+    pub to: Vec<String>,
+}
+
+fn default_smtp_port() -> u16 {
+    465
+}
+
+/// An additional maker identity served by the same process, configured in an
+/// `[[identities]]` section.
+///
+/// Every identity has its own peer id, listen addresses and trading parameters.
+/// The wallets, the database and the price ticker are shared with the identity
+/// configured in `[network]` and `[maker]`.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Identity {
+    pub name: String,
+    pub listen: Vec<Multiaddr>,
+    #[serde(default)]
+    pub external_addresses: Vec<Multiaddr>,
+    #[serde(with = "crate::bitcoin::as_btc")]
+    pub min_buy_btc: bitcoin::Amount,
+    #[serde(with = "crate::bitcoin::as_btc")]
+    pub max_buy_btc: bitcoin::Amount,
+    pub ask_spread: Decimal,
+}
+
+#[serde_as]
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Logging {
+    #[serde_as(as = "DisplayFromStr")]
+    pub level: LevelFilter,
+    /// Subsystems logged at a different level, e.g. `network = "trace"`.
+    #[serde_as(as = "BTreeMap<DisplayFromStr, DisplayFromStr>")]
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub subsystems: BTreeMap<Subsystem, LevelFilter>,
+}
+
+impl Logging {
+    pub fn levels(&self) -> Levels {
+        Levels {
+            level: self.level,
+            subsystems: self.subsystems.clone(),
+        }
+    }
+}
+
+impl Default for Logging {
+    fn default() -> Self {
+        Self {
+            level: LevelFilter::DEBUG,
+            subsystems: BTreeMap::new(),
+        }
+    }
 }
 
 impl Default for TorConf {
@@ -241,7 +686,14 @@ pub fn query_user_for_initial_config(testnet: bool) -> Result<Config> {
 
     let listen_addresses = Input::with_theme(&ColorfulTheme::default())
         .with_prompt("Enter multiaddresses (comma separated) on which asb should list for peer-to-peer communications or hit return to use default")
-        .default( format!("{},{}", defaults.listen_address_tcp, defaults.listen_address_ws))
+        .default(
+            defaults
+                .listen_addresses
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(","),
+        )
         .interact_text()?;
     let listen_addresses = listen_addresses
         .split(',')
@@ -297,7 +749,10 @@ pub fn query_user_for_initial_config(testnet: bool) -> Result<Config> {
     println!();
 
     Ok(Config {
-        data: Data { dir: data_dir },
+        data: Data {
+            dir: data_dir,
+            retention_days: None,
+        },
         network: Network {
             listen: listen_addresses,
             rendezvous_point: if rendezvous_point.is_empty() {
@@ -306,17 +761,25 @@ pub fn query_user_for_initial_config(testnet: bool) -> Result<Config> {
                 Some(rendezvous_point)
             },
             external_addresses: vec![],
+            address_family: None,
+            port_mapping: false,
+            relays: vec![],
         },
         bitcoin: Bitcoin {
             electrum_rpc_url,
+            electrum_rpc_fallback_urls: vec![],
             target_block,
             finality_confirmations: None,
             network: bitcoin_network,
+            explorer_url: None,
+            cpfp: false,
+            bitcoind: None,
         },
         monero: Monero {
             wallet_rpc_url: monero_wallet_rpc_url,
             finality_confirmations: None,
             network: monero_network,
+            explorer_url: None,
         },
         tor: TorConf {
             control_port: tor_control_port,
@@ -327,7 +790,22 @@ pub fn query_user_for_initial_config(testnet: bool) -> Result<Config> {
             max_buy_btc: max_buy,
             ask_spread,
             price_ticker_ws_url: defaults.price_ticker_ws_url,
+            additional_price_ticker_ws_urls: vec![],
+            max_price_deviation: default_max_price_deviation(),
+            max_price_age_secs: None,
+            max_swap_duration_hours: None,
         },
+        logging: Logging::default(),
+        identities: vec![],
+        policy: None,
+        webhook: None,
+        alerts: None,
+        approvals: None,
+        health: None,
+        rebalance: None,
+        backend_health: None,
+        load: None,
+        watchtower: None,
     })
 }
 
@@ -346,23 +824,32 @@ mod tests {
         let expected = Config {
             data: Data {
                 dir: Default::default(),
+                retention_days: None,
             },
             bitcoin: Bitcoin {
                 electrum_rpc_url: defaults.electrum_rpc_url,
+                electrum_rpc_fallback_urls: vec![],
                 target_block: defaults.bitcoin_confirmation_target,
                 finality_confirmations: None,
                 network: bitcoin::Network::Testnet,
+                explorer_url: None,
+                cpfp: false,
+                bitcoind: None,
             },
             network: Network {
-                listen: vec![defaults.listen_address_tcp, defaults.listen_address_ws],
+                listen: defaults.listen_addresses,
                 rendezvous_point: None,
                 external_addresses: vec![],
+                address_family: None,
+                port_mapping: false,
+                relays: vec![],
             },
 
             monero: Monero {
                 wallet_rpc_url: defaults.monero_wallet_rpc_url,
                 finality_confirmations: None,
                 network: monero::Network::Stagenet,
+                explorer_url: None,
             },
             tor: Default::default(),
             maker: Maker {
@@ -370,7 +857,22 @@ mod tests {
                 max_buy_btc: bitcoin::Amount::from_btc(DEFAULT_MAX_BUY_AMOUNT).unwrap(),
                 ask_spread: Decimal::from_f64(DEFAULT_SPREAD).unwrap(),
                 price_ticker_ws_url: defaults.price_ticker_ws_url,
+                additional_price_ticker_ws_urls: vec![],
+                max_price_deviation: default_max_price_deviation(),
+                max_price_age_secs: None,
+                max_swap_duration_hours: None,
             },
+            logging: Default::default(),
+            identities: vec![],
+            policy: None,
+            webhook: None,
+            alerts: None,
+            approvals: None,
+            health: None,
+            rebalance: None,
+            backend_health: None,
+            load: None,
+            watchtower: None,
         };
 
         initial_setup(config_path.clone(), expected.clone()).unwrap();
@@ -389,23 +891,32 @@ mod tests {
         let expected = Config {
             data: Data {
                 dir: Default::default(),
+                retention_days: None,
             },
             bitcoin: Bitcoin {
                 electrum_rpc_url: defaults.electrum_rpc_url,
+                electrum_rpc_fallback_urls: vec![],
                 target_block: defaults.bitcoin_confirmation_target,
                 finality_confirmations: None,
                 network: bitcoin::Network::Bitcoin,
+                explorer_url: None,
+                cpfp: false,
+                bitcoind: None,
             },
             network: Network {
-                listen: vec![defaults.listen_address_tcp, defaults.listen_address_ws],
+                listen: defaults.listen_addresses,
                 rendezvous_point: None,
                 external_addresses: vec![],
+                address_family: None,
+                port_mapping: false,
+                relays: vec![],
             },
 
             monero: Monero {
                 wallet_rpc_url: defaults.monero_wallet_rpc_url,
                 finality_confirmations: None,
                 network: monero::Network::Mainnet,
+                explorer_url: None,
             },
             tor: Default::default(),
             maker: Maker {
@@ -413,7 +924,22 @@ mod tests {
                 max_buy_btc: bitcoin::Amount::from_btc(DEFAULT_MAX_BUY_AMOUNT).unwrap(),
                 ask_spread: Decimal::from_f64(DEFAULT_SPREAD).unwrap(),
                 price_ticker_ws_url: defaults.price_ticker_ws_url,
+                additional_price_ticker_ws_urls: vec![],
+                max_price_deviation: default_max_price_deviation(),
+                max_price_age_secs: None,
+                max_swap_duration_hours: None,
             },
+            logging: Default::default(),
+            identities: vec![],
+            policy: None,
+            webhook: None,
+            alerts: None,
+            approvals: None,
+            health: None,
+            rebalance: None,
+            backend_health: None,
+            load: None,
+            watchtower: None,
         };
 
         initial_setup(config_path.clone(), expected.clone()).unwrap();
@@ -421,4 +947,345 @@ mod tests {
 
         assert_eq!(expected, actual);
     }
+
+    fn config_with_identities(identities: &str) -> Config {
+        toml::from_str(&format!(
+            r#"
+            [data]
+            dir = "/var/lib/asb"
+
+            [network]
+            listen = ["/ip4/0.0.0.0/tcp/9939"]
+
+            [bitcoin]
+            electrum_rpc_url = "ssl://electrum.blockstream.info:60002"
+            target_block = 1
+            network = "Testnet"
+
+            [monero]
+            wallet_rpc_url = "http://127.0.0.1:38083/json_rpc"
+            network = "Stagenet"
+
+            [tor]
+            control_port = 9051
+            socks5_port = 9050
+
+            [maker]
+            min_buy_btc = 0.002
+            max_buy_btc = 0.02
+            ask_spread = 0.02
+            price_ticker_ws_url = "wss://ws.kraken.com"
+            {}
+            "#,
+            identities
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn given_identities_then_config_roundtrips() {
+        let config = config_with_identities(
+            r#"
+            [[identities]]
+            name = "wide-spread"
+            listen = ["/ip4/0.0.0.0/tcp/9941"]
+            min_buy_btc = 0.01
+            max_buy_btc = 0.1
+            ask_spread = 0.05
+            "#,
+        );
+
+        let roundtripped = toml::from_str::<Config>(&toml::to_string(&config).unwrap()).unwrap();
+
+        assert_eq!(roundtripped, config);
+        assert_eq!(config.identities[0].name, "wide-spread");
+        assert!(config.validate_identities().is_ok());
+    }
+
+    #[test]
+    fn given_policy_webhook_then_config_roundtrips() {
+        let config = config_with_identities(
+            r#"
+            [policy]
+            webhook_url = "http://127.0.0.1:8080/policy"
+            "#,
+        );
+
+        let roundtripped = toml::from_str::<Config>(&toml::to_string(&config).unwrap()).unwrap();
+
+        assert_eq!(roundtripped, config);
+        assert_eq!(
+            config.policy.unwrap().webhook_url.as_str(),
+            "http://127.0.0.1:8080/policy"
+        );
+    }
+
+    #[test]
+    fn given_webhook_then_config_roundtrips() {
+        let config = config_with_identities(
+            r#"
+            [webhook]
+            url = "https://example.com/swaps"
+            secret = "hunter2"
+            "#,
+        );
+
+        let roundtripped = toml::from_str::<Config>(&toml::to_string(&config).unwrap()).unwrap();
+
+        assert_eq!(roundtripped, config);
+        assert_eq!(config.webhook.unwrap().secret, "hunter2");
+    }
+
+    #[test]
+    fn given_alerts_then_config_roundtrips() {
+        let config = config_with_identities(
+            r#"
+            [alerts]
+            min_severity = "critical"
+
+            [alerts.smtp]
+            host = "smtp.example.com"
+            username = "asb"
+            password = "hunter2"
+            from = "asb@example.com"
+            to = ["operator@example.com"]
+            "#,
+        );
+
+        let roundtripped = toml::from_str::<Config>(&toml::to_string(&config).unwrap()).unwrap();
+
+        assert_eq!(roundtripped, config);
+        let alerts = config.alerts.unwrap();
+        assert_eq!(alerts.min_severity, Severity::Critical);
+        assert_eq!(alerts.smtp.unwrap().port, 465);
+        assert_eq!(alerts.telegram, None);
+    }
+
+    #[test]
+    fn given_subsystem_levels_then_config_roundtrips() {
+        let config = config_with_identities(
+            r#"
+            [logging]
+            level = "info"
+
+            [logging.subsystems]
+            network = "trace"
+            db = "warn"
+            "#,
+        );
+
+        let roundtripped = toml::from_str::<Config>(&toml::to_string(&config).unwrap()).unwrap();
+
+        assert_eq!(roundtripped, config);
+        assert_eq!(
+            config.logging.subsystems,
+            vec![
+                (Subsystem::Network, LevelFilter::TRACE),
+                (Subsystem::Db, LevelFilter::WARN)
+            ]
+            .into_iter()
+            .collect()
+        );
+    }
+
+    #[test]
+    fn given_approvals_then_config_roundtrips() {
+        let config = config_with_identities(
+            r#"
+            [approvals]
+            min_btc = 0.5
+            "#,
+        );
+
+        let roundtripped = toml::from_str::<Config>(&toml::to_string(&config).unwrap()).unwrap();
+
+        assert_eq!(roundtripped, config);
+        assert_eq!(
+            config.approvals.unwrap().min_btc,
+            bitcoin::Amount::from_btc(0.5).unwrap()
+        );
+    }
+
+    #[test]
+    fn given_health_then_config_roundtrips() {
+        let config = config_with_identities(
+            r#"
+            [health]
+            listen = "127.0.0.1:9945"
+            "#,
+        );
+
+        let roundtripped = toml::from_str::<Config>(&toml::to_string(&config).unwrap()).unwrap();
+
+        assert_eq!(roundtripped, config);
+        assert_eq!(
+            config.health.unwrap().listen,
+            "127.0.0.1:9945".parse::<SocketAddr>().unwrap()
+        );
+    }
+
+    #[test]
+    fn given_rebalance_then_config_roundtrips() {
+        let config = config_with_identities(
+            r#"
+            [rebalance]
+            mode = "manual_approve"
+            min_xmr = "10 XMR"
+            target_xmr = "30 XMR"
+            reserve_btc = 0.1
+
+            [rebalance.kraken]
+            api_key = "key"
+            api_secret = "c2VjcmV0"
+            xmr_withdrawal_key = "asb-wallet"
+            "#,
+        );
+
+        let roundtripped = toml::from_str::<Config>(&toml::to_string(&config).unwrap()).unwrap();
+
+        assert_eq!(roundtripped, config);
+        let rebalance = config.rebalance.unwrap();
+        assert_eq!(rebalance.mode, rebalance::Mode::ManualApprove);
+        assert_eq!(
+            rebalance.min_xmr,
+            monero::Amount::from_monero(10.0).unwrap()
+        );
+        assert_eq!(rebalance.max_btc, None);
+        assert_eq!(rebalance.cooldown_hours, 6);
+    }
+
+    #[test]
+    fn given_relays_then_config_roundtrips() {
+        let mut config = config_with_identities("");
+        config.network.relays = vec![
+            "/dns4/relay.example.com/tcp/4001/p2p/12D3KooWQUt9DkNZxEn2R5ymJzWj15MpG6mTW84kyd8vDaRZi46o"
+                .parse()
+                .unwrap(),
+        ];
+
+        let roundtripped = toml::from_str::<Config>(&toml::to_string(&config).unwrap()).unwrap();
+
+        assert_eq!(roundtripped, config);
+    }
+
+    #[test]
+    fn given_bitcoind_then_config_roundtrips() {
+        let config = config_with_identities(
+            r#"
+            [bitcoin.bitcoind]
+            rpc_url = "http://127.0.0.1:18332"
+            cookie_file = "/var/lib/bitcoind/testnet3/.cookie"
+            "#,
+        );
+
+        let roundtripped = toml::from_str::<Config>(&toml::to_string(&config).unwrap()).unwrap();
+        let bitcoind = config.bitcoin.bitcoind.as_ref().unwrap();
+
+        assert_eq!(roundtripped, config);
+        assert_eq!(bitcoind.wallet_name, "asb-watch-only");
+        assert!(matches!(
+            bitcoind.config().unwrap().auth,
+            bitcoin::bitcoind::Auth::Cookie(_)
+        ));
+    }
+
+    #[test]
+    fn given_bitcoind_with_cookie_file_and_password_then_config_fails() {
+        let config = config_with_identities(
+            r#"
+            [bitcoin.bitcoind]
+            rpc_url = "http://127.0.0.1:18332"
+            cookie_file = "/var/lib/bitcoind/testnet3/.cookie"
+            rpc_user = "asb"
+            rpc_password = "hunter2"
+            "#,
+        );
+
+        assert!(config.bitcoin.bitcoind.unwrap().config().is_err());
+    }
+
+    #[test]
+    fn given_partial_backend_health_then_defaults_apply() {
+        let config = config_with_identities(
+            r#"
+            [backend_health]
+            max_rate_age_secs = 120
+            "#,
+        );
+
+        let thresholds = config.backend_health.unwrap().thresholds();
+
+        assert_eq!(thresholds.max_rate_age, Duration::from_secs(120));
+        assert_eq!(
+            thresholds.max_monero_stall,
+            backend_health::Thresholds::default().max_monero_stall
+        );
+    }
+
+    #[test]
+    fn given_load_section_without_lag_then_default_lag_applies() {
+        let config = config_with_identities(
+            r#"
+            [load]
+            max_active_swaps = 20
+            "#,
+        );
+
+        assert_eq!(config.load.unwrap().limits(), load::Limits {
+            max_active_swaps: Some(20),
+            ..load::Limits::default()
+        });
+    }
+
+    #[test]
+    fn given_identity_with_listen_address_of_default_identity_then_validation_fails() {
+        let config = config_with_identities(
+            r#"
+            [[identities]]
+            name = "wide-spread"
+            listen = ["/ip4/0.0.0.0/tcp/9939"]
+            min_buy_btc = 0.01
+            max_buy_btc = 0.1
+            ask_spread = 0.05
+            "#,
+        );
+
+        assert!(config.validate_identities().is_err());
+    }
+
+    #[test]
+    fn given_duplicate_identity_names_then_validation_fails() {
+        let config = config_with_identities(
+            r#"
+            [[identities]]
+            name = "wide-spread"
+            listen = ["/ip4/0.0.0.0/tcp/9941"]
+            min_buy_btc = 0.01
+            max_buy_btc = 0.1
+            ask_spread = 0.05
+
+            [[identities]]
+            name = "wide-spread"
+            listen = ["/ip4/0.0.0.0/tcp/9942"]
+            min_buy_btc = 0.01
+            max_buy_btc = 0.1
+            ask_spread = 0.05
+            "#,
+        );
+
+        assert!(config.validate_identities().is_err());
+    }
+
+    #[test]
+    fn given_address_family_without_listen_address_of_it_then_validation_fails() {
+        let mut config = config_with_identities("");
+
+        config.network.address_family = Some(AddressFamily::Ipv4);
+        assert!(config.validate_identities().is_ok());
+
+        config.network.address_family = Some(AddressFamily::Ipv6);
+        let roundtripped = toml::from_str::<Config>(&toml::to_string(&config).unwrap()).unwrap();
+        assert_eq!(roundtripped, config);
+        assert!(config.validate_identities().is_err());
+    }
 }
@@ -0,0 +1,238 @@
+use crate::asb::{BackendHealth, Load};
+use crate::bitcoin;
+use anyhow::{bail, Result};
+use libp2p::PeerId;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::{Arc, RwLock};
+use tokio::sync::Notify;
+
+/// The trading parameters of the ASB that can be changed while it is running.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TradingParameters {
+    /// When set, no quotes are handed out and no new swaps are accepted.
+    /// Unfinished swaps are still resumed and executed.
+    pub paused: bool,
+    pub ask_spread: Decimal,
+    pub min_buy: bitcoin::Amount,
+    pub max_buy: bitcoin::Amount,
+}
+
+impl TradingParameters {
+    fn validate(&self) -> Result<()> {
+        if self.ask_spread < Decimal::ZERO || self.ask_spread > Decimal::ONE {
+            bail!(
+                "Ask spread must be between 0 and 1, got {}",
+                self.ask_spread
+            )
+        }
+
+        if self.min_buy > self.max_buy {
+            bail!(
+                "Minimum buy amount {} must not exceed the maximum buy amount {}",
+                self.min_buy,
+                self.max_buy
+            )
+        }
+
+        Ok(())
+    }
+}
+
+/// A cheaply cloneable handle to the trading parameters shared between the
+/// network layer, the rate and the admin socket.
+///
+/// Changes are picked up by the next quote or swap request, swaps that are
+/// already set up are not affected.
+#[derive(Debug, Clone)]
+pub struct TradingControls {
+    parameters: Arc<RwLock<TradingParameters>>,
+    refused_peers: Arc<RwLock<HashSet<PeerId>>>,
+    peer_refused: Arc<Notify>,
+    backend_health: BackendHealth,
+    load: Load,
+}
+
+impl TradingControls {
+    pub fn new(parameters: TradingParameters) -> Self {
+        Self {
+            parameters: Arc::new(RwLock::new(parameters)),
+            refused_peers: Default::default(),
+            peer_refused: Default::default(),
+            backend_health: Default::default(),
+            load: Default::default(),
+        }
+    }
+
+    /// Suspends trading while `backend_health` reports a degraded backend.
+    pub fn with_backend_health(self, backend_health: BackendHealth) -> Self {
+        Self {
+            backend_health,
+            ..self
+        }
+    }
+
+    /// Refuses new quotes and swaps while `load` is saturated.
+    pub fn with_load(self, load: Load) -> Self {
+        Self { load, ..self }
+    }
+
+    pub fn parameters(&self) -> TradingParameters {
+        *self
+            .parameters
+            .read()
+            .expect("trading parameters lock not poisoned")
+    }
+
+    /// Why trading is suspended regardless of the parameters, `None` if all
+    /// backends are healthy.
+    pub fn degraded(&self) -> Option<String> {
+        self.backend_health.degraded()
+    }
+
+    /// Why new quotes and swaps are refused regardless of the parameters,
+    /// `None` unless the ASB is saturated.
+    pub fn overloaded(&self) -> Option<String> {
+        self.load.overloaded()
+    }
+
+    pub fn backend_health(&self) -> &BackendHealth {
+        &self.backend_health
+    }
+
+    pub fn set_paused(&self, paused: bool) {
+        self.parameters
+            .write()
+            .expect("trading parameters lock not poisoned")
+            .paused = paused;
+    }
+
+    pub fn set_ask_spread(&self, ask_spread: Decimal) -> Result<()> {
+        self.update(|parameters| parameters.ask_spread = ask_spread)
+    }
+
+    pub fn set_limits(&self, min_buy: bitcoin::Amount, max_buy: bitcoin::Amount) -> Result<()> {
+        self.update(|parameters| {
+            parameters.min_buy = min_buy;
+            parameters.max_buy = max_buy;
+        })
+    }
+
+    /// Applies `f` to the current parameters.
+    ///
+    /// The parameters are left untouched if the result is invalid.
+    pub fn update(&self, f: impl FnOnce(&mut TradingParameters)) -> Result<()> {
+        let mut parameters = self
+            .parameters
+            .write()
+            .expect("trading parameters lock not poisoned");
+
+        let mut updated = *parameters;
+        f(&mut updated);
+        updated.validate()?;
+        *parameters = updated;
+
+        Ok(())
+    }
+
+    /// Refuses all further connections from the given peer until the ASB is
+    /// restarted.
+    pub fn refuse_peer(&self, peer: PeerId) {
+        self.refused_peers
+            .write()
+            .expect("refused peers lock not poisoned")
+            .insert(peer);
+        self.peer_refused.notify_one();
+    }
+
+    pub fn refused_peers(&self) -> Vec<PeerId> {
+        self.refused_peers
+            .read()
+            .expect("refused peers lock not poisoned")
+            .iter()
+            .copied()
+            .collect()
+    }
+
+    /// Resolves once a peer was refused since the last time this resolved.
+    pub async fn peer_refused(&self) {
+        self.peer_refused.notified().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    fn controls() -> TradingControls {
+        TradingControls::new(TradingParameters {
+            paused: false,
+            ask_spread: dec!(0.02),
+            min_buy: bitcoin::Amount::from_sat(10_000),
+            max_buy: bitcoin::Amount::from_sat(1_000_000),
+        })
+    }
+
+    #[test]
+    fn changes_are_visible_to_all_clones() {
+        let controls = controls();
+        let clone = controls.clone();
+
+        controls.set_paused(true);
+        controls.set_ask_spread(dec!(0.05)).unwrap();
+        controls
+            .set_limits(
+                bitcoin::Amount::from_sat(20_000),
+                bitcoin::Amount::from_sat(2_000_000),
+            )
+            .unwrap();
+
+        assert_eq!(
+            clone.parameters(),
+            TradingParameters {
+                paused: true,
+                ask_spread: dec!(0.05),
+                min_buy: bitcoin::Amount::from_sat(20_000),
+                max_buy: bitcoin::Amount::from_sat(2_000_000),
+            }
+        );
+    }
+
+    #[test]
+    fn given_min_above_max_then_limits_are_not_changed() {
+        let controls = controls();
+
+        let result = controls.set_limits(
+            bitcoin::Amount::from_sat(2_000_000),
+            bitcoin::Amount::from_sat(20_000),
+        );
+
+        assert!(result.is_err());
+        assert_eq!(
+            controls.parameters().min_buy,
+            bitcoin::Amount::from_sat(10_000)
+        );
+    }
+
+    #[test]
+    fn given_spread_out_of_range_then_spread_is_not_changed() {
+        let controls = controls();
+
+        assert!(controls.set_ask_spread(dec!(-0.01)).is_err());
+        assert!(controls.set_ask_spread(dec!(1.01)).is_err());
+        assert_eq!(controls.parameters().ask_spread, dec!(0.02));
+    }
+
+    #[tokio::test]
+    async fn refusing_a_peer_notifies_listener() {
+        let controls = controls();
+        let peer = PeerId::random();
+
+        controls.refuse_peer(peer);
+        controls.peer_refused().await;
+
+        assert_eq!(controls.refused_peers(), vec![peer]);
+    }
+}
@@ -1,22 +1,46 @@
-use crate::asb::{Behaviour, OutEvent, Rate};
-use crate::network::quote::BidQuote;
+use crate::amounts::{Price, Quote, Rate};
+use crate::asb::notifications::{Event, Notifier};
+use crate::asb::policy::{self, Policy, Verdict};
+use crate::asb::price_feeds::{self, FeedStatus, PriceFeeds};
+use crate::asb::quotes::{IssuedQuotes, Outcome, PendingQuotes, QuoteRecord, QUOTE_VALIDITY};
+use crate::asb::{Approvals, Behaviour, OutEvent, TradingControls, TradingParameters};
+use crate::network::connection::ConnectionStats;
+use crate::network::cooperative_xmr_redeem_after_punish::{self, RejectReason};
+use crate::network::encrypted_signature;
+use crate::network::hole_punch;
+use crate::network::maker_key::MakerKey;
+use crate::network::outbox;
+use crate::network::quote::{self, DepthBucket, QuoteTerms, SignedQuote};
+use crate::network::receipt;
 use crate::network::swap_setup::alice::WalletSnapshot;
+use crate::network::swap_status::{self, Phase, Resolution, Status};
 use crate::network::transfer_proof;
+use crate::network::watchtower;
 use crate::protocol::alice::{AliceState, State3, Swap};
+use crate::protocol::receipt::Terms;
+use crate::protocol::sequence::MessageKind;
 use crate::protocol::{Database, State};
-use crate::{bitcoin, env, kraken, monero};
-use anyhow::{Context, Result};
+use crate::reservations::{Reservation, Reservations};
+use crate::{bitcoin, env, monero};
+use anyhow::{bail, Context, Result};
 use futures::future;
 use futures::future::{BoxFuture, FutureExt};
 use futures::stream::{FuturesUnordered, StreamExt};
+use libp2p::core::connection::ListenerId;
+use libp2p::identity;
+use libp2p::multiaddr::Protocol;
+use libp2p::relay::v2::client;
 use libp2p::request_response::{RequestId, ResponseChannel};
 use libp2p::swarm::SwarmEvent;
-use libp2p::{PeerId, Swarm};
+use libp2p::{Multiaddr, PeerId, Swarm};
 use rust_decimal::Decimal;
+use std::cmp::min;
 use std::collections::HashMap;
 use std::convert::{Infallible, TryInto};
 use std::fmt::Debug;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use time::OffsetDateTime;
 use tokio::sync::mpsc;
 use uuid::Uuid;
 
@@ -30,6 +54,43 @@ use uuid::Uuid;
 type OutgoingTransferProof =
     BoxFuture<'static, Result<(PeerId, transfer_proof::Request, bmrng::Responder<()>)>>;
 
+/// A request of a taker together with the verdict of the policy on it.
+///
+/// The policy may call a webhook, so it is asked outside of the event loop and
+/// the request is answered once the verdict is back.
+enum Decided {
+    SwapSetup {
+        verdict: Verdict,
+        wallet_snapshot: WalletSnapshot,
+        responder: bmrng::Responder<(WalletSnapshot, Verdict)>,
+    },
+    Quote {
+        verdict: Verdict,
+        peer: PeerId,
+        channel: ResponseChannel<SignedQuote>,
+        parameters: TradingParameters,
+        /// Whether the quote may be repeated to the peer for a while.
+        repeatable: bool,
+    },
+    CooperativeXmrRedeem {
+        response: cooperative_xmr_redeem_after_punish::Response,
+        peer: PeerId,
+        channel: ResponseChannel<cooperative_xmr_redeem_after_punish::Response>,
+    },
+}
+
+/// The name of the maker identity configured in the `[network]` and `[maker]`
+/// sections. Swaps without a recorded identity belong to it.
+pub const DEFAULT_IDENTITY: &str = "default";
+
+/// How far the completion time of a receipt Bob asks us to countersign may be
+/// off.
+const RECEIPT_CLOCK_TOLERANCE: time::Duration = time::Duration::HOUR;
+
+/// How long to wait before listening through a relay again after the
+/// connection to it was lost or could not be established.
+const RELAY_RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
 #[allow(missing_debug_implementations)]
 pub struct EventLoop<LR>
 where
@@ -41,24 +102,48 @@ where
     monero_wallet: Arc<monero::Wallet>,
     db: Arc<dyn Database + Send + Sync>,
     latest_rate: LR,
-    min_buy: bitcoin::Amount,
-    max_buy: bitcoin::Amount,
+    controls: TradingControls,
+    policy: Arc<dyn Policy>,
+    notifier: Option<Notifier>,
+    /// Gets the presigned cancel and punish transactions of every swap.
+    watchtower: Option<watchtower::Client>,
+    cpfp: bool,
+    approvals: Approvals,
+    issued_quotes: IssuedQuotes,
+    pending_quotes: PendingQuotes,
+    /// The requests waiting for the verdict of the policy.
+    decisions: FuturesUnordered<BoxFuture<'static, Decided>>,
+    /// The Monero of the swaps that did not lock it yet.
+    xmr_reservations: Reservations<monero::Amount>,
+    /// The maker identity this event loop sets up and resumes swaps for.
+    identity: String,
+    /// The key of the peer id of the swarm, signs the quotes.
+    identity_key: identity::Keypair,
+    /// Signs the quotes on top of the identity key, see
+    /// [`maker_key`](crate::network::maker_key).
+    maker_key: MakerKey,
+    connection_stats: ConnectionStats,
 
     swap_sender: mpsc::Sender<Swap>,
 
     /// Stores incoming [`EncryptedSignature`]s per swap.
-    recv_encrypted_signature: HashMap<Uuid, bmrng::RequestSender<bitcoin::EncryptedSignature, ()>>,
+    recv_encrypted_signature: HashMap<Uuid, bmrng::RequestSender<encrypted_signature::Request, ()>>,
     inflight_encrypted_signatures: FuturesUnordered<BoxFuture<'static, ResponseChannel<()>>>,
 
     send_transfer_proof: FuturesUnordered<OutgoingTransferProof>,
 
-    /// Tracks [`transfer_proof::Request`]s which could not yet be sent because
-    /// we are currently disconnected from the peer.
-    buffered_transfer_proofs: HashMap<PeerId, Vec<(transfer_proof::Request, bmrng::Responder<()>)>>,
+    /// The swaps waiting for Bob to acknowledge their transfer proof, which
+    /// stays in the outbox until then.
+    pending_transfer_proofs: HashMap<Uuid, bmrng::Responder<()>>,
 
-    /// Tracks [`transfer_proof::Request`]s which are currently inflight and
-    /// awaiting an acknowledgement.
-    inflight_transfer_proofs: HashMap<RequestId, bmrng::Responder<()>>,
+    /// Tracks the swaps of [`transfer_proof::Request`]s which are currently
+    /// inflight and awaiting an acknowledgement.
+    inflight_transfer_proofs: HashMap<RequestId, Uuid>,
+
+    /// The relays listened through, by the listener listening through them.
+    relay_listeners: HashMap<ListenerId, Multiaddr>,
+    /// The relays to listen through again once their retry interval passed.
+    relay_retries: FuturesUnordered<BoxFuture<'static, Multiaddr>>,
 }
 
 impl<LR> EventLoop<LR>
@@ -68,13 +153,20 @@ where
     #[allow(clippy::too_many_arguments)]
     pub fn new(
         swarm: Swarm<Behaviour<LR>>,
+        identity_key: identity::Keypair,
+        maker_key: MakerKey,
         env_config: env::Config,
         bitcoin_wallet: Arc<bitcoin::Wallet>,
         monero_wallet: Arc<monero::Wallet>,
         db: Arc<dyn Database + Send + Sync>,
         latest_rate: LR,
-        min_buy: bitcoin::Amount,
-        max_buy: bitcoin::Amount,
+        controls: TradingControls,
+        policy: Arc<dyn Policy>,
+        notifier: Option<Notifier>,
+        watchtower: Option<watchtower::Client>,
+        cpfp: bool,
+        approvals: Approvals,
+        identity: String,
     ) -> Result<(Self, mpsc::Receiver<Swap>)> {
         let swap_channel = MpscChannels::default();
 
@@ -86,27 +178,61 @@ where
             db,
             latest_rate,
             swap_sender: swap_channel.sender,
-            min_buy,
-            max_buy,
+            controls,
+            policy,
+            notifier,
+            watchtower,
+            cpfp,
+            approvals,
+            issued_quotes: Default::default(),
+            pending_quotes: PendingQuotes::new(identity.clone()),
+            decisions: Default::default(),
+            xmr_reservations: Default::default(),
+            identity,
+            identity_key,
+            maker_key,
+            connection_stats: Default::default(),
             recv_encrypted_signature: Default::default(),
             inflight_encrypted_signatures: Default::default(),
             send_transfer_proof: Default::default(),
-            buffered_transfer_proofs: Default::default(),
+            pending_transfer_proofs: Default::default(),
             inflight_transfer_proofs: Default::default(),
+            relay_listeners: Default::default(),
+            relay_retries: Default::default(),
         };
         Ok((event_loop, swap_channel.receiver))
     }
 
+    /// Listens for connections through the circuit relay at `relay`, which
+    /// must contain its peer ID. The event loop listens through the relay
+    /// again if the connection to it is lost.
+    pub fn listen_via_relay(&mut self, relay: Multiaddr) -> Result<()> {
+        let listener = self
+            .swarm
+            .listen_on(relay.clone().with(Protocol::P2pCircuit))
+            .with_context(|| format!("Failed to listen through relay {}", relay))?;
+        self.relay_listeners.insert(listener, relay);
+
+        Ok(())
+    }
+
     pub fn peer_id(&self) -> PeerId {
         *Swarm::local_peer_id(&self.swarm)
     }
 
+    /// The counts of the connections of this event loop's swarm, which keep
+    /// being updated while it runs.
+    pub fn connection_stats(&self) -> ConnectionStats {
+        self.connection_stats.clone()
+    }
+
     pub async fn run(mut self) {
         // ensure that these streams are NEVER empty, otherwise it will
         // terminate forever.
         self.send_transfer_proof.push(future::pending().boxed());
         self.inflight_encrypted_signatures
             .push(future::pending().boxed());
+        self.decisions.push(future::pending().boxed());
 
         let swaps = match self.db.all().await {
             Ok(swaps) => swaps,
@@ -121,7 +247,26 @@ where
             .filter(|(_swap_id, state)| !state.swap_finished())
             .collect::<Vec<(Uuid, State)>>();
 
+        // Resumed swaps reserve their Monero before new swaps are set up
+        let xmr_balance = match self.monero_wallet.get_balance().await {
+            Ok(balance) => Some(balance),
+            Err(error) => {
+                tracing::warn!("Failed to get Monero balance, resumed swaps reserve their Monero right before locking it: {:#}", error);
+                None
+            }
+        };
+
         for (swap_id, state) in unfinished_swaps {
+            match self.db.get_identity(swap_id).await {
+                Ok(identity)
+                    if identity.as_deref().unwrap_or(DEFAULT_IDENTITY) == self.identity => {}
+                Ok(_) => continue,
+                Err(error) => {
+                    tracing::warn!(%swap_id, "Resuming swap skipped because its identity could not be loaded: {:#}", error);
+                    continue;
+                }
+            }
+
             let peer_id = match self.db.get_peer_id(swap_id).await {
                 Ok(peer_id) => peer_id,
                 Err(_) => {
@@ -131,6 +276,18 @@ where
             };
 
             let handle = self.new_handle(peer_id, swap_id);
+            let state: AliceState = state.try_into().expect("Alice state loaded from db");
+
+            let reservation = match (state.xmr_to_lock(), xmr_balance) {
+                (Some(xmr), Some(balance)) => {
+                    let reservation = self.xmr_reservations.reserve(xmr, balance);
+                    if reservation.is_none() {
+                        tracing::warn!(%swap_id, %xmr, "Monero balance does not cover resumed swap next to other swaps, reserving it before locking the Monero");
+                    }
+                    reservation
+                }
+                _ => None,
+            };
 
             let swap = Swap {
                 event_loop_handle: handle,
@@ -138,8 +295,14 @@ where
                 monero_wallet: self.monero_wallet.clone(),
                 env_config: self.env_config,
                 db: self.db.clone(),
-                state: state.try_into().expect("Alice state loaded from db"),
+                state,
                 swap_id,
+                notifier: self.notifier.clone(),
+                cpfp: self.cpfp,
+                approvals: self.approvals.clone(),
+                policy: self.policy.clone(),
+                reservations: self.xmr_reservations.clone(),
+                reservation,
             };
 
             match self.swap_sender.send(swap).await {
@@ -154,7 +317,19 @@ where
             tokio::select! {
                 swarm_event = self.swarm.select_next_some() => {
                     match swarm_event {
-                        SwarmEvent::Behaviour(OutEvent::SwapSetupInitiated { mut send_wallet_snapshot }) => {
+                        SwarmEvent::Behaviour(OutEvent::SwapSetupInitiated { peer, mut send_wallet_snapshot, mut set_up_before }) => {
+                            // The handler refuses the swap itself, without waiting for us
+                            if self.controls.overloaded().is_some() {
+                                continue;
+                            }
+
+                            // Asked once Bob sent his first message, which is after the snapshot
+                            let db = self.db.clone();
+                            tokio::spawn(async move {
+                                if let Ok((swap_id, responder)) = set_up_before.recv().await {
+                                    let _ = responder.respond(db.get_state(swap_id).await.is_ok());
+                                }
+                            });
 
                             let (btc, responder) = match send_wallet_snapshot.recv().await {
                                 Ok((btc, responder)) => (btc, responder),
@@ -164,7 +339,7 @@ where
                                 }
                             };
 
-                            let wallet_snapshot = match WalletSnapshot::capture(&self.bitcoin_wallet, &self.monero_wallet, btc).await {
+                            let wallet_snapshot = match WalletSnapshot::capture(&self.bitcoin_wallet, &self.monero_wallet, &self.xmr_reservations, btc).await {
                                 Ok(wallet_snapshot) => wallet_snapshot,
                                 Err(error) => {
                                     tracing::error!("Swap request will be ignored because we were unable to create wallet snapshot for swap: {:#}", error);
@@ -172,32 +347,58 @@ where
                                 }
                             };
 
-                            // Ignore result, we should never hit this because the receiver will alive as long as the connection is.
-                            let _ = responder.respond(wallet_snapshot);
+                            let decision = self.decide(peer, Some(btc)).map(move |verdict| Decided::SwapSetup { verdict, wallet_snapshot, responder });
+                            self.decisions.push(decision.boxed());
                         }
-                        SwarmEvent::Behaviour(OutEvent::SwapSetupCompleted{peer_id, swap_id, state3}) => {
-                            let _ = self.handle_execution_setup_done(peer_id, swap_id, state3).await;
+                        SwarmEvent::Behaviour(OutEvent::SwapSetupCompleted{peer_id, swap_id, state3, reservation}) => {
+                            if let Some(mut quote) = self.pending_quotes.resolve(&peer_id, Outcome::Converted) {
+                                quote.swap_id = Some(swap_id);
+                                self.record_quote(quote).await;
+                            }
+
+                            let _ = self.handle_execution_setup_done(peer_id, swap_id, state3, reservation).await;
                         }
                         SwarmEvent::Behaviour(OutEvent::SwapDeclined { peer, error }) => {
                             tracing::warn!(%peer, "Ignoring spot price request: {}", error);
+
+                            if let Some(mut quote) = self.pending_quotes.resolve(&peer, Outcome::of_rejection(&error)) {
+                                quote.reason = Some(error.to_string());
+                                self.record_quote(quote).await;
+                            }
                         }
                         SwarmEvent::Behaviour(OutEvent::QuoteRequested { channel, peer }) => {
-                            let quote = match self.make_quote(self.min_buy, self.max_buy).await {
-                                Ok(quote) => quote,
-                                Err(error) => {
-                                    tracing::warn!(%peer, "Failed to make quote: {:#}", error);
-                                    continue;
-                                }
-                            };
+                            let parameters = self.controls.parameters();
 
-                            if self.swarm.behaviour_mut().quote.send_response(channel, quote).is_err() {
-                                tracing::debug!(%peer, "Failed to respond with quote");
+                            if let Some(quote) = self.repeatable_quote(&peer, parameters) {
+                                tracing::debug!(%peer, "Repeating quote issued recently");
+                                if self.swarm.behaviour_mut().quote.send_response(channel, quote).is_err() {
+                                    tracing::debug!(%peer, "Failed to respond with quote");
+                                }
+                                continue;
                             }
+
+                            // While a backend is degraded quotes are not repeated, neither the ones
+                            // made before nor the empty ones made since, so that takers get a full
+                            // quote as soon as the backend recovered
+                            let repeatable = self.controls.degraded().is_none() && self.controls.overloaded().is_none();
+                            // While overloaded the quote is empty anyway, the policy is not asked
+                            let verdict = if self.controls.overloaded().is_some() {
+                                future::ready(Verdict::Accept).boxed()
+                            } else {
+                                self.decide(peer, None)
+                            };
+                            let decision = verdict.map(move |verdict| Decided::Quote { verdict, peer, channel, parameters, repeatable });
+                            self.decisions.push(decision.boxed());
                         }
                         SwarmEvent::Behaviour(OutEvent::TransferProofAcknowledged { peer, id }) => {
                             tracing::debug!(%peer, "Bob acknowledged transfer proof");
-                            if let Some(responder) = self.inflight_transfer_proofs.remove(&id) {
-                                let _ = responder.respond(());
+                            if let Some(swap_id) = self.inflight_transfer_proofs.remove(&id) {
+                                if let Err(error) = self.db.remove_outbound(swap_id, MessageKind::TransferProof).await {
+                                    tracing::warn!(%swap_id, "Failed to remove acknowledged transfer proof from outbox: {:#}", error);
+                                }
+                                if let Some(responder) = self.pending_transfer_proofs.remove(&swap_id) {
+                                    let _ = responder.respond(());
+                                }
                             }
                         }
                         SwarmEvent::Behaviour(OutEvent::EncryptedSignatureReceived{ msg, channel, peer }) => {
@@ -226,16 +427,24 @@ where
                                 continue;
                             }
 
+                            let nonce = msg.nonce;
                             let sender = match self.recv_encrypted_signature.remove(&swap_id) {
                                 Some(sender) => sender,
                                 None => {
-                                    // TODO: Don't just drop encsig if we currently don't have a running swap for it, save in db
-                                    tracing::warn!(%swap_id, "No sender for encrypted signature, maybe already handled?");
+                                    match self.encrypted_signature_applied(swap_id, &msg).await {
+                                        Ok(true) => {
+                                            tracing::debug!(%swap_id, "Acknowledging encrypted signature that was applied before");
+                                            let _ = self.swarm.behaviour_mut().encrypted_signature.send_response(channel, ());
+                                        }
+                                        // TODO: Don't just drop encsig if we currently don't have a running swap for it, save in db
+                                        Ok(false) => tracing::warn!(%swap_id, "No sender for encrypted signature, swap is not running"),
+                                        Err(error) => tracing::warn!(%swap_id, from = %peer, "Ignoring encrypted signature: {:#}", error),
+                                    }
                                     continue;
                                 }
                             };
 
-                            let mut responder = match sender.send(msg.tx_redeem_encsig).await {
+                            let mut responder = match sender.send(msg).await {
                                 Ok(responder) => responder,
                                 Err(_) => {
                                     tracing::warn!(%swap_id, "Failed to relay encrypted signature to swap");
@@ -243,61 +452,124 @@ where
                                 }
                             };
 
+                            let db = self.db.clone();
                             self.inflight_encrypted_signatures.push(async move {
                                 let _ = responder.recv().await;
+                                outbox::record_received(db.as_ref(), swap_id, MessageKind::EncryptedSignature, nonce).await;
 
                                 channel
                             }.boxed());
                         }
+                        SwarmEvent::Behaviour(OutEvent::SwapStatusRequested { status, channel, peer }) => {
+                            let own_status = self.resynchronize(peer, status).await;
+
+                            if self.swarm.behaviour_mut().swap_status.send_response(channel, own_status).is_err() {
+                                tracing::debug!(%peer, "Failed to respond with swap status");
+                            }
+                        }
+                        SwarmEvent::Behaviour(OutEvent::CooperativeXmrRedeemRequested { swap_id, channel, peer }) => {
+                            let decision = self.cooperative_xmr_redeem(peer, swap_id).await.map(move |response| Decided::CooperativeXmrRedeem { response, peer, channel });
+                            self.decisions.push(decision.boxed());
+                        }
+                        SwarmEvent::Behaviour(OutEvent::ReceiptRequested { request, channel, peer }) => {
+                            let swap_id = request.terms.swap_id;
+                            let response = match self.countersign_receipt(peer, *request).await {
+                                Ok(signature) => {
+                                    tracing::info!(%swap_id, %peer, "Countersigned receipt of swap");
+                                    receipt::Response::Signed { swap_id, signature }
+                                }
+                                Err(error) => {
+                                    tracing::info!(%swap_id, %peer, "Refused to countersign receipt: {:#}", error);
+                                    receipt::Response::Rejected { swap_id, reason: format!("{:#}", error) }
+                                }
+                            };
+
+                            if self.swarm.behaviour_mut().receipt.send_response(channel, response).is_err() {
+                                tracing::debug!(%peer, "Failed to respond to receipt request");
+                            }
+                        }
                         SwarmEvent::Behaviour(OutEvent::Rendezvous(libp2p::rendezvous::client::Event::Registered { .. })) => {
                             tracing::info!("Successfully registered with rendezvous node");
                         }
                         SwarmEvent::Behaviour(OutEvent::Rendezvous(libp2p::rendezvous::client::Event::RegisterFailed(error))) => {
                             tracing::error!("Registration with rendezvous node failed: {:?}", error);
                         }
+                        SwarmEvent::Behaviour(OutEvent::Relay(client::Event::ReservationReqAccepted { relay_peer_id, renewal: false, .. })) => {
+                            tracing::info!(relay = %relay_peer_id, "Reserved a slot on relay");
+                        }
+                        SwarmEvent::Behaviour(OutEvent::Relay(client::Event::ReservationReqFailed { relay_peer_id, error, .. })) => {
+                            tracing::warn!(relay = %relay_peer_id, "Relay refused to reserve a slot: {:#}", error);
+                        }
                         SwarmEvent::Behaviour(OutEvent::Failure {peer, error}) => {
                             tracing::error!(
                                 %peer,
                                 "Communication error: {:#}", error);
                         }
+                        SwarmEvent::Behaviour(OutEvent::HolePunch(hole_punch::OutEvent::Upgraded { peer })) => {
+                            tracing::info!(%peer, "Upgraded relayed connection to a direct one");
+                        }
+                        SwarmEvent::Behaviour(OutEvent::HolePunch(hole_punch::OutEvent::UpgradeFailed { peer, error })) => {
+                            tracing::debug!(%peer, "Failed to upgrade relayed connection to a direct one: {:#}", error);
+                        }
                         SwarmEvent::ConnectionEstablished { peer_id: peer, endpoint, .. } => {
-                            tracing::debug!(%peer, address = %endpoint.get_remote_address(), "New connection established");
-
-                            if let Some(transfer_proofs) = self.buffered_transfer_proofs.remove(&peer) {
-                                for (transfer_proof, responder) in transfer_proofs {
-                                    tracing::debug!(%peer, "Found buffered transfer proof for peer");
+                            tracing::debug!(%peer, address = %endpoint.get_remote_address(), relayed = endpoint.is_relayed(), "New connection established");
 
-                                    let id = self.swarm.behaviour_mut().transfer_proof.send_request(&peer, transfer_proof);
-                                    self.inflight_transfer_proofs.insert(id, responder);
-                                }
+                            if self.connection_stats.established(peer, endpoint.is_relayed(), Instant::now()) {
+                                tracing::info!(%peer, "Peer reconnected after losing the connection");
                             }
+
+                            self.send_outbox(peer).await;
                         }
                         SwarmEvent::IncomingConnectionError { send_back_addr: address, error, .. } => {
                             tracing::warn!(%address, "Failed to set up connection with peer: {:#}", error);
                         }
                         SwarmEvent::ConnectionClosed { peer_id: peer, num_established, endpoint, cause: Some(error) } if num_established == 0 => {
                             tracing::debug!(%peer, address = %endpoint.get_remote_address(), "Lost connection to peer: {:#}", error);
+
+                            self.connection_stats.closed(peer, endpoint.is_relayed(), 0, true, Instant::now());
+
+                            if let Some(quote) = self.pending_quotes.resolve(&peer, Outcome::PeerDisconnected) {
+                                self.record_quote(quote).await;
+                            }
                         }
                         SwarmEvent::ConnectionClosed { peer_id: peer, num_established, endpoint, cause: None } if num_established == 0 => {
                             tracing::info!(%peer, address = %endpoint.get_remote_address(), "Successfully closed connection");
+
+                            self.connection_stats.closed(peer, endpoint.is_relayed(), 0, false, Instant::now());
+
+                            if let Some(quote) = self.pending_quotes.resolve(&peer, Outcome::PeerDisconnected) {
+                                self.record_quote(quote).await;
+                            }
+                        }
+                        SwarmEvent::ConnectionClosed { peer_id: peer, num_established, endpoint, .. } => {
+                            self.connection_stats.closed(peer, endpoint.is_relayed(), num_established, false, Instant::now());
                         }
                         SwarmEvent::NewListenAddr{address, ..} => {
                             tracing::info!(%address, "New listen address reported");
                         }
+                        SwarmEvent::ListenerClosed { listener_id, reason, .. } => {
+                            if let Some(relay) = self.relay_listeners.remove(&listener_id) {
+                                match reason {
+                                    Ok(()) => tracing::warn!(%relay, "Lost connection to relay, listening through it again in {} seconds", RELAY_RETRY_INTERVAL.as_secs()),
+                                    Err(error) => tracing::warn!(%relay, "Failed to listen through relay, trying again in {} seconds: {:#}", RELAY_RETRY_INTERVAL.as_secs(), error),
+                                }
+
+                                self.relay_retries.push(tokio::time::sleep(RELAY_RETRY_INTERVAL).map(move |_| relay).boxed());
+                            }
+                        }
                         _ => {}
                     }
                 },
+                Some(relay) = self.relay_retries.next() => {
+                    if let Err(error) = self.listen_via_relay(relay) {
+                        tracing::warn!("{:#}", error);
+                    }
+                }
                 next_transfer_proof = self.send_transfer_proof.next() => {
                     match next_transfer_proof {
                         Some(Ok((peer, transfer_proof, responder))) => {
-                            if !self.swarm.behaviour_mut().transfer_proof.is_connected(&peer) {
-                                tracing::warn!(%peer, "No active connection to peer, buffering transfer proof");
-                                self.buffered_transfer_proofs.entry(peer).or_insert_with(Vec::new).push((transfer_proof, responder));
-                                continue;
-                            }
-
-                            let id = self.swarm.behaviour_mut().transfer_proof.send_request(&peer, transfer_proof);
-                            self.inflight_transfer_proofs.insert(id, responder);
+                            self.pending_transfer_proofs.insert(transfer_proof.swap_id, responder);
+                            self.send_transfer_proof(peer, transfer_proof).await;
                         },
                         Some(Err(error)) => {
                             tracing::debug!("A swap stopped without sending a transfer proof: {:#}", error);
@@ -310,25 +582,466 @@ where
                 Some(response_channel) = self.inflight_encrypted_signatures.next() => {
                     let _ = self.swarm.behaviour_mut().encrypted_signature.send_response(response_channel, ());
                 }
+                Some(decided) = self.decisions.next() => {
+                    self.handle_decided(decided).await;
+                }
+                _ = self.controls.peer_refused() => {
+                    for peer in self.controls.refused_peers() {
+                        if self.swarm.is_connected(&peer) {
+                            tracing::info!(%peer, "Closing connection to refused peer");
+                        }
+
+                        self.swarm.ban_peer_id(peer);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Answers a request of a taker with the verdict of the policy on it.
+    async fn handle_decided(&mut self, decided: Decided) {
+        match decided {
+            Decided::SwapSetup {
+                verdict,
+                wallet_snapshot,
+                responder,
+            } => {
+                // Ignore result, we should never hit this because the receiver will alive as
+                // long as the connection is.
+                let _ = responder.respond((wallet_snapshot, verdict));
+            }
+            Decided::Quote {
+                verdict,
+                peer,
+                channel,
+                parameters,
+                repeatable,
+            } => {
+                let quote = match self.make_quote(verdict).await {
+                    Ok(quote) => quote,
+                    Err(error) => {
+                        tracing::warn!(%peer, "Failed to make quote: {:#}", error);
+                        return;
+                    }
+                };
+
+                if repeatable {
+                    self.issued_quotes
+                        .insert(peer, quote.clone(), parameters, Instant::now());
+                }
+                if let Some(expired) =
+                    self.pending_quotes
+                        .issue(peer, &quote.quote, OffsetDateTime::now_utc())
+                {
+                    self.record_quote(expired).await;
+                }
+
+                if self
+                    .swarm
+                    .behaviour_mut()
+                    .quote
+                    .send_response(channel, quote)
+                    .is_err()
+                {
+                    tracing::debug!(%peer, "Failed to respond with quote");
+                }
+            }
+            Decided::CooperativeXmrRedeem {
+                response,
+                peer,
+                channel,
+            } => {
+                if self
+                    .swarm
+                    .behaviour_mut()
+                    .cooperative_xmr_redeem
+                    .send_response(channel, response)
+                    .is_err()
+                {
+                    tracing::debug!(%peer, "Failed to respond to cooperative Monero redeem request");
+                }
             }
         }
     }
 
-    async fn make_quote(
+    /// The quote issued to `peer` recently under the same trading parameters,
+    /// if it may be repeated. Quotes are never repeated while a backend is
+    /// degraded or while overloaded.
+    fn repeatable_quote(
         &mut self,
-        min_buy: bitcoin::Amount,
-        max_buy: bitcoin::Amount,
-    ) -> Result<BidQuote> {
+        peer: &PeerId,
+        parameters: TradingParameters,
+    ) -> Option<SignedQuote> {
+        if self.controls.degraded().is_some() || self.controls.overloaded().is_some() {
+            return None;
+        }
+
+        self.issued_quotes.get(peer, parameters, Instant::now())
+    }
+
+    /// Makes a quote signed with the identity and the maker key.
+    async fn make_quote(&mut self, verdict: Verdict) -> Result<SignedQuote> {
+        let (quote, depth) = self.bid_quote(verdict).await?;
+        let terms = QuoteTerms::new(&self.env_config, OffsetDateTime::now_utc() + QUOTE_VALIDITY);
+
+        Ok(SignedQuote::sign(quote, terms, &self.identity_key, &self.maker_key)?.with_depth(depth))
+    }
+
+    async fn bid_quote(&mut self, verdict: Verdict) -> Result<(Quote, Vec<DepthBucket>)> {
         let rate = self
             .latest_rate
             .latest_rate()
             .context("Failed to get latest rate")?;
+        let parameters = self.controls.parameters();
 
-        Ok(BidQuote {
+        let (rate, refused) = match verdict {
+            Verdict::Accept => (rate, false),
+            Verdict::Reject { .. } => (rate, true),
+            Verdict::Modify { ask_spread } => (rate.with_spread(ask_spread), false),
+        };
+
+        let mut quote = Quote {
             price: rate.ask().context("Failed to compute asking price")?,
-            min_quantity: min_buy,
-            max_quantity: max_buy,
-        })
+            min_quantity: parameters.min_buy,
+            max_quantity: parameters.max_buy,
+        };
+
+        let degraded = self.controls.degraded();
+        if let Some(reason) = &degraded {
+            tracing::debug!(%reason, "Not offering any Monero while a backend is degraded");
+        }
+        let overloaded = self.controls.overloaded();
+        if let Some(reason) = &overloaded {
+            tracing::debug!(%reason, "Not offering any Monero while overloaded");
+        }
+
+        // A maximum of zero tells the taker that we are currently not accepting
+        // any swaps.
+        if parameters.paused || refused || degraded.is_some() || overloaded.is_some() {
+            quote.max_quantity = bitcoin::Amount::ZERO;
+            return Ok((quote, Vec::new()));
+        }
+
+        // Only offer the Monero that is not reserved for other swaps yet
+        let (balance, unlocked) = self
+            .monero_wallet
+            .get_balances()
+            .await
+            .context("Failed to get Monero balance")?;
+        let available = |balance| {
+            self.xmr_reservations
+                .available(balance)
+                .and_then(|available| available.checked_sub(monero::MONERO_FEE))
+                .unwrap_or(monero::Amount::ZERO)
+        };
+        quote.max_quantity = min(quote.max_quantity, quote.price.btc_for(available(balance))?);
+
+        // The Monero that is not unlocked yet is only locked once it is
+        let instant = quote.price.btc_for(available(unlocked))?;
+        let depth = quote::depth(&quote, instant, self.env_config.monero_unlock_delay());
+
+        Ok((quote, depth))
+    }
+
+    async fn record_quote(&mut self, quote: QuoteRecord) {
+        let (peer, outcome) = (quote.peer_id, quote.outcome);
+
+        if let Err(error) = self.db.insert_quote(quote).await {
+            tracing::warn!(%peer, %outcome, "Failed to record quote: {:#}", error);
+        }
+    }
+
+    /// Asks the policy about a request of `peer`, a policy that fails refuses
+    /// the request.
+    ///
+    /// The returned future does not borrow the event loop, so that it can be
+    /// polled next to the swarm.
+    fn decide(&self, peer: PeerId, btc: Option<bitcoin::Amount>) -> BoxFuture<'static, Verdict> {
+        let policy = self.policy.clone();
+
+        async move {
+            let verdict = policy
+                .decide(policy::Request { peer, btc })
+                .await
+                .unwrap_or_else(|error| Verdict::Reject {
+                    reason: format!("{:#}", error),
+                });
+
+            if let Verdict::Reject { reason } = &verdict {
+                tracing::info!(%peer, "Request refused by policy: {}", reason);
+            }
+
+            verdict
+        }
+        .boxed()
+    }
+
+    /// Whether an encrypted signature is the message we expect from Bob next,
+    /// judging by the swap's state.
+    fn check_encrypted_signature_sequence(state: &AliceState) -> Result<()> {
+        match state.sequence() {
+            Some(mut sequence) => sequence.receive::<encrypted_signature::Request>()?,
+            None => bail!("The swap is {}, the encrypted signature is no longer needed", state),
+        }
+
+        Ok(())
+    }
+
+    /// Whether an encrypted signature for a swap that is not running was
+    /// applied before, according to its nonce or by applying it to the
+    /// persisted state, which leaves a state that received it unchanged.
+    ///
+    /// Fails if the persisted state does not accept the encrypted signature.
+    async fn encrypted_signature_applied(
+        &mut self,
+        swap_id: Uuid,
+        msg: &encrypted_signature::Request,
+    ) -> Result<bool> {
+        if outbox::was_received(
+            self.db.as_ref(),
+            swap_id,
+            MessageKind::EncryptedSignature,
+            msg.nonce,
+        )
+        .await
+        {
+            return Ok(true);
+        }
+
+        let state: AliceState = self.db.get_state(swap_id).await?.try_into()?;
+        let applied = match state
+            .clone()
+            .receive_encrypted_signature(msg.tx_redeem_encsig.clone(), msg.transcript)
+        {
+            Ok(applied) => applied,
+            Err(error) => {
+                Self::check_encrypted_signature_sequence(&state)?;
+                return Err(error);
+            }
+        };
+
+        Ok(applied == state)
+    }
+
+    /// Resolves the status of a swap reported by Bob against ours and returns
+    /// ours. Swaps of other peers are reported as unknown.
+    async fn resynchronize(&mut self, peer: PeerId, bob_status: Status) -> Status {
+        let swap_id = bob_status.swap_id;
+        let state: Option<AliceState> = match self.db.get_peer_id(swap_id).await {
+            Ok(swap_peer) if swap_peer == peer => self
+                .db
+                .get_state(swap_id)
+                .await
+                .ok()
+                .and_then(|state| state.try_into().ok()),
+            _ => None,
+        };
+        let status = match &state {
+            Some(state) => Status::alice(swap_id, state),
+            None => Status::unknown(swap_id),
+        };
+
+        match swap_status::resolve(&status, &bob_status) {
+            Resolution::PeerBehind(Phase::BtcLocked) => {
+                if let Some(AliceState::XmrLockTransferProofSent { transfer_proof, .. }) = state {
+                    tracing::info!(%swap_id, "Bob did not receive the transfer proof, sending it again");
+
+                    let request = transfer_proof::Request {
+                        swap_id,
+                        tx_lock_proof: transfer_proof,
+                        nonce: Some(Uuid::new_v4()),
+                    };
+                    self.send_transfer_proof(peer, request).await;
+                }
+            }
+            resolution if resolution.is_unsafe() => {
+                tracing::warn!(%swap_id, own = ?status.phase, bob = ?bob_status.phase, "Swap status of Bob contradicts ours ({:?})", resolution);
+            }
+            resolution => {
+                tracing::debug!(%swap_id, ?resolution, "Resynchronized swap with Bob");
+            }
+        }
+
+        status
+    }
+
+    /// Puts the transfer proof into the outbox and sends it, or leaves it
+    /// there until `peer` connects. A transfer proof that is in the outbox
+    /// already is sent with its original nonce.
+    async fn send_transfer_proof(&mut self, peer: PeerId, transfer_proof: transfer_proof::Request) {
+        let swap_id = transfer_proof.swap_id;
+        let message = match self
+            .db
+            .insert_outbound(peer, transfer_proof.clone().into())
+            .await
+        {
+            Ok(message) => message,
+            Err(error) => {
+                tracing::warn!(%swap_id, "Failed to put transfer proof into outbox: {:#}", error);
+                transfer_proof.into()
+            }
+        };
+
+        if !self
+            .swarm
+            .behaviour_mut()
+            .transfer_proof
+            .is_connected(&peer)
+        {
+            tracing::warn!(%peer, %swap_id, "No active connection to peer, sending transfer proof once it connects");
+            return;
+        }
+
+        self.send_message(peer, message);
+    }
+
+    /// Sends the messages left in the outbox for `peer`, those of swaps that
+    /// finished in the meantime are dropped.
+    async fn send_outbox(&mut self, peer: PeerId) {
+        let messages = match self.db.get_outbound(peer).await {
+            Ok(messages) => messages,
+            Err(error) => {
+                tracing::warn!(%peer, "Failed to load outbox: {:#}", error);
+                return;
+            }
+        };
+
+        for message in messages {
+            let swap_id = message.swap_id();
+            let kind = message.kind();
+
+            let finished = match self.db.get_state(swap_id).await {
+                Ok(state) => state.swap_finished(),
+                Err(_) => true,
+            };
+            if finished {
+                tracing::debug!(%peer, %swap_id, %kind, "Dropping message of finished swap from outbox");
+                if let Err(error) = self.db.remove_outbound(swap_id, kind).await {
+                    tracing::warn!(%swap_id, %kind, "Failed to remove message from outbox: {:#}", error);
+                }
+                continue;
+            }
+
+            tracing::debug!(%peer, %swap_id, %kind, "Sending message from outbox");
+            self.send_message(peer, message);
+        }
+    }
+
+    fn send_message(&mut self, peer: PeerId, message: outbox::Message) {
+        match message {
+            outbox::Message::TransferProof(request) => {
+                let swap_id = request.swap_id;
+                let id = self
+                    .swarm
+                    .behaviour_mut()
+                    .transfer_proof
+                    .send_request(&peer, request);
+                self.inflight_transfer_proofs.insert(id, swap_id);
+            }
+            message => {
+                tracing::warn!(%peer, swap_id = %message.swap_id(), kind = %message.kind(), "Alice does not send this kind of message, ignoring it");
+            }
+        }
+    }
+
+    /// Hands out our key share of a swap in which we punished `peer`, if the
+    /// policy agrees.
+    ///
+    /// The returned future asks the policy and does not borrow the event loop,
+    /// so that it can be polled next to the swarm.
+    async fn cooperative_xmr_redeem(
+        &mut self,
+        peer: PeerId,
+        swap_id: Uuid,
+    ) -> BoxFuture<'static, cooperative_xmr_redeem_after_punish::Response> {
+        let state: Option<AliceState> = match self.db.get_peer_id(swap_id).await {
+            Ok(swap_peer) if swap_peer == peer => self
+                .db
+                .get_state(swap_id)
+                .await
+                .ok()
+                .and_then(|state| state.try_into().ok()),
+            _ => None,
+        };
+
+        let reject = move |reason: RejectReason| {
+            tracing::info!(%swap_id, %peer, "Refused cooperative Monero redeem: {}", reason);
+
+            cooperative_xmr_redeem_after_punish::Response::Rejected { swap_id, reason }
+        };
+
+        let state3 = match state {
+            Some(AliceState::BtcPunished {
+                state3: Some(state3),
+            }) => state3,
+            Some(AliceState::BtcPunished { state3: None }) => {
+                return future::ready(reject(RejectReason::Declined(
+                    "the swap was punished before key shares were kept".to_owned(),
+                )))
+                .boxed()
+            }
+            Some(_) => return future::ready(reject(RejectReason::NotPunished)).boxed(),
+            None => return future::ready(reject(RejectReason::UnknownSwap)).boxed(),
+        };
+        let policy = self.policy.clone();
+
+        async move {
+            match policy.cooperative_redeem(peer, swap_id).await {
+                Ok(Verdict::Reject { reason }) => reject(RejectReason::Declined(reason)),
+                Err(error) => reject(RejectReason::Unavailable(format!("{:#}", error))),
+                Ok(_) => {
+                    tracing::info!(%swap_id, %peer, "Handing out key share to let Bob redeem the Monero of the punished swap");
+
+                    cooperative_xmr_redeem_after_punish::Response::Fulfilled {
+                        swap_id,
+                        s_a: state3.s_a(),
+                    }
+                }
+            }
+        }
+        .boxed()
+    }
+
+    /// Signs the terms of a swap Bob signed, provided they match our side of
+    /// the swap.
+    async fn countersign_receipt(
+        &mut self,
+        peer: PeerId,
+        request: receipt::Request,
+    ) -> Result<receipt::Signature> {
+        let terms = request.terms;
+        let swap_id = terms.swap_id;
+
+        if self.db.get_peer_id(swap_id).await.ok() != Some(peer) {
+            bail!("Unknown swap {}", swap_id)
+        }
+        request.signature.verify(&terms, peer)?;
+
+        let completed_at = OffsetDateTime::from_unix_timestamp(terms.completed_at)
+            .context("Invalid completion time")?;
+        if (OffsetDateTime::now_utc() - completed_at).abs() > RECEIPT_CLOCK_TOLERANCE {
+            bail!("The swap was not completed at {}", completed_at)
+        }
+
+        let states = self
+            .db
+            .get_states(swap_id)
+            .await?
+            .into_iter()
+            .map(TryInto::try_into)
+            .collect::<Result<Vec<AliceState>, _>>()?;
+        let own_terms = Terms::of_alice(
+            swap_id,
+            self.peer_id(),
+            peer,
+            &states,
+            terms.completed_at,
+        )?;
+        if own_terms != terms {
+            bail!("The terms do not match the swap")
+        }
+
+        receipt::Signature::sign(&terms, &self.identity_key)
     }
 
     async fn handle_execution_setup_done(
@@ -336,7 +1049,15 @@ where
         bob_peer_id: PeerId,
         swap_id: Uuid,
         state3: State3,
+        reservation: Reservation<monero::Amount>,
     ) {
+        // the cancel and punish transactions are kept right away, so the swap can
+        // be wound down even if its state is lost
+        if let Err(error) = self.store_presigned_transactions(swap_id, &state3).await {
+            tracing::warn!(%swap_id, "Unable to save presigned transactions in database: {:#}", error);
+            return;
+        }
+
         let handle = self.new_handle(bob_peer_id, swap_id);
 
         let initial_state = AliceState::Started {
@@ -351,15 +1072,32 @@ where
             db: self.db.clone(),
             state: initial_state,
             swap_id,
+            notifier: self.notifier.clone(),
+            cpfp: self.cpfp,
+            approvals: self.approvals.clone(),
+            policy: self.policy.clone(),
+            reservations: self.xmr_reservations.clone(),
+            reservation: Some(reservation),
         };
 
         // TODO: Consider adding separate components for start/resume of swaps
 
+        // swaps save the identity so they are resumed by the same identity
+        if let Err(error) = self.db.insert_identity(swap_id, &self.identity).await {
+            tracing::warn!(%swap_id, "Unable to save identity in database: {}", error);
+            return;
+        }
+
         // swaps save peer id so we can resume
         match self.db.insert_peer_id(swap_id, bob_peer_id).await {
             Ok(_) => {
                 if let Err(error) = self.swap_sender.send(swap).await {
                     tracing::warn!(%swap_id, "Failed to start swap: {}", error);
+                    return;
+                }
+
+                if let Some(notifier) = &self.notifier {
+                    notifier.notify(swap_id, Event::Started);
                 }
             }
             Err(error) => {
@@ -368,6 +1106,21 @@ where
         }
     }
 
+    async fn store_presigned_transactions(&mut self, swap_id: Uuid, state3: &State3) -> Result<()> {
+        let presigned = state3.presigned_transactions()?;
+        for transaction in &presigned {
+            self.db
+                .insert_presigned_transaction(swap_id, transaction)
+                .await?;
+        }
+
+        if let Some(watchtower) = &self.watchtower {
+            watchtower.spawn_register(swap_id, presigned);
+        }
+
+        Ok(())
+    }
+
     /// Create a new [`EventLoopHandle`] that is scoped for communication with
     /// the given peer.
     fn new_handle(&mut self, peer: PeerId, swap_id: Uuid) -> EventLoopHandle {
@@ -387,6 +1140,7 @@ where
                 let request = transfer_proof::Request {
                     swap_id,
                     tx_lock_proof: transfer_proof,
+                    nonce: Some(Uuid::new_v4()),
                 };
 
                 Ok((peer, request, responder))
@@ -420,7 +1174,9 @@ impl FixedRate {
 
 impl Default for FixedRate {
     fn default() -> Self {
-        let ask = bitcoin::Amount::from_btc(Self::RATE).expect("Static value should never fail");
+        let ask = Price::new(
+            bitcoin::Amount::from_btc(Self::RATE).expect("Static value should never fail"),
+        );
         let spread = Decimal::from(0u64);
 
         Self(Rate::new(ask, spread))
@@ -435,29 +1191,68 @@ impl LatestRate for FixedRate {
     }
 }
 
-/// Produces [`Rate`]s based on [`PriceUpdate`]s from kraken and a configured
-/// spread.
+/// Produces [`Rate`]s based on the median of the kraken [`PriceFeeds`]
+/// and the spread currently set in the [`TradingControls`].
 #[derive(Debug, Clone)]
 pub struct KrakenRate {
-    ask_spread: Decimal,
-    price_updates: kraken::PriceUpdates,
+    controls: TradingControls,
+    price_feeds: Arc<Mutex<PriceFeeds>>,
 }
 
 impl KrakenRate {
-    pub fn new(ask_spread: Decimal, price_updates: kraken::PriceUpdates) -> Self {
+    pub fn new(controls: TradingControls, price_feeds: PriceFeeds) -> Self {
         Self {
-            ask_spread,
-            price_updates,
+            controls,
+            price_feeds: Arc::new(Mutex::new(price_feeds)),
         }
     }
+
+    /// Returns a rate that applies the spread of `controls` to the same price
+    /// feeds. Updating the price feeds of either rate affects both.
+    pub fn with_controls(&self, controls: TradingControls) -> Self {
+        Self {
+            controls,
+            price_feeds: self.price_feeds.clone(),
+        }
+    }
+
+    /// How long ago the price tickers sent their last message, see
+    /// [`PriceFeeds::last_message_age`].
+    pub fn last_message_age(&self) -> Option<Duration> {
+        self.lock().last_message_age()
+    }
+
+    /// Whether the price tickers currently agree on a price.
+    pub fn cross_check(&self) -> Result<(), price_feeds::Error> {
+        self.lock().median().map(|_| ())
+    }
+
+    pub fn feed_statuses(&self) -> Vec<FeedStatus> {
+        self.lock().statuses()
+    }
+
+    /// Updates the price feeds used by this rate and all its clones, e.g.
+    /// after the price tickers in the config were changed.
+    pub fn update_price_feeds(
+        &self,
+        update: impl FnOnce(&mut PriceFeeds) -> Result<()>,
+    ) -> Result<()> {
+        update(&mut self.lock())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, PriceFeeds> {
+        self.price_feeds
+            .lock()
+            .expect("price feeds lock not poisoned")
+    }
 }
 
 impl LatestRate for KrakenRate {
-    type Error = kraken::Error;
+    type Error = price_feeds::Error;
 
     fn latest_rate(&mut self) -> Result<Rate, Self::Error> {
-        let update = self.price_updates.latest_update()?;
-        let rate = Rate::new(update.ask, self.ask_spread);
+        let ask = self.lock().median()?;
+        let rate = Rate::new(ask, self.controls.parameters().ask_spread);
 
         Ok(rate)
     }
@@ -465,13 +1260,13 @@ impl LatestRate for KrakenRate {
 
 #[derive(Debug)]
 pub struct EventLoopHandle {
-    recv_encrypted_signature: Option<bmrng::RequestReceiver<bitcoin::EncryptedSignature, ()>>,
+    recv_encrypted_signature: Option<bmrng::RequestReceiver<encrypted_signature::Request, ()>>,
     send_transfer_proof: Option<bmrng::RequestSender<monero::TransferProof, ()>>,
 }
 
 impl EventLoopHandle {
-    pub async fn recv_encrypted_signature(&mut self) -> Result<bitcoin::EncryptedSignature> {
-        let (tx_redeem_encsig, responder) = self
+    pub async fn recv_encrypted_signature(&mut self) -> Result<encrypted_signature::Request> {
+        let (msg, responder) = self
             .recv_encrypted_signature
             .take()
             .context("Encrypted signature was already received")?
@@ -482,7 +1277,7 @@ impl EventLoopHandle {
             .respond(())
             .context("Failed to acknowledge receipt of encrypted signature")?;
 
-        Ok(tx_redeem_encsig)
+        Ok(msg)
     }
 
     pub async fn send_transfer_proof(&mut self, msg: monero::TransferProof) -> Result<()> {
@@ -0,0 +1,195 @@
+//! Load shedding: new quotes and swaps are refused right away while the ASB is
+//! saturated, instead of timing out while it gets around to them.
+//!
+//! The ASB counts as saturated while
+//! - at least [`Limits::max_active_swaps`] swaps are running,
+//! - tasks wake up more than [`Limits::max_lag`] later than they asked to.
+//!
+//! The lag is measured by a probe that sleeps for [`PROBE_INTERVAL`]. Tokio
+//! does not report how many tasks are queued, but the longer the queue the
+//! later the probe is scheduled, and the event loops wait in the same queue.
+//!
+//! While saturated, quotes are handed out with a maximum of zero and swap
+//! setups are refused before the wallets are asked for anything, as if trading
+//! was paused. Swaps that are already set up are not affected.
+
+use std::convert::TryFrom;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+pub const PROBE_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Limits {
+    /// How many swaps may run at once across all identities, `None` for no
+    /// limit.
+    pub max_active_swaps: Option<usize>,
+    pub max_lag: Duration,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_active_swaps: None,
+            max_lag: Duration::from_millis(500),
+        }
+    }
+}
+
+/// A cheaply cloneable handle to the load of the ASB, shared between the swap
+/// executors and the network layer of all identities.
+#[derive(Debug, Clone, Default)]
+pub struct Load {
+    limits: Limits,
+    inner: Arc<Counters>,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    active_swaps: AtomicUsize,
+    lag_micros: AtomicU64,
+}
+
+impl Load {
+    pub fn new(limits: Limits) -> Self {
+        Self {
+            limits,
+            inner: Default::default(),
+        }
+    }
+
+    /// Counts a swap as running until the returned guard is dropped.
+    pub fn swap_started(&self) -> ActiveSwap {
+        self.inner.active_swaps.fetch_add(1, Ordering::SeqCst);
+
+        ActiveSwap {
+            counters: self.inner.clone(),
+        }
+    }
+
+    pub fn active_swaps(&self) -> usize {
+        self.inner.active_swaps.load(Ordering::SeqCst)
+    }
+
+    /// How late the probe woke up the last time.
+    pub fn lag(&self) -> Duration {
+        Duration::from_micros(self.inner.lag_micros.load(Ordering::SeqCst))
+    }
+
+    /// Why new quotes and swaps are refused, `None` if the ASB is not
+    /// saturated.
+    pub fn overloaded(&self) -> Option<String> {
+        let mut reasons = Vec::new();
+
+        let active_swaps = self.active_swaps();
+        if let Some(max_active_swaps) = self.limits.max_active_swaps {
+            if active_swaps >= max_active_swaps {
+                reasons.push(format!(
+                    "{} swaps are running, at most {} are allowed",
+                    active_swaps, max_active_swaps
+                ));
+            }
+        }
+
+        let lag = self.lag();
+        if lag > self.limits.max_lag {
+            reasons.push(format!("tasks are scheduled {}ms late", lag.as_millis()));
+        }
+
+        if reasons.is_empty() {
+            None
+        } else {
+            Some(reasons.join(", "))
+        }
+    }
+
+    /// Measures the lag every [`PROBE_INTERVAL`], never returns.
+    pub async fn run(self) {
+        let mut overloaded = false;
+
+        loop {
+            let start = Instant::now();
+            tokio::time::sleep(PROBE_INTERVAL).await;
+            self.record_lag(start.elapsed().saturating_sub(PROBE_INTERVAL));
+
+            match self.overloaded() {
+                Some(reason) if !overloaded => {
+                    tracing::warn!(%reason, "Refusing new quotes and swaps while overloaded");
+                    overloaded = true;
+                }
+                None if overloaded => {
+                    tracing::info!("Accepting new quotes and swaps again");
+                    overloaded = false;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn record_lag(&self, lag: Duration) {
+        let lag_micros = u64::try_from(lag.as_micros()).unwrap_or(u64::MAX);
+        self.inner.lag_micros.store(lag_micros, Ordering::SeqCst);
+    }
+}
+
+/// A running swap, see [`Load::swap_started`].
+#[derive(Debug)]
+pub struct ActiveSwap {
+    counters: Arc<Counters>,
+}
+
+impl Drop for ActiveSwap {
+    fn drop(&mut self) {
+        self.counters.active_swaps.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn swaps_count_as_active_until_their_guard_is_dropped() {
+        let load = Load::new(Limits {
+            max_active_swaps: Some(2),
+            ..Limits::default()
+        });
+
+        let first = load.swap_started();
+        assert_eq!(load.overloaded(), None);
+
+        let second = load.swap_started();
+        assert_eq!(
+            load.overloaded(),
+            Some("2 swaps are running, at most 2 are allowed".to_owned())
+        );
+
+        drop(first);
+        assert_eq!(load.overloaded(), None);
+        drop(second);
+        assert_eq!(load.active_swaps(), 0);
+    }
+
+    #[test]
+    fn lag_above_the_limit_is_overloaded() {
+        let load = Load::default();
+
+        load.record_lag(Duration::from_millis(500));
+        assert_eq!(load.overloaded(), None);
+
+        load.record_lag(Duration::from_millis(750));
+        assert_eq!(
+            load.overloaded(),
+            Some("tasks are scheduled 750ms late".to_owned())
+        );
+    }
+
+    #[test]
+    fn no_limit_on_active_swaps_by_default() {
+        let load = Load::default();
+        let _swaps = (0..1000).map(|_| load.swap_started()).collect::<Vec<_>>();
+
+        assert_eq!(load.overloaded(), None);
+    }
+}
@@ -0,0 +1,130 @@
+//! The maker key of the default maker identity.
+//!
+//! By default the key is derived from the seed. Once an operator rotates it,
+//! the new key is stored in the data directory next to the rotations that
+//! lead to it and takes precedence over the derived one from then on.
+
+use crate::fs::ensure_directory_exists;
+use crate::network::maker_key::{MakerKey, Rotation};
+use crate::seed::Seed;
+use anyhow::{bail, Context, Result};
+use libp2p::identity::ed25519;
+use pem::Pem;
+use std::fs;
+use std::path::{Path, PathBuf};
+use time::OffsetDateTime;
+
+const KEY_FILE_NAME: &str = "maker_key.pem";
+const ROTATIONS_FILE_NAME: &str = "maker_key_rotations.json";
+const PEM_TAG: &str = "MAKER KEY";
+
+/// Returns the rotated key stored in `data_dir` or, if there is none, the key
+/// derived from the seed.
+pub fn load(data_dir: &Path, seed: &Seed) -> Result<MakerKey> {
+    let key_file = data_dir.join(KEY_FILE_NAME);
+
+    if !key_file.exists() {
+        return Ok(MakerKey::new(seed.derive_maker_key()));
+    }
+
+    let contents = fs::read_to_string(&key_file)
+        .with_context(|| format!("Failed to read maker key file {}", key_file.display()))?;
+    let pem = pem::parse(contents).context("Maker key file is not PEM encoded")?;
+
+    if pem.tag != PEM_TAG {
+        bail!(
+            "Maker key file {} has unexpected tag {}",
+            key_file.display(),
+            pem.tag
+        )
+    }
+
+    let mut bytes = pem.contents;
+    let secret = ed25519::SecretKey::from_bytes(&mut bytes)
+        .with_context(|| format!("Maker key file {} is corrupted", key_file.display()))?;
+
+    let rotations_file = rotations_file_path(data_dir);
+    let rotations = fs::read(&rotations_file).with_context(|| {
+        format!(
+            "Failed to read the rotations of the maker key from {}",
+            rotations_file.display()
+        )
+    })?;
+    let rotations = serde_json::from_slice::<Vec<Rotation>>(&rotations)
+        .with_context(|| format!("{} is corrupted", rotations_file.display()))?;
+
+    tracing::debug!("Reading in rotated maker key from {}", key_file.display());
+
+    MakerKey::with_rotations(secret.into(), rotations)
+}
+
+/// Replaces the maker key with a random one, signing the rotation with the
+/// current key, and stores both in `data_dir`.
+///
+/// Takers keep recognizing the maker by the key it started out with, the peer
+/// id does not change.
+pub fn rotate(data_dir: &Path, seed: &Seed) -> Result<MakerKey> {
+    let current = load(data_dir, seed)?;
+    let secret = ed25519::SecretKey::generate();
+    let rotated = current.rotate(secret.clone().into(), OffsetDateTime::now_utc());
+
+    let key_file = data_dir.join(KEY_FILE_NAME);
+    let rotations_file = rotations_file_path(data_dir);
+    let pem = Pem {
+        tag: PEM_TAG.to_owned(),
+        contents: secret.as_ref().to_vec(),
+    };
+
+    ensure_directory_exists(&key_file)?;
+    // The rotations are written first, a key without the rotations that lead
+    // to it would not be accepted by takers that know the previous key.
+    fs::write(
+        &rotations_file,
+        serde_json::to_vec_pretty(rotated.rotations())?,
+    )
+    .with_context(|| format!("Failed to write {}", rotations_file.display()))?;
+    fs::write(&key_file, pem::encode(&pem))
+        .with_context(|| format!("Failed to write maker key file {}", key_file.display()))?;
+
+    Ok(rotated)
+}
+
+fn rotations_file_path(data_dir: &Path) -> PathBuf {
+    data_dir.join(ROTATIONS_FILE_NAME)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn given_no_key_file_then_key_is_derived_from_seed() {
+        let dir = tempdir().unwrap();
+        let seed = Seed::random().unwrap();
+
+        let key = load(dir.path(), &seed).unwrap();
+
+        assert_eq!(
+            key.public(),
+            MakerKey::new(seed.derive_maker_key()).public()
+        );
+        assert!(key.rotations().is_empty());
+    }
+
+    #[test]
+    fn rotated_keys_keep_the_id_across_restarts() {
+        let dir = tempdir().unwrap();
+        let seed = Seed::random().unwrap();
+        let original = load(dir.path(), &seed).unwrap();
+
+        rotate(dir.path(), &seed).unwrap();
+        let rotated = rotate(dir.path(), &seed).unwrap();
+        let loaded = load(dir.path(), &seed).unwrap();
+
+        assert_eq!(loaded.public(), rotated.public());
+        assert_ne!(loaded.public(), original.public());
+        assert_eq!(loaded.id(), original.id());
+        assert_eq!(loaded.rotations().len(), 2);
+    }
+}
@@ -1,23 +1,32 @@
 use crate::asb::event_loop::LatestRate;
-use crate::env;
-use crate::network::quote::BidQuote;
+use crate::asb::policy::Verdict;
+use crate::asb::TradingControls;
+use crate::network::quote::SignedQuote;
 use crate::network::rendezvous::XmrBtcNamespace;
 use crate::network::swap_setup::alice;
 use crate::network::swap_setup::alice::WalletSnapshot;
 use crate::network::transport::authenticate_and_multiplex;
-use crate::network::{encrypted_signature, quote, transfer_proof};
+use crate::network::{
+    connection, cooperative_xmr_redeem_after_punish, encrypted_signature, hole_punch, quote,
+    receipt, swap_status, transfer_proof,
+};
 use crate::protocol::alice::State3;
+use crate::reservations::Reservation;
+use crate::{bitcoin, env, monero};
 use anyhow::{anyhow, Error, Result};
-use futures::FutureExt;
-use libp2p::core::connection::ConnectionId;
+use futures::{AsyncRead, AsyncWrite, FutureExt};
+use libp2p::core::connection::{ConnectedPoint, ConnectionId};
 use libp2p::core::muxing::StreamMuxerBox;
 use libp2p::core::transport::Boxed;
 use libp2p::dns::TokioDnsConfig;
-use libp2p::ping::{Ping, PingConfig, PingEvent};
+use libp2p::ping::{Ping, PingEvent};
+use libp2p::relay::v2::client::{self, Client};
 use libp2p::request_response::{RequestId, ResponseChannel};
+use libp2p::swarm::behaviour::toggle::Toggle;
+use libp2p::swarm::dial_opts::{DialOpts, PeerCondition};
 use libp2p::swarm::{
-    DialPeerCondition, IntoProtocolsHandler, NetworkBehaviour, NetworkBehaviourAction,
-    PollParameters, ProtocolsHandler,
+    ConnectionHandler, IntoConnectionHandler, NetworkBehaviour, NetworkBehaviourAction,
+    PollParameters,
 };
 use libp2p::tcp::TokioTcpConfig;
 use libp2p::websocket::WsConfig;
@@ -31,13 +40,36 @@ pub mod transport {
 
     /// Creates the libp2p transport for the ASB.
     pub fn new(identity: &identity::Keypair) -> Result<Boxed<(PeerId, StreamMuxerBox)>> {
-        let tcp = TokioTcpConfig::new().nodelay(true);
+        authenticate_and_multiplex(tcp_and_websocket(false)?, identity)
+    }
+
+    /// Creates the libp2p transport for an ASB that listens through circuit
+    /// relays, together with the relay [`Client`] behaviour that reserves a
+    /// slot on each relay the swarm listens through.
+    ///
+    /// Outgoing TCP connections are made from the port of a listen address,
+    /// the peers a relayed connection is upgraded with dial that port.
+    pub fn new_with_relay(
+        identity: &identity::Keypair,
+    ) -> Result<(Boxed<(PeerId, StreamMuxerBox)>, Client)> {
+        let (relay_transport, client) =
+            Client::new_transport_and_behaviour(identity.public().to_peer_id());
+        let transport = relay_transport.or_transport(tcp_and_websocket(true)?);
+
+        Ok((
+            authenticate_and_multiplex(transport.boxed(), identity)?,
+            client,
+        ))
+    }
+
+    fn tcp_and_websocket(
+        port_reuse: bool,
+    ) -> Result<Boxed<impl AsyncRead + AsyncWrite + Unpin + Send + 'static>> {
+        let tcp = TokioTcpConfig::new().nodelay(true).port_reuse(port_reuse);
         let tcp_with_dns = TokioDnsConfig::system(tcp)?;
         let websocket_with_dns = WsConfig::new(tcp_with_dns.clone());
 
-        let transport = tcp_with_dns.or_transport(websocket_with_dns).boxed();
-
-        authenticate_and_multiplex(transport, identity)
+        Ok(tcp_with_dns.or_transport(websocket_with_dns).boxed())
     }
 }
 
@@ -46,21 +78,26 @@ pub mod behaviour {
 
     #[allow(clippy::large_enum_variant)]
     #[derive(Debug)]
+    #[non_exhaustive]
     pub enum OutEvent {
         SwapSetupInitiated {
-            send_wallet_snapshot: bmrng::RequestReceiver<bitcoin::Amount, WalletSnapshot>,
+            peer: PeerId,
+            send_wallet_snapshot:
+                bmrng::RequestReceiver<bitcoin::Amount, (WalletSnapshot, Verdict)>,
+            set_up_before: bmrng::RequestReceiver<Uuid, bool>,
         },
         SwapSetupCompleted {
             peer_id: PeerId,
             swap_id: Uuid,
             state3: State3,
+            reservation: Reservation<monero::Amount>,
         },
         SwapDeclined {
             peer: PeerId,
             error: alice::Error,
         },
         QuoteRequested {
-            channel: ResponseChannel<BidQuote>,
+            channel: ResponseChannel<SignedQuote>,
             peer: PeerId,
         },
         TransferProofAcknowledged {
@@ -72,7 +109,24 @@ pub mod behaviour {
             channel: ResponseChannel<()>,
             peer: PeerId,
         },
+        SwapStatusRequested {
+            status: swap_status::Status,
+            channel: ResponseChannel<swap_status::Status>,
+            peer: PeerId,
+        },
+        CooperativeXmrRedeemRequested {
+            swap_id: Uuid,
+            channel: ResponseChannel<cooperative_xmr_redeem_after_punish::Response>,
+            peer: PeerId,
+        },
+        ReceiptRequested {
+            request: Box<receipt::Request>,
+            channel: ResponseChannel<receipt::Response>,
+            peer: PeerId,
+        },
         Rendezvous(libp2p::rendezvous::client::Event),
+        Relay(client::Event),
+        HolePunch(hole_punch::OutEvent),
         Failure {
             peer: PeerId,
             error: Error,
@@ -106,11 +160,18 @@ pub mod behaviour {
     where
         LR: LatestRate + Send + 'static,
     {
-        pub rendezvous: libp2p::swarm::toggle::Toggle<rendezous::Behaviour>,
+        pub rendezvous: Toggle<rendezous::Behaviour>,
         pub quote: quote::Behaviour,
         pub swap_setup: alice::Behaviour<LR>,
         pub transfer_proof: transfer_proof::Behaviour,
         pub encrypted_signature: encrypted_signature::Behaviour,
+        pub swap_status: swap_status::Behaviour,
+        pub cooperative_xmr_redeem: cooperative_xmr_redeem_after_punish::Behaviour,
+        pub receipt: receipt::Behaviour,
+        /// Only enabled if the ASB listens through circuit relays.
+        relay: Toggle<Client>,
+        /// Upgrades relayed connections, enabled together with `relay`.
+        hole_punch: Toggle<hole_punch::Behaviour>,
 
         /// Ping behaviour that ensures that the underlying network connection
         /// is still alive. If the ping fails a connection close event
@@ -123,15 +184,15 @@ pub mod behaviour {
         LR: LatestRate + Send + 'static,
     {
         pub fn new(
-            min_buy: bitcoin::Amount,
-            max_buy: bitcoin::Amount,
+            controls: TradingControls,
             latest_rate: LR,
-            resume_only: bool,
             env_config: env::Config,
             rendezvous_params: Option<(identity::Keypair, PeerId, Multiaddr, XmrBtcNamespace)>,
+            relay: Option<Client>,
+            hole_punch: Option<hole_punch::Behaviour>,
         ) -> Self {
             Self {
-                rendezvous: libp2p::swarm::toggle::Toggle::from(rendezvous_params.map(
+                rendezvous: Toggle::from(rendezvous_params.map(
                     |(identity, rendezvous_peer_id, rendezvous_address, namespace)| {
                         rendezous::Behaviour::new(
                             identity,
@@ -143,16 +204,15 @@ pub mod behaviour {
                     },
                 )),
                 quote: quote::asb(),
-                swap_setup: alice::Behaviour::new(
-                    min_buy,
-                    max_buy,
-                    env_config,
-                    latest_rate,
-                    resume_only,
-                ),
+                swap_setup: alice::Behaviour::new(controls, env_config, latest_rate),
                 transfer_proof: transfer_proof::alice(),
                 encrypted_signature: encrypted_signature::alice(),
-                ping: Ping::new(PingConfig::new().with_keep_alive(true)),
+                swap_status: swap_status::alice(),
+                cooperative_xmr_redeem: cooperative_xmr_redeem_after_punish::alice(),
+                receipt: receipt::alice(),
+                relay: relay.into(),
+                hole_punch: hole_punch.into(),
+                ping: connection::ping(),
             }
         }
     }
@@ -163,6 +223,24 @@ pub mod behaviour {
         }
     }
 
+    impl From<()> for OutEvent {
+        fn from(_: ()) -> Self {
+            OutEvent::Other
+        }
+    }
+
+    impl From<client::Event> for OutEvent {
+        fn from(event: client::Event) -> Self {
+            OutEvent::Relay(event)
+        }
+    }
+
+    impl From<hole_punch::OutEvent> for OutEvent {
+        fn from(event: hole_punch::OutEvent) -> Self {
+            OutEvent::HolePunch(event)
+        }
+    }
+
     impl From<libp2p::rendezvous::client::Event> for OutEvent {
         fn from(event: libp2p::rendezvous::client::Event) -> Self {
             OutEvent::Rendezvous(event)
@@ -226,14 +304,28 @@ pub mod rendezous {
                 self.registration_ttl,
             );
         }
+
+        fn dial_rendezvous_point(
+            &mut self,
+        ) -> NetworkBehaviourAction<
+            libp2p::rendezvous::client::Event,
+            <Self as NetworkBehaviour>::ConnectionHandler,
+        > {
+            NetworkBehaviourAction::Dial {
+                opts: DialOpts::peer_id(self.rendezvous_peer_id)
+                    .condition(PeerCondition::Disconnected)
+                    .build(),
+                handler: self.inner.new_handler(),
+            }
+        }
     }
 
     impl NetworkBehaviour for Behaviour {
-        type ProtocolsHandler =
-            <libp2p::rendezvous::client::Behaviour as NetworkBehaviour>::ProtocolsHandler;
+        type ConnectionHandler =
+            <libp2p::rendezvous::client::Behaviour as NetworkBehaviour>::ConnectionHandler;
         type OutEvent = libp2p::rendezvous::client::Event;
 
-        fn new_handler(&mut self) -> Self::ProtocolsHandler {
+        fn new_handler(&mut self) -> Self::ConnectionHandler {
             self.inner.new_handler()
         }
 
@@ -245,8 +337,15 @@ pub mod rendezous {
             vec![]
         }
 
-        fn inject_connected(&mut self, peer_id: &PeerId) {
-            if peer_id == &self.rendezvous_peer_id {
+        fn inject_connection_established(
+            &mut self,
+            peer_id: &PeerId,
+            _connection: &ConnectionId,
+            _endpoint: &ConnectedPoint,
+            _failed_addresses: Option<&Vec<Multiaddr>>,
+            other_established: usize,
+        ) {
+            if peer_id == &self.rendezvous_peer_id && other_established == 0 {
                 self.connection_status = ConnectionStatus::Connected;
 
                 match &self.registration_status {
@@ -260,8 +359,15 @@ pub mod rendezous {
             }
         }
 
-        fn inject_disconnected(&mut self, peer_id: &PeerId) {
-            if peer_id == &self.rendezvous_peer_id {
+        fn inject_connection_closed(
+            &mut self,
+            peer_id: &PeerId,
+            _connection: &ConnectionId,
+            _endpoint: &ConnectedPoint,
+            _handler: <Self::ConnectionHandler as IntoConnectionHandler>::Handler,
+            remaining_established: usize,
+        ) {
+            if peer_id == &self.rendezvous_peer_id && remaining_established == 0 {
                 self.connection_status = ConnectionStatus::Disconnected;
             }
         }
@@ -270,18 +376,18 @@ pub mod rendezous {
             &mut self,
             peer_id: PeerId,
             connection: ConnectionId,
-            event: <<Self::ProtocolsHandler as IntoProtocolsHandler>::Handler as ProtocolsHandler>::OutEvent,
+            event: <<Self::ConnectionHandler as IntoConnectionHandler>::Handler as ConnectionHandler>::OutEvent,
         ) {
             self.inner.inject_event(peer_id, connection, event)
         }
 
         fn inject_dial_failure(
             &mut self,
-            peer_id: &PeerId,
-            _handler: Self::ProtocolsHandler,
-            _error: DialError,
+            peer_id: Option<PeerId>,
+            _handler: Self::ConnectionHandler,
+            _error: &DialError,
         ) {
-            if peer_id == &self.rendezvous_peer_id {
+            if peer_id == Some(self.rendezvous_peer_id) {
                 self.connection_status = ConnectionStatus::Disconnected;
             }
         }
@@ -291,17 +397,13 @@ pub mod rendezous {
             &mut self,
             cx: &mut std::task::Context<'_>,
             params: &mut impl PollParameters,
-        ) -> Poll<NetworkBehaviourAction<Self::OutEvent, Self::ProtocolsHandler>> {
+        ) -> Poll<NetworkBehaviourAction<Self::OutEvent, Self::ConnectionHandler>> {
             match &mut self.registration_status {
                 RegistrationStatus::RegisterOnNextConnection => match self.connection_status {
                     ConnectionStatus::Disconnected => {
                         self.connection_status = ConnectionStatus::Dialling;
 
-                        return Poll::Ready(NetworkBehaviourAction::DialPeer {
-                            peer_id: self.rendezvous_peer_id,
-                            condition: DialPeerCondition::Disconnected,
-                            handler: Self::ProtocolsHandler::new(Duration::from_secs(30)),
-                        });
+                        return Poll::Ready(self.dial_rendezvous_point());
                     }
                     ConnectionStatus::Dialling => {}
                     ConnectionStatus::Connected => {
@@ -320,11 +422,7 @@ pub mod rendezous {
                                 self.registration_status =
                                     RegistrationStatus::RegisterOnNextConnection;
 
-                                return Poll::Ready(NetworkBehaviourAction::DialPeer {
-                                    peer_id: self.rendezvous_peer_id,
-                                    condition: DialPeerCondition::Disconnected,
-                                    handler: Self::ProtocolsHandler::new(Duration::from_secs(30)),
-                                });
+                                return Poll::Ready(self.dial_rendezvous_point());
                             }
                             ConnectionStatus::Dialling => {}
                         }
@@ -0,0 +1,160 @@
+//! Webhook notifications about the progress of swaps.
+//!
+//! Every notification is posted as JSON to the configured URL. The body is
+//! signed with HMAC-SHA256 using the configured secret, the hex encoded
+//! signature is sent in the `X-Swap-Signature` header so the receiver can
+//! verify that the notification was sent by this ASB. Notifications that
+//! cannot be delivered are retried in the background, they never hold up the
+//! swap.
+
+use crate::protocol::alice::AliceState;
+use anyhow::{anyhow, Result};
+use hmac::{Hmac, Mac, NewMac};
+use serde::Serialize;
+use sha2::Sha256;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use url::Url;
+use uuid::Uuid;
+
+pub const SIGNATURE_HEADER: &str = "X-Swap-Signature";
+
+/// Delivery of a notification is given up after this long.
+const MAX_RETRY_DURATION: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, strum::Display)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+#[non_exhaustive]
+pub enum Event {
+    Started,
+    BtcLocked,
+    XmrLocked,
+    Completed,
+    Refunded,
+    Punished,
+}
+
+impl Event {
+    /// The event of reaching `state`, if any.
+    ///
+    /// The start of a swap is reported when it is set up, it is not a
+    /// transition of a running swap.
+    pub fn reached(state: &AliceState) -> Option<Self> {
+        match state {
+            AliceState::BtcLocked { .. } => Some(Event::BtcLocked),
+            AliceState::XmrLocked { .. } => Some(Event::XmrLocked),
+            AliceState::BtcRedeemed => Some(Event::Completed),
+            AliceState::XmrRefunded => Some(Event::Refunded),
+            AliceState::BtcPunished { .. } => Some(Event::Punished),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Notification {
+    swap_id: Uuid,
+    event: Event,
+    timestamp: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Notifier {
+    url: Url,
+    secret: String,
+    client: reqwest::Client,
+}
+
+impl Notifier {
+    pub fn new(url: Url, secret: String) -> Self {
+        Self {
+            url,
+            secret,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Delivers the notification in the background.
+    pub fn notify(&self, swap_id: Uuid, event: Event) {
+        let notifier = self.clone();
+
+        tokio::spawn(async move {
+            let backoff = backoff::ExponentialBackoff {
+                max_elapsed_time: Some(MAX_RETRY_DURATION),
+                ..backoff::ExponentialBackoff::default()
+            };
+
+            let result = backoff::future::retry_notify(
+                backoff,
+                || async { Ok(notifier.send(swap_id, event).await?) },
+                |error, next: Duration| {
+                    tracing::debug!(%swap_id, %event, "Failed to deliver notification, retrying in {}s: {:#}", next.as_secs(), error);
+                },
+            )
+            .await;
+
+            if let Err(error) = result {
+                tracing::warn!(%swap_id, %event, "Giving up delivering notification: {:#}", error);
+            }
+        });
+    }
+
+    async fn send(&self, swap_id: Uuid, event: Event) -> Result<()> {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let body = serde_json::to_string(&Notification {
+            swap_id,
+            event,
+            timestamp,
+        })?;
+
+        self.client
+            .post(self.url.clone())
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(SIGNATURE_HEADER, sign(&self.secret, &body)?)
+            .body(body)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+}
+
+fn sign(secret: &str, body: &str) -> Result<String> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .map_err(|_| anyhow!("Invalid webhook secret"))?;
+    mac.update(body.as_bytes());
+
+    Ok(hex::encode(mac.finalize().into_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signature_matches_rfc_4231_test_case() {
+        // Test case 2 of RFC 4231
+        let signature = sign("Jefe", "what do ya want for nothing?").unwrap();
+
+        assert_eq!(
+            signature,
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn events_are_serialized_in_snake_case() {
+        let notification = Notification {
+            swap_id: Uuid::nil(),
+            event: Event::BtcLocked,
+            timestamp: 1_636_000_000,
+        };
+
+        assert_eq!(
+            serde_json::to_string(&notification).unwrap(),
+            r#"{"swap_id":"00000000-0000-0000-0000-000000000000","event":"btc_locked","timestamp":1636000000}"#
+        );
+        assert_eq!(Event::XmrLocked.to_string(), "xmr_locked");
+    }
+}
@@ -0,0 +1,102 @@
+//! The libp2p identity of the default maker identity.
+//!
+//! By default the identity is derived from the seed, which keeps the peer id
+//! stable across restarts. An operator can regenerate it, in which case the new
+//! identity is stored in the data directory and takes precedence over the
+//! derived one from then on.
+
+use crate::fs::ensure_directory_exists;
+use crate::seed::Seed;
+use anyhow::{bail, Context, Result};
+use libp2p::identity;
+use libp2p::identity::ed25519;
+use pem::Pem;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const FILE_NAME: &str = "identity.pem";
+const PEM_TAG: &str = "LIBP2P IDENTITY";
+
+/// Returns the regenerated identity stored in `data_dir` or, if there is none,
+/// the identity derived from the seed.
+pub fn load(data_dir: &Path, seed: &Seed) -> Result<identity::Keypair> {
+    let file = file_path(data_dir);
+
+    if !file.exists() {
+        return Ok(seed.derive_libp2p_identity());
+    }
+
+    let contents = fs::read_to_string(&file)
+        .with_context(|| format!("Failed to read identity file {}", file.display()))?;
+    let pem = pem::parse(contents).context("Identity file is not PEM encoded")?;
+
+    if pem.tag != PEM_TAG {
+        bail!(
+            "Identity file {} has unexpected tag {}",
+            file.display(),
+            pem.tag
+        )
+    }
+
+    let mut bytes = pem.contents;
+    let secret = ed25519::SecretKey::from_bytes(&mut bytes)
+        .with_context(|| format!("Identity file {} is corrupted", file.display()))?;
+
+    tracing::debug!("Reading in regenerated identity from {}", file.display());
+
+    Ok(identity::Keypair::Ed25519(secret.into()))
+}
+
+/// Replaces the identity with a random one and stores it in `data_dir`.
+///
+/// Takers that remembered the previous peer id will no longer be able to reach
+/// the ASB under it.
+pub fn regenerate(data_dir: &Path) -> Result<identity::Keypair> {
+    let file = file_path(data_dir);
+    let secret = ed25519::SecretKey::generate();
+
+    let pem = Pem {
+        tag: PEM_TAG.to_owned(),
+        contents: secret.as_ref().to_vec(),
+    };
+
+    ensure_directory_exists(&file)?;
+    fs::write(&file, pem::encode(&pem))
+        .with_context(|| format!("Failed to write identity file {}", file.display()))?;
+
+    Ok(identity::Keypair::Ed25519(secret.into()))
+}
+
+fn file_path(data_dir: &Path) -> PathBuf {
+    data_dir.join(FILE_NAME)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn given_no_identity_file_then_identity_is_derived_from_seed() {
+        let dir = tempdir().unwrap();
+        let seed = Seed::random().unwrap();
+
+        let first = load(dir.path(), &seed).unwrap();
+        let second = load(dir.path(), &seed).unwrap();
+
+        assert_eq!(first.public(), seed.derive_libp2p_identity().public());
+        assert_eq!(first.public(), second.public());
+    }
+
+    #[test]
+    fn regenerated_identity_replaces_derived_one_across_restarts() {
+        let dir = tempdir().unwrap();
+        let seed = Seed::random().unwrap();
+
+        let regenerated = regenerate(dir.path()).unwrap();
+        let loaded = load(dir.path(), &seed).unwrap();
+
+        assert_ne!(regenerated.public(), seed.derive_libp2p_identity().public());
+        assert_eq!(loaded.public(), regenerated.public());
+    }
+}
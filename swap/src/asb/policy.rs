@@ -0,0 +1,307 @@
+//! Custom acceptance logic of the maker.
+//!
+//! Before handing out a quote and before accepting a swap the ASB asks its
+//! [`Policy`] for a [`Verdict`]. The verdict can reject the request or change
+//! the spread applied to the market price, e.g. to refuse certain peers or to
+//! charge more for large swaps. The limits and the pause switch of the
+//! [`TradingControls`](crate::asb::TradingControls) are checked regardless of
+//! the policy.
+//!
+//! The policy also decides whether a punished taker gets Alice's key share to
+//! redeem the Monero with the `cooperative_xmr_redeem_after_punish`
+//! protocol.
+//! Any verdict but a rejection hands it out. Only a webhook can do so, the
+//! default policy keeps the Monero.
+//!
+//! Before the Monero of a swap is locked the policy is shown the outputs that
+//! funded the Bitcoin lock transaction of the taker, e.g. to screen them
+//! against a list of blocked transactions. A rejection aborts the swap, the
+//! taker refunds the Bitcoin once the cancel timelock expired. Any other
+//! verdict lets the swap continue.
+
+use crate::bitcoin;
+use ::bitcoin::OutPoint;
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use libp2p::PeerId;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use url::Url;
+use uuid::Uuid;
+
+/// Webhooks are called while the taker waits for a response.
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Request {
+    pub peer: PeerId,
+    /// The amount the taker wants to swap, `None` for a quote request.
+    pub btc: Option<bitcoin::Amount>,
+}
+
+/// The Bitcoin lock transaction of a swap whose Monero is about to be locked.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Funding {
+    pub peer: PeerId,
+    pub swap_id: Uuid,
+    /// The outputs spent by the lock transaction.
+    pub outpoints: Vec<OutPoint>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "verdict", rename_all = "snake_case")]
+pub enum Verdict {
+    Accept,
+    Reject {
+        reason: String,
+    },
+    /// Accept, but apply this spread instead of the configured one.
+    Modify {
+        ask_spread: Decimal,
+    },
+}
+
+#[async_trait]
+pub trait Policy: Send + Sync {
+    async fn decide(&self, request: Request) -> Result<Verdict>;
+
+    /// Whether `peer` may redeem the Monero of the punished swap.
+    async fn cooperative_redeem(&self, peer: PeerId, swap_id: Uuid) -> Result<Verdict>;
+
+    /// Whether the swap may continue with the Bitcoin it was funded with.
+    async fn vet_funding(&self, funding: Funding) -> Result<Verdict>;
+}
+
+/// The default policy, every request is judged by the trading controls alone
+/// and every funding is accepted. Cooperative redeems are declined, the
+/// punished taker forfeited the Monero.
+#[derive(Debug, Clone, Copy)]
+pub struct AcceptAll;
+
+#[async_trait]
+impl Policy for AcceptAll {
+    async fn decide(&self, _: Request) -> Result<Verdict> {
+        Ok(Verdict::Accept)
+    }
+
+    async fn cooperative_redeem(&self, _: PeerId, _: Uuid) -> Result<Verdict> {
+        Ok(Verdict::Reject {
+            reason: "cooperative redeems are not enabled".to_owned(),
+        })
+    }
+
+    async fn vet_funding(&self, _: Funding) -> Result<Verdict> {
+        Ok(Verdict::Accept)
+    }
+}
+
+/// Posts every request as JSON to an external service, which responds with
+/// the verdict, e.g. `{"verdict":"reject","reason":"blocked"}`.
+#[derive(Debug, Clone)]
+pub struct Webhook {
+    url: Url,
+    client: reqwest::Client,
+}
+
+#[derive(Debug, Serialize)]
+struct WebhookRequest {
+    kind: &'static str,
+    peer_id: String,
+    btc_sat: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    swap_id: Option<Uuid>,
+    /// The outpoints as `<txid>:<vout>`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    funding: Vec<String>,
+}
+
+impl Webhook {
+    pub fn new(url: Url) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(WEBHOOK_TIMEOUT)
+            .build()?;
+
+        Ok(Self { url, client })
+    }
+
+    async fn post(&self, request: WebhookRequest) -> Result<Verdict> {
+        let body = serde_json::to_string(&request)?;
+
+        let response = self
+            .client
+            .post(self.url.clone())
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body)
+            .send()
+            .await
+            .with_context(|| format!("Failed to call policy webhook {}", self.url))?
+            .error_for_status()?
+            .text()
+            .await?;
+
+        parse_verdict(&response)
+    }
+}
+
+#[async_trait]
+impl Policy for Webhook {
+    async fn decide(&self, request: Request) -> Result<Verdict> {
+        self.post(request.into()).await
+    }
+
+    async fn cooperative_redeem(&self, peer: PeerId, swap_id: Uuid) -> Result<Verdict> {
+        self.post(WebhookRequest::cooperative_redeem(peer, swap_id))
+            .await
+    }
+
+    async fn vet_funding(&self, funding: Funding) -> Result<Verdict> {
+        self.post(funding.into()).await
+    }
+}
+
+impl WebhookRequest {
+    fn cooperative_redeem(peer: PeerId, swap_id: Uuid) -> Self {
+        Self {
+            kind: "cooperative_redeem",
+            peer_id: peer.to_string(),
+            btc_sat: None,
+            swap_id: Some(swap_id),
+            funding: vec![],
+        }
+    }
+}
+
+impl From<Request> for WebhookRequest {
+    fn from(request: Request) -> Self {
+        Self {
+            kind: if request.btc.is_some() {
+                "swap"
+            } else {
+                "quote"
+            },
+            peer_id: request.peer.to_string(),
+            btc_sat: request.btc.map(bitcoin::Amount::as_sat),
+            swap_id: None,
+            funding: vec![],
+        }
+    }
+}
+
+impl From<Funding> for WebhookRequest {
+    fn from(funding: Funding) -> Self {
+        Self {
+            kind: "funding",
+            peer_id: funding.peer.to_string(),
+            btc_sat: None,
+            swap_id: Some(funding.swap_id),
+            funding: funding.outpoints.iter().map(ToString::to_string).collect(),
+        }
+    }
+}
+
+fn parse_verdict(response: &str) -> Result<Verdict> {
+    let verdict = serde_json::from_str::<Verdict>(response)
+        .with_context(|| format!("Unexpected policy verdict {}", response))?;
+
+    if let Verdict::Modify { ask_spread } = verdict {
+        if ask_spread < Decimal::ZERO || ask_spread > Decimal::ONE {
+            bail!("Ask spread must be between 0 and 1, got {}", ask_spread)
+        }
+    }
+
+    Ok(verdict)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::bitcoin::Txid;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn parses_verdicts() {
+        assert_eq!(
+            parse_verdict(r#"{"verdict":"accept"}"#).unwrap(),
+            Verdict::Accept
+        );
+        assert_eq!(
+            parse_verdict(r#"{"verdict":"reject","reason":"blocked"}"#).unwrap(),
+            Verdict::Reject {
+                reason: "blocked".to_owned()
+            }
+        );
+        assert_eq!(
+            parse_verdict(r#"{"verdict":"modify","ask_spread":0.05}"#).unwrap(),
+            Verdict::Modify {
+                ask_spread: dec!(0.05)
+            }
+        );
+    }
+
+    #[test]
+    fn spread_out_of_range_is_rejected() {
+        let error = parse_verdict(r#"{"verdict":"modify","ask_spread":1.5}"#).unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "Ask spread must be between 0 and 1, got 1.5"
+        );
+    }
+
+    #[test]
+    fn request_is_tagged_with_its_kind() {
+        let peer = PeerId::random();
+
+        let quote = WebhookRequest::from(Request { peer, btc: None });
+        let swap = WebhookRequest::from(Request {
+            peer,
+            btc: Some(bitcoin::Amount::from_sat(100_000)),
+        });
+
+        assert_eq!(
+            serde_json::to_string(&quote).unwrap(),
+            format!(r#"{{"kind":"quote","peer_id":"{}","btc_sat":null}}"#, peer)
+        );
+        assert_eq!(
+            serde_json::to_string(&swap).unwrap(),
+            format!(r#"{{"kind":"swap","peer_id":"{}","btc_sat":100000}}"#, peer)
+        );
+    }
+
+    #[test]
+    fn cooperative_redeem_request_names_the_swap() {
+        let peer = PeerId::random();
+        let swap_id = Uuid::nil();
+
+        assert_eq!(
+            serde_json::to_string(&WebhookRequest::cooperative_redeem(peer, swap_id)).unwrap(),
+            format!(
+                r#"{{"kind":"cooperative_redeem","peer_id":"{}","btc_sat":null,"swap_id":"{}"}}"#,
+                peer, swap_id
+            )
+        );
+    }
+
+    #[test]
+    fn funding_request_lists_the_outpoints() {
+        let peer = PeerId::random();
+        let outpoint = OutPoint::new(Txid::default(), 1);
+
+        let request = WebhookRequest::from(Funding {
+            peer,
+            swap_id: Uuid::nil(),
+            outpoints: vec![outpoint],
+        });
+
+        assert_eq!(
+            serde_json::to_string(&request).unwrap(),
+            format!(
+                r#"{{"kind":"funding","peer_id":"{}","btc_sat":null,"swap_id":"{}","funding":["{}:1"]}}"#,
+                peer,
+                Uuid::nil(),
+                Txid::default()
+            )
+        );
+    }
+}
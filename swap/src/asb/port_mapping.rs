@@ -0,0 +1,219 @@
+//! Forwarding the listen ports on the router of a home network with UPnP.
+//!
+//! With `port_mapping = true` in the `[network]` section the ASB asks the
+//! internet gateway of the local network to forward the port of each IPv4 TCP
+//! listen address to it. The mappings are leased for [`LEASE_DURATION`] and
+//! renewed before they run out. The address of a mapped port on the external
+//! IP of the gateway is advertised next to the configured external addresses.
+//!
+//! The gateway is talked to with the blocking API of `igd`, its async API
+//! depends on tokio 0.2.
+
+use anyhow::{bail, Context, Result};
+use igd::{search_gateway, Gateway, PortMappingProtocol, SearchOptions};
+use libp2p::multiaddr::Protocol;
+use libp2p::Multiaddr;
+use std::convert::TryFrom;
+use std::net::{IpAddr, Ipv4Addr, SocketAddrV4, UdpSocket};
+use std::time::Duration;
+
+/// How long the gateway keeps a mapping unless it is renewed.
+pub const LEASE_DURATION: Duration = Duration::from_secs(60 * 60);
+
+const SEARCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+const DESCRIPTION: &str = "xmr-btc-swap ASB";
+
+#[derive(Debug)]
+pub struct PortMapping {
+    gateway: Gateway,
+    external_ip: Ipv4Addr,
+    /// The IP of this host in the local network of the gateway.
+    local_ip: Ipv4Addr,
+    mapped: Vec<SocketAddrV4>,
+}
+
+impl PortMapping {
+    /// Looks for the internet gateway of the local network and its external
+    /// IP.
+    pub async fn new() -> Result<Self> {
+        let gateway = blocking(|| {
+            search_gateway(SearchOptions {
+                timeout: Some(SEARCH_TIMEOUT),
+                ..Default::default()
+            })
+            .context("Failed to find an internet gateway that supports UPnP")
+        })
+        .await?;
+        let external_ip = gateway_ip(&gateway)
+            .await
+            .context("Failed to get the external IP of the internet gateway")?;
+        let local_ip = local_ip_towards(gateway.addr)?;
+
+        tracing::info!(gateway = %gateway.addr, %external_ip, "Found internet gateway");
+
+        Ok(Self {
+            gateway,
+            external_ip,
+            local_ip,
+            mapped: Vec::new(),
+        })
+    }
+
+    /// Maps the ports of the IPv4 TCP addresses in `listen`, returns the
+    /// external addresses of the ports that were mapped.
+    pub async fn map(&mut self, listen: &[Multiaddr]) -> Vec<Multiaddr> {
+        let mut external_addresses = Vec::new();
+
+        for address in listen {
+            let (ip, port) = match ip4_tcp(address) {
+                Some(ip_and_port) => ip_and_port,
+                None => continue,
+            };
+            let local_ip = if ip.is_unspecified() {
+                self.local_ip
+            } else {
+                ip
+            };
+            let local = SocketAddrV4::new(local_ip, port);
+
+            if let Err(error) = self.add_port(local).await {
+                tracing::warn!(%address, "Failed to map port: {:#}", error);
+                continue;
+            }
+
+            let external_address = with_ip4(address, self.external_ip);
+            tracing::info!(%address, %external_address, "Mapped port on internet gateway");
+
+            self.mapped.push(local);
+            external_addresses.push(external_address);
+        }
+
+        external_addresses
+    }
+
+    /// Renews the mappings at half of their lease duration, forever.
+    pub async fn renew_periodically(self) {
+        loop {
+            tokio::time::sleep(LEASE_DURATION / 2).await;
+
+            for local in &self.mapped {
+                if let Err(error) = self.add_port(*local).await {
+                    tracing::warn!(
+                        port = local.port(),
+                        "Failed to renew port mapping: {:#}",
+                        error
+                    );
+                }
+            }
+
+            match gateway_ip(&self.gateway).await {
+                Ok(external_ip) if external_ip != self.external_ip => {
+                    tracing::warn!(%external_ip, previous = %self.external_ip, "External IP of the internet gateway changed, restart the ASB to advertise it");
+                }
+                Ok(_) => {}
+                Err(error) => {
+                    tracing::debug!(
+                        "Failed to get the external IP of the internet gateway: {:#}",
+                        error
+                    )
+                }
+            }
+        }
+    }
+
+    async fn add_port(&self, local: SocketAddrV4) -> Result<()> {
+        let gateway = self.gateway.clone();
+        let lease_duration = u32::try_from(LEASE_DURATION.as_secs())?;
+
+        blocking(move || {
+            gateway.add_port(
+                PortMappingProtocol::TCP,
+                local.port(),
+                local,
+                lease_duration,
+                DESCRIPTION,
+            )?;
+
+            Ok(())
+        })
+        .await
+    }
+}
+
+async fn gateway_ip(gateway: &Gateway) -> Result<Ipv4Addr> {
+    let gateway = gateway.clone();
+
+    blocking(move || Ok(gateway.get_external_ip()?)).await
+}
+
+/// Runs a request to the gateway on the blocking thread pool, a request can
+/// take as long as the HTTP timeout of `igd`.
+async fn blocking<T>(request: impl FnOnce() -> Result<T> + Send + 'static) -> Result<T>
+where
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(request)
+        .await
+        .context("Request to the internet gateway panicked")?
+}
+
+/// The IP of the interface through which `gateway` is reached. Connecting a
+/// UDP socket only looks up the route, nothing is sent.
+fn local_ip_towards(gateway: SocketAddrV4) -> Result<Ipv4Addr> {
+    let socket = UdpSocket::bind("0.0.0.0:0")?;
+    socket.connect(gateway)?;
+
+    match socket.local_addr()?.ip() {
+        IpAddr::V4(ip) => Ok(ip),
+        IpAddr::V6(ip) => bail!("Expected an IPv4 address, got {}", ip),
+    }
+}
+
+/// The IP and port of `/ip4/<ip>/tcp/<port>` addresses, also with `/ws`.
+fn ip4_tcp(address: &Multiaddr) -> Option<(Ipv4Addr, u16)> {
+    let mut protocols = address.iter();
+
+    match (protocols.next()?, protocols.next()?) {
+        (Protocol::Ip4(ip), Protocol::Tcp(port)) if port != 0 && !ip.is_loopback() => {
+            Some((ip, port))
+        }
+        _ => None,
+    }
+}
+
+fn with_ip4(address: &Multiaddr, ip: Ipv4Addr) -> Multiaddr {
+    address
+        .iter()
+        .map(|protocol| match protocol {
+            Protocol::Ip4(_) => Protocol::Ip4(ip),
+            protocol => protocol,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_ip4_tcp_addresses_are_mapped() {
+        let port = |address: &str| ip4_tcp(&address.parse().unwrap()).map(|(_, port)| port);
+
+        assert_eq!(port("/ip4/0.0.0.0/tcp/9939"), Some(9939));
+        assert_eq!(port("/ip4/192.168.1.10/tcp/9940/ws"), Some(9940));
+        assert_eq!(port("/ip4/127.0.0.1/tcp/9939"), None);
+        assert_eq!(port("/ip4/0.0.0.0/tcp/0"), None);
+        assert_eq!(port("/ip6/::/tcp/9939"), None);
+    }
+
+    #[test]
+    fn external_address_keeps_port_and_transport() {
+        let address = "/ip4/0.0.0.0/tcp/9940/ws".parse().unwrap();
+
+        assert_eq!(
+            with_ip4(&address, Ipv4Addr::new(203, 0, 113, 1)),
+            "/ip4/203.0.113.1/tcp/9940/ws".parse().unwrap()
+        );
+    }
+}
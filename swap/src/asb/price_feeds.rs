@@ -0,0 +1,330 @@
+//! Cross-checks the price of several price tickers, so a single ticker that
+//! fails or reports a manipulated price cannot move the price we quote at.
+//!
+//! The price is the median of the asks of the tickers that sent a message
+//! within [`Tolerance::max_age`] and, if set, an update of their ask within
+//! [`Tolerance::max_price_age`]. The asks are streamed into a cache by
+//! [`kraken::connect`], quotes never wait for a ticker. There is no price
+//! while
+//! - fewer than a majority of the tickers are fresh,
+//! - the asks of the fresh tickers differ by more than
+//!   [`Tolerance::max_deviation`] of the median.
+//!
+//! All tickers have to follow the Kraken price ticker protocol, see
+//! [`kraken::connect`].
+
+use crate::amounts::Price;
+use crate::bitcoin;
+use crate::kraken;
+use anyhow::Result;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use url::Url;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tolerance {
+    /// By how much the highest and the lowest ask may differ, relative to the
+    /// median, e.g. 0.02 for 2%.
+    pub max_deviation: Decimal,
+    pub max_age: Duration,
+    /// How old the latest update of an ask may be, `None` for no limit. The
+    /// ask of a quiet market is only updated on the next trade.
+    pub max_price_age: Option<Duration>,
+}
+
+impl Default for Tolerance {
+    fn default() -> Self {
+        Self {
+            max_deviation: dec!(0.02),
+            max_age: Duration::from_secs(60),
+            max_price_age: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, thiserror::Error)]
+pub enum Error {
+    #[error("Only {fresh} of {total} price tickers have a fresh price")]
+    NoQuorum { fresh: usize, total: usize },
+    #[error(
+        "The asks of the price tickers differ by {deviation}, at most {max_deviation} is allowed"
+    )]
+    Diverged {
+        deviation: Decimal,
+        max_deviation: Decimal,
+    },
+}
+
+/// The price tickers quoted from, cloning shares the connections.
+#[derive(Debug, Clone)]
+pub struct PriceFeeds {
+    feeds: Vec<Feed>,
+    tolerance: Tolerance,
+}
+
+#[derive(Debug, Clone)]
+struct Feed {
+    url: Url,
+    updates: kraken::PriceUpdates,
+}
+
+/// What a price ticker last reported, see [`PriceFeeds::statuses`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FeedStatus {
+    pub url: Url,
+    #[serde(default, with = "crate::bitcoin::as_btc::opt")]
+    pub ask: Option<bitcoin::Amount>,
+    /// Seconds since the ticker sent its last message.
+    #[serde(default)]
+    pub age_secs: Option<u64>,
+    /// Seconds since the ticker last updated the ask.
+    #[serde(default)]
+    pub price_age_secs: Option<u64>,
+    /// How far the ask is from the median, relative to the median.
+    #[serde(default)]
+    pub deviation: Option<Decimal>,
+    /// Why the ticker is not part of the median.
+    #[serde(default)]
+    pub problem: Option<String>,
+}
+
+impl PriceFeeds {
+    pub fn connect(urls: Vec<Url>, tolerance: Tolerance) -> Result<Self> {
+        let feeds = urls
+            .into_iter()
+            .map(|url| {
+                Ok(Feed {
+                    updates: kraken::connect(url.clone())?,
+                    url,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self { feeds, tolerance })
+    }
+
+    pub fn tolerance(&self) -> Tolerance {
+        self.tolerance
+    }
+
+    pub fn set_max_deviation(&mut self, max_deviation: Decimal) {
+        self.tolerance.max_deviation = max_deviation;
+    }
+
+    pub fn set_max_price_age(&mut self, max_price_age: Option<Duration>) {
+        self.tolerance.max_price_age = max_price_age;
+    }
+
+    /// How long ago a majority of the tickers sent their last message, `None`
+    /// if not enough of them sent one yet. For a single ticker this is the age
+    /// of its last message.
+    pub fn last_message_age(&self) -> Option<Duration> {
+        let mut ages = self
+            .feeds
+            .iter()
+            .filter_map(|feed| feed.updates.last_message_age())
+            .collect::<Vec<_>>();
+        ages.sort();
+
+        ages.get(quorum(self.feeds.len()) - 1).copied()
+    }
+
+    pub fn median(&mut self) -> Result<Price, Error> {
+        let tolerance = self.tolerance;
+        let asks = self
+            .feeds
+            .iter_mut()
+            .filter_map(|feed| feed.fresh_ask(tolerance).ok())
+            .collect::<Vec<_>>();
+
+        cross_check(asks, self.feeds.len(), self.tolerance)
+    }
+
+    pub fn statuses(&mut self) -> Vec<FeedStatus> {
+        let median = self.median().ok();
+        let tolerance = self.tolerance;
+
+        self.feeds
+            .iter_mut()
+            .map(|feed| {
+                let ask = feed.fresh_ask(tolerance);
+
+                FeedStatus {
+                    url: feed.url.clone(),
+                    ask: feed
+                        .updates
+                        .latest_update()
+                        .ok()
+                        .map(|update| update.ask.btc_per_xmr()),
+                    age_secs: feed.updates.last_message_age().map(|age| age.as_secs()),
+                    price_age_secs: feed.updates.last_update_age().map(|age| age.as_secs()),
+                    deviation: match (&ask, median) {
+                        (Ok(ask), Some(median)) => Some(deviation(*ask, median, median)),
+                        _ => None,
+                    },
+                    problem: ask.err(),
+                }
+            })
+            .collect()
+    }
+}
+
+impl Feed {
+    fn fresh_ask(&mut self, tolerance: Tolerance) -> Result<Price, String> {
+        let ask = self
+            .updates
+            .latest_update()
+            .map_err(|error| error.to_string())?
+            .ask;
+        check_freshness(
+            self.updates.last_message_age(),
+            self.updates.last_update_age(),
+            tolerance,
+        )?;
+
+        Ok(ask)
+    }
+}
+
+fn check_freshness(
+    message_age: Option<Duration>,
+    price_age: Option<Duration>,
+    tolerance: Tolerance,
+) -> Result<(), String> {
+    match message_age {
+        Some(age) if age <= tolerance.max_age => {}
+        Some(age) => return Err(format!("no message for {}s", age.as_secs())),
+        None => return Err("no message received yet".to_owned()),
+    }
+
+    match (price_age, tolerance.max_price_age) {
+        (Some(age), Some(max_price_age)) if age > max_price_age => {
+            Err(format!("ask not updated for {}s", age.as_secs()))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// How many of `total` tickers must be fresh for their median to count.
+fn quorum(total: usize) -> usize {
+    total / 2 + 1
+}
+
+fn cross_check(mut asks: Vec<Price>, total: usize, tolerance: Tolerance) -> Result<Price, Error> {
+    if asks.is_empty() || asks.len() < quorum(total) {
+        return Err(Error::NoQuorum {
+            fresh: asks.len(),
+            total,
+        });
+    }
+
+    asks.sort();
+    let middle = asks.len() / 2;
+    let median = if asks.len() % 2 == 0 {
+        let sats =
+            (asks[middle - 1].btc_per_xmr().as_sat() + asks[middle].btc_per_xmr().as_sat()) / 2;
+        Price::new(bitcoin::Amount::from_sat(sats))
+    } else {
+        asks[middle]
+    };
+
+    let spread = deviation(asks[asks.len() - 1], asks[0], median);
+    if spread > tolerance.max_deviation {
+        return Err(Error::Diverged {
+            deviation: spread,
+            max_deviation: tolerance.max_deviation,
+        });
+    }
+
+    Ok(median)
+}
+
+/// How far `ask` is from `other`, relative to `median`.
+fn deviation(ask: Price, other: Price, median: Price) -> Decimal {
+    let sats = |price: Price| Decimal::from(price.btc_per_xmr().as_sat());
+
+    if median == Price::ZERO {
+        return Decimal::ZERO;
+    }
+
+    ((sats(ask) - sats(other)) / sats(median)).abs().round_dp(4)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn price(sats: u64) -> Price {
+        Price::new(bitcoin::Amount::from_sat(sats))
+    }
+
+    #[test]
+    fn median_of_an_odd_and_an_even_number_of_asks() {
+        let tolerance = Tolerance::default();
+
+        assert_eq!(
+            cross_check(vec![price(1_010), price(1_000), price(1_005)], 3, tolerance),
+            Ok(price(1_005))
+        );
+        assert_eq!(
+            cross_check(vec![price(1_010), price(1_000)], 2, tolerance),
+            Ok(price(1_005))
+        );
+        assert_eq!(
+            cross_check(vec![price(1_000)], 1, tolerance),
+            Ok(price(1_000))
+        );
+    }
+
+    #[test]
+    fn diverging_asks_have_no_price() {
+        let tolerance = Tolerance {
+            max_deviation: dec!(0.02),
+            ..Tolerance::default()
+        };
+
+        assert_eq!(
+            cross_check(vec![price(1_000), price(1_020), price(1_040)], 3, tolerance),
+            Err(Error::Diverged {
+                deviation: dec!(0.0392),
+                max_deviation: dec!(0.02),
+            })
+        );
+    }
+
+    #[test]
+    fn a_majority_of_the_tickers_must_be_fresh() {
+        let tolerance = Tolerance::default();
+
+        assert!(cross_check(vec![price(1_000), price(1_000)], 3, tolerance).is_ok());
+        assert_eq!(
+            cross_check(vec![price(1_000)], 3, tolerance),
+            Err(Error::NoQuorum { fresh: 1, total: 3 })
+        );
+        assert!(cross_check(vec![price(1_000)], 2, tolerance).is_err());
+        assert!(cross_check(vec![], 1, tolerance).is_err());
+    }
+
+    #[test]
+    fn an_ask_not_updated_for_too_long_is_stale_if_limited() {
+        let second = Duration::from_secs(1);
+        let unlimited = Tolerance::default();
+        let limited = Tolerance {
+            max_price_age: Some(Duration::from_secs(600)),
+            ..Tolerance::default()
+        };
+
+        assert!(check_freshness(Some(second), Some(3_600 * second), unlimited).is_ok());
+        assert_eq!(
+            check_freshness(Some(second), Some(3_600 * second), limited),
+            Err("ask not updated for 3600s".to_owned())
+        );
+        assert!(check_freshness(Some(second), Some(60 * second), limited).is_ok());
+        assert_eq!(
+            check_freshness(Some(61 * second), Some(second), limited),
+            Err("no message for 61s".to_owned())
+        );
+    }
+}
@@ -0,0 +1,310 @@
+use crate::amounts::{Price, Quote};
+use crate::asb::TradingParameters;
+use crate::network::quote::SignedQuote;
+use crate::network::swap_setup::alice;
+use libp2p::PeerId;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+/// Quotes handed out to a peer are repeated for this long.
+pub const QUOTE_VALIDITY: Duration = Duration::from_secs(60);
+
+/// The quotes handed out recently, by peer.
+///
+/// A taker that retries a quote request, e.g. because the response timed out,
+/// gets the same quote again instead of a new one computed from a different
+/// rate. A quote is computed anew once it expired or the trading parameters
+/// changed.
+#[derive(Debug, Default)]
+pub struct IssuedQuotes {
+    quotes: HashMap<PeerId, Issued>,
+}
+
+#[derive(Debug)]
+struct Issued {
+    quote: SignedQuote,
+    parameters: TradingParameters,
+    issued_at: Instant,
+}
+
+impl IssuedQuotes {
+    /// The quote still valid for `peer`, expired quotes of all peers are
+    /// removed.
+    pub fn get(
+        &mut self,
+        peer: &PeerId,
+        parameters: TradingParameters,
+        now: Instant,
+    ) -> Option<SignedQuote> {
+        self.quotes
+            .retain(|_, issued| now.duration_since(issued.issued_at) < QUOTE_VALIDITY);
+
+        self.quotes
+            .get(peer)
+            .filter(|issued| issued.parameters == parameters)
+            .map(|issued| issued.quote.clone())
+    }
+
+    pub fn insert(
+        &mut self,
+        peer: PeerId,
+        quote: SignedQuote,
+        parameters: TradingParameters,
+        now: Instant,
+    ) {
+        self.quotes.insert(
+            peer,
+            Issued {
+                quote,
+                parameters,
+                issued_at: now,
+            },
+        );
+    }
+}
+
+/// What became of a quote, recorded to tune the spread and the limits.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+    strum::Display,
+    strum::EnumString,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+#[non_exhaustive]
+pub enum Outcome {
+    /// A swap was set up.
+    Converted,
+    /// The taker was handed a new quote after the quote expired.
+    Expired,
+    /// The taker disconnected without setting up a swap.
+    PeerDisconnected,
+    /// The amount of the swap was below the minimum or above the maximum.
+    AmountRejected,
+    /// The Monero balance did not cover the amount of the swap.
+    InsufficientLiquidity,
+    /// Trading was paused or suspended, or the policy refused the swap.
+    Refused,
+    /// The swap setup failed for another reason, e.g. the rate was not
+    /// available.
+    SetupFailed,
+}
+
+impl Outcome {
+    pub fn of_rejection(error: &alice::Error) -> Self {
+        match error {
+            alice::Error::AmountBelowMinimum { .. }
+            | alice::Error::AmountAboveMaximum { .. }
+            | alice::Error::XmrAmountNotCovered { .. } => Outcome::AmountRejected,
+            alice::Error::BalanceTooLow { .. } => Outcome::InsufficientLiquidity,
+            alice::Error::ResumeOnlyMode
+            | alice::Error::RefusedByPolicy(_)
+            | alice::Error::BackendDegraded(_)
+            | alice::Error::Overloaded(_) => Outcome::Refused,
+            alice::Error::LatestRateFetchFailed(_)
+            | alice::Error::SellQuoteCalculationFailed(_)
+            | alice::Error::BlockchainNetworkMismatch { .. } => Outcome::SetupFailed,
+        }
+    }
+}
+
+/// A quote handed out to a taker and what became of it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuoteRecord {
+    pub identity: String,
+    pub peer_id: PeerId,
+    pub issued_at: OffsetDateTime,
+    pub price: Price,
+    pub outcome: Outcome,
+    /// Why the swap setup was rejected.
+    pub reason: Option<String>,
+    pub swap_id: Option<Uuid>,
+}
+
+/// The latest quote of every peer that did not lead to an outcome yet.
+///
+/// Quotes still pending when the ASB stops are not recorded.
+#[derive(Debug)]
+pub struct PendingQuotes {
+    identity: String,
+    quotes: HashMap<PeerId, (OffsetDateTime, Price)>,
+}
+
+impl PendingQuotes {
+    pub fn new(identity: String) -> Self {
+        Self {
+            identity,
+            quotes: HashMap::new(),
+        }
+    }
+
+    /// Tracks a quote newly computed for `peer`. Returns the previous quote of
+    /// the peer, which expired since.
+    pub fn issue(
+        &mut self,
+        peer: PeerId,
+        quote: &Quote,
+        now: OffsetDateTime,
+    ) -> Option<QuoteRecord> {
+        let previous = self.resolve(&peer, Outcome::Expired);
+        self.quotes.insert(peer, (now, quote.price));
+
+        previous
+    }
+
+    /// Ends tracking the quote of `peer`, if any.
+    pub fn resolve(&mut self, peer: &PeerId, outcome: Outcome) -> Option<QuoteRecord> {
+        let (issued_at, price) = self.quotes.remove(peer)?;
+
+        Some(QuoteRecord {
+            identity: self.identity.clone(),
+            peer_id: *peer,
+            issued_at,
+            price,
+            outcome,
+            reason: None,
+            swap_id: None,
+        })
+    }
+}
+
+/// How many quotes had which outcome.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct QuoteStats {
+    pub total: u64,
+    pub outcomes: BTreeMap<Outcome, u64>,
+}
+
+impl QuoteStats {
+    pub fn new(records: &[QuoteRecord]) -> Self {
+        let mut stats = QuoteStats::default();
+
+        for record in records {
+            stats.total += 1;
+            *stats.outcomes.entry(record.outcome).or_default() += 1;
+        }
+
+        stats
+    }
+
+    pub fn count(&self, outcome: Outcome) -> u64 {
+        self.outcomes.get(&outcome).copied().unwrap_or_default()
+    }
+
+    /// The share of quotes that converted into a swap, `None` if there are no
+    /// quotes yet.
+    pub fn conversion_rate(&self) -> Option<Decimal> {
+        if self.total == 0 {
+            return None;
+        }
+
+        Some(Decimal::from(self.count(Outcome::Converted)) / Decimal::from(self.total))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitcoin;
+    use rust_decimal::Decimal;
+
+    fn parameters() -> TradingParameters {
+        TradingParameters {
+            paused: false,
+            ask_spread: Decimal::ZERO,
+            min_buy: bitcoin::Amount::ZERO,
+            max_buy: bitcoin::Amount::ONE_BTC,
+        }
+    }
+
+    fn quote(price: u64) -> Quote {
+        Quote {
+            price: Price::new(bitcoin::Amount::from_sat(price)),
+            min_quantity: bitcoin::Amount::ZERO,
+            max_quantity: bitcoin::Amount::ONE_BTC,
+        }
+    }
+
+    #[test]
+    fn retries_within_validity_get_the_same_quote() {
+        let mut quotes = IssuedQuotes::default();
+        let peer = PeerId::random();
+        let now = Instant::now();
+        quotes.insert(peer, quote(1000).into(), parameters(), now);
+
+        assert_eq!(
+            quotes.get(&peer, parameters(), now + Duration::from_secs(30)),
+            Some(SignedQuote::unsigned(quote(1000)))
+        );
+        assert_eq!(quotes.get(&PeerId::random(), parameters(), now), None);
+    }
+
+    #[test]
+    fn quote_is_not_repeated_once_expired_or_parameters_changed() {
+        let mut quotes = IssuedQuotes::default();
+        let peer = PeerId::random();
+        let now = Instant::now();
+        quotes.insert(peer, quote(1000).into(), parameters(), now);
+
+        let paused = TradingParameters {
+            paused: true,
+            ..parameters()
+        };
+        assert_eq!(quotes.get(&peer, paused, now), None);
+        assert_eq!(quotes.get(&peer, parameters(), now + QUOTE_VALIDITY), None);
+        assert!(quotes.quotes.is_empty());
+    }
+
+    #[test]
+    fn new_quote_expires_the_previous_one() {
+        let mut pending = PendingQuotes::new("default".to_owned());
+        let peer = PeerId::random();
+        let now = OffsetDateTime::now_utc();
+
+        assert_eq!(pending.issue(peer, &quote(1000), now), None);
+        let expired = pending.issue(peer, &quote(1100), now).unwrap();
+        assert_eq!(expired.outcome, Outcome::Expired);
+        assert_eq!(expired.price, Price::new(bitcoin::Amount::from_sat(1000)));
+
+        let converted = pending.resolve(&peer, Outcome::Converted).unwrap();
+        assert_eq!(converted.price, Price::new(bitcoin::Amount::from_sat(1100)));
+        assert_eq!(pending.resolve(&peer, Outcome::PeerDisconnected), None);
+    }
+
+    #[test]
+    fn stats_count_outcomes() {
+        let mut pending = PendingQuotes::new("default".to_owned());
+        let now = OffsetDateTime::now_utc();
+        let mut records = vec![];
+        for outcome in [
+            Outcome::Converted,
+            Outcome::AmountRejected,
+            Outcome::AmountRejected,
+            Outcome::PeerDisconnected,
+        ] {
+            let peer = PeerId::random();
+            pending.issue(peer, &quote(1000), now);
+            records.push(pending.resolve(&peer, outcome).unwrap());
+        }
+
+        let stats = QuoteStats::new(&records);
+
+        assert_eq!(stats.total, 4);
+        assert_eq!(stats.count(Outcome::AmountRejected), 2);
+        assert_eq!(stats.count(Outcome::Expired), 0);
+        assert_eq!(stats.conversion_rate(), Some(Decimal::new(25, 2)));
+        assert_eq!(QuoteStats::new(&[]).conversion_rate(), None);
+    }
+}
@@ -0,0 +1,468 @@
+//! Restocking the Monero of the maker once it runs low.
+//!
+//! Every swap sells Monero for Bitcoin, so over time the Monero wallet of the
+//! ASB depletes while Bitcoin accumulates. With a `[rebalance]` section in the
+//! config the ASB compares both balances against the configured [`Thresholds`]
+//! every [`CHECK_INTERVAL`]. Once the Monero balance falls below its minimum or
+//! the Bitcoin balance exceeds its maximum, an [`Order`] to sell Bitcoin for
+//! Monero is made and handled according to the [`Mode`]:
+//!
+//! - `dry_run` only logs the order and lists it through the admin socket.
+//! - `manual_approve` waits until the order is approved or rejected through
+//!   the admin socket.
+//! - `automatic` executes the order right away.
+//!
+//! Executing it sends the Bitcoin to the deposit address of a [`Rebalancer`],
+//! which trades it for Monero and withdraws that to the Monero wallet of the
+//! ASB. The Kraken rebalancer is built with the `kraken-rebalancer` feature.
+//! After an order the balances are not checked again for the configured
+//! cooldown, the Monero takes a while to arrive.
+
+#[cfg(feature = "kraken-rebalancer")]
+pub mod kraken;
+
+use crate::amounts::{CheckedArithmetic, Price, Rate};
+use crate::asb::{Decision, LatestRate};
+use crate::{bitcoin, monero};
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use time::OffsetDateTime;
+use tokio::sync::oneshot;
+
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// The size of the output script of a deposit address the fee of sending the
+/// Bitcoin is estimated with, the one of a P2WSH or P2TR output. Deposit
+/// addresses with smaller scripts pay slightly more fee than necessary.
+const DEPOSIT_SCRIPT_SIZE: usize = 34;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Mode {
+    DryRun,
+    ManualApprove,
+    Automatic,
+}
+
+impl Default for Mode {
+    fn default() -> Self {
+        Mode::DryRun
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Thresholds {
+    /// Rebalancing starts once the Monero balance falls below this.
+    pub min_xmr: monero::Amount,
+    /// The Monero balance an order restocks to.
+    pub target_xmr: monero::Amount,
+    /// Rebalancing also starts once the Bitcoin balance exceeds this, the
+    /// excess is sold.
+    pub max_btc: Option<bitcoin::Amount>,
+    /// Bitcoin that is never sold.
+    pub reserve_btc: bitcoin::Amount,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Trigger {
+    XmrBelowMinimum,
+    BtcAboveMaximum,
+}
+
+/// Bitcoin to sell for Monero.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Order {
+    pub trigger: Trigger,
+    pub btc: bitcoin::Amount,
+    /// The Monero the Bitcoin buys at the market price, before the fees of the
+    /// exchange.
+    pub xmr: monero::Amount,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum Status {
+    /// Logged but not executed, in the `dry_run` mode.
+    Proposed,
+    Rejected,
+    Executed {
+        /// The transaction that sent the Bitcoin to the exchange.
+        txid: String,
+        /// How the exchange refers to the trade and withdrawal.
+        reference: String,
+    },
+    Failed {
+        reason: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Outcome {
+    pub order: Order,
+    /// Seconds since the unix epoch.
+    pub decided_at: i64,
+    #[serde(flatten)]
+    pub status: Status,
+}
+
+/// What the admin socket reports about rebalancing.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Report {
+    pub mode: Mode,
+    /// The order waiting for approval.
+    pub pending: Option<Order>,
+    pub last: Option<Outcome>,
+}
+
+/// An exchange, OTC desk or other counterparty that buys Monero with the
+/// Bitcoin of the maker.
+#[async_trait]
+pub trait Rebalancer: Send + Sync {
+    /// Where to send the Bitcoin of an order.
+    async fn deposit_address(&self) -> Result<bitcoin::Address>;
+
+    /// Sells the Bitcoin of `order`, which was sent to the deposit address,
+    /// for Monero and withdraws that to `monero_address`. Returns a reference
+    /// to the trade once the withdrawal was started.
+    async fn rebalance(&self, order: &Order, monero_address: monero::Address) -> Result<String>;
+}
+
+#[derive(Debug, Default)]
+struct State {
+    pending: Option<(Order, oneshot::Sender<Decision>)>,
+    last: Option<Outcome>,
+}
+
+/// A cheaply cloneable handle to the rebalancing of the maker, shared between
+/// the rebalancing task and the admin socket.
+#[derive(Debug, Clone)]
+pub struct Rebalancing {
+    mode: Mode,
+    thresholds: Thresholds,
+    cooldown: Duration,
+    state: Arc<Mutex<State>>,
+}
+
+impl Rebalancing {
+    pub fn new(mode: Mode, thresholds: Thresholds, cooldown: Duration) -> Result<Self> {
+        if thresholds.target_xmr < thresholds.min_xmr {
+            bail!(
+                "The target of {} must not be below the minimum of {}",
+                thresholds.target_xmr,
+                thresholds.min_xmr
+            )
+        }
+
+        Ok(Self {
+            mode,
+            thresholds,
+            cooldown,
+            state: Default::default(),
+        })
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    pub fn report(&self) -> Report {
+        let state = self.lock();
+
+        Report {
+            mode: self.mode,
+            pending: state.pending.as_ref().map(|(order, _)| *order),
+            last: state.last.clone(),
+        }
+    }
+
+    /// Approves or rejects the order waiting for approval.
+    pub fn decide(&self, decision: Decision) -> Result<()> {
+        let (_, sender) = match self.lock().pending.take() {
+            Some(pending) => pending,
+            None => bail!("No rebalancing order is waiting for approval"),
+        };
+
+        // The rebalancing task stopped in the meantime, there is nothing to decide
+        let _ = sender.send(decision);
+
+        Ok(())
+    }
+
+    /// Checks the balances and handles an order if they crossed the
+    /// thresholds, forever.
+    pub async fn run<R>(
+        self,
+        rebalancer: Option<Arc<dyn Rebalancer>>,
+        bitcoin_wallet: Arc<bitcoin::Wallet>,
+        monero_wallet: Arc<monero::Wallet>,
+        mut rate: R,
+    ) where
+        R: LatestRate,
+    {
+        loop {
+            let wait = match self
+                .check(
+                    rebalancer.as_deref(),
+                    &bitcoin_wallet,
+                    &monero_wallet,
+                    &mut rate,
+                )
+                .await
+            {
+                Ok(Some(outcome)) => {
+                    self.lock().last = Some(outcome);
+                    self.cooldown
+                }
+                Ok(None) => CHECK_INTERVAL,
+                Err(error) => {
+                    tracing::warn!("Failed to check the balances for rebalancing: {:#}", error);
+                    CHECK_INTERVAL
+                }
+            };
+
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    async fn check<R>(
+        &self,
+        rebalancer: Option<&dyn Rebalancer>,
+        bitcoin_wallet: &bitcoin::Wallet,
+        monero_wallet: &monero::Wallet,
+        rate: &mut R,
+    ) -> Result<Option<Outcome>>
+    where
+        R: LatestRate,
+    {
+        let btc = bitcoin_wallet.max_giveable(DEPOSIT_SCRIPT_SIZE).await?;
+        let xmr = monero_wallet.get_balance().await?;
+        let price = rate
+            .latest_rate()
+            .context("Failed to get the market price")?
+            .with_spread(Decimal::ZERO)
+            .ask()?;
+
+        let order = match plan(btc, xmr, price, &self.thresholds)? {
+            Some(order) => order,
+            None => return Ok(None),
+        };
+        tracing::info!(
+            trigger = ?order.trigger,
+            btc = %order.btc,
+            xmr = %order.xmr,
+            mode = ?self.mode,
+            "Balances crossed the rebalancing thresholds"
+        );
+
+        let status = match self.mode {
+            Mode::DryRun => Status::Proposed,
+            Mode::ManualApprove => match self.request(order).await {
+                Decision::Approve => {
+                    execute(order, rebalancer, bitcoin_wallet, monero_wallet).await
+                }
+                Decision::Reject => Status::Rejected,
+            },
+            Mode::Automatic => execute(order, rebalancer, bitcoin_wallet, monero_wallet).await,
+        };
+        tracing::info!(?status, "Handled rebalancing order");
+
+        Ok(Some(Outcome {
+            order,
+            decided_at: OffsetDateTime::now_utc().unix_timestamp(),
+            status,
+        }))
+    }
+
+    async fn request(&self, order: Order) -> Decision {
+        let (sender, receiver) = oneshot::channel();
+        self.lock().pending = Some((order, sender));
+
+        receiver.await.unwrap_or(Decision::Reject)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().expect("rebalancing lock not poisoned")
+    }
+}
+
+async fn execute(
+    order: Order,
+    rebalancer: Option<&dyn Rebalancer>,
+    bitcoin_wallet: &bitcoin::Wallet,
+    monero_wallet: &monero::Wallet,
+) -> Status {
+    let result = async {
+        let rebalancer = rebalancer.context("No rebalancer is configured")?;
+
+        let address = rebalancer.deposit_address().await?;
+        let psbt = bitcoin_wallet
+            .send_to_address(address, order.btc, None)
+            .await?;
+        let transaction = bitcoin_wallet.sign_and_finalize(psbt).await?;
+        let (txid, _) = bitcoin_wallet.broadcast(transaction, "rebalance").await?;
+
+        let reference = rebalancer
+            .rebalance(&order, monero_wallet.get_main_address())
+            .await
+            .with_context(|| format!("Sent the Bitcoin in {} but failed to trade it", txid))?;
+
+        Result::<_>::Ok(Status::Executed {
+            txid: txid.to_string(),
+            reference,
+        })
+    }
+    .await;
+
+    result.unwrap_or_else(|error| Status::Failed {
+        reason: format!("{:#}", error),
+    })
+}
+
+/// The order that restocks the Monero balance or sells the excess Bitcoin,
+/// whichever is larger, `None` if the balances are within the thresholds or
+/// all Bitcoin is reserved.
+///
+/// `btc` is the Bitcoin that can be sent, `price` the market price.
+pub fn plan(
+    btc: bitcoin::Amount,
+    xmr: monero::Amount,
+    price: Price,
+    thresholds: &Thresholds,
+) -> Result<Option<Order>> {
+    let xmr_shortfall = if xmr < thresholds.min_xmr {
+        let missing = thresholds.target_xmr.try_sub(xmr)?;
+        Some(price.btc_for(missing)?)
+    } else {
+        None
+    };
+    let btc_excess = thresholds
+        .max_btc
+        .filter(|max_btc| btc > *max_btc)
+        .map(|max_btc| btc - max_btc);
+
+    let (trigger, wanted) = match (xmr_shortfall, btc_excess) {
+        (Some(shortfall), Some(excess)) if excess > shortfall => (Trigger::BtcAboveMaximum, excess),
+        (Some(shortfall), _) => (Trigger::XmrBelowMinimum, shortfall),
+        (None, Some(excess)) => (Trigger::BtcAboveMaximum, excess),
+        (None, None) => return Ok(None),
+    };
+
+    let spendable = btc
+        .checked_sub(thresholds.reserve_btc)
+        .unwrap_or(bitcoin::Amount::ZERO);
+    let btc = wanted.min(spendable);
+    if btc == bitcoin::Amount::ZERO {
+        tracing::warn!(?trigger, "Not rebalancing, all of the Bitcoin is reserved");
+        return Ok(None);
+    }
+
+    Ok(Some(Order {
+        trigger,
+        btc,
+        xmr: Rate::new(price, Decimal::ZERO).sell_quote(btc)?,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn thresholds() -> Thresholds {
+        Thresholds {
+            min_xmr: monero::Amount::from_monero(10.0).unwrap(),
+            target_xmr: monero::Amount::from_monero(30.0).unwrap(),
+            max_btc: Some(bitcoin::Amount::from_btc(2.0).unwrap()),
+            reserve_btc: bitcoin::Amount::from_btc(0.1).unwrap(),
+        }
+    }
+
+    fn price() -> Price {
+        Price::new(bitcoin::Amount::from_btc(0.005).unwrap())
+    }
+
+    #[test]
+    fn balances_within_thresholds_need_no_order() {
+        let order = plan(
+            bitcoin::Amount::from_btc(1.0).unwrap(),
+            monero::Amount::from_monero(10.0).unwrap(),
+            price(),
+            &thresholds(),
+        )
+        .unwrap();
+
+        assert_eq!(order, None);
+    }
+
+    #[test]
+    fn low_monero_is_restocked_to_the_target() {
+        let order = plan(
+            bitcoin::Amount::from_btc(1.0).unwrap(),
+            monero::Amount::from_monero(5.0).unwrap(),
+            price(),
+            &thresholds(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            order,
+            Some(Order {
+                trigger: Trigger::XmrBelowMinimum,
+                btc: bitcoin::Amount::from_btc(0.125).unwrap(),
+                xmr: monero::Amount::from_monero(25.0).unwrap(),
+            })
+        );
+    }
+
+    #[test]
+    fn excess_bitcoin_is_sold_but_not_the_reserve() {
+        let excess = plan(
+            bitcoin::Amount::from_btc(2.5).unwrap(),
+            monero::Amount::from_monero(20.0).unwrap(),
+            price(),
+            &thresholds(),
+        )
+        .unwrap()
+        .unwrap();
+        let reserved = plan(
+            bitcoin::Amount::from_btc(0.15).unwrap(),
+            monero::Amount::from_monero(5.0).unwrap(),
+            price(),
+            &thresholds(),
+        )
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(excess.trigger, Trigger::BtcAboveMaximum);
+        assert_eq!(excess.btc, bitcoin::Amount::from_btc(0.5).unwrap());
+        assert_eq!(reserved.btc, bitcoin::Amount::from_btc(0.05).unwrap());
+    }
+
+    #[tokio::test]
+    async fn pending_order_receives_the_decision() {
+        let rebalancing =
+            Rebalancing::new(Mode::ManualApprove, thresholds(), Duration::ZERO).unwrap();
+        let order = Order {
+            trigger: Trigger::XmrBelowMinimum,
+            btc: bitcoin::Amount::from_btc(0.125).unwrap(),
+            xmr: monero::Amount::from_monero(25.0).unwrap(),
+        };
+
+        let decision = tokio::spawn({
+            let rebalancing = rebalancing.clone();
+            async move { rebalancing.request(order).await }
+        });
+        while rebalancing.report().pending.is_none() {
+            tokio::task::yield_now().await;
+        }
+        rebalancing.decide(Decision::Approve).unwrap();
+
+        assert_eq!(decision.await.unwrap(), Decision::Approve);
+        assert!(rebalancing.report().pending.is_none());
+        assert!(rebalancing.decide(Decision::Reject).is_err());
+    }
+}
@@ -0,0 +1,301 @@
+//! A [`Rebalancer`] trading on Kraken through its private REST API.
+//!
+//! The API key needs the permissions to query funds, deposit funds, create
+//! orders and withdraw funds. Kraken only withdraws to addresses that were
+//! added to the account beforehand, the main address of the Monero wallet of
+//! the ASB has to be added under the name given as `xmr_withdrawal_key`.
+
+use crate::asb::rebalance::{Order, Rebalancer};
+use crate::{bitcoin, monero};
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use hmac::{Hmac, Mac, NewMac};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use sha2::{Digest, Sha256, Sha512};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use url::{form_urlencoded, Url};
+use zeroize::Zeroize;
+
+const API_URL: &str = "https://api.kraken.com";
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long to wait for Kraken to credit the deposit, it requires several
+/// confirmations.
+const DEPOSIT_TIMEOUT: Duration = Duration::from_secs(3 * 60 * 60);
+
+const DEPOSIT_POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The part of the Monero of an order that is not bought, it pays for the
+/// trading fee and the slippage of the market order.
+const FEE_MARGIN: Decimal = Decimal::from_parts(2, 0, 0, false, 2);
+
+/// Kraken trades Monero to eight decimal places.
+const XMR_DECIMALS: u32 = 8;
+
+#[derive(Clone)]
+pub struct Kraken {
+    api_key: String,
+    /// The decoded API secret.
+    secret: Vec<u8>,
+    xmr_withdrawal_key: String,
+    client: reqwest::Client,
+    base_url: Url,
+}
+
+impl Drop for Kraken {
+    fn drop(&mut self) {
+        self.secret.zeroize();
+    }
+}
+
+impl fmt::Debug for Kraken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Kraken")
+            .field("xmr_withdrawal_key", &self.xmr_withdrawal_key)
+            .finish()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Response<T> {
+    error: Vec<String>,
+    result: Option<T>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DepositAddress {
+    address: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct AddedOrder {
+    txid: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Withdrawal {
+    refid: String,
+}
+
+impl Kraken {
+    pub fn new(api_key: String, api_secret: &str, xmr_withdrawal_key: String) -> Result<Self> {
+        let secret = base64::decode(api_secret).context("Kraken API secret is not base64")?;
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()?;
+
+        Ok(Self {
+            api_key,
+            secret,
+            xmr_withdrawal_key,
+            client,
+            base_url: API_URL.parse().expect("valid url"),
+        })
+    }
+
+    async fn private<T>(&self, method: &str, params: &[(&str, &str)]) -> Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        let path = format!("/0/private/{}", method);
+        let nonce = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("system time after unix epoch")
+            .as_millis()
+            .to_string();
+
+        let mut body = form_urlencoded::Serializer::new(String::new());
+        body.append_pair("nonce", &nonce);
+        for (key, value) in params {
+            body.append_pair(key, value);
+        }
+        let body = body.finish();
+
+        let response = self
+            .client
+            .post(self.base_url.join(&path)?)
+            .header("API-Key", &self.api_key)
+            .header("API-Sign", sign(&self.secret, &path, &nonce, &body)?)
+            .header(
+                reqwest::header::CONTENT_TYPE,
+                "application/x-www-form-urlencoded",
+            )
+            .body(body)
+            .send()
+            .await
+            .with_context(|| format!("Failed to call Kraken {}", method))?
+            .error_for_status()?
+            .text()
+            .await?;
+
+        parse_response(&response).with_context(|| format!("Kraken {} failed", method))
+    }
+
+    async fn btc_balance(&self) -> Result<bitcoin::Amount> {
+        let balances = self
+            .private::<HashMap<String, String>>("Balance", &[])
+            .await?;
+        let balance = balances.get("XXBT").map_or("0", String::as_str);
+
+        // Kraken reports balances to ten decimal places
+        let sats = (Decimal::from_str(balance)? * Decimal::from(bitcoin::Amount::ONE_BTC.as_sat()))
+            .trunc()
+            .to_u64()
+            .with_context(|| format!("Unexpected Bitcoin balance {}", balance))?;
+
+        Ok(bitcoin::Amount::from_sat(sats))
+    }
+}
+
+#[async_trait]
+impl Rebalancer for Kraken {
+    async fn deposit_address(&self) -> Result<bitcoin::Address> {
+        let addresses = self
+            .private::<Vec<DepositAddress>>("DepositAddresses", &[
+                ("asset", "XBT"),
+                ("method", "Bitcoin"),
+            ])
+            .await?;
+        let address = addresses
+            .first()
+            .context("Kraken returned no Bitcoin deposit address")?;
+
+        Ok(bitcoin::Address::from_str(&address.address)?)
+    }
+
+    async fn rebalance(&self, order: &Order, monero_address: monero::Address) -> Result<String> {
+        let credited = async {
+            loop {
+                match self.btc_balance().await {
+                    Ok(balance) if balance >= order.btc => return,
+                    Ok(_) => {}
+                    Err(error) => tracing::debug!("Failed to get the Kraken balance: {:#}", error),
+                }
+                tokio::time::sleep(DEPOSIT_POLL_INTERVAL).await;
+            }
+        };
+        tokio::time::timeout(DEPOSIT_TIMEOUT, credited)
+            .await
+            .with_context(|| {
+                format!(
+                    "Kraken did not credit the deposit within {} hours",
+                    DEPOSIT_TIMEOUT.as_secs() / 3600
+                )
+            })?;
+
+        let volume = volume(order.xmr)?;
+        let added = self
+            .private::<AddedOrder>("AddOrder", &[
+                ("pair", "XXMRXXBT"),
+                ("type", "buy"),
+                ("ordertype", "market"),
+                ("volume", &volume),
+            ])
+            .await?;
+        let order_id = added.txid.join(",");
+        tracing::info!(%order_id, %volume, "Bought Monero on Kraken");
+
+        let withdrawal = self
+            .private::<Withdrawal>("Withdraw", &[
+                ("asset", "XMR"),
+                ("key", &self.xmr_withdrawal_key),
+                ("amount", &volume),
+            ])
+            .await?;
+        tracing::info!(
+            refid = %withdrawal.refid,
+            %monero_address,
+            key = %self.xmr_withdrawal_key,
+            "Started Monero withdrawal from Kraken"
+        );
+
+        Ok(format!(
+            "order {}, withdrawal {}",
+            order_id, withdrawal.refid
+        ))
+    }
+}
+
+/// `API-Sign` is the HMAC-SHA512 of the path and the SHA256 of the nonce and
+/// the body, keyed with the API secret.
+fn sign(secret: &[u8], path: &str, nonce: &str, body: &str) -> Result<String> {
+    let digest = Sha256::digest(format!("{}{}", nonce, body).as_bytes());
+
+    let mut mac = Hmac::<Sha512>::new_from_slice(secret).context("Invalid Kraken API secret")?;
+    mac.update(path.as_bytes());
+    mac.update(&digest);
+
+    Ok(base64::encode(mac.finalize().into_bytes()))
+}
+
+fn parse_response<T>(response: &str) -> Result<T>
+where
+    T: DeserializeOwned,
+{
+    let response = serde_json::from_str::<Response<T>>(response)
+        .with_context(|| format!("Unexpected response {}", response))?;
+
+    match response.result {
+        Some(result) if response.error.is_empty() => Ok(result),
+        _ => bail!("{}", response.error.join(", ")),
+    }
+}
+
+/// The Monero to buy for an order, leaving the [`FEE_MARGIN`].
+fn volume(xmr: monero::Amount) -> Result<String> {
+    let xmr = xmr.as_piconero_decimal() / monero::Amount::ONE_XMR.as_piconero_decimal();
+    let mut volume = (xmr * (Decimal::ONE - FEE_MARGIN))
+        .round_dp_with_strategy(XMR_DECIMALS, RoundingStrategy::ToZero);
+    volume.rescale(XMR_DECIMALS);
+
+    if volume <= Decimal::ZERO {
+        bail!("Nothing to buy for {} XMR", xmr)
+    }
+
+    Ok(volume.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signature_matches_documented_example() {
+        // The example of https://docs.kraken.com/rest/#section/Authentication
+        let secret = base64::decode(
+            "kQH5HW/8p1uGOVjbgWA7FunAmGO8lsSUXNsu3eow76sz84Q18fWxnyRzBHCd3pd5nE9qa99HAZtuZuj6F1huXg==",
+        )
+        .unwrap();
+        let body =
+            "nonce=1616492376594&ordertype=limit&pair=XBTUSD&price=37500&type=buy&volume=1.25";
+
+        assert_eq!(
+            sign(&secret, "/0/private/AddOrder", "1616492376594", body).unwrap(),
+            "4/dpxb3iT4tp/ZCVEwSnEsLxx0bqyhLpdfOpc6fn7OR8+UClSV5n9E6aSS8MPtnRfp32bAb0nmbRn6H8ndwLUQ=="
+        );
+    }
+
+    #[test]
+    fn errors_are_reported() {
+        let error =
+            parse_response::<AddedOrder>(r#"{"error":["EOrder:Insufficient funds"]}"#).unwrap_err();
+
+        assert_eq!(error.to_string(), "EOrder:Insufficient funds");
+    }
+
+    #[test]
+    fn volume_leaves_the_fee_margin() {
+        assert_eq!(
+            volume(monero::Amount::from_monero(25.0).unwrap()).unwrap(),
+            "24.50000000"
+        );
+        assert!(volume(monero::Amount::ZERO).is_err());
+    }
+}
@@ -1,4 +1,5 @@
 pub mod cancel;
+pub mod presigned;
 pub mod punish;
 pub mod redeem;
 pub mod refund;
@@ -38,7 +38,7 @@ pub async fn cancel(
         // Alice already in final state
         | AliceState::BtcRedeemed
         | AliceState::XmrRefunded
-        | AliceState::BtcPunished
+        | AliceState::BtcPunished { .. }
         | AliceState::SafelyAborted => bail!("Swap is is in state {} which is not cancelable", state),
     };
 
@@ -0,0 +1,45 @@
+use crate::bitcoin::{Txid, Wallet};
+use crate::protocol::presigned::{Kind, PresignedTransaction};
+use crate::protocol::Database;
+use anyhow::{bail, Context, Result};
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// The presigned transactions of the swap. Swaps set up before they were kept
+/// have none.
+pub async fn presigned(swap_id: Uuid, db: Arc<dyn Database>) -> Result<Vec<PresignedTransaction>> {
+    let transactions = db.get_presigned_transactions(swap_id).await?;
+
+    if transactions.is_empty() {
+        bail!("No presigned transactions stored for swap {}", swap_id)
+    }
+
+    Ok(transactions)
+}
+
+/// Publishes the presigned transaction of `kind` as it was stored, without
+/// looking at the state of the swap. Fails if its timelock did not expire
+/// yet.
+pub async fn publish_presigned(
+    swap_id: Uuid,
+    kind: Kind,
+    bitcoin_wallet: Arc<Wallet>,
+    db: Arc<dyn Database>,
+) -> Result<Txid> {
+    let transaction = presigned(swap_id, db)
+        .await?
+        .into_iter()
+        .find(|transaction| transaction.kind == kind)
+        .with_context(|| {
+            format!(
+                "No presigned {} transaction stored for swap {}",
+                kind, swap_id
+            )
+        })?;
+
+    let (txid, _) = bitcoin_wallet
+        .broadcast(transaction.transaction, &kind.to_string())
+        .await?;
+
+    Ok(txid)
+}
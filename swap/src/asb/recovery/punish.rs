@@ -7,6 +7,7 @@ use std::sync::Arc;
 use uuid::Uuid;
 
 #[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
 pub enum Error {
     #[error("Cannot punish swap because it is in state {0} which is not punishable")]
     SwapNotPunishable(AliceState),
@@ -38,7 +39,7 @@ pub async fn punish(
         // Alice already in final state
         | AliceState::BtcRedeemed
         | AliceState::XmrRefunded
-        | AliceState::BtcPunished
+        | AliceState::BtcPunished { .. }
         | AliceState::SafelyAborted => bail!(Error::SwapNotPunishable(state)),
     };
 
@@ -46,7 +47,9 @@ pub async fn punish(
 
     let txid = state3.punish_btc(&bitcoin_wallet).await?;
 
-    let state = AliceState::BtcPunished;
+    let state = AliceState::BtcPunished {
+        state3: Some(state3),
+    };
     db.insert_latest_state(swap_id, state.clone().into())
         .await?;
 
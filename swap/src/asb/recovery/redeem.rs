@@ -37,7 +37,7 @@ pub async fn redeem(
         } => {
             tracing::info!(%swap_id, "Trying to redeem swap");
 
-            let redeem_tx = state3.signed_redeem_transaction(*encrypted_signature)?;
+            let redeem_tx = state3.signed_redeem_transaction(&encrypted_signature)?;
             let (txid, subscription) = bitcoin_wallet.broadcast(redeem_tx, "redeem").await?;
 
             subscription.wait_until_seen().await?;
@@ -81,7 +81,7 @@ pub async fn redeem(
         | AliceState::BtcPunishable { .. }
         | AliceState::BtcRedeemed
         | AliceState::XmrRefunded
-        | AliceState::BtcPunished
+        | AliceState::BtcPunished { .. }
         | AliceState::SafelyAborted => bail!(
             "Cannot redeem swap {} because it is in state {} which cannot be manually redeemed",
             swap_id,
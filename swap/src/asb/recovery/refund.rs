@@ -9,6 +9,7 @@ use std::sync::Arc;
 use uuid::Uuid;
 
 #[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
 pub enum Error {
     #[error(
         "Counterparty {0} did not refund the BTC yet. You can try again later or try to punish."
@@ -55,7 +56,7 @@ pub async fn refund(
         AliceState::BtcRedeemTransactionPublished { .. }
         | AliceState::BtcRedeemed
         | AliceState::XmrRefunded
-        | AliceState::BtcPunished
+        | AliceState::BtcPunished { .. }
         | AliceState::SafelyAborted => bail!(Error::SwapNotRefundable(state)),
     };
 
@@ -76,7 +77,7 @@ pub async fn refund(
             &monero_wallet,
             monero_wallet_restore_blockheight,
             swap_id.to_string(),
-            spend_key,
+            *spend_key,
             transfer_proof,
         )
         .await?;
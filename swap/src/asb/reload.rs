@@ -0,0 +1,370 @@
+use crate::asb::config::{read_config, Config};
+use crate::asb::price_feeds::PriceFeeds;
+use crate::asb::tracing::{LogLevel, Subsystem};
+use crate::asb::{KrakenRate, TradingControls};
+use anyhow::{anyhow, bail, Context, Result};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tracing_subscriber::filter::LevelFilter;
+use url::Url;
+
+/// A setting that differs between the running config and the config file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change {
+    pub field: &'static str,
+    pub old: String,
+    pub new: String,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} -> {}", self.field, self.old, self.new)
+    }
+}
+
+/// Returns the settings that changed from `running` to `new`.
+///
+/// Only the `[maker]` and `[logging]` sections can be changed at runtime, except
+/// for the maximum swap duration. Fails if anything else changed, in which
+/// case nothing must be applied.
+pub fn diff(running: &Config, new: &Config) -> Result<Vec<Change>> {
+    let restart_required = [
+        ("[data]", running.data != new.data),
+        ("[network]", running.network != new.network),
+        ("[bitcoin]", running.bitcoin != new.bitcoin),
+        ("[monero]", running.monero != new.monero),
+        ("[tor]", running.tor != new.tor),
+        ("[identities]", running.identities != new.identities),
+        ("[policy]", running.policy != new.policy),
+        ("[webhook]", running.webhook != new.webhook),
+        ("[alerts]", running.alerts != new.alerts),
+        ("[approvals]", running.approvals != new.approvals),
+        ("[health]", running.health != new.health),
+        ("[rebalance]", running.rebalance != new.rebalance),
+        (
+            "[backend_health]",
+            running.backend_health != new.backend_health,
+        ),
+        ("[load]", running.load != new.load),
+        ("[watchtower]", running.watchtower != new.watchtower),
+        // Part of the environment the event loops are started with
+        (
+            "maker.max_swap_duration_hours",
+            running.maker.max_swap_duration_hours != new.maker.max_swap_duration_hours,
+        ),
+    ]
+    .iter()
+    .filter(|(_, changed)| *changed)
+    .map(|(setting, _)| setting.to_string())
+    .collect::<Vec<_>>();
+
+    if !restart_required.is_empty() {
+        bail!(
+            "Changes to {} can only be applied by restarting the ASB",
+            restart_required.join(", ")
+        )
+    }
+
+    let changes = vec![
+        change(
+            "maker.min_buy_btc",
+            &running.maker.min_buy_btc,
+            &new.maker.min_buy_btc,
+        ),
+        change(
+            "maker.max_buy_btc",
+            &running.maker.max_buy_btc,
+            &new.maker.max_buy_btc,
+        ),
+        change(
+            "maker.ask_spread",
+            &running.maker.ask_spread,
+            &new.maker.ask_spread,
+        ),
+        change(
+            "maker.price_ticker_ws_url",
+            &running.maker.price_ticker_ws_url,
+            &new.maker.price_ticker_ws_url,
+        ),
+        change(
+            "maker.additional_price_ticker_ws_urls",
+            &urls(&running.maker.additional_price_ticker_ws_urls),
+            &urls(&new.maker.additional_price_ticker_ws_urls),
+        ),
+        change(
+            "maker.max_price_deviation",
+            &running.maker.max_price_deviation,
+            &new.maker.max_price_deviation,
+        ),
+        change(
+            "maker.max_price_age_secs",
+            &seconds(running.maker.max_price_age_secs),
+            &seconds(new.maker.max_price_age_secs),
+        ),
+        change("logging.level", &running.logging.level, &new.logging.level),
+        change(
+            "logging.subsystems",
+            &subsystem_levels(&running.logging.subsystems),
+            &subsystem_levels(&new.logging.subsystems),
+        ),
+    ];
+
+    Ok(changes.into_iter().flatten().collect())
+}
+
+fn subsystem_levels(subsystems: &BTreeMap<Subsystem, LevelFilter>) -> String {
+    if subsystems.is_empty() {
+        return "none".to_owned();
+    }
+
+    subsystems
+        .iter()
+        .map(|(subsystem, level)| format!("{}={}", subsystem, level))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn seconds(secs: Option<u64>) -> String {
+    secs.map(|secs| format!("{}s", secs))
+        .unwrap_or_else(|| "none".to_owned())
+}
+
+fn urls(urls: &[Url]) -> String {
+    if urls.is_empty() {
+        return "none".to_owned();
+    }
+
+    urls.iter()
+        .map(Url::to_string)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn change<T>(field: &'static str, old: &T, new: &T) -> Option<Change>
+where
+    T: PartialEq + fmt::Display,
+{
+    if old == new {
+        return None;
+    }
+
+    Some(Change {
+        field,
+        old: old.to_string(),
+        new: new.to_string(),
+    })
+}
+
+/// Re-reads the config file of a running ASB and applies the changes that are
+/// safe to apply at runtime.
+#[allow(missing_debug_implementations)]
+pub struct Reloader {
+    config_path: PathBuf,
+    running: Mutex<Config>,
+    controls: TradingControls,
+    rate: KrakenRate,
+    log_level: LogLevel,
+}
+
+impl Reloader {
+    pub fn new(
+        config_path: PathBuf,
+        running: Config,
+        controls: TradingControls,
+        rate: KrakenRate,
+        log_level: LogLevel,
+    ) -> Self {
+        Self {
+            config_path,
+            running: Mutex::new(running),
+            controls,
+            rate,
+            log_level,
+        }
+    }
+
+    pub fn reload(&self) -> Result<Vec<Change>> {
+        let new = read_config(self.config_path.clone())?
+            .map_err(|_| anyhow!("Config file {} does not exist", self.config_path.display()))?;
+
+        let mut running = self.running.lock().expect("config lock not poisoned");
+        let changes = diff(&running, &new)?;
+
+        if changes.is_empty() {
+            tracing::info!("Config file did not change");
+            return Ok(changes);
+        }
+
+        self.controls.update(|parameters| {
+            parameters.ask_spread = new.maker.ask_spread;
+            parameters.min_buy = new.maker.min_buy_btc;
+            parameters.max_buy = new.maker.max_buy_btc;
+        })?;
+
+        self.rate.update_price_feeds(|price_feeds| {
+            if running.maker.price_ticker_ws_urls() != new.maker.price_ticker_ws_urls() {
+                *price_feeds =
+                    PriceFeeds::connect(new.maker.price_ticker_ws_urls(), price_feeds.tolerance())?;
+            }
+            price_feeds.set_max_deviation(new.maker.max_price_deviation);
+            price_feeds.set_max_price_age(new.maker.max_price_age());
+
+            Ok(())
+        })?;
+
+        if running.logging != new.logging {
+            self.log_level.set_levels(new.logging.levels())?;
+        }
+
+        for change in &changes {
+            tracing::info!(field = change.field, old = %change.old, new = %change.new, "Applied config change");
+        }
+
+        *running = new;
+
+        Ok(changes)
+    }
+}
+
+/// Reloads the config file every time the process receives a SIGHUP.
+#[cfg(unix)]
+pub async fn reload_on_sighup(reloader: Arc<Reloader>) -> Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangups = signal(SignalKind::hangup()).context("Failed to listen for SIGHUP")?;
+
+    while hangups.recv().await.is_some() {
+        tracing::info!("Received SIGHUP, reloading config file");
+
+        if let Err(error) = reloader.reload() {
+            tracing::error!("Failed to reload config file: {:#}", error);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub async fn reload_on_sighup(_: Arc<Reloader>) -> Result<()> {
+    bail!("Reloading the config file on SIGHUP is only supported on unix systems")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asb::config::{Data, Logging};
+    use rust_decimal_macros::dec;
+
+    fn config() -> Config {
+        toml::from_str(
+            r#"
+            [data]
+            dir = "/var/lib/asb"
+
+            [network]
+            listen = ["/ip4/0.0.0.0/tcp/9939"]
+
+            [bitcoin]
+            electrum_rpc_url = "ssl://electrum.blockstream.info:60002"
+            target_block = 1
+            network = "Testnet"
+
+            [monero]
+            wallet_rpc_url = "http://127.0.0.1:38083/json_rpc"
+            network = "Stagenet"
+
+            [tor]
+            control_port = 9051
+            socks5_port = 9050
+
+            [maker]
+            min_buy_btc = 0.002
+            max_buy_btc = 0.02
+            ask_spread = 0.02
+            price_ticker_ws_url = "wss://ws.kraken.com"
+            "#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn given_unchanged_config_then_no_changes() {
+        assert!(diff(&config(), &config()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn given_maker_and_logging_changes_then_diff_lists_them() {
+        let running = config();
+        let mut new = config();
+        new.maker.ask_spread = dec!(0.03);
+        new.logging = Logging {
+            level: LevelFilter::INFO,
+            subsystems: vec![(Subsystem::Network, LevelFilter::TRACE)]
+                .into_iter()
+                .collect(),
+        };
+
+        let changes = diff(&running, &new).unwrap();
+
+        assert_eq!(
+            changes.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            vec![
+                "maker.ask_spread: 0.02 -> 0.03",
+                "logging.level: debug -> info",
+                "logging.subsystems: none -> network=trace"
+            ]
+        );
+    }
+
+    #[test]
+    fn given_additional_price_tickers_then_diff_lists_them() {
+        let running = config();
+        let mut new = config();
+        new.maker.additional_price_ticker_ws_urls = vec![
+            "wss://ticker.example.com".parse().unwrap(),
+            "wss://ticker.example.org".parse().unwrap(),
+        ];
+
+        let changes = diff(&running, &new).unwrap();
+
+        assert_eq!(
+            changes.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            vec!["maker.additional_price_ticker_ws_urls: none -> wss://ticker.example.com/,wss://ticker.example.org/"]
+        );
+    }
+
+    #[test]
+    fn given_data_dir_or_network_changes_then_reload_is_rejected() {
+        let running = config();
+        let mut new = config();
+        new.data = Data {
+            dir: "/tmp/asb".into(),
+            retention_days: None,
+        };
+        new.bitcoin.network = bitcoin::Network::Bitcoin;
+        new.maker.ask_spread = dec!(0.03);
+
+        let error = diff(&running, &new).unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "Changes to [data], [bitcoin] can only be applied by restarting the ASB"
+        );
+    }
+
+    #[test]
+    fn given_max_swap_duration_changes_then_reload_is_rejected() {
+        let running = config();
+        let mut new = config();
+        new.maker.max_swap_duration_hours = Some(24);
+
+        let error = diff(&running, &new).unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "Changes to maker.max_swap_duration_hours can only be applied by restarting the ASB"
+        );
+    }
+}
@@ -0,0 +1,212 @@
+//! Running the ASB as a service, e.g. with systemd.
+//!
+//! The service manager learns about the state of the ASB from
+//! - the exit code, which tells whether restarting the ASB can help, see
+//!   [`exit_code`]
+//! - the readiness notification of units with `Type=notify`, see
+//!   [`notify_ready`]
+//! - the `/livez` and `/readyz` HTTP endpoints if the `[health]` section is
+//!   configured, see [`serve`]
+
+use crate::asb::BackendHealth;
+use anyhow::{Context, Result};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+/// The ASB stopped because of a failure that persists across restarts, e.g.
+/// an invalid config file. This is `EX_CONFIG` of `sysexits.h`.
+pub const EXIT_FATAL: i32 = 78;
+
+/// The ASB stopped because of a failure that may be gone after a restart,
+/// e.g. a lost connection to Electrum. This is `EX_TEMPFAIL` of `sysexits.h`.
+pub const EXIT_RECOVERABLE: i32 = 75;
+
+/// Marks an error as fatal, e.g. `read_config(path).context(Fatal)?`.
+#[derive(Debug, Clone, Copy, thiserror::Error)]
+#[error("Invalid setup")]
+pub struct Fatal;
+
+/// The exit code for `error`, errors are recoverable unless marked as
+/// [`Fatal`].
+pub fn exit_code(error: &anyhow::Error) -> i32 {
+    if error.downcast_ref::<Fatal>().is_some() {
+        EXIT_FATAL
+    } else {
+        EXIT_RECOVERABLE
+    }
+}
+
+/// Whether the logs end up in the journal, which adds its own timestamps.
+pub fn logs_to_journal() -> bool {
+    std::env::var_os("JOURNAL_STREAM").is_some()
+}
+
+/// Tells systemd that the ASB finished starting up, if it was started by a
+/// unit with `Type=notify`.
+#[cfg(unix)]
+pub fn notify_ready() {
+    use std::os::unix::net::UnixDatagram;
+
+    let socket = match std::env::var_os("NOTIFY_SOCKET") {
+        Some(socket) => socket,
+        None => return,
+    };
+
+    let result = UnixDatagram::unbound().and_then(|datagram| datagram.send_to(b"READY=1", &socket));
+    if let Err(error) = result {
+        tracing::warn!("Failed to notify systemd that the ASB is ready: {}", error);
+    }
+}
+
+#[cfg(not(unix))]
+pub fn notify_ready() {}
+
+/// Whether the ASB finished starting up and its backends are healthy, shared
+/// with the health endpoints.
+#[derive(Debug, Clone, Default)]
+pub struct Health {
+    ready: Arc<AtomicBool>,
+    backends: BackendHealth,
+}
+
+impl Health {
+    pub fn new(backends: BackendHealth) -> Self {
+        Self {
+            ready: Default::default(),
+            backends,
+        }
+    }
+
+    pub fn set_ready(&self) {
+        self.ready.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.ready.load(Ordering::SeqCst)
+    }
+}
+
+/// Serves `GET /livez`, which succeeds as long as the ASB is running, and
+/// `GET /readyz`, which fails with `503 Service Unavailable` until the ASB
+/// finished starting up and while trading is suspended because a backend is
+/// degraded.
+pub async fn serve(address: SocketAddr, health: Health) -> Result<()> {
+    let listener = TcpListener::bind(address)
+        .await
+        .with_context(|| format!("Failed to bind health endpoints to {}", address))?;
+
+    tracing::info!(%address, "Serving health endpoints");
+
+    loop {
+        let (stream, _) = listener
+            .accept()
+            .await
+            .context("Failed to accept health connection")?;
+
+        let health = health.clone();
+        tokio::spawn(async move {
+            if let Err(error) = handle_connection(stream, &health).await {
+                tracing::debug!("Failed to answer health request: {:#}", error);
+            }
+        });
+    }
+}
+
+async fn handle_connection(stream: TcpStream, health: &Health) -> Result<()> {
+    let (read, mut write) = stream.into_split();
+    let mut read = BufReader::new(read);
+
+    let mut request_line = String::new();
+    read.read_line(&mut request_line).await?;
+
+    // The headers are not needed but have to be read, closing the connection
+    // with unread data would reset it before the client read the response
+    let mut header = String::new();
+    while read.read_line(&mut header).await? > 0 && !header.trim().is_empty() {
+        header.clear();
+    }
+
+    write
+        .write_all(response(&request_line, health).as_bytes())
+        .await?;
+    write.shutdown().await?;
+
+    Ok(())
+}
+
+fn response(request_line: &str, health: &Health) -> String {
+    let mut request = request_line.split_whitespace();
+    let (status, body) = match (request.next(), request.next()) {
+        (Some("GET"), Some("/livez")) => ("200 OK", "alive"),
+        (Some("GET"), Some("/readyz")) if !health.is_ready() => {
+            ("503 Service Unavailable", "starting")
+        }
+        (Some("GET"), Some("/readyz")) if health.backends.degraded().is_some() => {
+            ("503 Service Unavailable", "degraded")
+        }
+        (Some("GET"), Some("/readyz")) => ("200 OK", "ready"),
+        _ => ("404 Not Found", "not found"),
+    };
+
+    format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asb::backend_health::Backend;
+    use anyhow::anyhow;
+
+    #[test]
+    fn only_errors_marked_as_fatal_exit_with_fatal_code() {
+        let fatal = anyhow!("Expected monero network in config file to be Mainnet")
+            .context(Fatal)
+            .context("Failed to start");
+        let recoverable = anyhow!("Failed to connect to Electrum");
+
+        assert_eq!(exit_code(&fatal), EXIT_FATAL);
+        assert_eq!(exit_code(&recoverable), EXIT_RECOVERABLE);
+    }
+
+    #[test]
+    fn not_ready_until_startup_finished() {
+        let health = Health::default();
+
+        assert!(response("GET /livez HTTP/1.1\r\n", &health).starts_with("HTTP/1.1 200 OK"));
+        assert!(response("GET /readyz HTTP/1.1\r\n", &health)
+            .starts_with("HTTP/1.1 503 Service Unavailable"));
+
+        health.set_ready();
+
+        assert!(response("GET /readyz HTTP/1.1\r\n", &health).starts_with("HTTP/1.1 200 OK"));
+    }
+
+    #[test]
+    fn not_ready_while_a_backend_is_degraded() {
+        let backends = BackendHealth::default();
+        let health = Health::new(backends.clone());
+        health.set_ready();
+
+        backends.record(Backend::Electrum, Err("unreachable".to_owned()), 0);
+
+        assert!(response("GET /readyz HTTP/1.1\r\n", &health).ends_with("\r\n\r\ndegraded"));
+    }
+
+    #[test]
+    fn unknown_paths_are_not_found() {
+        let response = response("GET /metrics HTTP/1.1\r\n", &Health::default());
+
+        assert_eq!(
+            response,
+            "HTTP/1.1 404 Not Found\r\nContent-Type: text/plain\r\nContent-Length: 9\r\nConnection: close\r\n\r\nnot found"
+        );
+    }
+}
@@ -0,0 +1,116 @@
+use anyhow::{anyhow, bail, Context, Result};
+use futures::future;
+use std::future::Future;
+use tokio::task::{JoinError, JoinHandle};
+
+/// The long running tasks of the ASB, e.g. the event loops and the executors
+/// of the swaps.
+///
+/// None of the tasks is expected to stop while the ASB is running. The first
+/// one that stops, either by returning or by panicking, determines the outcome
+/// of [`Supervisor::run`].
+#[derive(Debug, Default)]
+pub struct Supervisor {
+    names: Vec<String>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl Supervisor {
+    pub fn spawn(
+        &mut self,
+        name: impl Into<String>,
+        task: impl Future<Output = ()> + Send + 'static,
+    ) {
+        self.names.push(name.into());
+        self.tasks.push(tokio::spawn(task));
+    }
+
+    /// Resolves once the first task stopped, all other tasks are aborted.
+    pub async fn run(self) -> Result<()> {
+        if self.tasks.is_empty() {
+            return Ok(());
+        }
+
+        let (result, index, remaining) = future::select_all(self.tasks).await;
+        for task in remaining {
+            task.abort();
+        }
+
+        let name = &self.names[index];
+        match result {
+            Ok(()) => bail!("{} stopped unexpectedly", name),
+            Err(error) => Err(task_failed(error)).with_context(|| format!("{} failed", name)),
+        }
+    }
+}
+
+/// Runs `task` on its own tokio task, so a panic while executing it is
+/// returned as an error instead of being lost with the task.
+pub async fn catch_panic<T>(task: impl Future<Output = Result<T>> + Send + 'static) -> Result<T>
+where
+    T: Send + 'static,
+{
+    tokio::spawn(task).await.map_err(task_failed)?
+}
+
+fn task_failed(error: JoinError) -> anyhow::Error {
+    if !error.is_panic() {
+        return anyhow!("Task was cancelled");
+    }
+
+    let panic = error.into_panic();
+    let message = panic
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| panic.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown cause".to_owned());
+
+    anyhow!("Task panicked: {}", message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn panic_is_returned_as_error() {
+        let result = catch_panic(execute_swap()).await;
+
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Task panicked: swap went wrong"
+        );
+    }
+
+    #[tokio::test]
+    async fn first_stopped_task_determines_outcome() {
+        let mut supervisor = Supervisor::default();
+        supervisor.spawn("Event loop", future::pending());
+        supervisor.spawn("Swap executor", async {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        });
+
+        let error = supervisor.run().await.unwrap_err();
+
+        assert_eq!(error.to_string(), "Swap executor stopped unexpectedly");
+    }
+
+    #[tokio::test]
+    async fn panicking_task_is_reported_with_its_name() {
+        let mut supervisor = Supervisor::default();
+        supervisor.spawn("Event loop", future::pending());
+        supervisor.spawn("Swap executor", async { panic!("boom") });
+
+        let error = supervisor.run().await.unwrap_err();
+
+        assert_eq!(
+            format!("{:#}", error),
+            "Swap executor failed: Task panicked: boom"
+        );
+    }
+
+    async fn execute_swap() -> Result<()> {
+        panic!("swap went wrong")
+    }
+}
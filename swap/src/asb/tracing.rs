@@ -1,30 +1,220 @@
-use anyhow::Result;
-use tracing_subscriber::filter::LevelFilter;
+use crate::otlp;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DisplayFromStr};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use tracing_subscriber::filter::{EnvFilter, LevelFilter};
 use tracing_subscriber::fmt::time::ChronoLocal;
-use tracing_subscriber::FmtSubscriber;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, reload, Registry};
+use url::Url;
 
-pub fn init(level: LevelFilter, json_format: bool, timestamp: bool) -> Result<()> {
-    if level == LevelFilter::OFF {
-        return Ok(());
+/// Dependencies that are logged at `warn` unless they belong to a subsystem,
+/// as in the tests.
+const DEPENDENCY_LEVEL: LevelFilter = LevelFilter::WARN;
+
+/// A part of the ASB whose log level can be set separately.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+    strum::Display,
+    strum::EnumString,
+    strum::EnumIter,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum Subsystem {
+    /// The swap state machines.
+    Protocol,
+    /// libp2p and the protocols between maker and taker.
+    Network,
+    /// The Bitcoin wallet and Electrum.
+    Bitcoin,
+    /// The Monero wallet and its RPC client.
+    Monero,
+    /// The database.
+    Db,
+}
+
+impl Subsystem {
+    /// The tracing targets logged by the subsystem.
+    pub fn targets(self) -> &'static [&'static str] {
+        match self {
+            Subsystem::Protocol => &["swap::protocol"],
+            Subsystem::Network => &["swap::network", "swap::asb::network", "libp2p"],
+            Subsystem::Bitcoin => &["swap::bitcoin", "bdk", "electrum_client"],
+            Subsystem::Monero => &["swap::monero", "monero_rpc"],
+            Subsystem::Db => &["swap::database", "sqlx"],
+        }
+    }
+}
+
+/// The log level of the ASB and the subsystems that deviate from it.
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Levels {
+    #[serde_as(as = "DisplayFromStr")]
+    pub level: LevelFilter,
+    #[serde_as(as = "BTreeMap<_, DisplayFromStr>")]
+    pub subsystems: BTreeMap<Subsystem, LevelFilter>,
+}
+
+impl Levels {
+    pub fn new(level: LevelFilter) -> Self {
+        Self {
+            level,
+            subsystems: BTreeMap::new(),
+        }
+    }
+
+    fn env_filter(&self) -> EnvFilter {
+        EnvFilter::new(self.directives().join(","))
+    }
+
+    fn directives(&self) -> Vec<String> {
+        let mut directives = vec![
+            DEPENDENCY_LEVEL.to_string(),
+            format!("asb={}", self.level),
+            format!("swap={}", self.level),
+        ];
+
+        for (subsystem, level) in &self.subsystems {
+            for target in subsystem.targets() {
+                directives.push(format!("{}={}", target, level));
+            }
+        }
+
+        directives
+    }
+}
+
+/// Allows changing the log levels after tracing has been initialized.
+#[derive(Clone, Debug)]
+pub struct LogLevel {
+    handle: reload::Handle<EnvFilter, Registry>,
+    levels: Arc<Mutex<Levels>>,
+}
+
+impl LogLevel {
+    pub fn levels(&self) -> Levels {
+        self.lock().clone()
+    }
+
+    pub fn set(&self, level: LevelFilter) -> Result<()> {
+        self.update(|levels| levels.level = level)
     }
 
+    /// Sets the level of `subsystem`, which follows the level of the ASB again
+    /// if `level` is `None`.
+    pub fn set_subsystem(&self, subsystem: Subsystem, level: Option<LevelFilter>) -> Result<()> {
+        self.update(|levels| match level {
+            Some(level) => {
+                levels.subsystems.insert(subsystem, level);
+            }
+            None => {
+                levels.subsystems.remove(&subsystem);
+            }
+        })
+    }
+
+    pub fn set_levels(&self, new: Levels) -> Result<()> {
+        self.update(|levels| *levels = new)
+    }
+
+    fn update(&self, change: impl FnOnce(&mut Levels)) -> Result<()> {
+        let mut levels = self.lock();
+        let mut changed = levels.clone();
+        change(&mut changed);
+
+        self.handle
+            .reload(changed.env_filter())
+            .context("Failed to change log level")?;
+        *levels = changed;
+
+        Ok(())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Levels> {
+        self.levels.lock().expect("log levels lock not poisoned")
+    }
+}
+
+pub fn init(
+    levels: Levels,
+    json_format: bool,
+    timestamp: bool,
+    otlp_endpoint: Option<&Url>,
+) -> Result<LogLevel> {
     let is_terminal = atty::is(atty::Stream::Stderr);
 
-    let builder = FmtSubscriber::builder()
-        .with_env_filter(format!("asb={},swap={}", level, level))
+    let tracer = otlp_endpoint
+        .map(|endpoint| otlp::tracer(endpoint, "asb"))
+        .transpose()?;
+
+    let (filter, handle) = reload::Layer::new(levels.env_filter());
+    let registry = tracing_subscriber::registry()
+        .with(filter)
+        .with(otlp::layer(tracer.as_ref()));
+
+    let layer = fmt::layer()
         .with_writer(std::io::stderr)
         .with_ansi(is_terminal)
         .with_timer(ChronoLocal::with_format("%F %T".to_owned()))
         .with_target(false);
 
     match (json_format, timestamp) {
-        (true, true) => builder.json().init(),
-        (true, false) => builder.json().without_time().init(),
-        (false, true) => builder.init(),
-        (false, false) => builder.without_time().init(),
+        (true, true) => registry.with(layer.json()).try_init()?,
+        (true, false) => registry.with(layer.json().without_time()).try_init()?,
+        (false, true) => registry.with(layer).try_init()?,
+        (false, false) => registry.with(layer.without_time()).try_init()?,
     }
 
-    tracing::info!(%level, "Initialized tracing");
+    tracing::info!(level = %levels.level, "Initialized tracing");
+    if let Some(endpoint) = otlp_endpoint {
+        tracing::info!(%endpoint, "Exporting spans to OpenTelemetry collector");
+    }
+
+    Ok(LogLevel {
+        handle,
+        levels: Arc::new(Mutex::new(levels)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use strum::IntoEnumIterator;
+
+    #[test]
+    fn subsystems_override_the_level_of_their_targets() {
+        let mut levels = Levels::new(LevelFilter::INFO);
+        levels.subsystems.insert(Subsystem::Db, LevelFilter::TRACE);
 
-    Ok(())
+        assert_eq!(levels.directives(), vec![
+            "warn",
+            "asb=info",
+            "swap=info",
+            "swap::database=trace",
+            "sqlx=trace"
+        ]);
+    }
+
+    #[test]
+    fn every_subsystem_parses_from_its_name() {
+        for subsystem in Subsystem::iter() {
+            assert_eq!(
+                subsystem.to_string().parse::<Subsystem>().unwrap(),
+                subsystem
+            );
+        }
+    }
 }
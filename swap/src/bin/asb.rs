@@ -12,41 +12,90 @@
 #![forbid(unsafe_code)]
 #![allow(non_snake_case)]
 
-use anyhow::{bail, Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use comfy_table::Table;
+use dialoguer::theme::ColorfulTheme;
+use dialoguer::Password;
 use libp2p::core::multiaddr::Protocol;
 use libp2p::core::Multiaddr;
 use libp2p::swarm::AddressScore;
-use libp2p::Swarm;
+use libp2p::{identity, PeerId, Swarm};
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
 use std::convert::TryInto;
 use std::env;
+use std::iter;
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::sync::Arc;
+use std::time::Duration;
 use structopt::clap;
 use structopt::clap::ErrorKind;
+use swap::asb::admin::Admin;
+use swap::asb::alert::{Alert, Alerter, Severity, Sink, Smtp, Telegram};
 use swap::asb::command::{parse_args, Arguments, Command};
 use swap::asb::config::{
-    initial_setup, query_user_for_initial_config, read_config, Config, ConfigNotInitialized,
+    initial_setup, query_user_for_initial_config, read_config, Alerts, Config,
+    ConfigNotInitialized, KrakenRebalancerConf,
 };
-use swap::asb::{cancel, punish, redeem, refund, safely_abort, EventLoop, Finality, KrakenRate};
-use swap::database::open_db;
+use swap::asb::notifications::Notifier;
+use swap::asb::policy::{AcceptAll, Policy, Webhook};
+use swap::asb::port_mapping::PortMapping;
+use swap::asb::price_feeds::{self, PriceFeeds};
+use swap::asb::quotes::{QuoteRecord, QuoteStats};
+use swap::asb::rebalance::{self, Rebalancer, Rebalancing};
+use swap::asb::reload::Reloader;
+use swap::asb::service::{self, Fatal, Health};
+use swap::asb::tracing::Levels;
+use swap::asb::{
+    admin, cancel, catch_panic, maker_key, peer_identity, presigned, publish_presigned, punish,
+    redeem, refund, reload, safely_abort, Approvals, BackendHealth, EventLoop, Finality,
+    KrakenRate, Load, Supervisor, TradingControls, TradingParameters,
+};
+use swap::data::DataDir;
+use swap::database::{self, open_db};
+use swap::explorer::Explorer;
+use swap::fs::lock_data_dir;
 use swap::monero::Amount;
+use swap::network::address_family::{self, AddressFamily};
+use swap::network::maker_key::MakerKey;
 use swap::network::rendezvous::XmrBtcNamespace;
-use swap::network::swarm;
+use swap::network::{swarm, watchtower};
 use swap::protocol::alice::{run, AliceState};
+use swap::protocol::{alice, Database};
 use swap::seed::Seed;
+use swap::sync_check;
 use swap::tor::AuthenticatedClient;
-use swap::{asb, bitcoin, kraken, monero, tor};
+use swap::version::{agent_version, BuildInfo};
+use swap::{asb, bitcoin, kraken, monero, otlp, tor};
+use tokio::sync::mpsc;
+use tracing::Instrument;
 use tracing_subscriber::filter::LevelFilter;
 
 const DEFAULT_WALLET_NAME: &str = "asb-wallet";
 
+/// How long `init` waits for the first price from the price ticker.
+const PRICE_TICKER_TIMEOUT: Duration = Duration::from_secs(30);
+
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() {
+    if let Err(error) = run_asb().await {
+        let exit_code = service::exit_code(&error);
+        if tracing::dispatcher::has_been_set() {
+            tracing::error!(%exit_code, "{:#}", error);
+        } else {
+            eprintln!("Error: {:?}", error);
+        }
+
+        std::process::exit(exit_code);
+    }
+}
+
+async fn run_asb() -> Result<()> {
     let Arguments {
         testnet,
         json,
         disable_timestamp,
+        otlp_endpoint,
         config_path,
         env_config,
         cmd,
@@ -60,44 +109,110 @@ async fn main() -> Result<()> {
                         std::process::exit(0);
                     }
                     _ => {
-                        bail!(e);
+                        eprintln!("{}", clap_err.message);
+                        std::process::exit(service::EXIT_FATAL);
                     }
                 }
             }
-            bail!(e);
+            return Err(e.context(Fatal));
         }
     };
 
-    asb::tracing::init(LevelFilter::DEBUG, json, !disable_timestamp).expect("initialize tracing");
+    // Works without a config, the output is meant to be pasted into bug reports
+    if matches!(cmd, Command::Version) {
+        let build_info = BuildInfo::current();
+        if json {
+            println!("{}", serde_json::to_string(&build_info)?);
+        } else {
+            println!("{}", build_info);
+        }
+
+        return Ok(());
+    }
 
-    let config = match read_config(config_path.clone())? {
+    let log_level = asb::tracing::init(
+        Levels::new(LevelFilter::DEBUG),
+        json,
+        !disable_timestamp && !service::logs_to_journal(),
+        otlp_endpoint.as_ref(),
+    )
+    .expect("initialize tracing");
+
+    let config = match read_config(config_path.clone()).context(Fatal)? {
         Ok(config) => config,
+        Err(ConfigNotInitialized {}) if !atty::is(atty::Stream::Stdin) => {
+            return Err(anyhow!(
+                "Config file {} does not exist, run `asb init` in a terminal to create it",
+                config_path.display()
+            )
+            .context(Fatal));
+        }
         Err(ConfigNotInitialized {}) => {
             initial_setup(config_path.clone(), query_user_for_initial_config(testnet)?)?;
-            read_config(config_path)?.expect("after initial setup config can be read")
+            read_config(config_path.clone())?.expect("after initial setup config can be read")
         }
     };
+    log_level.set_levels(config.logging.levels())?;
+    config.validate_identities().context(Fatal)?;
 
     if config.monero.network != env_config.monero_network {
-        bail!(format!(
+        return Err(anyhow!(
             "Expected monero network in config file to be {:?} but was {:?}",
-            env_config.monero_network, config.monero.network
-        ));
+            env_config.monero_network,
+            config.monero.network
+        )
+        .context(Fatal));
     }
     if config.bitcoin.network != env_config.bitcoin_network {
-        bail!(format!(
+        return Err(anyhow!(
             "Expected bitcoin network in config file to be {:?} but was {:?}",
-            env_config.bitcoin_network, config.bitcoin.network
-        ));
+            env_config.bitcoin_network,
+            config.bitcoin.network
+        )
+        .context(Fatal));
     }
+    let env_config = env_config.with_max_swap_duration_hours(config.maker.max_swap_duration_hours);
+
+    // Only commands that do not act on swaps or the wallets may run next to a
+    // running ASB
+    let _data_dir_lock = match cmd {
+        Command::History
+        | Command::Config
+        | Command::Admin { .. }
+        | Command::ReportQuotes
+        | Command::Presigned { publish: None, .. } => None,
+        _ => Some(lock_data_dir(&config.data.dir)?),
+    };
 
-    let db = open_db(config.data.dir.join("sqlite")).await?;
+    if let Command::Init { restore } = cmd {
+        return init(&config, env_config, restore).await;
+    }
+
+    let db = open_db(DataDir::new(&config.data.dir).database()).await?;
 
     let seed =
         Seed::from_file_or_generate(&config.data.dir).expect("Could not retrieve/initialize seed");
 
     match cmd {
-        Command::Start { resume_only } => {
+        Command::Start {
+            resume_only,
+            regenerate_identity,
+            rotate_maker_key,
+        } => {
+            tracing::info!(agent = %agent_version("asb"), "Starting ASB");
+
+            let backend_health = BackendHealth::default();
+            let load = Load::new(config.load.map(|load| load.limits()).unwrap_or_default());
+            let health = Health::new(backend_health.clone());
+            if let Some(health_conf) = config.health {
+                let health = health.clone();
+                tokio::spawn(async move {
+                    if let Err(error) = service::serve(health_conf.listen, health).await {
+                        tracing::warn!("Health endpoints are not available: {:#}", error);
+                    }
+                });
+            }
+
             let bitcoin_wallet = init_bitcoin_wallet(&config, &seed, env_config).await?;
 
             let monero_wallet = init_monero_wallet(&config, env_config).await?;
@@ -116,7 +231,54 @@ async fn main() -> Result<()> {
                 tracing::info!(%monero_balance, "Initialized Monero wallet");
             }
 
-            let kraken_price_updates = kraken::connect(config.maker.price_ticker_ws_url.clone())?;
+            let out_of_sync = sync_check::check(
+                &bitcoin_wallet,
+                &monero_wallet,
+                env_config,
+                Default::default(),
+            )
+            .await;
+            for problem in out_of_sync {
+                tracing::warn!(
+                    "{}, swaps may not be able to act on expired timelocks in time",
+                    problem
+                );
+            }
+
+            let default_identity = if regenerate_identity {
+                let identity = peer_identity::regenerate(&config.data.dir)?;
+                tracing::warn!(
+                    peer_id = %PeerId::from(identity.public()),
+                    "Regenerated the identity of the ASB, takers that know the previous peer id will no longer find it"
+                );
+                identity
+            } else {
+                peer_identity::load(&config.data.dir, &seed)?
+            };
+            let default_maker_key = if rotate_maker_key {
+                let maker_key = maker_key::rotate(&config.data.dir, &seed)?;
+                tracing::info!(
+                    maker_key = %maker_key.public(),
+                    maker_id = %maker_key.id(),
+                    "Rotated the maker key"
+                );
+                maker_key
+            } else {
+                maker_key::load(&config.data.dir, &seed)?
+            };
+
+            let backend_thresholds = config
+                .backend_health
+                .map(|backend_health| backend_health.thresholds())
+                .unwrap_or_default();
+            let price_feeds = PriceFeeds::connect(
+                config.maker.price_ticker_ws_urls(),
+                price_feeds::Tolerance {
+                    max_deviation: config.maker.max_price_deviation,
+                    max_age: backend_thresholds.max_rate_age,
+                    max_price_age: config.maker.max_price_age(),
+                },
+            )?;
 
             // setup Tor hidden services
             let tor_client =
@@ -135,15 +297,29 @@ async fn main() -> Result<()> {
                 }
             };
 
-            let kraken_rate = KrakenRate::new(config.maker.ask_spread, kraken_price_updates);
-            let mut swarm = swarm::asb(
-                &seed,
-                config.maker.min_buy_btc,
-                config.maker.max_buy_btc,
+            let controls = TradingControls::new(TradingParameters {
+                paused: resume_only,
+                ask_spread: config.maker.ask_spread,
+                min_buy: config.maker.min_buy_btc,
+                max_buy: config.maker.max_buy_btc,
+            })
+            .with_backend_health(backend_health.clone())
+            .with_load(load.clone());
+            let kraken_rate = KrakenRate::new(controls.clone(), price_feeds);
+            let reloader = Arc::new(Reloader::new(
+                config_path,
+                config.clone(),
+                controls.clone(),
                 kraken_rate.clone(),
-                resume_only,
-                env_config,
-                config.network.rendezvous_point.map(|rendezvous_point| {
+                log_level.clone(),
+            ));
+            tokio::spawn(reload::reload_on_sighup(reloader.clone()));
+
+            let rendezvous = config
+                .network
+                .rendezvous_point
+                .clone()
+                .map(|rendezvous_point| {
                     (
                         rendezvous_point,
                         if testnet {
@@ -152,63 +328,256 @@ async fn main() -> Result<()> {
                             XmrBtcNamespace::Mainnet
                         },
                     )
-                }),
-            )?;
-
-            for listen in config.network.listen.clone() {
-                Swarm::listen_on(&mut swarm, listen.clone())
-                    .with_context(|| format!("Failed to listen on network interface {}", listen))?;
+                });
+            let sync_stats = bitcoin_wallet.sync_stats();
+            let bitcoin_wallet = Arc::new(bitcoin_wallet);
+            let monero_wallet = Arc::new(monero_wallet);
+            let policy: Arc<dyn Policy> = match config.policy {
+                Some(policy) => {
+                    tracing::info!(url = %policy.webhook_url, "Quotes and swaps are decided by policy webhook");
+                    Arc::new(Webhook::new(policy.webhook_url)?)
+                }
+                None => Arc::new(AcceptAll),
+            };
+            let notifier = config
+                .webhook
+                .map(|webhook| Notifier::new(webhook.url, webhook.secret));
+            let watchtower = config
+                .watchtower
+                .as_ref()
+                .map(|watchtower| watchtower.address.clone());
+            if let Some(address) = &watchtower {
+                tracing::info!(%address, "Registering the presigned transactions of every swap with watchtower");
+            }
+            let alerter = setup_alerter(config.alerts)?;
+            let min_approval_btc = config.approvals.map(|approvals| approvals.min_btc);
+            if let Some(min_btc) = min_approval_btc {
+                tracing::info!(%min_btc, "Locking the Monero of swaps of at least this amount requires approval");
             }
+            let approvals = Approvals::new(min_approval_btc);
 
-            tracing::info!(peer_id = %swarm.local_peer_id(), "Network layer initialized");
+            let mut supervisor = Supervisor::default();
+            supervisor.spawn(
+                "Backend health checks",
+                backend_health.clone().run(
+                    backend_thresholds,
+                    kraken_rate.clone(),
+                    bitcoin_wallet.clone(),
+                    monero_wallet.clone(),
+                    alerter.clone(),
+                ),
+            );
+            supervisor.spawn("Load probe", load.clone().run());
+            let mut connection_stats = BTreeMap::new();
+            let mut port_mapping = if config.network.port_mapping {
+                match PortMapping::new().await {
+                    Ok(port_mapping) => Some(port_mapping),
+                    Err(error) => {
+                        tracing::warn!("Not mapping any ports: {:#}", error);
+                        None
+                    }
+                }
+            } else {
+                None
+            };
+
+            // Additional identities share the wallets, the database and the price
+            // ticker but are not reachable through the Tor hidden service
+            let address_family = config.network.address_family;
+            for identity in config.identities {
+                let controls = TradingControls::new(TradingParameters {
+                    paused: resume_only,
+                    ask_spread: identity.ask_spread,
+                    min_buy: identity.min_buy_btc,
+                    max_buy: identity.max_buy_btc,
+                })
+                .with_backend_health(backend_health.clone())
+                .with_load(load.clone());
+                let rate = kraken_rate.with_controls(controls.clone());
+
+                let listen = address_family::select(identity.listen, address_family);
+                let mut external_addresses =
+                    address_family::select(identity.external_addresses, address_family);
+                if let Some(port_mapping) = port_mapping.as_mut() {
+                    external_addresses.extend(port_mapping.map(&listen).await);
+                }
 
-            for external_address in config.network.external_addresses {
-                let _ = Swarm::add_external_address(
-                    &mut swarm,
-                    external_address,
-                    AddressScore::Infinite,
+                let (event_loop, swap_receiver) = maker_event_loop(
+                    &identity.name,
+                    seed.derive_libp2p_identity_for(&identity.name),
+                    MakerKey::new(seed.derive_maker_key_for(&identity.name)),
+                    listen,
+                    external_addresses,
+                    controls,
+                    rate.clone(),
+                    policy.clone(),
+                    notifier.clone(),
+                    watchtower.clone(),
+                    config.bitcoin.cpfp,
+                    approvals.clone(),
+                    env_config,
+                    rendezvous.clone(),
+                    config.network.relays.clone(),
+                    bitcoin_wallet.clone(),
+                    monero_wallet.clone(),
+                    db.clone(),
+                )?;
+
+                connection_stats.insert(identity.name.clone(), event_loop.connection_stats());
+
+                let span = tracing::info_span!("identity", name = %identity.name);
+                supervisor.spawn(
+                    format!("Swap executor of identity {}", identity.name),
+                    run_swaps(swap_receiver, rate, alerter.clone(), load.clone())
+                        .instrument(span.clone()),
+                );
+                supervisor.spawn(
+                    format!("Event loop of identity {}", identity.name),
+                    event_loop.run().instrument(span),
                 );
             }
 
-            let (event_loop, mut swap_receiver) = EventLoop::new(
-                swarm,
+            let listen = config.network.listen();
+            let mut external_addresses = config.network.external_addresses();
+            if let Some(mut port_mapping) = port_mapping {
+                external_addresses.extend(port_mapping.map(&listen).await);
+                supervisor.spawn("Port mapping renewal", port_mapping.renew_periodically());
+            }
+
+            let rebalancing = match config.rebalance {
+                Some(rebalance) => {
+                    let rebalancing = Rebalancing::new(
+                        rebalance.mode,
+                        rebalance.thresholds(),
+                        Duration::from_secs(rebalance.cooldown_hours * 60 * 60),
+                    )
+                    .context(Fatal)?;
+                    let rebalancer = rebalancer(rebalance.kraken)?;
+                    if rebalancer.is_none() && rebalancing.mode() != rebalance::Mode::DryRun {
+                        return Err(anyhow!(
+                            "Rebalancing in a mode other than dry_run requires a rebalancer, e.g. [rebalance.kraken]"
+                        )
+                        .context(Fatal));
+                    }
+                    tracing::info!(mode = ?rebalancing.mode(), min_xmr = %rebalance.min_xmr, "Rebalancing once the balances cross the thresholds");
+
+                    supervisor.spawn(
+                        "Rebalancing",
+                        rebalancing.clone().run(
+                            rebalancer,
+                            bitcoin_wallet.clone(),
+                            monero_wallet.clone(),
+                            kraken_rate.clone(),
+                        ),
+                    );
+                    Some(rebalancing)
+                }
+                None => None,
+            };
+
+            let (event_loop, swap_receiver) = maker_event_loop(
+                asb::DEFAULT_IDENTITY,
+                default_identity,
+                default_maker_key,
+                listen,
+                external_addresses,
+                controls.clone(),
+                kraken_rate.clone(),
+                policy,
+                notifier,
+                watchtower,
+                config.bitcoin.cpfp,
+                approvals.clone(),
                 env_config,
-                Arc::new(bitcoin_wallet),
-                Arc::new(monero_wallet),
+                rendezvous,
+                config.network.relays.clone(),
+                bitcoin_wallet,
+                monero_wallet,
+                db.clone(),
+            )?;
+
+            connection_stats.insert(
+                asb::DEFAULT_IDENTITY.to_owned(),
+                event_loop.connection_stats(),
+            );
+
+            if let Some(retention_days) = config.data.retention_days {
+                tokio::spawn(database::prune_periodically(db.clone(), retention_days));
+            }
+
+            let admin = Admin::new(
+                controls,
+                approvals,
                 db,
+                reloader,
+                log_level,
+                connection_stats,
+                sync_stats,
                 kraken_rate.clone(),
-                config.maker.min_buy_btc,
-                config.maker.max_buy_btc,
-            )
-            .unwrap();
-
+                rebalancing,
+            );
+            let admin_socket = admin::socket_path(&config.data.dir);
             tokio::spawn(async move {
-                while let Some(swap) = swap_receiver.recv().await {
-                    let rate = kraken_rate.clone();
-                    tokio::spawn(async move {
-                        let swap_id = swap.swap_id;
-                        match run(swap, rate).await {
-                            Ok(state) => {
-                                tracing::debug!(%swap_id, final_state=%state, "Swap completed")
-                            }
-                            Err(error) => {
-                                tracing::error!(%swap_id, "Swap failed: {:#}", error)
-                            }
-                        }
-                    });
+                if let Err(error) = admin::serve(&admin_socket, admin).await {
+                    tracing::warn!("Admin socket is not available: {:#}", error);
                 }
             });
 
-            event_loop.run().await;
+            supervisor.spawn(
+                "Swap executor",
+                run_swaps(swap_receiver, kraken_rate, alerter.clone(), load),
+            );
+            supervisor.spawn("Event loop", event_loop.run());
+
+            health.set_ready();
+            service::notify_ready();
+
+            if let Err(error) = supervisor.run().await {
+                let message = format!("ASB stopped: {:#}", error);
+                alerter.alert(Alert::new(Severity::Critical, message)).await;
+                otlp::shutdown();
+
+                return Err(error);
+            }
         }
         Command::History => {
+            let explorer = Explorer::new(env_config.bitcoin_network, env_config.monero_network)
+                .with_bitcoin(config.bitcoin.explorer_url.clone())
+                .with_monero(config.monero.explorer_url.clone());
+
             let mut table = Table::new();
 
-            table.set_header(vec!["SWAP ID", "STATE"]);
+            table.set_header(vec!["SWAP ID", "STATE", "BTC LOCK", "XMR LOCK"]);
 
             for (swap_id, state) in db.all().await? {
                 let state: AliceState = state.try_into()?;
-                table.add_row(vec![swap_id.to_string(), state.to_string()]);
+
+                // Finished swaps no longer hold the transactions in their state
+                let states = db
+                    .get_states(swap_id)
+                    .await?
+                    .into_iter()
+                    .map(TryInto::try_into)
+                    .collect::<Result<Vec<AliceState>, _>>()?;
+                let btc_lock = states
+                    .iter()
+                    .find_map(AliceState::tx_lock_id)
+                    .and_then(|txid| explorer.bitcoin_tx(txid))
+                    .map(|url| url.to_string())
+                    .unwrap_or_default();
+                let xmr_lock = states
+                    .iter()
+                    .find_map(AliceState::transfer_proof)
+                    .and_then(|proof| explorer.monero_tx(&proof.tx_hash()))
+                    .map(|url| url.to_string())
+                    .unwrap_or_default();
+
+                table.add_row(vec![
+                    swap_id.to_string(),
+                    state.to_string(),
+                    btc_lock,
+                    xmr_lock,
+                ]);
             }
 
             println!("{}", table);
@@ -217,6 +586,152 @@ async fn main() -> Result<()> {
             let config_json = serde_json::to_string_pretty(&config)?;
             println!("{}", config_json);
         }
+        Command::Admin { request } => {
+            let admin_socket = admin::socket_path(&config.data.dir);
+
+            match admin::send(&admin_socket, request).await? {
+                admin::Response::Swaps { swaps, next } => {
+                    let mut table = Table::new();
+
+                    table.set_header(vec![
+                        "SWAP ID",
+                        "IDENTITY",
+                        "PEER ID",
+                        "STARTED",
+                        "BTC",
+                        "PHASE",
+                        "WIND DOWN",
+                    ]);
+
+                    for swap in swaps {
+                        table.add_row(vec![
+                            swap.swap_id.to_string(),
+                            swap.identity.unwrap_or_default(),
+                            swap.peer_id.unwrap_or_else(|| "unknown".to_owned()),
+                            database::format_started_at(swap.started_at)?,
+                            swap.btc_amount
+                                .map(|amount| amount.to_string())
+                                .unwrap_or_default(),
+                            swap.state,
+                            swap.wind_down.unwrap_or_default(),
+                        ]);
+                    }
+
+                    println!("{}", table);
+                    if let Some(next) = next {
+                        println!("More swaps are listed with --after {}", next);
+                    }
+                }
+                admin::Response::Approvals { approvals } => {
+                    let mut table = Table::new();
+
+                    table.set_header(vec![
+                        "SWAP ID",
+                        "PEER ID",
+                        "BTC LOCKED",
+                        "XMR TO LOCK",
+                        "XMR LOCK FEE",
+                        "BTC REDEEM FEE",
+                    ]);
+
+                    for approval in approvals {
+                        table.add_row(vec![
+                            approval.swap_id.to_string(),
+                            approval.peer_id.unwrap_or_else(|| "unknown".to_owned()),
+                            approval.btc.to_string(),
+                            approval.xmr.to_string(),
+                            approval.xmr_lock_fee.to_string(),
+                            approval.btc_redeem_fee.to_string(),
+                        ]);
+                    }
+
+                    println!("{}", table);
+                }
+                admin::Response::PriceFeeds { feeds, rate } => {
+                    let mut table = Table::new();
+
+                    table.set_header(vec![
+                        "URL",
+                        "ASK",
+                        "AGE",
+                        "PRICE AGE",
+                        "DEVIATION",
+                        "PROBLEM",
+                    ]);
+
+                    for feed in feeds {
+                        table.add_row(vec![
+                            feed.url.to_string(),
+                            feed.ask.map(|ask| ask.to_string()).unwrap_or_default(),
+                            feed.age_secs
+                                .map(|age| format!("{}s", age))
+                                .unwrap_or_default(),
+                            feed.price_age_secs
+                                .map(|age| format!("{}s", age))
+                                .unwrap_or_default(),
+                            feed.deviation
+                                .map(|deviation| deviation.to_string())
+                                .unwrap_or_default(),
+                            feed.problem.unwrap_or_default(),
+                        ]);
+                    }
+
+                    println!("{}", table);
+
+                    match rate.map(|rate| rate.ask()) {
+                        Some(Ok(ask)) => println!("Asking price: {}", ask),
+                        Some(Err(e)) => println!("Asking price: {:#}", e),
+                        None => println!("Asking price: none, the feeds do not agree"),
+                    }
+                }
+                response => println!("{}", serde_json::to_string_pretty(&response)?),
+            }
+        }
+        Command::PruneDatabase { retention_days } => {
+            let retention_days = retention_days
+                .or(config.data.retention_days)
+                .unwrap_or(database::DEFAULT_RETENTION_DAYS);
+
+            let pruned = db.prune(database::retention_cutoff(retention_days)).await?;
+
+            tracing::info!(%pruned, %retention_days, "Pruned states of finished swaps");
+        }
+        Command::CompactDatabase => {
+            db.compact().await?;
+
+            tracing::info!("Compacted database");
+        }
+        Command::Version => unreachable!("printed before the config is read"),
+        Command::Init { .. } => unreachable!("initialized before the seed is read"),
+        Command::ReportQuotes => {
+            let mut by_identity = BTreeMap::<String, Vec<QuoteRecord>>::new();
+            for quote in db.get_quotes().await? {
+                by_identity
+                    .entry(quote.identity.clone())
+                    .or_default()
+                    .push(quote);
+            }
+
+            let mut table = Table::new();
+
+            table.set_header(vec!["IDENTITY", "OUTCOME", "QUOTES", "SHARE"]);
+
+            for (identity, quotes) in by_identity {
+                let stats = QuoteStats::new(&quotes);
+
+                for (outcome, count) in &stats.outcomes {
+                    let share = Decimal::from(*count) / Decimal::from(stats.total);
+                    table.add_row(vec![
+                        identity.clone(),
+                        outcome.to_string(),
+                        count.to_string(),
+                        format!("{:.1}%", share * Decimal::ONE_HUNDRED),
+                    ]);
+                }
+            }
+
+            println!("{}", table);
+        }
         Command::WithdrawBtc { amount, address } => {
             let bitcoin_wallet = init_bitcoin_wallet(&config, &seed, env_config).await?;
 
@@ -281,6 +796,24 @@ async fn main() -> Result<()> {
 
             tracing::info!("Swap safely aborted");
         }
+        Command::Presigned {
+            swap_id,
+            publish: None,
+        } => {
+            let transactions = presigned(swap_id, db).await?;
+
+            println!("{}", serde_json::to_string_pretty(&transactions)?);
+        }
+        Command::Presigned {
+            swap_id,
+            publish: Some(kind),
+        } => {
+            let bitcoin_wallet = init_bitcoin_wallet(&config, &seed, env_config).await?;
+
+            let txid = publish_presigned(swap_id, kind, Arc::new(bitcoin_wallet), db).await?;
+
+            tracing::info!(%kind, "Presigned transaction successfully published with id {}", txid);
+        }
         Command::Redeem {
             swap_id,
             do_not_await_finality,
@@ -307,22 +840,184 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
+/// Sets up the seed and the wallets of a new ASB and checks that the backends
+/// are reachable. Running it again continues where a failed run stopped.
+///
+/// The mnemonics are only printed when the seed or the Monero wallet is
+/// created and never logged, so they do not end up in log files.
+async fn init(config: &Config, env_config: swap::env::Config, restore: bool) -> Result<()> {
+    let data_dir = &config.data.dir;
+    let seed_file = DataDir::new(data_dir).seed();
+
+    let seed = if seed_file.exists() {
+        if restore {
+            bail!(
+                "Seed file {} already exists, not restoring over it",
+                seed_file.display()
+            );
+        }
+        println!("Using the existing seed in {}", seed_file.display());
+
+        Seed::from_file_or_generate(data_dir)?
+    } else if restore {
+        let words = Password::with_theme(&ColorfulTheme::default())
+            .with_prompt("Enter the mnemonic of the seed to restore")
+            .interact()?;
+        let seed = Seed::from_mnemonic(&words)?;
+        seed.write_new(data_dir)?;
+        println!("Restored the seed to {}", seed_file.display());
+
+        seed
+    } else {
+        let seed = Seed::random()?;
+        seed.write_new(data_dir)?;
+
+        println!(
+            "Created the seed in {}. Write down its mnemonic, it restores the Bitcoin wallet and the peer id and is not shown again:",
+            seed_file.display()
+        );
+        print_mnemonic(&seed.to_mnemonic().to_string());
+
+        seed
+    };
+
+    let identity = peer_identity::load(data_dir, &seed)?;
+    println!("Peer id: {}", PeerId::from(identity.public()));
+    println!("Maker id: {}", maker_key::load(data_dir, &seed)?.id());
+
+    let bitcoin_wallet = init_bitcoin_wallet(config, &seed, env_config).await?;
+    println!(
+        "Connected to Electrum, deposit Bitcoin at {}",
+        bitcoin_wallet.new_address().await?
+    );
+
+    let monero_wallet = match monero::Wallet::create(
+        config.monero.wallet_rpc_url.clone(),
+        DEFAULT_WALLET_NAME.to_string(),
+        env_config,
+    )
+    .await
+    {
+        Ok(monero_wallet) => {
+            println!(
+                "Created the Monero wallet {}. Write down its mnemonic, it is not derived from the seed and is not shown again:",
+                DEFAULT_WALLET_NAME
+            );
+            print_mnemonic(&monero_wallet.mnemonic().await?);
+
+            monero_wallet
+        }
+        Err(error) if error.is::<monero::WalletExists>() => {
+            init_monero_wallet(config, env_config).await?
+        }
+        Err(error) => {
+            return Err(error).with_context(|| {
+                format!(
+                    "Failed to create the Monero wallet {} through {}",
+                    DEFAULT_WALLET_NAME, config.monero.wallet_rpc_url
+                )
+            })
+        }
+    };
+    println!(
+        "Connected to monero-wallet-rpc, deposit Monero at {}",
+        monero_wallet.get_main_address()
+    );
+
+    let out_of_sync = sync_check::check(
+        &bitcoin_wallet,
+        &monero_wallet,
+        env_config,
+        Default::default(),
+    )
+    .await;
+    for problem in &out_of_sync {
+        println!("Warning: {}", problem);
+    }
+
+    for price_ticker_ws_url in config.maker.price_ticker_ws_urls() {
+        let mut price_updates = kraken::connect(price_ticker_ws_url.clone())?;
+        let update =
+            tokio::time::timeout(PRICE_TICKER_TIMEOUT, price_updates.wait_for_next_update()).await;
+        match update {
+            Ok(Ok(Ok(_))) => println!("Connected to the price ticker at {}", price_ticker_ws_url),
+            _ => bail!(
+                "No price from the price ticker at {} within {} seconds",
+                price_ticker_ws_url,
+                PRICE_TICKER_TIMEOUT.as_secs()
+            ),
+        }
+    }
+
+    if out_of_sync.is_empty() {
+        println!("The ASB is ready to be started with `asb start`");
+    }
+
+    Ok(())
+}
+
+fn print_mnemonic(words: &str) {
+    let words = words.split_whitespace().collect::<Vec<_>>();
+
+    println!();
+    for line in words.chunks(6) {
+        println!("    {}", line.join(" "));
+    }
+    println!();
+}
+
+/// The rebalancer configured in the `[rebalance]` section, `None` if there is
+/// none.
+fn rebalancer(kraken: Option<KrakenRebalancerConf>) -> Result<Option<Arc<dyn Rebalancer>>> {
+    match kraken {
+        #[cfg(feature = "kraken-rebalancer")]
+        Some(kraken) => Ok(Some(Arc::new(rebalance::kraken::Kraken::new(
+            kraken.api_key,
+            &kraken.api_secret,
+            kraken.xmr_withdrawal_key,
+        )?))),
+        #[cfg(not(feature = "kraken-rebalancer"))]
+        Some(_) => Err(anyhow!(
+            "The Kraken rebalancer requires an ASB built with the kraken-rebalancer feature"
+        )
+        .context(Fatal)),
+        None => Ok(None),
+    }
+}
+
 async fn init_bitcoin_wallet(
     config: &Config,
     seed: &Seed,
     env_config: swap::env::Config,
 ) -> Result<bitcoin::Wallet> {
     tracing::debug!("Opening Bitcoin wallet");
-    let wallet_dir = config.data.dir.join("wallet");
+    let wallet_dir = DataDir::new(&config.data.dir).bitcoin_wallet();
 
-    let wallet = bitcoin::Wallet::new(
-        config.bitcoin.electrum_rpc_url.clone(),
-        &wallet_dir,
-        seed.derive_extended_private_key(env_config.bitcoin_network)?,
-        env_config,
-        config.bitcoin.target_block,
-    )
-    .await
+    let key = seed.derive_extended_private_key(env_config.bitcoin_network)?;
+    let wallet = match &config.bitcoin.bitcoind {
+        Some(bitcoind) => {
+            bitcoin::Wallet::with_bitcoind(
+                bitcoind.config()?,
+                &wallet_dir,
+                key,
+                env_config,
+                config.bitcoin.target_block,
+            )
+            .await
+        }
+        None => {
+            bitcoin::Wallet::new(
+                iter::once(config.bitcoin.electrum_rpc_url.clone())
+                    .chain(config.bitcoin.electrum_rpc_fallback_urls.clone())
+                    .collect(),
+                &wallet_dir,
+                key,
+                env_config,
+                config.bitcoin.target_block,
+            )
+            .await
+        }
+    }
     .context("Failed to initialize Bitcoin wallet")?;
 
     wallet.sync().await?;
@@ -347,6 +1042,165 @@ async fn init_monero_wallet(
 
 /// Registers a hidden service for each network.
 /// Note: Once ac goes out of scope, the services will be de-registered.
+/// Sets up the network layer of a maker identity and returns the event loop
+/// that sets up and resumes its swaps.
+#[allow(clippy::too_many_arguments)]
+fn maker_event_loop(
+    name: &str,
+    identity: identity::Keypair,
+    maker_key: MakerKey,
+    listen: Vec<Multiaddr>,
+    external_addresses: Vec<Multiaddr>,
+    controls: TradingControls,
+    rate: KrakenRate,
+    policy: Arc<dyn Policy>,
+    notifier: Option<Notifier>,
+    watchtower: Option<Multiaddr>,
+    cpfp: bool,
+    approvals: Approvals,
+    env_config: swap::env::Config,
+    rendezvous: Option<(Multiaddr, XmrBtcNamespace)>,
+    relays: Vec<Multiaddr>,
+    bitcoin_wallet: Arc<bitcoin::Wallet>,
+    monero_wallet: Arc<monero::Wallet>,
+    db: Arc<dyn Database + Send + Sync>,
+) -> Result<(EventLoop<KrakenRate>, mpsc::Receiver<alice::Swap>)> {
+    let mut swarm = swarm::asb(
+        identity.clone(),
+        controls.clone(),
+        rate.clone(),
+        env_config,
+        rendezvous,
+        &relays,
+    )?;
+
+    for listen in listen {
+        Swarm::listen_on(&mut swarm, listen.clone()).with_context(|| {
+            let hint = match AddressFamily::of(&listen) {
+                Some(AddressFamily::Ipv6) => ", set `address_family = \"ipv4\"` in the [network] section if the host has no IPv6",
+                _ => "",
+            };
+
+            format!("Failed to listen on network interface {}{}", listen, hint)
+        })?;
+    }
+
+    tracing::info!(identity = %name, peer_id = %swarm.local_peer_id(), maker_id = %maker_key.id(), "Network layer initialized");
+
+    for external_address in external_addresses {
+        let _ = Swarm::add_external_address(&mut swarm, external_address, AddressScore::Infinite);
+    }
+    // Advertised even while the connection to the relay is down, like the
+    // configured external addresses
+    for relay in &relays {
+        let relayed_address = relay.clone().with(Protocol::P2pCircuit);
+        let _ = Swarm::add_external_address(&mut swarm, relayed_address, AddressScore::Infinite);
+    }
+
+    let watchtower = watchtower
+        .map(|address| watchtower::Client::new(address, identity.clone()))
+        .transpose()?;
+
+    let (mut event_loop, swap_receiver) = EventLoop::new(
+        swarm,
+        identity,
+        maker_key,
+        env_config,
+        bitcoin_wallet,
+        monero_wallet,
+        db,
+        rate,
+        controls,
+        policy,
+        notifier,
+        watchtower,
+        cpfp,
+        approvals,
+        name.to_owned(),
+    )?;
+
+    for relay in relays {
+        event_loop.listen_via_relay(relay)?;
+    }
+
+    Ok((event_loop, swap_receiver))
+}
+
+/// Runs every swap handed out by an event loop until it completes.
+///
+/// Failed swaps are reported to the alerter, as critical if the Monero of the
+/// swap is locked. Swaps count towards the `load` while they run.
+async fn run_swaps(
+    mut swap_receiver: mpsc::Receiver<alice::Swap>,
+    rate: KrakenRate,
+    alerter: Alerter,
+    load: Load,
+) {
+    while let Some(swap) = swap_receiver.recv().await {
+        let rate = rate.clone();
+        let alerter = alerter.clone();
+        let active_swap = load.swap_started();
+        tokio::spawn(
+            async move {
+                let _active_swap = active_swap;
+                let swap_id = swap.swap_id;
+                let db = swap.db.clone();
+                match catch_panic(run(swap, rate).in_current_span()).await {
+                    Ok(state) => {
+                        tracing::debug!(%swap_id, final_state=%state, "Swap completed")
+                    }
+                    Err(error) => {
+                        let state: Option<AliceState> = db
+                            .get_state(swap_id)
+                            .await
+                            .ok()
+                            .and_then(|state| state.try_into().ok());
+                        // A swap whose state is unknown has to be looked at
+                        let severity = state
+                            .as_ref()
+                            .map_or(Severity::Critical, Severity::of_swap_failure);
+                        let message = match state {
+                            Some(state) => format!("Swap failed in state {}: {:#}", state, error),
+                            None => format!("Swap failed: {:#}", error),
+                        };
+
+                        alerter.alert(Alert::swap(severity, swap_id, message)).await;
+                    }
+                }
+            }
+            .in_current_span(),
+        );
+    }
+}
+
+/// Sets up the configured sinks, without any alerts are only logged.
+fn setup_alerter(alerts: Option<Alerts>) -> Result<Alerter> {
+    let alerts = match alerts {
+        Some(alerts) => alerts,
+        None => return Ok(Alerter::default()),
+    };
+
+    let mut sinks: Vec<Arc<dyn Sink>> = Vec::new();
+    if let Some(telegram) = alerts.telegram {
+        sinks.push(Arc::new(Telegram::new(
+            &telegram.bot_token,
+            telegram.chat_id,
+        )?));
+    }
+    if let Some(smtp) = alerts.smtp {
+        sinks.push(Arc::new(Smtp {
+            host: smtp.host,
+            port: smtp.port,
+            username: smtp.username,
+            password: smtp.password,
+            from: smtp.from,
+            to: smtp.to,
+        }));
+    }
+
+    Ok(Alerter::new(alerts.min_severity, sinks))
+}
+
 async fn register_tor_services(
     networks: Vec<Multiaddr>,
     tor_client: tor::Client,
@@ -0,0 +1,38 @@
+//! Runs a complete swap on regtest to verify a development environment.
+//!
+//! Starts bitcoind, electrs, monerod and the Monero wallet RPCs in Docker,
+//! funds a fresh Alice and Bob, swaps between them and removes the containers
+//! again. This is the setup of the end-to-end tests, which is why the harness
+//! is shared with them instead of being part of the library.
+
+#![warn(unused_extern_crates, rust_2018_idioms, clippy::dbg_macro)]
+#![forbid(unsafe_code)]
+
+#[path = "../../tests/harness/mod.rs"]
+pub mod harness;
+
+use harness::SlowCancelConfig;
+use swap::asb::FixedRate;
+use swap::protocol::{alice, bob};
+use tokio::join;
+
+#[tokio::main]
+async fn main() {
+    harness::setup_test(SlowCancelConfig, |mut ctx| async move {
+        let (bob_swap, _) = ctx.bob_swap().await;
+        let bob_swap = tokio::spawn(bob::run(bob_swap));
+
+        let alice_swap = ctx.alice_next_swap().await;
+        let alice_swap = tokio::spawn(alice::run(alice_swap, FixedRate::default()));
+
+        let (bob_state, alice_state) = join!(bob_swap, alice_swap);
+
+        ctx.assert_alice_redeemed(alice_state??).await;
+        ctx.assert_bob_redeemed(bob_state??).await;
+
+        println!("Swap completed, the development environment works");
+
+        Ok(())
+    })
+    .await;
+}
@@ -14,118 +14,254 @@
 
 use anyhow::{bail, Context, Result};
 use comfy_table::Table;
+use dialoguer::Confirm;
+use libp2p::Multiaddr;
+use monero_rpc::monerod;
 use qrcode::render::unicode;
 use qrcode::QrCode;
 use std::cmp::min;
 use std::convert::TryInto;
 use std::env;
 use std::future::Future;
-use std::path::PathBuf;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
+use swap::amounts::{Price, Quote};
 use swap::bitcoin::TxLock;
 use swap::cli::command::{parse_args_and_apply_defaults, Arguments, Command, ParseResult};
-use swap::cli::{list_sellers, EventLoop, SellerStatus};
-use swap::database::open_db;
+use swap::cli::{list_sellers, ConfirmLock, EventLoop, PriceLimit, SellerStatus, SwapFactory};
+use swap::data::DataDir;
+use swap::database::{self, open_db};
 use swap::env::Config;
+use swap::fs::lock_data_dir;
 use swap::libp2p_ext::MultiAddrExt;
-use swap::network::quote::BidQuote;
-use swap::network::swarm;
+use swap::network::{swarm, watchtower};
 use swap::protocol::bob;
-use swap::protocol::bob::{BobState, Swap};
+use swap::protocol::bob::BobState;
+use swap::protocol::receipt::Receipt;
+use swap::protocol::Database;
 use swap::seed::Seed;
-use swap::{bitcoin, cli, monero};
+use swap::sync_check;
+use swap::version::agent_version;
+use swap::{bitcoin, cli, fiat, monero, otlp};
 use url::Url;
 use uuid::Uuid;
 
+/// How long `buy-xmr` waits for the deposit to arrive and to be confirmed.
+const MAX_DEPOSIT_WAIT: Duration = Duration::from_secs(2 * 60 * 60);
+
 #[tokio::main]
 async fn main() -> Result<()> {
+    let arguments = match parse_args_and_apply_defaults(env::args_os())? {
+        ParseResult::Arguments(args) => args,
+        ParseResult::PrintAndExitZero { message } => {
+            println!("{}", message);
+            std::process::exit(0);
+        }
+    };
+    let json = arguments.json;
+
+    let result = run(arguments).await;
+    otlp::shutdown();
+
+    match result {
+        // Scripts only have to parse JSON lines, including the reason of a failure
+        Err(error) if json && tracing::dispatcher::has_been_set() => {
+            tracing::error!("{:#}", error);
+            std::process::exit(1);
+        }
+        result => result,
+    }
+}
+
+async fn run(arguments: Arguments) -> Result<()> {
     let Arguments {
         env_config,
         data_dir,
         debug,
         json,
+        otlp_endpoint,
         cmd,
-    } = match parse_args_and_apply_defaults(env::args_os())? {
-        ParseResult::Arguments(args) => args,
-        ParseResult::PrintAndExitZero { message } => {
-            println!("{}", message);
-            std::process::exit(0);
-        }
+    } = arguments;
+    let data_dir = DataDir::new(data_dir);
+
+    // Only commands that do not act on swaps or the wallets may run next to
+    // another swap process
+    let _data_dir_lock = match cmd {
+        Command::History { .. }
+        | Command::Config
+        | Command::ListSellers { .. }
+        | Command::MoneroRecovery { .. }
+        | Command::Presigned { .. }
+        | Command::Receipt { .. }
+        | Command::VerifyReceipt { .. } => None,
+        _ => Some(lock_data_dir(data_dir.root())?),
     };
 
     match cmd {
         Command::BuyXmr {
             seller,
-            bitcoin_electrum_rpc_url,
+            bitcoin_electrum_rpc_urls,
             bitcoin_target_block,
             bitcoin_change_address,
             monero_receive_address,
             monero_daemon_address,
+            monero_light_wallet_server,
             tor_socks5_port,
+            fiat_currency,
+            receive_xmr,
+            max_price,
+            max_price_deviation,
+            confirm_lock,
+            watchtower,
         } => {
             let swap_id = Uuid::new_v4();
 
-            cli::tracing::init(debug, json, data_dir.join("logs"), Some(swap_id))?;
-            let db = open_db(data_dir.join("sqlite")).await?;
-            let seed = Seed::from_file_or_generate(data_dir.as_path())
+            cli::tracing::init(
+                debug,
+                json,
+                data_dir.logs(),
+                Some(swap_id),
+                otlp_endpoint.as_ref(),
+            )?;
+            tracing::debug!(agent = %agent_version("swap"), "Starting swap");
+
+            let db = open_db(data_dir.database()).await?;
+            let seed = Seed::from_file_or_generate(data_dir.root())
                 .context("Failed to read in seed file")?;
 
             let bitcoin_wallet = init_bitcoin_wallet(
-                bitcoin_electrum_rpc_url,
+                bitcoin_electrum_rpc_urls,
                 &seed,
-                data_dir.clone(),
+                &data_dir,
                 env_config,
                 bitcoin_target_block,
             )
             .await?;
-            let (monero_wallet, _process) =
-                init_monero_wallet(data_dir, monero_daemon_address, env_config).await?;
-            let bitcoin_wallet = Arc::new(bitcoin_wallet);
+            let (monero_wallet, _process) = init_swap_monero_wallet(
+                &data_dir,
+                monero_daemon_address,
+                monero_light_wallet_server,
+                env_config,
+            )
+            .await?;
+
+            let out_of_sync = sync_check::check(
+                &bitcoin_wallet,
+                monero_wallet.as_ref(),
+                env_config,
+                Default::default(),
+            )
+            .await;
+            for problem in &out_of_sync {
+                tracing::error!("{}", problem);
+            }
+            if !out_of_sync.is_empty() {
+                bail!("Refusing to start a swap because the local clock or the blockchain backends are out of sync")
+            }
+
+            if !json {
+                tokio::spawn(cli::progress::show(
+                    bitcoin_wallet.progress(),
+                    monero_wallet.progress(),
+                    env_config,
+                ));
+            }
+
+            let factory = SwapFactory::new(
+                seed.derive_libp2p_identity(),
+                db,
+                Arc::new(bitcoin_wallet),
+                monero_wallet,
+                env_config,
+                tor_socks5_port,
+            );
+            let bitcoin_wallet = factory.bitcoin_wallet();
+            let db = factory.db();
+
             let seller_peer_id = seller
                 .extract_peer_id()
                 .context("Seller address must contain peer ID")?;
-            db.insert_address(seller_peer_id, seller.clone()).await?;
+            database::prune_dead_addresses(db.as_ref()).await;
+            db.insert_address(seller_peer_id, seller).await?;
 
-            let behaviour = cli::Behaviour::new(seller_peer_id, env_config, bitcoin_wallet.clone());
-            let mut swarm =
-                swarm::cli(seed.derive_libp2p_identity(), tor_socks5_port, behaviour).await?;
-            swarm.behaviour_mut().add_address(seller_peer_id, seller);
-
-            tracing::debug!(peer_id = %swarm.local_peer_id(), "Network layer initialized");
+            let seller_addresses = db.get_addresses(seller_peer_id).await?;
+            if seller_addresses.is_empty() {
+                bail!(
+                    "No address of seller {} is known, pass one to `--seller`",
+                    seller_peer_id
+                )
+            }
 
-            let (event_loop, mut event_loop_handle) =
-                EventLoop::new(swap_id, swarm, seller_peer_id, env_config)?;
+            let (event_loop, mut event_loop_handle) = factory
+                .event_loop(swap_id, seller_peer_id, seller_addresses)
+                .await?;
             let event_loop = tokio::spawn(event_loop.run());
 
-            let max_givable = || bitcoin_wallet.max_giveable(TxLock::script_size());
+            let price_url = Url::parse(fiat::DEFAULT_PRICE_URL)?;
+            let price_limit =
+                PriceLimit::new(max_price.map(Price::new), max_price_deviation, &price_url).await?;
+            let bid_quote = async {
+                let bid_quote = event_loop_handle.request_quote().await?;
+                price_limit
+                    .check(bid_quote.price)
+                    .context("Refusing the seller's quote")?;
+
+                Ok::<_, anyhow::Error>(bid_quote)
+            };
+
+            let max_givable = || factory.max_giveable();
             let (amount, fees) = determine_btc_to_swap(
                 json,
-                event_loop_handle.request_quote(),
+                bid_quote,
+                receive_xmr,
                 bitcoin_wallet.new_address(),
                 || bitcoin_wallet.balance(),
+                || bitcoin_wallet.unconfirmed_balance(),
                 max_givable,
                 || bitcoin_wallet.sync(),
+                MAX_DEPOSIT_WAIT,
             )
             .await?;
 
             tracing::info!(%amount, %fees,  "Determined swap amount");
 
-            db.insert_peer_id(swap_id, seller_peer_id).await?;
-            db.insert_monero_address(swap_id, monero_receive_address)
-                .await?;
+            if let Some(currency) = fiat_currency {
+                match fiat::fetch_rate(&price_url, currency).await {
+                    Ok(rate) => {
+                        let fiat_value = rate.value_of(amount);
+                        tracing::info!(%amount, %fiat_value, btc_price = %rate.btc_price, "Estimated fiat value of swap");
+                        db.insert_fiat_value(swap_id, fiat_value).await?;
+                    }
+                    Err(error) => {
+                        tracing::warn!("Failed to estimate fiat value of swap: {:#}", error)
+                    }
+                }
+            }
 
-            let swap = Swap::new(
-                db,
-                swap_id,
-                bitcoin_wallet,
-                Arc::new(monero_wallet),
-                env_config,
-                event_loop_handle,
-                monero_receive_address,
-                bitcoin_change_address,
-                amount,
-            );
+            let mut swap = factory
+                .new_swap(
+                    swap_id,
+                    seller_peer_id,
+                    event_loop_handle,
+                    monero_receive_address,
+                    bitcoin_change_address.clone(),
+                    amount,
+                    receive_xmr,
+                )
+                .await?;
+            if price_limit.is_limited() {
+                swap = swap.with_lock_approval(Arc::new(price_limit));
+            }
+            if confirm_lock {
+                swap = swap.with_lock_approval(Arc::new(ConfirmLock {
+                    seller: seller_peer_id,
+                }));
+            }
+            if let Some(address) = watchtower {
+                let watchtower = watchtower::Client::new(address, seed.derive_libp2p_identity())?
+                    .with_tor(tor_socks5_port);
+                swap = swap.with_watchtower(watchtower);
+            }
 
             tokio::select! {
                 result = event_loop => {
@@ -133,57 +269,88 @@ async fn main() -> Result<()> {
                         .context("EventLoop panicked")?;
                 },
                 result = bob::run(swap) => {
-                    result.context("Failed to complete swap")?;
+                    let state = result.context("Failed to complete swap")?;
+                    tracing::info!(%swap_id, %state, "Swap finished");
+
+                    // Only a rejection by the seller, declining the lock or the maximum swap
+                    // duration aborts a new swap
+                    if let BobState::SafelyAborted = state {
+                        offer_deposit_refund(
+                            json,
+                            bitcoin_change_address,
+                            &bitcoin_wallet,
+                            db.as_ref(),
+                        )
+                        .await?;
+                    }
                 }
             }
         }
-        Command::History => {
-            cli::tracing::init(debug, json, data_dir.join("logs"), None)?;
-
-            let db = open_db(data_dir.join("sqlite")).await?;
-            let swaps = db.all().await?;
-
-            if json {
-                for (swap_id, state) in swaps {
-                    let state: BobState = state.try_into()?;
-                    tracing::info!(swap_id=%swap_id.to_string(), state=%state.to_string(), "Read swap state from database");
-                }
-            } else {
-                let mut table = Table::new();
-
-                table.set_header(vec!["SWAP ID", "STATE"]);
-
-                for (swap_id, state) in swaps {
-                    let state: BobState = state.try_into()?;
-                    table.add_row(vec![swap_id.to_string(), state.to_string()]);
+        Command::History { explorer, query } => {
+            cli::tracing::init(debug, json, data_dir.logs(), None, otlp_endpoint.as_ref())?;
+
+            let db = open_db(data_dir.database()).await?;
+            let (filter, page) = query.into_filter_and_page();
+            let swaps = db.list_swaps(&filter, &page).await?;
+
+            let mut table = Table::new();
+            table.set_header(vec![
+                "SWAP ID", "STARTED", "STATE", "VALUE", "BTC LOCK", "XMR LOCK",
+            ]);
+
+            for summary in &swaps {
+                let swap = database::SwapInfo::load(db.as_ref(), summary, None).await?;
+
+                if json {
+                    println!("{}", serde_json::to_string(&swap)?);
+                } else {
+                    table.add_row(vec![
+                        swap.swap_id.to_string(),
+                        database::format_started_at(swap.started_at)?,
+                        swap.state,
+                        swap.fiat_value.unwrap_or_default(),
+                        swap.btc_lock_txid
+                            .and_then(|txid| explorer.bitcoin_tx(txid))
+                            .map(|url| url.to_string())
+                            .unwrap_or_default(),
+                        swap.xmr_lock_tx_hash
+                            .and_then(|tx_hash| explorer.monero_tx(&tx_hash))
+                            .map(|url| url.to_string())
+                            .unwrap_or_default(),
+                    ]);
                 }
+            }
 
+            if !json {
                 println!("{}", table);
+                if let Some(next) = page.next(&swaps) {
+                    println!("More swaps are listed with --after {}", next);
+                }
             }
         }
         Command::Config => {
-            cli::tracing::init(debug, json, data_dir.join("logs"), None)?;
-
-            tracing::info!(path=%data_dir.display(), "Data directory");
-            tracing::info!(path=%format!("{}/logs", data_dir.display()), "Log files directory");
-            tracing::info!(path=%format!("{}/sqlite", data_dir.display()), "Sqlite file location");
-            tracing::info!(path=%format!("{}/seed.pem", data_dir.display()), "Seed file location");
-            tracing::info!(path=%format!("{}/monero", data_dir.display()), "Monero-wallet-rpc directory");
-            tracing::info!(path=%format!("{}/wallet", data_dir.display()), "Internal bitcoin wallet directory");
+            cli::tracing::init(debug, json, data_dir.logs(), None, otlp_endpoint.as_ref())?;
+
+            tracing::info!(path=%data_dir.root().display(), "Data directory");
+            tracing::info!(path=%data_dir.logs().display(), "Log files directory");
+            tracing::info!(path=%data_dir.database().display(), "Sqlite file location");
+            tracing::info!(path=%data_dir.seed().display(), "Seed file location");
+            tracing::info!(path=%data_dir.monero_wallets().display(), "Monero-wallet-rpc directory");
+            tracing::info!(path=%data_dir.bitcoin_wallet().display(), "Internal bitcoin wallet directory");
         }
         Command::WithdrawBtc {
-            bitcoin_electrum_rpc_url,
+            bitcoin_electrum_rpc_urls,
             bitcoin_target_block,
             amount,
             address,
         } => {
-            cli::tracing::init(debug, json, data_dir.join("logs"), None)?;
-            let seed = Seed::from_file_or_generate(data_dir.as_path())
+            cli::tracing::init(debug, json, data_dir.logs(), None, otlp_endpoint.as_ref())?;
+            let seed = Seed::from_file_or_generate(data_dir.root())
                 .context("Failed to read in seed file")?;
             let bitcoin_wallet = init_bitcoin_wallet(
-                bitcoin_electrum_rpc_url,
+                bitcoin_electrum_rpc_urls,
                 &seed,
-                data_dir.clone(),
+                &data_dir,
                 env_config,
                 bitcoin_target_block,
             )
@@ -205,18 +372,40 @@ async fn main() -> Result<()> {
 
             bitcoin_wallet.broadcast(signed_tx, "withdraw").await?;
         }
+        Command::RefundDeposit {
+            bitcoin_electrum_rpc_urls,
+            bitcoin_target_block,
+            address,
+        } => {
+            cli::tracing::init(debug, json, data_dir.logs(), None, otlp_endpoint.as_ref())?;
+            let db = open_db(data_dir.database()).await?;
+            let seed = Seed::from_file_or_generate(data_dir.root())
+                .context("Failed to read in seed file")?;
+            let bitcoin_wallet = init_bitcoin_wallet(
+                bitcoin_electrum_rpc_urls,
+                &seed,
+                &data_dir,
+                env_config,
+                bitcoin_target_block,
+            )
+            .await?;
+
+            let (txid, amount) = cli::refund_deposit(address, &bitcoin_wallet, db.as_ref()).await?;
+
+            tracing::info!(%txid, %amount, "Returned the deposit");
+        }
 
         Command::Balance {
-            bitcoin_electrum_rpc_url,
+            bitcoin_electrum_rpc_urls,
             bitcoin_target_block,
         } => {
-            cli::tracing::init(debug, json, data_dir.join("logs"), None)?;
-            let seed = Seed::from_file_or_generate(data_dir.as_path())
+            cli::tracing::init(debug, json, data_dir.logs(), None, otlp_endpoint.as_ref())?;
+            let seed = Seed::from_file_or_generate(data_dir.root())
                 .context("Failed to read in seed file")?;
             let bitcoin_wallet = init_bitcoin_wallet(
-                bitcoin_electrum_rpc_url,
+                bitcoin_electrum_rpc_urls,
                 &seed,
-                data_dir.clone(),
+                &data_dir,
                 env_config,
                 bitcoin_target_block,
             )
@@ -230,122 +419,319 @@ async fn main() -> Result<()> {
         }
         Command::Resume {
             swap_id,
-            bitcoin_electrum_rpc_url,
+            bitcoin_electrum_rpc_urls,
             bitcoin_target_block,
             monero_daemon_address,
+            monero_light_wallet_server,
             tor_socks5_port,
+            confirm_lock,
+            watchtower,
         } => {
-            cli::tracing::init(debug, json, data_dir.join("logs"), Some(swap_id))?;
-            let db = open_db(data_dir.join("sqlite")).await?;
-            let seed = Seed::from_file_or_generate(data_dir.as_path())
+            cli::tracing::init(
+                debug,
+                json,
+                data_dir.logs(),
+                Some(swap_id),
+                otlp_endpoint.as_ref(),
+            )?;
+            tracing::debug!(agent = %agent_version("swap"), "Resuming swap");
+
+            let db = open_db(data_dir.database()).await?;
+            cli::export::ensure_not_exported(swap_id, db.as_ref()).await?;
+            let seed = Seed::from_file_or_generate(data_dir.root())
                 .context("Failed to read in seed file")?;
 
             let bitcoin_wallet = init_bitcoin_wallet(
-                bitcoin_electrum_rpc_url,
+                bitcoin_electrum_rpc_urls,
                 &seed,
-                data_dir.clone(),
+                &data_dir,
                 env_config,
                 bitcoin_target_block,
             )
             .await?;
-            let (monero_wallet, _process) =
-                init_monero_wallet(data_dir, monero_daemon_address, env_config).await?;
-            let bitcoin_wallet = Arc::new(bitcoin_wallet);
-
-            let seller_peer_id = db.get_peer_id(swap_id).await?;
-            let seller_addresses = db.get_addresses(seller_peer_id).await?;
-
-            let behaviour = cli::Behaviour::new(seller_peer_id, env_config, bitcoin_wallet.clone());
-            let mut swarm =
-                swarm::cli(seed.derive_libp2p_identity(), tor_socks5_port, behaviour).await?;
-            let our_peer_id = swarm.local_peer_id();
-            tracing::debug!(peer_id = %our_peer_id, "Network layer initialized");
+            let (monero_wallet, _process) = init_swap_monero_wallet(
+                &data_dir,
+                monero_daemon_address,
+                monero_light_wallet_server,
+                env_config,
+            )
+            .await?;
 
-            for seller_address in seller_addresses {
-                swarm
-                    .behaviour_mut()
-                    .add_address(seller_peer_id, seller_address);
+            // A swap that is already running has to be resumed to be able to refund
+            let out_of_sync = sync_check::check(
+                &bitcoin_wallet,
+                monero_wallet.as_ref(),
+                env_config,
+                Default::default(),
+            )
+            .await;
+            for problem in out_of_sync {
+                tracing::warn!(
+                    "{}, the swap may not be able to act on expired timelocks in time",
+                    problem
+                );
             }
 
-            let (event_loop, event_loop_handle) =
-                EventLoop::new(swap_id, swarm, seller_peer_id, env_config)?;
-            let handle = tokio::spawn(event_loop.run());
+            if !json {
+                tokio::spawn(cli::progress::show(
+                    bitcoin_wallet.progress(),
+                    monero_wallet.progress(),
+                    env_config,
+                ));
+            }
 
-            let monero_receive_address = db.get_monero_address(swap_id).await?;
-            let swap = Swap::from_db(
+            let factory = SwapFactory::new(
+                seed.derive_libp2p_identity(),
                 db,
-                swap_id,
-                bitcoin_wallet,
-                Arc::new(monero_wallet),
+                Arc::new(bitcoin_wallet),
+                monero_wallet,
                 env_config,
-                event_loop_handle,
-                monero_receive_address,
-            )
-            .await?;
+                tor_socks5_port,
+            );
+            let db = factory.db();
+
+            let seller_peer_id = db.get_peer_id(swap_id).await?;
+            database::prune_dead_addresses(db.as_ref()).await;
+            let seller_addresses = db.get_addresses(seller_peer_id).await?;
+
+            let (event_loop, event_loop_handle) = factory
+                .event_loop(swap_id, seller_peer_id, seller_addresses)
+                .await?;
+            let handle = tokio::spawn(event_loop.run());
+
+            let mut swap = factory.resume_swap(swap_id, event_loop_handle).await?;
+            if confirm_lock {
+                swap = swap.with_lock_approval(Arc::new(ConfirmLock {
+                    seller: seller_peer_id,
+                }));
+            }
+            if let Some(address) = watchtower {
+                let watchtower = watchtower::Client::new(address, seed.derive_libp2p_identity())?
+                    .with_tor(tor_socks5_port);
+
+                // Swaps that locked the Bitcoin already are registered right away
+                let presigned = db.get_presigned_transactions(swap_id).await?;
+                if !presigned.is_empty() {
+                    watchtower.spawn_register(swap_id, watchtower::of_bob(presigned));
+                }
+                swap = swap.with_watchtower(watchtower);
+            }
 
             tokio::select! {
                 event_loop_result = handle => {
                     event_loop_result?;
                 },
                 swap_result = bob::run(swap) => {
-                    swap_result?;
+                    let state = swap_result?;
+                    tracing::info!(%swap_id, %state, "Swap finished");
                 }
             }
         }
+        #[cfg(feature = "dashboard")]
+        Command::Dashboard {
+            bitcoin_electrum_rpc_urls,
+            bitcoin_target_block,
+            monero_daemon_address,
+            tor_socks5_port,
+        } => {
+            cli::tracing::init_file_only(debug, data_dir.logs(), "dashboard.log")?;
+
+            let db = open_db(data_dir.database()).await?;
+            let seed = Seed::from_file_or_generate(data_dir.root())
+                .context("Failed to read in seed file")?;
+
+            let bitcoin_wallet = init_bitcoin_wallet(
+                bitcoin_electrum_rpc_urls,
+                &seed,
+                &data_dir,
+                env_config,
+                bitcoin_target_block,
+            )
+            .await?;
+            let (monero_wallet, _process) =
+                init_monero_wallet(&data_dir, monero_daemon_address, env_config).await?;
+
+            let factory = SwapFactory::new(
+                seed.derive_libp2p_identity(),
+                db,
+                Arc::new(bitcoin_wallet),
+                Arc::new(monero_wallet),
+                env_config,
+                tor_socks5_port,
+            );
+            cli::dashboard::run(factory, env_config).await?;
+        }
+        #[cfg(not(feature = "dashboard"))]
+        Command::Dashboard { .. } => {
+            bail!("The dashboard requires a CLI built with the dashboard feature")
+        }
         Command::Cancel {
             swap_id,
-            bitcoin_electrum_rpc_url,
+            bitcoin_electrum_rpc_urls,
             bitcoin_target_block,
+            force,
         } => {
-            cli::tracing::init(debug, json, data_dir.join("logs"), Some(swap_id))?;
-            let db = open_db(data_dir.join("sqlite")).await?;
-            let seed = Seed::from_file_or_generate(data_dir.as_path())
+            cli::tracing::init(
+                debug,
+                json,
+                data_dir.logs(),
+                Some(swap_id),
+                otlp_endpoint.as_ref(),
+            )?;
+            let db = open_db(data_dir.database()).await?;
+            cli::export::ensure_not_exported(swap_id, db.as_ref()).await?;
+            let seed = Seed::from_file_or_generate(data_dir.root())
                 .context("Failed to read in seed file")?;
 
             let bitcoin_wallet = init_bitcoin_wallet(
-                bitcoin_electrum_rpc_url,
+                bitcoin_electrum_rpc_urls,
                 &seed,
-                data_dir,
+                &data_dir,
                 env_config,
                 bitcoin_target_block,
             )
             .await?;
 
-            let (txid, _) = cli::cancel(swap_id, Arc::new(bitcoin_wallet), db).await?;
+            let (txid, _) = cli::cancel(swap_id, Arc::new(bitcoin_wallet), db, force).await?;
             tracing::debug!("Cancel transaction successfully published with id {}", txid);
         }
+        Command::Artifacts {
+            swap_id,
+            bitcoin_electrum_rpc_urls,
+            bitcoin_target_block,
+        } => {
+            cli::tracing::init(debug, json, data_dir.logs(), None, otlp_endpoint.as_ref())?;
+            let db = open_db(data_dir.database()).await?;
+            let seed = Seed::from_file_or_generate(data_dir.root())
+                .context("Failed to read in seed file")?;
+
+            let bitcoin_wallet = init_bitcoin_wallet(
+                bitcoin_electrum_rpc_urls,
+                &seed,
+                &data_dir,
+                env_config,
+                bitcoin_target_block,
+            )
+            .await?;
+
+            let dir =
+                cli::artifacts::write(swap_id, &data_dir, db.as_ref(), &bitcoin_wallet).await?;
+            tracing::info!(path = %dir.display(), "Wrote artifacts of swap");
+        }
+        Command::Info {
+            swap_id,
+            bitcoin_electrum_rpc_urls,
+            bitcoin_target_block,
+        } => {
+            cli::tracing::init(debug, json, data_dir.logs(), None, otlp_endpoint.as_ref())?;
+            let db = open_db(data_dir.database()).await?;
+            let seed = Seed::from_file_or_generate(data_dir.root())
+                .context("Failed to read in seed file")?;
+
+            let bitcoin_wallet = init_bitcoin_wallet(
+                bitcoin_electrum_rpc_urls,
+                &seed,
+                &data_dir,
+                env_config,
+                bitcoin_target_block,
+            )
+            .await?;
+
+            let info = database::swap_info(db.as_ref(), swap_id, Some(&bitcoin_wallet)).await?;
+            if json {
+                println!("{}", serde_json::to_string(&info)?);
+            } else {
+                print!("{}", info);
+            }
+        }
+        Command::Inspect { swap_id, format } => {
+            cli::tracing::init(debug, json, data_dir.logs(), None, otlp_endpoint.as_ref())?;
+            let db = open_db(data_dir.database()).await?;
+
+            let rendered = cli::inspect::inspect(swap_id, format, db.as_ref()).await?;
+            print!("{}", rendered);
+        }
+        Command::Presigned { swap_id } => {
+            cli::tracing::init(debug, json, data_dir.logs(), None, otlp_endpoint.as_ref())?;
+            let db = open_db(data_dir.database()).await?;
+
+            let transactions = db.get_presigned_transactions(swap_id).await?;
+            if transactions.is_empty() {
+                bail!("No presigned transactions stored for swap {}", swap_id)
+            }
+
+            println!("{}", serde_json::to_string_pretty(&transactions)?);
+        }
+        Command::Receipt { swap_id } => {
+            cli::tracing::init(debug, json, data_dir.logs(), None, otlp_endpoint.as_ref())?;
+            let db = open_db(data_dir.database()).await?;
+
+            let receipt = db
+                .get_receipt(swap_id)
+                .await?
+                .with_context(|| format!("No signed receipt stored for swap {}", swap_id))?;
+
+            println!("{}", serde_json::to_string_pretty(&receipt)?);
+        }
+        Command::VerifyReceipt { file } => {
+            let contents = std::fs::read(&file)
+                .with_context(|| format!("Failed to read receipt from {}", file.display()))?;
+            let receipt = serde_json::from_slice::<Receipt>(&contents)
+                .with_context(|| format!("{} does not contain a receipt", file.display()))?;
+            receipt.verify()?;
+
+            let terms = receipt.terms;
+            println!(
+                "The receipt of swap {} is signed by the seller {} and the buyer {}: {} were swapped for {} at {}",
+                terms.swap_id,
+                terms.alice_peer_id,
+                terms.bob_peer_id,
+                terms.btc_amount,
+                terms.xmr_amount,
+                terms.price
+            );
+        }
         Command::Refund {
             swap_id,
-            bitcoin_electrum_rpc_url,
+            bitcoin_electrum_rpc_urls,
             bitcoin_target_block,
+            force,
         } => {
-            cli::tracing::init(debug, json, data_dir.join("logs"), Some(swap_id))?;
-            let db = open_db(data_dir.join("sqlite")).await?;
-            let seed = Seed::from_file_or_generate(data_dir.as_path())
+            cli::tracing::init(
+                debug,
+                json,
+                data_dir.logs(),
+                Some(swap_id),
+                otlp_endpoint.as_ref(),
+            )?;
+            let db = open_db(data_dir.database()).await?;
+            cli::export::ensure_not_exported(swap_id, db.as_ref()).await?;
+            let seed = Seed::from_file_or_generate(data_dir.root())
                 .context("Failed to read in seed file")?;
 
             let bitcoin_wallet = init_bitcoin_wallet(
-                bitcoin_electrum_rpc_url,
+                bitcoin_electrum_rpc_urls,
                 &seed,
-                data_dir,
+                &data_dir,
                 env_config,
                 bitcoin_target_block,
             )
             .await?;
 
-            cli::refund(swap_id, Arc::new(bitcoin_wallet), db).await?;
+            cli::refund(swap_id, Arc::new(bitcoin_wallet), db, force).await?;
         }
         Command::ListSellers {
             rendezvous_point,
             namespace,
             tor_socks5_port,
+            btc,
         } => {
             let rendezvous_node_peer_id = rendezvous_point
                 .extract_peer_id()
                 .context("Rendezvous node address must contain peer ID")?;
 
-            cli::tracing::init(debug, json, data_dir.join("logs"), None)?;
-            let seed = Seed::from_file_or_generate(data_dir.as_path())
+            cli::tracing::init(debug, json, data_dir.logs(), None, otlp_endpoint.as_ref())?;
+            let seed = Seed::from_file_or_generate(data_dir.root())
                 .context("Failed to read in seed file")?;
             let identity = seed.derive_libp2p_identity();
 
@@ -357,28 +743,18 @@ async fn main() -> Result<()> {
                 identity,
             )
             .await?;
+            let sellers = match btc {
+                Some(btc) => sellers
+                    .into_iter()
+                    .filter(|seller| seller.delay_for(btc).is_some())
+                    .collect(),
+                None => sellers,
+            };
 
             if json {
+                // One object per line on stdout, the logs go to stderr
                 for seller in sellers {
-                    match seller.status {
-                        SellerStatus::Online(quote) => {
-                            tracing::info!(
-                                price = %quote.price.to_string(),
-                                min_quantity = %quote.min_quantity.to_string(),
-                                max_quantity = %quote.max_quantity.to_string(),
-                                status = "Online",
-                                address = %seller.multiaddr.to_string(),
-                                "Fetched peer status"
-                            );
-                        }
-                        SellerStatus::Unreachable => {
-                            tracing::info!(
-                                status = "Unreachable",
-                                address = %seller.multiaddr.to_string(),
-                                "Fetched peer status"
-                            );
-                        }
-                    }
+                    println!("{}", serde_json::to_string(&seller)?);
                 }
             } else {
                 let mut table = Table::new();
@@ -387,18 +763,30 @@ async fn main() -> Result<()> {
                     "PRICE",
                     "MIN_QUANTITY",
                     "MAX_QUANTITY",
+                    "DEPTH",
                     "STATUS",
+                    "CONNECT",
+                    "QUOTE",
                     "ADDRESS",
                 ]);
 
                 for seller in sellers {
                     let row = match seller.status {
                         SellerStatus::Online(quote) => {
+                            let latency = seller.latency.unwrap_or_default();
                             vec![
                                 quote.price.to_string(),
                                 quote.min_quantity.to_string(),
                                 quote.max_quantity.to_string(),
+                                seller
+                                    .depth
+                                    .iter()
+                                    .map(|bucket| bucket.to_string())
+                                    .collect::<Vec<_>>()
+                                    .join(", "),
                                 "Online".to_owned(),
+                                format!("{}ms", latency.connect_ms),
+                                format!("{}ms", latency.quote_ms),
                                 seller.multiaddr.to_string(),
                             ]
                         }
@@ -407,7 +795,10 @@ async fn main() -> Result<()> {
                                 "???".to_owned(),
                                 "???".to_owned(),
                                 "???".to_owned(),
+                                "???".to_owned(),
                                 "Unreachable".to_owned(),
+                                "???".to_owned(),
+                                "???".to_owned(),
                                 seller.multiaddr.to_string(),
                             ]
                         }
@@ -420,17 +811,17 @@ async fn main() -> Result<()> {
             }
         }
         Command::ExportBitcoinWallet {
-            bitcoin_electrum_rpc_url,
+            bitcoin_electrum_rpc_urls,
             bitcoin_target_block,
         } => {
-            cli::tracing::init(debug, json, data_dir.join("logs"), None)?;
+            cli::tracing::init(debug, json, data_dir.logs(), None, otlp_endpoint.as_ref())?;
 
-            let seed = Seed::from_file_or_generate(data_dir.as_path())
+            let seed = Seed::from_file_or_generate(data_dir.root())
                 .context("Failed to read in seed file")?;
             let bitcoin_wallet = init_bitcoin_wallet(
-                bitcoin_electrum_rpc_url,
+                bitcoin_electrum_rpc_urls,
                 &seed,
-                data_dir.clone(),
+                &data_dir,
                 env_config,
                 bitcoin_target_block,
             )
@@ -439,7 +830,8 @@ async fn main() -> Result<()> {
             tracing::info!(descriptor=%wallet_export.to_string(), "Exported bitcoin wallet");
         }
         Command::MoneroRecovery { swap_id } => {
-            let db = open_db(data_dir.join("sqlite")).await?;
+            cli::tracing::init(debug, json, data_dir.logs(), None, otlp_endpoint.as_ref())?;
+            let db = open_db(data_dir.database()).await?;
 
             let swap_state: BobState = db.get_state(swap_id).await?.try_into()?;
 
@@ -469,27 +861,371 @@ async fn main() -> Result<()> {
                     tracing::info!("Wallet address: {}", address.to_string());
 
                     let view_key = serde_json::to_string(&view_key)?;
-                    println!("View key: {}", view_key);
+                    let restore_height = state5.monero_wallet_restore_blockheight.height;
+
+                    if json {
+                        tracing::info!(%view_key, %spend_key, %restore_height, "Monero recovery information");
+                    } else {
+                        println!("View key: {}", view_key);
+
+                        println!("Spend key: {}", spend_key);
+
+                        println!("Restore height: {}", restore_height);
+                    }
+                }
+            }
+        }
+        Command::Rescan {
+            swap_id,
+            monero_daemon_address,
+        } => {
+            cli::tracing::init(
+                debug,
+                json,
+                data_dir.logs(),
+                Some(swap_id),
+                otlp_endpoint.as_ref(),
+            )?;
+            let db = open_db(data_dir.database()).await?;
+
+            let swap_state: BobState = db.get_state(swap_id).await?.try_into()?;
+            let state5 = match swap_state {
+                BobState::BtcRedeemed(state5) => state5,
+                _ => bail!(
+                    "Cannot rescan the Monero wallet in state {}, only possible for BtcRedeemed",
+                    swap_state
+                ),
+            };
+
+            let (monero_wallet, _process) =
+                init_monero_wallet(&data_dir, monero_daemon_address, env_config).await?;
+
+            let (spend_key, view_key) = state5.xmr_keys();
+            let restore_height = state5.monero_wallet_restore_blockheight;
+
+            let wallet_file_name = swap_id.to_string();
+            if let Err(e) = monero_wallet.open(wallet_file_name.clone()).await {
+                tracing::debug!(%wallet_file_name, "Failed to open Monero wallet, generating it from keys: {:#}", e);
+
+                monero_wallet
+                    .create_from_and_load(wallet_file_name, spend_key, view_key, restore_height)
+                    .await?;
+            }
+
+            tracing::info!(
+                restore_height = %restore_height.height,
+                "Rescanning Monero wallet, this may take a while"
+            );
+            monero_wallet.rescan().await?;
+
+            let balance = monero_wallet.get_balance().await?;
+            tracing::info!(
+                %balance,
+                "Rescanned Monero wallet, resume the swap to transfer the Monero to your receive address"
+            );
+        }
+        Command::SweepSwapWallets {
+            monero_daemon_address,
+        } => {
+            cli::tracing::init(debug, json, data_dir.logs(), None, otlp_endpoint.as_ref())?;
+            let db = open_db(data_dir.database()).await?;
+
+            let (monero_wallet, monero_wallet_rpc_process) =
+                init_monero_wallet(&data_dir, monero_daemon_address, env_config).await?;
+
+            let swap_wallets =
+                cli::sweep_swap_wallets(monero_wallet_rpc_process.wallet_dir(), &monero_wallet, db)
+                    .await?;
+
+            if json {
+                for swap_wallet in swap_wallets {
+                    tracing::info!(
+                        swap_id = %swap_wallet.swap_id,
+                        balance = %swap_wallet.balance,
+                        unlocked_balance = %swap_wallet.unlocked_balance,
+                        action = %swap_wallet.action,
+                        "Processed swap wallet"
+                    );
+                }
+            } else {
+                let mut table = Table::new();
+
+                table.set_header(vec!["SWAP ID", "BALANCE", "UNLOCKED BALANCE", "ACTION"]);
+
+                for swap_wallet in swap_wallets {
+                    table.add_row(vec![
+                        swap_wallet.swap_id.to_string(),
+                        swap_wallet.balance.to_string(),
+                        swap_wallet.unlocked_balance.to_string(),
+                        swap_wallet.action.to_string(),
+                    ]);
+                }
+
+                println!("{}", table);
+            }
+        }
+        Command::Export { swap_id, file } => {
+            cli::tracing::init(
+                debug,
+                json,
+                data_dir.logs(),
+                Some(swap_id),
+                otlp_endpoint.as_ref(),
+            )?;
+            let db = open_db(data_dir.database()).await?;
+
+            let key = cli::export::export(swap_id, &file, db.as_ref()).await?;
+
+            if json {
+                tracing::info!(%swap_id, %key, file = %file.display(), "Exported swap");
+            } else {
+                println!("Export key: {}", key);
+                println!(
+                    "Import the swap on the other machine with `swap import --file <FILE> --key {}`",
+                    key
+                );
+            }
+        }
+        Command::Import { file, key } => {
+            cli::tracing::init(debug, json, data_dir.logs(), None, otlp_endpoint.as_ref())?;
+            let db = open_db(data_dir.database()).await?;
+
+            let swap_id = cli::export::import(&file, &key, db.as_ref()).await?;
+
+            tracing::info!("Resume the swap with `swap resume --swap-id {}`", swap_id);
+        }
+        Command::Check {
+            seller,
+            bitcoin_electrum_rpc_urls,
+            bitcoin_target_block,
+            monero_daemon_address,
+            tor_socks5_port,
+        } => {
+            cli::tracing::init(debug, json, data_dir.logs(), None, otlp_endpoint.as_ref())?;
+            let seed = Seed::from_file_or_generate(data_dir.root())
+                .context("Failed to read in seed file")?;
+
+            let mut report = cli::check::Report::default();
 
-                    println!("Spend key: {}", spend_key);
+            report.record("Disk space", cli::check::disk_space(data_dir.root()));
+
+            let bitcoin_wallet = init_bitcoin_wallet(
+                bitcoin_electrum_rpc_urls,
+                &seed,
+                &data_dir,
+                env_config,
+                bitcoin_target_block,
+            )
+            .await;
+            let bitcoin_wallet = match bitcoin_wallet {
+                Ok(bitcoin_wallet) => {
+                    report.pass(
+                        "Bitcoin backend",
+                        format!("Connected to {}", bitcoin_wallet.backend_url().await),
+                    );
+                    Some(Arc::new(bitcoin_wallet))
+                }
+                Err(e) => {
+                    report.fail("Bitcoin backend", e);
+                    None
                 }
+            };
+
+            match &bitcoin_wallet {
+                Some(bitcoin_wallet) => {
+                    let clock_skew = async {
+                        let latest_block_timestamp =
+                            bitcoin_wallet.latest_block_timestamp().await?;
+                        cli::check::clock_skew(SystemTime::now(), latest_block_timestamp)
+                    };
+                    report.record("Clock", clock_skew.await);
+                }
+                None => report.skip("Clock", "Requires the Bitcoin backend"),
+            }
+
+            let monero_wallet =
+                init_monero_wallet(&data_dir, monero_daemon_address.clone(), env_config).await;
+            // The monero-wallet-rpc process has to be kept alive until all checks are done
+            let _monero_wallet = match monero_wallet {
+                Ok((monero_wallet, monero_wallet_rpc_process)) => {
+                    match monero_wallet.block_height().await {
+                        Ok(block_height) => report.pass(
+                            "Monero backend",
+                            format!(
+                                "Connected to {} at block height {}",
+                                monero_daemon_address, block_height.height
+                            ),
+                        ),
+                        Err(e) => report.fail("Monero backend", e),
+                    }
+                    Some((monero_wallet, monero_wallet_rpc_process))
+                }
+                Err(e) => {
+                    report.fail("Monero backend", e);
+                    None
+                }
+            };
+
+            let quote = match (seller, &bitcoin_wallet) {
+                (Some(seller), Some(bitcoin_wallet)) => {
+                    match request_quote(
+                        seller.clone(),
+                        &seed,
+                        env_config,
+                        tor_socks5_port,
+                        bitcoin_wallet.clone(),
+                    )
+                    .await
+                    {
+                        Ok(quote) => {
+                            report.pass(
+                                "Seller",
+                                format!("{} is online, price is {}", seller, quote.price),
+                            );
+                            Some(quote)
+                        }
+                        Err(e) => {
+                            report.fail("Seller", e);
+                            None
+                        }
+                    }
+                }
+                (Some(_), None) => {
+                    report.skip("Seller", "Requires the Bitcoin backend");
+                    None
+                }
+                (None, _) => {
+                    report.skip("Seller", "No seller given, pass `--seller` to check one");
+                    None
+                }
+            };
+
+            match &bitcoin_wallet {
+                Some(bitcoin_wallet) => {
+                    let balance = async {
+                        let max_giveable =
+                            bitcoin_wallet.max_giveable(TxLock::script_size()).await?;
+                        cli::check::bitcoin_balance(max_giveable, quote)
+                    };
+                    report.record("Bitcoin balance", balance.await);
+                }
+                None => report.skip("Bitcoin balance", "Requires the Bitcoin backend"),
+            }
+
+            if json {
+                for check in report.checks() {
+                    tracing::info!(
+                        check = %check.name,
+                        result = %check.outcome,
+                        details = %check.details,
+                        "Ran check"
+                    );
+                }
+            } else {
+                let mut table = Table::new();
+
+                table.set_header(vec!["CHECK", "RESULT", "DETAILS"]);
+
+                for check in report.checks() {
+                    table.add_row(vec![
+                        check.name.to_owned(),
+                        check.outcome.to_string(),
+                        check.details.clone(),
+                    ]);
+                }
+
+                println!("{}", table);
+            }
+
+            if report.failures() > 0 {
+                bail!(
+                    "{} of {} checks failed, fix them before starting a swap",
+                    report.failures(),
+                    report.checks().len()
+                );
             }
         }
     };
     Ok(())
 }
 
+/// Asks whether to send the deposit of a swap the seller rejected back to the
+/// change address. Scripts are only told how to do so.
+async fn offer_deposit_refund(
+    json: bool,
+    change_address: bitcoin::Address,
+    bitcoin_wallet: &bitcoin::Wallet,
+    db: &(dyn Database + Send + Sync),
+) -> Result<()> {
+    if json {
+        tracing::info!(
+            "The swap was aborted before the Bitcoin was locked, use `swap refund-deposit <ADDRESS>` to return the deposit"
+        );
+        return Ok(());
+    }
+
+    let refund = Confirm::new()
+        .with_prompt(format!(
+            "The swap was aborted before the Bitcoin was locked, send the balance of the internal Bitcoin wallet back to {}?",
+            change_address
+        ))
+        .default(true)
+        .interact()?;
+    if !refund {
+        tracing::info!(
+            "The deposit stays in the internal Bitcoin wallet, use `swap refund-deposit <ADDRESS>` to return it later"
+        );
+        return Ok(());
+    }
+
+    let (txid, amount) = cli::refund_deposit(change_address, bitcoin_wallet, db).await?;
+    tracing::info!(%txid, %amount, "Returned the deposit");
+
+    Ok(())
+}
+
+/// Requests a quote from the seller to check that it is reachable.
+async fn request_quote(
+    seller: Multiaddr,
+    seed: &Seed,
+    env_config: Config,
+    tor_socks5_port: u16,
+    bitcoin_wallet: Arc<bitcoin::Wallet>,
+) -> Result<Quote> {
+    let seller_peer_id = seller
+        .extract_peer_id()
+        .context("Seller address must contain peer ID")?;
+
+    let mut swarm = swarm::cli(seed.derive_libp2p_identity(), tor_socks5_port, |relay, _| {
+        cli::Behaviour::new(seller_peer_id, env_config, bitcoin_wallet, Some(relay), None)
+    })
+    .await?;
+    swarm.behaviour_mut().add_address(seller_peer_id, seller);
+
+    let (event_loop, mut event_loop_handle) =
+        EventLoop::new(Uuid::new_v4(), swarm, seller_peer_id, None, env_config)?;
+    let event_loop = tokio::spawn(event_loop.run());
+
+    let quote = event_loop_handle
+        .request_quote()
+        .await
+        .context("Failed to request quote from seller");
+    event_loop.abort();
+
+    quote
+}
+
 async fn init_bitcoin_wallet(
-    electrum_rpc_url: Url,
+    electrum_rpc_urls: Vec<Url>,
     seed: &Seed,
-    data_dir: PathBuf,
+    data_dir: &DataDir,
     env_config: Config,
     bitcoin_target_block: usize,
 ) -> Result<bitcoin::Wallet> {
-    let wallet_dir = data_dir.join("wallet");
+    let wallet_dir = data_dir.bitcoin_wallet();
 
     let wallet = bitcoin::Wallet::new(
-        electrum_rpc_url.clone(),
+        electrum_rpc_urls,
         &wallet_dir,
         seed.derive_extended_private_key(env_config.bitcoin_network)?,
         env_config,
@@ -504,7 +1240,7 @@ async fn init_bitcoin_wallet(
 }
 
 async fn init_monero_wallet(
-    data_dir: PathBuf,
+    data_dir: &DataDir,
     monero_daemon_address: String,
     env_config: Config,
 ) -> Result<(monero::Wallet, monero::WalletRpcProcess)> {
@@ -512,7 +1248,7 @@ async fn init_monero_wallet(
 
     const MONERO_BLOCKCHAIN_MONITORING_WALLET_NAME: &str = "swap-tool-blockchain-monitoring-wallet";
 
-    let monero_wallet_rpc = monero::WalletRpc::new(data_dir.join("monero")).await?;
+    let monero_wallet_rpc = monero::WalletRpc::new(data_dir.monero_wallets()).await?;
 
     let monero_wallet_rpc_process = monero_wallet_rpc
         .run(network, monero_daemon_address.as_str())
@@ -523,11 +1259,44 @@ async fn init_monero_wallet(
         MONERO_BLOCKCHAIN_MONITORING_WALLET_NAME.to_string(),
         env_config,
     )
-    .await?;
+    .await?
+    .with_monerod(monerod::Client::from_daemon_address(
+        monero_daemon_address.as_str(),
+    )?);
 
     Ok((monero_wallet, monero_wallet_rpc_process))
 }
 
+/// The Monero wallet a swap runs with, the monero-wallet-rpc runs as long as
+/// its process is kept.
+///
+/// Swaps are refused with a light wallet server until the CLI can sweep the
+/// redeemed Monero without monero-wallet-rpc.
+async fn init_swap_monero_wallet(
+    data_dir: &DataDir,
+    monero_daemon_address: String,
+    monero_light_wallet_server: Option<Url>,
+    env_config: Config,
+) -> Result<(
+    Arc<dyn monero::MoneroWallet>,
+    Option<monero::WalletRpcProcess>,
+)> {
+    match monero_light_wallet_server {
+        Some(url) => {
+            bail!(
+                "The light wallet server at {} cannot sweep the Monero of a swap yet, run the swap without `--monero-light-wallet-server`",
+                url
+            )
+        }
+        None => {
+            let (wallet, process) =
+                init_monero_wallet(data_dir, monero_daemon_address, env_config).await?;
+
+            Ok((Arc::new(wallet), Some(process)))
+        }
+    }
+}
+
 fn qr_code(value: &impl ToString) -> Result<String> {
     let code = QrCode::new(value.to_string())?;
     let qr_code = code
@@ -538,17 +1307,23 @@ fn qr_code(value: &impl ToString) -> Result<String> {
     Ok(qr_code)
 }
 
-async fn determine_btc_to_swap<FB, TB, FMG, TMG, FS, TS>(
+#[allow(clippy::too_many_arguments)]
+async fn determine_btc_to_swap<FB, TB, FU, TU, FMG, TMG, FS, TS>(
     json: bool,
-    bid_quote: impl Future<Output = Result<BidQuote>>,
+    bid_quote: impl Future<Output = Result<Quote>>,
+    xmr_amount: Option<monero::Amount>,
     get_new_address: impl Future<Output = Result<bitcoin::Address>>,
     balance: FB,
+    unconfirmed_balance: FU,
     max_giveable_fn: FMG,
     sync: FS,
+    max_wait: Duration,
 ) -> Result<(bitcoin::Amount, bitcoin::Amount)>
 where
     TB: Future<Output = Result<bitcoin::Amount>>,
     FB: Fn() -> TB,
+    TU: Future<Output = Result<bitcoin::Amount>>,
+    FU: Fn() -> TU,
     TMG: Future<Output = Result<bitcoin::Amount>>,
     FMG: Fn() -> TMG,
     TS: Future<Output = Result<()>>,
@@ -563,47 +1338,104 @@ where
         "Received quote",
     );
 
+    let required = match xmr_amount {
+        Some(xmr) => {
+            let required = bid_quote.price.btc_for(xmr)?;
+            if required < bid_quote.min_quantity || required > bid_quote.max_quantity {
+                bail!(
+                    "Buying {} costs {}, but the seller only accepts swaps between {} and {}",
+                    xmr,
+                    required,
+                    bid_quote.min_quantity,
+                    bid_quote.max_quantity
+                );
+            }
+            tracing::info!(%xmr, btc = %required, "Determined Bitcoin needed to buy the Monero");
+
+            Some(required)
+        }
+        None => None,
+    };
+    let minimum_amount = required.unwrap_or(bid_quote.min_quantity);
+
     let mut max_giveable = max_giveable_fn().await?;
+    let mut unconfirmed = unconfirmed_balance().await?;
 
-    if max_giveable == bitcoin::Amount::ZERO || max_giveable < bid_quote.min_quantity {
+    if max_giveable == bitcoin::Amount::ZERO
+        || max_giveable < minimum_amount
+        || unconfirmed > bitcoin::Amount::ZERO
+    {
         let deposit_address = get_new_address.await?;
-        let minimum_amount = bid_quote.min_quantity;
         let maximum_amount = bid_quote.max_quantity;
 
         if !json {
             eprintln!("{}", qr_code(&deposit_address)?);
         }
 
-        loop {
-            tracing::info!(
-                %deposit_address,
-                %max_giveable,
-                %minimum_amount,
-                %maximum_amount,
-                "Waiting for Bitcoin deposit",
-            );
+        tracing::info!(
+            %deposit_address,
+            %max_giveable,
+            %minimum_amount,
+            %maximum_amount,
+            "Waiting for Bitcoin deposit",
+        );
 
-            max_giveable = loop {
-                sync().await?;
-                let new_max_givable = max_giveable_fn().await?;
+        let deposit = async {
+            loop {
+                let (new_max_giveable, new_unconfirmed) = loop {
+                    sync().await?;
+                    let new_max_giveable = max_giveable_fn().await?;
+                    let new_unconfirmed = unconfirmed_balance().await?;
 
-                if new_max_givable > max_giveable {
-                    break new_max_givable;
-                }
+                    // Only report changes, a deposit may take hours to confirm
+                    if (new_max_giveable, new_unconfirmed) != (max_giveable, unconfirmed) {
+                        break (new_max_giveable, new_unconfirmed);
+                    }
 
-                tokio::time::sleep(Duration::from_secs(1)).await;
-            };
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                };
+                max_giveable = new_max_giveable;
+                unconfirmed = new_unconfirmed;
+
+                let new_balance = balance().await?;
+                tracing::info!(
+                    %new_balance,
+                    "Received {} of {} ({} unconfirmed)",
+                    max_giveable,
+                    minimum_amount,
+                    unconfirmed
+                );
+
+                if max_giveable < minimum_amount {
+                    match required {
+                        Some(_) => {
+                            tracing::info!("Deposited amount is less than the required amount")
+                        }
+                        None => tracing::info!("Deposited amount is less than `min_quantity`"),
+                    }
+                    continue;
+                }
 
-            let new_balance = balance().await?;
-            tracing::info!(%new_balance, %max_giveable, "Received Bitcoin");
+                // Swapping unconfirmed funds would make the lock transaction depend on them
+                if unconfirmed > bitcoin::Amount::ZERO {
+                    tracing::info!("Waiting for the deposit to be confirmed");
+                    continue;
+                }
 
-            if max_giveable < bid_quote.min_quantity {
-                tracing::info!("Deposited amount is less than `min_quantity`");
-                continue;
+                break;
             }
 
-            break;
-        }
+            Ok::<_, anyhow::Error>(())
+        };
+
+        tokio::time::timeout(max_wait, deposit)
+            .await
+            .with_context(|| {
+                format!(
+                    "No sufficient confirmed deposit within {} minutes, anything received so far stays in the internal wallet",
+                    max_wait.as_secs() / 60
+                )
+            })??;
     };
 
     let balance = balance().await?;
@@ -611,7 +1443,8 @@ where
 
     let max_accepted = bid_quote.max_quantity;
 
-    let btc_swap_amount = min(max_giveable, max_accepted);
+    // Anything deposited beyond the price of the requested Monero stays in the wallet
+    let btc_swap_amount = required.unwrap_or_else(|| min(max_giveable, max_accepted));
 
     Ok((btc_swap_amount, fees))
 }
@@ -620,7 +1453,7 @@ where
 mod tests {
     use super::*;
     use crate::determine_btc_to_swap;
-    use ::bitcoin::Amount;
+    use swap::bitcoin::Amount;
     use std::sync::Mutex;
     use swap::tracing_ext::capture_logs;
     use tracing::level_filters::LevelFilter;
@@ -628,7 +1461,7 @@ mod tests {
     #[tokio::test]
     async fn given_no_balance_and_transfers_less_than_max_swaps_max_giveable() {
         let writer = capture_logs(LevelFilter::INFO);
-        let givable = Arc::new(Mutex::new(MaxGiveable::new(vec![
+        let givable = Arc::new(Mutex::new(AmountSequence::new(vec![
             Amount::ZERO,
             Amount::from_btc(0.0009).unwrap(),
         ])));
@@ -636,13 +1469,16 @@ mod tests {
         let (amount, fees) = determine_btc_to_swap(
             true,
             async { Ok(quote_with_max(0.01)) },
+            None,
             get_dummy_address(),
             || async { Ok(Amount::from_btc(0.001)?) },
+            || async { Ok(Amount::ZERO) },
             || async {
                 let mut result = givable.lock().unwrap();
                 result.give()
             },
             || async { Ok(()) },
+            MAX_DEPOSIT_WAIT,
         )
         .await
         .unwrap();
@@ -653,9 +1489,9 @@ mod tests {
         assert_eq!((amount, fees), (expected_amount, expected_fees));
         assert_eq!(
             writer.captured(),
-            r" INFO swap: Received quote price=0.00100000 BTC minimum_amount=0.00000000 BTC maximum_amount=0.01000000 BTC
+            r" INFO swap: Received quote price=0.00100000 BTC/XMR minimum_amount=0.00000000 BTC maximum_amount=0.01000000 BTC
  INFO swap: Waiting for Bitcoin deposit deposit_address=1PdfytjS7C8wwd9Lq5o4x9aXA2YRqaCpH6 max_giveable=0.00000000 BTC minimum_amount=0.00000000 BTC maximum_amount=0.01000000 BTC
- INFO swap: Received Bitcoin new_balance=0.00100000 BTC max_giveable=0.00090000 BTC
+ INFO swap: Received 0.00090000 BTC of 0.00000000 BTC (0.00000000 BTC unconfirmed) new_balance=0.00100000 BTC
 "
         );
     }
@@ -663,7 +1499,7 @@ mod tests {
     #[tokio::test]
     async fn given_no_balance_and_transfers_more_then_swaps_max_quantity_from_quote() {
         let writer = capture_logs(LevelFilter::INFO);
-        let givable = Arc::new(Mutex::new(MaxGiveable::new(vec![
+        let givable = Arc::new(Mutex::new(AmountSequence::new(vec![
             Amount::ZERO,
             Amount::from_btc(0.1).unwrap(),
         ])));
@@ -671,13 +1507,16 @@ mod tests {
         let (amount, fees) = determine_btc_to_swap(
             true,
             async { Ok(quote_with_max(0.01)) },
+            None,
             get_dummy_address(),
             || async { Ok(Amount::from_btc(0.1001)?) },
+            || async { Ok(Amount::ZERO) },
             || async {
                 let mut result = givable.lock().unwrap();
                 result.give()
             },
             || async { Ok(()) },
+            MAX_DEPOSIT_WAIT,
         )
         .await
         .unwrap();
@@ -688,9 +1527,9 @@ mod tests {
         assert_eq!((amount, fees), (expected_amount, expected_fees));
         assert_eq!(
             writer.captured(),
-            r" INFO swap: Received quote price=0.00100000 BTC minimum_amount=0.00000000 BTC maximum_amount=0.01000000 BTC
+            r" INFO swap: Received quote price=0.00100000 BTC/XMR minimum_amount=0.00000000 BTC maximum_amount=0.01000000 BTC
  INFO swap: Waiting for Bitcoin deposit deposit_address=1PdfytjS7C8wwd9Lq5o4x9aXA2YRqaCpH6 max_giveable=0.00000000 BTC minimum_amount=0.00000000 BTC maximum_amount=0.01000000 BTC
- INFO swap: Received Bitcoin new_balance=0.10010000 BTC max_giveable=0.10000000 BTC
+ INFO swap: Received 0.10000000 BTC of 0.00000000 BTC (0.00000000 BTC unconfirmed) new_balance=0.10010000 BTC
 "
         );
     }
@@ -698,7 +1537,7 @@ mod tests {
     #[tokio::test]
     async fn given_initial_balance_below_max_quantity_swaps_max_givable() {
         let writer = capture_logs(LevelFilter::INFO);
-        let givable = Arc::new(Mutex::new(MaxGiveable::new(vec![
+        let givable = Arc::new(Mutex::new(AmountSequence::new(vec![
             Amount::from_btc(0.0049).unwrap(),
             Amount::from_btc(99.9).unwrap(),
         ])));
@@ -706,13 +1545,16 @@ mod tests {
         let (amount, fees) = determine_btc_to_swap(
             true,
             async { Ok(quote_with_max(0.01)) },
+            None,
             async { panic!("should not request new address when initial balance  is > 0") },
             || async { Ok(Amount::from_btc(0.005)?) },
+            || async { Ok(Amount::ZERO) },
             || async {
                 let mut result = givable.lock().unwrap();
                 result.give()
             },
             || async { Ok(()) },
+            MAX_DEPOSIT_WAIT,
         )
         .await
         .unwrap();
@@ -723,7 +1565,7 @@ mod tests {
         assert_eq!((amount, fees), (expected_amount, expected_fees));
         assert_eq!(
             writer.captured(),
-            r" INFO swap: Received quote price=0.00100000 BTC minimum_amount=0.00000000 BTC maximum_amount=0.01000000 BTC
+            r" INFO swap: Received quote price=0.00100000 BTC/XMR minimum_amount=0.00000000 BTC maximum_amount=0.01000000 BTC
 "
         );
     }
@@ -731,7 +1573,7 @@ mod tests {
     #[tokio::test]
     async fn given_initial_balance_above_max_quantity_swaps_max_quantity() {
         let writer = capture_logs(LevelFilter::INFO);
-        let givable = Arc::new(Mutex::new(MaxGiveable::new(vec![
+        let givable = Arc::new(Mutex::new(AmountSequence::new(vec![
             Amount::from_btc(0.1).unwrap(),
             Amount::from_btc(99.9).unwrap(),
         ])));
@@ -739,13 +1581,16 @@ mod tests {
         let (amount, fees) = determine_btc_to_swap(
             true,
             async { Ok(quote_with_max(0.01)) },
+            None,
             async { panic!("should not request new address when initial balance is > 0") },
             || async { Ok(Amount::from_btc(0.1001)?) },
+            || async { Ok(Amount::ZERO) },
             || async {
                 let mut result = givable.lock().unwrap();
                 result.give()
             },
             || async { Ok(()) },
+            MAX_DEPOSIT_WAIT,
         )
         .await
         .unwrap();
@@ -756,7 +1601,7 @@ mod tests {
         assert_eq!((amount, fees), (expected_amount, expected_fees));
         assert_eq!(
             writer.captured(),
-            r" INFO swap: Received quote price=0.00100000 BTC minimum_amount=0.00000000 BTC maximum_amount=0.01000000 BTC
+            r" INFO swap: Received quote price=0.00100000 BTC/XMR minimum_amount=0.00000000 BTC maximum_amount=0.01000000 BTC
 "
         );
     }
@@ -764,7 +1609,7 @@ mod tests {
     #[tokio::test]
     async fn given_no_initial_balance_then_min_wait_for_sufficient_deposit() {
         let writer = capture_logs(LevelFilter::INFO);
-        let givable = Arc::new(Mutex::new(MaxGiveable::new(vec![
+        let givable = Arc::new(Mutex::new(AmountSequence::new(vec![
             Amount::ZERO,
             Amount::from_btc(0.01).unwrap(),
         ])));
@@ -772,13 +1617,16 @@ mod tests {
         let (amount, fees) = determine_btc_to_swap(
             true,
             async { Ok(quote_with_min(0.01)) },
+            None,
             get_dummy_address(),
             || async { Ok(Amount::from_btc(0.0101)?) },
+            || async { Ok(Amount::ZERO) },
             || async {
                 let mut result = givable.lock().unwrap();
                 result.give()
             },
             || async { Ok(()) },
+            MAX_DEPOSIT_WAIT,
         )
         .await
         .unwrap();
@@ -789,9 +1637,9 @@ mod tests {
         assert_eq!((amount, fees), (expected_amount, expected_fees));
         assert_eq!(
             writer.captured(),
-            r" INFO swap: Received quote price=0.00100000 BTC minimum_amount=0.01000000 BTC maximum_amount=184467440737.09551615 BTC
+            r" INFO swap: Received quote price=0.00100000 BTC/XMR minimum_amount=0.01000000 BTC maximum_amount=184467440737.09551615 BTC
  INFO swap: Waiting for Bitcoin deposit deposit_address=1PdfytjS7C8wwd9Lq5o4x9aXA2YRqaCpH6 max_giveable=0.00000000 BTC minimum_amount=0.01000000 BTC maximum_amount=184467440737.09551615 BTC
- INFO swap: Received Bitcoin new_balance=0.01010000 BTC max_giveable=0.01000000 BTC
+ INFO swap: Received 0.01000000 BTC of 0.01000000 BTC (0.00000000 BTC unconfirmed) new_balance=0.01010000 BTC
 "
         );
     }
@@ -799,7 +1647,7 @@ mod tests {
     #[tokio::test]
     async fn given_balance_less_then_min_wait_for_sufficient_deposit() {
         let writer = capture_logs(LevelFilter::INFO);
-        let givable = Arc::new(Mutex::new(MaxGiveable::new(vec![
+        let givable = Arc::new(Mutex::new(AmountSequence::new(vec![
             Amount::from_btc(0.0001).unwrap(),
             Amount::from_btc(0.01).unwrap(),
         ])));
@@ -807,13 +1655,16 @@ mod tests {
         let (amount, fees) = determine_btc_to_swap(
             true,
             async { Ok(quote_with_min(0.01)) },
+            None,
             get_dummy_address(),
             || async { Ok(Amount::from_btc(0.0101)?) },
+            || async { Ok(Amount::ZERO) },
             || async {
                 let mut result = givable.lock().unwrap();
                 result.give()
             },
             || async { Ok(()) },
+            MAX_DEPOSIT_WAIT,
         )
         .await
         .unwrap();
@@ -824,9 +1675,9 @@ mod tests {
         assert_eq!((amount, fees), (expected_amount, expected_fees));
         assert_eq!(
             writer.captured(),
-            r" INFO swap: Received quote price=0.00100000 BTC minimum_amount=0.01000000 BTC maximum_amount=184467440737.09551615 BTC
+            r" INFO swap: Received quote price=0.00100000 BTC/XMR minimum_amount=0.01000000 BTC maximum_amount=184467440737.09551615 BTC
  INFO swap: Waiting for Bitcoin deposit deposit_address=1PdfytjS7C8wwd9Lq5o4x9aXA2YRqaCpH6 max_giveable=0.00010000 BTC minimum_amount=0.01000000 BTC maximum_amount=184467440737.09551615 BTC
- INFO swap: Received Bitcoin new_balance=0.01010000 BTC max_giveable=0.01000000 BTC
+ INFO swap: Received 0.01000000 BTC of 0.01000000 BTC (0.00000000 BTC unconfirmed) new_balance=0.01010000 BTC
 "
         );
     }
@@ -834,7 +1685,7 @@ mod tests {
     #[tokio::test]
     async fn given_no_initial_balance_and_transfers_less_than_min_keep_waiting() {
         let writer = capture_logs(LevelFilter::INFO);
-        let givable = Arc::new(Mutex::new(MaxGiveable::new(vec![
+        let givable = Arc::new(Mutex::new(AmountSequence::new(vec![
             Amount::ZERO,
             Amount::from_btc(0.01).unwrap(),
             Amount::from_btc(0.01).unwrap(),
@@ -847,13 +1698,16 @@ mod tests {
             determine_btc_to_swap(
                 true,
                 async { Ok(quote_with_min(0.1)) },
+                None,
                 get_dummy_address(),
                 || async { Ok(Amount::from_btc(0.0101)?) },
+                || async { Ok(Amount::ZERO) },
                 || async {
                     let mut result = givable.lock().unwrap();
                     result.give()
                 },
                 || async { Ok(()) },
+                MAX_DEPOSIT_WAIT,
             ),
         )
         .await
@@ -862,11 +1716,10 @@ mod tests {
         assert!(matches!(error, tokio::time::error::Elapsed { .. }));
         assert_eq!(
             writer.captured(),
-            r" INFO swap: Received quote price=0.00100000 BTC minimum_amount=0.10000000 BTC maximum_amount=184467440737.09551615 BTC
+            r" INFO swap: Received quote price=0.00100000 BTC/XMR minimum_amount=0.10000000 BTC maximum_amount=184467440737.09551615 BTC
  INFO swap: Waiting for Bitcoin deposit deposit_address=1PdfytjS7C8wwd9Lq5o4x9aXA2YRqaCpH6 max_giveable=0.00000000 BTC minimum_amount=0.10000000 BTC maximum_amount=184467440737.09551615 BTC
- INFO swap: Received Bitcoin new_balance=0.01010000 BTC max_giveable=0.01000000 BTC
+ INFO swap: Received 0.01000000 BTC of 0.10000000 BTC (0.00000000 BTC unconfirmed) new_balance=0.01010000 BTC
  INFO swap: Deposited amount is less than `min_quantity`
- INFO swap: Waiting for Bitcoin deposit deposit_address=1PdfytjS7C8wwd9Lq5o4x9aXA2YRqaCpH6 max_giveable=0.01000000 BTC minimum_amount=0.10000000 BTC maximum_amount=184467440737.09551615 BTC
 "
         );
     }
@@ -874,7 +1727,7 @@ mod tests {
     #[tokio::test]
     async fn given_longer_delay_until_deposit_should_not_spam_user() {
         let writer = capture_logs(LevelFilter::INFO);
-        let givable = Arc::new(Mutex::new(MaxGiveable::new(vec![
+        let givable = Arc::new(Mutex::new(AmountSequence::new(vec![
             Amount::ZERO,
             Amount::ZERO,
             Amount::ZERO,
@@ -892,14 +1745,17 @@ mod tests {
             determine_btc_to_swap(
                 true,
                 async { Ok(quote_with_min(0.1)) },
+                None,
                 get_dummy_address(),
                 || async { Ok(Amount::from_btc(0.21)?) },
+                || async { Ok(Amount::ZERO) },
                 || async {
                     let mut result = givable.lock().unwrap();
 
                     result.give()
                 },
                 || async { Ok(()) },
+                MAX_DEPOSIT_WAIT,
             ),
         )
         .await
@@ -908,19 +1764,94 @@ mod tests {
 
         assert_eq!(
             writer.captured(),
-            r" INFO swap: Received quote price=0.00100000 BTC minimum_amount=0.10000000 BTC maximum_amount=184467440737.09551615 BTC
+            r" INFO swap: Received quote price=0.00100000 BTC/XMR minimum_amount=0.10000000 BTC maximum_amount=184467440737.09551615 BTC
  INFO swap: Waiting for Bitcoin deposit deposit_address=1PdfytjS7C8wwd9Lq5o4x9aXA2YRqaCpH6 max_giveable=0.00000000 BTC minimum_amount=0.10000000 BTC maximum_amount=184467440737.09551615 BTC
- INFO swap: Received Bitcoin new_balance=0.21000000 BTC max_giveable=0.20000000 BTC
+ INFO swap: Received 0.20000000 BTC of 0.10000000 BTC (0.00000000 BTC unconfirmed) new_balance=0.21000000 BTC
+"
+        );
+    }
+
+    #[tokio::test]
+    async fn given_unconfirmed_deposit_then_wait_until_confirmed() {
+        let writer = capture_logs(LevelFilter::INFO);
+        let givable = Arc::new(Mutex::new(AmountSequence::new(vec![
+            Amount::ZERO,
+            Amount::from_btc(0.01).unwrap(),
+            Amount::from_btc(0.01).unwrap(),
+        ])));
+        let unconfirmed = Arc::new(Mutex::new(AmountSequence::new(vec![
+            Amount::ZERO,
+            Amount::from_btc(0.0101).unwrap(),
+            Amount::ZERO,
+        ])));
+
+        let (amount, fees) = determine_btc_to_swap(
+            true,
+            async { Ok(quote_with_min(0.01)) },
+            None,
+            get_dummy_address(),
+            || async { Ok(Amount::from_btc(0.0101)?) },
+            || async {
+                let mut result = unconfirmed.lock().unwrap();
+                result.give()
+            },
+            || async {
+                let mut result = givable.lock().unwrap();
+                result.give()
+            },
+            || async { Ok(()) },
+            MAX_DEPOSIT_WAIT,
+        )
+        .await
+        .unwrap();
+
+        let expected_amount = Amount::from_btc(0.01).unwrap();
+        let expected_fees = Amount::from_btc(0.0001).unwrap();
+
+        assert_eq!((amount, fees), (expected_amount, expected_fees));
+        assert_eq!(
+            writer.captured(),
+            r" INFO swap: Received quote price=0.00100000 BTC/XMR minimum_amount=0.01000000 BTC maximum_amount=184467440737.09551615 BTC
+ INFO swap: Waiting for Bitcoin deposit deposit_address=1PdfytjS7C8wwd9Lq5o4x9aXA2YRqaCpH6 max_giveable=0.00000000 BTC minimum_amount=0.01000000 BTC maximum_amount=184467440737.09551615 BTC
+ INFO swap: Received 0.01000000 BTC of 0.01000000 BTC (0.01010000 BTC unconfirmed) new_balance=0.01010000 BTC
+ INFO swap: Waiting for the deposit to be confirmed
+ INFO swap: Received 0.01000000 BTC of 0.01000000 BTC (0.00000000 BTC unconfirmed) new_balance=0.01010000 BTC
 "
         );
     }
 
-    struct MaxGiveable {
+    #[tokio::test]
+    async fn given_no_deposit_within_max_wait_then_fails() {
+        let givable = Arc::new(Mutex::new(AmountSequence::new(vec![Amount::ZERO; 3])));
+
+        let error = determine_btc_to_swap(
+            true,
+            async { Ok(quote_with_min(0.01)) },
+            None,
+            get_dummy_address(),
+            || async { Ok(Amount::ZERO) },
+            || async { Ok(Amount::ZERO) },
+            || async {
+                let mut result = givable.lock().unwrap();
+                result.give()
+            },
+            || async { Ok(()) },
+            Duration::from_millis(100),
+        )
+        .await
+        .unwrap_err();
+
+        assert!(error
+            .to_string()
+            .starts_with("No sufficient confirmed deposit within"));
+    }
+
+    struct AmountSequence {
         amounts: Vec<Amount>,
         call_counter: usize,
     }
 
-    impl MaxGiveable {
+    impl AmountSequence {
         fn new(amounts: Vec<Amount>) -> Self {
             Self {
                 amounts,
@@ -937,18 +1868,18 @@ mod tests {
         }
     }
 
-    fn quote_with_max(btc: f64) -> BidQuote {
-        BidQuote {
-            price: Amount::from_btc(0.001).unwrap(),
+    fn quote_with_max(btc: f64) -> Quote {
+        Quote {
+            price: Price::new(Amount::from_btc(0.001).unwrap()),
             max_quantity: Amount::from_btc(btc).unwrap(),
             min_quantity: Amount::ZERO,
         }
     }
 
-    fn quote_with_min(btc: f64) -> BidQuote {
-        BidQuote {
-            price: Amount::from_btc(0.001).unwrap(),
-            max_quantity: Amount::max_value(),
+    fn quote_with_min(btc: f64) -> Quote {
+        Quote {
+            price: Price::new(Amount::from_btc(0.001).unwrap()),
+            max_quantity: Amount::MAX,
             min_quantity: Amount::from_btc(btc).unwrap(),
         }
     }
@@ -0,0 +1,130 @@
+#![warn(
+    unused_extern_crates,
+    missing_copy_implementations,
+    rust_2018_idioms,
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    clippy::fallible_impl_from,
+    clippy::cast_precision_loss,
+    clippy::cast_possible_wrap,
+    clippy::dbg_macro
+)]
+#![forbid(unsafe_code)]
+
+//! Publishes the presigned transactions takers and makers register with it
+//! once their timelocks expired, see [`swap::watchtower`].
+
+use anyhow::{Context, Result};
+use libp2p::Multiaddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use structopt::StructOpt;
+use swap::data::system_data_dir;
+use swap::env::{GetConfig, Mainnet, Testnet};
+use swap::fs::ensure_directory_exists;
+use swap::network::{swarm, watchtower};
+use swap::seed::Seed;
+use swap::watchtower::{registrations_file, Registrations, Tower};
+use swap::{bitcoin, env};
+use url::Url;
+
+const DEFAULT_LISTEN_ADDRESS: &str = "/ip4/0.0.0.0/tcp/9940";
+const DEFAULT_MAX_AGE_DAYS: &str = "30";
+
+#[derive(StructOpt, Debug)]
+#[structopt(
+    name = "watchtower",
+    about = "Publishes presigned transactions of swaps"
+)]
+struct Arguments {
+    #[structopt(long, help = "Watch swaps on testnet")]
+    testnet: bool,
+
+    #[structopt(
+        long = "data-dir",
+        help = "The directory of the seed, the wallet and the registrations",
+        parse(from_os_str)
+    )]
+    data_dir: Option<PathBuf>,
+
+    #[structopt(
+        long = "listen",
+        help = "The address to listen for registrations on",
+        default_value = DEFAULT_LISTEN_ADDRESS
+    )]
+    listen: Vec<Multiaddr>,
+
+    #[structopt(
+        long = "electrum-rpc",
+        help = "The Electrum server to watch the swaps with"
+    )]
+    electrum_rpc_url: Url,
+
+    #[structopt(
+        long = "max-age-days",
+        help = "Registrations older than this many days are dropped",
+        default_value = DEFAULT_MAX_AGE_DAYS
+    )]
+    max_age_days: u64,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Arguments::from_args();
+
+    tracing::subscriber::set_global_default(
+        tracing_subscriber::fmt().with_env_filter("info").finish(),
+    )?;
+
+    let env_config = if args.testnet {
+        Testnet::get_config()
+    } else {
+        Mainnet::get_config()
+    };
+    let data_dir = match args.data_dir {
+        Some(data_dir) => data_dir,
+        None => system_data_dir()?
+            .join("watchtower")
+            .join(network_name(&env_config)),
+    };
+    ensure_directory_exists(&registrations_file(&data_dir))?;
+
+    let seed = Seed::from_file_or_generate(&data_dir).context("Failed to read in seed file")?;
+    let identity = seed.derive_libp2p_identity();
+
+    // The wallet holds no funds, it is only used to watch and publish transactions
+    let bitcoin_wallet = bitcoin::Wallet::new(
+        vec![args.electrum_rpc_url],
+        &data_dir.join("wallet"),
+        seed.derive_extended_private_key(env_config.bitcoin_network)?,
+        env_config,
+        1,
+    )
+    .await
+    .context("Failed to initialize Bitcoin wallet")?;
+
+    let mut swarm = swarm::watchtower(identity, watchtower::tower())?;
+    for address in args.listen {
+        swarm
+            .listen_on(address.clone())
+            .with_context(|| format!("Failed to listen on {}", address))?;
+    }
+    tracing::info!(peer_id = %swarm.local_peer_id(), "Watchtower started");
+
+    let registrations = Registrations::open(registrations_file(&data_dir))?;
+    let max_age = Duration::from_secs(args.max_age_days * 24 * 60 * 60);
+
+    Tower::new(swarm, Arc::new(bitcoin_wallet), registrations, max_age)
+        .run()
+        .await;
+
+    Ok(())
+}
+
+fn network_name(env_config: &env::Config) -> &'static str {
+    match env_config.bitcoin_network {
+        bitcoin::Network::Bitcoin => "mainnet",
+        _ => "testnet",
+    }
+}
@@ -1,10 +1,14 @@
+pub mod bitcoind;
 pub mod wallet;
 
+mod backends;
 mod cancel;
 mod lock;
 mod punish;
 mod redeem;
 mod refund;
+mod reorg;
+mod sync_stats;
 mod timelocks;
 
 pub use crate::bitcoin::cancel::{CancelTimelock, PunishTimelock, TxCancel};
@@ -12,8 +16,9 @@ pub use crate::bitcoin::lock::TxLock;
 pub use crate::bitcoin::punish::TxPunish;
 pub use crate::bitcoin::redeem::TxRedeem;
 pub use crate::bitcoin::refund::TxRefund;
+pub use crate::bitcoin::sync_stats::{SyncCounts, SyncStats, SYNC_BUDGET};
 pub use crate::bitcoin::timelocks::{BlockHeight, ExpiredTimelocks};
-pub use ::bitcoin::util::amount::Amount;
+pub use ::bitcoin::util::amount::Denomination;
 pub use ::bitcoin::util::psbt::PartiallySignedTransaction;
 pub use ::bitcoin::{Address, Network, Transaction, Txid};
 pub use ecdsa_fun::adaptor::EncryptedSignature;
@@ -21,12 +26,15 @@ pub use ecdsa_fun::fun::Scalar;
 pub use ecdsa_fun::Signature;
 pub use wallet::Wallet;
 
-#[cfg(test)]
+#[cfg(any(test, feature = "test-utils"))]
 pub use wallet::WalletBuilder;
 
+use crate::amounts::{self, Btc};
 use crate::bitcoin::wallet::ScriptStatus;
+use crate::secret::Wipe;
 use ::bitcoin::hashes::hex::ToHex;
 use ::bitcoin::hashes::Hash;
+use ::bitcoin::util::amount::ParseAmountError;
 use ::bitcoin::{secp256k1, SigHash};
 use anyhow::{bail, Context, Result};
 use bdk::miniscript::descriptor::Wsh;
@@ -38,8 +46,11 @@ use ecdsa_fun::ECDSA;
 use rand::{CryptoRng, RngCore};
 use serde::{Deserialize, Serialize};
 use sha2::Sha256;
+use std::fmt;
 use std::str::FromStr;
 
+pub type Amount = amounts::Amount<Btc>;
+
 #[derive(Serialize, Deserialize)]
 #[serde(remote = "Network")]
 #[allow(non_camel_case_types)]
@@ -51,12 +62,22 @@ pub enum network {
     Regtest,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+#[derive(Clone, Deserialize, Serialize, PartialEq)]
 pub struct SecretKey {
     inner: Scalar,
     public: Point,
 }
 
+// States end up in error messages and test failures, only the public key is
+// safe to print
+impl fmt::Debug for SecretKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SecretKey")
+            .field("public", &self.public)
+            .finish()
+    }
+}
+
 impl SecretKey {
     pub fn new_random<R: RngCore + CryptoRng>(rng: &mut R) -> Self {
         let scalar = Scalar::random(rng);
@@ -108,6 +129,30 @@ impl SecretKey {
     }
 }
 
+impl Wipe for SecretKey {
+    fn wipe(&mut self) {
+        self.inner = Scalar::one();
+    }
+}
+
+// The scalars of an encrypted signature are private, wiping one replaces it with
+// an encrypted signature that belongs to no swap
+impl Wipe for EncryptedSignature {
+    fn wipe(&mut self) {
+        *self = BLANK_ENCRYPTED_SIGNATURE.with(Clone::clone);
+    }
+}
+
+thread_local! {
+    static BLANK_ENCRYPTED_SIGNATURE: EncryptedSignature = {
+        let inner = Scalar::one();
+        let public = ECDSA::<()>::default().verification_key_for(&inner);
+        let key = SecretKey { inner, public };
+
+        key.encsign(key.public(), SigHash::from_inner([0u8; 32]))
+    };
+}
+
 #[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq)]
 pub struct PublicKey(Point);
 
@@ -171,7 +216,7 @@ pub fn verify_sig(
 ) -> Result<()> {
     let ecdsa = ECDSA::verify_only();
 
-    if ecdsa.verify(&verification_key.0, &transaction_sighash.into_inner(), &sig) {
+    if ecdsa.verify(&verification_key.0, &transaction_sighash.into_inner(), sig) {
         Ok(())
     } else {
         bail!(InvalidSignature)
@@ -194,7 +239,7 @@ pub fn verify_encsig(
         &verification_key.0,
         &encryption_key.0,
         &digest.into_inner(),
-        &encsig,
+        encsig,
     ) {
         Ok(())
     } else {
@@ -302,6 +347,122 @@ pub fn parse_rpc_error_code(error: &anyhow::Error) -> anyhow::Result<i64> {
     }
 }
 
+impl Amount {
+    pub const ONE_SAT: Self = Self::from_sat(1);
+    pub const ONE_BTC: Self = Self::from_sat(100_000_000);
+
+    pub const fn from_sat(satoshi: u64) -> Self {
+        Self::from_atomic(satoshi)
+    }
+
+    pub const fn as_sat(self) -> u64 {
+        self.as_atomic()
+    }
+
+    pub fn from_btc(btc: f64) -> Result<Self, ParseAmountError> {
+        ::bitcoin::Amount::from_btc(btc).map(Self::from)
+    }
+
+    pub fn as_btc(self) -> f64 {
+        ::bitcoin::Amount::from(self).as_btc()
+    }
+
+    pub fn from_str_in(s: &str, denomination: Denomination) -> Result<Self, ParseAmountError> {
+        ::bitcoin::Amount::from_str_in(s, denomination).map(Self::from)
+    }
+
+    pub fn to_string_in(self, denomination: Denomination) -> String {
+        ::bitcoin::Amount::from(self).to_string_in(denomination)
+    }
+}
+
+impl From<::bitcoin::Amount> for Amount {
+    fn from(amount: ::bitcoin::Amount) -> Self {
+        Self::from_sat(amount.as_sat())
+    }
+}
+
+impl From<Amount> for ::bitcoin::Amount {
+    fn from(amount: Amount) -> Self {
+        ::bitcoin::Amount::from_sat(amount.as_sat())
+    }
+}
+
+/// Parses an amount with its denomination, e.g. `0.5 BTC`.
+impl FromStr for Amount {
+    type Err = ParseAmountError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ::bitcoin::Amount::from_str(s).map(Self::from)
+    }
+}
+
+/// Serializes an amount as a number of BTC, as written in the config file of
+/// the ASB.
+pub mod as_btc {
+    use crate::bitcoin::Amount;
+    use ::bitcoin::util::amount::serde::as_btc;
+    use serde::{Deserializer, Serializer};
+
+    pub fn serialize<S>(amount: &Amount, s: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        as_btc::serialize(&::bitcoin::Amount::from(*amount), s)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Amount, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        as_btc::deserialize::<::bitcoin::Amount, D>(deserializer).map(Amount::from)
+    }
+
+    pub mod opt {
+        use crate::bitcoin::Amount;
+        use ::bitcoin::util::amount::serde::as_btc;
+        use serde::{Deserializer, Serializer};
+
+        pub fn serialize<S>(amount: &Option<Amount>, s: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            as_btc::opt::serialize(&amount.map(::bitcoin::Amount::from), s)
+        }
+
+        pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Amount>, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            let amount = as_btc::opt::deserialize::<::bitcoin::Amount, D>(deserializer)?;
+
+            Ok(amount.map(Amount::from))
+        }
+    }
+}
+
+/// Parses an amount of Bitcoin as typed by a user, e.g. `0.5 BTC`, `0.5btc` or
+/// `50000 sat`. An amount without a denomination is read as BTC.
+///
+/// Amounts that are more precise than one satoshi are rejected instead of
+/// being rounded.
+pub fn parse_amount(s: &str) -> Result<Amount> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| c.is_ascii_alphabetic())
+        .unwrap_or_else(|| s.len());
+    let (amount, denomination) = s.split_at(split);
+
+    let denomination = match denomination.to_lowercase().as_str() {
+        "" | "btc" => Denomination::Bitcoin,
+        "sat" | "sats" | "satoshi" => Denomination::Satoshi,
+        _ => bail!("Unknown Bitcoin denomination {}", denomination),
+    };
+
+    Amount::from_str_in(amount.trim(), denomination)
+        .with_context(|| format!("Invalid Bitcoin amount {}", s))
+}
+
 #[derive(Clone, Copy, thiserror::Error, Debug)]
 #[error("transaction does not spend anything")]
 pub struct NoInputs;
@@ -326,6 +487,44 @@ mod tests {
     use rand::rngs::OsRng;
     use uuid::Uuid;
 
+    #[test]
+    fn wiping_replaces_the_key_and_the_encrypted_signature() {
+        let mut key = SecretKey::new_random(&mut OsRng);
+        let mut encsig = key.encsign(
+            SecretKey::new_random(&mut OsRng).public(),
+            SigHash::from_inner([1u8; 32]),
+        );
+
+        key.wipe();
+        encsig.wipe();
+
+        assert_eq!(key.inner, Scalar::one());
+        assert_eq!(encsig, BLANK_ENCRYPTED_SIGNATURE.with(Clone::clone));
+    }
+
+    #[test]
+    fn parse_amount_with_and_without_denomination() {
+        let half_btc = Amount::from_sat(50_000_000);
+
+        assert_eq!(parse_amount("0.5").unwrap(), half_btc);
+        assert_eq!(parse_amount("0.5 BTC").unwrap(), half_btc);
+        assert_eq!(parse_amount(" 0.5btc ").unwrap(), half_btc);
+        assert_eq!(parse_amount("50000000 sat").unwrap(), half_btc);
+    }
+
+    #[test]
+    fn parse_amount_rejects_sub_satoshi_precision() {
+        assert!(parse_amount("0.000000001 BTC").is_err());
+        assert!(parse_amount("1.5 sat").is_err());
+    }
+
+    #[test]
+    fn parse_amount_rejects_unknown_denomination() {
+        let error = parse_amount("1 XMR").unwrap_err();
+
+        assert_eq!(error.to_string(), "Unknown Bitcoin denomination XMR");
+    }
+
     #[test]
     fn lock_confirmations_le_to_cancel_timelock_no_timelock_expired() {
         let tx_lock_status = ScriptStatus::from_confirmations(4);
@@ -442,7 +641,7 @@ mod tests {
         let cancel_transaction = alice_state3.signed_cancel_transaction().unwrap();
         let punish_transaction = alice_state3.signed_punish_transaction().unwrap();
         let redeem_transaction = alice_state3
-            .signed_redeem_transaction(encrypted_signature)
+            .signed_redeem_transaction(&encrypted_signature)
             .unwrap();
         let refund_transaction = bob_state6.signed_refund_transaction().unwrap();
 
@@ -0,0 +1,232 @@
+//! Failover between the Electrum servers of a wallet.
+//!
+//! The tip heights of all servers are compared against the quorum height, the
+//! upper median of the heights reported by the servers that responded. A
+//! server is in sync if its tip is at most [`MAX_LAG`] blocks away from the
+//! quorum height, a single server far ahead of the others is not trusted
+//! either. The current server is kept as long as it is in sync, otherwise the
+//! wallet switches to the server in sync with the highest tip. Servers listed
+//! first are preferred.
+
+use crate::bitcoin::timelocks::BlockHeight;
+use anyhow::{bail, Context, Result};
+use bdk::electrum_client::{self, ElectrumApi};
+use std::convert::TryFrom;
+use url::Url;
+
+/// A server whose tip is more than this many blocks away from the quorum
+/// height is not acted upon.
+pub const MAX_LAG: u32 = 2;
+
+#[derive(Debug, Clone)]
+pub struct Backends {
+    urls: Vec<Url>,
+    current: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Choice {
+    Keep,
+    Switch(usize),
+    Unavailable,
+}
+
+impl Backends {
+    pub fn new(urls: Vec<Url>) -> Result<Self> {
+        if urls.is_empty() {
+            bail!("At least one Electrum server is required");
+        }
+
+        Ok(Self { urls, current: 0 })
+    }
+
+    pub fn current(&self) -> &Url {
+        &self.urls[self.current]
+    }
+
+    pub fn has_fallbacks(&self) -> bool {
+        self.urls.len() > 1
+    }
+
+    /// Connects to the healthiest server.
+    pub fn connect(&mut self) -> Result<electrum_client::Client> {
+        if !self.has_fallbacks() {
+            let (client, _) = connect_to(self.current())?;
+            return Ok(client);
+        }
+
+        let mut connections = self.urls.iter().map(probe).collect::<Vec<_>>();
+        let heights = heights(&connections);
+
+        match choose(None, &heights) {
+            Choice::Switch(index) => {
+                self.current = index;
+                let (client, _) = connections[index].take().expect("chosen server responded");
+                tracing::debug!(url = %self.current(), "Connected to Electrum server");
+
+                Ok(client)
+            }
+            Choice::Keep | Choice::Unavailable => bail!("None of the Electrum servers responded"),
+        }
+    }
+
+    /// Compares the tip height of the current server with those of the other
+    /// servers and returns a connection to the server to switch to, if any.
+    ///
+    /// Fails if the current server does not respond or lags behind and none
+    /// of the other servers can take over.
+    pub fn check(
+        &mut self,
+        current_height: Option<u32>,
+    ) -> Result<Option<electrum_client::Client>> {
+        let mut connections = self
+            .urls
+            .iter()
+            .enumerate()
+            .map(|(index, url)| {
+                if index == self.current {
+                    None
+                } else {
+                    probe(url)
+                }
+            })
+            .collect::<Vec<_>>();
+        let mut heights = heights(&connections);
+        heights[self.current] = current_height;
+
+        match choose(Some(self.current), &heights) {
+            Choice::Keep => Ok(None),
+            Choice::Switch(index) => {
+                tracing::warn!(
+                    from = %self.current(),
+                    to = %self.urls[index],
+                    from_height = ?current_height,
+                    to_height = ?heights[index],
+                    "Switching Bitcoin backend"
+                );
+                self.current = index;
+                let (client, _) = connections[index].take().expect("chosen server responded");
+
+                Ok(Some(client))
+            }
+            Choice::Unavailable => match current_height {
+                Some(height) => bail!(
+                    "Electrum server {} lags behind at block {} and no other server is in sync",
+                    self.current(),
+                    height
+                ),
+                None => bail!("None of the Electrum servers responded"),
+            },
+        }
+    }
+}
+
+fn probe(url: &Url) -> Option<(electrum_client::Client, u32)> {
+    match connect_to(url) {
+        Ok(connection) => Some(connection),
+        Err(error) => {
+            tracing::debug!(%url, "Electrum server is not available: {:#}", error);
+            None
+        }
+    }
+}
+
+fn connect_to(url: &Url) -> Result<(electrum_client::Client, u32)> {
+    let client = electrum_client::Client::new(url.as_str())
+        .with_context(|| format!("Failed to connect to {}", url))?;
+    let latest_block = client
+        .block_headers_subscribe()
+        .context("Failed to subscribe to header notifications")?;
+    let height = u32::from(BlockHeight::try_from(latest_block)?);
+
+    Ok((client, height))
+}
+
+fn heights(connections: &[Option<(electrum_client::Client, u32)>]) -> Vec<Option<u32>> {
+    connections
+        .iter()
+        .map(|connection| connection.as_ref().map(|(_, height)| *height))
+        .collect()
+}
+
+/// Decides which server to use given the tip heights of all servers, `None`
+/// for servers that did not respond.
+fn choose(current: Option<usize>, heights: &[Option<u32>]) -> Choice {
+    let mut responded = heights.iter().flatten().copied().collect::<Vec<_>>();
+    responded.sort_unstable();
+    let quorum_height = match responded.get(responded.len() / 2) {
+        Some(height) => *height,
+        None => return Choice::Unavailable,
+    };
+    let in_sync = |height: &Option<u32>| match height {
+        Some(height) => distance(*height, quorum_height) <= MAX_LAG,
+        None => false,
+    };
+
+    if let Some(current) = current {
+        if in_sync(&heights[current]) {
+            return Choice::Keep;
+        }
+    }
+
+    // `max_by_key` returns the last maximum, servers listed first are preferred
+    heights
+        .iter()
+        .enumerate()
+        .rev()
+        .filter(|(_, height)| in_sync(height))
+        .max_by_key(|(_, height)| **height)
+        .map_or(Choice::Unavailable, |(index, _)| Choice::Switch(index))
+}
+
+fn distance(a: u32, b: u32) -> u32 {
+    if a > b {
+        a - b
+    } else {
+        b - a
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn current_server_is_kept_while_in_sync() {
+        assert_eq!(
+            choose(Some(0), &[Some(100), Some(102), Some(101)]),
+            Choice::Keep
+        );
+        assert_eq!(choose(Some(1), &[None, Some(100)]), Choice::Keep);
+    }
+
+    #[test]
+    fn lagging_or_unavailable_server_is_replaced_by_highest_one() {
+        assert_eq!(
+            choose(Some(0), &[Some(90), Some(100), Some(101)]),
+            Choice::Switch(2)
+        );
+        assert_eq!(
+            choose(Some(0), &[None, Some(100), Some(100)]),
+            Choice::Switch(1)
+        );
+    }
+
+    #[test]
+    fn single_server_far_ahead_does_not_outvote_the_others() {
+        assert_eq!(
+            choose(Some(0), &[Some(100), Some(100), Some(1000)]),
+            Choice::Keep
+        );
+        assert_eq!(
+            choose(None, &[Some(100), Some(100), Some(1000)]),
+            Choice::Switch(0)
+        );
+    }
+
+    #[test]
+    fn no_responding_server_is_unavailable() {
+        assert_eq!(choose(Some(0), &[None, None]), Choice::Unavailable);
+        assert_eq!(choose(None, &[]), Choice::Unavailable);
+    }
+}
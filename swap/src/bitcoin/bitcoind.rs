@@ -0,0 +1,791 @@
+//! A local bitcoind as the backend of the Bitcoin wallet, instead of an
+//! Electrum server.
+//!
+//! bitcoind does not index scripts. The scripts of the bdk wallet and of the
+//! swap transactions are imported into a watch-only descriptor wallet of the
+//! node with `importdescriptors`, which then tracks the transactions paying to
+//! or spending from them. Scripts of the bdk wallet that are new to the node
+//! are looked up in the UTXO set with `scantxoutset` first, so the node only
+//! rescans the blocks since the oldest unspent output instead of the whole
+//! chain.
+//!
+//! The node is polled for new blocks and wallet transactions every second,
+//! which costs two requests against a local node, ZMQ notifications are not
+//! used. Requires bitcoind 0.21 or later for descriptor wallets.
+
+use crate::bitcoin::timelocks::BlockHeight;
+use crate::bitcoin::wallet::{Confirmed, ScriptStatus};
+use crate::bitcoin::{Amount, Transaction};
+use anyhow::{bail, Context, Result};
+use bdk::blockchain::{Capability, Progress};
+use bdk::database::BatchDatabase;
+use bdk::{FeeRate, KeychainKind, LocalUtxo, TransactionDetails};
+use bitcoin::consensus::encode;
+use bitcoin::{Address, BlockHash, BlockHeader, Network, OutPoint, Script, TxOut, Txid};
+use bitcoincore_rpc::jsonrpc;
+use bitcoincore_rpc::jsonrpc::simple_http::SimpleHttpTransport;
+use bitcoincore_rpc::RpcApi;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use url::Url;
+
+/// Scans of the UTXO set and rescans take minutes on mainnet.
+const TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+/// How far back the node looks for transactions of a swap script that is
+/// imported for the first time, e.g. when resuming a swap that was started
+/// with an Electrum server.
+const SWAP_RESCAN_WINDOW: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// `RPC_INVALID_ADDRESS_OR_KEY`, returned for transactions the node does not
+/// know.
+const NOT_FOUND: i32 = -5;
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// The JSON-RPC interface of the node, only plain HTTP is supported.
+    pub url: Url,
+    pub auth: Auth,
+    /// The watch-only wallet of the node the scripts are imported into, it
+    /// is created if it does not exist.
+    pub wallet_name: String,
+}
+
+#[derive(Clone, Debug)]
+pub enum Auth {
+    /// The `.cookie` file in the data directory of the node.
+    Cookie(PathBuf),
+    UserPass {
+        user: String,
+        password: String,
+    },
+}
+
+/// A connection to the watch-only wallet of the node.
+#[derive(Debug)]
+pub struct Bitcoind {
+    rpc: bitcoincore_rpc::Client,
+    url: Url,
+    network: Network,
+    /// Scripts imported or found in the node wallet since the wallet was
+    /// opened.
+    imported: HashSet<Script>,
+    last_tip: Option<BlockHash>,
+    last_tx_count: Option<u64>,
+}
+
+impl Bitcoind {
+    /// Connects to the wallet `config.wallet_name` of the node, which is
+    /// loaded or created if necessary.
+    pub fn connect(config: &Config, network: Network) -> Result<Self> {
+        let node = client(config, None)?;
+
+        let info: BlockchainInfo = call(&node, "getblockchaininfo", &[])
+            .with_context(|| format!("Failed to connect to bitcoind at {}", config.url))?;
+        let chain = chain(network);
+        if info.chain != chain {
+            bail!(
+                "bitcoind at {} runs on {}, expected {}",
+                config.url,
+                info.chain,
+                chain
+            );
+        }
+
+        let loaded: Vec<String> = call(&node, "listwallets", &[])?;
+        if !loaded.contains(&config.wallet_name) {
+            let load: Result<Value> = call(&node, "loadwallet", &[json!(config.wallet_name)]);
+            if load.is_err() {
+                // disable_private_keys, blank, passphrase, avoid_reuse, descriptors,
+                // load_on_startup
+                let _: Value = call(
+                    &node,
+                    "createwallet",
+                    &[
+                        json!(config.wallet_name),
+                        json!(true),
+                        json!(true),
+                        json!(""),
+                        json!(false),
+                        json!(true),
+                        json!(true),
+                    ],
+                )
+                .with_context(|| {
+                    format!("Failed to create wallet {} in bitcoind", config.wallet_name)
+                })?;
+                tracing::info!(wallet = %config.wallet_name, "Created watch-only wallet in bitcoind");
+            }
+        }
+
+        Ok(Self {
+            rpc: client(config, Some(&config.wallet_name))?,
+            url: config.url.clone(),
+            network,
+            imported: HashSet::new(),
+            last_tip: None,
+            last_tx_count: None,
+        })
+    }
+
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    pub fn tip(&self) -> Result<(BlockHeight, BlockHeader)> {
+        let hash = self
+            .rpc
+            .get_best_block_hash()
+            .context("Failed to get best block hash")?;
+        let info: HeaderInfo = call(&self.rpc, "getblockheader", &[json!(hash)])?;
+        let header = self.rpc.get_block_header(&hash)?;
+
+        Ok((BlockHeight::from(info.height), header))
+    }
+
+    pub fn block_header(&self, height: u32) -> Result<BlockHeader> {
+        let hash = self
+            .rpc
+            .get_block_hash(u64::from(height))
+            .with_context(|| format!("Failed to get hash of block {}", height))?;
+
+        Ok(self.rpc.get_block_header(&hash)?)
+    }
+
+    pub fn broadcast(&self, transaction: &Transaction) -> Result<()> {
+        self.rpc.send_raw_transaction(transaction)?;
+
+        Ok(())
+    }
+
+    /// Transactions of the imported scripts are known to the wallet, others
+    /// only while in the mempool or if the node runs with `-txindex`.
+    pub fn get_tx(&self, txid: Txid) -> Result<Transaction> {
+        match call::<WalletTransaction>(&self.rpc, "gettransaction", &[json!(txid), json!(true)]) {
+            Ok(transaction) => deserialize_hex(&transaction.hex),
+            Err(_) => Ok(self
+                .rpc
+                .get_raw_transaction(&txid, None)
+                .with_context(|| format!("bitcoind does not know transaction {}", txid))?),
+        }
+    }
+
+    pub fn status(&self, txid: Txid, latest_block: BlockHeight) -> Result<ScriptStatus> {
+        match call::<WalletTransaction>(&self.rpc, "gettransaction", &[json!(txid), json!(true)]) {
+            Ok(transaction) => Ok(script_status(
+                transaction.confirmations,
+                transaction.blockheight,
+                u32::from(latest_block),
+            )),
+            Err(error) if is_not_found(&error) => {
+                // A transaction that entered the mempool before its script was imported
+                match call::<Value>(&self.rpc, "getmempoolentry", &[json!(txid)]) {
+                    Ok(_) => Ok(ScriptStatus::InMempool),
+                    Err(error) if is_not_found(&error) => Ok(ScriptStatus::Unseen),
+                    Err(error) => Err(error),
+                }
+            }
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Imports the scripts of swap transactions the node does not track yet.
+    pub fn watch_swap_script(&mut self, script: &Script) -> Result<()> {
+        let since = SystemTime::now()
+            .duration_since(UNIX_EPOCH)?
+            .saturating_sub(SWAP_RESCAN_WINDOW)
+            .as_secs();
+
+        self.import(&[script.clone()], |_, _| Ok(Some(since)))
+    }
+
+    /// Imports the scripts of the bdk wallet the node does not track yet,
+    /// after looking up their unspent outputs to know where to rescan from.
+    pub fn watch_wallet_scripts(&mut self, scripts: &[Script]) -> Result<()> {
+        self.import(scripts, |bitcoind, scripts| {
+            bitcoind.oldest_unspent(scripts)
+        })
+    }
+
+    fn import(
+        &mut self,
+        scripts: &[Script],
+        rescan_since: impl FnOnce(&Self, &[Script]) -> Result<Option<u64>>,
+    ) -> Result<()> {
+        let mut new = Vec::new();
+        for script in scripts {
+            if self.imported.contains(script) {
+                continue;
+            }
+
+            // Scripts imported before the wallet was opened are still tracked by the node
+            if self.is_watched(script)? {
+                self.imported.insert(script.clone());
+            } else {
+                new.push(script.clone());
+            }
+        }
+        if new.is_empty() {
+            return Ok(());
+        }
+
+        let since = rescan_since(self, &new)?;
+        let requests = new
+            .iter()
+            .map(|script| {
+                Ok(json!({
+                    "desc": descriptor(script)?,
+                    "timestamp": since.map_or_else(|| json!("now"), |since| json!(since)),
+                }))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        tracing::debug!(scripts = new.len(), rescan_since = ?since, "Importing scripts into bitcoind");
+        let results: Vec<ImportResult> = call(&self.rpc, "importdescriptors", &[json!(requests)])
+            .context("Failed to import scripts into bitcoind")?;
+        if let Some(error) = results.iter().find_map(|result| result.error.as_ref()) {
+            bail!("bitcoind refused to import a script: {}", error.message);
+        }
+
+        self.imported.extend(new);
+
+        Ok(())
+    }
+
+    fn is_watched(&self, script: &Script) -> Result<bool> {
+        let address = match Address::from_script(script, self.network) {
+            Some(address) => address,
+            None => return Ok(false),
+        };
+        let info: AddressInfo = call(&self.rpc, "getaddressinfo", &[json!(address)])?;
+
+        Ok(info.ismine || info.iswatchonly)
+    }
+
+    /// The time of the block with the oldest unspent output of the scripts,
+    /// `None` if they have none.
+    fn oldest_unspent(&self, scripts: &[Script]) -> Result<Option<u64>> {
+        let descriptors = scripts.iter().map(descriptor).collect::<Result<Vec<_>>>()?;
+        let scan: ScanResult = call(
+            &self.rpc,
+            "scantxoutset",
+            &[json!("start"), json!(descriptors)],
+        )
+        .context("Failed to scan the UTXO set")?;
+
+        let height = match scan.unspents.iter().map(|unspent| unspent.height).min() {
+            Some(height) => height,
+            None => return Ok(None),
+        };
+        let header = self.block_header(height)?;
+
+        Ok(Some(u64::from(header.time)))
+    }
+
+    /// Whether a block was mined or a transaction of an imported script was
+    /// seen since the last call.
+    pub fn poll(&mut self) -> Result<(bool, bool)> {
+        let tip = self.rpc.get_best_block_hash()?;
+        let info: WalletInfo = call(&self.rpc, "getwalletinfo", &[])?;
+
+        let new_block = self.last_tip.replace(tip) != Some(tip);
+        let new_transaction = self.last_tx_count.replace(info.txcount) != Some(info.txcount);
+
+        Ok((new_block, new_transaction))
+    }
+
+    pub fn estimate_fee(&self, target_block: usize) -> Result<FeeRate> {
+        let estimate: FeeEstimate = call(&self.rpc, "estimatesmartfee", &[json!(target_block)])?;
+
+        match estimate.feerate {
+            // BTC per kvB
+            #[allow(clippy::cast_possible_truncation)]
+            Some(fee_rate) => Ok(FeeRate::from_btc_per_kvb(fee_rate as f32)),
+            // Regtest nodes rarely see enough transactions for an estimate
+            None if self.network == Network::Regtest => {
+                let relay_fee = self.relay_fee()?;
+                #[allow(clippy::cast_precision_loss)]
+                Ok(FeeRate::from_sat_per_vb(relay_fee.as_sat() as f32 / 1000.0))
+            }
+            None => bail!(
+                "bitcoind has no fee estimate for {} blocks yet",
+                target_block
+            ),
+        }
+    }
+
+    /// The minimum relay fee in BTC per kvB.
+    pub fn relay_fee(&self) -> Result<Amount> {
+        let info: NetworkInfo = call(&self.rpc, "getnetworkinfo", &[])?;
+
+        Ok(Amount::from_btc(info.relayfee)?)
+    }
+}
+
+/// The bdk blockchain of a wallet whose scripts are imported into the node,
+/// see [`Bitcoind::watch_wallet_scripts`].
+#[derive(Debug)]
+pub struct Blockchain {
+    bitcoind: Bitcoind,
+    /// Transactions of the node wallet that neither pay to nor spend from the
+    /// bdk wallet, i.e. swap transactions, so they are only fetched once.
+    foreign: Mutex<HashSet<Txid>>,
+}
+
+impl Blockchain {
+    pub fn new(bitcoind: Bitcoind) -> Self {
+        Self {
+            bitcoind,
+            foreign: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Replaces the transactions and unspent outputs of the bdk database with
+    /// those the node knows for the scripts of the database.
+    fn update<D: BatchDatabase>(&self, database: &mut D) -> Result<()> {
+        let rpc = &self.bitcoind.rpc;
+
+        let mut last_index = HashMap::new();
+        for keychain in [KeychainKind::External, KeychainKind::Internal] {
+            last_index.insert(keychain, database.get_last_index(keychain)?.unwrap_or(0));
+        }
+        let known_txs = database
+            .iter_txs(false)?
+            .into_iter()
+            .map(|tx| (tx.txid, tx))
+            .collect::<HashMap<_, _>>();
+
+        let mut foreign = self
+            .foreign
+            .lock()
+            .expect("foreign transactions lock not poisoned");
+        let mut txids = HashSet::new();
+        for entry in list_transactions(rpc)? {
+            // Replaced transactions stay in the wallet with negative confirmations
+            if entry.confirmations < 0 || foreign.contains(&entry.txid) || !txids.insert(entry.txid)
+            {
+                continue;
+            }
+            let confirmation_time = bdk::ConfirmationTime::new(entry.blockheight, entry.blocktime);
+
+            if let Some(known) = known_txs.get(&entry.txid) {
+                if known.confirmation_time != confirmation_time {
+                    let mut known = known.clone();
+                    known.confirmation_time = confirmation_time;
+                    database.set_tx(&known)?;
+                }
+                continue;
+            }
+
+            let details: WalletTransaction =
+                call(rpc, "gettransaction", &[json!(entry.txid), json!(true)])?;
+            let transaction: Transaction = deserialize_hex(&details.hex)?;
+
+            let mut received = 0;
+            for output in &transaction.output {
+                if let Some((keychain, index)) =
+                    database.get_path_from_script_pubkey(&output.script_pubkey)?
+                {
+                    let last = last_index.entry(keychain).or_default();
+                    *last = (*last).max(index);
+                    received += output.value;
+                }
+            }
+            let mut sent = 0;
+            for input in &transaction.input {
+                let previous = input.previous_output;
+                let previous_output = database
+                    .get_raw_tx(&previous.txid)?
+                    .and_then(|tx| tx.output.get(previous.vout as usize).cloned());
+                if let Some(previous_output) = previous_output {
+                    sent += previous_output.value;
+                }
+            }
+            // The node wallet also tracks the swap transactions
+            if received == 0 && sent == 0 {
+                txids.remove(&entry.txid);
+                foreign.insert(entry.txid);
+                continue;
+            }
+
+            database.set_tx(&TransactionDetails {
+                transaction: Some(transaction),
+                txid: entry.txid,
+                received,
+                sent,
+                fee: details
+                    .fee
+                    .map(|fee| Amount::from_btc(fee.abs()).map(|fee| fee.as_sat()))
+                    .transpose()?,
+                confirmation_time,
+                verified: true,
+            })?;
+        }
+
+        for txid in known_txs.keys().filter(|txid| !txids.contains(*txid)) {
+            database.del_tx(txid, false)?;
+        }
+
+        let unspent: Vec<Unspent> = call(
+            rpc,
+            "listunspent",
+            &[json!(0), json!(9_999_999), json!([]), json!(true)],
+        )?;
+        let mut utxos = HashSet::new();
+        for unspent in unspent {
+            let script_pubkey = deserialize_script(&unspent.script_pub_key)?;
+            let keychain = match database.get_path_from_script_pubkey(&script_pubkey)? {
+                Some((keychain, _)) => keychain,
+                None => continue,
+            };
+
+            utxos.insert(LocalUtxo {
+                outpoint: OutPoint::new(unspent.txid, unspent.vout),
+                txout: TxOut {
+                    value: Amount::from_btc(unspent.amount)?.as_sat(),
+                    script_pubkey,
+                },
+                keychain,
+            });
+        }
+
+        let known_utxos = database.iter_utxos()?.into_iter().collect::<HashSet<_>>();
+        for spent in known_utxos.difference(&utxos) {
+            database.del_utxo(&spent.outpoint)?;
+        }
+        for received in utxos.difference(&known_utxos) {
+            database.set_utxo(received)?;
+        }
+
+        for (keychain, index) in last_index {
+            database.set_last_index(keychain, index)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl bdk::blockchain::Blockchain for Blockchain {
+    fn get_capabilities(&self) -> HashSet<Capability> {
+        vec![Capability::FullHistory].into_iter().collect()
+    }
+
+    fn setup<D: BatchDatabase, P: 'static + Progress>(
+        &self,
+        database: &mut D,
+        _: P,
+    ) -> Result<(), bdk::Error> {
+        self.update(database).map_err(generic)
+    }
+
+    fn get_tx(&self, txid: &Txid) -> Result<Option<Transaction>, bdk::Error> {
+        self.bitcoind.get_tx(*txid).map(Some).map_err(generic)
+    }
+
+    fn broadcast(&self, tx: &Transaction) -> Result<(), bdk::Error> {
+        self.bitcoind.broadcast(tx).map_err(generic)
+    }
+
+    fn get_height(&self) -> Result<u32, bdk::Error> {
+        let height = self.bitcoind.rpc.get_block_count().map_err(generic)?;
+
+        u32::try_from(height).map_err(generic)
+    }
+
+    fn estimate_fee(&self, target: usize) -> Result<FeeRate, bdk::Error> {
+        self.bitcoind.estimate_fee(target).map_err(generic)
+    }
+}
+
+fn generic(error: impl Into<anyhow::Error>) -> bdk::Error {
+    bdk::Error::Generic(format!("{:#}", error.into()))
+}
+
+fn client(config: &Config, wallet: Option<&str>) -> Result<bitcoincore_rpc::Client> {
+    let mut url = config.url.clone();
+    if let Some(wallet) = wallet {
+        url.path_segments_mut()
+            .map_err(|_| anyhow::anyhow!("Invalid bitcoind URL {}", config.url))?
+            .pop_if_empty()
+            .extend(&["wallet", wallet]);
+    }
+
+    let cookie = match &config.auth {
+        Auth::Cookie(path) => std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read bitcoind cookie file {}", path.display()))?
+            .trim()
+            .to_owned(),
+        Auth::UserPass { user, password } => format!("{}:{}", user, password),
+    };
+    let transport = SimpleHttpTransport::builder()
+        .url(url.as_str())
+        .with_context(|| format!("Invalid bitcoind URL {}", config.url))?
+        .timeout(TIMEOUT)
+        .cookie_auth(cookie)
+        .build();
+
+    Ok(bitcoincore_rpc::Client::from_jsonrpc(
+        jsonrpc::Client::with_transport(transport),
+    ))
+}
+
+fn call<T>(rpc: &bitcoincore_rpc::Client, method: &str, params: &[Value]) -> Result<T>
+where
+    T: DeserializeOwned,
+{
+    rpc.call(method, params)
+        .with_context(|| format!("bitcoind request {} failed", method))
+}
+
+fn is_not_found(error: &anyhow::Error) -> bool {
+    matches!(
+        error.downcast_ref::<bitcoincore_rpc::Error>(),
+        Some(bitcoincore_rpc::Error::JsonRpc(jsonrpc::Error::Rpc(error))) if error.code == NOT_FOUND
+    )
+}
+
+/// The wallet transactions of the node, newest last.
+fn list_transactions(rpc: &bitcoincore_rpc::Client) -> Result<Vec<ListedTransaction>> {
+    const PAGE: usize = 1000;
+
+    let mut all = Vec::new();
+    loop {
+        // label, count, skip, include_watchonly
+        let page: Vec<ListedTransaction> = call(
+            rpc,
+            "listtransactions",
+            &[json!("*"), json!(PAGE), json!(all.len()), json!(true)],
+        )?;
+        let complete = page.len() < PAGE;
+        all.extend(page);
+
+        if complete {
+            return Ok(all);
+        }
+    }
+}
+
+/// The status of a wallet transaction given its confirmations as reported by
+/// the node.
+///
+/// The depth is computed from the block height of the transaction and
+/// `latest_block`, like for Electrum servers, so it does not get ahead of the
+/// block height the wallet notified about.
+fn script_status(confirmations: i32, blockheight: Option<u32>, latest_block: u32) -> ScriptStatus {
+    match (confirmations, blockheight) {
+        // Conflicts with a transaction in the chain or was replaced in the mempool
+        (confirmations, _) if confirmations < 0 => ScriptStatus::Unseen,
+        (0, _) => ScriptStatus::InMempool,
+        (_, Some(height)) => ScriptStatus::Confirmed(Confirmed::from_inclusion_and_latest_block(
+            height,
+            latest_block,
+        )),
+        (confirmations, None) => ScriptStatus::from_confirmations(confirmations.unsigned_abs()),
+    }
+}
+
+/// The checksummed descriptor of a single script, as expected by
+/// `importdescriptors`.
+fn descriptor(script: &Script) -> Result<String> {
+    let descriptor = format!("raw({})", hex::encode(script.as_bytes()));
+    let checksum = bdk::descriptor::get_checksum(&descriptor)
+        .map_err(|error| anyhow::anyhow!("Failed to compute descriptor checksum: {:?}", error))?;
+
+    Ok(format!("{}#{}", descriptor, checksum))
+}
+
+fn chain(network: Network) -> &'static str {
+    match network {
+        Network::Bitcoin => "main",
+        Network::Testnet => "test",
+        Network::Signet => "signet",
+        Network::Regtest => "regtest",
+    }
+}
+
+fn deserialize_hex<T: encode::Decodable>(hex: &str) -> Result<T> {
+    let bytes = hex::decode(hex).context("Invalid hex in bitcoind response")?;
+
+    Ok(encode::deserialize(&bytes)?)
+}
+
+fn deserialize_script(hex: &str) -> Result<Script> {
+    let bytes = hex::decode(hex).context("Invalid script in bitcoind response")?;
+
+    Ok(Script::from(bytes))
+}
+
+#[derive(Deserialize)]
+struct BlockchainInfo {
+    chain: String,
+}
+
+#[derive(Deserialize)]
+struct HeaderInfo {
+    height: u32,
+}
+
+#[derive(Deserialize)]
+struct WalletTransaction {
+    confirmations: i32,
+    blockheight: Option<u32>,
+    fee: Option<f64>,
+    hex: String,
+}
+
+#[derive(Deserialize)]
+struct ListedTransaction {
+    txid: Txid,
+    confirmations: i32,
+    blockheight: Option<u32>,
+    blocktime: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct Unspent {
+    txid: Txid,
+    vout: u32,
+    #[serde(rename = "scriptPubKey")]
+    script_pub_key: String,
+    amount: f64,
+}
+
+#[derive(Deserialize)]
+struct AddressInfo {
+    ismine: bool,
+    #[serde(default)]
+    iswatchonly: bool,
+}
+
+#[derive(Deserialize)]
+struct ScanResult {
+    unspents: Vec<ScannedUnspent>,
+}
+
+#[derive(Deserialize)]
+struct ScannedUnspent {
+    height: u32,
+}
+
+#[derive(Deserialize)]
+struct ImportResult {
+    error: Option<ImportError>,
+}
+
+#[derive(Deserialize)]
+struct ImportError {
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct WalletInfo {
+    txcount: u64,
+}
+
+#[derive(Deserialize)]
+struct FeeEstimate {
+    feerate: Option<f64>,
+}
+
+#[derive(Deserialize)]
+struct NetworkInfo {
+    relayfee: f64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn given_negative_confirmations_then_transaction_is_unseen() {
+        let replaced: WalletTransaction = serde_json::from_value(json!({
+            "amount": 0.001,
+            "confirmations": -2,
+            "txid": "0e3e2357e806b6cdb1f70b54c3a3a17b6714ee1f0e68bebb44a74b1efd512098",
+            "walletconflicts": [],
+            "time": 1231006505,
+            "hex": "00"
+        }))
+        .unwrap();
+
+        let status = script_status(replaced.confirmations, replaced.blockheight, 100);
+
+        assert_eq!(status, ScriptStatus::Unseen);
+    }
+
+    #[test]
+    fn given_zero_confirmations_then_transaction_is_in_mempool() {
+        assert_eq!(script_status(0, None, 100), ScriptStatus::InMempool);
+    }
+
+    #[test]
+    fn depth_follows_the_latest_block_the_wallet_knows() {
+        // The node already saw block 101, the wallet was only notified about 100
+        let status = script_status(2, Some(100), 100);
+
+        assert_eq!(status, ScriptStatus::Confirmed(Confirmed::new(0)));
+    }
+
+    #[test]
+    fn given_no_block_height_then_confirmations_are_used() {
+        assert_eq!(
+            script_status(3, None, 100),
+            ScriptStatus::from_confirmations(3)
+        );
+    }
+
+    #[test]
+    fn descriptor_of_script_is_checksummed_raw_descriptor() {
+        let script =
+            Script::from(hex::decode("0014751e76e8199196d454941c45d1b3a323f1433bd6").unwrap());
+
+        let descriptor = descriptor(&script).unwrap();
+
+        let (raw, checksum) = descriptor.split_once('#').unwrap();
+        assert_eq!(raw, "raw(0014751e76e8199196d454941c45d1b3a323f1433bd6)");
+        assert_eq!(checksum.len(), 8);
+        assert_eq!(bdk::descriptor::get_checksum(raw).unwrap(), checksum);
+    }
+
+    #[test]
+    fn parses_unspent_outputs_and_import_errors() {
+        let unspent: Vec<Unspent> = serde_json::from_value(json!([{
+            "txid": "0e3e2357e806b6cdb1f70b54c3a3a17b6714ee1f0e68bebb44a74b1efd512098",
+            "vout": 1,
+            "address": "bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080",
+            "scriptPubKey": "0014751e76e8199196d454941c45d1b3a323f1433bd6",
+            "amount": 0.0025,
+            "confirmations": 6,
+            "spendable": false,
+            "solvable": false,
+            "safe": true
+        }]))
+        .unwrap();
+        let results: Vec<ImportResult> = serde_json::from_value(json!([
+            { "success": true },
+            { "success": false, "error": { "code": -5, "message": "Invalid descriptor" } }
+        ]))
+        .unwrap();
+
+        assert_eq!(unspent[0].vout, 1);
+        assert_eq!(
+            Amount::from_btc(unspent[0].amount).unwrap(),
+            Amount::from_sat(250_000)
+        );
+        assert_eq!(
+            deserialize_script(&unspent[0].script_pub_key).unwrap(),
+            Script::from(hex::decode("0014751e76e8199196d454941c45d1b3a323f1433bd6").unwrap())
+        );
+        assert!(results[0].error.is_none());
+        assert_eq!(
+            results[1].error.as_ref().unwrap().message,
+            "Invalid descriptor"
+        );
+    }
+}
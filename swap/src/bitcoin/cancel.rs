@@ -48,6 +48,12 @@ impl PartialEq<CancelTimelock> for u32 {
     }
 }
 
+impl From<CancelTimelock> for u32 {
+    fn from(timelock: CancelTimelock) -> Self {
+        timelock.0
+    }
+}
+
 impl fmt::Display for CancelTimelock {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{} blocks", self.0)
@@ -88,6 +94,12 @@ impl PartialEq<PunishTimelock> for u32 {
     }
 }
 
+impl From<PunishTimelock> for u32 {
+    fn from(timelock: PunishTimelock) -> Self {
+        timelock.0
+    }
+}
+
 #[derive(Debug)]
 pub struct TxCancel {
     inner: Transaction,
@@ -114,7 +126,11 @@ impl TxCancel {
         };
 
         let tx_out = TxOut {
-            value: tx_lock.lock_amount().as_sat() - spending_fee.as_sat(),
+            value: tx_lock
+                .lock_amount()
+                .checked_sub(spending_fee)
+                .expect("fees are checked against the lock amount during swap setup")
+                .as_sat(),
             script_pubkey: cancel_output_descriptor.script_pubkey(),
         };
 
@@ -235,7 +251,11 @@ impl TxCancel {
         };
 
         let tx_out = TxOut {
-            value: self.amount().as_sat() - spending_fee.as_sat(),
+            value: self
+                .amount()
+                .checked_sub(spending_fee)
+                .expect("fees are checked against the lock amount during swap setup")
+                .as_sat(),
             script_pubkey: spend_address.script_pubkey(),
         };
 
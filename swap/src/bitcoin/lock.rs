@@ -2,13 +2,14 @@ use crate::bitcoin::wallet::{EstimateFeeRate, Watchable};
 use crate::bitcoin::{
     build_shared_output_descriptor, Address, Amount, PublicKey, Transaction, Wallet,
 };
-use ::bitcoin::util::psbt::PartiallySignedTransaction;
+use ::bitcoin::util::psbt::{self, PartiallySignedTransaction};
 use ::bitcoin::{OutPoint, TxIn, TxOut, Txid};
 use anyhow::{bail, Result};
 use bdk::database::BatchDatabase;
 use bdk::miniscript::{Descriptor, DescriptorTrait};
 use bitcoin::Script;
 use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
 
 const SCRIPT_SIZE: usize = 34;
 
@@ -99,6 +100,38 @@ impl TxLock {
         Amount::from_sat(self.inner.clone().extract_tx().output[self.lock_output_vout()].value)
     }
 
+    /// The outputs spent by the transaction.
+    pub fn funding(&self) -> Vec<OutPoint> {
+        self.inner
+            .global
+            .unsigned_tx
+            .input
+            .iter()
+            .map(|input| input.previous_output)
+            .collect()
+    }
+
+    /// The fee paid by the transaction, `None` if the PSBT does not include
+    /// the outputs spent by all of its inputs.
+    pub fn fee(&self) -> Option<Amount> {
+        let tx = &self.inner.global.unsigned_tx;
+
+        let spent = self
+            .inner
+            .inputs
+            .iter()
+            .zip(&tx.input)
+            .try_fold(0u64, |spent, (input, tx_in)| {
+                spent.checked_add(spent_value(input, tx_in)?)
+            })?;
+        let sent = tx
+            .output
+            .iter()
+            .try_fold(0u64, |sent, output| sent.checked_add(output.value))?;
+
+        spent.checked_sub(sent).map(Amount::from_sat)
+    }
+
     pub fn txid(&self) -> Txid {
         self.inner.clone().extract_tx().txid()
     }
@@ -148,7 +181,10 @@ impl TxLock {
 
         let fee = spending_fee.as_sat();
         let tx_out = TxOut {
-            value: self.inner.clone().extract_tx().output[self.lock_output_vout()].value - fee,
+            value: self.inner.clone().extract_tx().output[self.lock_output_vout()]
+                .value
+                .checked_sub(fee)
+                .expect("fees are checked against the lock amount during swap setup"),
             script_pubkey: spend_address.script_pubkey(),
         };
 
@@ -179,6 +215,18 @@ impl Watchable for TxLock {
     }
 }
 
+/// The value of the output spent by `tx_in`, if the PSBT input includes it.
+fn spent_value(input: &psbt::Input, tx_in: &TxIn) -> Option<u64> {
+    match (&input.witness_utxo, &input.non_witness_utxo) {
+        (Some(utxo), _) => Some(utxo.value),
+        (None, Some(previous_tx)) => {
+            let vout = usize::try_from(tx_in.previous_output.vout).ok()?;
+            Some(previous_tx.output.get(vout)?.value)
+        }
+        (None, None) => None,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -215,6 +263,21 @@ mod tests {
         result.expect("PSBT to be valid");
     }
 
+    #[tokio::test]
+    async fn fee_is_the_difference_of_inputs_and_outputs() {
+        let (A, B) = alice_and_bob();
+        let fees = 610;
+        let agreed_amount = Amount::from_sat(10000);
+        let wallet = WalletBuilder::new(agreed_amount.as_sat() + fees).build();
+        let change = wallet.new_address().await.unwrap();
+
+        let tx_lock = TxLock::new(&wallet, agreed_amount, A, B, change)
+            .await
+            .unwrap();
+
+        assert_eq!(tx_lock.fee(), Some(Amount::from_sat(fees)));
+    }
+
     #[tokio::test]
     async fn given_bob_is_sending_less_than_agreed_when_reconstructing_txlock_then_fails() {
         let (A, B) = alice_and_bob();
@@ -262,7 +325,7 @@ mod tests {
         amount: Amount,
     ) -> PartiallySignedTransaction {
         let change = wallet.new_address().await.unwrap();
-        TxLock::new(&wallet, amount, A, B, change)
+        TxLock::new(wallet, amount, A, B, change)
             .await
             .unwrap()
             .into()
@@ -132,7 +132,7 @@ impl TxRefund {
 
         let sig = sigs
             .into_iter()
-            .find(|sig| verify_sig(&B, &self.digest(), &sig).is_ok())
+            .find(|sig| verify_sig(&B, &self.digest(), sig).is_ok())
             .context("Neither signature on witness stack verifies against B")?;
 
         Ok(sig)
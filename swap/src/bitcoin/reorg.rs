@@ -0,0 +1,209 @@
+//! Detects reorgs of the Bitcoin chain.
+//!
+//! The Electrum server only tells us about the tip of its chain. A reorg that
+//! replaces the block a transaction was confirmed in would otherwise only show
+//! as the transaction having fewer confirmations, or none at all, on the next
+//! sync. [`Blocks`] remembers the hashes of the last [`KEPT_BLOCKS`] blocks and
+//! checks that every new tip builds on them.
+
+use ::bitcoin::{BlockHash, BlockHeader};
+use anyhow::Result;
+use std::collections::BTreeMap;
+
+/// Reorgs deeper than this are reported as this deep.
+const KEPT_BLOCKS: u32 = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Reorg {
+    /// The height of the first block that was replaced.
+    pub fork_height: u32,
+    /// How many blocks of the previous chain were replaced or dropped.
+    pub depth: u32,
+}
+
+/// The hashes of the latest blocks, by height.
+#[derive(Debug, Default)]
+pub struct Blocks {
+    hashes: BTreeMap<u32, BlockHash>,
+}
+
+impl Blocks {
+    /// Records `header` as the tip at `height`.
+    ///
+    /// If the tip does not build on the latest block we know, the headers below
+    /// it are fetched through `header_at` until the new chain joins ours.
+    pub fn connect(
+        &mut self,
+        height: u32,
+        header: BlockHeader,
+        mut header_at: impl FnMut(u32) -> Result<BlockHeader>,
+    ) -> Result<Option<Reorg>> {
+        let (oldest, old_tip) = match (self.hashes.keys().next(), self.hashes.keys().next_back()) {
+            (Some(oldest), Some(old_tip)) if height <= *old_tip + KEPT_BLOCKS => {
+                (*oldest, *old_tip)
+            }
+            // Nothing to compare with
+            _ => {
+                self.hashes.clear();
+                self.hashes.insert(height, header.block_hash());
+
+                return Ok(None);
+            }
+        };
+
+        if self.hashes.get(&height) == Some(&header.block_hash()) {
+            return Ok(None);
+        }
+
+        // Blocks above the new tip are no longer part of the chain
+        let dropped = self.hashes.split_off(&(height + 1));
+        let mut fork_height = dropped.keys().next().copied();
+
+        let (mut current_height, mut current) = (height, header);
+        loop {
+            let hash = current.block_hash();
+            if let Some(replaced) = self.hashes.insert(current_height, hash) {
+                if replaced == hash {
+                    break;
+                }
+                fork_height = Some(current_height);
+            }
+
+            if current_height <= oldest {
+                break;
+            }
+            if self.hashes.get(&(current_height - 1)) == Some(&current.prev_blockhash) {
+                break;
+            }
+
+            current_height -= 1;
+            current = header_at(current_height)?;
+        }
+
+        self.hashes = self
+            .hashes
+            .split_off(&height.saturating_sub(KEPT_BLOCKS - 1));
+
+        Ok(fork_height.map(|fork_height| Reorg {
+            fork_height,
+            depth: old_tip + 1 - fork_height,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::bitcoin::hashes::Hash;
+    use ::bitcoin::TxMerkleNode;
+    use anyhow::anyhow;
+    use std::convert::TryFrom;
+
+    const START: u32 = 700_000;
+
+    /// `length` blocks on top of `prev`, `nonce` tells the branches apart.
+    fn branch(prev: BlockHash, length: usize, nonce: u32) -> Vec<BlockHeader> {
+        let mut headers = Vec::<BlockHeader>::new();
+
+        for _ in 0..length {
+            let prev_blockhash = headers.last().map_or(prev, |header| header.block_hash());
+            headers.push(BlockHeader {
+                version: 1,
+                prev_blockhash,
+                merkle_root: TxMerkleNode::from_inner([0; 32]),
+                time: 0,
+                bits: 0,
+                nonce,
+            });
+        }
+
+        headers
+    }
+
+    fn height(index: usize) -> u32 {
+        START + u32::try_from(index).unwrap()
+    }
+
+    fn header_at(chain: &[BlockHeader]) -> impl FnMut(u32) -> Result<BlockHeader> + '_ {
+        move |height| {
+            chain
+                .get((height - START) as usize)
+                .copied()
+                .ok_or_else(|| anyhow!("No block at height {}", height))
+        }
+    }
+
+    fn connect_all(blocks: &mut Blocks, chain: &[BlockHeader]) {
+        for (index, header) in chain.iter().enumerate() {
+            let reorg = blocks
+                .connect(height(index), *header, header_at(chain))
+                .unwrap();
+
+            assert_eq!(reorg, None);
+        }
+    }
+
+    #[test]
+    fn extending_the_chain_is_no_reorg() {
+        let chain = branch(BlockHash::from_inner([0; 32]), 10, 0);
+        let mut blocks = Blocks::default();
+
+        connect_all(&mut blocks, &chain);
+    }
+
+    #[test]
+    fn skipped_blocks_are_fetched() {
+        let chain = branch(BlockHash::from_inner([0; 32]), 10, 0);
+        let mut blocks = Blocks::default();
+        connect_all(&mut blocks, &chain[..2]);
+
+        let reorg = blocks
+            .connect(height(9), chain[9], header_at(&chain))
+            .unwrap();
+
+        assert_eq!(reorg, None);
+    }
+
+    #[test]
+    fn replaced_blocks_are_a_reorg() {
+        let chain = branch(BlockHash::from_inner([0; 32]), 6, 0);
+        let mut blocks = Blocks::default();
+        connect_all(&mut blocks, &chain);
+
+        let mut fork = chain[..4].to_vec();
+        fork.extend(branch(chain[3].block_hash(), 2, 1));
+        let reorg = blocks
+            .connect(height(5), fork[5], header_at(&fork))
+            .unwrap();
+
+        assert_eq!(
+            reorg,
+            Some(Reorg {
+                fork_height: height(4),
+                depth: 2
+            })
+        );
+        connect_all(&mut blocks, &fork);
+    }
+
+    #[test]
+    fn shorter_chain_is_a_reorg() {
+        let chain = branch(BlockHash::from_inner([0; 32]), 6, 0);
+        let mut blocks = Blocks::default();
+        connect_all(&mut blocks, &chain);
+
+        let mut fork = chain[..4].to_vec();
+        fork.extend(branch(chain[3].block_hash(), 1, 1));
+        let reorg = blocks
+            .connect(height(4), fork[4], header_at(&fork))
+            .unwrap();
+
+        assert_eq!(
+            reorg,
+            Some(Reorg {
+                fork_height: height(4),
+                depth: 2
+            })
+        );
+    }
+}
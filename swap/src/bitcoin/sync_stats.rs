@@ -0,0 +1,89 @@
+//! How long syncing the balance of the Bitcoin wallet takes.
+//!
+//! The wallet only syncs once one of its scripts changed or a block was mined
+//! since its last sync, [`SyncStats`] counts the syncs that ran and the ones
+//! that were skipped. A sync that takes longer than [`SYNC_BUDGET`] is logged
+//! and counted, it delays whatever protocol step waits for the balance.
+
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+/// How long a sync may take before it is logged as slow.
+pub const SYNC_BUDGET: Duration = Duration::from_secs(10);
+
+/// How often the wallet was synced since the start and how long it took.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct SyncCounts {
+    pub synced: u64,
+    /// Syncs that were skipped because nothing changed since the last one.
+    pub skipped: u64,
+    pub over_budget: u64,
+    pub last_duration_ms: u64,
+    pub max_duration_ms: u64,
+}
+
+/// A cheaply cloneable handle to the sync counts of a wallet.
+#[derive(Debug, Clone, Default)]
+pub struct SyncStats {
+    inner: Arc<Mutex<SyncCounts>>,
+}
+
+impl SyncStats {
+    /// Records a sync that took `duration`, returns whether it exceeded the
+    /// [`SYNC_BUDGET`].
+    pub fn synced(&self, duration: Duration) -> bool {
+        let mut counts = self.lock();
+        let duration_ms = u64::try_from(duration.as_millis()).unwrap_or(u64::MAX);
+
+        counts.synced += 1;
+        counts.last_duration_ms = duration_ms;
+        counts.max_duration_ms = counts.max_duration_ms.max(duration_ms);
+
+        let over_budget = duration > SYNC_BUDGET;
+        if over_budget {
+            counts.over_budget += 1;
+        }
+
+        over_budget
+    }
+
+    pub fn skipped(&self) {
+        self.lock().skipped += 1;
+    }
+
+    pub fn counts(&self) -> SyncCounts {
+        *self.lock()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, SyncCounts> {
+        self.inner.lock().expect("sync stats lock not poisoned")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn syncs_over_budget_are_counted() {
+        let stats = SyncStats::default();
+
+        assert!(!stats.synced(Duration::from_millis(1500)));
+        assert!(stats.synced(SYNC_BUDGET + Duration::from_secs(2)));
+        assert!(!stats.synced(Duration::from_millis(200)));
+        stats.skipped();
+
+        assert_eq!(
+            stats.counts(),
+            SyncCounts {
+                synced: 3,
+                skipped: 1,
+                over_budget: 1,
+                last_duration_ms: 200,
+                max_duration_ms: 12_000,
+            }
+        );
+    }
+}
@@ -17,6 +17,12 @@ impl From<BlockHeight> for u32 {
     }
 }
 
+impl From<u32> for BlockHeight {
+    fn from(height: u32) -> Self {
+        Self(height)
+    }
+}
+
 impl TryFrom<HeaderNotification> for BlockHeight {
     type Error = anyhow::Error;
 
@@ -37,7 +43,8 @@ impl Add<u32> for BlockHeight {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum ExpiredTimelocks {
     None,
     Cancel,
@@ -1,27 +1,31 @@
+use crate::bitcoin::backends::Backends;
+use crate::bitcoin::bitcoind::{self, Bitcoind};
+use crate::bitcoin::reorg::Blocks;
 use crate::bitcoin::timelocks::BlockHeight;
-use crate::bitcoin::{Address, Amount, Transaction};
+use crate::bitcoin::{Address, Amount, SyncStats, Transaction, SYNC_BUDGET};
 use crate::env;
+use crate::progress::{Chain, Confirmations, Progress};
 use ::bitcoin::util::psbt::PartiallySignedTransaction;
 use ::bitcoin::Txid;
 use anyhow::{bail, Context, Result};
-use bdk::blockchain::{noop_progress, Blockchain, ElectrumBlockchain};
-use bdk::database::BatchDatabase;
+use bdk::blockchain::{noop_progress, Capability, ElectrumBlockchain};
+use bdk::database::{BatchDatabase, Database};
 use bdk::descriptor::Segwitv0;
 use bdk::electrum_client::{ElectrumApi, GetHistoryRes};
 use bdk::keys::DerivableKey;
 use bdk::wallet::export::WalletExport;
 use bdk::wallet::AddressIndex;
 use bdk::{FeeRate, KeychainKind, SignOptions};
-use bitcoin::{Network, Script};
+use bitcoin::{BlockHeader, Network, OutPoint, Script};
 use reqwest::Url;
 use rust_decimal::prelude::*;
 use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::convert::TryFrom;
 use std::fmt;
 use std::path::Path;
-use std::sync::Arc;
+use std::sync::{Arc, Weak};
 use std::time::{Duration, Instant};
 use tokio::sync::{watch, Mutex};
 
@@ -33,49 +37,136 @@ const MAX_RELATIVE_TX_FEE: Decimal = dec!(0.03);
 const MAX_ABSOLUTE_TX_FEE: Decimal = dec!(100_000);
 const DUST_AMOUNT: u64 = 546;
 
-pub struct Wallet<B = ElectrumBlockchain, D = bdk::sled::Tree, C = Client> {
+/// How often to check for notifications pushed by the Electrum server, or to
+/// poll bitcoind for new blocks and transactions.
+const BLOCK_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How often to compare the tip heights of the Electrum servers, if there is
+/// more than one.
+const BACKEND_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Connects the bdk wallet to another Electrum server after a failover.
+type Reconnect<B, D> = dyn Fn(&Url) -> Result<bdk::Wallet<B, D>> + Send + Sync;
+
+pub struct Wallet<B = Blockchain, D = bdk::sled::Tree, C = Client> {
     client: Arc<Mutex<C>>,
     wallet: Arc<Mutex<bdk::Wallet<B, D>>>,
+    reconnect: Arc<Reconnect<B, D>>,
+    /// The database of `wallet`, which bdk 0.12 does not give access to.
+    /// Test wallets do not keep it.
+    database: Option<bdk::sled::Tree>,
+    sync_stats: SyncStats,
+    progress: Progress,
     finality_confirmations: u32,
     network: Network,
     target_block: usize,
 }
 
 impl Wallet {
+    /// Opens the wallet with the given Electrum servers.
+    ///
+    /// The wallet uses the first of the servers that is in sync, the others
+    /// take over if it becomes unavailable or lags behind.
     pub async fn new(
-        electrum_rpc_url: Url,
+        electrum_rpc_urls: Vec<Url>,
         wallet_dir: &Path,
-        key: impl DerivableKey<Segwitv0> + Clone,
+        key: impl DerivableKey<Segwitv0> + Clone + Send + Sync + 'static,
         env_config: env::Config,
         target_block: usize,
     ) -> Result<Self> {
-        let config = bdk::electrum_client::ConfigBuilder::default()
-            .retry(5)
-            .build();
-        let client = bdk::electrum_client::Client::from_config(electrum_rpc_url.as_str(), config)
-            .context("Failed to initialize Electrum RPC client")?;
+        let mut backends = Backends::new(electrum_rpc_urls)?;
+        let electrum = backends.connect()?;
 
         let db = bdk::sled::open(wallet_dir)?.open_tree(SLED_TREE_NAME)?;
+        let database = db.clone();
+        let network = env_config.bitcoin_network;
+
+        let reconnect = move |electrum_rpc_url: &Url| -> Result<bdk::Wallet<_, _>> {
+            let config = bdk::electrum_client::ConfigBuilder::default()
+                .retry(5)
+                .build();
+            let client =
+                bdk::electrum_client::Client::from_config(electrum_rpc_url.as_str(), config)
+                    .context("Failed to initialize Electrum RPC client")?;
+
+            let wallet = bdk::Wallet::new(
+                bdk::template::Bip84(key.clone(), KeychainKind::External),
+                Some(bdk::template::Bip84(key.clone(), KeychainKind::Internal)),
+                network,
+                db.clone(),
+                Blockchain::Electrum(ElectrumBlockchain::from(client)),
+            )?;
+
+            Ok(wallet)
+        };
+        let wallet = reconnect(backends.current())?;
+
+        let source = Source::Electrum {
+            electrum,
+            backends,
+            last_backend_check: Instant::now(),
+        };
+        let client = Arc::new(Mutex::new(Client::new(
+            source,
+            env_config.bitcoin_sync_interval(),
+        )?));
+        tokio::spawn(watch_blocks(Arc::downgrade(&client)));
+
+        Ok(Self {
+            client,
+            wallet: Arc::new(Mutex::new(wallet)),
+            reconnect: Arc::new(reconnect),
+            database: Some(database),
+            sync_stats: SyncStats::default(),
+            progress: Progress::default(),
+            finality_confirmations: env_config.bitcoin_finality_confirmations,
+            network,
+            target_block,
+        })
+    }
+
+    /// Opens the wallet with a bitcoind node instead of Electrum servers, see
+    /// the [`bitcoind`] module for how the node tracks the wallet.
+    pub async fn with_bitcoind(
+        config: bitcoind::Config,
+        wallet_dir: &Path,
+        key: impl DerivableKey<Segwitv0> + Clone + Send + Sync + 'static,
+        env_config: env::Config,
+        target_block: usize,
+    ) -> Result<Self> {
+        let network = env_config.bitcoin_network;
+        let bitcoind = Bitcoind::connect(&config, network)?;
+
+        let db = bdk::sled::open(wallet_dir)?.open_tree(SLED_TREE_NAME)?;
+        let database = db.clone();
 
         let wallet = bdk::Wallet::new(
             bdk::template::Bip84(key.clone(), KeychainKind::External),
             Some(bdk::template::Bip84(key, KeychainKind::Internal)),
-            env_config.bitcoin_network,
+            network,
             db,
-            ElectrumBlockchain::from(client),
+            Blockchain::Bitcoind(bitcoind::Blockchain::new(Bitcoind::connect(
+                &config, network,
+            )?)),
         )?;
+        // There is a single node, `sync_or_failover` never reconnects
+        let reconnect = |_: &Url| -> Result<bdk::Wallet<_, _>> {
+            bail!("Cannot fail over to another bitcoind")
+        };
 
-        let electrum = bdk::electrum_client::Client::new(electrum_rpc_url.as_str())
-            .context("Failed to initialize Electrum RPC client")?;
-
-        let network = wallet.network();
+        let client = Arc::new(Mutex::new(Client::new(
+            Source::Bitcoind(bitcoind),
+            env_config.bitcoin_sync_interval(),
+        )?));
+        tokio::spawn(watch_blocks(Arc::downgrade(&client)));
 
         Ok(Self {
-            client: Arc::new(Mutex::new(Client::new(
-                electrum,
-                env_config.bitcoin_sync_interval(),
-            )?)),
+            client,
             wallet: Arc::new(Mutex::new(wallet)),
+            reconnect: Arc::new(reconnect),
+            database: Some(database),
+            sync_stats: SyncStats::default(),
+            progress: Progress::default(),
             finality_confirmations: env_config.bitcoin_finality_confirmations,
             network,
             target_block,
@@ -99,10 +190,10 @@ impl Wallet {
             .subscribe_to((txid, transaction.output[0].script_pubkey.clone()))
             .await;
 
-        self.wallet
+        self.client
             .lock()
             .await
-            .broadcast(transaction)
+            .broadcast(&transaction)
             .with_context(|| {
                 format!("Failed to broadcast Bitcoin {} transaction {}", kind, txid)
             })?;
@@ -112,12 +203,160 @@ impl Wallet {
         Ok((txid, subscription))
     }
 
+    pub async fn get_tx(&self, txid: Txid) -> Result<Option<Transaction>> {
+        let tx = self.client.lock().await.get_tx(txid)?;
+
+        Ok(Some(tx))
+    }
+
+    /// Syncs the balance if a script of the wallet changed or a block was
+    /// mined since the last sync, on failure the sync is retried once with the
+    /// Electrum server the wallet failed over to.
+    ///
+    /// The scripts of the wallet are watched, so the syncs in between protocol
+    /// steps are skipped unless something happened. A script that is not
+    /// watched yet, e.g. of a new address, is synced once.
+    pub async fn sync(&self) -> Result<()> {
+        let mut wallet = self.wallet.lock().await;
+
+        let scripts = self.wallet_scripts()?;
+        let mut client = self.client.lock().await;
+        let newly_watched = client.watch_wallet_scripts(scripts);
+        let changed = client.take_wallet_changed() || newly_watched;
+        drop(client);
+
+        if !changed {
+            self.sync_stats.skipped();
+            return Ok(());
+        }
+
+        let started = Instant::now();
+        if let Err(error) = self.sync_or_failover(&mut wallet).await {
+            self.client.lock().await.wallet_changed = true;
+            return Err(error);
+        }
+        let duration = started.elapsed();
+
+        if self.sync_stats.synced(duration) {
+            tracing::warn!(
+                seconds = duration.as_secs(),
+                "Syncing the Bitcoin wallet took longer than its budget of {} seconds",
+                SYNC_BUDGET.as_secs()
+            );
+        }
+
+        // Syncing derives new addresses once the last ones were used, bitcoind only
+        // knows the transactions of scripts imported before the sync
+        let scripts = self.wallet_scripts()?;
+        let mut client = self.client.lock().await;
+        let resync = client.watch_wallet_scripts(scripts)
+            && client.is_bitcoind()
+            && client.take_wallet_changed();
+        drop(client);
+
+        if resync {
+            self.sync_or_failover(&mut wallet).await?;
+        }
+
+        Ok(())
+    }
+
+    fn wallet_scripts(&self) -> Result<Vec<Script>> {
+        let database = self
+            .database
+            .as_ref()
+            .context("Bitcoin wallet has no database")?;
+
+        database
+            .iter_script_pubkeys(None)
+            .context("Failed to list scripts of Bitcoin wallet")
+    }
+
+    async fn sync_or_failover(
+        &self,
+        wallet: &mut bdk::Wallet<Blockchain, bdk::sled::Tree>,
+    ) -> Result<()> {
+        let error = match wallet.sync(noop_progress(), None) {
+            Ok(()) => return Ok(()),
+            Err(error) => error,
+        };
+
+        let mut client = self.client.lock().await;
+        if !client.has_fallbacks() {
+            return Err(error).context("Failed to sync balance of Bitcoin wallet");
+        }
+
+        tracing::debug!("Failed to sync balance of Bitcoin wallet: {:#}", error);
+        client.check_backends()?;
+        *wallet = (self.reconnect)(&client.url())?;
+        drop(client);
+
+        wallet
+            .sync(noop_progress(), None)
+            .context("Failed to sync balance of Bitcoin wallet")?;
+
+        Ok(())
+    }
+
+    /// A handle to the counts of the syncs of this wallet.
+    pub fn sync_stats(&self) -> SyncStats {
+        self.sync_stats.clone()
+    }
+
+    /// Where the subscriptions of the wallet report the confirmations they
+    /// are waited for.
+    pub fn progress(&self) -> Progress {
+        self.progress.clone()
+    }
+
+    /// The Electrum server or bitcoind node the wallet currently uses.
+    pub async fn backend_url(&self) -> Url {
+        self.client.lock().await.url()
+    }
+
     pub async fn get_raw_transaction(&self, txid: Txid) -> Result<Transaction> {
         self.get_tx(txid)
             .await?
             .with_context(|| format!("Could not get raw tx with id: {}", txid))
     }
 
+    /// Bumps the fee of the unconfirmed transaction `txid` by publishing a
+    /// child that spends its outputs to the wallet, see
+    /// [`Wallet::child_pays_for_parent`].
+    ///
+    /// Returns the ID of the child transaction.
+    pub async fn cpfp(&self, txid: Txid) -> Result<Txid> {
+        let parent = self.get_raw_transaction(txid).await?;
+
+        let mut spent = Amount::ZERO;
+        for input in &parent.input {
+            let previous = input.previous_output;
+            let previous_tx = self.get_raw_transaction(previous.txid).await?;
+            let txout = previous_tx
+                .output
+                .get(usize::try_from(previous.vout)?)
+                .with_context(|| {
+                    format!(
+                        "Transaction {} has no output {}",
+                        previous.txid, previous.vout
+                    )
+                })?;
+            spent += Amount::from_sat(txout.value);
+        }
+        let sent = Amount::from_sat(parent.output.iter().map(|txout| txout.value).sum());
+        let parent_fee = spent
+            .checked_sub(sent)
+            .with_context(|| format!("Transaction {} spends more than its inputs", txid))?;
+
+        // The outputs of the parent are only known to the wallet once synced
+        self.sync().await?;
+        let psbt = self.child_pays_for_parent(&parent, parent_fee).await?;
+        let child = self.sign_and_finalize(psbt).await?;
+        let (child_txid, _) = self.broadcast(child, "cpfp").await?;
+
+        Ok(child_txid)
+    }
+
     pub async fn status_of_script<T>(&self, tx: &T) -> Result<ScriptStatus>
     where
         T: Watchable,
@@ -125,14 +364,25 @@ impl Wallet {
         self.client.lock().await.status_of_script(tx)
     }
 
+    /// Whether the transaction has the finality confirmations right now, which
+    /// a reorg can take back.
+    pub async fn is_final<T>(&self, tx: &T) -> Result<bool>
+    where
+        T: Watchable,
+    {
+        let status = self.status_of_script(tx).await?;
+
+        Ok(status.is_confirmed_with(self.finality_confirmations))
+    }
+
     pub async fn subscribe_to(&self, tx: impl Watchable + Send + 'static) -> Subscription {
         let txid = tx.id();
         let script = tx.script();
 
-        let sub = self
-            .client
-            .lock()
-            .await
+        let mut client = self.client.lock().await;
+        let mut blocks = client.block_heights.clone();
+
+        let sub = client
             .subscriptions
             .entry((txid, script.clone()))
             .or_insert_with(|| {
@@ -143,7 +393,12 @@ impl Wallet {
                     let mut last_status = None;
 
                     loop {
-                        tokio::time::sleep(Duration::from_secs(5)).await;
+                        // Confirmations only change with a new block, but a transaction can
+                        // enter the mempool at any time
+                        tokio::select! {
+                            _ = tokio::time::sleep(Duration::from_secs(5)) => {}
+                            _ = blocks.changed() => {}
+                        }
 
                         let new_status = match client.lock().await.status_of_script(&tx) {
                             Ok(new_status) => new_status,
@@ -152,16 +407,18 @@ impl Wallet {
                                 ScriptStatus::Retrying
                             }
                         };
-                        
+
                         if new_status != ScriptStatus::Retrying
                         {
                             last_status = Some(print_status_change(txid, last_status, new_status));
 
-                            let all_receivers_gone = sender.send(new_status).is_err();
-    
+                            // The subscription kept by the client holds a receiver itself
+                            let all_receivers_gone =
+                                sender.send(new_status).is_err() || sender.receiver_count() <= 1;
+
                             if all_receivers_gone {
                                 tracing::debug!(%txid, "All receivers gone, removing subscription");
-                                client.lock().await.subscriptions.remove(&(txid, script));
+                                client.lock().await.unwatch(txid, script);
                                 return;
                             }
                         }
@@ -172,6 +429,7 @@ impl Wallet {
                     receiver,
                     finality_confirmations: self.finality_confirmations,
                     txid,
+                    progress: self.progress.clone(),
                 }
             })
             .clone();
@@ -190,6 +448,44 @@ impl Wallet {
             Err(err_msg) => Err(anyhow::Error::msg(err_msg)),
         }
     }
+
+    /// Returns a receiver that is notified with the height of every new block.
+    pub async fn subscribe_to_blocks(&self) -> watch::Receiver<BlockHeight> {
+        self.client.lock().await.block_heights.clone()
+    }
+
+    /// Returns the height of the latest block known to the backend.
+    pub async fn latest_block_height(&self) -> Result<BlockHeight> {
+        let (height, _) = self.client.lock().await.source.tip()?;
+
+        Ok(height)
+    }
+
+    /// Returns the timestamp of the latest block known to the backend in
+    /// seconds since the unix epoch.
+    pub async fn latest_block_timestamp(&self) -> Result<u32> {
+        let (_, header) = self.client.lock().await.source.tip()?;
+
+        Ok(header.time)
+    }
+}
+
+/// Checks for new blocks and changes to watched scripts until the wallet is
+/// dropped.
+async fn watch_blocks(client: Weak<Mutex<Client>>) {
+    loop {
+        tokio::time::sleep(BLOCK_POLL_INTERVAL).await;
+
+        let client = match client.upgrade() {
+            Some(client) => client,
+            None => return,
+        };
+
+        let result = client.lock().await.poll_notifications();
+        if let Err(error) = result {
+            tracing::debug!("Failed to check for notifications: {:#}", error);
+        }
+    }
 }
 
 fn print_status_change(txid: Txid, old: Option<ScriptStatus>, new: ScriptStatus) -> ScriptStatus {
@@ -212,6 +508,7 @@ pub struct Subscription {
     receiver: watch::Receiver<ScriptStatus>,
     finality_confirmations: u32,
     txid: Txid,
+    progress: Progress,
 }
 
 impl Subscription {
@@ -222,50 +519,95 @@ impl Subscription {
         tracing::info!(%txid, required_confirmation=%conf_target, "Waiting for Bitcoin transaction finality");
 
         let mut seen_confirmations = 0;
+        let mut report = self.reporter(conf_target);
 
-        self.wait_until(|status| match status {
-            ScriptStatus::Confirmed(inner) => {
-                let confirmations = inner.confirmations();
-
-                if confirmations > seen_confirmations {
-                    tracing::info!(%txid,
-                        seen_confirmations = %confirmations,
-                        needed_confirmations = %conf_target,
-                        "Waiting for Bitcoin transaction finality");
-                    seen_confirmations = confirmations;
-                }
+        self.wait_until(|status| {
+            report(status);
+
+            match status {
+                ScriptStatus::Confirmed(inner) => {
+                    let confirmations = inner.confirmations();
 
-                inner.meets_target(conf_target)
+                    if confirmations > seen_confirmations {
+                        tracing::info!(%txid,
+                            seen_confirmations = %confirmations,
+                            needed_confirmations = %conf_target,
+                            "Waiting for Bitcoin transaction finality");
+                        seen_confirmations = confirmations;
+                    }
+
+                    inner.meets_target(conf_target)
+                }
+                _ => false,
             }
-            _ => false,
         })
         .await
+        .map(|_| ())
     }
 
-    pub async fn wait_until_seen(&self) -> Result<()> {
+    /// Waits until the transaction is in the mempool or in a block.
+    ///
+    /// The returned status tells whether the transaction was seen unconfirmed
+    /// or was already confirmed when it was first noticed.
+    pub async fn wait_until_seen(&self) -> Result<ScriptStatus> {
         self.wait_until(ScriptStatus::has_been_seen).await
     }
 
     pub async fn wait_until_confirmed_with<T>(&self, target: T) -> Result<()>
     where
         u32: PartialOrd<T>,
-        T: Copy,
+        T: Copy + Into<u32>,
     {
-        self.wait_until(|status| status.is_confirmed_with(target))
-            .await
+        let mut report = self.reporter(target.into());
+
+        self.wait_until(|status| {
+            report(status);
+            status.is_confirmed_with(target)
+        })
+        .await
+        .map(|_| ())
+    }
+
+    /// Reports the confirmations of every status that has more of them than
+    /// the previous one.
+    fn reporter(&self, needed: u32) -> impl FnMut(&ScriptStatus) + '_ {
+        let mut reported = None;
+
+        move |status| {
+            let seen = match status {
+                ScriptStatus::Confirmed(inner) => inner.confirmations(),
+                _ => 0,
+            };
+
+            if reported.map_or(true, |reported| seen > reported) {
+                reported = Some(seen);
+                self.progress.report(Confirmations {
+                    chain: Chain::Bitcoin,
+                    txid: self.txid.to_string(),
+                    seen: seen.into(),
+                    needed: needed.into(),
+                });
+            }
+        }
     }
 
-    async fn wait_until(&self, mut predicate: impl FnMut(&ScriptStatus) -> bool) -> Result<()> {
+    async fn wait_until(
+        &self,
+        mut predicate: impl FnMut(&ScriptStatus) -> bool,
+    ) -> Result<ScriptStatus> {
         let mut receiver = self.receiver.clone();
 
-        while !predicate(&receiver.borrow()) {
+        loop {
+            let status = *receiver.borrow();
+            if predicate(&status) {
+                return Ok(status);
+            }
+
             receiver
                 .changed()
                 .await
                 .context("Failed while waiting for next status update")?;
         }
-
-        Ok(())
     }
 }
 
@@ -304,6 +646,28 @@ where
         Ok(Amount::from_sat(balance))
     }
 
+    /// The part of the balance that is held in unconfirmed outputs.
+    pub async fn unconfirmed_balance(&self) -> Result<Amount> {
+        let wallet = self.wallet.lock().await;
+
+        let unconfirmed = wallet
+            .list_transactions(false)
+            .context("Failed to list Bitcoin transactions")?
+            .into_iter()
+            .filter(|tx| tx.confirmation_time.is_none())
+            .map(|tx| tx.txid)
+            .collect::<HashSet<_>>();
+        let balance = wallet
+            .list_unspent()
+            .context("Failed to list unspent Bitcoin outputs")?
+            .into_iter()
+            .filter(|utxo| unconfirmed.contains(&utxo.outpoint.txid))
+            .map(|utxo| utxo.txout.value)
+            .sum();
+
+        Ok(Amount::from_sat(balance))
+    }
+
     pub async fn new_address(&self) -> Result<Address> {
         let address = self
             .wallet
@@ -391,6 +755,80 @@ where
         Ok(psbt)
     }
 
+    /// Builds a transaction spending the outputs of the unconfirmed `parent`
+    /// that belong to the wallet back to the wallet. Its fee is high enough for
+    /// parent and child to confirm within the target block together.
+    ///
+    /// Only transactions paying to the wallet can be bumped this way. For
+    /// example Alice can bump her redeem transaction, but not Bob's lock
+    /// transaction whose outputs are the shared lock output and Bob's change.
+    pub async fn child_pays_for_parent(
+        &self,
+        parent: &Transaction,
+        parent_fee: Amount,
+    ) -> Result<PartiallySignedTransaction> {
+        let txid = parent.txid();
+        let wallet = self.wallet.lock().await;
+
+        let mut outpoints = Vec::new();
+        for (vout, txout) in parent.output.iter().enumerate() {
+            if wallet.is_mine(&txout.script_pubkey)? {
+                outpoints.push(OutPoint::new(txid, u32::try_from(vout)?));
+            }
+        }
+        if outpoints.is_empty() {
+            bail!(
+                "None of the outputs of transaction {} belongs to the wallet, it cannot be bumped",
+                txid
+            )
+        }
+
+        let fee_rate = self
+            .client
+            .lock()
+            .await
+            .estimate_feerate(self.target_block)?;
+        let drain_script = wallet
+            .get_address(AddressIndex::New)
+            .context("Failed to get new Bitcoin address")?
+            .address
+            .script_pubkey();
+
+        let build = |fee: Option<Amount>| -> Result<_> {
+            let mut tx_builder = wallet.build_tx();
+            for outpoint in &outpoints {
+                tx_builder.add_utxo(*outpoint)?;
+            }
+            tx_builder.manually_selected_only();
+            tx_builder.drain_to(drain_script.clone());
+            match fee {
+                Some(fee) => tx_builder.fee_absolute(fee.as_sat()),
+                None => tx_builder.fee_rate(fee_rate),
+            };
+
+            Ok(tx_builder.finish()?)
+        };
+
+        // The fee the child pays for itself at the target fee rate tells its size
+        let (_, details) = build(None)?;
+        let child_fee = Amount::from_sat(
+            details
+                .fee
+                .expect("fees are always present with Electrum backend"),
+        );
+        let fee = cpfp_fee(parent.get_weight(), parent_fee, child_fee, fee_rate)?
+            .with_context(|| format!("Transaction {} already pays the target fee rate", txid))?;
+
+        let (psbt, _) = build(Some(fee)).with_context(|| {
+            format!(
+                "Outputs of transaction {} do not cover a fee of {}",
+                txid, fee
+            )
+        })?;
+
+        Ok(psbt)
+    }
+
     /// Calculates the maximum "giveable" amount of this wallet.
     ///
     /// We define this as the maximum amount we can pay to a single output,
@@ -438,8 +876,8 @@ where
     pub async fn estimate_fee(
         &self,
         weight: usize,
-        transfer_amount: bitcoin::Amount,
-    ) -> Result<bitcoin::Amount> {
+        transfer_amount: Amount,
+    ) -> Result<Amount> {
         let client = self.client.lock().await;
         let fee_rate = client.estimate_feerate(self.target_block)?;
         let min_relay_fee = client.min_relay_fee()?;
@@ -512,32 +950,34 @@ fn estimate_fee(
         sats_per_vbyte.to_u64()
     };
     let amount = recommended_fee
-        .map(bitcoin::Amount::from_sat)
+        .map(Amount::from_sat)
         .context("Could not estimate tranasction fee.")?;
 
     Ok(amount)
 }
 
-impl<B, D, C> Wallet<B, D, C>
-where
-    B: Blockchain,
-    D: BatchDatabase,
-{
-    pub async fn get_tx(&self, txid: Txid) -> Result<Option<Transaction>> {
-        let tx = self.wallet.lock().await.client().get_tx(&txid)?;
-
-        Ok(tx)
-    }
-
-    pub async fn sync(&self) -> Result<()> {
-        self.wallet
-            .lock()
-            .await
-            .sync(noop_progress(), None)
-            .context("Failed to sync balance of Bitcoin wallet")?;
-
-        Ok(())
-    }
+/// The fee a child has to pay for the package of itself and its parent to
+/// reach `fee_rate`. `child_fee` is what the child pays on its own at
+/// `fee_rate`.
+///
+/// Returns `None` if the parent pays `fee_rate` on its own.
+fn cpfp_fee(
+    parent_weight: usize,
+    parent_fee: Amount,
+    child_fee: Amount,
+    fee_rate: FeeRate,
+) -> Result<Option<Amount>> {
+    let parent_vbytes = Decimal::from(parent_weight) / dec!(4.0);
+    let fee_rate = Decimal::from_f32(fee_rate.as_sat_vb()).context("Failed to parse fee rate")?;
+    let parent_target_fee = (parent_vbytes * fee_rate)
+        .ceil()
+        .to_u64()
+        .context("Failed to calculate the fee of the parent at the target fee rate")?;
+
+    Ok(match parent_target_fee.checked_sub(parent_fee.as_sat()) {
+        Some(missing) if missing > 0 => Some(child_fee + Amount::from_sat(missing)),
+        _ => None,
+    })
 }
 
 impl<B, D, C> Wallet<B, D, C> {
@@ -549,27 +989,27 @@ impl<B, D, C> Wallet<B, D, C> {
 
 pub trait EstimateFeeRate {
     fn estimate_feerate(&self, target_block: usize) -> Result<FeeRate>;
-    fn min_relay_fee(&self) -> Result<bitcoin::Amount>;
+    fn min_relay_fee(&self) -> Result<Amount>;
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "test-utils"))]
 pub struct StaticFeeRate {
     fee_rate: FeeRate,
-    min_relay_fee: bitcoin::Amount,
+    min_relay_fee: Amount,
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "test-utils"))]
 impl EstimateFeeRate for StaticFeeRate {
     fn estimate_feerate(&self, _target_block: usize) -> Result<FeeRate> {
         Ok(self.fee_rate)
     }
 
-    fn min_relay_fee(&self) -> Result<bitcoin::Amount> {
+    fn min_relay_fee(&self) -> Result<Amount> {
         Ok(self.min_relay_fee)
     }
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "test-utils"))]
 pub struct WalletBuilder {
     utxo_amount: u64,
     sats_per_vb: f32,
@@ -578,7 +1018,8 @@ pub struct WalletBuilder {
     num_utxos: u8,
 }
 
-#[cfg(test)]
+#[cfg(any(test, feature = "test-utils"))]
+#[allow(clippy::unwrap_used)]
 impl WalletBuilder {
     /// Creates a new, funded wallet with sane default fees.
     ///
@@ -645,9 +1086,15 @@ impl WalletBuilder {
         Wallet {
             client: Arc::new(Mutex::new(StaticFeeRate {
                 fee_rate: FeeRate::from_sat_per_vb(self.sats_per_vb),
-                min_relay_fee: bitcoin::Amount::from_sat(self.min_relay_fee_sats),
+                min_relay_fee: Amount::from_sat(self.min_relay_fee_sats),
             })),
             wallet: Arc::new(Mutex::new(wallet)),
+            reconnect: Arc::new(|_: &Url| -> Result<bdk::Wallet<(), MemoryDatabase>> {
+                bail!("Test wallets are offline")
+            }),
+            database: None,
+            sync_stats: SyncStats::default(),
+            progress: Progress::default(),
             finality_confirmations: 1,
             network: Network::Regtest,
             target_block: 1,
@@ -676,33 +1123,189 @@ impl Watchable for (Txid, Script) {
     }
 }
 
+/// The bdk blockchain of the wallet, backed by the same Electrum servers or
+/// bitcoind node as its [`Client`].
+pub enum Blockchain {
+    Electrum(ElectrumBlockchain),
+    Bitcoind(bitcoind::Blockchain),
+}
+
+impl bdk::blockchain::Blockchain for Blockchain {
+    fn get_capabilities(&self) -> HashSet<Capability> {
+        match self {
+            Blockchain::Electrum(electrum) => electrum.get_capabilities(),
+            Blockchain::Bitcoind(bitcoind) => bitcoind.get_capabilities(),
+        }
+    }
+
+    fn setup<D: BatchDatabase, P: 'static + bdk::blockchain::Progress>(
+        &self,
+        database: &mut D,
+        progress: P,
+    ) -> Result<(), bdk::Error> {
+        match self {
+            Blockchain::Electrum(electrum) => electrum.setup(database, progress),
+            Blockchain::Bitcoind(bitcoind) => bitcoind.setup(database, progress),
+        }
+    }
+
+    fn sync<D: BatchDatabase, P: 'static + bdk::blockchain::Progress>(
+        &self,
+        database: &mut D,
+        progress: P,
+    ) -> Result<(), bdk::Error> {
+        match self {
+            Blockchain::Electrum(electrum) => electrum.sync(database, progress),
+            Blockchain::Bitcoind(bitcoind) => bitcoind.sync(database, progress),
+        }
+    }
+
+    fn get_tx(&self, txid: &Txid) -> Result<Option<Transaction>, bdk::Error> {
+        match self {
+            Blockchain::Electrum(electrum) => electrum.get_tx(txid),
+            Blockchain::Bitcoind(bitcoind) => bitcoind.get_tx(txid),
+        }
+    }
+
+    fn broadcast(&self, tx: &Transaction) -> Result<(), bdk::Error> {
+        match self {
+            Blockchain::Electrum(electrum) => electrum.broadcast(tx),
+            Blockchain::Bitcoind(bitcoind) => bitcoind.broadcast(tx),
+        }
+    }
+
+    fn get_height(&self) -> Result<u32, bdk::Error> {
+        match self {
+            Blockchain::Electrum(electrum) => electrum.get_height(),
+            Blockchain::Bitcoind(bitcoind) => bitcoind.get_height(),
+        }
+    }
+
+    fn estimate_fee(&self, target: usize) -> Result<FeeRate, bdk::Error> {
+        match self {
+            Blockchain::Electrum(electrum) => electrum.estimate_fee(target),
+            Blockchain::Bitcoind(bitcoind) => bitcoind.estimate_fee(target),
+        }
+    }
+}
+
+/// Where the client learns about blocks and transactions from.
+enum Source {
+    Electrum {
+        electrum: bdk::electrum_client::Client,
+        backends: Backends,
+        last_backend_check: Instant,
+    },
+    Bitcoind(Bitcoind),
+}
+
+impl Source {
+    fn url(&self) -> Url {
+        match self {
+            Source::Electrum { backends, .. } => backends.current().clone(),
+            Source::Bitcoind(bitcoind) => bitcoind.url().clone(),
+        }
+    }
+
+    /// The height and header of the latest block. Asking an Electrum server
+    /// also subscribes to notifications about new blocks.
+    fn tip(&self) -> Result<(BlockHeight, BlockHeader)> {
+        match self {
+            Source::Electrum { electrum, .. } => {
+                let tip = electrum
+                    .block_headers_subscribe()
+                    .context("Failed to subscribe to header notifications")?;
+                let header = tip.header;
+
+                Ok((BlockHeight::try_from(tip)?, header))
+            }
+            Source::Bitcoind(bitcoind) => bitcoind.tip(),
+        }
+    }
+
+    fn block_header(&self, height: u32) -> Result<BlockHeader> {
+        match self {
+            Source::Electrum { electrum, .. } => electrum
+                .block_header(height as usize)
+                .with_context(|| format!("Failed to get block header at height {}", height)),
+            Source::Bitcoind(bitcoind) => bitcoind.block_header(height),
+        }
+    }
+
+    fn broadcast(&self, transaction: &Transaction) -> Result<()> {
+        match self {
+            Source::Electrum { electrum, .. } => {
+                electrum.transaction_broadcast(transaction)?;
+            }
+            Source::Bitcoind(bitcoind) => bitcoind.broadcast(transaction)?,
+        }
+
+        Ok(())
+    }
+
+    fn get_tx(&self, txid: Txid) -> Result<Transaction> {
+        match self {
+            Source::Electrum { electrum, .. } => Ok(electrum.transaction_get(&txid)?),
+            Source::Bitcoind(bitcoind) => bitcoind.get_tx(txid),
+        }
+    }
+}
+
 pub struct Client {
-    electrum: bdk::electrum_client::Client,
+    source: Source,
     latest_block_height: BlockHeight,
+    /// The hashes of the latest blocks, to detect reorgs.
+    blocks: Blocks,
     last_sync: Instant,
     sync_interval: Duration,
+    /// The scripts of the swap transactions, with their histories if the
+    /// source is an Electrum server.
     script_history: BTreeMap<Script, Vec<GetHistoryRes>>,
     subscriptions: HashMap<(Txid, Script), Subscription>,
+    /// The scripts of the bdk wallet the Electrum server notifies us about, or
+    /// that are imported into bitcoind.
+    wallet_scripts: HashSet<Script>,
+    /// Whether the balance may have changed since the last sync.
+    wallet_changed: bool,
+    new_block: watch::Sender<BlockHeight>,
+    block_heights: watch::Receiver<BlockHeight>,
 }
 
 impl Client {
-    fn new(electrum: bdk::electrum_client::Client, interval: Duration) -> Result<Self> {
-        // Initially fetch the latest block for storing the height.
-        // We do not act on this subscription after this call.
-        let latest_block = electrum
-            .block_headers_subscribe()
-            .context("Failed to subscribe to header notifications")?;
+    fn new(source: Source, interval: Duration) -> Result<Self> {
+        // Initially fetch the latest block for storing the height and subscribe to
+        // notifications about new blocks, see `poll_notifications`.
+        let (latest_block_height, _) = source.tip()?;
+        let (new_block, block_heights) = watch::channel(latest_block_height);
 
         Ok(Self {
-            electrum,
-            latest_block_height: BlockHeight::try_from(latest_block)?,
+            source,
+            latest_block_height,
+            blocks: Blocks::default(),
             last_sync: Instant::now(),
             sync_interval: interval,
             script_history: Default::default(),
             subscriptions: Default::default(),
+            wallet_scripts: Default::default(),
+            wallet_changed: true,
+            new_block,
+            block_heights,
         })
     }
 
+    fn url(&self) -> Url {
+        self.source.url()
+    }
+
+    fn is_bitcoind(&self) -> bool {
+        matches!(self.source, Source::Bitcoind(_))
+    }
+
+    /// Whether there are other Electrum servers to fail over to.
+    fn has_fallbacks(&self) -> bool {
+        matches!(&self.source, Source::Electrum { backends, .. } if backends.has_fallbacks())
+    }
+
     fn update_state(&mut self) -> Result<()> {
         let now = Instant::now();
         if now < self.last_sync + self.sync_interval {
@@ -710,8 +1313,86 @@ impl Client {
         }
 
         self.last_sync = now;
-        self.update_latest_block()?;
-        self.update_script_histories()?;
+
+        let check_due = match &self.source {
+            Source::Electrum {
+                backends,
+                last_backend_check,
+                ..
+            } => backends.has_fallbacks() && now > *last_backend_check + BACKEND_CHECK_INTERVAL,
+            Source::Bitcoind(_) => false,
+        };
+        if check_due {
+            self.check_backends()?;
+        }
+
+        self.with_failover(|client| {
+            client.update_latest_block()?;
+            client.update_script_histories()
+        })
+    }
+
+    fn broadcast(&mut self, transaction: &Transaction) -> Result<()> {
+        self.with_failover(|client| client.source.broadcast(transaction))?;
+        self.wallet_changed = true;
+
+        Ok(())
+    }
+
+    fn get_tx(&mut self, txid: Txid) -> Result<Transaction> {
+        self.with_failover(|client| client.source.get_tx(txid))
+    }
+
+    /// Runs the request against the current Electrum server, if it fails the
+    /// request is retried once after checking all servers.
+    fn with_failover<T>(&mut self, request: impl Fn(&mut Self) -> Result<T>) -> Result<T> {
+        match request(self) {
+            Ok(value) => Ok(value),
+            Err(error) if self.has_fallbacks() => {
+                tracing::debug!(url = %self.url(), "Request to Electrum server failed: {:#}", error);
+                self.check_backends()?;
+
+                request(self)
+            }
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Switches to another Electrum server if the current one is unavailable
+    /// or lags behind the others.
+    ///
+    /// Fails if no server in sync is available, nothing should be decided
+    /// based on the view of a lagging server.
+    fn check_backends(&mut self) -> Result<()> {
+        let (electrum, backends, last_backend_check) = match &mut self.source {
+            Source::Electrum {
+                electrum,
+                backends,
+                last_backend_check,
+            } => (electrum, backends, last_backend_check),
+            Source::Bitcoind(_) => return Ok(()),
+        };
+        *last_backend_check = Instant::now();
+
+        let current_height = electrum
+            .block_headers_subscribe()
+            .ok()
+            .and_then(|header| BlockHeight::try_from(header).ok())
+            .map(u32::from);
+
+        if let Some(new_electrum) = backends.check(current_height)? {
+            *electrum = new_electrum;
+
+            // Subscriptions do not carry over to the new server
+            for script in self.script_history.keys() {
+                self.subscribe_to_script(script);
+            }
+            let wallet_scripts = std::mem::take(&mut self.wallet_scripts);
+            self.watch_wallet_scripts(wallet_scripts);
+            self.wallet_changed = true;
+            self.update_latest_block()?;
+            self.update_script_histories()?;
+        }
 
         Ok(())
     }
@@ -725,10 +1406,18 @@ impl Client {
 
         if !self.script_history.contains_key(&script) {
             self.script_history.insert(script.clone(), vec![]);
+            self.subscribe_to_script(&script);
         }
 
         self.update_state()?;
 
+        if let Source::Bitcoind(bitcoind) = &mut self.source {
+            // Does nothing once imported, a failed import is retried here
+            bitcoind.watch_swap_script(&script)?;
+
+            return bitcoind.status(txid, self.latest_block_height);
+        }
+
         let history = self.script_history.entry(script).or_default();
 
         let history_of_tx = history
@@ -757,32 +1446,242 @@ impl Client {
         }
     }
 
+    /// Reacts to blocks and script changes pushed by the Electrum server since
+    /// the last call.
+    ///
+    /// This is cheap enough to be called every second. On a new block or when
+    /// a transaction of a watched script enters the mempool the script
+    /// histories are updated right away, so subscriptions are not delayed
+    /// until the next sync.
+    fn poll_notifications(&mut self) -> Result<()> {
+        let electrum = match &self.source {
+            Source::Electrum { electrum, .. } => electrum,
+            Source::Bitcoind(_) => return self.poll_bitcoind(),
+        };
+
+        // Notifications are only read from the connection while handling a request
+        electrum.ping().context("Failed to ping Electrum server")?;
+
+        // After a reorg the tip can be lower than before, the last notification is
+        // the current one
+        let mut latest_notified = None;
+        while let Some(notification) = electrum
+            .block_headers_pop()
+            .context("Failed to read header notifications")?
+        {
+            latest_notified = Some(notification);
+        }
+
+        let mut scripts_changed = false;
+        let mut wallet_changed = false;
+        let only_wallet_scripts = self
+            .wallet_scripts
+            .iter()
+            .filter(|script| !self.script_history.contains_key(script));
+        for script in self.script_history.keys().chain(only_wallet_scripts) {
+            let mut changed = false;
+            while electrum
+                .script_pop(script)
+                .context("Failed to read script notifications")?
+                .is_some()
+            {
+                changed = true;
+            }
+
+            if changed {
+                scripts_changed |= self.script_history.contains_key(script);
+                wallet_changed |= self.wallet_scripts.contains(script);
+            }
+        }
+        self.wallet_changed |= wallet_changed;
+
+        let new_block = match latest_notified {
+            Some(tip) => {
+                let header = tip.header;
+                self.connect_tip(BlockHeight::try_from(tip)?, header)?
+            }
+            None => None,
+        };
+
+        if new_block.is_some() || scripts_changed {
+            self.update_script_histories()?;
+            self.last_sync = Instant::now();
+        }
+        if let Some(height) = new_block {
+            self.set_latest_block(height);
+        }
+
+        Ok(())
+    }
+
+    /// Checks whether bitcoind saw a new block or a transaction of an imported
+    /// script since the last call.
+    ///
+    /// The statuses of the swap transactions are asked for when needed, a new
+    /// block only has to notify the subscriptions.
+    fn poll_bitcoind(&mut self) -> Result<()> {
+        let (new_block, new_transaction) = match &mut self.source {
+            Source::Bitcoind(bitcoind) => bitcoind.poll()?,
+            Source::Electrum { .. } => return Ok(()),
+        };
+        self.wallet_changed |= new_transaction;
+
+        if new_block {
+            self.update_latest_block()?;
+            self.last_sync = Instant::now();
+        }
+
+        Ok(())
+    }
+
+    /// Asks the Electrum server to notify us about changes to the history of
+    /// `script`, e.g. a transaction spending to it entering the mempool.
+    ///
+    /// Without the subscription changes are only picked up on the next sync.
+    /// Scripts are imported into bitcoind when their status is first asked
+    /// for instead.
+    fn subscribe_to_script(&self, script: &Script) {
+        if let Source::Electrum { electrum, .. } = &self.source {
+            if let Err(error) = electrum.script_subscribe(script) {
+                tracing::debug!("Failed to subscribe to script notifications: {}", error);
+            }
+        }
+    }
+
+    /// Subscribes to the `scripts` of the bdk wallet that are not watched yet,
+    /// or imports them into bitcoind, returns whether there were any.
+    fn watch_wallet_scripts(&mut self, scripts: impl IntoIterator<Item = Script>) -> bool {
+        let new = scripts
+            .into_iter()
+            .filter(|script| !self.wallet_scripts.contains(script))
+            .collect::<Vec<_>>();
+        if new.is_empty() {
+            return false;
+        }
+
+        match &mut self.source {
+            Source::Electrum { electrum, .. } => {
+                for script in new {
+                    // Scripts of swap transactions are subscribed to already
+                    let subscribed = self.script_history.contains_key(&script)
+                        || electrum.script_subscribe(&script).is_ok();
+                    if subscribed {
+                        self.wallet_scripts.insert(script);
+                    }
+                }
+            }
+            Source::Bitcoind(bitcoind) => match bitcoind.watch_wallet_scripts(&new) {
+                Ok(()) => {
+                    self.wallet_scripts.extend(new);
+                    self.wallet_changed = true;
+                }
+                Err(error) => {
+                    tracing::warn!("Failed to import wallet scripts into bitcoind: {:#}", error)
+                }
+            },
+        }
+
+        true
+    }
+
+    /// Whether the balance may have changed since the last call.
+    fn take_wallet_changed(&mut self) -> bool {
+        std::mem::replace(&mut self.wallet_changed, false)
+    }
+
+    /// Stops watching the script of a subscription nobody listens to anymore,
+    /// unless another subscription or the bdk wallet still needs it.
+    ///
+    /// Scripts imported into bitcoind stay in its wallet.
+    fn unwatch(&mut self, txid: Txid, script: Script) {
+        self.subscriptions.remove(&(txid, script.clone()));
+
+        let still_needed = self
+            .subscriptions
+            .keys()
+            .any(|(_, subscribed)| *subscribed == script);
+        if still_needed {
+            return;
+        }
+
+        self.script_history.remove(&script);
+        if self.wallet_scripts.contains(&script) {
+            return;
+        }
+        if let Source::Electrum { electrum, .. } = &self.source {
+            if let Err(error) = electrum.script_unsubscribe(&script) {
+                tracing::debug!("Failed to unsubscribe from script notifications: {}", error);
+            }
+        }
+    }
+
     fn update_latest_block(&mut self) -> Result<()> {
         // Fetch the latest block for storing the height.
-        // We do not act on this subscription after this call, as we cannot rely on
-        // subscription push notifications because eventually the Electrum server will
-        // close the connection and subscriptions are not automatically renewed
-        // upon renewing the connection.
-        let latest_block = self
-            .electrum
-            .block_headers_subscribe()
-            .context("Failed to subscribe to header notifications")?;
-        let latest_block_height = BlockHeight::try_from(latest_block)?;
+        // We cannot rely on subscription push notifications alone because eventually
+        // the Electrum server will close the connection and subscriptions are not
+        // automatically renewed upon renewing the connection. Subscribing again on
+        // every sync renews the subscription.
+        let (height, header) = self.source.tip()?;
+
+        if let Some(height) = self.connect_tip(height, header)? {
+            self.set_latest_block(height);
+        }
+
+        Ok(())
+    }
 
-        if latest_block_height > self.latest_block_height {
-            tracing::debug!(
-                block_height = u32::from(latest_block_height),
-                "Got notification for new block"
+    /// Records the tip reported by the backend. Returns its height if it is a
+    /// new block or it replaced blocks we knew, in which case the script
+    /// histories have to be updated.
+    ///
+    /// After a reorg the height can be the same or lower than before, all
+    /// subscriptions are notified anyway so they re-evaluate whether their
+    /// transaction is still confirmed.
+    fn connect_tip(
+        &mut self,
+        height: BlockHeight,
+        header: BlockHeader,
+    ) -> Result<Option<BlockHeight>> {
+        let source = &self.source;
+        let reorg = self
+            .blocks
+            .connect(u32::from(height), header, |height| {
+                source.block_header(height)
+            })
+            .context("Failed to check for a reorg")?;
+
+        if let Some(reorg) = reorg {
+            tracing::warn!(
+                fork_height = reorg.fork_height,
+                depth = reorg.depth,
+                "Bitcoin chain reorganized, re-checking the confirmations of all watched transactions"
             );
-            self.latest_block_height = latest_block_height;
+
+            return Ok(Some(height));
         }
 
-        Ok(())
+        Ok((height > self.latest_block_height).then(|| height))
+    }
+
+    fn set_latest_block(&mut self, height: BlockHeight) {
+        tracing::debug!(
+            block_height = u32::from(height),
+            "Got notification for new block"
+        );
+        self.latest_block_height = height;
+        self.wallet_changed = true;
+
+        // cannot fail, the client holds a receiver itself
+        let _ = self.new_block.send(height);
     }
 
     fn update_script_histories(&mut self) -> Result<()> {
-        let histories = self
-            .electrum
+        let electrum = match &self.source {
+            Source::Electrum { electrum, .. } => electrum,
+            // bitcoind is asked for the status of each transaction instead
+            Source::Bitcoind(_) => return Ok(()),
+        };
+        let histories = electrum
             .batch_script_get_history(self.script_history.keys())
             .context("Failed to get script histories")?;
 
@@ -805,18 +1704,28 @@ impl Client {
 
 impl EstimateFeeRate for Client {
     fn estimate_feerate(&self, target_block: usize) -> Result<FeeRate> {
+        let electrum = match &self.source {
+            Source::Electrum { electrum, .. } => electrum,
+            Source::Bitcoind(bitcoind) => return bitcoind.estimate_fee(target_block),
+        };
+
         // https://github.com/romanz/electrs/blob/f9cf5386d1b5de6769ee271df5eef324aa9491bc/src/rpc.rs#L213
         // Returned estimated fees are per BTC/kb.
-        let fee_per_byte = self.electrum.estimate_fee(target_block)?;
+        let fee_per_byte = electrum.estimate_fee(target_block)?;
         // we do not expect fees being that high.
         #[allow(clippy::cast_possible_truncation)]
         Ok(FeeRate::from_btc_per_kvb(fee_per_byte as f32))
     }
 
-    fn min_relay_fee(&self) -> Result<bitcoin::Amount> {
+    fn min_relay_fee(&self) -> Result<Amount> {
+        let electrum = match &self.source {
+            Source::Electrum { electrum, .. } => electrum,
+            Source::Bitcoind(bitcoind) => return bitcoind.relay_fee(),
+        };
+
         // https://github.com/romanz/electrs/blob/f9cf5386d1b5de6769ee271df5eef324aa9491bc/src/rpc.rs#L219
         // Returned fee is in BTC/kb
-        let relay_fee = bitcoin::Amount::from_btc(self.electrum.relay_fee()?)?;
+        let relay_fee = Amount::from_btc(electrum.relay_fee()?)?;
         Ok(relay_fee)
     }
 }
@@ -950,16 +1859,16 @@ mod tests {
     fn given_one_BTC_and_100k_sats_per_vb_fees_should_not_hit_max() {
         // 400 weight = 100 vbyte
         let weight = 400;
-        let amount = bitcoin::Amount::from_sat(100_000_000);
+        let amount = Amount::from_sat(100_000_000);
 
         let sat_per_vb = 100.0;
         let fee_rate = FeeRate::from_sat_per_vb(sat_per_vb);
 
-        let relay_fee = bitcoin::Amount::ONE_SAT;
+        let relay_fee = Amount::ONE_SAT;
         let is_fee = estimate_fee(weight, amount, fee_rate, relay_fee).unwrap();
 
         // weight / 4.0 *  sat_per_vb
-        let should_fee = bitcoin::Amount::from_sat(10_000);
+        let should_fee = Amount::from_sat(10_000);
         assert_eq!(is_fee, should_fee);
     }
 
@@ -967,17 +1876,17 @@ mod tests {
     fn given_1BTC_and_1_sat_per_vb_fees_and_100ksat_min_relay_fee_should_hit_min() {
         // 400 weight = 100 vbyte
         let weight = 400;
-        let amount = bitcoin::Amount::from_sat(100_000_000);
+        let amount = Amount::from_sat(100_000_000);
 
         let sat_per_vb = 1.0;
         let fee_rate = FeeRate::from_sat_per_vb(sat_per_vb);
 
-        let relay_fee = bitcoin::Amount::from_sat(100_000);
+        let relay_fee = Amount::from_sat(100_000);
         let is_fee = estimate_fee(weight, amount, fee_rate, relay_fee).unwrap();
 
         // weight / 4.0 *  sat_per_vb would be smaller than relay fee hence we take min
         // relay fee
-        let should_fee = bitcoin::Amount::from_sat(100_000);
+        let should_fee = Amount::from_sat(100_000);
         assert_eq!(is_fee, should_fee);
     }
 
@@ -985,17 +1894,17 @@ mod tests {
     fn given_1mio_sat_and_1k_sats_per_vb_fees_should_hit_relative_max() {
         // 400 weight = 100 vbyte
         let weight = 400;
-        let amount = bitcoin::Amount::from_sat(1_000_000);
+        let amount = Amount::from_sat(1_000_000);
 
         let sat_per_vb = 1_000.0;
         let fee_rate = FeeRate::from_sat_per_vb(sat_per_vb);
 
-        let relay_fee = bitcoin::Amount::ONE_SAT;
+        let relay_fee = Amount::ONE_SAT;
         let is_fee = estimate_fee(weight, amount, fee_rate, relay_fee).unwrap();
 
         // weight / 4.0 *  sat_per_vb would be greater than 3% hence we take max
         // relative fee.
-        let should_fee = bitcoin::Amount::from_sat(30_000);
+        let should_fee = Amount::from_sat(30_000);
         assert_eq!(is_fee, should_fee);
     }
 
@@ -1004,12 +1913,12 @@ mod tests {
         // even if we send 1BTC we don't want to pay 0.3BTC in fees. This would be
         // $1,650 at the moment.
         let weight = 400;
-        let amount = bitcoin::Amount::from_sat(100_000_000);
+        let amount = Amount::from_sat(100_000_000);
 
         let sat_per_vb = 4_000_000.0;
         let fee_rate = FeeRate::from_sat_per_vb(sat_per_vb);
 
-        let relay_fee = bitcoin::Amount::ONE_SAT;
+        let relay_fee = Amount::ONE_SAT;
         let is_fee = estimate_fee(weight, amount, fee_rate, relay_fee).unwrap();
 
         // weight / 4.0 *  sat_per_vb would be greater than 3% hence we take total
@@ -1025,11 +1934,11 @@ mod tests {
             relay_fee in 0u64..100_000_000u64
         ) {
             let weight = 400;
-            let amount = bitcoin::Amount::from_sat(amount);
+            let amount = Amount::from_sat(amount);
 
             let fee_rate = FeeRate::from_sat_per_vb(sat_per_vb);
 
-            let relay_fee = bitcoin::Amount::from_sat(relay_fee);
+            let relay_fee = Amount::from_sat(relay_fee);
             let _is_fee = estimate_fee(weight, amount, fee_rate, relay_fee).unwrap();
 
         }
@@ -1041,12 +1950,12 @@ mod tests {
             amount in 1u64..100_000_000,
         ) {
             let weight = 400;
-            let amount = bitcoin::Amount::from_sat(amount);
+            let amount = Amount::from_sat(amount);
 
             let sat_per_vb = 100.0;
             let fee_rate = FeeRate::from_sat_per_vb(sat_per_vb);
 
-            let relay_fee = bitcoin::Amount::ONE_SAT;
+            let relay_fee = Amount::ONE_SAT;
             let is_fee = estimate_fee(weight, amount, fee_rate, relay_fee).unwrap();
 
             // weight / 4 * 1_000 is always lower than MAX_ABSOLUTE_TX_FEE
@@ -1060,12 +1969,12 @@ mod tests {
             amount in 100_000_000u64..,
         ) {
             let weight = 400;
-            let amount = bitcoin::Amount::from_sat(amount);
+            let amount = Amount::from_sat(amount);
 
             let sat_per_vb = 1_000.0;
             let fee_rate = FeeRate::from_sat_per_vb(sat_per_vb);
 
-            let relay_fee = bitcoin::Amount::ONE_SAT;
+            let relay_fee = Amount::ONE_SAT;
             let is_fee = estimate_fee(weight, amount, fee_rate, relay_fee).unwrap();
 
             // weight / 4 * 1_000  is always higher than MAX_ABSOLUTE_TX_FEE
@@ -1076,14 +1985,15 @@ mod tests {
     proptest! {
         #[test]
         fn given_fee_above_max_should_always_errors(
-            sat_per_vb in 100_000_000.0f32..,
+            // Bounded, rand refuses float ranges whose width overflows
+            sat_per_vb in 100_000_000.0f32..f32::MAX / 2.0,
         ) {
             let weight = 400;
-            let amount = bitcoin::Amount::from_sat(547u64);
+            let amount = Amount::from_sat(547u64);
 
             let fee_rate = FeeRate::from_sat_per_vb(sat_per_vb);
 
-            let relay_fee = bitcoin::Amount::from_sat(1);
+            let relay_fee = Amount::from_sat(1);
             assert!(estimate_fee(weight, amount, fee_rate, relay_fee).is_err());
 
         }
@@ -1095,15 +2005,42 @@ mod tests {
             relay_fee in 100_000_000u64..
         ) {
             let weight = 400;
-            let amount = bitcoin::Amount::from_sat(547u64);
+            let amount = Amount::from_sat(547u64);
 
             let fee_rate = FeeRate::from_sat_per_vb(1.0);
 
-            let relay_fee = bitcoin::Amount::from_sat(relay_fee);
+            let relay_fee = Amount::from_sat(relay_fee);
             assert!(estimate_fee(weight, amount, fee_rate, relay_fee).is_err());
         }
     }
 
+    #[test]
+    fn child_pays_the_fee_the_parent_is_missing() {
+        // 800 weight = 200 vbyte, which at 10 sat/vb is a fee of 2000 sats
+        let fee = cpfp_fee(
+            800,
+            Amount::from_sat(400),
+            Amount::from_sat(1100),
+            FeeRate::from_sat_per_vb(10.0),
+        )
+        .unwrap();
+
+        assert_eq!(fee, Some(Amount::from_sat(2700)));
+    }
+
+    #[test]
+    fn parent_paying_the_fee_rate_is_not_bumped() {
+        let fee = cpfp_fee(
+            800,
+            Amount::from_sat(2000),
+            Amount::from_sat(1100),
+            FeeRate::from_sat_per_vb(10.0),
+        )
+        .unwrap();
+
+        assert_eq!(fee, None);
+    }
+
     #[tokio::test]
     async fn given_no_balance_returns_amount_0() {
         let wallet = WalletBuilder::new(0).with_fees(1.0, 1).build();
@@ -1149,7 +2086,7 @@ mod tests {
         for amount in above_dust..(balance - (above_dust - 1)) {
             let (A, B) = (PublicKey::random(), PublicKey::random());
             let change = wallet.new_address().await.unwrap();
-            let txlock = TxLock::new(&wallet, bitcoin::Amount::from_sat(amount), A, B, change)
+            let txlock = TxLock::new(&wallet, Amount::from_sat(amount), A, B, change)
                 .await
                 .unwrap();
             let txlock_output = txlock.script_pubkey();
@@ -1191,6 +2128,29 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn wait_until_seen_tells_mempool_and_confirmed_apart() {
+        let (sender, receiver) = watch::channel(ScriptStatus::Unseen);
+        let subscription = Subscription {
+            receiver,
+            finality_confirmations: 1,
+            txid: Txid::default(),
+            progress: Progress::default(),
+        };
+
+        let seen = tokio::spawn({
+            let subscription = subscription.clone();
+            async move { subscription.wait_until_seen().await }
+        });
+        sender.send(ScriptStatus::InMempool).unwrap();
+
+        assert_eq!(seen.await.unwrap().unwrap(), ScriptStatus::InMempool);
+
+        sender.send(confs(2)).unwrap();
+
+        assert_eq!(subscription.wait_until_seen().await.unwrap(), confs(2));
+    }
+
     #[test]
     fn printing_status_change_doesnt_spam_on_same_status() {
         let writer = capture_logs(LevelFilter::DEBUG);
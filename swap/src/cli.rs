@@ -1,9 +1,21 @@
+pub mod artifacts;
 mod behaviour;
 pub mod cancel;
+pub mod check;
 pub mod command;
+#[cfg(feature = "dashboard")]
+pub mod dashboard;
 mod event_loop;
+pub mod export;
+pub mod inspect;
 mod list_sellers;
+mod lock_approval;
+mod price_limit;
+pub mod progress;
 pub mod refund;
+mod refund_deposit;
+mod swap_factory;
+mod swap_wallets;
 pub mod tracing;
 pub mod transport;
 
@@ -11,15 +23,21 @@ pub use behaviour::{Behaviour, OutEvent};
 pub use cancel::cancel;
 pub use event_loop::{EventLoop, EventLoopHandle};
 pub use list_sellers::{list_sellers, Seller, Status as SellerStatus};
+pub use lock_approval::ConfirmLock;
+pub use price_limit::PriceLimit;
 pub use refund::refund;
+pub use refund_deposit::refund_deposit;
+pub use swap_factory::SwapFactory;
+pub use swap_wallets::{sweep_swap_wallets, Action as SwapWalletAction, SwapWallet};
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::amounts::{Price, Quote};
+    use crate::bitcoin;
     use crate::asb;
     use crate::cli::list_sellers::{Seller, Status};
     use crate::network::quote;
-    use crate::network::quote::BidQuote;
     use crate::network::rendezvous::XmrBtcNamespace;
     use crate::network::test::{new_swarm, SwarmExt};
     use futures::StreamExt;
@@ -52,8 +70,16 @@ mod tests {
             .unwrap()
             .unwrap();
 
+        assert!(sellers.iter().all(|seller| seller.latency.is_some()));
+        let sellers = sellers
+            .into_iter()
+            .map(|seller| Seller {
+                latency: None,
+                ..seller
+            })
+            .collect::<HashSet<Seller>>();
         assert_eq!(
-            HashSet::<Seller>::from_iter(sellers),
+            sellers,
             HashSet::<Seller>::from_iter([expected_seller_1, expected_seller_2])
         )
     }
@@ -77,8 +103,8 @@ mod tests {
         rendezvous_address: Multiaddr,
         namespace: XmrBtcNamespace,
     ) -> Seller {
-        let static_quote = BidQuote {
-            price: bitcoin::Amount::from_sat(1337),
+        let static_quote = Quote {
+            price: Price::new(bitcoin::Amount::from_sat(1337)),
             min_quantity: bitcoin::Amount::from_sat(42),
             max_quantity: bitcoin::Amount::from_sat(9001),
         };
@@ -115,8 +141,10 @@ mod tests {
         });
 
         Seller {
+            latency: None,
             multiaddr: asb_address.with(Protocol::P2p(asb_peer_id.into())),
             status: Status::Online(static_quote),
+            depth: Vec::new(),
         }
     }
 
@@ -129,7 +157,7 @@ mod tests {
         quote: quote::Behaviour,
 
         #[behaviour(ignore)]
-        static_quote: BidQuote,
+        static_quote: Quote,
         #[behaviour(ignore)]
         registered: bool,
     }
@@ -152,7 +180,7 @@ mod tests {
             } = event
             {
                 self.quote
-                    .send_response(channel, self.static_quote)
+                    .send_response(channel, self.static_quote.into())
                     .unwrap();
             }
         }
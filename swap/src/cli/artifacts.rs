@@ -0,0 +1,207 @@
+//! A directory per swap with everything that helps analysing the swap after the
+//! fact, e.g. when asking for support or in a dispute with the seller.
+//!
+//! Only information that is public or meant to be shared is written. Bitcoin
+//! transactions are included once they were published, the secret keys of the
+//! swap are never included. The directory contains:
+//!
+//! - `summary.json`: the current state, the states the swap went through, the
+//!   ids of its Bitcoin transactions and why the swap was wound down if it
+//!   exceeded the maximum swap duration
+//! - `bitcoin/<name>.hex`: the raw published Bitcoin transactions
+//! - `monero_lock_proof.json`: the transfer proof of the Monero lock
+//!   transaction sent by the seller
+//! - `quotes.json`: the quotes the seller signed, as evidence of the offered
+//!   price and terms
+//! - `swap.log`: the log file of the swap
+
+use crate::bitcoin::{self, Txid};
+use crate::data::DataDir;
+use crate::monero::TransferProof;
+use crate::protocol::bob::BobState;
+use crate::protocol::Database;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::convert::TryInto;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+#[derive(Debug, Serialize)]
+struct Summary {
+    swap_id: Uuid,
+    state: String,
+    history: Vec<String>,
+    bitcoin_transactions: BTreeMap<&'static str, Txid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    wind_down: Option<String>,
+}
+
+/// Writes the artifacts of the swap to `<data_dir>/artifacts/<swap_id>` and
+/// returns the path of the directory.
+///
+/// Existing artifacts of the swap are overwritten.
+pub async fn write(
+    swap_id: Uuid,
+    data_dir: &DataDir,
+    db: &(dyn Database + Send + Sync),
+    bitcoin_wallet: &bitcoin::Wallet,
+) -> Result<PathBuf> {
+    let states = db
+        .get_states(swap_id)
+        .await?
+        .into_iter()
+        .map(TryInto::try_into)
+        .collect::<Result<Vec<BobState>, _>>()?;
+    let state = states
+        .last()
+        .with_context(|| format!("No state in database for swap: {}", swap_id))?;
+
+    let dir = data_dir.artifacts().join(swap_id.to_string());
+    let bitcoin_dir = dir.join("bitcoin");
+    tokio::fs::create_dir_all(&bitcoin_dir)
+        .await
+        .with_context(|| format!("Failed to create directory {}", bitcoin_dir.display()))?;
+
+    let bitcoin_transactions = bitcoin_transactions(&states);
+    for (name, txid) in &bitcoin_transactions {
+        // The Electrum server does not tell an unknown transaction apart from
+        // other failures, transactions that cannot be fetched are skipped
+        match bitcoin_wallet.get_raw_transaction(*txid).await {
+            Ok(transaction) => {
+                let hex = ::bitcoin::consensus::encode::serialize_hex(&transaction);
+                write_file(&bitcoin_dir.join(format!("{}.hex", name)), hex).await?;
+            }
+            Err(error) => {
+                tracing::debug!(%txid, "Skipping {} transaction: {:#}", name, error)
+            }
+        }
+    }
+
+    if let Some(proof) = monero_lock_proof(&states) {
+        write_file(
+            &dir.join("monero_lock_proof.json"),
+            serde_json::to_string_pretty(proof)?,
+        )
+        .await?;
+    }
+
+    let quotes = db.get_signed_quotes(swap_id).await?;
+    if !quotes.is_empty() {
+        write_file(
+            &dir.join("quotes.json"),
+            serde_json::to_string_pretty(&quotes)?,
+        )
+        .await?;
+    }
+
+    let log_file = data_dir.logs().join(format!("swap-{}.log", swap_id));
+    if log_file.exists() {
+        tokio::fs::copy(&log_file, dir.join("swap.log"))
+            .await
+            .with_context(|| format!("Failed to copy log file {}", log_file.display()))?;
+    }
+
+    let summary = Summary {
+        swap_id,
+        state: state.to_string(),
+        history: states.iter().map(ToString::to_string).collect(),
+        bitcoin_transactions,
+        wind_down: db.get_wind_down(swap_id).await?,
+    };
+    write_file(
+        &dir.join("summary.json"),
+        serde_json::to_string_pretty(&summary)?,
+    )
+    .await?;
+
+    Ok(dir)
+}
+
+/// The ids of all Bitcoin transactions that are known in any of the states.
+fn bitcoin_transactions(states: &[BobState]) -> BTreeMap<&'static str, Txid> {
+    let mut transactions = BTreeMap::new();
+
+    for state in states {
+        match state {
+            BobState::Started { .. }
+            | BobState::SwapSetupCompleted(_)
+            | BobState::SafelyAborted => {}
+            BobState::BtcLocked {
+                state3,
+                monero_wallet_restore_blockheight,
+            }
+            | BobState::XmrLockProofReceived {
+                state: state3,
+                monero_wallet_restore_blockheight,
+                ..
+            } => {
+                let state6 = state3.cancel(*monero_wallet_restore_blockheight);
+                transactions.insert("lock", state3.tx_lock_id());
+                transactions.insert("cancel", state6.tx_cancel_id());
+                transactions.insert("refund", state6.tx_refund_id());
+            }
+            BobState::XmrLocked(state4) | BobState::EncSigSent(state4) => {
+                transactions.insert("redeem", state4.tx_redeem_id());
+                let state6 = state4.clone().cancel();
+                transactions.insert("lock", state6.tx_lock_id());
+                transactions.insert("cancel", state6.tx_cancel_id());
+                transactions.insert("refund", state6.tx_refund_id());
+            }
+            BobState::BtcRedeemed(state5) => {
+                transactions.insert("lock", state5.tx_lock_id());
+            }
+            BobState::CancelTimelockExpired(state6)
+            | BobState::BtcCancelled(state6)
+            | BobState::BtcRefunded(state6) => {
+                transactions.insert("lock", state6.tx_lock_id());
+                transactions.insert("cancel", state6.tx_cancel_id());
+                transactions.insert("refund", state6.tx_refund_id());
+            }
+            BobState::XmrRedeemed { tx_lock_id } | BobState::BtcPunished { tx_lock_id } => {
+                transactions.insert("lock", *tx_lock_id);
+            }
+        }
+    }
+
+    transactions
+}
+
+fn monero_lock_proof(states: &[BobState]) -> Option<&TransferProof> {
+    states.iter().find_map(BobState::xmr_lock_proof)
+}
+
+async fn write_file(path: &Path, contents: String) -> Result<()> {
+    tokio::fs::write(path, contents)
+        .await
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn finished_swap_only_knows_lock_transaction() {
+        let tx_lock_id =
+            Txid::from_str("5d11bd5e70a97e9e4bfd5a6d1ee6bcc0fa2a6a7ca8ee738e038fd5cf6823465d")
+                .unwrap();
+        let states = vec![
+            BobState::Started {
+                btc_amount: bitcoin::Amount::from_sat(100_000),
+                change_address: "bc1qe4epnfklcaa0mun26yz5g8k24em5u9f92hy325"
+                    .parse()
+                    .unwrap(),
+                xmr_amount: None,
+            },
+            BobState::XmrRedeemed { tx_lock_id },
+        ];
+
+        let transactions = bitcoin_transactions(&states);
+
+        assert_eq!(transactions.len(), 1);
+        assert_eq!(transactions["lock"], tx_lock_id);
+        assert!(monero_lock_proof(&states).is_none());
+    }
+}
@@ -1,21 +1,27 @@
-use crate::network::quote::BidQuote;
+use crate::network::quote::SignedQuote;
 use crate::network::swap_setup::bob;
-use crate::network::{encrypted_signature, quote, redial, transfer_proof};
+use crate::network::{
+    connection, cooperative_xmr_redeem_after_punish, encrypted_signature, hole_punch, quote,
+    receipt, redial, swap_status, transfer_proof,
+};
 use crate::protocol::bob::State2;
 use crate::{bitcoin, env};
 use anyhow::{anyhow, Error, Result};
 use libp2p::core::Multiaddr;
-use libp2p::ping::{Ping, PingConfig, PingEvent};
+use libp2p::ping::{Ping, PingEvent};
+use libp2p::relay::v2::client::{self, Client};
 use libp2p::request_response::{RequestId, ResponseChannel};
+use libp2p::swarm::behaviour::toggle::Toggle;
 use libp2p::{NetworkBehaviour, PeerId};
 use std::sync::Arc;
 use std::time::Duration;
 
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum OutEvent {
     QuoteReceived {
         id: RequestId,
-        response: BidQuote,
+        response: SignedQuote,
     },
     SwapSetupCompleted(Box<Result<State2>>),
     TransferProofReceived {
@@ -26,9 +32,19 @@ pub enum OutEvent {
     EncryptedSignatureAcknowledged {
         id: RequestId,
     },
+    SwapStatusReceived(Box<swap_status::Status>),
+    CooperativeXmrRedeemReceived {
+        id: RequestId,
+        response: cooperative_xmr_redeem_after_punish::Response,
+    },
+    ReceiptReceived {
+        id: RequestId,
+        response: receipt::Response,
+    },
     AllRedialAttemptsExhausted {
         peer: PeerId,
     },
+    HolePunch(hole_punch::OutEvent),
     Failure {
         peer: PeerId,
         error: Error,
@@ -63,7 +79,16 @@ pub struct Behaviour {
     pub swap_setup: bob::Behaviour,
     pub transfer_proof: transfer_proof::Behaviour,
     pub encrypted_signature: encrypted_signature::Behaviour,
+    pub swap_status: swap_status::Behaviour,
+    pub cooperative_xmr_redeem: cooperative_xmr_redeem_after_punish::Behaviour,
+    pub receipt: receipt::Behaviour,
     pub redial: redial::Behaviour,
+    /// Dials sellers through a circuit relay, only enabled if the transport
+    /// supports it.
+    relay: Toggle<Client>,
+    /// Upgrades a relayed connection to the seller to a direct one, only
+    /// enabled without Tor.
+    hole_punch: Toggle<hole_punch::Behaviour>,
 
     /// Ping behaviour that ensures that the underlying network connection is
     /// still alive. If the ping fails a connection close event will be
@@ -76,14 +101,21 @@ impl Behaviour {
         alice: PeerId,
         env_config: env::Config,
         bitcoin_wallet: Arc<bitcoin::Wallet>,
+        relay: Option<Client>,
+        hole_punch: Option<hole_punch::Behaviour>,
     ) -> Self {
         Self {
             quote: quote::cli(),
             swap_setup: bob::Behaviour::new(env_config, bitcoin_wallet),
             transfer_proof: transfer_proof::bob(),
             encrypted_signature: encrypted_signature::bob(),
+            swap_status: swap_status::bob(),
+            cooperative_xmr_redeem: cooperative_xmr_redeem_after_punish::bob(),
+            receipt: receipt::bob(),
             redial: redial::Behaviour::new(alice, Duration::from_secs(2)),
-            ping: Ping::new(PingConfig::new().with_keep_alive(true)),
+            relay: relay.into(),
+            hole_punch: hole_punch.into(),
+            ping: connection::ping(),
         }
     }
 
@@ -91,7 +123,12 @@ impl Behaviour {
     pub fn add_address(&mut self, peer_id: PeerId, address: Multiaddr) {
         self.quote.add_address(&peer_id, address.clone());
         self.transfer_proof.add_address(&peer_id, address.clone());
-        self.encrypted_signature.add_address(&peer_id, address);
+        self.encrypted_signature
+            .add_address(&peer_id, address.clone());
+        self.swap_status.add_address(&peer_id, address.clone());
+        self.cooperative_xmr_redeem
+            .add_address(&peer_id, address.clone());
+        self.receipt.add_address(&peer_id, address);
     }
 }
 
@@ -100,3 +137,21 @@ impl From<PingEvent> for OutEvent {
         OutEvent::Other
     }
 }
+
+impl From<client::Event> for OutEvent {
+    fn from(_: client::Event) -> Self {
+        OutEvent::Other
+    }
+}
+
+impl From<hole_punch::OutEvent> for OutEvent {
+    fn from(event: hole_punch::OutEvent) -> Self {
+        OutEvent::HolePunch(event)
+    }
+}
+
+impl From<()> for OutEvent {
+    fn from(_: ()) -> Self {
+        OutEvent::Other
+    }
+}
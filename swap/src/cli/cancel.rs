@@ -1,4 +1,4 @@
-use crate::bitcoin::{parse_rpc_error_code, RpcErrorCode, Txid, Wallet};
+use crate::bitcoin::{parse_rpc_error_code, ExpiredTimelocks, RpcErrorCode, Txid, Wallet};
 use crate::protocol::bob::BobState;
 use crate::protocol::Database;
 use anyhow::{bail, Result};
@@ -6,16 +6,35 @@ use std::convert::TryInto;
 use std::sync::Arc;
 use uuid::Uuid;
 
+#[derive(Debug, thiserror::Error, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub enum Error {
+    #[error("The cancel timelock has not expired yet, use --force to publish the cancel transaction anyway")]
+    CancelTimelockNotExpiredYet,
+}
+
+/// Publishes the cancel transaction of the swap.
+///
+/// Unless `force` is set, the cancel transaction is only published once the
+/// cancel timelock expired according to the Electrum server.
 pub async fn cancel(
     swap_id: Uuid,
     bitcoin_wallet: Arc<Wallet>,
     db: Arc<dyn Database>,
+    force: bool,
 ) -> Result<(Txid, BobState)> {
     let state = db.get_state(swap_id).await?.try_into()?;
 
     let state6 = match state {
-        BobState::BtcLocked { state3, .. } => state3.cancel(),
-        BobState::XmrLockProofReceived { state, .. } => state.cancel(),
+        BobState::BtcLocked {
+            state3,
+            monero_wallet_restore_blockheight,
+        } => state3.cancel(monero_wallet_restore_blockheight),
+        BobState::XmrLockProofReceived {
+            state,
+            monero_wallet_restore_blockheight,
+            ..
+        } => state.cancel(monero_wallet_restore_blockheight),
         BobState::XmrLocked(state4) => state4.cancel(),
         BobState::EncSigSent(state4) => state4.cancel(),
         BobState::CancelTimelockExpired(state6) => state6,
@@ -34,7 +53,13 @@ pub async fn cancel(
         ),
     };
 
-    tracing::info!(%swap_id, "Manually cancelling swap");
+    if !force {
+        if let ExpiredTimelocks::None = state6.expired_timelock(bitcoin_wallet.as_ref()).await? {
+            bail!(Error::CancelTimelockNotExpiredYet)
+        }
+    }
+
+    tracing::info!(%swap_id, %force, "Manually cancelling swap");
 
     let txid = match state6.submit_tx_cancel(bitcoin_wallet.as_ref()).await {
         Ok(txid) => txid,
@@ -0,0 +1,239 @@
+use crate::amounts::Quote;
+use crate::bitcoin;
+use anyhow::{bail, Context, Result};
+use big_bytes::BigByte;
+use std::cmp::min;
+use std::fmt;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Bitcoin nodes reject blocks with a timestamp more than two hours ahead of
+/// their clock. If the latest block is further ahead than that, our clock is
+/// behind.
+const MAX_BLOCK_TIME_AHEAD: Duration = Duration::from_secs(2 * 60 * 60);
+
+/// A block is expected every ten minutes. If the latest block is older than
+/// this, either our clock is ahead or the Electrum server is lagging behind.
+const MAX_BLOCK_AGE: Duration = Duration::from_secs(3 * 60 * 60);
+
+/// The free space we require in the data directory for the database, the
+/// Bitcoin wallet and the Monero wallets generated for swaps.
+const MIN_FREE_DISK_SPACE: u64 = 500 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub enum Outcome {
+    Pass,
+    Fail,
+    Skipped,
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Outcome::Pass => write!(f, "PASS"),
+            Outcome::Fail => write!(f, "FAIL"),
+            Outcome::Skipped => write!(f, "SKIPPED"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Check {
+    pub name: &'static str,
+    pub outcome: Outcome,
+    pub details: String,
+}
+
+/// The checks executed by the `check` command in the order they were run.
+#[derive(Debug, Default)]
+pub struct Report {
+    checks: Vec<Check>,
+}
+
+impl Report {
+    pub fn pass(&mut self, name: &'static str, details: impl Into<String>) {
+        self.push(name, Outcome::Pass, details.into());
+    }
+
+    pub fn fail(&mut self, name: &'static str, error: anyhow::Error) {
+        self.push(name, Outcome::Fail, format!("{:#}", error));
+    }
+
+    pub fn skip(&mut self, name: &'static str, reason: impl Into<String>) {
+        self.push(name, Outcome::Skipped, reason.into());
+    }
+
+    pub fn record(&mut self, name: &'static str, result: Result<String>) {
+        match result {
+            Ok(details) => self.pass(name, details),
+            Err(error) => self.fail(name, error),
+        }
+    }
+
+    pub fn checks(&self) -> &[Check] {
+        &self.checks
+    }
+
+    pub fn failures(&self) -> usize {
+        self.checks
+            .iter()
+            .filter(|check| check.outcome == Outcome::Fail)
+            .count()
+    }
+
+    fn push(&mut self, name: &'static str, outcome: Outcome, details: String) {
+        self.checks.push(Check {
+            name,
+            outcome,
+            details,
+        });
+    }
+}
+
+/// Compares the local clock against the timestamp of the latest Bitcoin
+/// block.
+///
+/// Block timestamps are only accurate to a couple of hours, hence this only
+/// detects a clock that is off by more than that.
+pub fn clock_skew(now: SystemTime, latest_block_timestamp: u32) -> Result<String> {
+    let latest_block = UNIX_EPOCH + Duration::from_secs(latest_block_timestamp.into());
+
+    match latest_block.duration_since(now) {
+        Ok(ahead) if ahead > MAX_BLOCK_TIME_AHEAD => bail!(
+            "The latest Bitcoin block is {} minutes ahead of the local clock, the local clock is behind",
+            ahead.as_secs() / 60
+        ),
+        Ok(_) => Ok("Local clock is in line with the latest Bitcoin block".to_owned()),
+        Err(e) if e.duration() > MAX_BLOCK_AGE => bail!(
+            "The latest Bitcoin block is {} minutes old, either the local clock is ahead or the Electrum server is not in sync",
+            e.duration().as_secs() / 60
+        ),
+        Err(e) => Ok(format!(
+            "Latest Bitcoin block was mined {} minutes ago",
+            e.duration().as_secs() / 60
+        )),
+    }
+}
+
+/// Checks that there is enough free disk space in the data directory.
+pub fn disk_space(data_dir: &Path) -> Result<String> {
+    let available = fs2::available_space(data_dir).with_context(|| {
+        format!(
+            "Failed to determine free disk space of {}",
+            data_dir.display()
+        )
+    })?;
+
+    enough_disk_space(available)
+}
+
+fn enough_disk_space(available: u64) -> Result<String> {
+    if available < MIN_FREE_DISK_SPACE {
+        bail!(
+            "Only {} of disk space available, at least {} are required",
+            available.big_byte(2),
+            MIN_FREE_DISK_SPACE.big_byte(2)
+        )
+    }
+
+    Ok(format!("{} of disk space available", available.big_byte(2)))
+}
+
+/// Checks that the Bitcoin wallet holds enough funds to swap with the seller.
+///
+/// Without a quote this only reports how much can be swapped.
+pub fn bitcoin_balance(max_giveable: bitcoin::Amount, quote: Option<Quote>) -> Result<String> {
+    let quote = match quote {
+        Some(quote) => quote,
+        None => return Ok(format!("Can swap up to {}", max_giveable)),
+    };
+
+    if max_giveable < quote.min_quantity {
+        bail!(
+            "Can swap up to {} but the seller requires at least {}, deposit more Bitcoin before starting a swap",
+            max_giveable,
+            quote.min_quantity
+        )
+    }
+
+    Ok(format!(
+        "Can swap {} with the seller",
+        min(max_giveable, quote.max_quantity)
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::amounts::Price;
+    use std::convert::TryFrom;
+
+    const NOW: u64 = 1_640_000_000;
+
+    fn now() -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(NOW)
+    }
+
+    #[test]
+    fn given_recent_block_then_clock_skew_check_passes() {
+        let timestamp = u32::try_from(NOW - 20 * 60).unwrap();
+
+        assert!(clock_skew(now(), timestamp).is_ok());
+    }
+
+    #[test]
+    fn given_block_far_ahead_of_local_clock_then_clock_skew_check_fails() {
+        let timestamp = u32::try_from(NOW + 3 * 60 * 60).unwrap();
+
+        let error = clock_skew(now(), timestamp).unwrap_err();
+
+        assert!(error.to_string().contains("local clock is behind"));
+    }
+
+    #[test]
+    fn given_stale_block_then_clock_skew_check_fails() {
+        let timestamp = u32::try_from(NOW - 4 * 60 * 60).unwrap();
+
+        let error = clock_skew(now(), timestamp).unwrap_err();
+
+        assert!(error.to_string().contains("240 minutes old"));
+    }
+
+    #[test]
+    fn given_too_little_disk_space_then_check_fails() {
+        assert!(enough_disk_space(MIN_FREE_DISK_SPACE - 1).is_err());
+        assert!(enough_disk_space(MIN_FREE_DISK_SPACE).is_ok());
+    }
+
+    #[test]
+    fn given_balance_below_min_quantity_then_balance_check_fails() {
+        let quote = Quote {
+            price: Price::new(bitcoin::Amount::from_sat(1_000)),
+            min_quantity: bitcoin::Amount::from_sat(10_000),
+            max_quantity: bitcoin::Amount::from_sat(100_000),
+        };
+
+        assert!(bitcoin_balance(bitcoin::Amount::from_sat(9_999), Some(quote)).is_err());
+        assert_eq!(
+            bitcoin_balance(bitcoin::Amount::from_sat(200_000), Some(quote)).unwrap(),
+            "Can swap 0.00100000 BTC with the seller"
+        );
+    }
+
+    #[test]
+    fn skipped_checks_are_not_counted_as_failures() {
+        let mut report = Report::default();
+
+        report.pass("Disk space", "plenty");
+        report.skip("Seller", "No seller given");
+        report.record(
+            "Bitcoin backend",
+            Err(anyhow::anyhow!("connection refused")),
+        );
+
+        assert_eq!(report.checks().len(), 3);
+        assert_eq!(report.failures(), 1);
+        assert_eq!(report.checks()[2].details, "connection refused");
+    }
+}
@@ -1,11 +1,17 @@
-use crate::bitcoin::Amount;
+use crate::bitcoin::{parse_amount, Amount};
+use crate::cli::export::ExportKey;
+use crate::cli::inspect;
+use crate::data::system_data_dir;
+use crate::database::SwapQuery;
 use crate::env::GetConfig;
-use crate::fs::system_data_dir;
+use crate::explorer::Explorer;
 use crate::network::rendezvous::XmrBtcNamespace;
-use crate::{env, monero};
+use crate::version::BuildInfo;
+use crate::{env, fiat, monero};
 use anyhow::{bail, Context, Result};
 use bitcoin::{Address, AddressType};
 use libp2p::core::Multiaddr;
+use rust_decimal::Decimal;
 use serde::Serialize;
 use std::ffi::OsString;
 use std::path::PathBuf;
@@ -19,12 +25,12 @@ pub const DEFAULT_MONERO_DAEMON_ADDRESS: &str = "node.melo.tools:18081";
 pub const DEFAULT_MONERO_DAEMON_ADDRESS_STAGENET: &str = "stagenet.melo.tools:38081";
 
 // See: https://1209k.com/bitcoin-eye/ele.php?chain=btc
-const DEFAULT_ELECTRUM_RPC_URL: &str = "ssl://blockstream.info:700";
+pub const DEFAULT_ELECTRUM_RPC_URL: &str = "ssl://blockstream.info:700";
 // See: https://1209k.com/bitcoin-eye/ele.php?chain=tbtc
 pub const DEFAULT_ELECTRUM_RPC_URL_TESTNET: &str = "ssl://electrum.blockstream.info:60002";
 
-const DEFAULT_BITCOIN_CONFIRMATION_TARGET: usize = 3;
-const DEFAULT_BITCOIN_CONFIRMATION_TARGET_TESTNET: usize = 1;
+pub const DEFAULT_BITCOIN_CONFIRMATION_TARGET: usize = 3;
+pub const DEFAULT_BITCOIN_CONFIRMATION_TARGET_TESTNET: usize = 1;
 
 const DEFAULT_TOR_SOCKS5_PORT: &str = "9050";
 
@@ -33,6 +39,7 @@ pub struct Arguments {
     pub env_config: env::Config,
     pub debug: bool,
     pub json: bool,
+    pub otlp_endpoint: Option<Url>,
     pub data_dir: PathBuf,
     pub cmd: Command,
 }
@@ -66,6 +73,7 @@ where
 
     let debug = args.debug;
     let json = args.json;
+    let otlp_endpoint = args.otlp_endpoint;
     let is_testnet = args.testnet;
     let data = args.data;
 
@@ -77,8 +85,24 @@ where
             monero,
             monero_receive_address,
             tor: Tor { tor_socks5_port },
+            fiat_currency,
+            receive_xmr,
+            price:
+                MaxPrice {
+                    max_price,
+                    max_price_deviation,
+                },
+            lock: LockConfirmation { confirm_lock },
+            duration: MaxSwapDuration {
+                max_swap_duration_hours,
+            },
+            watchtower: Watchtower { watchtower },
+            light_wallet:
+                LightWalletServer {
+                    monero_light_wallet_server,
+                },
         } => {
-            let (bitcoin_electrum_rpc_url, bitcoin_target_block) =
+            let (bitcoin_electrum_rpc_urls, bitcoin_target_block) =
                 bitcoin.apply_defaults(is_testnet)?;
             let monero_daemon_address = monero.apply_defaults(is_testnet);
             let monero_receive_address =
@@ -87,52 +111,75 @@ where
                 validate_bitcoin_address(bitcoin_change_address, is_testnet)?;
 
             Arguments {
-                env_config: env_config_from(is_testnet),
+                env_config: env_config_from(is_testnet)
+                    .with_max_swap_duration_hours(max_swap_duration_hours),
                 debug,
                 json,
+                otlp_endpoint,
                 data_dir: data::data_dir_from(data, is_testnet)?,
                 cmd: Command::BuyXmr {
                     seller,
-                    bitcoin_electrum_rpc_url,
+                    bitcoin_electrum_rpc_urls,
                     bitcoin_target_block,
                     bitcoin_change_address,
                     monero_receive_address,
                     monero_daemon_address,
+                    monero_light_wallet_server,
                     tor_socks5_port,
+                    fiat_currency,
+                    receive_xmr,
+                    max_price,
+                    max_price_deviation,
+                    confirm_lock,
+                    watchtower,
                 },
             }
         }
-        RawCommand::History => Arguments {
-            env_config: env_config_from(is_testnet),
-            debug,
-            json,
-            data_dir: data::data_dir_from(data, is_testnet)?,
-            cmd: Command::History,
-        },
+        RawCommand::History {
+            bitcoin_explorer,
+            monero_explorer,
+            query,
+        } => {
+            let env_config = env_config_from(is_testnet);
+            let explorer = Explorer::new(env_config.bitcoin_network, env_config.monero_network)
+                .with_bitcoin(bitcoin_explorer)
+                .with_monero(monero_explorer);
+
+            Arguments {
+                env_config,
+                debug,
+                json,
+                otlp_endpoint,
+                data_dir: data::data_dir_from(data, is_testnet)?,
+                cmd: Command::History { explorer, query },
+            }
+        }
         RawCommand::Config => Arguments {
             env_config: env_config_from(is_testnet),
             debug,
             json,
+            otlp_endpoint,
             data_dir: data::data_dir_from(data, is_testnet)?,
             cmd: Command::Config,
         },
         RawCommand::Balance {
-            bitcoin_electrum_rpc_url,
+            bitcoin_electrum_rpc_urls,
         } => {
             let bitcoin = Bitcoin {
-                bitcoin_electrum_rpc_url,
+                bitcoin_electrum_rpc_urls,
                 bitcoin_target_block: None,
             };
-            let (bitcoin_electrum_rpc_url, bitcoin_target_block) =
+            let (bitcoin_electrum_rpc_urls, bitcoin_target_block) =
                 bitcoin.apply_defaults(is_testnet)?;
 
             Arguments {
                 env_config: env_config_from(is_testnet),
                 debug,
                 json,
+                otlp_endpoint,
                 data_dir: data::data_dir_from(data, is_testnet)?,
                 cmd: Command::Balance {
-                    bitcoin_electrum_rpc_url,
+                    bitcoin_electrum_rpc_urls,
                     bitcoin_target_block,
                 },
             }
@@ -142,40 +189,95 @@ where
             amount,
             address,
         } => {
-            let (bitcoin_electrum_rpc_url, bitcoin_target_block) =
+            let (bitcoin_electrum_rpc_urls, bitcoin_target_block) =
                 bitcoin.apply_defaults(is_testnet)?;
 
             Arguments {
                 env_config: env_config_from(is_testnet),
                 debug,
                 json,
+                otlp_endpoint,
                 data_dir: data::data_dir_from(data, is_testnet)?,
                 cmd: Command::WithdrawBtc {
-                    bitcoin_electrum_rpc_url,
+                    bitcoin_electrum_rpc_urls,
                     bitcoin_target_block,
                     amount,
                     address: bitcoin_address(address, is_testnet)?,
                 },
             }
         }
+        RawCommand::RefundDeposit { bitcoin, address } => {
+            let (bitcoin_electrum_rpc_urls, bitcoin_target_block) =
+                bitcoin.apply_defaults(is_testnet)?;
+
+            Arguments {
+                env_config: env_config_from(is_testnet),
+                debug,
+                json,
+                otlp_endpoint,
+                data_dir: data::data_dir_from(data, is_testnet)?,
+                cmd: Command::RefundDeposit {
+                    bitcoin_electrum_rpc_urls,
+                    bitcoin_target_block,
+                    address: bitcoin_address(address, is_testnet)?,
+                },
+            }
+        }
         RawCommand::Resume {
             swap_id: SwapId { swap_id },
             bitcoin,
             monero,
             tor: Tor { tor_socks5_port },
+            lock: LockConfirmation { confirm_lock },
+            duration: MaxSwapDuration {
+                max_swap_duration_hours,
+            },
+            watchtower: Watchtower { watchtower },
+            light_wallet:
+                LightWalletServer {
+                    monero_light_wallet_server,
+                },
         } => {
-            let (bitcoin_electrum_rpc_url, bitcoin_target_block) =
+            let (bitcoin_electrum_rpc_urls, bitcoin_target_block) =
                 bitcoin.apply_defaults(is_testnet)?;
             let monero_daemon_address = monero.apply_defaults(is_testnet);
 
             Arguments {
-                env_config: env_config_from(is_testnet),
+                env_config: env_config_from(is_testnet)
+                    .with_max_swap_duration_hours(max_swap_duration_hours),
                 debug,
                 json,
+                otlp_endpoint,
                 data_dir: data::data_dir_from(data, is_testnet)?,
                 cmd: Command::Resume {
                     swap_id,
-                    bitcoin_electrum_rpc_url,
+                    bitcoin_electrum_rpc_urls,
+                    bitcoin_target_block,
+                    monero_daemon_address,
+                    monero_light_wallet_server,
+                    tor_socks5_port,
+                    confirm_lock,
+                    watchtower,
+                },
+            }
+        }
+        RawCommand::Dashboard {
+            bitcoin,
+            monero,
+            tor: Tor { tor_socks5_port },
+        } => {
+            let (bitcoin_electrum_rpc_urls, bitcoin_target_block) =
+                bitcoin.apply_defaults(is_testnet)?;
+            let monero_daemon_address = monero.apply_defaults(is_testnet);
+
+            Arguments {
+                env_config: env_config_from(is_testnet),
+                debug,
+                json,
+                otlp_endpoint,
+                data_dir: data::data_dir_from(data, is_testnet)?,
+                cmd: Command::Dashboard {
+                    bitcoin_electrum_rpc_urls,
                     bitcoin_target_block,
                     monero_daemon_address,
                     tor_socks5_port,
@@ -185,66 +287,163 @@ where
         RawCommand::Cancel {
             swap_id: SwapId { swap_id },
             bitcoin,
+            force,
         } => {
-            let (bitcoin_electrum_rpc_url, bitcoin_target_block) =
+            let (bitcoin_electrum_rpc_urls, bitcoin_target_block) =
                 bitcoin.apply_defaults(is_testnet)?;
 
             Arguments {
                 env_config: env_config_from(is_testnet),
                 debug,
                 json,
+                otlp_endpoint,
                 data_dir: data::data_dir_from(data, is_testnet)?,
                 cmd: Command::Cancel {
                     swap_id,
-                    bitcoin_electrum_rpc_url,
+                    bitcoin_electrum_rpc_urls,
+                    bitcoin_target_block,
+                    force,
+                },
+            }
+        }
+        RawCommand::Artifacts {
+            swap_id: SwapId { swap_id },
+            bitcoin,
+        } => {
+            let (bitcoin_electrum_rpc_urls, bitcoin_target_block) =
+                bitcoin.apply_defaults(is_testnet)?;
+
+            Arguments {
+                env_config: env_config_from(is_testnet),
+                debug,
+                json,
+                otlp_endpoint,
+                data_dir: data::data_dir_from(data, is_testnet)?,
+                cmd: Command::Artifacts {
+                    swap_id,
+                    bitcoin_electrum_rpc_urls,
+                    bitcoin_target_block,
+                },
+            }
+        }
+        RawCommand::Info {
+            swap_id: SwapId { swap_id },
+            bitcoin,
+        } => {
+            let (bitcoin_electrum_rpc_urls, bitcoin_target_block) =
+                bitcoin.apply_defaults(is_testnet)?;
+
+            Arguments {
+                env_config: env_config_from(is_testnet),
+                debug,
+                json,
+                otlp_endpoint,
+                data_dir: data::data_dir_from(data, is_testnet)?,
+                cmd: Command::Info {
+                    swap_id,
+                    bitcoin_electrum_rpc_urls,
                     bitcoin_target_block,
                 },
             }
         }
+        RawCommand::Inspect {
+            swap_id: SwapId { swap_id },
+            dot,
+            mermaid,
+        } => Arguments {
+            env_config: env_config_from(is_testnet),
+            debug,
+            json,
+            otlp_endpoint,
+            data_dir: data::data_dir_from(data, is_testnet)?,
+            cmd: Command::Inspect {
+                swap_id,
+                format: match (dot, mermaid) {
+                    (true, _) => inspect::Format::Dot,
+                    (_, true) => inspect::Format::Mermaid,
+                    _ => inspect::Format::Text,
+                },
+            },
+        },
+        RawCommand::Presigned {
+            swap_id: SwapId { swap_id },
+        } => Arguments {
+            env_config: env_config_from(is_testnet),
+            debug,
+            json,
+            otlp_endpoint,
+            data_dir: data::data_dir_from(data, is_testnet)?,
+            cmd: Command::Presigned { swap_id },
+        },
+        RawCommand::Receipt {
+            swap_id: SwapId { swap_id },
+        } => Arguments {
+            env_config: env_config_from(is_testnet),
+            debug,
+            json,
+            otlp_endpoint,
+            data_dir: data::data_dir_from(data, is_testnet)?,
+            cmd: Command::Receipt { swap_id },
+        },
+        RawCommand::VerifyReceipt { file } => Arguments {
+            env_config: env_config_from(is_testnet),
+            debug,
+            json,
+            otlp_endpoint,
+            data_dir: data::data_dir_from(data, is_testnet)?,
+            cmd: Command::VerifyReceipt { file },
+        },
         RawCommand::Refund {
             swap_id: SwapId { swap_id },
             bitcoin,
+            force,
         } => {
-            let (bitcoin_electrum_rpc_url, bitcoin_target_block) =
+            let (bitcoin_electrum_rpc_urls, bitcoin_target_block) =
                 bitcoin.apply_defaults(is_testnet)?;
 
             Arguments {
                 env_config: env_config_from(is_testnet),
                 debug,
                 json,
+                otlp_endpoint,
                 data_dir: data::data_dir_from(data, is_testnet)?,
                 cmd: Command::Refund {
                     swap_id,
-                    bitcoin_electrum_rpc_url,
+                    bitcoin_electrum_rpc_urls,
                     bitcoin_target_block,
+                    force,
                 },
             }
         }
         RawCommand::ListSellers {
             rendezvous_point,
             tor: Tor { tor_socks5_port },
+            btc,
         } => Arguments {
             env_config: env_config_from(is_testnet),
             debug,
             json,
+            otlp_endpoint,
             data_dir: data::data_dir_from(data, is_testnet)?,
             cmd: Command::ListSellers {
                 rendezvous_point,
                 namespace: rendezvous_namespace_from(is_testnet),
                 tor_socks5_port,
+                btc,
             },
         },
         RawCommand::ExportBitcoinWallet { bitcoin } => {
-            let (bitcoin_electrum_rpc_url, bitcoin_target_block) =
+            let (bitcoin_electrum_rpc_urls, bitcoin_target_block) =
                 bitcoin.apply_defaults(is_testnet)?;
 
             Arguments {
                 env_config: env_config_from(is_testnet),
                 debug,
                 json,
+                otlp_endpoint,
                 data_dir: data::data_dir_from(data, is_testnet)?,
                 cmd: Command::ExportBitcoinWallet {
-                    bitcoin_electrum_rpc_url,
+                    bitcoin_electrum_rpc_urls,
                     bitcoin_target_block,
                 },
             }
@@ -253,11 +452,99 @@ where
             env_config: env_config_from(is_testnet),
             debug,
             json,
+            otlp_endpoint,
             data_dir: data::data_dir_from(data, is_testnet)?,
             cmd: Command::MoneroRecovery {
                 swap_id: swap_id.swap_id,
             },
         },
+        RawCommand::Rescan {
+            swap_id: SwapId { swap_id },
+            monero,
+        } => Arguments {
+            env_config: env_config_from(is_testnet),
+            debug,
+            json,
+            otlp_endpoint,
+            data_dir: data::data_dir_from(data, is_testnet)?,
+            cmd: Command::Rescan {
+                swap_id,
+                monero_daemon_address: monero.apply_defaults(is_testnet),
+            },
+        },
+        RawCommand::Export {
+            swap_id: SwapId { swap_id },
+            file,
+        } => Arguments {
+            env_config: env_config_from(is_testnet),
+            debug,
+            json,
+            otlp_endpoint,
+            data_dir: data::data_dir_from(data, is_testnet)?,
+            cmd: Command::Export { swap_id, file },
+        },
+        RawCommand::Import { file, key } => Arguments {
+            env_config: env_config_from(is_testnet),
+            debug,
+            json,
+            otlp_endpoint,
+            data_dir: data::data_dir_from(data, is_testnet)?,
+            cmd: Command::Import { file, key },
+        },
+        RawCommand::Check {
+            seller,
+            bitcoin,
+            monero,
+            tor: Tor { tor_socks5_port },
+        } => {
+            let (bitcoin_electrum_rpc_urls, bitcoin_target_block) =
+                bitcoin.apply_defaults(is_testnet)?;
+
+            Arguments {
+                env_config: env_config_from(is_testnet),
+                debug,
+                json,
+                otlp_endpoint,
+                data_dir: data::data_dir_from(data, is_testnet)?,
+                cmd: Command::Check {
+                    seller,
+                    bitcoin_electrum_rpc_urls,
+                    bitcoin_target_block,
+                    monero_daemon_address: monero.apply_defaults(is_testnet),
+                    tor_socks5_port,
+                },
+            }
+        }
+        RawCommand::Monero(MoneroCommand::SweepSwapWallets { monero }) => Arguments {
+            env_config: env_config_from(is_testnet),
+            debug,
+            json,
+            otlp_endpoint,
+            data_dir: data::data_dir_from(data, is_testnet)?,
+            cmd: Command::SweepSwapWallets {
+                monero_daemon_address: monero.apply_defaults(is_testnet),
+            },
+        },
+        RawCommand::Version => {
+            let build_info = BuildInfo::current();
+            let message = if json {
+                serde_json::to_string(&build_info)?
+            } else {
+                build_info.to_string()
+            };
+
+            return Ok(ParseResult::PrintAndExitZero { message });
+        }
+        RawCommand::Completions { shell } => {
+            let mut app = RawArguments::clap();
+            let name = app.get_name().to_owned();
+            let mut script = Vec::new();
+            app.gen_completions_to(name, shell, &mut script);
+
+            return Ok(ParseResult::PrintAndExitZero {
+                message: String::from_utf8(script)?,
+            });
+        }
     };
 
     Ok(ParseResult::Arguments(arguments))
@@ -267,54 +554,126 @@ where
 pub enum Command {
     BuyXmr {
         seller: Multiaddr,
-        bitcoin_electrum_rpc_url: Url,
+        bitcoin_electrum_rpc_urls: Vec<Url>,
         bitcoin_target_block: usize,
         bitcoin_change_address: bitcoin::Address,
         monero_receive_address: monero::Address,
         monero_daemon_address: String,
+        monero_light_wallet_server: Option<Url>,
         tor_socks5_port: u16,
+        fiat_currency: Option<fiat::Currency>,
+        receive_xmr: Option<monero::Amount>,
+        max_price: Option<Amount>,
+        max_price_deviation: Option<Decimal>,
+        confirm_lock: bool,
+        watchtower: Option<Multiaddr>,
+    },
+    History {
+        explorer: Explorer,
+        query: SwapQuery,
     },
-    History,
     Config,
     WithdrawBtc {
-        bitcoin_electrum_rpc_url: Url,
+        bitcoin_electrum_rpc_urls: Vec<Url>,
         bitcoin_target_block: usize,
         amount: Option<Amount>,
         address: Address,
     },
+    RefundDeposit {
+        bitcoin_electrum_rpc_urls: Vec<Url>,
+        bitcoin_target_block: usize,
+        address: Address,
+    },
     Balance {
-        bitcoin_electrum_rpc_url: Url,
+        bitcoin_electrum_rpc_urls: Vec<Url>,
         bitcoin_target_block: usize,
     },
     Resume {
         swap_id: Uuid,
-        bitcoin_electrum_rpc_url: Url,
+        bitcoin_electrum_rpc_urls: Vec<Url>,
+        bitcoin_target_block: usize,
+        monero_daemon_address: String,
+        monero_light_wallet_server: Option<Url>,
+        tor_socks5_port: u16,
+        confirm_lock: bool,
+        watchtower: Option<Multiaddr>,
+    },
+    Dashboard {
+        bitcoin_electrum_rpc_urls: Vec<Url>,
         bitcoin_target_block: usize,
         monero_daemon_address: String,
         tor_socks5_port: u16,
     },
     Cancel {
         swap_id: Uuid,
-        bitcoin_electrum_rpc_url: Url,
+        bitcoin_electrum_rpc_urls: Vec<Url>,
         bitcoin_target_block: usize,
+        force: bool,
     },
     Refund {
         swap_id: Uuid,
-        bitcoin_electrum_rpc_url: Url,
+        bitcoin_electrum_rpc_urls: Vec<Url>,
+        bitcoin_target_block: usize,
+        force: bool,
+    },
+    Artifacts {
+        swap_id: Uuid,
+        bitcoin_electrum_rpc_urls: Vec<Url>,
+        bitcoin_target_block: usize,
+    },
+    Info {
+        swap_id: Uuid,
+        bitcoin_electrum_rpc_urls: Vec<Url>,
         bitcoin_target_block: usize,
     },
+    Inspect {
+        swap_id: Uuid,
+        format: inspect::Format,
+    },
+    Presigned {
+        swap_id: Uuid,
+    },
+    Receipt {
+        swap_id: Uuid,
+    },
+    VerifyReceipt {
+        file: PathBuf,
+    },
     ListSellers {
         rendezvous_point: Multiaddr,
         namespace: XmrBtcNamespace,
         tor_socks5_port: u16,
+        btc: Option<Amount>,
     },
     ExportBitcoinWallet {
-        bitcoin_electrum_rpc_url: Url,
+        bitcoin_electrum_rpc_urls: Vec<Url>,
         bitcoin_target_block: usize,
     },
     MoneroRecovery {
         swap_id: Uuid,
     },
+    Rescan {
+        swap_id: Uuid,
+        monero_daemon_address: String,
+    },
+    SweepSwapWallets {
+        monero_daemon_address: String,
+    },
+    Export {
+        swap_id: Uuid,
+        file: PathBuf,
+    },
+    Import {
+        file: PathBuf,
+        key: ExportKey,
+    },
+    Check {
+        seller: Option<Multiaddr>,
+        bitcoin_electrum_rpc_urls: Vec<Url>,
+        bitcoin_target_block: usize,
+        monero_daemon_address: String,
+        tor_socks5_port: u16,
+    },
 }
 
 #[derive(structopt::StructOpt, Debug)]
@@ -335,6 +694,7 @@ struct RawArguments {
 
     #[structopt(
         long = "--data-base-dir",
+        visible_alias = "data-dir",
         help = "The base data directory to be used for mainnet / testnet specific data like database, wallets etc"
     )]
     data: Option<PathBuf>,
@@ -349,6 +709,12 @@ struct RawArguments {
     )]
     json: bool,
 
+    #[structopt(
+        long = "otlp-endpoint",
+        help = "Export the tracing spans to this OpenTelemetry collector using OTLP over gRPC, e.g. http://localhost:4317"
+    )]
+    otlp_endpoint: Option<Url>,
+
     #[structopt(subcommand)]
     cmd: RawCommand,
 }
@@ -356,6 +722,7 @@ struct RawArguments {
 #[derive(structopt::StructOpt, Debug)]
 enum RawCommand {
     /// Start a BTC for XMR swap
+    #[structopt(visible_alias = "buy")]
     BuyXmr {
         #[structopt(flatten)]
         seller: Seller,
@@ -380,9 +747,52 @@ enum RawCommand {
 
         #[structopt(flatten)]
         tor: Tor,
+
+        #[structopt(
+            long = "fiat-currency",
+            help = "Estimate the value of the swap in this currency, either USD or EUR. The price of Bitcoin is requested from Kraken."
+        )]
+        fiat_currency: Option<fiat::Currency>,
+
+        #[structopt(
+            long = "receive-xmr",
+            help = "Buy exactly this amount of monero, e.g. 10 or 10XMR. The bitcoin needed is computed from the seller's price, anything deposited beyond it stays in the wallet."
+        )]
+        receive_xmr: Option<monero::Amount>,
+
+        #[structopt(flatten)]
+        price: MaxPrice,
+
+        #[structopt(flatten)]
+        lock: LockConfirmation,
+
+        #[structopt(flatten)]
+        duration: MaxSwapDuration,
+
+        #[structopt(flatten)]
+        watchtower: Watchtower,
+
+        #[structopt(flatten)]
+        light_wallet: LightWalletServer,
     },
     /// Show a list of past, ongoing and completed swaps
-    History,
+    #[structopt(visible_alias = "ls")]
+    History {
+        #[structopt(
+            long = "bitcoin-explorer",
+            help = "Link Bitcoin transactions to this block explorer instead of mempool.space. Transactions have to be served under <url>/tx/<txid>."
+        )]
+        bitcoin_explorer: Option<Url>,
+
+        #[structopt(
+            long = "monero-explorer",
+            help = "Link Monero transactions to this block explorer instead of xmrchain.net. Transactions have to be served under <url>/tx/<txid>."
+        )]
+        monero_explorer: Option<Url>,
+
+        #[structopt(flatten)]
+        query: SwapQuery,
+    },
     #[structopt(about = "Prints the current config")]
     Config,
     #[structopt(about = "Allows withdrawing BTC from the internal Bitcoin wallet.")]
@@ -392,16 +802,31 @@ enum RawCommand {
 
         #[structopt(
             long = "amount",
-            help = "Optionally specify the amount of Bitcoin to be withdrawn. If not specified the wallet will be drained."
+            help = "Optionally specify the amount of Bitcoin to be withdrawn, e.g. '0.5 BTC'. If not specified the wallet will be drained.",
+            parse(try_from_str = parse_amount)
         )]
         amount: Option<Amount>,
         #[structopt(long = "address", help = "The address to receive the Bitcoin.")]
         address: Address,
     },
+    /// Return the Bitcoin deposited into the internal wallet, e.g. after the
+    /// seller rejected the swap. The whole balance is sent, which is refused
+    /// while a swap did not lock its Bitcoin yet.
+    RefundDeposit {
+        #[structopt(flatten)]
+        bitcoin: Bitcoin,
+
+        #[structopt(help = "The address to return the deposit to")]
+        address: Address,
+    },
     #[structopt(about = "Prints the Bitcoin balance.")]
     Balance {
-        #[structopt(long = "electrum-rpc", help = "Provide the Bitcoin Electrum RPC URL")]
-        bitcoin_electrum_rpc_url: Option<Url>,
+        #[structopt(
+            long = "electrum-rpc",
+            number_of_values = 1,
+            help = "Provide the Bitcoin Electrum RPC URL, repeat to add fallback servers"
+        )]
+        bitcoin_electrum_rpc_urls: Vec<Url>,
     },
     /// Resume a swap
     Resume {
@@ -416,37 +841,137 @@ enum RawCommand {
 
         #[structopt(flatten)]
         tor: Tor,
+
+        #[structopt(flatten)]
+        lock: LockConfirmation,
+
+        #[structopt(flatten)]
+        duration: MaxSwapDuration,
+
+        #[structopt(flatten)]
+        watchtower: Watchtower,
+
+        #[structopt(flatten)]
+        light_wallet: LightWalletServer,
+    },
+    /// Show the open swaps in a dashboard that can resume, cancel and inspect
+    /// them (requires the dashboard feature)
+    Dashboard {
+        #[structopt(flatten)]
+        bitcoin: Bitcoin,
+
+        #[structopt(flatten)]
+        monero: Monero,
+
+        #[structopt(flatten)]
+        tor: Tor,
     },
-    /// Force submission of the cancel transaction overriding the protocol state
-    /// machine and blockheight checks (expert users only)
+    /// Submit the cancel transaction once the cancel timelock expired, overriding
+    /// the protocol state machine (expert users only)
     Cancel {
         #[structopt(flatten)]
         swap_id: SwapId,
 
         #[structopt(flatten)]
         bitcoin: Bitcoin,
+
+        #[structopt(
+            long = "force",
+            help = "Submit the cancel transaction even if the cancel timelock did not expire according to the Electrum server"
+        )]
+        force: bool,
     },
-    /// Force submission of the refund transaction overriding the protocol state
-    /// machine and blockheight checks (expert users only)
+    /// Submit the refund transaction once the cancel timelock expired, overriding
+    /// the protocol state machine (expert users only)
     Refund {
         #[structopt(flatten)]
         swap_id: SwapId,
 
         #[structopt(flatten)]
         bitcoin: Bitcoin,
-    },
-    /// Discover and list sellers (i.e. ASB providers)
-    ListSellers {
+
         #[structopt(
-            long,
-            help = "Address of the rendezvous point you want to use to discover ASBs"
+            long = "force",
+            help = "Submit the refund transaction even if the cancel timelock did not expire according to the Electrum server or the swap was already refunded"
         )]
-        rendezvous_point: Multiaddr,
+        force: bool,
+    },
+    /// Write the published transactions, the Monero transfer proof and the log
+    /// file of a swap to a directory, e.g. to share them when asking for support
+    Artifacts {
+        #[structopt(flatten)]
+        swap_id: SwapId,
 
         #[structopt(flatten)]
-        tor: Tor,
+        bitcoin: Bitcoin,
     },
-    /// Print the internal bitcoin wallet descriptor
+    /// Show the state, amounts, transactions, timelocks and errors of a swap,
+    /// as JSON with --json
+    Info {
+        #[structopt(flatten)]
+        swap_id: SwapId,
+
+        #[structopt(flatten)]
+        bitcoin: Bitcoin,
+    },
+    /// Show the states a swap went through with the time each was entered,
+    /// optionally as a Graphviz or Mermaid graph
+    Inspect {
+        #[structopt(flatten)]
+        swap_id: SwapId,
+
+        #[structopt(
+            long,
+            conflicts_with = "mermaid",
+            help = "Print the states as a Graphviz graph"
+        )]
+        dot: bool,
+
+        #[structopt(long, help = "Print the states as a Mermaid graph")]
+        mermaid: bool,
+    },
+    /// Print the cancel and refund transactions that were signed when the
+    /// Bitcoin was locked, as raw transactions any Bitcoin node can publish
+    Presigned {
+        #[structopt(flatten)]
+        swap_id: SwapId,
+    },
+    /// Print the receipt of a completed swap, signed by both you and the
+    /// seller
+    Receipt {
+        #[structopt(flatten)]
+        swap_id: SwapId,
+    },
+    /// Check that a receipt printed by the receipt command is signed by both
+    /// peers of the swap
+    VerifyReceipt {
+        #[structopt(
+            long = "file",
+            help = "The file the receipt is stored in",
+            parse(from_os_str)
+        )]
+        file: PathBuf,
+    },
+    /// Discover and list sellers (i.e. ASB providers)
+    #[structopt(visible_alias = "sellers")]
+    ListSellers {
+        #[structopt(
+            long,
+            help = "Address of the rendezvous point you want to use to discover ASBs"
+        )]
+        rendezvous_point: Multiaddr,
+
+        #[structopt(flatten)]
+        tor: Tor,
+
+        #[structopt(
+            long = "btc",
+            help = "Only list sellers that can serve a swap of this amount, e.g. '0.1 BTC'.",
+            parse(try_from_str = parse_amount)
+        )]
+        btc: Option<Amount>,
+    },
+    /// Print the internal bitcoin wallet descriptor
     ExportBitcoinWallet {
         #[structopt(flatten)]
         bitcoin: Bitcoin,
@@ -458,6 +983,89 @@ enum RawCommand {
         #[structopt(flatten)]
         swap_id: SwapId,
     },
+    /// Rescan the generated Monero redeem wallet of a swap, starting from the
+    /// blockheight recorded when the swap was started. Only needed in case the
+    /// wallet does not pick up the locked Monero. This can only be used for
+    /// swaps that are in a `btc is redeemed` state.
+    Rescan {
+        #[structopt(flatten)]
+        swap_id: SwapId,
+
+        #[structopt(flatten)]
+        monero: Monero,
+    },
+    /// Contains sub-commands for managing the Monero wallets generated for
+    /// swaps
+    Monero(MoneroCommand),
+    /// Export an unfinished swap to a file in order to resume it on another
+    /// machine. The file is encrypted with a key that is printed once. The
+    /// swap can no longer be resumed on this machine unless it is imported
+    /// again.
+    Export {
+        #[structopt(flatten)]
+        swap_id: SwapId,
+
+        #[structopt(
+            long = "file",
+            help = "The file the encrypted swap is written to",
+            parse(from_os_str)
+        )]
+        file: PathBuf,
+    },
+    /// Import a swap that was exported on another machine
+    Import {
+        #[structopt(
+            long = "file",
+            help = "The file written by the export command",
+            parse(from_os_str)
+        )]
+        file: PathBuf,
+
+        #[structopt(long = "key", help = "The key printed by the export command")]
+        key: ExportKey,
+    },
+    /// Check that everything needed for a swap is in place before starting
+    /// one: the Bitcoin and Monero backends, the seller, the wallet balance,
+    /// the local clock and the available disk space
+    Check {
+        #[structopt(
+            long,
+            help = "The address of the seller to check. Must include a peer ID part, i.e. `/p2p/`"
+        )]
+        seller: Option<Multiaddr>,
+
+        #[structopt(flatten)]
+        bitcoin: Bitcoin,
+
+        #[structopt(flatten)]
+        monero: Monero,
+
+        #[structopt(flatten)]
+        tor: Tor,
+    },
+    /// Print the version, git commit, target, features and protocols of this
+    /// build. Please include the output in bug reports
+    Version,
+    /// Print the completion script for a shell, e.g. `swap completions bash >
+    /// /etc/bash_completion.d/swap`
+    Completions {
+        #[structopt(
+            possible_values = &clap::Shell::variants(),
+            case_insensitive = true,
+            help = "The shell to complete the commands of the CLI in"
+        )]
+        shell: clap::Shell,
+    },
+}
+
+#[derive(structopt::StructOpt, Debug)]
+enum MoneroCommand {
+    /// List the Monero wallets left behind by swaps, sweep their unlocked
+    /// balance to the receive address of the swap and remove empty wallets
+    SweepSwapWallets {
+        #[structopt(flatten)]
+        monero: Monero,
+    },
 }
 
 #[derive(structopt::StructOpt, Debug)]
@@ -483,8 +1091,12 @@ impl Monero {
 
 #[derive(structopt::StructOpt, Debug)]
 struct Bitcoin {
-    #[structopt(long = "electrum-rpc", help = "Provide the Bitcoin Electrum RPC URL")]
-    bitcoin_electrum_rpc_url: Option<Url>,
+    #[structopt(
+        long = "electrum-rpc",
+        number_of_values = 1,
+        help = "Provide the Bitcoin Electrum RPC URL, repeat to add fallback servers"
+    )]
+    bitcoin_electrum_rpc_urls: Vec<Url>,
 
     #[structopt(
         long = "bitcoin-target-block",
@@ -494,13 +1106,13 @@ struct Bitcoin {
 }
 
 impl Bitcoin {
-    fn apply_defaults(self, testnet: bool) -> Result<(Url, usize)> {
-        let bitcoin_electrum_rpc_url = if let Some(url) = self.bitcoin_electrum_rpc_url {
-            url
+    fn apply_defaults(self, testnet: bool) -> Result<(Vec<Url>, usize)> {
+        let bitcoin_electrum_rpc_urls = if !self.bitcoin_electrum_rpc_urls.is_empty() {
+            self.bitcoin_electrum_rpc_urls
         } else if testnet {
-            Url::from_str(DEFAULT_ELECTRUM_RPC_URL_TESTNET)?
+            vec![Url::from_str(DEFAULT_ELECTRUM_RPC_URL_TESTNET)?]
         } else {
-            Url::from_str(DEFAULT_ELECTRUM_RPC_URL)?
+            vec![Url::from_str(DEFAULT_ELECTRUM_RPC_URL)?]
         };
 
         let bitcoin_target_block = if let Some(target_block) = self.bitcoin_target_block {
@@ -511,7 +1123,7 @@ impl Bitcoin {
             DEFAULT_BITCOIN_CONFIRMATION_TARGET
         };
 
-        Ok((bitcoin_electrum_rpc_url, bitcoin_target_block))
+        Ok((bitcoin_electrum_rpc_urls, bitcoin_target_block))
     }
 }
 
@@ -525,6 +1137,58 @@ struct Tor {
     tor_socks5_port: u16,
 }
 
+#[derive(structopt::StructOpt, Debug)]
+struct MaxPrice {
+    #[structopt(
+        long = "max-price-btc-per-xmr",
+        help = "Abort the swap before any Bitcoin is locked if the seller asks more than this for one XMR, e.g. 0.007 or 700000sat",
+        parse(try_from_str = parse_amount)
+    )]
+    max_price: Option<Amount>,
+
+    #[structopt(
+        long = "max-price-deviation",
+        help = "Abort the swap before any Bitcoin is locked if the seller's price is more than this many percent above the last XMR/BTC trade on Kraken, e.g. 3.5"
+    )]
+    max_price_deviation: Option<Decimal>,
+}
+
+#[derive(structopt::StructOpt, Debug)]
+struct LockConfirmation {
+    #[structopt(
+        long = "confirm-lock",
+        help = "Show the amounts, fees and seller and ask for confirmation before the Bitcoin is locked"
+    )]
+    confirm_lock: bool,
+}
+
+#[derive(structopt::StructOpt, Debug)]
+struct Watchtower {
+    #[structopt(
+        long = "watchtower",
+        help = "Register the presigned cancel transaction with the watchtower at this address, which publishes it if the swap is not cancelled in time. The refund is left to the CLI. The address has to include the peer id of the watchtower."
+    )]
+    watchtower: Option<Multiaddr>,
+}
+
+#[derive(structopt::StructOpt, Debug)]
+struct LightWalletServer {
+    #[structopt(
+        long = "monero-light-wallet-server",
+        help = "Not supported yet: a light wallet server cannot sweep the Monero of a swap, swaps are refused with this option."
+    )]
+    monero_light_wallet_server: Option<Url>,
+}
+
+#[derive(structopt::StructOpt, Debug)]
+struct MaxSwapDuration {
+    #[structopt(
+        long = "max-swap-duration",
+        help = "Stop waiting for the seller once the swap runs for this many hours. A swap that did not lock the Bitcoin yet is aborted, otherwise it is refunded once the cancel timelock expired."
+    )]
+    max_swap_duration_hours: Option<u64>,
+}
+
 #[derive(structopt::StructOpt, Debug)]
 struct SwapId {
     #[structopt(
@@ -538,7 +1202,7 @@ struct SwapId {
 struct Seller {
     #[structopt(
         long,
-        help = "The seller's address. Must include a peer ID part, i.e. `/p2p/`"
+        help = "The seller's address. Must include a peer ID part, i.e. `/p2p/`. Only the peer ID part, e.g. `/p2p/<peer-id>`, dials the addresses the seller was reachable at in earlier swaps"
     )]
     seller: Multiaddr,
 }
@@ -595,7 +1259,7 @@ fn bitcoin_address(address: Address, is_testnet: bool) -> Result<Address> {
     Ok(address)
 }
 
-fn validate_monero_address(
+pub fn validate_monero_address(
     address: monero::Address,
     testnet: bool,
 ) -> Result<monero::Address, MoneroAddressNetworkMismatch> {
@@ -615,7 +1279,10 @@ fn validate_monero_address(
     Ok(address)
 }
 
-fn validate_bitcoin_address(address: bitcoin::Address, testnet: bool) -> Result<bitcoin::Address> {
+pub fn validate_bitcoin_address(
+    address: bitcoin::Address,
+    testnet: bool,
+) -> Result<bitcoin::Address> {
     let expected_network = if testnet {
         bitcoin::Network::Testnet
     } else {
@@ -665,180 +1332,943 @@ pub struct BitcoinAddressNetworkMismatch {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::database::SwapStatus;
     use crate::tor::DEFAULT_SOCKS5_PORT;
+    use rand::rngs::OsRng;
+
+    const BINARY_NAME: &str = "swap";
+
+    const TESTNET: &str = "testnet";
+    const MAINNET: &str = "mainnet";
+
+    const MONERO_STAGENET_ADDRESS: &str = "53gEuGZUhP9JMEBZoGaFNzhwEgiG7hwQdMCqFxiyiTeFPmkbt1mAoNybEUvYBKHcnrSgxnVWgZsTvRBaHBNXPa8tHiCU51a";
+    const BITCOIN_TESTNET_ADDRESS: &str = "tb1qr3em6k3gfnyl8r7q0v7t4tlnyxzgxma3lressv";
+    const MONERO_MAINNET_ADDRESS: &str = "44Ato7HveWidJYUAVw5QffEcEtSH1DwzSP3FPPkHxNAS4LX9CqgucphTisH978FLHE34YNEx7FcbBfQLQUU8m3NUC4VqsRa";
+    const BITCOIN_MAINNET_ADDRESS: &str = "bc1qe4epnfklcaa0mun26yz5g8k24em5u9f92hy325";
+    const MULTI_ADDRESS: &str =
+        "/ip4/127.0.0.1/tcp/9939/p2p/12D3KooWCdMKjesXMJz1SiZ7HgotrxuqhQJbP5sgBm2BwP1cqThi";
+    const SWAP_ID: &str = "ea030832-3be9-454f-bb98-5ea9a788406b";
+
+    #[test]
+    fn given_buy_xmr_on_mainnet_then_defaults_to_mainnet() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "buy-xmr",
+            "--receive-address",
+            MONERO_MAINNET_ADDRESS,
+            "--change-address",
+            BITCOIN_MAINNET_ADDRESS,
+            "--seller",
+            MULTI_ADDRESS,
+        ];
+
+        let expected_args = ParseResult::Arguments(Arguments::buy_xmr_mainnet_defaults());
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(expected_args, args);
+    }
+
+    #[test]
+    fn given_buy_xmr_on_testnet_then_defaults_to_testnet() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "--testnet",
+            "buy-xmr",
+            "--receive-address",
+            MONERO_STAGENET_ADDRESS,
+            "--change-address",
+            BITCOIN_TESTNET_ADDRESS,
+            "--seller",
+            MULTI_ADDRESS,
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(
+            args,
+            ParseResult::Arguments(Arguments::buy_xmr_testnet_defaults())
+        );
+    }
+
+    #[test]
+    fn given_buy_xmr_with_multiple_electrum_rpc_urls_then_all_are_used_in_order() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "buy-xmr",
+            "--receive-address",
+            MONERO_MAINNET_ADDRESS,
+            "--change-address",
+            BITCOIN_MAINNET_ADDRESS,
+            "--seller",
+            MULTI_ADDRESS,
+            "--electrum-rpc",
+            "ssl://electrum.example.com:50002",
+            "--electrum-rpc",
+            DEFAULT_ELECTRUM_RPC_URL,
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        let mut expected_args = Arguments::buy_xmr_mainnet_defaults();
+        if let Command::BuyXmr {
+            bitcoin_electrum_rpc_urls,
+            ..
+        } = &mut expected_args.cmd
+        {
+            *bitcoin_electrum_rpc_urls = vec![
+                Url::from_str("ssl://electrum.example.com:50002").unwrap(),
+                Url::from_str(DEFAULT_ELECTRUM_RPC_URL).unwrap(),
+            ];
+        }
+        assert_eq!(args, ParseResult::Arguments(expected_args));
+    }
+
+    #[test]
+    fn given_buy_xmr_on_mainnet_with_testnet_address_then_fails() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "buy-xmr",
+            "--receive-address",
+            MONERO_STAGENET_ADDRESS,
+            "--change-address",
+            BITCOIN_TESTNET_ADDRESS,
+            "--seller",
+            MULTI_ADDRESS,
+        ];
+
+        let err = parse_args_and_apply_defaults(raw_ars).unwrap_err();
+
+        assert_eq!(
+            err.downcast_ref::<MoneroAddressNetworkMismatch>().unwrap(),
+            &MoneroAddressNetworkMismatch {
+                expected: monero::Network::Mainnet,
+                actual: monero::Network::Stagenet
+            }
+        );
+    }
+
+    #[test]
+    fn given_buy_xmr_on_testnet_with_mainnet_address_then_fails() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "--testnet",
+            "buy-xmr",
+            "--receive-address",
+            MONERO_MAINNET_ADDRESS,
+            "--change-address",
+            BITCOIN_MAINNET_ADDRESS,
+            "--seller",
+            MULTI_ADDRESS,
+        ];
+
+        let err = parse_args_and_apply_defaults(raw_ars).unwrap_err();
+
+        assert_eq!(
+            err.downcast_ref::<MoneroAddressNetworkMismatch>().unwrap(),
+            &MoneroAddressNetworkMismatch {
+                expected: monero::Network::Stagenet,
+                actual: monero::Network::Mainnet
+            }
+        );
+    }
+
+    #[test]
+    fn given_buy_xmr_on_testnet_with_stagenet_subaddress_then_accepts_subaddress() {
+        let private_view_key = monero::PrivateKey::from_scalar(monero::Scalar::random(&mut OsRng));
+        let public_spend_key = monero::PublicKey::from_private_key(
+            &monero::PrivateKey::from_scalar(monero::Scalar::random(&mut OsRng)),
+        );
+        let subaddress = monero::subaddress(
+            monero::Network::Stagenet,
+            private_view_key,
+            public_spend_key,
+            1,
+            4,
+        );
+        let subaddress_string = subaddress.to_string();
+
+        let raw_ars = vec![
+            BINARY_NAME,
+            "--testnet",
+            "buy-xmr",
+            "--receive-address",
+            &subaddress_string,
+            "--change-address",
+            BITCOIN_TESTNET_ADDRESS,
+            "--seller",
+            MULTI_ADDRESS,
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        match args {
+            ParseResult::Arguments(Arguments {
+                cmd:
+                    Command::BuyXmr {
+                        monero_receive_address,
+                        ..
+                    },
+                ..
+            }) => assert_eq!(monero_receive_address, subaddress),
+            _ => panic!("expected buy-xmr command"),
+        }
+    }
+
+    #[test]
+    fn given_buy_xmr_with_fiat_currency_then_currency_is_set() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "buy-xmr",
+            "--receive-address",
+            MONERO_MAINNET_ADDRESS,
+            "--change-address",
+            BITCOIN_MAINNET_ADDRESS,
+            "--seller",
+            MULTI_ADDRESS,
+            "--fiat-currency",
+            "eur",
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        match args {
+            ParseResult::Arguments(Arguments {
+                cmd: Command::BuyXmr { fiat_currency, .. },
+                ..
+            }) => assert_eq!(fiat_currency, Some(fiat::Currency::Eur)),
+            _ => panic!("expected buy-xmr command"),
+        }
+    }
+
+    #[test]
+    fn given_buy_xmr_with_max_price_then_limits_are_set() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "buy-xmr",
+            "--change-address",
+            BITCOIN_MAINNET_ADDRESS,
+            "--receive-address",
+            MONERO_MAINNET_ADDRESS,
+            "--seller",
+            MULTI_ADDRESS,
+            "--max-price-btc-per-xmr",
+            "0.007",
+            "--max-price-deviation",
+            "3.5",
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        match args {
+            ParseResult::Arguments(Arguments {
+                cmd:
+                    Command::BuyXmr {
+                        max_price,
+                        max_price_deviation,
+                        ..
+                    },
+                ..
+            }) => {
+                assert_eq!(max_price, Some(Amount::from_sat(700_000)));
+                assert_eq!(max_price_deviation, Some(Decimal::new(35, 1)));
+            }
+            _ => panic!("expected buy-xmr command"),
+        }
+    }
+
+    #[test]
+    fn given_buy_xmr_with_receive_xmr_then_amount_is_set() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "buy-xmr",
+            "--receive-address",
+            MONERO_MAINNET_ADDRESS,
+            "--change-address",
+            BITCOIN_MAINNET_ADDRESS,
+            "--seller",
+            MULTI_ADDRESS,
+            "--receive-xmr",
+            "10",
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        match args {
+            ParseResult::Arguments(Arguments {
+                cmd: Command::BuyXmr { receive_xmr, .. },
+                ..
+            }) => assert_eq!(
+                receive_xmr,
+                Some(monero::Amount::parse_monero("10").unwrap())
+            ),
+            _ => panic!("expected buy-xmr command"),
+        }
+    }
+
+    #[test]
+    fn given_resume_with_max_swap_duration_then_it_is_set() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "resume",
+            "--swap-id",
+            SWAP_ID,
+            "--max-swap-duration",
+            "12",
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        match args {
+            ParseResult::Arguments(Arguments { env_config, .. }) => assert_eq!(
+                env_config.max_swap_duration,
+                Some(std::time::Duration::from_secs(12 * 60 * 60))
+            ),
+            _ => panic!("expected resume command"),
+        }
+    }
+
+    #[test]
+    fn given_resume_with_confirm_lock_then_confirmation_is_required() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "resume",
+            "--swap-id",
+            SWAP_ID,
+            "--confirm-lock",
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        match args {
+            ParseResult::Arguments(Arguments {
+                cmd: Command::Resume { confirm_lock, .. },
+                ..
+            }) => assert!(confirm_lock),
+            _ => panic!("expected resume command"),
+        }
+    }
+
+    #[test]
+    fn given_dashboard_on_testnet_then_defaults_to_testnet() {
+        let raw_ars = vec![BINARY_NAME, "--testnet", "dashboard"];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        match args {
+            ParseResult::Arguments(Arguments {
+                cmd:
+                    Command::Dashboard {
+                        bitcoin_electrum_rpc_urls,
+                        bitcoin_target_block,
+                        monero_daemon_address,
+                        tor_socks5_port,
+                    },
+                ..
+            }) => {
+                assert_eq!(
+                    bitcoin_electrum_rpc_urls,
+                    electrum_rpc_urls(DEFAULT_ELECTRUM_RPC_URL_TESTNET)
+                );
+                assert_eq!(
+                    bitcoin_target_block,
+                    DEFAULT_BITCOIN_CONFIRMATION_TARGET_TESTNET
+                );
+                assert_eq!(
+                    monero_daemon_address,
+                    DEFAULT_MONERO_DAEMON_ADDRESS_STAGENET
+                );
+                assert_eq!(tor_socks5_port, DEFAULT_SOCKS5_PORT);
+            }
+            _ => panic!("expected dashboard command"),
+        }
+    }
+
+    #[test]
+    fn given_resume_with_watchtower_then_watchtower_is_set() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "resume",
+            "--swap-id",
+            SWAP_ID,
+            "--watchtower",
+            MULTI_ADDRESS,
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        match args {
+            ParseResult::Arguments(Arguments {
+                cmd: Command::Resume { watchtower, .. },
+                ..
+            }) => assert_eq!(
+                watchtower,
+                Some(Multiaddr::from_str(MULTI_ADDRESS).unwrap())
+            ),
+            _ => panic!("expected resume command"),
+        }
+    }
+
+    #[test]
+    fn given_resume_with_light_wallet_server_then_it_is_set() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "resume",
+            "--swap-id",
+            SWAP_ID,
+            "--monero-light-wallet-server",
+            "https://lws.example.com",
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        match args {
+            ParseResult::Arguments(Arguments {
+                cmd:
+                    Command::Resume {
+                        monero_light_wallet_server,
+                        ..
+                    },
+                ..
+            }) => assert_eq!(
+                monero_light_wallet_server,
+                Some(Url::parse("https://lws.example.com").unwrap())
+            ),
+            _ => panic!("expected resume command"),
+        }
+    }
+
+    #[test]
+    fn given_resume_on_mainnet_then_defaults_to_mainnet() {
+        let raw_ars = vec![BINARY_NAME, "resume", "--swap-id", SWAP_ID];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(
+            args,
+            ParseResult::Arguments(Arguments::resume_mainnet_defaults())
+        );
+    }
+
+    #[test]
+    fn given_resume_on_testnet_then_defaults_to_testnet() {
+        let raw_ars = vec![BINARY_NAME, "--testnet", "resume", "--swap-id", SWAP_ID];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(
+            args,
+            ParseResult::Arguments(Arguments::resume_testnet_defaults())
+        );
+    }
+
+    #[test]
+    fn given_cancel_on_mainnet_then_defaults_to_mainnet() {
+        let raw_ars = vec![BINARY_NAME, "cancel", "--swap-id", SWAP_ID];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(
+            args,
+            ParseResult::Arguments(Arguments::cancel_mainnet_defaults())
+        );
+    }
+
+    #[test]
+    fn given_cancel_on_testnet_then_defaults_to_testnet() {
+        let raw_ars = vec![BINARY_NAME, "--testnet", "cancel", "--swap-id", SWAP_ID];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(
+            args,
+            ParseResult::Arguments(Arguments::cancel_testnet_defaults())
+        );
+    }
+
+    #[test]
+    fn given_cancel_with_force_then_force_is_set() {
+        let raw_ars = vec![BINARY_NAME, "cancel", "--swap-id", SWAP_ID, "--force"];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert!(matches!(
+            args,
+            ParseResult::Arguments(Arguments {
+                cmd: Command::Cancel { force: true, .. },
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn given_artifacts_on_testnet_then_defaults_to_testnet() {
+        let raw_ars = vec![BINARY_NAME, "--testnet", "artifacts", "--swap-id", SWAP_ID];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(
+            args,
+            ParseResult::Arguments(Arguments {
+                env_config: env::Testnet::get_config(),
+                debug: false,
+                json: false,
+                otlp_endpoint: None,
+                data_dir: data_dir_path_cli().join(TESTNET),
+                cmd: Command::Artifacts {
+                    swap_id: Uuid::from_str(SWAP_ID).unwrap(),
+                    bitcoin_electrum_rpc_urls: electrum_rpc_urls(DEFAULT_ELECTRUM_RPC_URL_TESTNET),
+                    bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET_TESTNET,
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn given_info_then_defaults_to_mainnet_electrum() {
+        let raw_ars = vec![BINARY_NAME, "info", "--swap-id", SWAP_ID];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(
+            args,
+            ParseResult::Arguments(Arguments {
+                env_config: env::Mainnet::get_config(),
+                debug: false,
+                json: false,
+                otlp_endpoint: None,
+                data_dir: data_dir_path_cli().join(MAINNET),
+                cmd: Command::Info {
+                    swap_id: Uuid::from_str(SWAP_ID).unwrap(),
+                    bitcoin_electrum_rpc_urls: electrum_rpc_urls(DEFAULT_ELECTRUM_RPC_URL),
+                    bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET,
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn given_inspect_with_dot_then_dot_format() {
+        let raw_ars = vec![BINARY_NAME, "inspect", "--swap-id", SWAP_ID, "--dot"];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(
+            args,
+            ParseResult::Arguments(Arguments {
+                env_config: env::Mainnet::get_config(),
+                debug: false,
+                json: false,
+                otlp_endpoint: None,
+                data_dir: data_dir_path_cli().join(MAINNET),
+                cmd: Command::Inspect {
+                    swap_id: Uuid::from_str(SWAP_ID).unwrap(),
+                    format: inspect::Format::Dot,
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn given_presigned_then_only_needs_the_swap_id() {
+        let raw_ars = vec![BINARY_NAME, "presigned", "--swap-id", SWAP_ID];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(
+            args,
+            ParseResult::Arguments(Arguments {
+                env_config: env::Mainnet::get_config(),
+                debug: false,
+                json: false,
+                otlp_endpoint: None,
+                data_dir: data_dir_path_cli().join(MAINNET),
+                cmd: Command::Presigned {
+                    swap_id: Uuid::from_str(SWAP_ID).unwrap(),
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn given_verify_receipt_then_needs_the_file() {
+        let raw_ars = vec![BINARY_NAME, "verify-receipt", "--file", "receipt.json"];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(
+            args,
+            ParseResult::Arguments(Arguments {
+                env_config: env::Mainnet::get_config(),
+                debug: false,
+                json: false,
+                otlp_endpoint: None,
+                data_dir: data_dir_path_cli().join(MAINNET),
+                cmd: Command::VerifyReceipt {
+                    file: PathBuf::from("receipt.json"),
+                },
+            })
+        );
+
+        let raw_ars = vec![BINARY_NAME, "verify-receipt"];
+        assert!(parse_args_and_apply_defaults(raw_ars).is_err());
+    }
+
+    #[test]
+    fn given_inspect_with_dot_and_mermaid_then_fails() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "inspect",
+            "--swap-id",
+            SWAP_ID,
+            "--dot",
+            "--mermaid",
+        ];
+
+        assert!(parse_args_and_apply_defaults(raw_ars).is_err());
+    }
+
+    #[test]
+    fn given_history_with_bitcoin_explorer_then_explorer_is_replaced() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "--testnet",
+            "history",
+            "--bitcoin-explorer",
+            "http://localhost:8080",
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        let env_config = env::Testnet::get_config();
+        let explorer = Explorer::new(env_config.bitcoin_network, env_config.monero_network)
+            .with_bitcoin(Some(Url::from_str("http://localhost:8080").unwrap()));
+        assert_eq!(
+            args,
+            ParseResult::Arguments(Arguments {
+                env_config,
+                debug: false,
+                json: false,
+                otlp_endpoint: None,
+                data_dir: data_dir_path_cli().join(TESTNET),
+                cmd: Command::History {
+                    explorer,
+                    query: SwapQuery::from_iter(["history"]),
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn given_history_with_filters_then_they_are_parsed() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "history",
+            "--status",
+            "open",
+            "--min-btc",
+            "0.01 BTC",
+            "--limit",
+            "20",
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        let (filter, page) = match args {
+            ParseResult::Arguments(Arguments {
+                cmd: Command::History { query, .. },
+                ..
+            }) => query.into_filter_and_page(),
+            _ => panic!("Expected the history command"),
+        };
+        assert_eq!(filter.status, Some(SwapStatus::Open));
+        assert_eq!(filter.min_btc, Some(Amount::from_sat(1_000_000)));
+        assert_eq!(page.limit, Some(20));
+    }
+
+    #[test]
+    fn given_refund_on_mainnet_then_defaults_to_mainnet() {
+        let raw_ars = vec![BINARY_NAME, "refund", "--swap-id", SWAP_ID];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(
+            args,
+            ParseResult::Arguments(Arguments::refund_mainnet_defaults())
+        );
+    }
+
+    #[test]
+    fn given_refund_on_testnet_then_defaults_to_testnet() {
+        let raw_ars = vec![BINARY_NAME, "--testnet", "refund", "--swap-id", SWAP_ID];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(
+            args,
+            ParseResult::Arguments(Arguments::refund_testnet_defaults())
+        );
+    }
+
+    #[test]
+    fn given_refund_deposit_then_address_set() {
+        let raw_ars = vec![BINARY_NAME, "refund-deposit", BITCOIN_MAINNET_ADDRESS];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(
+            args,
+            ParseResult::Arguments(Arguments {
+                env_config: env::Mainnet::get_config(),
+                debug: false,
+                json: false,
+                otlp_endpoint: None,
+                data_dir: data_dir_path_cli().join(MAINNET),
+                cmd: Command::RefundDeposit {
+                    bitcoin_electrum_rpc_urls: electrum_rpc_urls(DEFAULT_ELECTRUM_RPC_URL),
+                    bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET,
+                    address: Address::from_str(BITCOIN_MAINNET_ADDRESS).unwrap(),
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn given_refund_deposit_with_testnet_address_on_mainnet_then_fails() {
+        let raw_ars = vec![BINARY_NAME, "refund-deposit", BITCOIN_TESTNET_ADDRESS];
+
+        let err = parse_args_and_apply_defaults(raw_ars).unwrap_err();
+
+        assert!(err
+            .downcast_ref::<BitcoinAddressNetworkMismatch>()
+            .is_some());
+    }
+
+    #[test]
+    fn given_rescan_on_mainnet_then_defaults_to_mainnet() {
+        let raw_ars = vec![BINARY_NAME, "rescan", "--swap-id", SWAP_ID];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(
+            args,
+            ParseResult::Arguments(Arguments::rescan_mainnet_defaults())
+        );
+    }
 
-    const BINARY_NAME: &str = "swap";
+    #[test]
+    fn given_rescan_on_testnet_then_defaults_to_testnet() {
+        let raw_ars = vec![BINARY_NAME, "--testnet", "rescan", "--swap-id", SWAP_ID];
 
-    const TESTNET: &str = "testnet";
-    const MAINNET: &str = "mainnet";
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
 
-    const MONERO_STAGENET_ADDRESS: &str = "53gEuGZUhP9JMEBZoGaFNzhwEgiG7hwQdMCqFxiyiTeFPmkbt1mAoNybEUvYBKHcnrSgxnVWgZsTvRBaHBNXPa8tHiCU51a";
-    const BITCOIN_TESTNET_ADDRESS: &str = "tb1qr3em6k3gfnyl8r7q0v7t4tlnyxzgxma3lressv";
-    const MONERO_MAINNET_ADDRESS: &str = "44Ato7HveWidJYUAVw5QffEcEtSH1DwzSP3FPPkHxNAS4LX9CqgucphTisH978FLHE34YNEx7FcbBfQLQUU8m3NUC4VqsRa";
-    const BITCOIN_MAINNET_ADDRESS: &str = "bc1qe4epnfklcaa0mun26yz5g8k24em5u9f92hy325";
-    const MULTI_ADDRESS: &str =
-        "/ip4/127.0.0.1/tcp/9939/p2p/12D3KooWCdMKjesXMJz1SiZ7HgotrxuqhQJbP5sgBm2BwP1cqThi";
-    const SWAP_ID: &str = "ea030832-3be9-454f-bb98-5ea9a788406b";
+        assert_eq!(
+            args,
+            ParseResult::Arguments(Arguments::rescan_testnet_defaults())
+        );
+    }
 
     #[test]
-    fn given_buy_xmr_on_mainnet_then_defaults_to_mainnet() {
-        let raw_ars = vec![
-            BINARY_NAME,
-            "buy-xmr",
-            "--receive-address",
-            MONERO_MAINNET_ADDRESS,
-            "--change-address",
-            BITCOIN_MAINNET_ADDRESS,
-            "--seller",
-            MULTI_ADDRESS,
-        ];
+    fn given_sweep_swap_wallets_on_mainnet_then_defaults_to_mainnet() {
+        let raw_ars = vec![BINARY_NAME, "monero", "sweep-swap-wallets"];
 
-        let expected_args = ParseResult::Arguments(Arguments::buy_xmr_mainnet_defaults());
         let args = parse_args_and_apply_defaults(raw_ars).unwrap();
 
-        assert_eq!(expected_args, args);
+        assert_eq!(
+            args,
+            ParseResult::Arguments(Arguments::sweep_swap_wallets_mainnet_defaults())
+        );
     }
 
     #[test]
-    fn given_buy_xmr_on_testnet_then_defaults_to_testnet() {
-        let raw_ars = vec![
-            BINARY_NAME,
-            "--testnet",
-            "buy-xmr",
-            "--receive-address",
-            MONERO_STAGENET_ADDRESS,
-            "--change-address",
-            BITCOIN_TESTNET_ADDRESS,
-            "--seller",
-            MULTI_ADDRESS,
-        ];
+    fn given_sweep_swap_wallets_on_testnet_then_defaults_to_testnet() {
+        let raw_ars = vec![BINARY_NAME, "--testnet", "monero", "sweep-swap-wallets"];
 
         let args = parse_args_and_apply_defaults(raw_ars).unwrap();
 
         assert_eq!(
             args,
-            ParseResult::Arguments(Arguments::buy_xmr_testnet_defaults())
+            ParseResult::Arguments(Arguments::sweep_swap_wallets_testnet_defaults())
         );
     }
 
     #[test]
-    fn given_buy_xmr_on_mainnet_with_testnet_address_then_fails() {
+    fn given_export_then_swap_id_and_file_set() {
         let raw_ars = vec![
             BINARY_NAME,
-            "buy-xmr",
-            "--receive-address",
-            MONERO_STAGENET_ADDRESS,
-            "--change-address",
-            BITCOIN_TESTNET_ADDRESS,
-            "--seller",
-            MULTI_ADDRESS,
+            "export",
+            "--swap-id",
+            SWAP_ID,
+            "--file",
+            "/tmp/swap.json",
         ];
 
-        let err = parse_args_and_apply_defaults(raw_ars).unwrap_err();
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
 
         assert_eq!(
-            err.downcast_ref::<MoneroAddressNetworkMismatch>().unwrap(),
-            &MoneroAddressNetworkMismatch {
-                expected: monero::Network::Mainnet,
-                actual: monero::Network::Stagenet
-            }
+            args,
+            ParseResult::Arguments(Arguments {
+                env_config: env::Mainnet::get_config(),
+                debug: false,
+                json: false,
+                otlp_endpoint: None,
+                data_dir: data_dir_path_cli().join(MAINNET),
+                cmd: Command::Export {
+                    swap_id: Uuid::from_str(SWAP_ID).unwrap(),
+                    file: PathBuf::from("/tmp/swap.json"),
+                },
+            })
         );
     }
 
     #[test]
-    fn given_buy_xmr_on_testnet_with_mainnet_address_then_fails() {
+    fn given_import_on_testnet_then_key_parsed() {
+        let key = "ab".repeat(32);
         let raw_ars = vec![
             BINARY_NAME,
             "--testnet",
-            "buy-xmr",
-            "--receive-address",
-            MONERO_MAINNET_ADDRESS,
-            "--change-address",
-            BITCOIN_MAINNET_ADDRESS,
-            "--seller",
-            MULTI_ADDRESS,
+            "import",
+            "--file",
+            "/tmp/swap.json",
+            "--key",
+            key.as_str(),
         ];
 
-        let err = parse_args_and_apply_defaults(raw_ars).unwrap_err();
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
 
         assert_eq!(
-            err.downcast_ref::<MoneroAddressNetworkMismatch>().unwrap(),
-            &MoneroAddressNetworkMismatch {
-                expected: monero::Network::Stagenet,
-                actual: monero::Network::Mainnet
-            }
+            args,
+            ParseResult::Arguments(Arguments {
+                env_config: env::Testnet::get_config(),
+                debug: false,
+                json: false,
+                otlp_endpoint: None,
+                data_dir: data_dir_path_cli().join(TESTNET),
+                cmd: Command::Import {
+                    file: PathBuf::from("/tmp/swap.json"),
+                    key: key.parse().unwrap(),
+                },
+            })
         );
     }
 
     #[test]
-    fn given_resume_on_mainnet_then_defaults_to_mainnet() {
-        let raw_ars = vec![BINARY_NAME, "resume", "--swap-id", SWAP_ID];
+    fn given_import_with_malformed_key_then_fails() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "import",
+            "--file",
+            "/tmp/swap.json",
+            "--key",
+            "not-a-key",
+        ];
+
+        assert!(parse_args_and_apply_defaults(raw_ars).is_err());
+    }
+
+    #[test]
+    fn given_check_on_mainnet_then_defaults_to_mainnet() {
+        let raw_ars = vec![BINARY_NAME, "check"];
 
         let args = parse_args_and_apply_defaults(raw_ars).unwrap();
 
         assert_eq!(
             args,
-            ParseResult::Arguments(Arguments::resume_mainnet_defaults())
+            ParseResult::Arguments(Arguments::check_mainnet_defaults())
         );
     }
 
     #[test]
-    fn given_resume_on_testnet_then_defaults_to_testnet() {
-        let raw_ars = vec![BINARY_NAME, "--testnet", "resume", "--swap-id", SWAP_ID];
+    fn given_check_on_testnet_then_defaults_to_testnet() {
+        let raw_ars = vec![BINARY_NAME, "--testnet", "check"];
 
         let args = parse_args_and_apply_defaults(raw_ars).unwrap();
 
         assert_eq!(
             args,
-            ParseResult::Arguments(Arguments::resume_testnet_defaults())
+            ParseResult::Arguments(Arguments::check_testnet_defaults())
         );
     }
 
     #[test]
-    fn given_cancel_on_mainnet_then_defaults_to_mainnet() {
-        let raw_ars = vec![BINARY_NAME, "cancel", "--swap-id", SWAP_ID];
+    fn given_check_with_seller_then_seller_set() {
+        let raw_ars = vec![BINARY_NAME, "check", "--seller", MULTI_ADDRESS];
 
         let args = parse_args_and_apply_defaults(raw_ars).unwrap();
 
         assert_eq!(
             args,
-            ParseResult::Arguments(Arguments::cancel_mainnet_defaults())
+            ParseResult::Arguments(
+                Arguments::check_mainnet_defaults()
+                    .with_seller(Multiaddr::from_str(MULTI_ADDRESS).unwrap())
+            )
         );
     }
 
     #[test]
-    fn given_cancel_on_testnet_then_defaults_to_testnet() {
-        let raw_ars = vec![BINARY_NAME, "--testnet", "cancel", "--swap-id", SWAP_ID];
+    fn given_version_then_prints_build_info() {
+        let raw_ars = vec![BINARY_NAME, "version"];
 
         let args = parse_args_and_apply_defaults(raw_ars).unwrap();
 
         assert_eq!(
             args,
-            ParseResult::Arguments(Arguments::cancel_testnet_defaults())
+            ParseResult::PrintAndExitZero {
+                message: BuildInfo::current().to_string()
+            }
         );
     }
 
     #[test]
-    fn given_refund_on_mainnet_then_defaults_to_mainnet() {
-        let raw_ars = vec![BINARY_NAME, "refund", "--swap-id", SWAP_ID];
+    fn given_version_with_json_then_prints_build_info_as_json() {
+        let raw_ars = vec![BINARY_NAME, "--json", "version"];
 
         let args = parse_args_and_apply_defaults(raw_ars).unwrap();
 
         assert_eq!(
             args,
-            ParseResult::Arguments(Arguments::refund_mainnet_defaults())
+            ParseResult::PrintAndExitZero {
+                message: serde_json::to_string(&BuildInfo::current()).unwrap()
+            }
         );
     }
 
     #[test]
-    fn given_refund_on_testnet_then_defaults_to_testnet() {
-        let raw_ars = vec![BINARY_NAME, "--testnet", "refund", "--swap-id", SWAP_ID];
+    fn given_completions_then_prints_script_for_the_shell() {
+        for shell in &["bash", "zsh", "fish"] {
+            let raw_ars = vec![BINARY_NAME, "completions", shell];
+
+            let message = match parse_args_and_apply_defaults(raw_ars).unwrap() {
+                ParseResult::PrintAndExitZero { message } => message,
+                ParseResult::Arguments(_) => panic!("Expected a completion script"),
+            };
+
+            assert!(message.contains("buy-xmr"), "{}", message);
+            assert!(message.contains("change-address"), "{}", message);
+        }
+    }
 
+    #[test]
+    fn given_aliases_then_parses_as_the_full_commands() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "buy",
+            "--receive-address",
+            MONERO_MAINNET_ADDRESS,
+            "--change-address",
+            BITCOIN_MAINNET_ADDRESS,
+            "--seller",
+            MULTI_ADDRESS,
+        ];
         let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+        assert_eq!(
+            args,
+            ParseResult::Arguments(Arguments::buy_xmr_mainnet_defaults())
+        );
 
+        let args = parse_args_and_apply_defaults(vec![BINARY_NAME, "ls"]).unwrap();
         assert_eq!(
             args,
-            ParseResult::Arguments(Arguments::refund_testnet_defaults())
+            parse_args_and_apply_defaults(vec![BINARY_NAME, "history"]).unwrap()
         );
     }
 
@@ -933,6 +2363,30 @@ mod tests {
         );
     }
 
+    #[test]
+    fn given_with_data_dir_alias_then_data_dir_set() {
+        let data_dir = "/some/path/to/dir";
+
+        let raw_ars = vec![
+            BINARY_NAME,
+            "--data-dir",
+            data_dir,
+            "resume",
+            "--swap-id",
+            SWAP_ID,
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+
+        assert_eq!(
+            args,
+            ParseResult::Arguments(
+                Arguments::resume_mainnet_defaults()
+                    .with_data_dir(PathBuf::from_str(data_dir).unwrap().join("mainnet"))
+            )
+        );
+    }
+
     #[test]
     fn given_with_debug_then_debug_set() {
         let raw_ars = vec![
@@ -1059,6 +2513,27 @@ mod tests {
         );
     }
 
+    #[test]
+    fn given_otlp_endpoint_then_it_is_passed_on() {
+        let raw_ars = vec![
+            BINARY_NAME,
+            "--otlp-endpoint",
+            "http://localhost:4317",
+            "resume",
+            "--swap-id",
+            SWAP_ID,
+        ];
+
+        let args = parse_args_and_apply_defaults(raw_ars).unwrap();
+        assert_eq!(
+            args,
+            ParseResult::Arguments(
+                Arguments::resume_mainnet_defaults()
+                    .with_otlp_endpoint(Url::parse("http://localhost:4317").unwrap())
+            )
+        );
+    }
+
     #[test]
     fn only_bech32_addresses_mainnet_are_allowed() {
         let raw_ars = vec![
@@ -1164,17 +2639,24 @@ mod tests {
                 env_config: env::Testnet::get_config(),
                 debug: false,
                 json: false,
+                otlp_endpoint: None,
                 data_dir: data_dir_path_cli().join(TESTNET),
                 cmd: Command::BuyXmr {
                     seller: Multiaddr::from_str(MULTI_ADDRESS).unwrap(),
-                    bitcoin_electrum_rpc_url: Url::from_str(DEFAULT_ELECTRUM_RPC_URL_TESTNET)
-                        .unwrap(),
+                    bitcoin_electrum_rpc_urls: electrum_rpc_urls(DEFAULT_ELECTRUM_RPC_URL_TESTNET),
                     bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET_TESTNET,
                     bitcoin_change_address: BITCOIN_TESTNET_ADDRESS.parse().unwrap(),
                     monero_receive_address: monero::Address::from_str(MONERO_STAGENET_ADDRESS)
                         .unwrap(),
                     monero_daemon_address: DEFAULT_MONERO_DAEMON_ADDRESS_STAGENET.to_string(),
+                    monero_light_wallet_server: None,
                     tor_socks5_port: DEFAULT_SOCKS5_PORT,
+                    fiat_currency: None,
+                    receive_xmr: None,
+                    max_price: None,
+                    max_price_deviation: None,
+                    confirm_lock: false,
+                    watchtower: None,
                 },
             }
         }
@@ -1184,16 +2666,24 @@ mod tests {
                 env_config: env::Mainnet::get_config(),
                 debug: false,
                 json: false,
+                otlp_endpoint: None,
                 data_dir: data_dir_path_cli().join(MAINNET),
                 cmd: Command::BuyXmr {
                     seller: Multiaddr::from_str(MULTI_ADDRESS).unwrap(),
-                    bitcoin_electrum_rpc_url: Url::from_str(DEFAULT_ELECTRUM_RPC_URL).unwrap(),
+                    bitcoin_electrum_rpc_urls: electrum_rpc_urls(DEFAULT_ELECTRUM_RPC_URL),
                     bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET,
                     bitcoin_change_address: BITCOIN_MAINNET_ADDRESS.parse().unwrap(),
                     monero_receive_address: monero::Address::from_str(MONERO_MAINNET_ADDRESS)
                         .unwrap(),
                     monero_daemon_address: DEFAULT_MONERO_DAEMON_ADDRESS.to_string(),
+                    monero_light_wallet_server: None,
                     tor_socks5_port: DEFAULT_SOCKS5_PORT,
+                    fiat_currency: None,
+                    receive_xmr: None,
+                    max_price: None,
+                    max_price_deviation: None,
+                    confirm_lock: false,
+                    watchtower: None,
                 },
             }
         }
@@ -1203,14 +2693,17 @@ mod tests {
                 env_config: env::Testnet::get_config(),
                 debug: false,
                 json: false,
+                otlp_endpoint: None,
                 data_dir: data_dir_path_cli().join(TESTNET),
                 cmd: Command::Resume {
                     swap_id: Uuid::from_str(SWAP_ID).unwrap(),
-                    bitcoin_electrum_rpc_url: Url::from_str(DEFAULT_ELECTRUM_RPC_URL_TESTNET)
-                        .unwrap(),
+                    bitcoin_electrum_rpc_urls: electrum_rpc_urls(DEFAULT_ELECTRUM_RPC_URL_TESTNET),
                     bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET_TESTNET,
                     monero_daemon_address: DEFAULT_MONERO_DAEMON_ADDRESS_STAGENET.to_string(),
+                    monero_light_wallet_server: None,
                     tor_socks5_port: DEFAULT_SOCKS5_PORT,
+                    confirm_lock: false,
+                    watchtower: None,
                 },
             }
         }
@@ -1220,13 +2713,17 @@ mod tests {
                 env_config: env::Mainnet::get_config(),
                 debug: false,
                 json: false,
+                otlp_endpoint: None,
                 data_dir: data_dir_path_cli().join(MAINNET),
                 cmd: Command::Resume {
                     swap_id: Uuid::from_str(SWAP_ID).unwrap(),
-                    bitcoin_electrum_rpc_url: Url::from_str(DEFAULT_ELECTRUM_RPC_URL).unwrap(),
+                    bitcoin_electrum_rpc_urls: electrum_rpc_urls(DEFAULT_ELECTRUM_RPC_URL),
                     bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET,
                     monero_daemon_address: DEFAULT_MONERO_DAEMON_ADDRESS.to_string(),
+                    monero_light_wallet_server: None,
                     tor_socks5_port: DEFAULT_SOCKS5_PORT,
+                    confirm_lock: false,
+                    watchtower: None,
                 },
             }
         }
@@ -1236,12 +2733,13 @@ mod tests {
                 env_config: env::Testnet::get_config(),
                 debug: false,
                 json: false,
+                otlp_endpoint: None,
                 data_dir: data_dir_path_cli().join(TESTNET),
                 cmd: Command::Cancel {
                     swap_id: Uuid::from_str(SWAP_ID).unwrap(),
-                    bitcoin_electrum_rpc_url: Url::from_str(DEFAULT_ELECTRUM_RPC_URL_TESTNET)
-                        .unwrap(),
+                    bitcoin_electrum_rpc_urls: electrum_rpc_urls(DEFAULT_ELECTRUM_RPC_URL_TESTNET),
                     bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET_TESTNET,
+                    force: false,
                 },
             }
         }
@@ -1251,11 +2749,13 @@ mod tests {
                 env_config: env::Mainnet::get_config(),
                 debug: false,
                 json: false,
+                otlp_endpoint: None,
                 data_dir: data_dir_path_cli().join(MAINNET),
                 cmd: Command::Cancel {
                     swap_id: Uuid::from_str(SWAP_ID).unwrap(),
-                    bitcoin_electrum_rpc_url: Url::from_str(DEFAULT_ELECTRUM_RPC_URL).unwrap(),
+                    bitcoin_electrum_rpc_urls: electrum_rpc_urls(DEFAULT_ELECTRUM_RPC_URL),
                     bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET,
+                    force: false,
                 },
             }
         }
@@ -1265,12 +2765,13 @@ mod tests {
                 env_config: env::Testnet::get_config(),
                 debug: false,
                 json: false,
+                otlp_endpoint: None,
                 data_dir: data_dir_path_cli().join(TESTNET),
                 cmd: Command::Refund {
                     swap_id: Uuid::from_str(SWAP_ID).unwrap(),
-                    bitcoin_electrum_rpc_url: Url::from_str(DEFAULT_ELECTRUM_RPC_URL_TESTNET)
-                        .unwrap(),
+                    bitcoin_electrum_rpc_urls: electrum_rpc_urls(DEFAULT_ELECTRUM_RPC_URL_TESTNET),
                     bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET_TESTNET,
+                    force: false,
                 },
             }
         }
@@ -1280,15 +2781,112 @@ mod tests {
                 env_config: env::Mainnet::get_config(),
                 debug: false,
                 json: false,
+                otlp_endpoint: None,
                 data_dir: data_dir_path_cli().join(MAINNET),
                 cmd: Command::Refund {
                     swap_id: Uuid::from_str(SWAP_ID).unwrap(),
-                    bitcoin_electrum_rpc_url: Url::from_str(DEFAULT_ELECTRUM_RPC_URL).unwrap(),
+                    bitcoin_electrum_rpc_urls: electrum_rpc_urls(DEFAULT_ELECTRUM_RPC_URL),
+                    bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET,
+                    force: false,
+                },
+            }
+        }
+
+        pub fn rescan_testnet_defaults() -> Self {
+            Self {
+                env_config: env::Testnet::get_config(),
+                debug: false,
+                json: false,
+                otlp_endpoint: None,
+                data_dir: data_dir_path_cli().join(TESTNET),
+                cmd: Command::Rescan {
+                    swap_id: Uuid::from_str(SWAP_ID).unwrap(),
+                    monero_daemon_address: DEFAULT_MONERO_DAEMON_ADDRESS_STAGENET.to_string(),
+                },
+            }
+        }
+
+        pub fn rescan_mainnet_defaults() -> Self {
+            Self {
+                env_config: env::Mainnet::get_config(),
+                debug: false,
+                json: false,
+                otlp_endpoint: None,
+                data_dir: data_dir_path_cli().join(MAINNET),
+                cmd: Command::Rescan {
+                    swap_id: Uuid::from_str(SWAP_ID).unwrap(),
+                    monero_daemon_address: DEFAULT_MONERO_DAEMON_ADDRESS.to_string(),
+                },
+            }
+        }
+
+        pub fn sweep_swap_wallets_testnet_defaults() -> Self {
+            Self {
+                env_config: env::Testnet::get_config(),
+                debug: false,
+                json: false,
+                otlp_endpoint: None,
+                data_dir: data_dir_path_cli().join(TESTNET),
+                cmd: Command::SweepSwapWallets {
+                    monero_daemon_address: DEFAULT_MONERO_DAEMON_ADDRESS_STAGENET.to_string(),
+                },
+            }
+        }
+
+        pub fn sweep_swap_wallets_mainnet_defaults() -> Self {
+            Self {
+                env_config: env::Mainnet::get_config(),
+                debug: false,
+                json: false,
+                otlp_endpoint: None,
+                data_dir: data_dir_path_cli().join(MAINNET),
+                cmd: Command::SweepSwapWallets {
+                    monero_daemon_address: DEFAULT_MONERO_DAEMON_ADDRESS.to_string(),
+                },
+            }
+        }
+
+        pub fn check_testnet_defaults() -> Self {
+            Self {
+                env_config: env::Testnet::get_config(),
+                debug: false,
+                json: false,
+                otlp_endpoint: None,
+                data_dir: data_dir_path_cli().join(TESTNET),
+                cmd: Command::Check {
+                    seller: None,
+                    bitcoin_electrum_rpc_urls: electrum_rpc_urls(DEFAULT_ELECTRUM_RPC_URL_TESTNET),
+                    bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET_TESTNET,
+                    monero_daemon_address: DEFAULT_MONERO_DAEMON_ADDRESS_STAGENET.to_string(),
+                    tor_socks5_port: DEFAULT_SOCKS5_PORT,
+                },
+            }
+        }
+
+        pub fn check_mainnet_defaults() -> Self {
+            Self {
+                env_config: env::Mainnet::get_config(),
+                debug: false,
+                json: false,
+                otlp_endpoint: None,
+                data_dir: data_dir_path_cli().join(MAINNET),
+                cmd: Command::Check {
+                    seller: None,
+                    bitcoin_electrum_rpc_urls: electrum_rpc_urls(DEFAULT_ELECTRUM_RPC_URL),
                     bitcoin_target_block: DEFAULT_BITCOIN_CONFIRMATION_TARGET,
+                    monero_daemon_address: DEFAULT_MONERO_DAEMON_ADDRESS.to_string(),
+                    tor_socks5_port: DEFAULT_SOCKS5_PORT,
                 },
             }
         }
 
+        pub fn with_seller(mut self, seller_address: Multiaddr) -> Self {
+            if let Command::Check { seller, .. } = &mut self.cmd {
+                *seller = Some(seller_address);
+            }
+            self
+        }
+
         pub fn with_data_dir(mut self, data_dir: PathBuf) -> Self {
             self.data_dir = data_dir;
             self
@@ -1303,9 +2901,18 @@ mod tests {
             self.json = true;
             self
         }
+
+        pub fn with_otlp_endpoint(mut self, endpoint: Url) -> Self {
+            self.otlp_endpoint = Some(endpoint);
+            self
+        }
     }
 
     fn data_dir_path_cli() -> PathBuf {
         system_data_dir().unwrap().join("cli")
     }
+
+    fn electrum_rpc_urls(url: &str) -> Vec<Url> {
+        vec![Url::from_str(url).unwrap()]
+    }
 }
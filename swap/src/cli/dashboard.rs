@@ -0,0 +1,594 @@
+//! A terminal dashboard of the open swaps, built with the `dashboard`
+//! feature.
+//!
+//! The dashboard shows the open swaps of the database with their phase, the
+//! confirmations the wallets are waiting for, the Bitcoin balance and the
+//! recent events, i.e. state changes and the outcome of the actions taken
+//! from the dashboard. The selected swap can be resumed, which runs it in the
+//! dashboard process next to the other resumed swaps, cancelled or inspected.
+//!
+//! The confirmations are the ones the wallets report through
+//! [`Progress`](crate::progress::Progress), i.e. those of the swaps running
+//! in the dashboard. Swaps that were not resumed show up with their phase
+//! only.
+
+use crate::bitcoin;
+use crate::cli::{progress, SwapFactory};
+use crate::database::{self, SwapFilter, SwapPage, SwapStatus, SwapSummary};
+use crate::env;
+use crate::progress::{Chain, Confirmations};
+use crate::protocol::bob;
+use crate::protocol::bob::BobState;
+use crate::protocol::sequence::Role;
+use anyhow::{bail, Result};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
+use crossterm::execute;
+use crossterm::terminal::{
+    disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
+};
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::io;
+use std::time::Duration;
+use time::OffsetDateTime;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::mpsc;
+use tui::backend::{Backend, CrosstermBackend};
+use tui::layout::{Constraint, Direction, Layout, Rect};
+use tui::style::{Modifier, Style};
+use tui::widgets::{
+    Block, Borders, Clear, List, ListItem, Paragraph, Row, Table, TableState, Wrap,
+};
+use tui::{Frame, Terminal};
+use uuid::Uuid;
+
+/// How often the swaps are read from the database.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How often the Bitcoin wallet is synced to update the balance.
+const BALANCE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How many events are kept, older ones are dropped.
+const MAX_EVENTS: usize = 100;
+
+/// What a key pressed in the dashboard asks for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    Resume(Uuid),
+    Cancel(Uuid),
+    Inspect(Uuid),
+    Quit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Balances {
+    pub bitcoin: bitcoin::Amount,
+    /// What the wallet can lock in a new swap, i.e. the balance minus the
+    /// fees and the Bitcoin reserved for the swaps running in the dashboard.
+    pub available: bitcoin::Amount,
+}
+
+/// Reported by the tasks of the dashboard.
+#[derive(Debug)]
+enum Update {
+    Balances(Balances),
+    Event(String),
+    Stopped { swap_id: Uuid, outcome: String },
+}
+
+/// What the dashboard shows, updated by the event loop in [`run`].
+#[derive(Debug, Default)]
+pub struct Dashboard {
+    /// Whether the swaps were read once, the swaps that are open when the
+    /// dashboard is started are not reported as started.
+    loaded: bool,
+    swaps: Vec<SwapSummary>,
+    selected: usize,
+    running: HashSet<Uuid>,
+    waits: BTreeMap<(Chain, String), Confirmations>,
+    balances: Option<Balances>,
+    events: VecDeque<String>,
+    popup: Option<String>,
+}
+
+impl Dashboard {
+    pub fn selected(&self) -> Option<&SwapSummary> {
+        self.swaps.get(self.selected)
+    }
+
+    pub fn events(&self) -> impl Iterator<Item = &String> {
+        self.events.iter()
+    }
+
+    /// Replaces the listed swaps, records the swaps that appeared, changed
+    /// their phase or disappeared as events.
+    pub fn update_swaps(&mut self, swaps: Vec<SwapSummary>, at: OffsetDateTime) {
+        let selected = self.selected().map(|swap| swap.swap_id);
+
+        for swap in &swaps {
+            match self
+                .swaps
+                .iter()
+                .find(|known| known.swap_id == swap.swap_id)
+            {
+                Some(known) if known.state != swap.state => {
+                    self.event(at, format!("Swap {}: {}", swap.swap_id, swap.state))
+                }
+                None if self.loaded => {
+                    self.event(at, format!("Swap {} started: {}", swap.swap_id, swap.state))
+                }
+                _ => {}
+            }
+        }
+        let closed = self
+            .swaps
+            .iter()
+            .filter(|known| !swaps.iter().any(|swap| swap.swap_id == known.swap_id))
+            .map(|known| known.swap_id)
+            .collect::<Vec<_>>();
+        for swap_id in closed {
+            self.event(at, format!("Swap {} is no longer open", swap_id));
+        }
+
+        self.loaded = true;
+        self.swaps = swaps;
+        self.selected = selected
+            .and_then(|selected| self.swaps.iter().position(|swap| swap.swap_id == selected))
+            .unwrap_or_else(|| self.selected.min(self.swaps.len().saturating_sub(1)));
+    }
+
+    pub fn confirmations(&mut self, confirmations: Confirmations) {
+        let key = (confirmations.chain, confirmations.txid.clone());
+
+        if confirmations.is_complete() {
+            self.waits.remove(&key);
+        } else {
+            self.waits.insert(key, confirmations);
+        }
+    }
+
+    pub fn event(&mut self, at: OffsetDateTime, message: String) {
+        self.events.push_front(format!(
+            "{:02}:{:02}:{:02} {}",
+            at.hour(),
+            at.minute(),
+            at.second(),
+            message
+        ));
+        self.events.truncate(MAX_EVENTS);
+    }
+
+    /// What the key asks for, `None` if it only changes what is shown.
+    pub fn key(&mut self, key: KeyEvent) -> Option<Action> {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            return Some(Action::Quit);
+        }
+        if self.popup.take().is_some() {
+            return None;
+        }
+
+        let selected = self.selected().map(|swap| swap.swap_id);
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => Some(Action::Quit),
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.selected = self.selected.saturating_sub(1);
+                None
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.selected = (self.selected + 1).min(self.swaps.len().saturating_sub(1));
+                None
+            }
+            KeyCode::Char('r') => selected.map(Action::Resume),
+            KeyCode::Char('c') => selected.map(Action::Cancel),
+            KeyCode::Char('i') | KeyCode::Enter => selected.map(Action::Inspect),
+            _ => None,
+        }
+    }
+
+    fn draw<B: Backend>(&self, frame: &mut Frame<'_, B>, env_config: &env::Config) {
+        let areas = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(
+                [
+                    Constraint::Min(6),
+                    Constraint::Length(6),
+                    Constraint::Length(3),
+                    Constraint::Length(10),
+                    Constraint::Length(1),
+                ]
+                .as_ref(),
+            )
+            .split(frame.size());
+
+        let rows = self.swaps.iter().map(|swap| {
+            let started =
+                database::format_started_at(swap.started_at.unix_timestamp()).unwrap_or_default();
+            let running = if self.running.contains(&swap.swap_id) {
+                "running"
+            } else {
+                ""
+            };
+
+            Row::new(vec![
+                swap.swap_id.to_string(),
+                started,
+                swap.state.clone(),
+                swap.btc_amount
+                    .map(|amount| amount.to_string())
+                    .unwrap_or_default(),
+                running.to_owned(),
+            ])
+        });
+        let widths = [
+            Constraint::Length(36),
+            Constraint::Length(20),
+            Constraint::Min(20),
+            Constraint::Length(16),
+            Constraint::Length(7),
+        ];
+        let table = Table::new(rows)
+            .header(
+                Row::new(vec!["SWAP ID", "STARTED", "PHASE", "BTC", ""])
+                    .style(Style::default().add_modifier(Modifier::BOLD)),
+            )
+            .block(Block::default().borders(Borders::ALL).title("Open swaps"))
+            .widths(&widths)
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        let mut state = TableState::default();
+        state.select(self.selected().map(|_| self.selected));
+        frame.render_stateful_widget(table, areas[0], &mut state);
+
+        let waits = if self.waits.is_empty() {
+            vec![ListItem::new("Not waiting for any confirmations")]
+        } else {
+            self.waits
+                .values()
+                .map(|wait| ListItem::new(progress::render(wait, env_config)))
+                .collect()
+        };
+        frame.render_widget(
+            List::new(waits).block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title("Confirmations"),
+            ),
+            areas[1],
+        );
+
+        let balances = match self.balances {
+            Some(balances) => format!(
+                "{} in the wallet, {} available for new swaps",
+                balances.bitcoin, balances.available
+            ),
+            None => "Syncing the Bitcoin wallet".to_owned(),
+        };
+        frame.render_widget(
+            Paragraph::new(balances).block(Block::default().borders(Borders::ALL).title("Balance")),
+            areas[2],
+        );
+
+        let events = self
+            .events
+            .iter()
+            .map(|event| ListItem::new(event.as_str()))
+            .collect::<Vec<_>>();
+        frame.render_widget(
+            List::new(events).block(Block::default().borders(Borders::ALL).title("Events")),
+            areas[3],
+        );
+
+        frame.render_widget(
+            Paragraph::new("↑/↓ select  r resume  c cancel  i inspect  q quit"),
+            areas[4],
+        );
+
+        if let Some(popup) = &self.popup {
+            let area = centered(frame.size());
+            frame.render_widget(Clear, area);
+            frame.render_widget(
+                Paragraph::new(popup.as_str())
+                    .block(
+                        Block::default()
+                            .borders(Borders::ALL)
+                            .title("Inspect (any key to close)"),
+                    )
+                    .wrap(Wrap { trim: false }),
+                area,
+            );
+        }
+    }
+}
+
+/// Runs the dashboard until it is quit, the swaps resumed from it are stopped
+/// along with it.
+pub async fn run(factory: SwapFactory, env_config: env::Config) -> Result<()> {
+    let mut terminal = RawTerminal::enter()?;
+
+    let db = factory.db();
+    let mut dashboard = Dashboard::default();
+    let (updates, mut update_receiver) = mpsc::unbounded_channel();
+    let (keys, mut key_receiver) = mpsc::unbounded_channel();
+
+    // Reading the terminal blocks, the thread ends with the process
+    std::thread::spawn(move || loop {
+        match event::read() {
+            Ok(Event::Key(key)) => {
+                if keys.send(key).is_err() {
+                    break;
+                }
+            }
+            Ok(_) => {}
+            Err(_) => break,
+        }
+    });
+    tokio::spawn(report_balances(factory.clone(), updates.clone()));
+
+    let mut bitcoin_progress = factory.bitcoin_wallet().progress().subscribe();
+    let mut monero_progress = factory.monero_wallet().progress().subscribe();
+    let mut refresh = tokio::time::interval(REFRESH_INTERVAL);
+
+    loop {
+        tokio::select! {
+            _ = refresh.tick() => {
+                match open_swaps(&factory).await {
+                    Ok(swaps) => dashboard.update_swaps(swaps, OffsetDateTime::now_utc()),
+                    Err(error) => dashboard.event(OffsetDateTime::now_utc(), format!("Failed to read the swaps: {:#}", error)),
+                }
+            }
+            Some(key) = key_receiver.recv() => {
+                match dashboard.key(key) {
+                    Some(Action::Quit) => break,
+                    Some(Action::Resume(swap_id)) => {
+                        if dashboard.running.insert(swap_id) {
+                            dashboard.event(OffsetDateTime::now_utc(), format!("Resuming swap {}", swap_id));
+                            tokio::spawn(resume(factory.clone(), swap_id, updates.clone()));
+                        } else {
+                            dashboard.event(OffsetDateTime::now_utc(), format!("Swap {} is running already", swap_id));
+                        }
+                    }
+                    Some(Action::Cancel(swap_id)) => {
+                        let message = if dashboard.running.contains(&swap_id) {
+                            format!("Swap {} is running, it cancels by itself once the cancel timelock expired", swap_id)
+                        } else {
+                            match crate::cli::cancel(swap_id, factory.bitcoin_wallet(), factory.db(), false).await {
+                                Ok((txid, _)) => format!("Published the cancel transaction {} of swap {}", txid, swap_id),
+                                Err(error) => format!("Failed to cancel swap {}: {:#}", swap_id, error),
+                            }
+                        };
+                        dashboard.event(OffsetDateTime::now_utc(), message);
+                    }
+                    Some(Action::Inspect(swap_id)) => {
+                        dashboard.popup = Some(
+                            database::swap_info(db.as_ref(), swap_id, Some(factory.bitcoin_wallet().as_ref()))
+                                .await
+                                .map(|info| info.to_string())
+                                .unwrap_or_else(|error| format!("{:#}", error)),
+                        );
+                    }
+                    None => {}
+                }
+            }
+            Some(update) = update_receiver.recv() => {
+                match update {
+                    Update::Balances(balances) => dashboard.balances = Some(balances),
+                    Update::Event(message) => dashboard.event(OffsetDateTime::now_utc(), message),
+                    Update::Stopped { swap_id, outcome } => {
+                        dashboard.running.remove(&swap_id);
+                        dashboard.event(OffsetDateTime::now_utc(), outcome);
+                    }
+                }
+            }
+            received = bitcoin_progress.recv() => confirmations(&mut dashboard, received),
+            received = monero_progress.recv() => confirmations(&mut dashboard, received),
+        }
+
+        terminal
+            .terminal
+            .draw(|frame| dashboard.draw(frame, &env_config))?;
+    }
+
+    Ok(())
+}
+
+fn confirmations(dashboard: &mut Dashboard, received: Result<Confirmations, RecvError>) {
+    // Only the latest report of a transaction matters, missed ones are skipped
+    if let Ok(confirmations) = received {
+        dashboard.confirmations(confirmations);
+    }
+}
+
+async fn open_swaps(factory: &SwapFactory) -> Result<Vec<SwapSummary>> {
+    let filter = SwapFilter {
+        role: Some(Role::Bob),
+        status: Some(SwapStatus::Open),
+        ..Default::default()
+    };
+
+    factory.db().list_swaps(&filter, &SwapPage::default()).await
+}
+
+async fn report_balances(factory: SwapFactory, updates: mpsc::UnboundedSender<Update>) {
+    let mut interval = tokio::time::interval(BALANCE_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let update = match balances(&factory).await {
+            Ok(balances) => Update::Balances(balances),
+            Err(error) => Update::Event(format!("Failed to update the balance: {:#}", error)),
+        };
+        if updates.send(update).is_err() {
+            return;
+        }
+    }
+}
+
+async fn balances(factory: &SwapFactory) -> Result<Balances> {
+    let bitcoin_wallet = factory.bitcoin_wallet();
+    bitcoin_wallet.sync().await?;
+
+    Ok(Balances {
+        bitcoin: bitcoin_wallet.balance().await?,
+        available: factory.max_giveable().await?,
+    })
+}
+
+async fn resume(factory: SwapFactory, swap_id: Uuid, updates: mpsc::UnboundedSender<Update>) {
+    let outcome = match resume_swap(&factory, swap_id).await {
+        Ok(state) => format!("Swap {} finished: {}", swap_id, state),
+        Err(error) => format!("Swap {} stopped: {:#}", swap_id, error),
+    };
+
+    let _ = updates.send(Update::Stopped { swap_id, outcome });
+}
+
+async fn resume_swap(factory: &SwapFactory, swap_id: Uuid) -> Result<BobState> {
+    let db = factory.db();
+    crate::cli::export::ensure_not_exported(swap_id, db.as_ref()).await?;
+
+    let seller_peer_id = db.get_peer_id(swap_id).await?;
+    database::prune_dead_addresses(db.as_ref()).await;
+    let seller_addresses = db.get_addresses(seller_peer_id).await?;
+    if seller_addresses.is_empty() {
+        bail!("No address of the seller {} is known", seller_peer_id)
+    }
+
+    let (event_loop, event_loop_handle) = factory
+        .event_loop(swap_id, seller_peer_id, seller_addresses)
+        .await?;
+    let event_loop = tokio::spawn(event_loop.run());
+
+    let swap = factory.resume_swap(swap_id, event_loop_handle).await?;
+    let result = bob::run(swap).await;
+    event_loop.abort();
+
+    result
+}
+
+/// The terminal in raw mode on the alternate screen, restored when dropped so
+/// an error does not leave the shell unusable.
+struct RawTerminal {
+    terminal: Terminal<CrosstermBackend<io::Stdout>>,
+}
+
+impl RawTerminal {
+    fn enter() -> Result<Self> {
+        enable_raw_mode()?;
+        execute!(io::stdout(), EnterAlternateScreen)?;
+
+        let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+        terminal.hide_cursor()?;
+
+        Ok(Self { terminal })
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        let _ = disable_raw_mode();
+        let _ = execute!(io::stdout(), LeaveAlternateScreen);
+        let _ = self.terminal.show_cursor();
+    }
+}
+
+/// The middle of `area`, for the popup.
+fn centered(area: Rect) -> Rect {
+    let width = area.width * 4 / 5;
+    let height = area.height * 4 / 5;
+
+    Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn swap(state: &str) -> SwapSummary {
+        SwapSummary {
+            swap_id: Uuid::new_v4(),
+            role: Role::Bob,
+            started_at: OffsetDateTime::UNIX_EPOCH,
+            state: state.to_owned(),
+            finished: false,
+            btc_amount: None,
+            peer_id: None,
+        }
+    }
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    #[test]
+    fn changes_of_the_listed_swaps_are_recorded_as_events() {
+        let mut dashboard = Dashboard::default();
+        let at = OffsetDateTime::UNIX_EPOCH;
+        let first = swap("btc is locked");
+
+        dashboard.update_swaps(vec![first.clone()], at);
+        assert_eq!(dashboard.events().count(), 0);
+
+        let mut advanced = first.clone();
+        advanced.state = "xmr is locked".to_owned();
+        let second = swap("quote has been requested");
+        dashboard.update_swaps(vec![advanced, second.clone()], at);
+        dashboard.update_swaps(vec![second.clone()], at);
+
+        assert_eq!(dashboard.events().collect::<Vec<_>>(), vec![
+            &format!("00:00:00 Swap {} is no longer open", first.swap_id),
+            &format!(
+                "00:00:00 Swap {} started: quote has been requested",
+                second.swap_id
+            ),
+            &format!("00:00:00 Swap {}: xmr is locked", first.swap_id),
+        ]);
+        assert_eq!(
+            dashboard.selected().map(|swap| swap.swap_id),
+            Some(second.swap_id)
+        );
+    }
+
+    #[test]
+    fn keys_act_on_the_selected_swap() {
+        let mut dashboard = Dashboard::default();
+        let swaps = vec![swap("btc is locked"), swap("xmr is locked")];
+        dashboard.update_swaps(swaps.clone(), OffsetDateTime::UNIX_EPOCH);
+
+        assert_eq!(
+            dashboard.key(key(KeyCode::Char('r'))),
+            Some(Action::Resume(swaps[0].swap_id))
+        );
+        assert_eq!(dashboard.key(key(KeyCode::Down)), None);
+        assert_eq!(dashboard.key(key(KeyCode::Down)), None);
+        assert_eq!(
+            dashboard.key(key(KeyCode::Char('c'))),
+            Some(Action::Cancel(swaps[1].swap_id))
+        );
+
+        dashboard.popup = Some("inspected".to_owned());
+        assert_eq!(dashboard.key(key(KeyCode::Char('q'))), None);
+        assert_eq!(dashboard.key(key(KeyCode::Char('q'))), Some(Action::Quit));
+    }
+
+    #[test]
+    fn confirmed_transactions_are_no_longer_shown() {
+        let mut dashboard = Dashboard::default();
+        let wait = |seen| Confirmations {
+            chain: Chain::Bitcoin,
+            txid: "3f1c0e2b9a5d4c7e8f6a".to_owned(),
+            seen,
+            needed: 3,
+        };
+
+        dashboard.confirmations(wait(1));
+        dashboard.confirmations(wait(2));
+        assert_eq!(dashboard.waits.len(), 1);
+
+        dashboard.confirmations(wait(3));
+        assert!(dashboard.waits.is_empty());
+    }
+}
@@ -1,18 +1,31 @@
+use crate::amounts::Quote;
 use crate::bitcoin::EncryptedSignature;
 use crate::cli::behaviour::{Behaviour, OutEvent};
+use crate::network::connection::ConnectionStats;
+use crate::network::cooperative_xmr_redeem_after_punish::{self, Response};
 use crate::network::encrypted_signature;
-use crate::network::quote::BidQuote;
+use crate::network::hole_punch;
+use crate::network::outbox;
+use crate::network::quote::SignedQuote;
+use crate::network::receipt;
 use crate::network::swap_setup::bob::NewSwap;
-use crate::protocol::bob::State2;
+use crate::network::swap_status::{self, Phase, Resolution, Status};
+use crate::protocol::bob::{BobState, State2};
+use crate::protocol::sequence::MessageKind;
+use crate::protocol::transcript::TranscriptHash;
+use crate::protocol::Database;
 use crate::{env, monero};
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
 use futures::future::{BoxFuture, OptionFuture};
 use futures::{FutureExt, StreamExt};
 use libp2p::request_response::{RequestId, ResponseChannel};
-use libp2p::swarm::SwarmEvent;
-use libp2p::{PeerId, Swarm};
+use libp2p::swarm::{DialError, SwarmEvent};
+use libp2p::{Multiaddr, PeerId, Swarm};
 use std::collections::HashMap;
-use std::time::Duration;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use time::OffsetDateTime;
+use tokio::sync::watch;
 use uuid::Uuid;
 
 #[allow(missing_debug_implementations)]
@@ -20,18 +33,27 @@ pub struct EventLoop {
     swap_id: Uuid,
     swarm: libp2p::Swarm<Behaviour>,
     alice_peer_id: PeerId,
+    /// Keeps the encrypted signature in the outbox until Alice acknowledged it
+    /// and records the transfer proofs that were processed. None if the event
+    /// loop only requests a quote.
+    db: Option<Arc<dyn Database + Send + Sync>>,
 
     // these streams represents outgoing requests that we have to make
-    quote_requests: bmrng::RequestReceiverStream<(), BidQuote>,
-    encrypted_signatures: bmrng::RequestReceiverStream<EncryptedSignature, ()>,
+    quote_requests: bmrng::RequestReceiverStream<(), Result<Quote>>,
+    encrypted_signatures:
+        bmrng::RequestReceiverStream<(EncryptedSignature, Option<TranscriptHash>), ()>,
     swap_setup_requests: bmrng::RequestReceiverStream<NewSwap, Result<State2>>,
+    cooperative_xmr_redeem_requests: bmrng::RequestReceiverStream<(), Response>,
+    receipt_requests: bmrng::RequestReceiverStream<receipt::Request, receipt::Response>,
 
     // these represents requests that are currently in-flight.
     // once we get a response to a matching [`RequestId`], we will use the responder to relay the
     // response.
-    inflight_quote_requests: HashMap<RequestId, bmrng::Responder<BidQuote>>,
+    inflight_quote_requests: HashMap<RequestId, bmrng::Responder<Result<Quote>>>,
     inflight_encrypted_signature_requests: HashMap<RequestId, bmrng::Responder<()>>,
     inflight_swap_setup: Option<bmrng::Responder<Result<State2>>>,
+    inflight_cooperative_xmr_redeem_requests: HashMap<RequestId, bmrng::Responder<Response>>,
+    inflight_receipt_requests: HashMap<RequestId, bmrng::Responder<receipt::Response>>,
 
     /// The sender we will use to relay incoming transfer proofs.
     transfer_proof: bmrng::RequestSender<monero::TransferProof, ()>,
@@ -43,6 +65,20 @@ pub struct EventLoop {
     /// resolves, we use the `ResponseChannel` returned from it to send an ACK
     /// to Alice that we have successfully processed the transfer proof.
     pending_transfer_proof: OptionFuture<BoxFuture<'static, ResponseChannel<()>>>,
+
+    /// The latest state of the swap, used to resynchronize with Alice after
+    /// (re-)connecting to her.
+    swap_state: watch::Receiver<Option<BobState>>,
+    /// Whether the status of the swap has to be exchanged with Alice.
+    swap_status_due: bool,
+    /// Alice already learned the encrypted signature, it does not have to be
+    /// sent (again).
+    encrypted_signature_acknowledged: bool,
+    /// Alice's status of the swap contradicts ours, the encrypted signature
+    /// must not be handed out.
+    withhold_encrypted_signature: bool,
+    /// Counts how often the connection to Alice was lost and re-established.
+    connection_stats: ConnectionStats,
 }
 
 impl EventLoop {
@@ -50,25 +86,39 @@ impl EventLoop {
         swap_id: Uuid,
         swarm: Swarm<Behaviour>,
         alice_peer_id: PeerId,
+        db: Option<Arc<dyn Database + Send + Sync>>,
         env_config: env::Config,
     ) -> Result<(Self, EventLoopHandle)> {
         let execution_setup = bmrng::channel_with_timeout(1, Duration::from_secs(60));
         let transfer_proof = bmrng::channel_with_timeout(1, Duration::from_secs(60));
         let encrypted_signature = bmrng::channel(1);
         let quote = bmrng::channel_with_timeout(1, Duration::from_secs(60));
+        let cooperative_xmr_redeem = bmrng::channel_with_timeout(1, Duration::from_secs(60));
+        let receipt = bmrng::channel_with_timeout(1, Duration::from_secs(60));
+        let swap_state = watch::channel(None);
 
         let event_loop = EventLoop {
             swap_id,
             swarm,
             alice_peer_id,
+            db,
             swap_setup_requests: execution_setup.1.into(),
             transfer_proof: transfer_proof.0,
             encrypted_signatures: encrypted_signature.1.into(),
             quote_requests: quote.1.into(),
+            cooperative_xmr_redeem_requests: cooperative_xmr_redeem.1.into(),
+            receipt_requests: receipt.1.into(),
             inflight_quote_requests: HashMap::default(),
             inflight_swap_setup: None,
             inflight_encrypted_signature_requests: HashMap::default(),
+            inflight_cooperative_xmr_redeem_requests: HashMap::default(),
+            inflight_receipt_requests: HashMap::default(),
             pending_transfer_proof: OptionFuture::from(None),
+            swap_state: swap_state.1,
+            swap_status_due: false,
+            encrypted_signature_acknowledged: false,
+            withhold_encrypted_signature: false,
+            connection_stats: ConnectionStats::default(),
         };
 
         let handle = EventLoopHandle {
@@ -76,6 +126,9 @@ impl EventLoop {
             transfer_proof: transfer_proof.1,
             encrypted_signature: encrypted_signature.0,
             quote: quote.0,
+            cooperative_xmr_redeem: cooperative_xmr_redeem.0,
+            receipt: receipt.0,
+            swap_state: swap_state.0,
             env_config,
         };
 
@@ -83,7 +136,7 @@ impl EventLoop {
     }
 
     pub async fn run(mut self) {
-        match self.swarm.dial(&self.alice_peer_id) {
+        match self.swarm.dial(self.alice_peer_id) {
             Ok(()) => {}
             Err(e) => {
                 tracing::error!("Failed to initiate dial to Alice: {}", e);
@@ -98,7 +151,7 @@ impl EventLoop {
                     match swarm_event {
                         SwarmEvent::Behaviour(OutEvent::QuoteReceived { id, response }) => {
                             if let Some(responder) = self.inflight_quote_requests.remove(&id) {
-                                let _ = responder.respond(response);
+                                let _ = responder.respond(self.accept_quote(response).await);
                             }
                         }
                         SwarmEvent::Behaviour(OutEvent::SwapSetupCompleted(response)) => {
@@ -128,6 +181,13 @@ impl EventLoop {
                                 continue;
                             }
 
+                            let nonce = msg.nonce;
+                            if self.transfer_proof_processed(nonce, &msg.tx_lock_proof).await {
+                                tracing::debug!("Acknowledging transfer proof that was applied before");
+                                let _ = self.swarm.behaviour_mut().transfer_proof.send_response(channel, ());
+                                continue;
+                            }
+
                             let mut responder = match self.transfer_proof.send(msg.tx_lock_proof).await {
                                 Ok(responder) => responder,
                                 Err(e) => {
@@ -136,17 +196,35 @@ impl EventLoop {
                                 }
                             };
 
+                            let db = self.db.clone();
                             self.pending_transfer_proof = OptionFuture::from(Some(async move {
                                 let _ = responder.recv().await;
+                                if let Some(db) = db {
+                                    outbox::record_received(db.as_ref(), swap_id, MessageKind::TransferProof, nonce).await;
+                                }
 
                                 channel
                             }.boxed()));
                         }
                         SwarmEvent::Behaviour(OutEvent::EncryptedSignatureAcknowledged { id }) => {
+                            self.remove_encrypted_signature_from_outbox().await;
                             if let Some(responder) = self.inflight_encrypted_signature_requests.remove(&id) {
                                 let _ = responder.respond(());
                             }
                         }
+                        SwarmEvent::Behaviour(OutEvent::CooperativeXmrRedeemReceived { id, response }) => {
+                            if let Some(responder) = self.inflight_cooperative_xmr_redeem_requests.remove(&id) {
+                                let _ = responder.respond(response);
+                            }
+                        }
+                        SwarmEvent::Behaviour(OutEvent::ReceiptReceived { id, response }) => {
+                            if let Some(responder) = self.inflight_receipt_requests.remove(&id) {
+                                let _ = responder.respond(response);
+                            }
+                        }
+                        SwarmEvent::Behaviour(OutEvent::SwapStatusReceived(status)) => {
+                            self.resynchronize(*status).await;
+                        }
                         SwarmEvent::Behaviour(OutEvent::AllRedialAttemptsExhausted { peer }) if peer == self.alice_peer_id => {
                             tracing::error!("Exhausted all re-dial attempts to Alice");
                             return;
@@ -155,22 +233,60 @@ impl EventLoop {
                             tracing::warn!(%peer, "Communication error: {:#}", error);
                             return;
                         }
+                        SwarmEvent::Behaviour(OutEvent::HolePunch(hole_punch::OutEvent::Upgraded { peer })) if peer == self.alice_peer_id => {
+                            tracing::info!("Upgraded the relayed connection to Alice to a direct one");
+                        }
+                        SwarmEvent::Behaviour(OutEvent::HolePunch(hole_punch::OutEvent::UpgradeFailed { peer, error })) if peer == self.alice_peer_id => {
+                            tracing::info!("Failed to upgrade the relayed connection to Alice, staying on the relay: {:#}", error);
+                        }
                         SwarmEvent::ConnectionEstablished { peer_id, endpoint, .. } if peer_id == self.alice_peer_id => {
-                            tracing::info!("Connected to Alice at {}", endpoint.get_remote_address());
+                            let relayed = endpoint.is_relayed();
+                            let kind = if relayed { "relayed" } else { "direct" };
+
+                            if self.connection_stats.established(peer_id, relayed, Instant::now()) {
+                                let reconnects = self.connection_stats.counts().reconnects;
+                                tracing::info!(%reconnects, "Reconnected to Alice at {} ({})", endpoint.get_remote_address(), kind);
+                            } else {
+                                tracing::info!("Connected to Alice at {} ({})", endpoint.get_remote_address(), kind);
+                            }
+
+                            self.record_address_outcome(endpoint.get_remote_address().clone(), true).await;
+
+                            self.swap_status_due = true;
+                            self.request_swap_status();
                         }
                         SwarmEvent::Dialing(peer_id) if peer_id == self.alice_peer_id => {
                             tracing::debug!("Dialling Alice at {}", peer_id);
                         }
                         SwarmEvent::ConnectionClosed { peer_id, endpoint, num_established, cause: Some(error) } if peer_id == self.alice_peer_id && num_established == 0 => {
                             tracing::warn!("Lost connection to Alice at {}, cause: {}", endpoint.get_remote_address(), error);
+
+                            self.connection_stats.closed(peer_id, endpoint.is_relayed(), 0, true, Instant::now());
+
+                            // The substream of the swap setup was closed with the connection
+                            if let Some(responder) = self.inflight_swap_setup.take() {
+                                let _ = responder.respond(Err(anyhow!("Lost connection to Alice during the swap setup: {}", error)));
+                            }
                         }
                         SwarmEvent::ConnectionClosed { peer_id, num_established, cause: None, .. } if peer_id == self.alice_peer_id && num_established == 0 => {
                             // no error means the disconnection was requested
                             tracing::info!("Successfully closed connection to Alice");
                             return;
                         }
-                        SwarmEvent::UnreachableAddr { peer_id, address, attempts_remaining, error } if peer_id == self.alice_peer_id && attempts_remaining == 0 => {
-                            tracing::warn!(%address, "Failed to dial Alice: {}", error);
+                        SwarmEvent::ConnectionClosed { peer_id, endpoint, num_established, .. } if peer_id == self.alice_peer_id => {
+                            // e.g. the relayed connection after it was upgraded
+                            tracing::debug!("Closed one of the connections to Alice at {}", endpoint.get_remote_address());
+
+                            self.connection_stats.closed(peer_id, endpoint.is_relayed(), num_established, false, Instant::now());
+                        }
+                        SwarmEvent::OutgoingConnectionError { peer_id: Some(peer_id), error } if peer_id == self.alice_peer_id => {
+                            if let DialError::Transport(errors) = &error {
+                                for (address, _) in errors {
+                                    self.record_address_outcome(address.clone(), false).await;
+                                }
+                            }
+
+                            tracing::warn!("Failed to dial Alice: {}", error);
 
                             if let Some(duration) = self.swarm.behaviour_mut().redial.until_next_redial() {
                                 tracing::info!("Next redial attempt in {}s", duration.as_secs());
@@ -190,21 +306,47 @@ impl EventLoop {
                     self.swarm.behaviour_mut().swap_setup.start(self.alice_peer_id, swap).await;
                     self.inflight_swap_setup = Some(responder);
                 },
-                Some((tx_redeem_encsig, responder)) = self.encrypted_signatures.next().fuse(), if self.is_connected_to_alice() => {
-                    let request = encrypted_signature::Request {
+                Some(((tx_redeem_encsig, transcript), responder)) = self.encrypted_signatures.next().fuse(), if self.can_send_encrypted_signature() => {
+                    if self.encrypted_signature_acknowledged {
+                        let _ = responder.respond(());
+                        continue;
+                    }
+
+                    let request = self.put_into_outbox(encrypted_signature::Request {
                         swap_id: self.swap_id,
-                        tx_redeem_encsig
-                    };
+                        tx_redeem_encsig,
+                        nonce: Some(Uuid::new_v4()),
+                        transcript,
+                    }).await;
 
                     let id = self.swarm.behaviour_mut().encrypted_signature.send_request(&self.alice_peer_id, request);
                     self.inflight_encrypted_signature_requests.insert(id, responder);
                 },
 
+                Some(((), responder)) = self.cooperative_xmr_redeem_requests.next().fuse(), if self.is_connected_to_alice() => {
+                    let request = cooperative_xmr_redeem_after_punish::Request {
+                        swap_id: self.swap_id,
+                    };
+
+                    let id = self.swarm.behaviour_mut().cooperative_xmr_redeem.send_request(&self.alice_peer_id, request);
+                    self.inflight_cooperative_xmr_redeem_requests.insert(id, responder);
+                },
+
+                Some((request, responder)) = self.receipt_requests.next().fuse(), if self.is_connected_to_alice() => {
+                    let id = self.swarm.behaviour_mut().receipt.send_request(&self.alice_peer_id, request);
+                    self.inflight_receipt_requests.insert(id, responder);
+                },
+
                 Some(response_channel) = &mut self.pending_transfer_proof => {
                     let _ = self.swarm.behaviour_mut().transfer_proof.send_response(response_channel, ());
 
                     self.pending_transfer_proof = OptionFuture::from(None);
                 }
+
+                // The swap may only report its state after we connected to Alice.
+                Ok(()) = self.swap_state.changed() => {
+                    self.request_swap_status();
+                }
             }
         }
     }
@@ -212,14 +354,251 @@ impl EventLoop {
     fn is_connected_to_alice(&self) -> bool {
         self.swarm.is_connected(&self.alice_peer_id)
     }
+
+    /// Keeps track of how reliably Alice can be reached at her known
+    /// addresses, so the swap and the next ones dial the ones that work first.
+    async fn record_address_outcome(&mut self, address: Multiaddr, reachable: bool) {
+        let db = match &self.db {
+            Some(db) => db,
+            None => return,
+        };
+
+        if let Err(error) = db
+            .record_address_outcome(
+                self.alice_peer_id,
+                address.clone(),
+                reachable,
+                OffsetDateTime::now_utc(),
+            )
+            .await
+        {
+            tracing::warn!(%address, "Failed to record whether Alice is reachable: {:#}", error);
+        }
+    }
+
+    fn can_send_encrypted_signature(&self) -> bool {
+        !self.withhold_encrypted_signature
+            && (self.encrypted_signature_acknowledged || self.is_connected_to_alice())
+    }
+
+    fn own_status(&self) -> Option<Status> {
+        self.swap_state
+            .borrow()
+            .as_ref()
+            .map(|state| Status::bob(self.swap_id, state))
+    }
+
+    /// Sends our status of the swap to Alice once per connection, provided
+    /// the swap is in progress.
+    fn request_swap_status(&mut self) {
+        if !self.swap_status_due || !self.is_connected_to_alice() {
+            return;
+        }
+        let status = match self.own_status() {
+            Some(status) => status,
+            None => return,
+        };
+
+        self.swap_status_due = false;
+        if status.is_ongoing() {
+            self.swarm
+                .behaviour_mut()
+                .swap_status
+                .send_request(&self.alice_peer_id, status);
+        }
+    }
+
+    /// Puts the encrypted signature into the outbox, where it stays until
+    /// Alice acknowledged it. One that is in the outbox already is returned
+    /// with its original nonce.
+    async fn put_into_outbox(
+        &mut self,
+        request: encrypted_signature::Request,
+    ) -> encrypted_signature::Request {
+        let db = match &self.db {
+            Some(db) => db,
+            None => return request,
+        };
+
+        match db
+            .insert_outbound(self.alice_peer_id, request.clone().into())
+            .await
+        {
+            Ok(outbox::Message::EncryptedSignature(stored)) => stored,
+            Ok(_) => request,
+            Err(error) => {
+                tracing::warn!("Failed to put encrypted signature into outbox: {:#}", error);
+                request
+            }
+        }
+    }
+
+    /// The encrypted signature of this swap that is waiting in the outbox for
+    /// Alice's acknowledgement, if any.
+    async fn encrypted_signature_in_outbox(&mut self) -> Option<encrypted_signature::Request> {
+        let db = self.db.as_ref()?;
+
+        let messages = match db.get_outbound(self.alice_peer_id).await {
+            Ok(messages) => messages,
+            Err(error) => {
+                tracing::warn!(
+                    "Failed to load encrypted signature from outbox: {:#}",
+                    error
+                );
+                return None;
+            }
+        };
+
+        messages.into_iter().find_map(|message| match message {
+            outbox::Message::EncryptedSignature(request) if request.swap_id == self.swap_id => {
+                Some(request)
+            }
+            _ => None,
+        })
+    }
+
+    /// Checks that a signed quote was signed by Alice and keeps it as evidence
+    /// of the terms she offered. Quotes of sellers that do not sign them are
+    /// accepted as well.
+    async fn accept_quote(&mut self, response: SignedQuote) -> Result<Quote> {
+        let terms = response
+            .verify(&self.alice_peer_id)
+            .context("Refusing the seller's quote")?;
+        if let Some(maker_id) = response
+            .maker_id(&self.alice_peer_id)
+            .context("Refusing the seller's quote")?
+        {
+            tracing::debug!(%maker_id, "Seller signed the quote with its maker key");
+        }
+
+        match (terms, &self.db) {
+            (Some(_), Some(db)) => {
+                if let Err(error) = db
+                    .insert_signed_quote(self.swap_id, self.alice_peer_id, &response)
+                    .await
+                {
+                    tracing::warn!("Failed to store signed quote: {:#}", error);
+                }
+            }
+            (Some(_), None) => {}
+            (None, _) => tracing::debug!("Received a quote the seller did not sign"),
+        }
+
+        Ok(response.quote)
+    }
+
+    async fn remove_encrypted_signature_from_outbox(&mut self) {
+        if let Some(db) = &self.db {
+            if let Err(error) = db
+                .remove_outbound(self.swap_id, MessageKind::EncryptedSignature)
+                .await
+            {
+                tracing::warn!(
+                    "Failed to remove acknowledged encrypted signature from outbox: {:#}",
+                    error
+                );
+            }
+        }
+    }
+
+    /// Whether the transfer proof was applied before, i.e. applying it to the
+    /// latest state of the swap leaves the state unchanged, or processed
+    /// before according to its nonce while the swap no longer waits for it.
+    /// It is only acknowledged then.
+    async fn transfer_proof_processed(
+        &mut self,
+        nonce: Option<Uuid>,
+        transfer_proof: &monero::TransferProof,
+    ) -> bool {
+        let (applied, waiting) = match &*self.swap_state.borrow() {
+            Some(state) => (
+                state
+                    .clone()
+                    .receive_transfer_proof(transfer_proof.clone())
+                    .map_or(false, |applied| &applied == state),
+                matches!(state, BobState::BtcLocked { .. }),
+            ),
+            None => (false, true),
+        };
+        if applied {
+            return true;
+        }
+
+        match &self.db {
+            Some(db) if !waiting => {
+                outbox::was_received(db.as_ref(), self.swap_id, MessageKind::TransferProof, nonce)
+                    .await
+            }
+            _ => false,
+        }
+    }
+
+    async fn resynchronize(&mut self, alice_status: Status) {
+        let status = match self.own_status() {
+            Some(status) => status,
+            None => return,
+        };
+
+        match swap_status::resolve(&status, &alice_status) {
+            Resolution::InSync => {
+                tracing::debug!(phase = ?status.phase, "Swap is in sync with Alice");
+            }
+            Resolution::PeerBehind(Phase::XmrLocked) => {
+                let state4 = match &*self.swap_state.borrow() {
+                    Some(BobState::EncSigSent(state4)) => state4.clone(),
+                    _ => return,
+                };
+                tracing::info!("Alice did not receive the encrypted signature, sending it again");
+
+                // Resent with the nonce it was first sent with, should the first one still arrive
+                let request = match self.encrypted_signature_in_outbox().await {
+                    Some(request) => request,
+                    None => {
+                        self.put_into_outbox(encrypted_signature::Request {
+                            swap_id: self.swap_id,
+                            tx_redeem_encsig: state4.tx_redeem_encsig(),
+                            nonce: Some(Uuid::new_v4()),
+                            transcript: state4.transcript(),
+                        })
+                        .await
+                    }
+                };
+                let _ = self
+                    .swarm
+                    .behaviour_mut()
+                    .encrypted_signature
+                    .send_request(&self.alice_peer_id, request);
+            }
+            Resolution::PeerAhead(phase) if phase >= Phase::EncSigExchanged => {
+                tracing::info!("Alice already received the encrypted signature");
+                self.encrypted_signature_acknowledged = true;
+                self.remove_encrypted_signature_from_outbox().await;
+            }
+            Resolution::PeerBehind(phase) | Resolution::PeerAhead(phase) => {
+                tracing::debug!(own = ?status.phase, alice = ?phase, "Swap is resynchronized with Alice");
+            }
+            resolution @ Resolution::Cancel | resolution @ Resolution::Conflict(_) => {
+                tracing::warn!(
+                    own = ?status.phase,
+                    alice = ?alice_status.phase,
+                    "Swap status of Alice contradicts ours ({:?}), the encrypted signature will not be sent",
+                    resolution
+                );
+                self.withhold_encrypted_signature = true;
+            }
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct EventLoopHandle {
     swap_setup: bmrng::RequestSender<NewSwap, Result<State2>>,
     transfer_proof: bmrng::RequestReceiver<monero::TransferProof, ()>,
-    encrypted_signature: bmrng::RequestSender<EncryptedSignature, ()>,
-    quote: bmrng::RequestSender<(), BidQuote>,
+    encrypted_signature: bmrng::RequestSender<(EncryptedSignature, Option<TranscriptHash>), ()>,
+    quote: bmrng::RequestSender<(), Result<Quote>>,
+    cooperative_xmr_redeem: bmrng::RequestSender<(), Response>,
+    receipt: bmrng::RequestSender<receipt::Request, receipt::Response>,
+    swap_state: watch::Sender<Option<BobState>>,
     env_config: env::Config,
 }
 
@@ -241,17 +620,37 @@ impl EventLoopHandle {
         Ok(transfer_proof)
     }
 
-    pub async fn request_quote(&mut self) -> Result<BidQuote> {
-        Ok(self.quote.send_receive(()).await?)
+    /// Lets the event loop know about the latest state of the swap.
+    pub fn update_state(&self, state: &BobState) {
+        let _ = self.swap_state.send(Some(state.clone()));
+    }
+
+    /// Requests a quote, failing if it is signed by anybody but Alice.
+    pub async fn request_quote(&mut self) -> Result<Quote> {
+        self.quote.send_receive(()).await?
+    }
+
+    /// Asks Alice for her key share after she punished us.
+    pub async fn request_cooperative_xmr_redeem(&mut self) -> Result<Response> {
+        Ok(self.cooperative_xmr_redeem.send_receive(()).await?)
+    }
+
+    /// Asks Alice to countersign the receipt of the swap.
+    pub async fn request_receipt(
+        &mut self,
+        request: receipt::Request,
+    ) -> Result<receipt::Response> {
+        Ok(self.receipt.send_receive(request).await?)
     }
 
     pub async fn send_encrypted_signature(
         &mut self,
         tx_redeem_encsig: EncryptedSignature,
-    ) -> Result<(), bmrng::error::RequestError<EncryptedSignature>> {
+        transcript: Option<TranscriptHash>,
+    ) -> Result<(), bmrng::error::RequestError<(EncryptedSignature, Option<TranscriptHash>)>> {
         Ok(self
             .encrypted_signature
-            .send_receive(tx_redeem_encsig)
+            .send_receive((tx_redeem_encsig, transcript))
             .await?)
     }
 }
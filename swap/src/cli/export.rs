@@ -0,0 +1,376 @@
+//! Moving an unfinished swap to another machine.
+//!
+//! An export contains everything needed to resume the swap: its latest state,
+//! the peer id and addresses of the seller and the Monero receive address. It
+//! is encrypted with a random key that is printed once and has to be passed to
+//! the import on the other machine.
+//!
+//! Every export increments the generation of the swap and marks it as exported
+//! in the local database, after which it can no longer be resumed, cancelled or
+//! refunded from there. An import is only accepted if its generation is newer
+//! than the one known to the importing database. This allows moving a swap back
+//! and forth while making sure an outdated export cannot be imported again.
+
+use crate::database::{Generation, Swap};
+use crate::monero;
+use crate::protocol::bob::BobState;
+use crate::protocol::{Database, State};
+use anyhow::{anyhow, bail, Context, Result};
+use chacha20poly1305::aead::{Aead, NewAead};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use libp2p::{Multiaddr, PeerId};
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DisplayFromStr};
+use std::convert::{TryFrom, TryInto};
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use subtle::ConstantTimeEq;
+use uuid::Uuid;
+use zeroize::Zeroize;
+
+const VERSION: u8 = 1;
+
+/// The key an exported swap is encrypted with.
+#[derive(Clone)]
+pub struct ExportKey([u8; 32]);
+
+impl ExportKey {
+    fn random() -> Self {
+        Self(rand::random())
+    }
+
+    fn cipher(&self) -> ChaCha20Poly1305 {
+        ChaCha20Poly1305::new(Key::from_slice(&self.0))
+    }
+}
+
+impl fmt::Display for ExportKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", hex::encode(self.0))
+    }
+}
+
+impl PartialEq for ExportKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.0[..].ct_eq(&other.0[..]).into()
+    }
+}
+
+impl Drop for ExportKey {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl fmt::Debug for ExportKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ExportKey(..)")
+    }
+}
+
+impl FromStr for ExportKey {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let bytes = hex::decode(s).context("Export key is not hex encoded")?;
+        let key = <[u8; 32]>::try_from(bytes.as_slice())
+            .map_err(|_| anyhow!("Export key must be 32 bytes long"))?;
+
+        Ok(Self(key))
+    }
+}
+
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct ExportedSwap {
+    swap_id: Uuid,
+    generation: u32,
+    state: Swap,
+    #[serde_as(as = "DisplayFromStr")]
+    peer_id: PeerId,
+    #[serde_as(as = "Vec<DisplayFromStr>")]
+    peer_addresses: Vec<Multiaddr>,
+    #[serde_as(as = "DisplayFromStr")]
+    monero_receive_address: monero::Address,
+}
+
+/// The content of an export file.
+#[derive(Debug, Serialize, Deserialize)]
+struct EncryptedSwap {
+    version: u8,
+    nonce: String,
+    ciphertext: String,
+}
+
+impl EncryptedSwap {
+    fn seal(swap: &ExportedSwap, key: &ExportKey) -> Result<Self> {
+        let plaintext = serde_json::to_vec(swap)?;
+        let nonce = rand::random::<[u8; 12]>();
+
+        let ciphertext = key
+            .cipher()
+            .encrypt(Nonce::from_slice(&nonce), plaintext.as_slice())
+            .map_err(|_| anyhow!("Failed to encrypt swap"))?;
+
+        Ok(Self {
+            version: VERSION,
+            nonce: base64::encode(nonce),
+            ciphertext: base64::encode(ciphertext),
+        })
+    }
+
+    fn open(&self, key: &ExportKey) -> Result<ExportedSwap> {
+        if self.version != VERSION {
+            bail!("Unsupported export version {}", self.version)
+        }
+
+        let nonce = base64::decode(&self.nonce)?;
+        if nonce.len() != 12 {
+            bail!("Export file is corrupted")
+        }
+        let ciphertext = base64::decode(&self.ciphertext)?;
+
+        let plaintext = key
+            .cipher()
+            .decrypt(Nonce::from_slice(&nonce), ciphertext.as_slice())
+            .map_err(|_| anyhow!("Failed to decrypt swap, the export key does not match"))?;
+
+        Ok(serde_json::from_slice(&plaintext)?)
+    }
+}
+
+/// Writes the swap to `file` and marks it as exported, so it can only be
+/// resumed on the machine it is imported on.
+pub async fn export(
+    swap_id: Uuid,
+    file: &Path,
+    db: &(dyn Database + Send + Sync),
+) -> Result<ExportKey> {
+    ensure_not_exported(swap_id, db).await?;
+
+    let state = db.get_state(swap_id).await?;
+    if state.swap_finished() {
+        bail!(
+            "Swap {} is already finished, there is nothing to export",
+            swap_id
+        )
+    }
+    let state: BobState = state.try_into()?;
+
+    let peer_id = db.get_peer_id(swap_id).await?;
+    let generation = db
+        .get_generation(swap_id)
+        .await?
+        .map_or(1, |generation| generation.number + 1);
+    let peer_addresses = db.get_addresses(peer_id).await?;
+    let monero_receive_address = db.get_monero_address(swap_id).await?;
+
+    let swap = ExportedSwap {
+        swap_id,
+        generation,
+        state: State::Bob(state).into(),
+        peer_id,
+        peer_addresses,
+        monero_receive_address,
+    };
+
+    let key = ExportKey::random();
+    let encrypted = serde_json::to_vec_pretty(&EncryptedSwap::seal(&swap, &key)?)?;
+
+    tokio::fs::write(file, encrypted)
+        .await
+        .with_context(|| format!("Failed to write export file {}", file.display()))?;
+
+    db.insert_generation(swap_id, Generation {
+        number: generation,
+        exported: true,
+    })
+    .await?;
+
+    tracing::info!(%swap_id, %generation, file = %file.display(), "Exported swap");
+
+    Ok(key)
+}
+
+/// Imports a swap exported on another machine and returns its id.
+pub async fn import(
+    file: &Path,
+    key: &ExportKey,
+    db: &(dyn Database + Send + Sync),
+) -> Result<Uuid> {
+    let encrypted = tokio::fs::read(file)
+        .await
+        .with_context(|| format!("Failed to read export file {}", file.display()))?;
+    let swap = serde_json::from_slice::<EncryptedSwap>(&encrypted)
+        .context("Not an export file")?
+        .open(key)?;
+    let swap_id = swap.swap_id;
+
+    match db.get_generation(swap_id).await? {
+        Some(known) if known.number >= swap.generation => bail!(
+            "Swap {} was already moved with generation {}, this export of generation {} is outdated",
+            swap_id,
+            known.number,
+            swap.generation
+        ),
+        Some(_) => {}
+        None if db.get_state(swap_id).await.is_ok() => {
+            bail!("Swap {} already exists in the database", swap_id)
+        }
+        None => {}
+    }
+
+    db.insert_latest_state(swap_id, swap.state.into()).await?;
+
+    if db.get_peer_id(swap_id).await.is_err() {
+        db.insert_peer_id(swap_id, swap.peer_id).await?;
+    }
+    let known_addresses = db.get_addresses(swap.peer_id).await?;
+    for address in swap.peer_addresses {
+        if !known_addresses.contains(&address) {
+            db.insert_address(swap.peer_id, address).await?;
+        }
+    }
+    if db.get_monero_address(swap_id).await.is_err() {
+        db.insert_monero_address(swap_id, swap.monero_receive_address)
+            .await?;
+    }
+
+    db.insert_generation(swap_id, Generation {
+        number: swap.generation,
+        exported: false,
+    })
+    .await?;
+
+    tracing::info!(%swap_id, generation = %swap.generation, "Imported swap");
+
+    Ok(swap_id)
+}
+
+/// Fails if the swap was exported to another machine and not imported back.
+pub async fn ensure_not_exported(swap_id: Uuid, db: &(dyn Database + Send + Sync)) -> Result<()> {
+    if let Some(Generation { exported: true, .. }) = db.get_generation(swap_id).await? {
+        bail!(
+            "Swap {} was exported to another machine, import it again to resume it here",
+            swap_id
+        )
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitcoin;
+    use crate::database::SqliteDatabase;
+    use std::fs::File;
+    use tempfile::{tempdir, TempDir};
+
+    #[tokio::test]
+    async fn exported_swap_can_be_imported_and_is_locked_on_origin() -> Result<()> {
+        let dir = tempdir()?;
+        let origin = open_db(&dir, "origin").await?;
+        let target = open_db(&dir, "target").await?;
+        let file = dir.path().join("swap.json");
+        let swap_id = insert_swap(&origin).await?;
+
+        let key = export(swap_id, &file, &origin).await?;
+        let imported = import(&file, &key, &target).await?;
+
+        assert_eq!(imported, swap_id);
+        assert_eq!(
+            target.get_state(swap_id).await?,
+            origin.get_state(swap_id).await?
+        );
+        assert!(ensure_not_exported(swap_id, &origin).await.is_err());
+        assert!(ensure_not_exported(swap_id, &target).await.is_ok());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn given_outdated_export_then_import_fails() -> Result<()> {
+        let dir = tempdir()?;
+        let origin = open_db(&dir, "origin").await?;
+        let target = open_db(&dir, "target").await?;
+        let first = dir.path().join("first.json");
+        let second = dir.path().join("second.json");
+        let swap_id = insert_swap(&origin).await?;
+
+        let first_key = export(swap_id, &first, &origin).await?;
+        import(&first, &first_key, &target).await?;
+
+        // move the swap back, which makes the first export outdated
+        let second_key = export(swap_id, &second, &target).await?;
+        import(&second, &second_key, &origin).await?;
+
+        assert!(ensure_not_exported(swap_id, &origin).await.is_ok());
+        assert!(import(&first, &first_key, &target).await.is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn given_wrong_key_then_import_fails() -> Result<()> {
+        let dir = tempdir()?;
+        let origin = open_db(&dir, "origin").await?;
+        let target = open_db(&dir, "target").await?;
+        let file = dir.path().join("swap.json");
+        let swap_id = insert_swap(&origin).await?;
+
+        export(swap_id, &file, &origin).await?;
+        let error = import(&file, &ExportKey::random(), &target)
+            .await
+            .unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "Failed to decrypt swap, the export key does not match"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn export_key_roundtrips_through_string() {
+        let key = ExportKey::random();
+
+        assert_eq!(key.to_string().parse::<ExportKey>().unwrap(), key);
+    }
+
+    async fn insert_swap(db: &SqliteDatabase) -> Result<Uuid> {
+        let swap_id = Uuid::new_v4();
+        let peer_id = PeerId::random();
+
+        db.insert_latest_state(
+            swap_id,
+            State::Bob(BobState::Started {
+                btc_amount: bitcoin::Amount::from_sat(100_000),
+                change_address: "tb1qyccwk4yun26708qg5h6g6we8kxln232wclxf5a".parse()?,
+                xmr_amount: None,
+            }),
+        )
+        .await?;
+        db.insert_peer_id(swap_id, peer_id).await?;
+        db.insert_address(peer_id, "/ip4/127.0.0.1/tcp/9939".parse()?)
+            .await?;
+        db.insert_monero_address(
+            swap_id,
+            "53gEuGZUhP9JMEBZoGaFNzhwEgiG7hwQdMCqFxiyiTeFPmkbt1mAoNybEUvYBKHcnrSgxnVWgZsTvRBaHBNXPa8tHiCU51a".parse()?,
+        )
+        .await?;
+
+        Ok(swap_id)
+    }
+
+    async fn open_db(dir: &TempDir, name: &str) -> Result<SqliteDatabase> {
+        let path = dir.path().join(name);
+
+        // file has to exist in order to connect with sqlite
+        File::create(&path)?;
+
+        SqliteDatabase::open(path).await
+    }
+}
@@ -0,0 +1,198 @@
+//! Renders the states a swap went through, e.g. to see where a swap got stuck.
+//!
+//! Every state stored in the database becomes a node labelled with the time it
+//! was entered, the edge leading to it is labelled with the event that caused
+//! the transition. Pruned swaps only have their latest state left.
+
+use crate::protocol::bob::BobState;
+use crate::protocol::Database;
+use anyhow::{bail, Result};
+use std::convert::TryInto;
+use time::{OffsetDateTime, UtcOffset};
+use uuid::Uuid;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Text,
+    Dot,
+    Mermaid,
+}
+
+#[derive(Debug, PartialEq)]
+struct Transition {
+    entered_at: OffsetDateTime,
+    state: String,
+    event: &'static str,
+}
+
+pub async fn inspect(
+    swap_id: Uuid,
+    format: Format,
+    db: &(dyn Database + Send + Sync),
+) -> Result<String> {
+    let history = db.get_state_history(swap_id).await?;
+    if history.is_empty() {
+        bail!("No state in database for swap: {}", swap_id)
+    }
+
+    let transitions = history
+        .into_iter()
+        .map(|(entered_at, state)| {
+            let state: BobState = state.try_into()?;
+
+            Ok(Transition {
+                entered_at,
+                event: event(&state),
+                state: state.to_string(),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(match format {
+        Format::Text => text(&transitions),
+        Format::Dot => dot(swap_id, &transitions),
+        Format::Mermaid => mermaid(&transitions),
+    })
+}
+
+/// The event that makes Bob enter the state.
+fn event(state: &BobState) -> &'static str {
+    match state {
+        BobState::Started { .. } => "Swap started",
+        BobState::SwapSetupCompleted(..) => "Swap setup with seller completed",
+        BobState::BtcLocked { .. } => "Bitcoin lock transaction published",
+        BobState::XmrLockProofReceived { .. } => "Transfer proof received",
+        BobState::XmrLocked(..) => "Monero lock transaction confirmed",
+        BobState::EncSigSent(..) => "Encrypted signature sent",
+        BobState::BtcRedeemed(..) => "Bitcoin redeem transaction seen",
+        BobState::CancelTimelockExpired(..) => "Cancel timelock expired",
+        BobState::BtcCancelled(..) => "Bitcoin cancel transaction published",
+        BobState::BtcRefunded(..) => "Bitcoin refund transaction published",
+        BobState::XmrRedeemed { .. } => "Monero redeemed",
+        BobState::BtcPunished { .. } => "Bitcoin punished by seller",
+        BobState::SafelyAborted => "Swap aborted",
+    }
+}
+
+fn timestamp(entered_at: OffsetDateTime) -> String {
+    let utc = entered_at.to_offset(UtcOffset::UTC);
+
+    format!(
+        "{} {:02}:{:02}:{:02} UTC",
+        utc.date(),
+        utc.hour(),
+        utc.minute(),
+        utc.second()
+    )
+}
+
+fn text(transitions: &[Transition]) -> String {
+    transitions
+        .iter()
+        .map(|transition| {
+            format!(
+                "{}  {} ({})\n",
+                timestamp(transition.entered_at),
+                transition.state,
+                transition.event
+            )
+        })
+        .collect()
+}
+
+fn dot(swap_id: Uuid, transitions: &[Transition]) -> String {
+    let mut out = format!("digraph \"{}\" {{\n    node [shape=box];\n", swap_id);
+    for (i, transition) in transitions.iter().enumerate() {
+        out.push_str(&format!(
+            "    s{} [label=\"{}\\n{}\"];\n",
+            i,
+            transition.state,
+            timestamp(transition.entered_at)
+        ));
+        if i > 0 {
+            out.push_str(&format!(
+                "    s{} -> s{} [label=\"{}\"];\n",
+                i - 1,
+                i,
+                transition.event
+            ));
+        }
+    }
+    out.push_str("}\n");
+
+    out
+}
+
+fn mermaid(transitions: &[Transition]) -> String {
+    let mut out = String::from("graph TD\n");
+    for (i, transition) in transitions.iter().enumerate() {
+        out.push_str(&format!(
+            "    s{}[\"{}<br/>{}\"]\n",
+            i,
+            transition.state,
+            timestamp(transition.entered_at)
+        ));
+        if i > 0 {
+            out.push_str(&format!(
+                "    s{} -->|\"{}\"| s{}\n",
+                i - 1,
+                transition.event,
+                i
+            ));
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transitions() -> Vec<Transition> {
+        // 2021-09-01 10:00:00 UTC
+        let entered_at = OffsetDateTime::from_unix_timestamp(1_630_490_400).unwrap();
+
+        vec![
+            Transition {
+                entered_at,
+                state: "btc is locked".to_owned(),
+                event: "Bitcoin lock transaction published",
+            },
+            Transition {
+                entered_at: (entered_at + time::Duration::seconds(1805))
+                    .to_offset(UtcOffset::from_hms(2, 0, 0).unwrap()),
+                state: "XMR lock transaction transfer proof received".to_owned(),
+                event: "Transfer proof received",
+            },
+        ]
+    }
+
+    #[test]
+    fn renders_dot_graph() {
+        let swap_id = Uuid::nil();
+
+        assert_eq!(
+            dot(swap_id, &transitions()),
+            r#"digraph "00000000-0000-0000-0000-000000000000" {
+    node [shape=box];
+    s0 [label="btc is locked\n2021-09-01 10:00:00 UTC"];
+    s1 [label="XMR lock transaction transfer proof received\n2021-09-01 10:30:05 UTC"];
+    s0 -> s1 [label="Transfer proof received"];
+}
+"#
+        );
+    }
+
+    #[test]
+    fn renders_mermaid_graph() {
+        assert_eq!(
+            mermaid(&transitions()),
+            r#"graph TD
+    s0["btc is locked<br/>2021-09-01 10:00:00 UTC"]
+    s1["XMR lock transaction transfer proof received<br/>2021-09-01 10:30:05 UTC"]
+    s0 -->|"Transfer proof received"| s1
+"#
+        );
+    }
+}
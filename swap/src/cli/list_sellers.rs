@@ -1,25 +1,28 @@
-use crate::network::quote::BidQuote;
+use crate::amounts::Quote;
+use crate::bitcoin;
+use crate::network::quote::DepthBucket;
 use crate::network::rendezvous::XmrBtcNamespace;
-use crate::network::{quote, swarm};
+use crate::network::{address_family, quote, swarm};
 use anyhow::{Context, Result};
 use futures::StreamExt;
 use libp2p::multiaddr::Protocol;
 use libp2p::ping::{Ping, PingConfig, PingEvent};
+use libp2p::relay::v2::client::{self, Client};
 use libp2p::request_response::{RequestResponseEvent, RequestResponseMessage};
-use libp2p::swarm::SwarmEvent;
+use libp2p::swarm::{DialError, SwarmEvent};
 use libp2p::{identity, rendezvous, Multiaddr, PeerId, Swarm};
 use serde::Serialize;
 use serde_with::{serde_as, DisplayFromStr};
-use std::collections::hash_map::Entry;
 use std::collections::HashMap;
-use std::time::Duration;
+use std::convert::TryFrom;
+use std::time::{Duration, Instant};
 
 /// Returns sorted list of sellers, with [Online](Status::Online) listed first.
 ///
 /// First uses the rendezvous node to discover peers in the given namespace,
-/// then fetches a quote from each peer that was discovered. If fetching a quote
-/// from a discovered peer fails the seller's status will be
-/// [Unreachable](Status::Unreachable).
+/// then dials each peer that was discovered and fetches a quote from it,
+/// measuring how long both took. If dialing a discovered peer or fetching its
+/// quote fails the seller's status will be [Unreachable](Status::Unreachable).
 pub async fn list_sellers(
     rendezvous_node_peer_id: PeerId,
     rendezvous_node_addr: Multiaddr,
@@ -27,23 +30,25 @@ pub async fn list_sellers(
     tor_socks5_port: u16,
     identity: identity::Keypair,
 ) -> Result<Vec<Seller>> {
-    let behaviour = Behaviour {
-        rendezvous: rendezvous::client::Behaviour::new(identity.clone()),
+    let rendezvous = rendezvous::client::Behaviour::new(identity.clone());
+    let mut swarm = swarm::cli(identity, tor_socks5_port, |relay, _| Behaviour {
+        rendezvous,
         quote: quote::cli(),
+        relay,
         ping: Ping::new(
             PingConfig::new()
                 .with_keep_alive(false)
                 .with_interval(Duration::from_secs(86_400)),
         ),
-    };
-    let mut swarm = swarm::cli(identity, tor_socks5_port, behaviour).await?;
+    })
+    .await?;
 
     swarm
         .behaviour_mut()
         .quote
         .add_address(&rendezvous_node_peer_id, rendezvous_node_addr.clone());
     swarm
-        .dial(&rendezvous_node_peer_id)
+        .dial(rendezvous_node_peer_id)
         .context("Failed to dial rendezvous node")?;
 
     let event_loop = EventLoop::new(
@@ -61,13 +66,48 @@ pub async fn list_sellers(
 #[derive(Debug, Serialize, PartialEq, Eq, Hash, Ord, PartialOrd)]
 pub struct Seller {
     pub status: Status,
+    /// `None` if the seller is unreachable.
+    pub latency: Option<Latency>,
     #[serde_as(as = "DisplayFromStr")]
     pub multiaddr: Multiaddr,
+    /// How fast the seller can serve which amounts, empty if it does not say.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub depth: Vec<DepthBucket>,
+}
+
+impl Seller {
+    /// How long the seller may take to serve a swap of `btc`, `None` if it
+    /// cannot serve it at all.
+    pub fn delay_for(&self, btc: bitcoin::Amount) -> Option<Duration> {
+        match &self.status {
+            Status::Online(quote) => quote::delay_for(quote, &self.depth, btc),
+            Status::Unreachable => None,
+        }
+    }
+}
+
+/// How long it took to connect to a seller and, once connected, to receive
+/// its quote.
+#[derive(Debug, Default, Serialize, PartialEq, Eq, Hash, Copy, Clone, Ord, PartialOrd)]
+pub struct Latency {
+    pub connect_ms: u64,
+    pub quote_ms: u64,
+}
+
+impl Latency {
+    fn new(connect: Duration, quote: Duration) -> Self {
+        let millis = |duration: Duration| u64::try_from(duration.as_millis()).unwrap_or(u64::MAX);
+
+        Self {
+            connect_ms: millis(connect),
+            quote_ms: millis(quote),
+        }
+    }
 }
 
 #[derive(Debug, Serialize, PartialEq, Eq, Hash, Copy, Clone, Ord, PartialOrd)]
 pub enum Status {
-    Online(BidQuote),
+    Online(Quote),
     Unreachable,
 }
 
@@ -76,6 +116,7 @@ enum OutEvent {
     Rendezvous(rendezvous::client::Event),
     Quote(quote::OutEvent),
     Ping(PingEvent),
+    Relay,
 }
 
 impl From<rendezvous::client::Event> for OutEvent {
@@ -96,6 +137,8 @@ impl From<quote::OutEvent> for OutEvent {
 struct Behaviour {
     rendezvous: rendezvous::client::Behaviour,
     quote: quote::Behaviour,
+    /// Dials the sellers that are only reachable through a circuit relay.
+    relay: Client,
     ping: Ping,
 }
 
@@ -119,6 +162,10 @@ struct EventLoop {
     reachable_asb_address: HashMap<PeerId, Multiaddr>,
     unreachable_asb_address: HashMap<PeerId, Multiaddr>,
     asb_quote_status: HashMap<PeerId, QuoteStatus>,
+    quote_requested_at: HashMap<PeerId, Instant>,
+    connect_latency: HashMap<PeerId, Duration>,
+    latency: HashMap<PeerId, Latency>,
+    depth: HashMap<PeerId, Vec<DepthBucket>>,
     state: State,
 }
 
@@ -137,6 +184,10 @@ impl EventLoop {
             reachable_asb_address: Default::default(),
             unreachable_asb_address: Default::default(),
             asb_quote_status: Default::default(),
+            quote_requested_at: Default::default(),
+            connect_latency: Default::default(),
+            latency: Default::default(),
+            depth: Default::default(),
             state: State::WaitForDiscovery,
         }
     }
@@ -162,34 +213,33 @@ impl EventLoop {
                             } else {
                                 let address = endpoint.get_remote_address();
                                 self.reachable_asb_address.insert(peer_id, address.clone());
+
+                                if let Some(requested_at) = self.quote_requested_at.get(&peer_id) {
+                                    self.connect_latency.entry(peer_id).or_insert_with(|| requested_at.elapsed());
+                                }
                             }
                         }
-                        SwarmEvent::UnreachableAddr { peer_id, error, address, .. } => {
-                            if address == self.rendezvous_addr {
+                        SwarmEvent::OutgoingConnectionError { peer_id: Some(peer_id), error } => {
+                            if peer_id == self.rendezvous_peer_id {
                                 tracing::error!(
                                     "Failed to connect to rendezvous point at {}: {}",
-                                    address,
+                                    self.rendezvous_addr,
                                     error
                                 );
 
                                 // if the rendezvous node is unreachable we just stop
                                 return Vec::new();
-                            } else {
-                                tracing::debug!(
-                                    "Failed to connect to peer at {}: {}",
-                                    address,
-                                    error
-                                );
-                                self.unreachable_asb_address.insert(peer_id, address.clone());
-
-                                match self.asb_quote_status.entry(peer_id) {
-                                    Entry::Occupied(mut entry) => {
-                                        entry.insert(QuoteStatus::Received(Status::Unreachable));
-                                    },
-                                    _ => {
-                                        tracing::debug!(%peer_id, %error, "Connection error with unexpected peer")
+                            } else if self.asb_quote_status.contains_key(&peer_id) {
+                                tracing::debug!(%peer_id, "Failed to connect to peer: {}", error);
+
+                                if let DialError::Transport(errors) = &error {
+                                    if let Some((address, _)) = errors.last() {
+                                        self.unreachable_asb_address.insert(peer_id, address.clone());
                                     }
                                 }
+                                self.quote_failed(peer_id);
+                            } else {
+                                tracing::debug!(%peer_id, %error, "Connection error with unexpected peer")
                             }
                         }
                         SwarmEvent::Behaviour(OutEvent::Rendezvous(
@@ -199,7 +249,7 @@ impl EventLoop {
 
                             for registration in registrations {
                                 let peer = registration.record.peer_id();
-                                for address in registration.record.addresses() {
+                                for address in address_family::reachable_first(registration.record.addresses().to_vec()) {
                                     tracing::info!(peer_id=%peer, address=%address, "Discovered peer");
 
                                     let p2p_suffix = Protocol::P2p(*peer.as_ref());
@@ -214,11 +264,12 @@ impl EventLoop {
                                     self.asb_quote_status.insert(peer, QuoteStatus::Pending);
 
                                     // add all external addresses of that peer to the quote behaviour
-                                    self.swarm.behaviour_mut().quote.add_address(&peer, address.clone());
+                                    self.swarm.behaviour_mut().quote.add_address(&peer, address);
                                 }
 
                                 // request the quote, if we are not connected to the peer it will be dialed automatically
                                 let _request_id = self.swarm.behaviour_mut().quote.send_request(&peer, ());
+                                self.quote_requested_at.insert(peer, Instant::now());
                             }
                         }
                         SwarmEvent::Behaviour(OutEvent::Quote(quote_response)) => {
@@ -226,9 +277,19 @@ impl EventLoop {
                                 RequestResponseEvent::Message { peer, message } => {
                                     match message {
                                         RequestResponseMessage::Response { response, .. } => {
-                                            if self.asb_quote_status.insert(peer, QuoteStatus::Received(Status::Online(response))).is_none() {
+                                            if let Err(error) = response.verify(&peer).and_then(|_| response.maker_id(&peer)) {
+                                                tracing::debug!(%peer, "Ignoring seller, because its quote is not signed by it: {:#}", error);
+                                                self.asb_quote_status.remove(&peer);
+                                            } else if self.asb_quote_status.insert(peer, QuoteStatus::Received(Status::Online(response.quote))).is_none() {
                                                 tracing::error!(%peer, "Received bid quote from unexpected peer, this record will be removed!");
                                                 self.asb_quote_status.remove(&peer);
+                                            } else {
+                                                self.depth.insert(peer, response.depth);
+                                                if let Some(requested_at) = self.quote_requested_at.get(&peer) {
+                                                    let connect = self.connect_latency.get(&peer).copied().unwrap_or_default();
+                                                    let quote = requested_at.elapsed().saturating_sub(connect);
+                                                    self.latency.insert(peer, Latency::new(connect, quote));
+                                                }
                                             }
                                         }
                                         RequestResponseMessage::Request { .. } => unreachable!()
@@ -238,8 +299,8 @@ impl EventLoop {
                                     if peer == self.rendezvous_peer_id {
                                         tracing::debug!(%peer, "Outbound failure when communicating with rendezvous node: {:#}", error);
                                     } else {
-                                        tracing::debug!(%peer, "Ignoring seller, because unable to request quote: {:#}", error);
-                                        self.asb_quote_status.remove(&peer);
+                                        tracing::debug!(%peer, "Unable to request quote from seller: {:#}", error);
+                                        self.quote_failed(peer);
                                     }
                                 }
                                 RequestResponseEvent::InboundFailure { peer, error, .. } => {
@@ -271,23 +332,27 @@ impl EventLoop {
                             QuoteStatus::Received(Status::Online(quote)) => {
                                 let address = self
                                     .reachable_asb_address
-                                    .get(&peer_id)
+                                    .get(peer_id)
                                     .expect("if we got a quote we must have stored an address");
 
                                 Ok(Seller {
                                     multiaddr: address.clone(),
+                                    latency: self.latency.get(peer_id).copied(),
                                     status: Status::Online(*quote),
+                                    depth: self.depth.get(peer_id).cloned().unwrap_or_default(),
                                 })
                             }
                             QuoteStatus::Received(Status::Unreachable) => {
                                 let address = self
                                     .unreachable_asb_address
-                                    .get(&peer_id)
+                                    .get(peer_id)
                                     .expect("if we got a quote we must have stored an address");
 
                                 Ok(Seller {
                                     multiaddr: address.clone(),
+                                    latency: None,
                                     status: Status::Unreachable,
+                                    depth: Vec::new(),
                                 })
                             }
                         })
@@ -306,6 +371,23 @@ impl EventLoop {
     }
 }
 
+impl EventLoop {
+    /// Lists a seller that could not be dialed or did not answer with a
+    /// quote as unreachable, at the address it was last tried at.
+    fn quote_failed(&mut self, peer: PeerId) {
+        if let Some(address) = self.reachable_asb_address.get(&peer).cloned() {
+            self.unreachable_asb_address.entry(peer).or_insert(address);
+        }
+
+        if self.unreachable_asb_address.contains_key(&peer) {
+            self.asb_quote_status
+                .insert(peer, QuoteStatus::Received(Status::Unreachable));
+        } else {
+            self.asb_quote_status.remove(&peer);
+        }
+    }
+}
+
 #[derive(Debug)]
 struct StillPending {}
 
@@ -315,6 +397,12 @@ impl From<PingEvent> for OutEvent {
     }
 }
 
+impl From<client::Event> for OutEvent {
+    fn from(_: client::Event) -> Self {
+        OutEvent::Relay
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -323,20 +411,26 @@ mod tests {
     fn sellers_sort_with_unreachable_coming_last() {
         let mut list = vec![
             Seller {
+                latency: None,
                 multiaddr: "/ip4/127.0.0.1/tcp/1234".parse().unwrap(),
                 status: Status::Unreachable,
+                depth: vec![],
             },
             Seller {
+                latency: None,
                 multiaddr: Multiaddr::empty(),
                 status: Status::Unreachable,
+                depth: vec![],
             },
             Seller {
+                latency: None,
                 multiaddr: "/ip4/127.0.0.1/tcp/5678".parse().unwrap(),
-                status: Status::Online(BidQuote {
+                status: Status::Online(Quote {
                     price: Default::default(),
                     min_quantity: Default::default(),
                     max_quantity: Default::default(),
                 }),
+                depth: vec![],
             },
         ];
 
@@ -344,21 +438,49 @@ mod tests {
 
         assert_eq!(list, vec![
             Seller {
+                latency: None,
                 multiaddr: "/ip4/127.0.0.1/tcp/5678".parse().unwrap(),
-                status: Status::Online(BidQuote {
+                status: Status::Online(Quote {
                     price: Default::default(),
                     min_quantity: Default::default(),
                     max_quantity: Default::default(),
-                })
+                }),
+                depth: vec![],
             },
             Seller {
+                latency: None,
                 multiaddr: Multiaddr::empty(),
-                status: Status::Unreachable
+                status: Status::Unreachable,
+                depth: vec![],
             },
             Seller {
+                latency: None,
                 multiaddr: "/ip4/127.0.0.1/tcp/1234".parse().unwrap(),
-                status: Status::Unreachable
+                status: Status::Unreachable,
+                depth: vec![],
             },
         ])
     }
+
+    #[test]
+    fn sellers_with_the_same_quote_sort_by_latency() {
+        let seller = |connect_ms, quote_ms| Seller {
+            status: Status::Online(Quote {
+                price: Default::default(),
+                min_quantity: Default::default(),
+                max_quantity: Default::default(),
+            }),
+            latency: Some(Latency {
+                connect_ms,
+                quote_ms,
+            }),
+            multiaddr: Multiaddr::empty(),
+            depth: vec![],
+        };
+        let mut list = vec![seller(300, 50), seller(100, 900), seller(100, 20)];
+
+        list.sort();
+
+        assert_eq!(list, vec![seller(100, 20), seller(100, 900), seller(300, 50)]);
+    }
 }
@@ -0,0 +1,74 @@
+use crate::protocol::bob::{LockApproval, LockDetails};
+use anyhow::Result;
+use async_trait::async_trait;
+use dialoguer::Confirm;
+use libp2p::PeerId;
+
+/// Asks the user on the terminal before the Bitcoin of a swap is locked.
+#[derive(Debug, Clone, Copy)]
+pub struct ConfirmLock {
+    pub seller: PeerId,
+}
+
+impl ConfirmLock {
+    fn prompt(&self, lock: &LockDetails) -> String {
+        let lock_fee = lock
+            .btc_lock_fee
+            .map_or_else(|| "unknown".to_owned(), |fee| fee.to_string());
+
+        format!(
+            "Lock {} (fee {}) to receive {} from seller {}? If the seller does not lock the Monero, the Bitcoin can be refunded after {} minus {} and {} in fees",
+            lock.btc,
+            lock_fee,
+            lock.xmr,
+            self.seller,
+            lock.cancel_timelock,
+            lock.btc_cancel_fee,
+            lock.btc_refund_fee,
+        )
+    }
+}
+
+#[async_trait]
+impl LockApproval for ConfirmLock {
+    async fn approve(&self, lock: &LockDetails) -> Result<bool> {
+        let prompt = self.prompt(lock);
+
+        let approved = tokio::task::spawn_blocking(move || {
+            Confirm::new().with_prompt(prompt).default(false).interact()
+        })
+        .await??;
+
+        Ok(approved)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitcoin::CancelTimelock;
+    use crate::{bitcoin, monero};
+    use uuid::Uuid;
+
+    #[test]
+    fn prompt_shows_amounts_fees_and_seller() {
+        let seller = PeerId::random();
+        let lock = LockDetails {
+            swap_id: Uuid::new_v4(),
+            btc: bitcoin::Amount::from_sat(1_000_000),
+            btc_lock_fee: Some(bitcoin::Amount::from_sat(610)),
+            btc_cancel_fee: bitcoin::Amount::from_sat(1_000),
+            btc_refund_fee: bitcoin::Amount::from_sat(1_500),
+            xmr: monero::Amount::from_piconero(1_500_000_000_000),
+            cancel_timelock: CancelTimelock::new(72),
+        };
+
+        assert_eq!(
+            ConfirmLock { seller }.prompt(&lock),
+            format!(
+                "Lock 0.01000000 BTC (fee 0.00000610 BTC) to receive 1.500000000000 XMR from seller {}? If the seller does not lock the Monero, the Bitcoin can be refunded after 72 blocks minus 0.00001000 BTC and 0.00001500 BTC in fees",
+                seller
+            )
+        );
+    }
+}
@@ -0,0 +1,161 @@
+//! Refusing swaps at a price far off the market.
+//!
+//! The price of the seller, in BTC per XMR, is compared against the maximum
+//! price given by the user and against the price of the last XMR/BTC trade on
+//! Kraken plus the accepted deviation. The quote is checked before waiting for
+//! a deposit and the amounts agreed on in the swap setup before the Bitcoin is
+//! locked, a swap that fails either check is aborted.
+
+use crate::amounts::Price;
+use crate::protocol::bob::{LockApproval, LockDetails};
+use crate::{bitcoin, fiat};
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use rust_decimal::Decimal;
+use url::Url;
+
+const KRAKEN_PAIR: &str = "XMRXBT";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PriceLimit {
+    max_price: Option<Price>,
+    /// The market price and how many percent above it are accepted.
+    market: Option<(Price, Decimal)>,
+}
+
+impl PriceLimit {
+    /// Fetches the market price from `price_url` if `max_deviation` is set,
+    /// failing if it is not available.
+    pub async fn new(
+        max_price: Option<Price>,
+        max_deviation: Option<Decimal>,
+        price_url: &Url,
+    ) -> Result<Self> {
+        let market = match max_deviation {
+            Some(max_deviation) => {
+                let market_price = fetch_market_price(price_url)
+                    .await
+                    .context("Failed to fetch the market price to compare the seller's price to")?;
+                tracing::info!(%market_price, "Fetched market price of one XMR");
+
+                Some((market_price, max_deviation))
+            }
+            None => None,
+        };
+
+        Ok(Self { max_price, market })
+    }
+
+    pub fn is_limited(&self) -> bool {
+        self.max_price.is_some() || self.market.is_some()
+    }
+
+    /// Fails if `price`, the price of one XMR, exceeds either limit.
+    pub fn check(&self, price: Price) -> Result<()> {
+        if let Some(max_price) = self.max_price {
+            if price > max_price {
+                bail!(
+                    "The seller's price of {} exceeds the maximum price of {}",
+                    price,
+                    max_price
+                );
+            }
+        }
+
+        if let Some((market_price, max_deviation)) = self.market {
+            // Prices are whole satoshis, rounding the limit down to one does
+            // not change which prices exceed it
+            if price > market_price.markup(max_deviation)? {
+                bail!(
+                    "The seller's price of {} is more than {}% above the market price of {}",
+                    price,
+                    max_deviation,
+                    market_price
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl LockApproval for PriceLimit {
+    async fn approve(&self, lock: &LockDetails) -> Result<bool> {
+        let price = Price::of(lock.btc, lock.xmr)?;
+
+        match self.check(price) {
+            Ok(()) => Ok(true),
+            Err(error) => {
+                tracing::error!(swap_id = %lock.swap_id, "Not locking the Bitcoin: {:#}", error);
+                Ok(false)
+            }
+        }
+    }
+}
+
+async fn fetch_market_price(price_url: &Url) -> Result<Price> {
+    let price = fiat::fetch_last_trade(price_url, KRAKEN_PAIR, "Monero").await?;
+
+    let btc_per_xmr = bitcoin::Amount::from_str_in(
+        &price.round_dp(8).to_string(),
+        ::bitcoin::Denomination::Bitcoin,
+    )
+    .with_context(|| format!("Failed to parse Monero price {}", price))?;
+
+    Ok(Price::new(btc_per_xmr))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::monero;
+    use rust_decimal_macros::dec;
+
+    fn price(sats: u64) -> Price {
+        Price::new(bitcoin::Amount::from_sat(sats))
+    }
+
+    fn limit(max_price: Option<Price>, market: Option<(Price, Decimal)>) -> PriceLimit {
+        PriceLimit { max_price, market }
+    }
+
+    #[test]
+    fn price_above_maximum_is_refused() {
+        let limit = limit(Some(price(700_000)), None);
+
+        assert!(limit.check(price(700_000)).is_ok());
+        assert_eq!(
+            limit.check(price(700_001)).unwrap_err().to_string(),
+            "The seller's price of 0.00700001 BTC/XMR exceeds the maximum price of 0.00700000 BTC/XMR"
+        );
+    }
+
+    #[test]
+    fn price_off_market_is_refused() {
+        let limit = limit(None, Some((price(600_000), dec!(5))));
+
+        assert!(limit.check(price(630_000)).is_ok());
+        assert_eq!(
+            limit.check(price(630_001)).unwrap_err().to_string(),
+            "The seller's price of 0.00630001 BTC/XMR is more than 5% above the market price of 0.00600000 BTC/XMR"
+        );
+    }
+
+    #[tokio::test]
+    async fn lock_at_a_worse_price_than_quoted_is_declined() {
+        let limit = limit(Some(price(700_000)), None);
+        let lock = |xmr| LockDetails {
+            swap_id: uuid::Uuid::new_v4(),
+            btc: bitcoin::Amount::from_sat(7_000_000),
+            btc_lock_fee: None,
+            btc_cancel_fee: bitcoin::Amount::from_sat(1_000),
+            btc_refund_fee: bitcoin::Amount::from_sat(1_000),
+            xmr: monero::Amount::parse_monero(xmr).unwrap(),
+            cancel_timelock: bitcoin::CancelTimelock::new(72),
+        };
+
+        assert!(limit.approve(&lock("10")).await.unwrap());
+        assert!(!limit.approve(&lock("9.99")).await.unwrap());
+    }
+}
@@ -0,0 +1,198 @@
+//! Shows the waits for confirmations as a line of progress bars at the bottom
+//! of the terminal.
+//!
+//! The line is updated in place whenever a wallet reports a new confirmation,
+//! log lines are printed above it through [`terminal`].
+
+use crate::env;
+use crate::progress::{Chain, Confirmations, Progress};
+use conquer_once::Lazy;
+use indicatif::{ProgressBar, ProgressStyle};
+use std::collections::BTreeMap;
+use std::io;
+use std::io::Write;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+
+const BAR_WIDTH: u64 = 10;
+
+/// The bar currently shown, hidden while nothing is waited for.
+static BAR: Lazy<Mutex<ProgressBar>> = Lazy::new(|| Mutex::new(ProgressBar::hidden()));
+
+/// Shows the confirmations reported by the wallets until one of them is
+/// dropped.
+///
+/// Does nothing if stderr is not a terminal, the logs contain the same
+/// information.
+pub async fn show(bitcoin: Progress, monero: Progress, env_config: env::Config) {
+    if !atty::is(atty::Stream::Stderr) {
+        return;
+    }
+
+    let mut bitcoin = bitcoin.subscribe();
+    let mut monero = monero.subscribe();
+    let mut waits = BTreeMap::new();
+
+    loop {
+        let received = tokio::select! {
+            received = bitcoin.recv() => received,
+            received = monero.recv() => received,
+        };
+        let confirmations = match received {
+            Ok(confirmations) => confirmations,
+            Err(RecvError::Lagged(_)) => continue,
+            Err(RecvError::Closed) => break,
+        };
+
+        waits.insert(
+            (confirmations.chain, confirmations.txid.clone()),
+            confirmations,
+        );
+        let line = waits
+            .values()
+            .map(|wait| render(wait, &env_config))
+            .collect::<Vec<_>>()
+            .join("  |  ");
+        waits.retain(|_, wait| !wait.is_complete());
+
+        let bar = current_bar();
+        bar.set_message(line);
+        if waits.is_empty() {
+            bar.finish();
+            *lock() = ProgressBar::hidden();
+        }
+    }
+
+    current_bar().finish_and_clear();
+}
+
+/// A writer to stderr that keeps the progress bar below the written lines.
+#[derive(Debug)]
+pub struct Terminal;
+
+pub fn terminal() -> Terminal {
+    Terminal
+}
+
+impl Write for Terminal {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let bar = lock().clone();
+
+        if bar.is_hidden() {
+            return io::stderr().write(buf);
+        }
+
+        // Log lines are written at once, printing them through the bar redraws
+        // it below them
+        bar.println(String::from_utf8_lossy(buf).trim_end_matches('\n'));
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stderr().flush()
+    }
+}
+
+fn lock() -> std::sync::MutexGuard<'static, ProgressBar> {
+    BAR.lock().expect("progress bar lock not poisoned")
+}
+
+/// The bar to report to, replaces the hidden bar with a visible one.
+fn current_bar() -> ProgressBar {
+    let mut bar = lock();
+
+    if bar.is_hidden() {
+        *bar = ProgressBar::new(0).with_style(ProgressStyle::default_bar().template("{msg}"));
+    }
+
+    bar.clone()
+}
+
+// The bar is never wider than BAR_WIDTH
+#[allow(clippy::cast_possible_truncation)]
+pub(crate) fn render(wait: &Confirmations, env_config: &env::Config) -> String {
+    let block_time = match wait.chain {
+        Chain::Bitcoin => env_config.bitcoin_avg_block_time,
+        Chain::Monero => env_config.monero_avg_block_time,
+    };
+    let filled = if wait.needed == 0 {
+        BAR_WIDTH
+    } else {
+        (wait.seen * BAR_WIDTH / wait.needed).min(BAR_WIDTH)
+    };
+    let status = if wait.is_complete() {
+        "confirmed".to_owned()
+    } else {
+        format_remaining(wait.remaining(block_time))
+    };
+
+    format!(
+        "{} {} [{}{}] {}/{} confirmations, {}",
+        wait.chain,
+        short_txid(&wait.txid),
+        "#".repeat(filled as usize),
+        "-".repeat((BAR_WIDTH - filled) as usize),
+        wait.seen.min(wait.needed),
+        wait.needed,
+        status
+    )
+}
+
+fn short_txid(txid: &str) -> &str {
+    txid.get(..8).unwrap_or(txid)
+}
+
+fn format_remaining(remaining: Duration) -> String {
+    let minutes = remaining.as_secs() / 60;
+
+    match (minutes / 60, minutes % 60) {
+        (0, 0) => "less than a minute left".to_owned(),
+        (0, minutes) => format!("about {}m left", minutes),
+        (hours, minutes) => format!("about {}h {}m left", hours, minutes),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::env::GetConfig;
+
+    #[test]
+    fn renders_the_remaining_blocks_with_the_block_time_of_the_chain() {
+        let env_config = env::Mainnet::get_config();
+        let wait = |chain, seen| Confirmations {
+            chain,
+            txid: "3f1c0e2b9a5d4c7e8f6a".to_owned(),
+            seen,
+            needed: 10,
+        };
+
+        assert_eq!(
+            render(&wait(Chain::Monero, 4), &env_config),
+            "Monero 3f1c0e2b [####------] 4/10 confirmations, about 12m left"
+        );
+        assert_eq!(
+            render(&wait(Chain::Bitcoin, 1), &env_config),
+            "Bitcoin 3f1c0e2b [#---------] 1/10 confirmations, about 1h 30m left"
+        );
+        assert_eq!(
+            render(&wait(Chain::Bitcoin, 11), &env_config),
+            "Bitcoin 3f1c0e2b [##########] 10/10 confirmations, confirmed"
+        );
+    }
+
+    #[test]
+    fn remaining_time_is_rounded_down_to_minutes() {
+        assert_eq!(
+            format_remaining(Duration::from_secs(59)),
+            "less than a minute left"
+        );
+        assert_eq!(format_remaining(Duration::from_secs(61)), "about 1m left");
+        assert_eq!(
+            format_remaining(Duration::from_secs(2 * 60 * 60)),
+            "about 2h 0m left"
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use crate::bitcoin::Wallet;
+use crate::bitcoin::{ExpiredTimelocks, Wallet};
 use crate::protocol::bob::BobState;
 use crate::protocol::Database;
 use anyhow::{bail, Result};
@@ -6,20 +6,42 @@ use std::convert::TryInto;
 use std::sync::Arc;
 use uuid::Uuid;
 
+#[derive(Debug, thiserror::Error, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub enum Error {
+    #[error("The cancel timelock has not expired yet, use --force to publish the refund transaction anyway")]
+    CancelTimelockNotExpiredYet,
+}
+
+/// Publishes the refund transaction of the swap.
+///
+/// Unless `force` is set, the refund transaction is only published once the
+/// cancel timelock expired according to the Electrum server and not for a
+/// swap that was already refunded. With `force` an already refunded swap is
+/// refunded again, which republishes the refund transaction.
 pub async fn refund(
     swap_id: Uuid,
     bitcoin_wallet: Arc<Wallet>,
     db: Arc<dyn Database>,
+    force: bool,
 ) -> Result<BobState> {
     let state = db.get_state(swap_id).await?.try_into()?;
 
     let state6 = match state {
-        BobState::BtcLocked { state3, .. } => state3.cancel(),
-        BobState::XmrLockProofReceived { state, .. } => state.cancel(),
+        BobState::BtcLocked {
+            state3,
+            monero_wallet_restore_blockheight,
+        } => state3.cancel(monero_wallet_restore_blockheight),
+        BobState::XmrLockProofReceived {
+            state,
+            monero_wallet_restore_blockheight,
+            ..
+        } => state.cancel(monero_wallet_restore_blockheight),
         BobState::XmrLocked(state4) => state4.cancel(),
         BobState::EncSigSent(state4) => state4.cancel(),
         BobState::CancelTimelockExpired(state6) => state6,
         BobState::BtcCancelled(state6) => state6,
+        BobState::BtcRefunded(state6) if force => state6,
         BobState::Started { .. }
         | BobState::SwapSetupCompleted(_)
         | BobState::BtcRedeemed(_)
@@ -33,6 +55,14 @@ pub async fn refund(
         ),
     };
 
+    if !force {
+        if let ExpiredTimelocks::None = state6.expired_timelock(bitcoin_wallet.as_ref()).await? {
+            bail!(Error::CancelTimelockNotExpiredYet)
+        }
+    }
+
+    tracing::info!(%swap_id, %force, "Manually refunding swap");
+
     state6.publish_refund_btc(bitcoin_wallet.as_ref()).await?;
 
     let state = BobState::BtcRefunded(state6);
@@ -0,0 +1,82 @@
+use crate::bitcoin::{Address, Amount, Txid, Wallet};
+use crate::protocol::bob::BobState;
+use crate::protocol::Database;
+use anyhow::{bail, Result};
+use std::convert::TryInto;
+use uuid::Uuid;
+
+/// Returns the Bitcoin deposited into the internal wallet, e.g. after the
+/// seller rejected the swap.
+///
+/// The whole balance is sent to `address`. Swaps that did not lock their
+/// Bitcoin yet are still going to spend it, so nothing is sent while there
+/// is one.
+pub async fn refund_deposit(
+    address: Address,
+    bitcoin_wallet: &Wallet,
+    db: &(dyn Database + Send + Sync),
+) -> Result<(Txid, Amount)> {
+    let swaps = db
+        .all()
+        .await?
+        .into_iter()
+        .map(|(swap_id, state)| Ok((swap_id, state.try_into()?)))
+        .collect::<Result<Vec<(Uuid, BobState)>>>()?;
+    if let Some(swap_id) = unlocked_swap(&swaps) {
+        bail!(
+            "Swap {} did not lock its Bitcoin yet, resume it before returning the deposit",
+            swap_id
+        )
+    }
+
+    bitcoin_wallet.sync().await?;
+    let amount = bitcoin_wallet
+        .max_giveable(address.script_pubkey().len())
+        .await?;
+    if amount == Amount::ZERO {
+        bail!("The internal Bitcoin wallet holds no deposit that could be returned")
+    }
+
+    let psbt = bitcoin_wallet
+        .send_to_address(address, amount, None)
+        .await?;
+    let signed_tx = bitcoin_wallet.sign_and_finalize(psbt).await?;
+    let (txid, _) = bitcoin_wallet
+        .broadcast(signed_tx, "deposit refund")
+        .await?;
+
+    Ok((txid, amount))
+}
+
+fn unlocked_swap(swaps: &[(Uuid, BobState)]) -> Option<Uuid> {
+    swaps.iter().find_map(|(swap_id, state)| match state {
+        BobState::Started { .. } | BobState::SwapSetupCompleted(..) => Some(*swap_id),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn swaps_that_may_still_lock_block_the_refund() {
+        let started = Uuid::new_v4();
+        let swaps = vec![
+            (Uuid::new_v4(), BobState::SafelyAborted),
+            (
+                started,
+                BobState::Started {
+                    btc_amount: Amount::from_sat(100_000),
+                    change_address: Address::from_str("bc1qe4epnfklcaa0mun26yz5g8k24em5u9f92hy325")
+                        .unwrap(),
+                    xmr_amount: None,
+                },
+            ),
+        ];
+
+        assert_eq!(unlocked_swap(&swaps[..1]), None);
+        assert_eq!(unlocked_swap(&swaps), Some(started));
+    }
+}
@@ -0,0 +1,256 @@
+use crate::cli::{Behaviour, EventLoop, EventLoopHandle};
+use crate::network::address_family;
+use crate::network::duplex_transport::{self, DuplexTransport};
+use crate::network::hole_punch;
+use crate::network::swarm;
+use crate::protocol::{bob, Database};
+use crate::reservations::Reservations;
+use crate::{bitcoin, env, monero};
+use anyhow::{Context, Result};
+use futures::{AsyncRead, AsyncWrite};
+use libp2p::core::transport::Boxed;
+use libp2p::relay::v2::client::Client;
+use libp2p::{identity, Multiaddr, PeerId, Swarm, Transport};
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Everything the swaps of Bob have in common.
+///
+/// The wallets and the database are opened once and shared by all swaps
+/// created by the factory, only the network layer is set up per swap. This
+/// allows running several swaps next to each other without connecting to
+/// the blockchain backends for each of them. The Bitcoin of a swap is
+/// reserved from its creation until it is locked, so these swaps do not count
+/// on the same balance.
+#[derive(Clone)]
+#[allow(missing_debug_implementations)]
+pub struct SwapFactory {
+    identity: identity::Keypair,
+    db: Arc<dyn Database + Send + Sync>,
+    bitcoin_wallet: Arc<bitcoin::Wallet>,
+    monero_wallet: Arc<dyn monero::MoneroWallet>,
+    env_config: env::Config,
+    tor_socks5_port: u16,
+    btc_reservations: Reservations<bitcoin::Amount>,
+}
+
+impl SwapFactory {
+    pub fn new(
+        identity: identity::Keypair,
+        db: Arc<dyn Database + Send + Sync>,
+        bitcoin_wallet: Arc<bitcoin::Wallet>,
+        monero_wallet: Arc<dyn monero::MoneroWallet>,
+        env_config: env::Config,
+        tor_socks5_port: u16,
+    ) -> Self {
+        Self {
+            identity,
+            db,
+            bitcoin_wallet,
+            monero_wallet,
+            env_config,
+            tor_socks5_port,
+            btc_reservations: Default::default(),
+        }
+    }
+
+    pub fn db(&self) -> Arc<dyn Database + Send + Sync> {
+        self.db.clone()
+    }
+
+    pub fn bitcoin_wallet(&self) -> Arc<bitcoin::Wallet> {
+        self.bitcoin_wallet.clone()
+    }
+
+    pub fn monero_wallet(&self) -> Arc<dyn monero::MoneroWallet> {
+        self.monero_wallet.clone()
+    }
+
+    /// The most a new swap can lock, what the Bitcoin wallet can spend minus
+    /// the Bitcoin reserved for the other swaps.
+    pub async fn max_giveable(&self) -> Result<bitcoin::Amount> {
+        let max_giveable = self
+            .bitcoin_wallet
+            .max_giveable(bitcoin::TxLock::script_size())
+            .await?;
+
+        Ok(self
+            .btc_reservations
+            .available(max_giveable)
+            .unwrap_or(bitcoin::Amount::ZERO))
+    }
+
+    /// Sets up the network layer of a swap with the seller.
+    ///
+    /// The returned event loop has to be run for as long as the swap is
+    /// executed.
+    pub async fn event_loop(
+        &self,
+        swap_id: Uuid,
+        seller_peer_id: PeerId,
+        seller_addresses: impl IntoIterator<Item = Multiaddr>,
+    ) -> Result<(EventLoop, EventLoopHandle)> {
+        let swarm = swarm::cli(
+            self.identity.clone(),
+            self.tor_socks5_port,
+            |relay, hole_punch| self.behaviour(seller_peer_id, Some(relay), hole_punch),
+        )
+        .await?;
+
+        self.event_loop_with_swarm(swap_id, seller_peer_id, seller_addresses, swarm)
+    }
+
+    /// Sets up the network layer of a swap with the seller on top of a
+    /// transport provided by the host application instead of TCP or Tor.
+    pub fn event_loop_with_transport<S>(
+        &self,
+        swap_id: Uuid,
+        seller_peer_id: PeerId,
+        seller_addresses: impl IntoIterator<Item = Multiaddr>,
+        transport: Boxed<S>,
+    ) -> Result<(EventLoop, EventLoopHandle)>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        let swarm = swarm::cli_with_transport(
+            self.identity.clone(),
+            transport,
+            self.behaviour(seller_peer_id, None, None),
+        )?;
+
+        self.event_loop_with_swarm(swap_id, seller_peer_id, seller_addresses, swarm)
+    }
+
+    /// Sets up the network layer of a swap with the seller over a connection
+    /// to the seller the host application already established.
+    ///
+    /// See [`DuplexTransport`] for the requirements on the connection.
+    pub fn event_loop_over<S>(
+        &self,
+        swap_id: Uuid,
+        seller_peer_id: PeerId,
+        connection: S,
+    ) -> Result<(EventLoop, EventLoopHandle)>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        self.event_loop_with_transport(
+            swap_id,
+            seller_peer_id,
+            vec![duplex_transport::address()],
+            DuplexTransport::new(connection).boxed(),
+        )
+    }
+
+    fn behaviour(
+        &self,
+        seller_peer_id: PeerId,
+        relay: Option<Client>,
+        hole_punch: Option<hole_punch::Behaviour>,
+    ) -> Behaviour {
+        Behaviour::new(
+            seller_peer_id,
+            self.env_config,
+            self.bitcoin_wallet.clone(),
+            relay,
+            hole_punch,
+        )
+    }
+
+    fn event_loop_with_swarm(
+        &self,
+        swap_id: Uuid,
+        seller_peer_id: PeerId,
+        seller_addresses: impl IntoIterator<Item = Multiaddr>,
+        mut swarm: Swarm<Behaviour>,
+    ) -> Result<(EventLoop, EventLoopHandle)> {
+        let seller_addresses =
+            address_family::reachable_first(seller_addresses.into_iter().collect());
+        for seller_address in seller_addresses {
+            swarm
+                .behaviour_mut()
+                .add_address(seller_peer_id, seller_address);
+        }
+
+        tracing::debug!(peer_id = %swarm.local_peer_id(), "Network layer initialized");
+
+        EventLoop::new(
+            swap_id,
+            swarm,
+            seller_peer_id,
+            Some(self.db.clone()),
+            self.env_config,
+        )
+    }
+
+    /// Creates a new swap and records the seller and the Monero receive
+    /// address, so the swap can be resumed later on.
+    ///
+    /// Fails if `btc_amount` is reserved for other swaps of the factory.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn new_swap(
+        &self,
+        swap_id: Uuid,
+        seller_peer_id: PeerId,
+        event_loop_handle: EventLoopHandle,
+        monero_receive_address: monero::Address,
+        bitcoin_change_address: bitcoin::Address,
+        btc_amount: bitcoin::Amount,
+        xmr_amount: Option<monero::Amount>,
+    ) -> Result<bob::Swap> {
+        let max_giveable = self
+            .bitcoin_wallet
+            .max_giveable(bitcoin::TxLock::script_size())
+            .await?;
+        let reservation = self
+            .btc_reservations
+            .reserve(btc_amount, max_giveable)
+            .with_context(|| {
+                format!(
+                    "Failed to reserve {} for the swap, the spendable {} are partly reserved for other swaps",
+                    btc_amount, max_giveable
+                )
+            })?;
+
+        self.db.insert_peer_id(swap_id, seller_peer_id).await?;
+        self.db
+            .insert_monero_address(swap_id, monero_receive_address)
+            .await?;
+
+        Ok(bob::Swap::new(
+            self.db.clone(),
+            swap_id,
+            self.bitcoin_wallet.clone(),
+            self.monero_wallet.clone(),
+            self.env_config,
+            event_loop_handle,
+            monero_receive_address,
+            bitcoin_change_address,
+            btc_amount,
+            xmr_amount,
+        )
+        .with_reservation(reservation)
+        .with_receipt(self.identity.clone()))
+    }
+
+    /// Loads a swap created by [`SwapFactory::new_swap`] from the database.
+    pub async fn resume_swap(
+        &self,
+        swap_id: Uuid,
+        event_loop_handle: EventLoopHandle,
+    ) -> Result<bob::Swap> {
+        let monero_receive_address = self.db.get_monero_address(swap_id).await?;
+
+        bob::Swap::from_db(
+            self.db.clone(),
+            swap_id,
+            self.bitcoin_wallet.clone(),
+            self.monero_wallet.clone(),
+            self.env_config,
+            event_loop_handle,
+            monero_receive_address,
+        )
+        .await
+        .map(|swap| swap.with_receipt(self.identity.clone()))
+    }
+}
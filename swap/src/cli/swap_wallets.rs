@@ -0,0 +1,230 @@
+use crate::monero;
+use crate::protocol::Database;
+use anyhow::{Context, Result};
+use std::fmt;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// The files monero-wallet-rpc creates for a wallet, relative to the wallet
+/// file name.
+const WALLET_FILE_EXTENSIONS: &[&str] = &["", ".keys", ".address.txt"];
+
+/// A Monero wallet that was generated for a swap and is still present in the
+/// wallet directory.
+#[derive(Debug, Clone)]
+pub struct SwapWallet {
+    pub swap_id: Uuid,
+    pub balance: monero::Amount,
+    pub unlocked_balance: monero::Amount,
+    pub action: Action,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    /// The unlocked balance was swept to the Monero receive address of the
+    /// swap.
+    Swept { address: monero::Address },
+    /// The wallet was empty and its files were removed.
+    Removed,
+    /// The wallet was left untouched, e.g. because the funds are still locked.
+    Kept,
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Action::Swept { address } => write!(f, "swept to {}", address),
+            Action::Removed => write!(f, "removed"),
+            Action::Kept => write!(f, "kept"),
+        }
+    }
+}
+
+/// Sweeps the funds of all swap wallets found in `wallet_dir` to the Monero
+/// receive address recorded for the respective swap and removes the files of
+/// wallets that are empty.
+///
+/// A wallet that was swept in this run is only removed the next time this is
+/// executed, once the balance is reported as zero.
+pub async fn sweep_swap_wallets<'a>(
+    wallet_dir: &'a Path,
+    monero_wallet: &'a monero::Wallet,
+    db: Arc<dyn Database + Send + Sync>,
+) -> Result<Vec<SwapWallet>> {
+    let mut swap_wallets = Vec::new();
+
+    for swap_id in find_swap_wallets(wallet_dir)? {
+        let wallet_file_name = swap_id.to_string();
+
+        if let Err(e) = monero_wallet.open(wallet_file_name.clone()).await {
+            tracing::warn!(%wallet_file_name, "Failed to open Monero wallet: {:#}", e);
+            continue;
+        }
+        monero_wallet.refresh().await?;
+
+        let balance = monero_wallet.get_balance().await?;
+        let unlocked_balance = monero_wallet.get_unlocked_balance().await?;
+
+        let action = if unlocked_balance > monero::Amount::ZERO {
+            match db.get_monero_address(swap_id).await {
+                Ok(address) => {
+                    for tx_hash in monero_wallet.sweep_all(address).await? {
+                        tracing::info!(%swap_id, monero_address = %address, txid = %tx_hash.0, "Swept Monero from swap wallet");
+                    }
+
+                    Action::Swept { address }
+                }
+                Err(e) => {
+                    tracing::warn!(%swap_id, "No Monero receive address known for swap: {:#}", e);
+                    Action::Kept
+                }
+            }
+        } else if balance == monero::Amount::ZERO {
+            Action::Removed
+        } else {
+            Action::Kept
+        };
+
+        swap_wallets.push(SwapWallet {
+            swap_id,
+            balance,
+            unlocked_balance,
+            action,
+        });
+    }
+
+    // Switch back to the wallet we started with, the wallet files of the last swap
+    // wallet cannot be removed while it is loaded.
+    monero_wallet.re_open().await?;
+
+    for swap_wallet in swap_wallets
+        .iter()
+        .filter(|swap_wallet| swap_wallet.action == Action::Removed)
+    {
+        remove_swap_wallet(wallet_dir, swap_wallet.swap_id)?;
+        tracing::debug!(swap_id = %swap_wallet.swap_id, "Removed empty swap wallet");
+    }
+
+    Ok(swap_wallets)
+}
+
+/// Returns the ids of all swaps a wallet exists for in `wallet_dir`.
+///
+/// Swap wallets are named after the swap id, wallets with any other name are
+/// ignored.
+fn find_swap_wallets(wallet_dir: &Path) -> Result<Vec<Uuid>> {
+    if !wallet_dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let entries = std::fs::read_dir(wallet_dir).with_context(|| {
+        format!(
+            "Failed to read Monero wallet directory {}",
+            wallet_dir.display()
+        )
+    })?;
+
+    let mut swap_ids = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+
+        if path.extension().and_then(|extension| extension.to_str()) != Some("keys") {
+            continue;
+        }
+
+        if let Some(swap_id) = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| Uuid::from_str(stem).ok())
+        {
+            swap_ids.push(swap_id);
+        }
+    }
+    swap_ids.sort();
+
+    Ok(swap_ids)
+}
+
+fn remove_swap_wallet(wallet_dir: &Path, swap_id: Uuid) -> Result<()> {
+    for extension in WALLET_FILE_EXTENSIONS {
+        let path = wallet_dir.join(format!("{}{}", swap_id, extension));
+
+        match std::fs::remove_file(&path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to remove {}", path.display()))
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use tempfile::tempdir;
+
+    const SWAP_ID_1: &str = "ea030832-3be9-454f-bb98-5ea9a788406b";
+    const SWAP_ID_2: &str = "0f6d4ae1-1b86-4e8f-8a3c-8e4e51a0c7f2";
+
+    #[test]
+    fn only_wallets_named_after_a_swap_id_are_found() {
+        let dir = tempdir().unwrap();
+        for file in &[
+            SWAP_ID_1.to_owned(),
+            format!("{}.keys", SWAP_ID_1),
+            format!("{}.keys", SWAP_ID_2),
+            "swap-tool-blockchain-monitoring-wallet".to_owned(),
+            "swap-tool-blockchain-monitoring-wallet.keys".to_owned(),
+            "not-a-wallet.txt".to_owned(),
+        ] {
+            File::create(dir.path().join(file)).unwrap();
+        }
+
+        let swap_ids = find_swap_wallets(dir.path()).unwrap();
+
+        let mut expected = vec![
+            Uuid::from_str(SWAP_ID_1).unwrap(),
+            Uuid::from_str(SWAP_ID_2).unwrap(),
+        ];
+        expected.sort();
+        assert_eq!(swap_ids, expected);
+    }
+
+    #[test]
+    fn given_no_wallet_dir_then_no_wallets_are_found() {
+        let dir = tempdir().unwrap();
+
+        let swap_ids = find_swap_wallets(&dir.path().join("monero-data")).unwrap();
+
+        assert!(swap_ids.is_empty());
+    }
+
+    #[test]
+    fn removing_a_swap_wallet_leaves_other_wallets_untouched() {
+        let dir = tempdir().unwrap();
+        let swap_id = Uuid::from_str(SWAP_ID_1).unwrap();
+        for file in &[
+            SWAP_ID_1.to_owned(),
+            format!("{}.keys", SWAP_ID_1),
+            format!("{}.address.txt", SWAP_ID_1),
+            format!("{}.keys", SWAP_ID_2),
+        ] {
+            File::create(dir.path().join(file)).unwrap();
+        }
+
+        remove_swap_wallet(dir.path(), swap_id).unwrap();
+
+        assert_eq!(
+            find_swap_wallets(dir.path()).unwrap(),
+            vec![Uuid::from_str(SWAP_ID_2).unwrap()]
+        );
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+}
@@ -1,3 +1,5 @@
+use crate::cli::progress;
+use crate::otlp;
 use anyhow::Result;
 use std::option::Option::Some;
 use std::path::Path;
@@ -6,14 +8,28 @@ use tracing::{Event, Level, Subscriber};
 use tracing_subscriber::fmt::format::{DefaultFields, Format, JsonFields};
 use tracing_subscriber::fmt::time::ChronoLocal;
 use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::util::SubscriberInitExt;
 use tracing_subscriber::{fmt, EnvFilter, FmtSubscriber, Layer, Registry};
+use url::Url;
 use uuid::Uuid;
 
-pub fn init(debug: bool, json: bool, dir: impl AsRef<Path>, swap_id: Option<Uuid>) -> Result<()> {
+pub fn init(
+    debug: bool,
+    json: bool,
+    dir: impl AsRef<Path>,
+    swap_id: Option<Uuid>,
+    otlp_endpoint: Option<&Url>,
+) -> Result<()> {
+    let tracer = otlp_endpoint
+        .map(|endpoint| otlp::tracer(endpoint, "swap"))
+        .transpose()?;
+
     if let Some(swap_id) = swap_id {
         let level_filter = EnvFilter::try_new("swap=debug")?;
 
-        let registry = Registry::default().with(level_filter);
+        let registry = Registry::default()
+            .with(level_filter)
+            .with(otlp::layer(tracer.as_ref()));
 
         let appender = tracing_appender::rolling::never(dir, format!("swap-{}.log", swap_id));
         let (appender, guard) = tracing_appender::non_blocking(appender);
@@ -56,15 +72,41 @@ pub fn init(debug: bool, json: bool, dir: impl AsRef<Path>, swap_id: Option<Uuid
             .with_target(false);
 
         if json {
-            builder.json().init();
+            builder
+                .json()
+                .finish()
+                .with(otlp::layer(tracer.as_ref()))
+                .init();
         } else {
-            builder.init();
+            builder.finish().with(otlp::layer(tracer.as_ref())).init();
         }
 
         Ok(())
     }
 }
 
+/// Logs to `file_name` in `dir` only, for commands that draw on the whole
+/// terminal.
+pub fn init_file_only(debug: bool, dir: impl AsRef<Path>, file_name: &str) -> Result<()> {
+    let level = if debug { Level::DEBUG } else { Level::INFO };
+
+    let appender = tracing_appender::rolling::never(dir, file_name);
+    let (appender, guard) = tracing_appender::non_blocking(appender);
+
+    std::mem::forget(guard);
+
+    FmtSubscriber::builder()
+        .with_env_filter(format!("swap={}", level))
+        .with_writer(appender)
+        .with_ansi(false)
+        .with_timer(ChronoLocal::with_format("%F %T".to_owned()))
+        .with_target(false)
+        .finish()
+        .try_init()?;
+
+    Ok(())
+}
+
 pub struct StdErrPrinter<L> {
     inner: L,
     level: Level,
@@ -74,7 +116,7 @@ type StdErrLayer<S, T> = tracing_subscriber::fmt::Layer<
     S,
     DefaultFields,
     Format<tracing_subscriber::fmt::format::Full, T>,
-    fn() -> std::io::Stderr,
+    fn() -> progress::Terminal,
 >;
 
 type StdErrJsonLayer<S, T> = tracing_subscriber::fmt::Layer<
@@ -91,7 +133,7 @@ fn debug_terminal_printer<S>() -> StdErrPrinter<StdErrLayer<S, ChronoLocal>> {
             .with_ansi(is_terminal)
             .with_target(false)
             .with_timer(ChronoLocal::with_format("%F %T".to_owned()))
-            .with_writer(std::io::stderr),
+            .with_writer(progress::terminal),
         level: Level::DEBUG,
     }
 }
@@ -117,7 +159,7 @@ fn info_terminal_printer<S>() -> StdErrPrinter<StdErrLayer<S, ()>> {
             .with_target(false)
             .with_level(false)
             .without_time()
-            .with_writer(std::io::stderr),
+            .with_writer(progress::terminal),
         level: Level::INFO,
     }
 }
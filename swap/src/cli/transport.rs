@@ -4,6 +4,7 @@ use anyhow::Result;
 use libp2p::core::muxing::StreamMuxerBox;
 use libp2p::core::transport::{Boxed, OptionalTransport};
 use libp2p::dns::TokioDnsConfig;
+use libp2p::relay::v2::client::Client;
 use libp2p::tcp::TokioTcpConfig;
 use libp2p::{identity, PeerId, Transport};
 
@@ -15,18 +16,31 @@ use libp2p::{identity, PeerId, Transport};
 /// - Dial onion-addresses through a running Tor daemon by connecting to the
 ///   socks5 port. If the port is not given, we will fall back to the regular
 ///   TCP transport.
+/// - Dial sellers through the circuit relay in their `/p2p-circuit` address,
+///   if the returned relay [`Client`] behaviour is part of the swarm.
+/// - Without Tor, make outgoing TCP connections from the port of a listen
+///   address, for upgrading relayed connections by hole punching.
 pub fn new(
     identity: &identity::Keypair,
     maybe_tor_socks5_port: Option<u16>,
-) -> Result<Boxed<(PeerId, StreamMuxerBox)>> {
-    let tcp = TokioTcpConfig::new().nodelay(true);
+) -> Result<(Boxed<(PeerId, StreamMuxerBox)>, Client)> {
+    let tcp = TokioTcpConfig::new()
+        .nodelay(true)
+        .port_reuse(maybe_tor_socks5_port.is_none());
     let tcp_with_dns = TokioDnsConfig::system(tcp)?;
     let maybe_tor_transport = match maybe_tor_socks5_port {
         Some(port) => OptionalTransport::some(TorDialOnlyTransport::new(port)),
         None => OptionalTransport::none(),
     };
 
-    let transport = maybe_tor_transport.or_transport(tcp_with_dns).boxed();
+    let (relay_transport, client) =
+        Client::new_transport_and_behaviour(identity.public().to_peer_id());
+    let transport = relay_transport
+        .or_transport(maybe_tor_transport)
+        .or_transport(tcp_with_dns);
 
-    authenticate_and_multiplex(transport, identity)
+    Ok((
+        authenticate_and_multiplex(transport.boxed(), identity)?,
+        client,
+    ))
 }
@@ -0,0 +1,114 @@
+//! Where the CLI and the ASB keep their files.
+//!
+//! The default directories follow the conventions of the operating system:
+//!
+//! - Linux: `$XDG_CONFIG_HOME/xmr-btc-swap` and `$XDG_DATA_HOME/xmr-btc-swap`,
+//!   i.e. `~/.config/xmr-btc-swap` and `~/.local/share/xmr-btc-swap` unless
+//!   the variables are set
+//! - macOS: `~/Library/Application Support/xmr-btc-swap`
+//! - Windows: `%APPDATA%\xmr-btc-swap\config` and `%APPDATA%\xmr-btc-swap\data`
+//!
+//! The CLI and the ASB use a sub-directory of the default data directory per
+//! network, overridden with `--data-dir` respectively the `data.dir` setting of
+//! the ASB config file. Everything in a data directory is laid out by
+//! [`DataDir`].
+
+use anyhow::{Context, Result};
+use directories_next::ProjectDirs;
+use std::path::{Path, PathBuf};
+
+const DATABASE: &str = "sqlite";
+const SEED_FILE: &str = "seed.pem";
+const LOGS: &str = "logs";
+const BITCOIN_WALLET: &str = "wallet";
+const MONERO_WALLETS: &str = "monero";
+const ARTIFACTS: &str = "artifacts";
+
+fn project_dirs() -> Option<ProjectDirs> {
+    ProjectDirs::from("", "", "xmr-btc-swap")
+}
+
+/// The default directory of the config files.
+pub fn system_config_dir() -> Result<PathBuf> {
+    project_dirs()
+        .map(|dirs| dirs.config_dir().to_path_buf())
+        .context("Could not generate default system configuration dir path")
+}
+
+/// The default directory of the data directories.
+pub fn system_data_dir() -> Result<PathBuf> {
+    project_dirs()
+        .map(|dirs| dirs.data_dir().to_path_buf())
+        .context("Could not generate default system data-dir dir path")
+}
+
+/// The files in a data directory of the CLI or the ASB.
+///
+/// Not every file exists for both, e.g. the ASB neither writes log files nor
+/// runs its own `monero-wallet-rpc`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DataDir {
+    root: PathBuf,
+}
+
+impl DataDir {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The sqlite database of the swaps.
+    pub fn database(&self) -> PathBuf {
+        self.root.join(DATABASE)
+    }
+
+    /// The file all keys are derived from.
+    pub fn seed(&self) -> PathBuf {
+        self.root.join(SEED_FILE)
+    }
+
+    pub fn logs(&self) -> PathBuf {
+        self.root.join(LOGS)
+    }
+
+    /// The database of the internal Bitcoin wallet.
+    pub fn bitcoin_wallet(&self) -> PathBuf {
+        self.root.join(BITCOIN_WALLET)
+    }
+
+    /// The `monero-wallet-rpc` binary and the Monero wallets of the swaps.
+    pub fn monero_wallets(&self) -> PathBuf {
+        self.root.join(MONERO_WALLETS)
+    }
+
+    /// The artifacts of a swap, see [`crate::cli::artifacts`].
+    pub fn artifacts(&self) -> PathBuf {
+        self.root.join(ARTIFACTS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn files_are_laid_out_below_the_root() {
+        let data_dir = DataDir::new("/tmp/xmr-btc-swap/cli/mainnet");
+
+        assert_eq!(
+            data_dir.database(),
+            PathBuf::from("/tmp/xmr-btc-swap/cli/mainnet/sqlite")
+        );
+        assert_eq!(
+            data_dir.seed(),
+            PathBuf::from("/tmp/xmr-btc-swap/cli/mainnet/seed.pem")
+        );
+        assert_eq!(
+            data_dir.monero_wallets(),
+            PathBuf::from("/tmp/xmr-btc-swap/cli/mainnet/monero")
+        );
+    }
+}
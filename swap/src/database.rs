@@ -1,5 +1,9 @@
 pub use alice::Alice;
 pub use bob::Bob;
+pub use info::{swap_info, StateChange, SwapError, SwapInfo};
+pub use listing::{
+    format_started_at, SwapFilter, SwapOrder, SwapPage, SwapQuery, SwapStatus, SwapSummary,
+};
 pub use sqlite::SqliteDatabase;
 
 use crate::fs::ensure_directory_exists;
@@ -9,11 +13,36 @@ use serde::{Deserialize, Serialize};
 use std::fmt::Display;
 use std::path::Path;
 use std::sync::Arc;
+use std::time::Duration;
+use time::OffsetDateTime;
 
 mod alice;
 mod bob;
+mod info;
+mod listing;
 mod sqlite;
 
+/// Finished swaps keep their full state history for this many days unless
+/// configured otherwise.
+pub const DEFAULT_RETENTION_DAYS: u32 = 30;
+
+const PRUNE_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// A known address of a peer is forgotten once connecting to it failed this
+/// many times in a row and it was not reachable for [`DEAD_ADDRESS_DAYS`].
+const DEAD_ADDRESS_FAILURES: u32 = 5;
+const DEAD_ADDRESS_DAYS: i64 = 30;
+
+/// Tracks a swap that was moved between machines.
+///
+/// The number is incremented on every export. A swap that is marked as
+/// exported must not be resumed from this database.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Generation {
+    pub number: u32,
+    pub exported: bool,
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
 pub enum Swap {
     Alice(Alice),
@@ -96,3 +125,36 @@ pub async fn open_db(sqlite_path: impl AsRef<Path>) -> Result<Arc<dyn Database +
         Ok(Arc::new(sqlite))
     }
 }
+
+/// Returns the point in time before which finished swaps are pruned.
+pub fn retention_cutoff(retention_days: u32) -> OffsetDateTime {
+    OffsetDateTime::now_utc() - time::Duration::days(i64::from(retention_days))
+}
+
+/// Forgets the addresses of peers that were unreachable for a long time.
+pub async fn prune_dead_addresses(db: &(dyn Database + Send + Sync)) {
+    let unreachable_since = OffsetDateTime::now_utc() - time::Duration::days(DEAD_ADDRESS_DAYS);
+
+    match db
+        .prune_addresses(unreachable_since, DEAD_ADDRESS_FAILURES)
+        .await
+    {
+        Ok(0) => {}
+        Ok(pruned) => tracing::debug!(%pruned, "Forgot unreachable peer addresses"),
+        Err(error) => tracing::warn!("Failed to prune peer addresses: {:#}", error),
+    }
+}
+
+/// Prunes the states of swaps that finished more than `retention_days` ago,
+/// once right away and then once a day.
+pub async fn prune_periodically(db: Arc<dyn Database + Send + Sync>, retention_days: u32) {
+    loop {
+        match db.prune(retention_cutoff(retention_days)).await {
+            Ok(0) => {}
+            Ok(pruned) => tracing::info!(%pruned, "Pruned states of finished swaps"),
+            Err(error) => tracing::warn!("Failed to prune database: {:#}", error),
+        }
+
+        tokio::time::sleep(PRUNE_INTERVAL).await;
+    }
+}
@@ -3,6 +3,7 @@ use crate::monero;
 use crate::monero::{monero_private_key, TransferProof};
 use crate::protocol::alice;
 use crate::protocol::alice::AliceState;
+use crate::secret::Secret;
 use monero_rpc::wallet::BlockHeight;
 use serde::{Deserialize, Serialize};
 use std::fmt;
@@ -39,7 +40,7 @@ pub enum Alice {
     EncSigLearned {
         monero_wallet_restore_blockheight: BlockHeight,
         transfer_proof: TransferProof,
-        encrypted_signature: EncryptedSignature,
+        encrypted_signature: Secret<EncryptedSignature>,
         state3: alice::State3,
     },
     BtcRedeemTransactionPublished {
@@ -64,8 +65,13 @@ pub enum Alice {
         monero_wallet_restore_blockheight: BlockHeight,
         transfer_proof: TransferProof,
         state3: alice::State3,
-        #[serde(with = "monero_private_key")]
-        spend_key: monero::PrivateKey,
+        #[serde(with = "monero_private_key::secret")]
+        spend_key: Secret<monero::PrivateKey>,
+    },
+    /// Swaps punished before Alice kept her key share are stored as
+    /// [`AliceEndState::BtcPunished`].
+    BtcPunished {
+        state3: alice::State3,
     },
     Done(AliceEndState),
 }
@@ -173,7 +179,12 @@ impl From<AliceState> for Alice {
                 transfer_proof,
                 state3: state3.as_ref().clone(),
             },
-            AliceState::BtcPunished => Alice::Done(AliceEndState::BtcPunished),
+            AliceState::BtcPunished {
+                state3: Some(state3),
+            } => Alice::BtcPunished {
+                state3: state3.as_ref().clone(),
+            },
+            AliceState::BtcPunished { state3: None } => Alice::Done(AliceEndState::BtcPunished),
             AliceState::SafelyAborted => Alice::Done(AliceEndState::SafelyAborted),
         }
     }
@@ -273,11 +284,14 @@ impl From<Alice> for AliceState {
                 spend_key,
                 state3: Box::new(state3),
             },
+            Alice::BtcPunished { state3 } => AliceState::BtcPunished {
+                state3: Some(Box::new(state3)),
+            },
             Alice::Done(end_state) => match end_state {
                 AliceEndState::SafelyAborted => AliceState::SafelyAborted,
                 AliceEndState::BtcRedeemed => AliceState::BtcRedeemed,
                 AliceEndState::XmrRefunded => AliceState::XmrRefunded,
-                AliceEndState::BtcPunished => AliceState::BtcPunished,
+                AliceEndState::BtcPunished => AliceState::BtcPunished { state3: None },
             },
         }
     }
@@ -304,6 +318,7 @@ impl fmt::Display for Alice {
             Alice::BtcCancelled { .. } => f.write_str("Bitcoin cancel transaction published"),
             Alice::BtcPunishable { .. } => f.write_str("Bitcoin punishable"),
             Alice::BtcRefunded { .. } => f.write_str("Monero refundable"),
+            Alice::BtcPunished { .. } => f.write_str("Bitcoin punished"),
             Alice::Done(end_state) => write!(f, "Done: {}", end_state),
         }
     }
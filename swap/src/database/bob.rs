@@ -1,3 +1,5 @@
+use crate::bitcoin;
+use crate::monero;
 use crate::monero::TransferProof;
 use crate::protocol::bob;
 use crate::protocol::bob::BobState;
@@ -10,10 +12,11 @@ use std::fmt;
 #[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
 pub enum Bob {
     Started {
-        #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
         btc_amount: bitcoin::Amount,
         #[serde_as(as = "DisplayFromStr")]
         change_address: bitcoin::Address,
+        #[serde(default)]
+        xmr_amount: Option<monero::Amount>,
     },
     ExecutionSetupDone {
         state2: bob::State2,
@@ -53,9 +56,11 @@ impl From<BobState> for Bob {
             BobState::Started {
                 btc_amount,
                 change_address,
+                xmr_amount,
             } => Bob::Started {
                 btc_amount,
                 change_address,
+                xmr_amount,
             },
             BobState::SwapSetupCompleted(state2) => Bob::ExecutionSetupDone { state2 },
             BobState::BtcLocked {
@@ -97,9 +102,11 @@ impl From<Bob> for BobState {
             Bob::Started {
                 btc_amount,
                 change_address,
+                xmr_amount,
             } => BobState::Started {
                 btc_amount,
                 change_address,
+                xmr_amount,
             },
             Bob::ExecutionSetupDone { state2 } => BobState::SwapSetupCompleted(state2),
             Bob::BtcLocked {
@@ -0,0 +1,323 @@
+//! Everything known about a single swap, in one structure for all frontends.
+//!
+//! The CLI, the dashboard, the taker libraries and the admin interface of the
+//! ASB all show a swap through [`SwapInfo`], so they agree on what a swap is
+//! in and only differ in how they render it. [`SwapInfo`] serializes to JSON
+//! for machines and implements `Display` for people.
+
+use crate::bitcoin::{self, ExpiredTimelocks, Txid};
+use crate::database::{format_started_at, SwapSummary};
+use crate::monero;
+use crate::protocol::bob::BobState;
+use crate::protocol::sequence::Role;
+use crate::protocol::{Database, State};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SwapInfo {
+    pub swap_id: Uuid,
+    pub role: Role,
+    /// The phase of the swap, e.g. `btc is locked`.
+    pub state: String,
+    pub finished: bool,
+    /// Seconds since the unix epoch.
+    pub started_at: i64,
+    /// When the swap entered its current state, in seconds since the unix
+    /// epoch.
+    pub updated_at: i64,
+    pub peer_id: Option<String>,
+    /// The maker identity of the ASB the swap was set up with.
+    #[serde(default)]
+    pub identity: Option<String>,
+    #[serde(default)]
+    pub btc_amount: Option<bitcoin::Amount>,
+    /// In piconero.
+    #[serde(default)]
+    pub xmr_amount: Option<monero::Amount>,
+    /// The estimated fiat value at the time the swap was set up.
+    #[serde(default)]
+    pub fiat_value: Option<String>,
+    #[serde(default)]
+    pub btc_lock_txid: Option<Txid>,
+    #[serde(default)]
+    pub xmr_lock_tx_hash: Option<monero::TxHash>,
+    /// Which timelock expired, only checked for swaps that are not finished
+    /// and only if a Bitcoin wallet was at hand.
+    #[serde(default)]
+    pub timelock: Option<ExpiredTimelocks>,
+    /// Why the swap stopped waiting for the counterparty.
+    #[serde(default)]
+    pub wind_down: Option<String>,
+    /// The states the swap went through, a pruned swap only keeps its last
+    /// one.
+    #[serde(default)]
+    pub history: Vec<StateChange>,
+    /// The errors that stopped the swap, oldest first.
+    #[serde(default)]
+    pub errors: Vec<SwapError>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StateChange {
+    /// Seconds since the unix epoch.
+    pub entered_at: i64,
+    pub state: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SwapError {
+    /// Seconds since the unix epoch.
+    pub failed_at: i64,
+    pub error: String,
+}
+
+/// Loads everything known about the swap, see [`SwapInfo::load`].
+pub async fn swap_info(
+    db: &(dyn Database + Send + Sync),
+    swap_id: Uuid,
+    bitcoin_wallet: Option<&bitcoin::Wallet>,
+) -> Result<SwapInfo> {
+    let summary = db.get_summary(swap_id).await?;
+
+    SwapInfo::load(db, &summary, bitcoin_wallet).await
+}
+
+impl SwapInfo {
+    /// Completes the summary of a swap with its state history. The timelocks
+    /// are checked with `bitcoin_wallet` if the swap is not finished.
+    pub async fn load(
+        db: &(dyn Database + Send + Sync),
+        summary: &SwapSummary,
+        bitcoin_wallet: Option<&bitcoin::Wallet>,
+    ) -> Result<SwapInfo> {
+        let swap_id = summary.swap_id;
+        let states = db.get_state_history(swap_id).await?;
+        let (updated_at, latest) = states
+            .last()
+            .with_context(|| format!("No state found for swap {}", swap_id))?;
+
+        let timelock = match bitcoin_wallet {
+            Some(bitcoin_wallet) if !summary.finished => expired_timelock(latest, bitcoin_wallet)
+                .await
+                .context("Failed to check the timelocks")?,
+            _ => None,
+        };
+        let identity = db.get_identity(swap_id).await?;
+        let fiat_value = db.get_fiat_value(swap_id).await?;
+        let wind_down = db.get_wind_down(swap_id).await?;
+        let errors = db.get_errors(swap_id).await?;
+
+        Ok(SwapInfo {
+            swap_id,
+            role: summary.role,
+            state: summary.state.clone(),
+            finished: summary.finished,
+            started_at: summary.started_at.unix_timestamp(),
+            updated_at: updated_at.unix_timestamp(),
+            peer_id: summary.peer_id.map(|peer_id| peer_id.to_string()),
+            identity,
+            btc_amount: summary.btc_amount,
+            xmr_amount: latest_known(&states, xmr_amount),
+            fiat_value: fiat_value.map(|value| value.to_string()),
+            btc_lock_txid: latest_known(&states, tx_lock_id),
+            xmr_lock_tx_hash: latest_known(&states, xmr_lock_tx_hash),
+            timelock,
+            wind_down,
+            history: states
+                .iter()
+                .map(|(entered_at, state)| StateChange {
+                    entered_at: entered_at.unix_timestamp(),
+                    state: SwapSummary::describe(state).1,
+                })
+                .collect(),
+            errors: errors
+                .into_iter()
+                .map(|(failed_at, error)| SwapError {
+                    failed_at: failed_at.unix_timestamp(),
+                    error,
+                })
+                .collect(),
+        })
+    }
+}
+
+/// Later states forget what the earlier ones knew, e.g. Bob's Monero lock
+/// transaction, a fact is taken from the latest state that still knows it.
+fn latest_known<T>(
+    states: &[(OffsetDateTime, State)],
+    fact: impl Fn(&State) -> Option<T>,
+) -> Option<T> {
+    states.iter().rev().find_map(|(_, state)| fact(state))
+}
+
+fn xmr_amount(state: &State) -> Option<monero::Amount> {
+    match state {
+        State::Alice(state) => state.xmr_amount(),
+        State::Bob(state) => state.xmr_amount(),
+    }
+}
+
+fn tx_lock_id(state: &State) -> Option<Txid> {
+    match state {
+        State::Alice(state) => state.tx_lock_id(),
+        State::Bob(state) => state.tx_lock_id(),
+    }
+}
+
+fn xmr_lock_tx_hash(state: &State) -> Option<monero::TxHash> {
+    let transfer_proof = match state {
+        State::Alice(state) => state.transfer_proof(),
+        State::Bob(state) => state.xmr_lock_proof(),
+    };
+
+    transfer_proof.map(|transfer_proof| transfer_proof.tx_hash())
+}
+
+async fn expired_timelock(
+    state: &State,
+    bitcoin_wallet: &bitcoin::Wallet,
+) -> Result<Option<ExpiredTimelocks>> {
+    let expired = match state {
+        State::Alice(state) => match state.state3() {
+            Some(state3) => state3.expired_timelocks(bitcoin_wallet).await?,
+            None => return Ok(None),
+        },
+        State::Bob(BobState::BtcLocked { state3, .. })
+        | State::Bob(BobState::XmrLockProofReceived { state: state3, .. }) => {
+            state3.current_epoch(bitcoin_wallet).await?
+        }
+        State::Bob(BobState::XmrLocked(state4)) | State::Bob(BobState::EncSigSent(state4)) => {
+            state4.expired_timelock(bitcoin_wallet).await?
+        }
+        State::Bob(BobState::CancelTimelockExpired(state6))
+        | State::Bob(BobState::BtcCancelled(state6))
+        | State::Bob(BobState::BtcRefunded(state6)) => {
+            state6.expired_timelock(bitcoin_wallet).await?
+        }
+        State::Bob(_) => return Ok(None),
+    };
+
+    Ok(Some(expired))
+}
+
+impl fmt::Display for SwapInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let time = |timestamp| format_started_at(timestamp).map_err(|_| fmt::Error);
+        let or_unknown = |value: Option<String>| value.unwrap_or_else(|| "unknown".to_owned());
+
+        writeln!(f, "Swap {} as {}", self.swap_id, self.role)?;
+        writeln!(f, "State:       {}", self.state)?;
+        writeln!(f, "Started:     {}", time(self.started_at)?)?;
+        writeln!(f, "Updated:     {}", time(self.updated_at)?)?;
+        writeln!(f, "Peer:        {}", or_unknown(self.peer_id.clone()))?;
+        if let Some(identity) = &self.identity {
+            writeln!(f, "Identity:    {}", identity)?;
+        }
+        writeln!(
+            f,
+            "Bitcoin:     {}",
+            or_unknown(self.btc_amount.map(|amount| amount.to_string()))
+        )?;
+        writeln!(
+            f,
+            "Monero:      {}",
+            or_unknown(self.xmr_amount.map(|amount| amount.to_string()))
+        )?;
+        if let Some(fiat_value) = &self.fiat_value {
+            writeln!(f, "Fiat value:  {}", fiat_value)?;
+        }
+        writeln!(
+            f,
+            "BTC lock:    {}",
+            or_unknown(self.btc_lock_txid.map(|txid| txid.to_string()))
+        )?;
+        writeln!(
+            f,
+            "XMR lock:    {}",
+            or_unknown(self.xmr_lock_tx_hash.as_ref().map(|hash| hash.to_string()))
+        )?;
+        if let Some(timelock) = self.timelock {
+            let expired = match timelock {
+                ExpiredTimelocks::None => "none",
+                ExpiredTimelocks::Cancel => "cancel",
+                ExpiredTimelocks::Punish => "cancel and punish",
+            };
+            writeln!(f, "Expired:     {}", expired)?;
+        }
+        if let Some(wind_down) = &self.wind_down {
+            writeln!(f, "Wound down:  {}", wind_down)?;
+        }
+
+        writeln!(f)?;
+        writeln!(f, "History:")?;
+        for change in &self.history {
+            writeln!(f, "  {}  {}", time(change.entered_at)?, change.state)?;
+        }
+
+        if !self.errors.is_empty() {
+            writeln!(f)?;
+            writeln!(f, "Errors:")?;
+            for error in &self.errors {
+                writeln!(f, "  {}  {}", time(error.failed_at)?, error.error)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::SqliteDatabase;
+    use std::fs::File;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn info_of_a_failed_swap() -> Result<()> {
+        let temp_db = tempdir()?.into_path().join("tempdb");
+        File::create(&temp_db)?;
+        let db = SqliteDatabase::open(temp_db).await?;
+        let swap_id = Uuid::new_v4();
+
+        db.insert_latest_state(
+            swap_id,
+            State::Bob(BobState::Started {
+                btc_amount: bitcoin::Amount::from_sat(100_000),
+                change_address: "tb1qyccwk4yun26708qg5h6g6we8kxln232wclxf5a".parse()?,
+                xmr_amount: Some(monero::Amount::from_piconero(1_000)),
+            }),
+        )
+        .await?;
+        db.insert_latest_state(swap_id, State::Bob(BobState::SafelyAborted))
+            .await?;
+        db.insert_error(swap_id, "Seller refused the swap").await?;
+
+        let info = swap_info(&db, swap_id, None).await?;
+
+        assert_eq!(info.role, Role::Bob);
+        assert_eq!(info.state, "safely aborted");
+        assert!(info.finished);
+        assert_eq!(info.btc_amount, Some(bitcoin::Amount::from_sat(100_000)));
+        assert_eq!(info.xmr_amount, Some(monero::Amount::from_piconero(1_000)));
+        assert_eq!(info.btc_lock_txid, None);
+        assert_eq!(
+            info.history
+                .iter()
+                .map(|change| change.state.as_str())
+                .collect::<Vec<_>>(),
+            vec!["quote has been requested", "safely aborted"]
+        );
+        assert_eq!(info.errors.len(), 1);
+        assert!(info.to_string().contains("Seller refused the swap"));
+
+        let json = serde_json::to_string(&info)?;
+        assert_eq!(serde_json::from_str::<SwapInfo>(&json)?, info);
+
+        Ok(())
+    }
+}
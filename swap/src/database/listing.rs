@@ -0,0 +1,256 @@
+//! Listing swaps by what they are, not by their full state.
+//!
+//! Next to the state history the database keeps a summary of every swap: its
+//! role, when it started, the phase it is in, whether it finished and the
+//! amount of Bitcoin it locks. Swaps are filtered and paginated on the indexed
+//! columns of the summaries, the states are not deserialized for that.
+//!
+//! Pages are ordered by the start of the swaps and continue after the last
+//! swap of the previous page, so swaps that start in the meantime neither
+//! shift nor repeat the following pages.
+
+use crate::bitcoin::{self, parse_amount};
+use crate::protocol::sequence::Role;
+use crate::protocol::State;
+use anyhow::{Context, Result};
+use libp2p::PeerId;
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DisplayFromStr};
+use structopt::StructOpt;
+use time::{Date, OffsetDateTime};
+use uuid::Uuid;
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Serialize, Deserialize, strum::Display, strum::EnumString,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum SwapStatus {
+    Open,
+    Finished,
+}
+
+/// Which swaps to list, every filter that is set has to match.
+#[serde_as]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SwapFilter {
+    pub role: Option<Role>,
+    pub status: Option<SwapStatus>,
+    /// The phase as printed by `history`, e.g. `btc is locked`.
+    pub state: Option<String>,
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    pub peer_id: Option<PeerId>,
+    /// Seconds since the unix epoch, inclusive.
+    pub started_from: Option<i64>,
+    /// Seconds since the unix epoch, exclusive.
+    pub started_before: Option<i64>,
+    pub min_btc: Option<bitcoin::Amount>,
+    pub max_btc: Option<bitcoin::Amount>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SwapOrder {
+    OldestFirst,
+    NewestFirst,
+}
+
+impl Default for SwapOrder {
+    fn default() -> Self {
+        SwapOrder::OldestFirst
+    }
+}
+
+/// A page of the swaps matching a [`SwapFilter`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SwapPage {
+    /// All remaining swaps if not set.
+    pub limit: Option<u32>,
+    /// The last swap of the previous page.
+    pub after: Option<Uuid>,
+    pub order: SwapOrder,
+}
+
+impl SwapPage {
+    /// The swap the page after `swaps` starts after, `None` if `swaps` were
+    /// the last page.
+    pub fn next(&self, swaps: &[SwapSummary]) -> Option<Uuid> {
+        match (self.limit, swaps.last()) {
+            (Some(limit), Some(last)) if swaps.len() >= limit as usize => Some(last.swap_id),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SwapSummary {
+    pub swap_id: Uuid,
+    pub role: Role,
+    pub started_at: OffsetDateTime,
+    pub state: String,
+    pub finished: bool,
+    /// The amount of Bitcoin locked, `None` if no state of the swap knew it.
+    pub btc_amount: Option<bitcoin::Amount>,
+    pub peer_id: Option<PeerId>,
+}
+
+impl SwapSummary {
+    /// The role, phase and Bitcoin amount of `state`, whether it finished the
+    /// swap.
+    pub fn describe(state: &State) -> (Role, String, bool, Option<bitcoin::Amount>) {
+        let finished = state.swap_finished();
+
+        match state {
+            State::Alice(state) => (Role::Alice, state.to_string(), finished, state.btc_amount()),
+            State::Bob(state) => (Role::Bob, state.to_string(), finished, state.btc_amount()),
+        }
+    }
+}
+
+/// Command line options for filtering and paginating swaps.
+#[derive(Debug, Clone, PartialEq, StructOpt)]
+pub struct SwapQuery {
+    #[structopt(long = "status", help = "Only list swaps that are open or finished.")]
+    status: Option<SwapStatus>,
+
+    #[structopt(
+        long = "state",
+        help = "Only list swaps in this phase, as printed in the STATE column, e.g. 'btc is locked'."
+    )]
+    state: Option<String>,
+
+    #[structopt(long = "peer-id", help = "Only list swaps with this peer.")]
+    peer_id: Option<PeerId>,
+
+    #[structopt(
+        long = "since",
+        help = "Only list swaps that started on or after this day, e.g. 2021-11-01.",
+        parse(try_from_str = parse_date)
+    )]
+    since: Option<Date>,
+
+    #[structopt(
+        long = "until",
+        help = "Only list swaps that started on or before this day, e.g. 2021-11-30.",
+        parse(try_from_str = parse_date)
+    )]
+    until: Option<Date>,
+
+    #[structopt(
+        long = "min-btc",
+        help = "Only list swaps of at least this amount, e.g. '0.01 BTC'.",
+        parse(try_from_str = parse_amount)
+    )]
+    min_btc: Option<bitcoin::Amount>,
+
+    #[structopt(
+        long = "max-btc",
+        help = "Only list swaps of at most this amount, e.g. '0.1 BTC'.",
+        parse(try_from_str = parse_amount)
+    )]
+    max_btc: Option<bitcoin::Amount>,
+
+    #[structopt(long = "limit", help = "List at most this many swaps.")]
+    limit: Option<u32>,
+
+    #[structopt(
+        long = "after",
+        help = "Continue after this swap, the last one of the previous page."
+    )]
+    after: Option<Uuid>,
+
+    #[structopt(long = "newest-first", help = "List the most recent swaps first.")]
+    newest_first: bool,
+}
+
+impl SwapQuery {
+    pub fn into_filter_and_page(self) -> (SwapFilter, SwapPage) {
+        let filter = SwapFilter {
+            role: None,
+            status: self.status,
+            state: self.state,
+            peer_id: self.peer_id,
+            started_from: self.since.map(start_of_day),
+            started_before: self.until.and_then(Date::next_day).map(start_of_day),
+            min_btc: self.min_btc,
+            max_btc: self.max_btc,
+        };
+        let page = SwapPage {
+            limit: self.limit,
+            after: self.after,
+            order: if self.newest_first {
+                SwapOrder::NewestFirst
+            } else {
+                SwapOrder::OldestFirst
+            },
+        };
+
+        (filter, page)
+    }
+}
+
+/// Formats the start of a swap in seconds since the unix epoch as UTC, to the
+/// minute.
+pub fn format_started_at(started_at: i64) -> Result<String> {
+    let format = time::format_description::parse("[year]-[month]-[day] [hour]:[minute] UTC")?;
+
+    Ok(OffsetDateTime::from_unix_timestamp(started_at)?.format(&format)?)
+}
+
+fn parse_date(s: &str) -> Result<Date> {
+    let format = time::format_description::parse("[year]-[month]-[day]")?;
+
+    Date::parse(s, &format).with_context(|| format!("Expected a day like 2021-11-01, got {}", s))
+}
+
+fn start_of_day(date: Date) -> i64 {
+    date.midnight().assume_utc().unix_timestamp()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn until_includes_the_whole_day() {
+        let query =
+            SwapQuery::from_iter(["history", "--since", "2021-11-01", "--until", "2021-11-01"]);
+
+        let (filter, _) = query.into_filter_and_page();
+
+        assert_eq!(filter.started_from, Some(1_635_724_800));
+        assert_eq!(filter.started_before, Some(1_635_724_800 + 24 * 60 * 60));
+    }
+
+    #[test]
+    fn start_is_formatted_as_utc() {
+        assert_eq!(
+            format_started_at(1_635_724_800 + 90 * 60).unwrap(),
+            "2021-11-01 01:30 UTC"
+        );
+    }
+
+    #[test]
+    fn next_page_starts_after_the_last_swap_of_a_full_page() {
+        let summary = |swap_id| SwapSummary {
+            swap_id,
+            role: Role::Bob,
+            started_at: OffsetDateTime::UNIX_EPOCH,
+            state: "btc is locked".to_owned(),
+            finished: false,
+            btc_amount: None,
+            peer_id: None,
+        };
+        let swaps = vec![summary(Uuid::new_v4()), summary(Uuid::new_v4())];
+        let page = |limit| SwapPage {
+            limit,
+            ..SwapPage::default()
+        };
+
+        assert_eq!(page(Some(2)).next(&swaps), Some(swaps[1].swap_id));
+        assert_eq!(page(Some(3)).next(&swaps), None);
+        assert_eq!(page(None).next(&swaps), None);
+    }
+}
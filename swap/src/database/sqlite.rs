@@ -1,11 +1,23 @@
-use crate::database::Swap;
+use crate::amounts::Price;
+use crate::asb::quotes::QuoteRecord;
+use crate::bitcoin;
+use crate::database::{Generation, Swap, SwapFilter, SwapOrder, SwapPage, SwapStatus, SwapSummary};
+use crate::fiat::FiatValue;
 use crate::monero::Address;
+use crate::network::outbox;
+use crate::network::quote::SignedQuote;
+use crate::protocol::presigned::PresignedTransaction;
+use crate::protocol::receipt::Receipt;
+use crate::protocol::sequence::MessageKind;
 use crate::protocol::{Database, State};
 use anyhow::{Context, Result};
 use async_trait::async_trait;
+use libp2p::multiaddr::Protocol;
 use libp2p::{Multiaddr, PeerId};
-use sqlx::sqlite::Sqlite;
-use sqlx::{Pool, SqlitePool};
+use sqlx::pool::PoolConnection;
+use sqlx::sqlite::{Sqlite, SqliteRow};
+use sqlx::{Pool, Row, SqlitePool};
+use std::convert::TryFrom;
 use std::path::Path;
 use std::str::FromStr;
 use time::OffsetDateTime;
@@ -24,6 +36,7 @@ impl SqliteDatabase {
         let pool = SqlitePool::connect(&path_str).await?;
         let mut sqlite = Self { pool };
         sqlite.run_migrations().await?;
+        sqlite.summarize_swaps().await?;
         Ok(sqlite)
     }
 
@@ -31,6 +44,37 @@ impl SqliteDatabase {
         sqlx::migrate!("./migrations").run(&self.pool).await?;
         Ok(())
     }
+
+    /// Summarizes the swaps recorded before swaps were summarized.
+    async fn summarize_swaps(&self) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+        let rows = sqlx::query!(
+            r#"
+           SELECT DISTINCT swap_id
+           FROM swap_states
+           WHERE swap_id NOT IN (SELECT swap_id FROM swap_summaries)
+        "#
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        for row in rows {
+            let history = self
+                .get_state_history(Uuid::from_str(&row.swap_id)?)
+                .await?;
+            let started_at = match history.first() {
+                Some((entered_at, _)) => entered_at.unix_timestamp(),
+                None => continue,
+            };
+
+            // The amount is only known to some of the states
+            for (_, state) in &history {
+                upsert_summary(&mut conn, &row.swap_id, started_at, state).await?;
+            }
+        }
+
+        Ok(())
+    }
 }
 
 #[async_trait]
@@ -121,20 +165,28 @@ impl Database for SqliteDatabase {
     }
 
     async fn insert_address(&self, peer_id: PeerId, address: Multiaddr) -> Result<()> {
+        let address = without_peer_id(address);
+        if address.is_empty() {
+            return Ok(());
+        }
+
         let mut conn = self.pool.acquire().await?;
 
         let peer_id = peer_id.to_string();
         let address = address.to_string();
+        let added_at = OffsetDateTime::now_utc().unix_timestamp();
 
         sqlx::query!(
             r#"
-        insert into peer_addresses (
+        insert or ignore into peer_address_book (
             peer_id,
-            address
-            ) values (?, ?);
+            address,
+            added_at
+            ) values (?, ?, ?);
         "#,
             peer_id,
-            address
+            address,
+            added_at
         )
         .execute(&mut conn)
         .await?;
@@ -150,8 +202,9 @@ impl Database for SqliteDatabase {
         let rows = sqlx::query!(
             r#"
         SELECT address
-        FROM peer_addresses
+        FROM peer_address_book
         WHERE peer_id = ?
+        ORDER BY last_success IS NULL, last_success DESC, consecutive_failures, added_at, rowid
         "#,
             peer_id,
         )
@@ -169,11 +222,80 @@ impl Database for SqliteDatabase {
         addresses
     }
 
+    async fn record_address_outcome(
+        &self,
+        peer_id: PeerId,
+        address: Multiaddr,
+        reachable: bool,
+        at: OffsetDateTime,
+    ) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+
+        let peer_id = peer_id.to_string();
+        let address = without_peer_id(address).to_string();
+        let at = at.unix_timestamp();
+
+        if reachable {
+            sqlx::query!(
+                r#"
+            UPDATE peer_address_book
+            SET successes = successes + 1, consecutive_failures = 0, last_success = ?
+            WHERE peer_id = ? AND address = ?
+        "#,
+                at,
+                peer_id,
+                address
+            )
+            .execute(&mut conn)
+            .await?;
+        } else {
+            sqlx::query!(
+                r#"
+            UPDATE peer_address_book
+            SET failures = failures + 1, consecutive_failures = consecutive_failures + 1
+            WHERE peer_id = ? AND address = ?
+        "#,
+                peer_id,
+                address
+            )
+            .execute(&mut conn)
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn prune_addresses(
+        &self,
+        unreachable_since: OffsetDateTime,
+        failures: u32,
+    ) -> Result<u64> {
+        let mut conn = self.pool.acquire().await?;
+
+        let unreachable_since = unreachable_since.unix_timestamp();
+        let failures = i64::from(failures);
+
+        let result = sqlx::query!(
+            r#"
+        DELETE FROM peer_address_book
+        WHERE consecutive_failures >= ?
+        AND coalesce(last_success, added_at) < ?
+        "#,
+            failures,
+            unreachable_since
+        )
+        .execute(&mut conn)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
     async fn insert_latest_state(&self, swap_id: Uuid, state: State) -> Result<()> {
         let mut conn = self.pool.acquire().await?;
         let entered_at = OffsetDateTime::now_utc();
 
         let swap_id = swap_id.to_string();
+        upsert_summary(&mut conn, &swap_id, entered_at.unix_timestamp(), &state).await?;
         let swap = serde_json::to_string(&Swap::from(state))?;
         let entered_at = entered_at.to_string();
 
@@ -220,6 +342,53 @@ impl Database for SqliteDatabase {
         Ok(swap.into())
     }
 
+    async fn get_states(&self, swap_id: Uuid) -> Result<Vec<State>> {
+        let mut conn = self.pool.acquire().await?;
+        let swap_id = swap_id.to_string();
+        let rows = sqlx::query!(
+            r#"
+           SELECT state
+           FROM swap_states
+           WHERE swap_id = ?
+           ORDER BY id
+        "#,
+            swap_id
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        rows.iter()
+            .map(|row| {
+                let swap: Swap = serde_json::from_str(&row.state)?;
+                Ok(swap.into())
+            })
+            .collect()
+    }
+
+    async fn get_state_history(&self, swap_id: Uuid) -> Result<Vec<(OffsetDateTime, State)>> {
+        let mut conn = self.pool.acquire().await?;
+        let swap_id = swap_id.to_string();
+        let rows = sqlx::query!(
+            r#"
+           SELECT entered_at, state
+           FROM swap_states
+           WHERE swap_id = ?
+           ORDER BY id
+        "#,
+            swap_id
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        rows.iter()
+            .map(|row| {
+                let entered_at = parse_entered_at(&row.entered_at)?;
+                let swap: Swap = serde_json::from_str(&row.state)?;
+                Ok((entered_at, swap.into()))
+            })
+            .collect()
+    }
+
     async fn all(&self) -> Result<Vec<(Uuid, State)>> {
         let mut conn = self.pool.acquire().await?;
         let rows = sqlx::query!(
@@ -249,127 +418,1497 @@ impl Database for SqliteDatabase {
 
         result
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::protocol::alice::AliceState;
-    use crate::protocol::bob::BobState;
-    use std::fs::File;
-    use tempfile::tempdir;
 
-    #[tokio::test]
-    async fn test_insert_and_load_state() {
-        let db = setup_test_db().await.unwrap();
+    async fn list_swaps(&self, filter: &SwapFilter, page: &SwapPage) -> Result<Vec<SwapSummary>> {
+        let mut conn = self.pool.acquire().await?;
 
-        let state_1 = State::Alice(AliceState::BtcRedeemed);
-        let swap_id_1 = Uuid::new_v4();
+        let (sql, binds) = list_swaps_query(filter, page)?;
+        let mut query = sqlx::query(&sql);
+        for bind in binds {
+            query = match bind {
+                Bind::Text(text) => query.bind(text),
+                Bind::Integer(integer) => query.bind(integer),
+            };
+        }
 
-        db.insert_latest_state(swap_id_1, state_1).await.unwrap();
+        let rows = query.fetch_all(&mut conn).await?;
 
-        let state_1 = State::Alice(AliceState::BtcRedeemed);
+        rows.iter().map(summary_from_row).collect()
+    }
 
-        db.insert_latest_state(swap_id_1, state_1.clone())
-            .await
-            .unwrap();
+    async fn get_summary(&self, swap_id: Uuid) -> Result<SwapSummary> {
+        let mut conn = self.pool.acquire().await?;
 
-        let state_1_loaded = db.get_state(swap_id_1).await.unwrap();
+        let row = sqlx::query(&format!("{} WHERE s.swap_id = ?", SELECT_SUMMARIES))
+            .bind(swap_id.to_string())
+            .fetch_optional(&mut conn)
+            .await?
+            .with_context(|| format!("No swap found with id {}", swap_id))?;
 
-        assert_eq!(state_1, state_1_loaded);
+        summary_from_row(&row)
     }
 
-    #[tokio::test]
-    async fn test_retrieve_all_latest_states() {
-        let db = setup_test_db().await.unwrap();
+    async fn prune(&self, finished_before: OffsetDateTime) -> Result<u64> {
+        let mut conn = self.pool.acquire().await?;
+        let rows = sqlx::query!(
+            r#"
+           SELECT swap_id, entered_at, state
+           FROM (
+           SELECT max(id), swap_id, entered_at, state
+           FROM swap_states
+           GROUP BY swap_id
+           )
+        "#
+        )
+        .fetch_all(&mut conn)
+        .await?;
 
-        let state_1 = State::Alice(AliceState::BtcRedeemed);
-        let state_2 = State::Alice(AliceState::BtcPunished);
-        let state_3 = State::Alice(AliceState::SafelyAborted);
-        let state_4 = State::Bob(BobState::SafelyAborted);
-        let swap_id_1 = Uuid::new_v4();
-        let swap_id_2 = Uuid::new_v4();
+        let mut pruned = 0;
 
-        db.insert_latest_state(swap_id_1, state_1.clone())
-            .await
-            .unwrap();
-        db.insert_latest_state(swap_id_1, state_2.clone())
-            .await
-            .unwrap();
-        db.insert_latest_state(swap_id_1, state_3.clone())
-            .await
-            .unwrap();
-        db.insert_latest_state(swap_id_2, state_4.clone())
+        for row in rows {
+            let state = State::from(serde_json::from_str::<Swap>(&row.state)?);
+
+            // Swaps that are not finished may still have to be cancelled, refunded or
+            // punished, their states must be kept no matter how old they are.
+            if !state.swap_finished() || parse_entered_at(&row.entered_at)? > finished_before {
+                continue;
+            }
+
+            let result = sqlx::query!(
+                r#"
+            DELETE FROM swap_states
+            WHERE swap_id = ?
+            AND id < (SELECT max(id) FROM swap_states WHERE swap_id = ?)
+        "#,
+                row.swap_id,
+                row.swap_id
+            )
+            .execute(&mut conn)
+            .await?;
+
+            if result.rows_affected() > 0 {
+                tracing::debug!(swap_id = %row.swap_id, states = result.rows_affected(), "Pruned states of finished swap");
+            }
+
+            pruned += result.rows_affected();
+        }
+
+        Ok(pruned)
+    }
+
+    async fn compact(&self) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+
+        sqlx::query("VACUUM")
+            .execute(&mut conn)
             .await
-            .unwrap();
+            .context("Failed to compact database")?;
 
-        let latest_loaded = db.all().await.unwrap();
+        Ok(())
+    }
 
-        assert_eq!(latest_loaded.len(), 2);
+    async fn insert_generation(&self, swap_id: Uuid, generation: Generation) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
 
-        assert!(latest_loaded.contains(&(swap_id_1, state_3)));
-        assert!(latest_loaded.contains(&(swap_id_2, state_4)));
+        let swap_id = swap_id.to_string();
+        let number = i64::from(generation.number);
 
-        assert!(!latest_loaded.contains(&(swap_id_1, state_1)));
-        assert!(!latest_loaded.contains(&(swap_id_1, state_2)));
+        sqlx::query!(
+            r#"
+        insert or replace into swap_generations (
+            swap_id,
+            generation,
+            exported
+            ) values (?, ?, ?);
+        "#,
+            swap_id,
+            number,
+            generation.exported
+        )
+        .execute(&mut conn)
+        .await?;
+
+        Ok(())
     }
 
-    #[tokio::test]
-    async fn test_insert_load_monero_address() -> Result<()> {
-        let db = setup_test_db().await?;
+    async fn get_generation(&self, swap_id: Uuid) -> Result<Option<Generation>> {
+        let mut conn = self.pool.acquire().await?;
 
-        let swap_id = Uuid::new_v4();
-        let monero_address = "53gEuGZUhP9JMEBZoGaFNzhwEgiG7hwQdMCqFxiyiTeFPmkbt1mAoNybEUvYBKHcnrSgxnVWgZsTvRBaHBNXPa8tHiCU51a".parse()?;
+        let swap_id = swap_id.to_string();
 
-        db.insert_monero_address(swap_id, monero_address).await?;
+        let row = sqlx::query!(
+            r#"
+        SELECT generation, exported
+        FROM swap_generations
+        WHERE swap_id = ?
+        "#,
+            swap_id
+        )
+        .fetch_optional(&mut conn)
+        .await?;
 
-        let loaded_monero_address = db.get_monero_address(swap_id).await?;
+        row.map(|row| {
+            Ok(Generation {
+                number: u32::try_from(row.generation)?,
+                exported: row.exported,
+            })
+        })
+        .transpose()
+    }
 
-        assert_eq!(monero_address, loaded_monero_address);
+    async fn insert_identity(&self, swap_id: Uuid, identity: &str) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+
+        let swap_id = swap_id.to_string();
+
+        sqlx::query!(
+            r#"
+        insert into swap_identities (
+            swap_id,
+            identity
+            ) values (?, ?);
+        "#,
+            swap_id,
+            identity
+        )
+        .execute(&mut conn)
+        .await?;
 
         Ok(())
     }
 
-    #[tokio::test]
-    async fn test_insert_and_load_multiaddr() -> Result<()> {
-        let db = setup_test_db().await?;
+    async fn get_identity(&self, swap_id: Uuid) -> Result<Option<String>> {
+        let mut conn = self.pool.acquire().await?;
 
-        let peer_id = PeerId::random();
-        let multiaddr1 = "/ip4/127.0.0.1".parse::<Multiaddr>()?;
-        let multiaddr2 = "/ip4/127.0.0.2".parse::<Multiaddr>()?;
+        let swap_id = swap_id.to_string();
 
-        db.insert_address(peer_id, multiaddr1.clone()).await?;
-        db.insert_address(peer_id, multiaddr2.clone()).await?;
+        let row = sqlx::query!(
+            r#"
+        SELECT identity
+        FROM swap_identities
+        WHERE swap_id = ?
+        "#,
+            swap_id
+        )
+        .fetch_optional(&mut conn)
+        .await?;
 
-        let loaded_multiaddr = db.get_addresses(peer_id).await?;
+        Ok(row.map(|row| row.identity))
+    }
 
-        assert!(loaded_multiaddr.contains(&multiaddr1));
-        assert!(loaded_multiaddr.contains(&multiaddr2));
-        assert_eq!(loaded_multiaddr.len(), 2);
+    async fn insert_fiat_value(&self, swap_id: Uuid, value: FiatValue) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+
+        let swap_id = swap_id.to_string();
+        let currency = value.currency.to_string();
+        let btc_price = value.btc_price.to_string();
+        let fiat_value = value.value.to_string();
+
+        sqlx::query!(
+            r#"
+        insert into swap_fiat_values (
+            swap_id,
+            currency,
+            btc_price,
+            value
+            ) values (?, ?, ?, ?);
+        "#,
+            swap_id,
+            currency,
+            btc_price,
+            fiat_value
+        )
+        .execute(&mut conn)
+        .await?;
 
         Ok(())
     }
 
-    #[tokio::test]
-    async fn test_insert_and_load_peer_id() -> Result<()> {
-        let db = setup_test_db().await?;
+    async fn get_fiat_value(&self, swap_id: Uuid) -> Result<Option<FiatValue>> {
+        let mut conn = self.pool.acquire().await?;
 
-        let peer_id = PeerId::random();
-        let multiaddr1 = "/ip4/127.0.0.1".parse::<Multiaddr>()?;
-        let multiaddr2 = "/ip4/127.0.0.2".parse::<Multiaddr>()?;
+        let swap_id = swap_id.to_string();
 
-        db.insert_address(peer_id, multiaddr1.clone()).await?;
-        db.insert_address(peer_id, multiaddr2.clone()).await?;
+        let row = sqlx::query!(
+            r#"
+        SELECT currency, btc_price, value
+        FROM swap_fiat_values
+        WHERE swap_id = ?
+        "#,
+            swap_id
+        )
+        .fetch_optional(&mut conn)
+        .await?;
 
-        let loaded_multiaddr = db.get_addresses(peer_id).await?;
+        row.map(|row| {
+            Ok(FiatValue {
+                currency: row.currency.parse()?,
+                btc_price: row.btc_price.parse()?,
+                value: row.value.parse()?,
+            })
+        })
+        .transpose()
+    }
 
-        assert!(loaded_multiaddr.contains(&multiaddr1));
-        assert!(loaded_multiaddr.contains(&multiaddr2));
-        assert_eq!(loaded_multiaddr.len(), 2);
+    async fn insert_quote(&self, quote: QuoteRecord) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+
+        let peer_id = quote.peer_id.to_string();
+        let issued_at = quote.issued_at.to_string();
+        let price = i64::try_from(quote.price.btc_per_xmr().as_sat())?;
+        let outcome = quote.outcome.to_string();
+        let swap_id = quote.swap_id.map(|swap_id| swap_id.to_string());
+
+        sqlx::query!(
+            r#"
+        insert into quotes (
+            identity,
+            peer_id,
+            issued_at,
+            price,
+            outcome,
+            reason,
+            swap_id
+            ) values (?, ?, ?, ?, ?, ?, ?);
+        "#,
+            quote.identity,
+            peer_id,
+            issued_at,
+            price,
+            outcome,
+            quote.reason,
+            swap_id
+        )
+        .execute(&mut conn)
+        .await?;
 
         Ok(())
     }
 
+    async fn get_quotes(&self) -> Result<Vec<QuoteRecord>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let rows = sqlx::query!(
+            r#"
+        SELECT identity, peer_id, issued_at, price, outcome, reason, swap_id
+        FROM quotes
+        ORDER BY id
+        "#
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| {
+                Ok(QuoteRecord {
+                    identity: row.identity,
+                    peer_id: PeerId::from_str(&row.peer_id)?,
+                    issued_at: parse_entered_at(&row.issued_at)?,
+                    price: Price::new(bitcoin::Amount::from_sat(u64::try_from(row.price)?)),
+                    outcome: row.outcome.parse()?,
+                    reason: row.reason,
+                    swap_id: row.swap_id.map(|swap_id| swap_id.parse()).transpose()?,
+                })
+            })
+            .collect()
+    }
+
+    async fn insert_signed_quote(
+        &self,
+        swap_id: Uuid,
+        peer_id: PeerId,
+        quote: &SignedQuote,
+    ) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+
+        let swap_id = swap_id.to_string();
+        let peer_id = peer_id.to_string();
+        let quote = serde_json::to_string(quote)?;
+
+        sqlx::query!(
+            r#"
+        insert into signed_quotes (
+            swap_id,
+            peer_id,
+            quote
+            ) values (?, ?, ?);
+        "#,
+            swap_id,
+            peer_id,
+            quote
+        )
+        .execute(&mut conn)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_signed_quotes(&self, swap_id: Uuid) -> Result<Vec<SignedQuote>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let swap_id = swap_id.to_string();
+
+        let rows = sqlx::query!(
+            r#"
+        SELECT quote
+        FROM signed_quotes
+        WHERE swap_id = ?
+        ORDER BY id
+        "#,
+            swap_id
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        rows.iter()
+            .map(|row| Ok(serde_json::from_str(&row.quote)?))
+            .collect()
+    }
+
+    async fn insert_presigned_transaction(
+        &self,
+        swap_id: Uuid,
+        transaction: &PresignedTransaction,
+    ) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+
+        let swap_id = swap_id.to_string();
+        let kind = transaction.kind.to_string();
+        let presigned = serde_json::to_string(transaction)?;
+
+        sqlx::query!(
+            r#"
+        insert or replace into presigned_transactions (
+            swap_id,
+            kind,
+            presigned
+            ) values (?, ?, ?);
+        "#,
+            swap_id,
+            kind,
+            presigned
+        )
+        .execute(&mut conn)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_presigned_transactions(&self, swap_id: Uuid) -> Result<Vec<PresignedTransaction>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let swap_id = swap_id.to_string();
+
+        let rows = sqlx::query!(
+            r#"
+        SELECT presigned
+        FROM presigned_transactions
+        WHERE swap_id = ?
+        ORDER BY kind
+        "#,
+            swap_id
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        rows.iter()
+            .map(|row| Ok(serde_json::from_str(&row.presigned)?))
+            .collect()
+    }
+
+    async fn insert_receipt(&self, swap_id: Uuid, receipt: &Receipt) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+
+        let swap_id = swap_id.to_string();
+        let receipt = serde_json::to_string(receipt)?;
+
+        sqlx::query!(
+            r#"
+        insert or replace into receipts (
+            swap_id,
+            receipt
+            ) values (?, ?);
+        "#,
+            swap_id,
+            receipt
+        )
+        .execute(&mut conn)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_receipt(&self, swap_id: Uuid) -> Result<Option<Receipt>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let swap_id = swap_id.to_string();
+
+        let row = sqlx::query!(
+            r#"
+        SELECT receipt
+        FROM receipts
+        WHERE swap_id = ?
+        "#,
+            swap_id
+        )
+        .fetch_optional(&mut conn)
+        .await?;
+
+        row.map(|row| Ok(serde_json::from_str(&row.receipt)?))
+            .transpose()
+    }
+
+    async fn insert_wind_down(&self, swap_id: Uuid, reason: &str) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+
+        let swap_id = swap_id.to_string();
+
+        sqlx::query!(
+            r#"
+        insert or ignore into swap_wind_downs (
+            swap_id,
+            reason
+            ) values (?, ?);
+        "#,
+            swap_id,
+            reason
+        )
+        .execute(&mut conn)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_wind_down(&self, swap_id: Uuid) -> Result<Option<String>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let swap_id = swap_id.to_string();
+
+        let row = sqlx::query!(
+            r#"
+        SELECT reason
+        FROM swap_wind_downs
+        WHERE swap_id = ?
+        "#,
+            swap_id
+        )
+        .fetch_optional(&mut conn)
+        .await?;
+
+        Ok(row.map(|row| row.reason))
+    }
+
+    async fn insert_error(&self, swap_id: Uuid, error: &str) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+
+        let swap_id = swap_id.to_string();
+        let failed_at = OffsetDateTime::now_utc().to_string();
+
+        sqlx::query!(
+            r#"
+        insert into swap_errors (
+            swap_id,
+            failed_at,
+            error
+            ) values (?, ?, ?);
+        "#,
+            swap_id,
+            failed_at,
+            error
+        )
+        .execute(&mut conn)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_errors(&self, swap_id: Uuid) -> Result<Vec<(OffsetDateTime, String)>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let swap_id = swap_id.to_string();
+
+        let rows = sqlx::query!(
+            r#"
+        SELECT failed_at, error
+        FROM swap_errors
+        WHERE swap_id = ?
+        ORDER BY id
+        "#,
+            swap_id
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| Ok((parse_entered_at(&row.failed_at)?, row.error)))
+            .collect()
+    }
+
+    async fn insert_outbound(
+        &self,
+        peer_id: PeerId,
+        message: outbox::Message,
+    ) -> Result<outbox::Message> {
+        let mut conn = self.pool.acquire().await?;
+
+        let swap_id = message.swap_id().to_string();
+        let peer_id = peer_id.to_string();
+        let kind = message.kind().to_string();
+        let message = serde_json::to_string(&message)?;
+
+        sqlx::query!(
+            r#"
+        insert or ignore into outbox (
+            swap_id,
+            peer_id,
+            kind,
+            message
+            ) values (?, ?, ?, ?);
+        "#,
+            swap_id,
+            peer_id,
+            kind,
+            message
+        )
+        .execute(&mut conn)
+        .await?;
+
+        let row = sqlx::query!(
+            r#"
+        SELECT message
+        FROM outbox
+        WHERE swap_id = ? AND kind = ?
+        "#,
+            swap_id,
+            kind
+        )
+        .fetch_one(&mut conn)
+        .await?;
+
+        Ok(serde_json::from_str(&row.message)?)
+    }
+
+    async fn get_outbound(&self, peer_id: PeerId) -> Result<Vec<outbox::Message>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let peer_id = peer_id.to_string();
+
+        let rows = sqlx::query!(
+            r#"
+        SELECT message
+        FROM outbox
+        WHERE peer_id = ?
+        ORDER BY id
+        "#,
+            peer_id
+        )
+        .fetch_all(&mut conn)
+        .await?;
+
+        rows.iter()
+            .map(|row| Ok(serde_json::from_str(&row.message)?))
+            .collect()
+    }
+
+    async fn remove_outbound(&self, swap_id: Uuid, kind: MessageKind) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+
+        let swap_id = swap_id.to_string();
+        let kind = kind.to_string();
+
+        sqlx::query!(
+            r#"
+        DELETE FROM outbox
+        WHERE swap_id = ? AND kind = ?
+        "#,
+            swap_id,
+            kind
+        )
+        .execute(&mut conn)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn insert_received(&self, swap_id: Uuid, kind: MessageKind, nonce: Uuid) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+
+        let swap_id = swap_id.to_string();
+        let kind = kind.to_string();
+        let nonce = nonce.to_string();
+
+        sqlx::query!(
+            r#"
+        insert or ignore into received_messages (
+            swap_id,
+            kind,
+            nonce
+            ) values (?, ?, ?);
+        "#,
+            swap_id,
+            kind,
+            nonce
+        )
+        .execute(&mut conn)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn has_received(&self, swap_id: Uuid, kind: MessageKind, nonce: Uuid) -> Result<bool> {
+        let mut conn = self.pool.acquire().await?;
+
+        let swap_id = swap_id.to_string();
+        let kind = kind.to_string();
+        let nonce = nonce.to_string();
+
+        let row = sqlx::query!(
+            r#"
+        SELECT nonce
+        FROM received_messages
+        WHERE swap_id = ? AND kind = ? AND nonce = ?
+        "#,
+            swap_id,
+            kind,
+            nonce
+        )
+        .fetch_optional(&mut conn)
+        .await?;
+
+        Ok(row.is_some())
+    }
+}
+
+/// Parses the `entered_at` column, which stores the `Display` representation
+/// of an [`OffsetDateTime`].
+/// Inserts the summary of a swap that entered `state` or updates its phase. A
+/// swap keeps the start and the Bitcoin amount it was first summarized with.
+async fn upsert_summary(
+    conn: &mut PoolConnection<Sqlite>,
+    swap_id: &str,
+    started_at: i64,
+    state: &State,
+) -> Result<()> {
+    let (role, phase, finished, btc_amount) = SwapSummary::describe(state);
+    let role = role.to_string();
+    let btc_amount = btc_amount
+        .map(|amount| i64::try_from(amount.as_sat()))
+        .transpose()?;
+
+    sqlx::query!(
+        r#"
+        insert into swap_summaries (
+            swap_id,
+            role,
+            started_at,
+            state,
+            finished,
+            btc_amount
+            ) values (?, ?, ?, ?, ?, ?)
+        on conflict (swap_id) do update set
+            state = excluded.state,
+            finished = excluded.finished,
+            btc_amount = coalesce(swap_summaries.btc_amount, excluded.btc_amount);
+        "#,
+        swap_id,
+        role,
+        started_at,
+        phase,
+        finished,
+        btc_amount
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+const SELECT_SUMMARIES: &str =
+    "SELECT s.swap_id, s.role, s.started_at, s.state, s.finished, s.btc_amount, p.peer_id \
+     FROM swap_summaries s LEFT JOIN peers p ON p.swap_id = s.swap_id";
+
+fn summary_from_row(row: &SqliteRow) -> Result<SwapSummary> {
+    let btc_amount = row
+        .try_get::<Option<i64>, _>("btc_amount")?
+        .map(u64::try_from)
+        .transpose()?
+        .map(bitcoin::Amount::from_sat);
+    let peer_id = row
+        .try_get::<Option<String>, _>("peer_id")?
+        .map(|peer_id| PeerId::from_str(&peer_id))
+        .transpose()?;
+
+    Ok(SwapSummary {
+        swap_id: Uuid::from_str(row.try_get("swap_id")?)?,
+        role: row.try_get::<&str, _>("role")?.parse()?,
+        started_at: OffsetDateTime::from_unix_timestamp(row.try_get("started_at")?)?,
+        state: row.try_get("state")?,
+        finished: row.try_get("finished")?,
+        btc_amount,
+        peer_id,
+    })
+}
+
+enum Bind {
+    Text(String),
+    Integer(i64),
+}
+
+/// Builds the query for [`Database::list_swaps`], only the filters that are
+/// set become conditions so they can use the indexes of the summaries.
+fn list_swaps_query(filter: &SwapFilter, page: &SwapPage) -> Result<(String, Vec<Bind>)> {
+    let mut conditions = Vec::new();
+    let mut binds = Vec::new();
+
+    if let Some(role) = filter.role {
+        conditions.push("s.role = ?");
+        binds.push(Bind::Text(role.to_string()));
+    }
+    if let Some(status) = filter.status {
+        conditions.push("s.finished = ?");
+        binds.push(Bind::Integer(i64::from(status == SwapStatus::Finished)));
+    }
+    if let Some(state) = &filter.state {
+        conditions.push("s.state = ?");
+        binds.push(Bind::Text(state.clone()));
+    }
+    if let Some(peer_id) = filter.peer_id {
+        conditions.push("p.peer_id = ?");
+        binds.push(Bind::Text(peer_id.to_string()));
+    }
+    if let Some(started_from) = filter.started_from {
+        conditions.push("s.started_at >= ?");
+        binds.push(Bind::Integer(started_from));
+    }
+    if let Some(started_before) = filter.started_before {
+        conditions.push("s.started_at < ?");
+        binds.push(Bind::Integer(started_before));
+    }
+    if let Some(min_btc) = filter.min_btc {
+        conditions.push("s.btc_amount >= ?");
+        binds.push(Bind::Integer(i64::try_from(min_btc.as_sat())?));
+    }
+    if let Some(max_btc) = filter.max_btc {
+        conditions.push("s.btc_amount <= ?");
+        binds.push(Bind::Integer(i64::try_from(max_btc.as_sat())?));
+    }
+
+    let (after, direction) = match page.order {
+        SwapOrder::OldestFirst => (">", "ASC"),
+        SwapOrder::NewestFirst => ("<", "DESC"),
+    };
+    let after_condition = format!(
+        "(s.started_at, s.swap_id) {} (SELECT started_at, swap_id FROM swap_summaries WHERE swap_id = ?)",
+        after
+    );
+    if let Some(swap_id) = page.after {
+        conditions.push(&after_condition);
+        binds.push(Bind::Text(swap_id.to_string()));
+    }
+
+    let mut sql = String::from(SELECT_SUMMARIES);
+    if !conditions.is_empty() {
+        sql.push_str(" WHERE ");
+        sql.push_str(&conditions.join(" AND "));
+    }
+    sql.push_str(&format!(
+        " ORDER BY s.started_at {0}, s.swap_id {0}",
+        direction
+    ));
+    if let Some(limit) = page.limit {
+        sql.push_str(" LIMIT ?");
+        binds.push(Bind::Integer(i64::from(limit)));
+    }
+
+    Ok((sql, binds))
+}
+
+fn parse_entered_at(entered_at: &str) -> Result<OffsetDateTime> {
+    let format = time::format_description::parse(
+        "[year]-[month]-[day] [hour padding:none]:[minute]:[second].[subsecond] [offset_hour sign:mandatory]:[offset_minute]:[offset_second]",
+    )?;
+
+    OffsetDateTime::parse(entered_at, &format)
+        .with_context(|| format!("Failed to parse state timestamp {}", entered_at))
+}
+
+/// The addresses of the address book are stored without the `/p2p/` part, the
+/// peer id is stored next to them.
+fn without_peer_id(mut address: Multiaddr) -> Multiaddr {
+    if let Some(Protocol::P2p(_)) = address.iter().last() {
+        address.pop();
+    }
+
+    address
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::amounts::Quote;
+    use crate::asb::quotes::Outcome;
+    use crate::env::{self, GetConfig};
+    use crate::fiat::Currency;
+    use crate::monero;
+    use crate::network::maker_key::MakerKey;
+    use crate::network::quote::QuoteTerms;
+    use crate::network::transfer_proof;
+    use crate::protocol::alice::AliceState;
+    use crate::protocol::bob::BobState;
+    use crate::protocol::sequence::Role;
+    use libp2p::identity;
+    use libp2p::identity::ed25519;
+    use rust_decimal_macros::dec;
+    use std::fs::File;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_insert_and_load_state() {
+        let db = setup_test_db().await.unwrap();
+
+        let state_1 = State::Alice(AliceState::BtcRedeemed);
+        let swap_id_1 = Uuid::new_v4();
+
+        db.insert_latest_state(swap_id_1, state_1).await.unwrap();
+
+        let state_1 = State::Alice(AliceState::BtcRedeemed);
+
+        db.insert_latest_state(swap_id_1, state_1.clone())
+            .await
+            .unwrap();
+
+        let state_1_loaded = db.get_state(swap_id_1).await.unwrap();
+
+        assert_eq!(state_1, state_1_loaded);
+    }
+
+    #[tokio::test]
+    async fn test_retrieve_all_latest_states() {
+        let db = setup_test_db().await.unwrap();
+
+        let state_1 = State::Alice(AliceState::BtcRedeemed);
+        let state_2 = State::Alice(AliceState::XmrRefunded);
+        let state_3 = State::Alice(AliceState::SafelyAborted);
+        let state_4 = State::Bob(BobState::SafelyAborted);
+        let swap_id_1 = Uuid::new_v4();
+        let swap_id_2 = Uuid::new_v4();
+
+        db.insert_latest_state(swap_id_1, state_1.clone())
+            .await
+            .unwrap();
+        db.insert_latest_state(swap_id_1, state_2.clone())
+            .await
+            .unwrap();
+        db.insert_latest_state(swap_id_1, state_3.clone())
+            .await
+            .unwrap();
+        db.insert_latest_state(swap_id_2, state_4.clone())
+            .await
+            .unwrap();
+
+        let latest_loaded = db.all().await.unwrap();
+
+        assert_eq!(latest_loaded.len(), 2);
+
+        assert!(latest_loaded.contains(&(swap_id_1, state_3)));
+        assert!(latest_loaded.contains(&(swap_id_2, state_4)));
+
+        assert!(!latest_loaded.contains(&(swap_id_1, state_1)));
+        assert!(!latest_loaded.contains(&(swap_id_1, state_2)));
+    }
+
+    #[tokio::test]
+    async fn test_retrieve_all_states_of_swap_in_order() -> Result<()> {
+        let db = setup_test_db().await?;
+
+        let swap_id = Uuid::new_v4();
+        let other_swap_id = Uuid::new_v4();
+        let states = vec![
+            State::Alice(AliceState::SafelyAborted),
+            State::Alice(AliceState::BtcRedeemed),
+            State::Alice(AliceState::XmrRefunded),
+        ];
+
+        for state in &states {
+            db.insert_latest_state(swap_id, state.clone()).await?;
+        }
+        db.insert_latest_state(other_swap_id, State::Bob(BobState::SafelyAborted))
+            .await?;
+
+        assert_eq!(db.get_states(swap_id).await?, states);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_state_history_is_timestamped_in_order() -> Result<()> {
+        let db = setup_test_db().await?;
+
+        let swap_id = Uuid::new_v4();
+        let states = vec![
+            State::Bob(BobState::SafelyAborted),
+            State::Bob(BobState::XmrRedeemed {
+                tx_lock_id: bitcoin::Txid::default(),
+            }),
+        ];
+
+        for state in &states {
+            db.insert_latest_state(swap_id, state.clone()).await?;
+        }
+
+        let history = db.get_state_history(swap_id).await?;
+        let (timestamps, loaded): (Vec<_>, Vec<_>) = history.into_iter().unzip();
+
+        assert_eq!(loaded, states);
+        assert!(timestamps[0] <= timestamps[1]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_insert_load_monero_address() -> Result<()> {
+        let db = setup_test_db().await?;
+
+        let swap_id = Uuid::new_v4();
+        let monero_address = "53gEuGZUhP9JMEBZoGaFNzhwEgiG7hwQdMCqFxiyiTeFPmkbt1mAoNybEUvYBKHcnrSgxnVWgZsTvRBaHBNXPa8tHiCU51a".parse()?;
+
+        db.insert_monero_address(swap_id, monero_address).await?;
+
+        let loaded_monero_address = db.get_monero_address(swap_id).await?;
+
+        assert_eq!(monero_address, loaded_monero_address);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_insert_and_load_multiaddr() -> Result<()> {
+        let db = setup_test_db().await?;
+
+        let peer_id = PeerId::random();
+        let multiaddr1 = "/ip4/127.0.0.1".parse::<Multiaddr>()?;
+        let multiaddr2 = "/ip4/127.0.0.2".parse::<Multiaddr>()?;
+
+        db.insert_address(peer_id, multiaddr1.clone()).await?;
+        db.insert_address(peer_id, multiaddr2.clone()).await?;
+
+        let loaded_multiaddr = db.get_addresses(peer_id).await?;
+
+        assert!(loaded_multiaddr.contains(&multiaddr1));
+        assert!(loaded_multiaddr.contains(&multiaddr2));
+        assert_eq!(loaded_multiaddr.len(), 2);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn known_good_addresses_come_first_and_dead_ones_are_pruned() -> Result<()> {
+        let db = setup_test_db().await?;
+
+        let peer_id = PeerId::random();
+        let dead = "/ip4/127.0.0.1/tcp/9939".parse::<Multiaddr>()?;
+        let good = "/ip4/127.0.0.2/tcp/9939".parse::<Multiaddr>()?;
+        let with_peer_id = good.clone().with(Protocol::P2p(peer_id.into()));
+
+        db.insert_address(peer_id, dead.clone()).await?;
+        db.insert_address(peer_id, good.clone()).await?;
+        db.insert_address(peer_id, with_peer_id.clone()).await?;
+        assert_eq!(db.get_addresses(peer_id).await?, vec![
+            dead.clone(),
+            good.clone()
+        ]);
+
+        let now = OffsetDateTime::now_utc();
+        db.record_address_outcome(peer_id, with_peer_id, true, now)
+            .await?;
+        for _ in 0..3 {
+            db.record_address_outcome(peer_id, dead.clone(), false, now)
+                .await?;
+        }
+        assert_eq!(db.get_addresses(peer_id).await?, vec![good.clone(), dead]);
+
+        assert_eq!(db.prune_addresses(now, 4).await?, 0);
+        assert_eq!(
+            db.prune_addresses(now + time::Duration::seconds(1), 3)
+                .await?,
+            1
+        );
+        assert_eq!(db.get_addresses(peer_id).await?, vec![good]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_insert_and_load_peer_id() -> Result<()> {
+        let db = setup_test_db().await?;
+
+        let peer_id = PeerId::random();
+        let multiaddr1 = "/ip4/127.0.0.1".parse::<Multiaddr>()?;
+        let multiaddr2 = "/ip4/127.0.0.2".parse::<Multiaddr>()?;
+
+        db.insert_address(peer_id, multiaddr1.clone()).await?;
+        db.insert_address(peer_id, multiaddr2.clone()).await?;
+
+        let loaded_multiaddr = db.get_addresses(peer_id).await?;
+
+        assert!(loaded_multiaddr.contains(&multiaddr1));
+        assert!(loaded_multiaddr.contains(&multiaddr2));
+        assert_eq!(loaded_multiaddr.len(), 2);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_insert_and_load_generation() -> Result<()> {
+        let db = setup_test_db().await?;
+        let swap_id = Uuid::new_v4();
+
+        assert_eq!(db.get_generation(swap_id).await?, None);
+
+        let exported = Generation {
+            number: 1,
+            exported: true,
+        };
+        db.insert_generation(swap_id, exported).await?;
+        assert_eq!(db.get_generation(swap_id).await?, Some(exported));
+
+        let imported = Generation {
+            number: 2,
+            exported: false,
+        };
+        db.insert_generation(swap_id, imported).await?;
+        assert_eq!(db.get_generation(swap_id).await?, Some(imported));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_insert_and_load_identity() -> Result<()> {
+        let db = setup_test_db().await?;
+        let swap_id = Uuid::new_v4();
+
+        assert_eq!(db.get_identity(swap_id).await?, None);
+
+        db.insert_identity(swap_id, "wide-spread").await?;
+
+        assert_eq!(
+            db.get_identity(swap_id).await?,
+            Some("wide-spread".to_owned())
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_insert_and_load_fiat_value() -> Result<()> {
+        let db = setup_test_db().await?;
+        let swap_id = Uuid::new_v4();
+        let value = FiatValue {
+            currency: Currency::Usd,
+            btc_price: dec!(48000.5),
+            value: dec!(480.01),
+        };
+
+        assert_eq!(db.get_fiat_value(swap_id).await?, None);
+
+        db.insert_fiat_value(swap_id, value).await?;
+
+        assert_eq!(db.get_fiat_value(swap_id).await?, Some(value));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_insert_and_load_quotes() -> Result<()> {
+        let db = setup_test_db().await?;
+        let converted = QuoteRecord {
+            identity: "default".to_owned(),
+            peer_id: PeerId::random(),
+            issued_at: OffsetDateTime::now_utc(),
+            price: Price::new(bitcoin::Amount::from_sat(250_000)),
+            outcome: Outcome::Converted,
+            reason: None,
+            swap_id: Some(Uuid::new_v4()),
+        };
+        let rejected = QuoteRecord {
+            outcome: Outcome::AmountRejected,
+            reason: Some("Amount 0.1 BTC above maximum 0.05 BTC".to_owned()),
+            swap_id: None,
+            ..converted.clone()
+        };
+
+        db.insert_quote(converted.clone()).await?;
+        db.insert_quote(rejected.clone()).await?;
+
+        assert_eq!(db.get_quotes().await?, vec![converted, rejected]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_insert_and_load_signed_quotes() -> Result<()> {
+        let db = setup_test_db().await?;
+        let swap_id = Uuid::new_v4();
+        let identity = identity::Keypair::generate_ed25519();
+        let quote = SignedQuote::sign(
+            Quote {
+                price: Price::new(bitcoin::Amount::from_sat(250_000)),
+                min_quantity: bitcoin::Amount::ZERO,
+                max_quantity: bitcoin::Amount::ONE_BTC,
+            },
+            QuoteTerms::new(&env::Testnet::get_config(), OffsetDateTime::now_utc()),
+            &identity,
+            &MakerKey::new(ed25519::Keypair::generate()),
+        )?;
+
+        db.insert_signed_quote(swap_id, PeerId::from(identity.public()), &quote)
+            .await?;
+
+        assert_eq!(db.get_signed_quotes(swap_id).await?, vec![quote]);
+        assert!(db.get_signed_quotes(Uuid::new_v4()).await?.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_first_wind_down_reason_is_kept() -> Result<()> {
+        let db = setup_test_db().await?;
+        let swap_id = Uuid::new_v4();
+
+        db.insert_wind_down(swap_id, "first").await?;
+        db.insert_wind_down(swap_id, "second").await?;
+
+        assert_eq!(db.get_wind_down(swap_id).await?.as_deref(), Some("first"));
+        assert!(db.get_wind_down(Uuid::new_v4()).await?.is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_errors_are_kept_in_order() -> Result<()> {
+        let db = setup_test_db().await?;
+        let swap_id = Uuid::new_v4();
+
+        db.insert_error(swap_id, "first").await?;
+        db.insert_error(swap_id, "second").await?;
+
+        let errors = db
+            .get_errors(swap_id)
+            .await?
+            .into_iter()
+            .map(|(_, error)| error)
+            .collect::<Vec<_>>();
+        assert_eq!(errors, vec!["first", "second"]);
+        assert!(db.get_errors(Uuid::new_v4()).await?.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn given_finished_swap_then_prune_keeps_only_latest_state() -> Result<()> {
+        let db = setup_test_db().await?;
+
+        let finished_swap = Uuid::new_v4();
+        let unfinished_swap = Uuid::new_v4();
+
+        db.insert_latest_state(finished_swap, State::Alice(AliceState::SafelyAborted))
+            .await?;
+        db.insert_latest_state(finished_swap, State::Alice(AliceState::BtcRedeemed))
+            .await?;
+        let started = State::Bob(BobState::Started {
+            btc_amount: bitcoin::Amount::from_sat(100_000),
+            change_address: "tb1qyccwk4yun26708qg5h6g6we8kxln232wclxf5a".parse()?,
+            xmr_amount: None,
+        });
+        for _ in 0..3 {
+            db.insert_latest_state(unfinished_swap, started.clone())
+                .await?;
+        }
+
+        let pruned = db
+            .prune(OffsetDateTime::now_utc() + time::Duration::minutes(1))
+            .await?;
+
+        assert_eq!(pruned, 1);
+        assert_eq!(count_states(&db, finished_swap).await?, 1);
+        assert_eq!(count_states(&db, unfinished_swap).await?, 3);
+        assert_eq!(
+            db.get_state(finished_swap).await?,
+            State::Alice(AliceState::BtcRedeemed)
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn given_recently_finished_swap_then_prune_keeps_all_states() -> Result<()> {
+        let db = setup_test_db().await?;
+
+        let swap_id = Uuid::new_v4();
+        db.insert_latest_state(swap_id, State::Alice(AliceState::SafelyAborted))
+            .await?;
+        db.insert_latest_state(swap_id, State::Alice(AliceState::BtcRedeemed))
+            .await?;
+
+        let pruned = db
+            .prune(OffsetDateTime::now_utc() - time::Duration::days(30))
+            .await?;
+
+        assert_eq!(pruned, 0);
+        assert_eq!(count_states(&db, swap_id).await?, 2);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn outbox_keeps_the_first_message_of_a_kind_until_removed() -> Result<()> {
+        let db = setup_test_db().await?;
+        let peer_id = PeerId::random();
+        let first = transfer_proof(Uuid::new_v4());
+        let swap_id = first.swap_id();
+
+        db.insert_outbound(peer_id, first.clone()).await?;
+        let stored = db.insert_outbound(peer_id, transfer_proof(swap_id)).await?;
+
+        assert_eq!(stored.nonce(), first.nonce());
+        assert_eq!(db.get_outbound(peer_id).await?.len(), 1);
+        assert!(db.get_outbound(PeerId::random()).await?.is_empty());
+
+        db.remove_outbound(swap_id, MessageKind::TransferProof)
+            .await?;
+
+        assert!(db.get_outbound(peer_id).await?.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn received_messages_are_recognized() -> Result<()> {
+        let db = setup_test_db().await?;
+        let swap_id = Uuid::new_v4();
+        let nonce = Uuid::new_v4();
+
+        db.insert_received(swap_id, MessageKind::EncryptedSignature, nonce)
+            .await?;
+        db.insert_received(swap_id, MessageKind::EncryptedSignature, nonce)
+            .await?;
+
+        assert!(
+            db.has_received(swap_id, MessageKind::EncryptedSignature, nonce)
+                .await?
+        );
+        assert!(
+            !db.has_received(swap_id, MessageKind::TransferProof, nonce)
+                .await?
+        );
+        assert!(
+            !db.has_received(swap_id, MessageKind::EncryptedSignature, Uuid::new_v4())
+                .await?
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn given_filters_then_only_matching_swaps_are_listed() -> Result<()> {
+        let db = setup_test_db().await?;
+
+        let aborted = Uuid::new_v4();
+        let open = Uuid::new_v4();
+        let redeemed = Uuid::new_v4();
+        let peer_id = PeerId::random();
+        let started = |sats| {
+            Ok::<_, anyhow::Error>(State::Bob(BobState::Started {
+                btc_amount: bitcoin::Amount::from_sat(sats),
+                change_address: "tb1qyccwk4yun26708qg5h6g6we8kxln232wclxf5a".parse()?,
+                xmr_amount: None,
+            }))
+        };
+
+        db.insert_latest_state(aborted, started(100_000)?).await?;
+        db.insert_latest_state(aborted, State::Bob(BobState::SafelyAborted))
+            .await?;
+        db.insert_latest_state(open, started(500_000)?).await?;
+        db.insert_peer_id(open, peer_id).await?;
+        db.insert_latest_state(redeemed, State::Alice(AliceState::BtcRedeemed))
+            .await?;
+
+        let db = &db;
+        let list = |filter: SwapFilter| async move {
+            let swaps = db.list_swaps(&filter, &SwapPage::default()).await?;
+            Ok::<_, anyhow::Error>(swaps.iter().map(|swap| swap.swap_id).collect::<Vec<_>>())
+        };
+
+        assert_eq!(
+            list(SwapFilter {
+                status: Some(SwapStatus::Open),
+                ..Default::default()
+            })
+            .await?,
+            vec![open]
+        );
+        assert_eq!(
+            list(SwapFilter {
+                min_btc: Some(bitcoin::Amount::from_sat(200_000)),
+                ..Default::default()
+            })
+            .await?,
+            vec![open]
+        );
+        assert_eq!(
+            list(SwapFilter {
+                max_btc: Some(bitcoin::Amount::from_sat(200_000)),
+                ..Default::default()
+            })
+            .await?,
+            vec![aborted]
+        );
+        assert_eq!(
+            list(SwapFilter {
+                peer_id: Some(peer_id),
+                ..Default::default()
+            })
+            .await?,
+            vec![open]
+        );
+        assert_eq!(
+            list(SwapFilter {
+                role: Some(Role::Alice),
+                ..Default::default()
+            })
+            .await?,
+            vec![redeemed]
+        );
+        assert_eq!(
+            list(SwapFilter {
+                state: Some("safely aborted".to_owned()),
+                ..Default::default()
+            })
+            .await?,
+            vec![aborted]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn given_swap_then_its_summary_is_loaded() -> Result<()> {
+        let db = setup_test_db().await?;
+        let swap_id = Uuid::new_v4();
+        let peer_id = PeerId::random();
+
+        db.insert_latest_state(swap_id, State::Alice(AliceState::BtcRedeemed))
+            .await?;
+        db.insert_peer_id(swap_id, peer_id).await?;
+        let summary = db.get_summary(swap_id).await?;
+
+        assert_eq!(summary.role, Role::Alice);
+        assert_eq!(summary.state, "btc is redeemed");
+        assert!(summary.finished);
+        assert_eq!(summary.peer_id, Some(peer_id));
+        assert!(db.get_summary(Uuid::new_v4()).await.is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn given_pages_then_every_swap_is_listed_once() -> Result<()> {
+        let db = setup_test_db().await?;
+
+        for _ in 0..5 {
+            db.insert_latest_state(Uuid::new_v4(), State::Alice(AliceState::BtcRedeemed))
+                .await?;
+        }
+
+        for order in [SwapOrder::OldestFirst, SwapOrder::NewestFirst] {
+            let mut listed = Vec::new();
+            let mut page = SwapPage {
+                limit: Some(2),
+                after: None,
+                order,
+            };
+
+            loop {
+                let swaps = db.list_swaps(&SwapFilter::default(), &page).await?;
+                listed.extend(swaps.iter().map(|swap| swap.swap_id));
+
+                match page.next(&swaps) {
+                    Some(after) => page.after = Some(after),
+                    None => break,
+                }
+            }
+
+            let all = db
+                .list_swaps(&SwapFilter::default(), &SwapPage {
+                    order,
+                    ..Default::default()
+                })
+                .await?;
+            assert_eq!(
+                listed,
+                all.iter().map(|swap| swap.swap_id).collect::<Vec<_>>()
+            );
+            assert_eq!(listed.len(), 5);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn given_swaps_without_summary_then_they_are_summarized_on_open() -> Result<()> {
+        let temp_db = tempdir()?.into_path().join("tempdb");
+        File::create(&temp_db)?;
+        let db = SqliteDatabase::open(&temp_db).await?;
+
+        let swap_id = Uuid::new_v4();
+        db.insert_latest_state(swap_id, State::Alice(AliceState::BtcRedeemed))
+            .await?;
+        sqlx::query("DELETE FROM swap_summaries")
+            .execute(&db.pool)
+            .await?;
+        drop(db);
+
+        let db = SqliteDatabase::open(&temp_db).await?;
+        let swaps = db
+            .list_swaps(&SwapFilter::default(), &SwapPage::default())
+            .await?;
+
+        assert_eq!(swaps.len(), 1);
+        assert_eq!(swaps[0].swap_id, swap_id);
+        assert!(swaps[0].finished);
+
+        Ok(())
+    }
+
+    fn transfer_proof(swap_id: Uuid) -> outbox::Message {
+        outbox::Message::from(transfer_proof::Request {
+            swap_id,
+            tx_lock_proof: monero::TransferProof::new(
+                monero::TxHash("abcd".to_owned()),
+                monero::PrivateKey {
+                    scalar: monero::Scalar::random(&mut rand::thread_rng()),
+                },
+            ),
+            nonce: Some(Uuid::new_v4()),
+        })
+    }
+
+    #[test]
+    fn entered_at_can_be_parsed() {
+        let now = OffsetDateTime::now_utc();
+
+        assert_eq!(parse_entered_at(&now.to_string()).unwrap(), now);
+    }
+
+    async fn count_states(db: &SqliteDatabase, swap_id: Uuid) -> Result<i64> {
+        let count = sqlx::query_scalar("SELECT COUNT(*) FROM swap_states WHERE swap_id = ?")
+            .bind(swap_id.to_string())
+            .fetch_one(&db.pool)
+            .await?;
+
+        Ok(count)
+    }
+
     async fn setup_test_db() -> Result<SqliteDatabase> {
         let temp_db = tempdir().unwrap().into_path().join("tempdb");
 
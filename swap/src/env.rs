@@ -18,6 +18,9 @@ pub struct Config {
     pub monero_finality_confirmations: u64,
     #[serde(with = "monero_network")]
     pub monero_network: monero::Network,
+    /// How long a swap may run before it stops waiting for the counterparty,
+    /// see [`crate::protocol::deadline`].
+    pub max_swap_duration: Option<Duration>,
 }
 
 impl Config {
@@ -28,6 +31,18 @@ impl Config {
     pub fn monero_sync_interval(&self) -> Duration {
         sync_interval(self.monero_avg_block_time)
     }
+
+    /// How long the outputs of a Monero transaction take to unlock.
+    pub fn monero_unlock_delay(&self) -> Duration {
+        self.monero_avg_block_time * crate::monero::UNLOCK_CONFIRMATIONS
+    }
+
+    pub fn with_max_swap_duration_hours(self, hours: Option<u64>) -> Self {
+        Self {
+            max_swap_duration: hours.map(|hours| Duration::from_secs(hours * 60 * 60)),
+            ..self
+        }
+    }
 }
 
 pub trait GetConfig {
@@ -56,6 +71,7 @@ impl GetConfig for Mainnet {
             monero_avg_block_time: 2.std_minutes(),
             monero_finality_confirmations: 10,
             monero_network: monero::Network::Mainnet,
+            max_swap_duration: None,
         }
     }
 }
@@ -73,6 +89,7 @@ impl GetConfig for Testnet {
             monero_avg_block_time: 2.std_minutes(),
             monero_finality_confirmations: 10,
             monero_network: monero::Network::Stagenet,
+            max_swap_duration: None,
         }
     }
 }
@@ -90,6 +107,7 @@ impl GetConfig for Regtest {
             monero_avg_block_time: 1.std_seconds(),
             monero_finality_confirmations: 10,
             monero_network: monero::Network::Mainnet, // yes this is strange
+            max_swap_duration: None,
         }
     }
 }
@@ -138,7 +156,7 @@ mod monero_network {
             Network::Stagenet => "stagenet",
             Network::Testnet => "testnet",
         };
-        s.serialize_str(&str)
+        s.serialize_str(str)
     }
 }
 
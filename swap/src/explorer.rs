@@ -0,0 +1,129 @@
+//! Links to block explorers for the transactions of a swap.
+//!
+//! By default mempool.space is used for Bitcoin and xmrchain.net for Monero,
+//! both can be replaced with a self-hosted explorer that serves transactions
+//! under `<url>/tx/<id>`. There is no default explorer for regtest.
+
+use crate::bitcoin::Txid;
+use crate::monero::TxHash;
+use crate::{bitcoin, monero};
+use url::Url;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Explorer {
+    bitcoin: Option<Url>,
+    monero: Option<Url>,
+}
+
+impl Explorer {
+    pub fn new(bitcoin_network: bitcoin::Network, monero_network: monero::Network) -> Self {
+        let bitcoin = match bitcoin_network {
+            bitcoin::Network::Bitcoin => Some("https://mempool.space/"),
+            bitcoin::Network::Testnet => Some("https://mempool.space/testnet/"),
+            bitcoin::Network::Signet => Some("https://mempool.space/signet/"),
+            _ => None,
+        };
+        // Regtest runs on the Monero mainnet network
+        let monero = match monero_network {
+            _ if bitcoin_network == bitcoin::Network::Regtest => None,
+            monero::Network::Mainnet => Some("https://xmrchain.net/"),
+            monero::Network::Stagenet => Some("https://stagenet.xmrchain.net/"),
+            monero::Network::Testnet => Some("https://testnet.xmrchain.net/"),
+        };
+
+        Self {
+            bitcoin: bitcoin.map(|url| Url::parse(url).expect("static url to be valid")),
+            monero: monero.map(|url| Url::parse(url).expect("static url to be valid")),
+        }
+    }
+
+    /// Replaces the default Bitcoin explorer if `url` is set.
+    pub fn with_bitcoin(self, url: Option<Url>) -> Self {
+        Self {
+            bitcoin: url.map(directory).or(self.bitcoin),
+            ..self
+        }
+    }
+
+    /// Replaces the default Monero explorer if `url` is set.
+    pub fn with_monero(self, url: Option<Url>) -> Self {
+        Self {
+            monero: url.map(directory).or(self.monero),
+            ..self
+        }
+    }
+
+    pub fn bitcoin_tx(&self, txid: Txid) -> Option<Url> {
+        tx_url(self.bitcoin.as_ref()?, &txid.to_string())
+    }
+
+    pub fn monero_tx(&self, tx_hash: &TxHash) -> Option<Url> {
+        tx_url(self.monero.as_ref()?, &tx_hash.0)
+    }
+}
+
+/// Makes sure relative paths are joined onto the whole path of `url`, e.g.
+/// `https://example.com/explorer` becomes `https://example.com/explorer/`.
+fn directory(mut url: Url) -> Url {
+    if !url.path().ends_with('/') {
+        let path = format!("{}/", url.path());
+        url.set_path(&path);
+    }
+
+    url
+}
+
+fn tx_url(base: &Url, id: &str) -> Option<Url> {
+    base.join(&format!("tx/{}", id)).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    const TXID: &str = "5d11bd5e70a97e9e4bfd5a6d1ee6bcc0fa2a6a7ca8ee738e038fd5cf6823465d";
+
+    #[test]
+    fn links_depend_on_network() {
+        let txid = Txid::from_str(TXID).unwrap();
+        let tx_hash = TxHash(TXID.to_owned());
+
+        let mainnet = Explorer::new(bitcoin::Network::Bitcoin, monero::Network::Mainnet);
+        let testnet = Explorer::new(bitcoin::Network::Testnet, monero::Network::Stagenet);
+
+        assert_eq!(
+            mainnet.bitcoin_tx(txid).unwrap().as_str(),
+            format!("https://mempool.space/tx/{}", TXID)
+        );
+        assert_eq!(
+            mainnet.monero_tx(&tx_hash).unwrap().as_str(),
+            format!("https://xmrchain.net/tx/{}", TXID)
+        );
+        assert_eq!(
+            testnet.bitcoin_tx(txid).unwrap().as_str(),
+            format!("https://mempool.space/testnet/tx/{}", TXID)
+        );
+        assert_eq!(
+            testnet.monero_tx(&tx_hash).unwrap().as_str(),
+            format!("https://stagenet.xmrchain.net/tx/{}", TXID)
+        );
+    }
+
+    #[test]
+    fn regtest_has_no_explorer_unless_configured() {
+        let txid = Txid::from_str(TXID).unwrap();
+        let explorer = Explorer::new(bitcoin::Network::Regtest, monero::Network::Mainnet);
+
+        assert_eq!(explorer.bitcoin_tx(txid), None);
+        assert_eq!(explorer.monero_tx(&TxHash(TXID.to_owned())), None);
+
+        let explorer =
+            explorer.with_bitcoin(Some(Url::parse("http://localhost:8080/explorer").unwrap()));
+
+        assert_eq!(
+            explorer.bitcoin_tx(txid).unwrap().as_str(),
+            format!("http://localhost:8080/explorer/tx/{}", TXID)
+        );
+    }
+}
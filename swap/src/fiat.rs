@@ -0,0 +1,170 @@
+//! Approximate fiat value of swapped amounts.
+//!
+//! The price of Bitcoin is fetched from the public Kraken REST API once, when
+//! a swap is set up, and stored together with the swap. The estimate is only
+//! used for display and reporting, it never influences the swap itself.
+
+use crate::bitcoin;
+use anyhow::{bail, Context, Result};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt;
+use url::Url;
+
+pub const DEFAULT_PRICE_URL: &str = "https://api.kraken.com";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::Display, strum::EnumString)]
+#[strum(serialize_all = "UPPERCASE", ascii_case_insensitive)]
+pub enum Currency {
+    Usd,
+    Eur,
+}
+
+impl Currency {
+    fn kraken_pair(&self) -> &'static str {
+        match self {
+            Currency::Usd => "XBTUSD",
+            Currency::Eur => "XBTEUR",
+        }
+    }
+}
+
+/// The price of one BTC in a fiat currency.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FiatRate {
+    pub currency: Currency,
+    pub btc_price: Decimal,
+}
+
+impl FiatRate {
+    /// The value of `amount` rounded to two decimal places.
+    pub fn value_of(&self, amount: bitcoin::Amount) -> FiatValue {
+        let amount_in_btc =
+            Decimal::from(amount.as_sat()) / Decimal::from(bitcoin::Amount::ONE_BTC.as_sat());
+
+        FiatValue {
+            currency: self.currency,
+            btc_price: self.btc_price,
+            value: (amount_in_btc * self.btc_price).round_dp(2),
+        }
+    }
+}
+
+/// The fiat value of a swap at the time it was set up.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FiatValue {
+    pub currency: Currency,
+    pub btc_price: Decimal,
+    pub value: Decimal,
+}
+
+impl fmt::Display for FiatValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "~{} {}", self.value, self.currency)
+    }
+}
+
+/// Fetches the last traded price of Bitcoin in `currency`.
+pub async fn fetch_rate(price_url: &Url, currency: Currency) -> Result<FiatRate> {
+    Ok(FiatRate {
+        currency,
+        btc_price: fetch_last_trade(price_url, currency.kraken_pair(), "Bitcoin").await?,
+    })
+}
+
+/// Fetches the price of the last trade of the Kraken `pair`, `asset` names
+/// what is traded in error messages.
+pub async fn fetch_last_trade(price_url: &Url, pair: &str, asset: &str) -> Result<Decimal> {
+    let mut url = price_url.join("0/public/Ticker")?;
+    url.query_pairs_mut().append_pair("pair", pair);
+
+    let body = reqwest::get(url)
+        .await
+        .with_context(|| format!("Failed to request {} price", asset))?
+        .text()
+        .await?;
+
+    parse_ticker(&body, asset)
+}
+
+#[derive(Debug, Deserialize)]
+struct TickerResponse {
+    error: Vec<String>,
+    #[serde(default)]
+    result: HashMap<String, Ticker>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Ticker {
+    /// The price and volume of the last trade.
+    #[serde(rename = "c")]
+    last_trade: Vec<String>,
+}
+
+fn parse_ticker(body: &str, asset: &str) -> Result<Decimal> {
+    let response =
+        serde_json::from_str::<TickerResponse>(body).context("Unexpected ticker response")?;
+
+    if !response.error.is_empty() {
+        bail!(
+            "Failed to fetch {} price: {}",
+            asset,
+            response.error.join(", ")
+        )
+    }
+
+    let price = response
+        .result
+        .values()
+        .next()
+        .and_then(|ticker| ticker.last_trade.first())
+        .context("Ticker response does not contain a price")?;
+
+    price
+        .parse()
+        .with_context(|| format!("Failed to parse {} price {}", asset, price))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn parses_last_trade_price_of_ticker() {
+        let body = r#"{"error":[],"result":{"XXBTZUSD":{"a":["48001.10000","1","1.000"],"b":["48000.00000","2","2.000"],"c":["48000.50000","0.00100000"],"v":["1616.78520777","3716.48111204"]}}}"#;
+
+        assert_eq!(parse_ticker(body, "Bitcoin").unwrap(), dec!(48000.5));
+    }
+
+    #[test]
+    fn ticker_error_is_reported() {
+        let body = r#"{"error":["EQuery:Unknown asset pair"]}"#;
+
+        assert_eq!(
+            parse_ticker(body, "Bitcoin").unwrap_err().to_string(),
+            "Failed to fetch Bitcoin price: EQuery:Unknown asset pair"
+        );
+    }
+
+    #[test]
+    fn value_is_rounded_to_cents() {
+        let rate = FiatRate {
+            currency: Currency::Eur,
+            btc_price: dec!(40123.45),
+        };
+
+        let value = rate.value_of(bitcoin::Amount::from_sat(123_456));
+
+        assert_eq!(value.value, dec!(49.53));
+        assert_eq!(value.to_string(), "~49.53 EUR");
+    }
+
+    #[test]
+    fn currency_is_parsed_case_insensitive() {
+        assert_eq!("usd".parse::<Currency>().unwrap(), Currency::Usd);
+        assert_eq!("EUR".parse::<Currency>().unwrap(), Currency::Eur);
+        assert!("GBP".parse::<Currency>().is_err());
+    }
+}
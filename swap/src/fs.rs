@@ -1,24 +1,10 @@
 use anyhow::{Context, Result};
-use directories_next::ProjectDirs;
+use fs2::FileExt;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
 
-/// This is the default location for the overall config-dir specific by system
-// Linux: /home/<user>/.config/xmr-btc-swap/
-// OSX: /Users/<user>/Library/Preferences/xmr-btc-swap/
-pub fn system_config_dir() -> Result<PathBuf> {
-    ProjectDirs::from("", "", "xmr-btc-swap")
-        .map(|proj_dirs| proj_dirs.config_dir().to_path_buf())
-        .context("Could not generate default system configuration dir path")
-}
-
-/// This is the default location for the overall data-dir specific by system
-// Linux: /home/<user>/.local/share/xmr-btc-swap/
-// OSX: /Users/<user>/Library/ApplicationSupport/xmr-btc-swap/
-pub fn system_data_dir() -> Result<PathBuf> {
-    ProjectDirs::from("", "", "xmr-btc-swap")
-        .map(|proj_dirs| proj_dirs.data_dir().to_path_buf())
-        .context("Could not generate default system data-dir dir path")
-}
+const LOCK_FILE_NAME: &str = "lock";
 
 pub fn ensure_directory_exists(file: &Path) -> Result<(), std::io::Error> {
     if let Some(path) = file.parent() {
@@ -32,3 +18,82 @@ pub fn ensure_directory_exists(file: &Path) -> Result<(), std::io::Error> {
     }
     Ok(())
 }
+
+#[derive(Debug, thiserror::Error)]
+#[error("Data directory {} is in use by another process (PID {pid}), stop it before running this command", .data_dir.display())]
+pub struct DataDirLocked {
+    pub data_dir: PathBuf,
+    pub pid: String,
+}
+
+/// An exclusive lock on a data directory that is released when dropped.
+#[derive(Debug)]
+pub struct DataDirLock {
+    _file: File,
+}
+
+/// Locks the data directory for as long as the returned guard is alive.
+///
+/// The lock is advisory and prevents two processes from acting on the same
+/// database at the same time. Fails with [`DataDirLocked`] if another process
+/// holds it. The lock is released by the operating system if the process dies.
+pub fn lock_data_dir(data_dir: &Path) -> Result<DataDirLock> {
+    std::fs::create_dir_all(data_dir)
+        .with_context(|| format!("Failed to create data directory {}", data_dir.display()))?;
+
+    let path = data_dir.join(LOCK_FILE_NAME);
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open lock file {}", path.display()))?;
+
+    if file.try_lock_exclusive().is_err() {
+        // On Windows the lock also prevents other processes from reading the file
+        let mut pid = String::new();
+        let pid = match file.read_to_string(&mut pid) {
+            Ok(_) if !pid.trim().is_empty() => pid.trim().to_owned(),
+            _ => "unknown".to_owned(),
+        };
+
+        return Err(DataDirLocked {
+            data_dir: data_dir.to_path_buf(),
+            pid,
+        }
+        .into());
+    }
+
+    file.set_len(0)?;
+    file.seek(SeekFrom::Start(0))?;
+    write!(file, "{}", std::process::id())?;
+    file.flush()?;
+
+    Ok(DataDirLock { _file: file })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn given_locked_data_dir_then_second_lock_fails_with_pid() {
+        let dir = tempdir().unwrap();
+
+        let _lock = lock_data_dir(dir.path()).unwrap();
+        let error = lock_data_dir(dir.path()).unwrap_err();
+
+        let locked = error.downcast_ref::<DataDirLocked>().unwrap();
+        assert_eq!(locked.pid, std::process::id().to_string());
+    }
+
+    #[test]
+    fn given_lock_was_dropped_then_data_dir_can_be_locked_again() {
+        let dir = tempdir().unwrap();
+
+        drop(lock_data_dir(dir.path()).unwrap());
+
+        assert!(lock_data_dir(dir.path()).is_ok());
+    }
+}
@@ -1,9 +1,11 @@
+use crate::amounts::Price;
+use crate::bitcoin;
 use anyhow::{anyhow, Context, Result};
 use futures::{SinkExt, StreamExt, TryStreamExt};
 use serde::Deserialize;
 use std::convert::{Infallible, TryFrom};
-use std::sync::Arc;
-use std::time::Duration;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tokio::sync::watch;
 use url::Url;
 
@@ -17,6 +19,13 @@ use url::Url;
 pub fn connect(price_ticker_ws_url: Url) -> Result<PriceUpdates> {
     let (price_update, price_update_receiver) = watch::channel(Err(Error::NotYetAvailable));
     let price_update = Arc::new(price_update);
+    let last_message = LastMessage::default();
+    let last_update = LastMessage::default();
+    let price_updates = PriceUpdates {
+        inner: price_update_receiver,
+        last_message: last_message.clone(),
+        last_update: last_update.clone(),
+    };
 
     tokio::spawn(async move {
         // The default backoff config is fine for us apart from one thing:
@@ -32,10 +41,13 @@ pub fn connect(price_ticker_ws_url: Url) -> Result<PriceUpdates> {
             || {
                 let price_update = price_update.clone();
                 let price_ticker_ws_url = price_ticker_ws_url.clone();
+                let last_message = last_message.clone();
+                let last_update = last_update.clone();
                 async move {
-                    let mut stream = connection::new(price_ticker_ws_url).await?;
+                    let mut stream = connection::new(price_ticker_ws_url, last_message).await?;
 
                     while let Some(update) = stream.try_next().await.map_err(to_backoff)? {
+                        last_update.received();
                         let send_result = price_update.send(Ok(update));
 
                         if send_result.is_err() {
@@ -69,14 +81,14 @@ pub fn connect(price_ticker_ws_url: Url) -> Result<PriceUpdates> {
         }
     });
 
-    Ok(PriceUpdates {
-        inner: price_update_receiver,
-    })
+    Ok(price_updates)
 }
 
 #[derive(Clone, Debug)]
 pub struct PriceUpdates {
     inner: watch::Receiver<PriceUpdate>,
+    last_message: LastMessage,
+    last_update: LastMessage,
 }
 
 impl PriceUpdates {
@@ -89,9 +101,51 @@ impl PriceUpdates {
     pub fn latest_update(&mut self) -> PriceUpdate {
         self.inner.borrow().clone()
     }
+
+    /// How long ago the price ticker sent its last message, `None` if it never
+    /// sent one.
+    ///
+    /// Heartbeats count as well, the latest update stays available while the
+    /// connection is re-established but cannot be trusted for long.
+    pub fn last_message_age(&self) -> Option<Duration> {
+        self.last_message.age()
+    }
+
+    /// How long ago the latest update was received, `None` if there was none
+    /// yet.
+    ///
+    /// Kraken only sends an update when the ticker changed, an old update is
+    /// not necessarily outdated while heartbeats keep arriving.
+    pub fn last_update_age(&self) -> Option<Duration> {
+        self.last_update.age()
+    }
+}
+
+/// When the price ticker last sent a message, see
+/// [`PriceUpdates::last_message_age`] and [`PriceUpdates::last_update_age`].
+#[derive(Clone, Debug, Default)]
+struct LastMessage {
+    received_at: Arc<Mutex<Option<Instant>>>,
+}
+
+impl LastMessage {
+    fn received(&self) {
+        *self
+            .received_at
+            .lock()
+            .expect("last message lock not poisoned") = Some(Instant::now());
+    }
+
+    fn age(&self) -> Option<Duration> {
+        self.received_at
+            .lock()
+            .expect("last message lock not poisoned")
+            .map(|received_at| received_at.elapsed())
+    }
 }
 
 #[derive(Clone, Debug, thiserror::Error)]
+#[non_exhaustive]
 pub enum Error {
     #[error("Rate is not yet available")]
     NotYetAvailable,
@@ -129,7 +183,10 @@ mod connection {
     use futures::stream::BoxStream;
     use tokio_tungstenite::tungstenite;
 
-    pub async fn new(ws_url: Url) -> Result<BoxStream<'static, Result<wire::PriceUpdate, Error>>> {
+    pub(super) async fn new(
+        ws_url: Url,
+        last_message: LastMessage,
+    ) -> Result<BoxStream<'static, Result<wire::PriceUpdate, Error>>> {
         let (mut rate_stream, _) = tokio_tungstenite::connect_async(ws_url)
             .await
             .context("Failed to connect to Kraken websocket API")?;
@@ -138,7 +195,11 @@ mod connection {
             .send(SUBSCRIBE_XMR_BTC_TICKER_PAYLOAD.into())
             .await?;
 
-        let stream = rate_stream.err_into().try_filter_map(parse_message).boxed();
+        let stream = rate_stream
+            .err_into()
+            .inspect_ok(move |_| last_message.received())
+            .try_filter_map(parse_message)
+            .boxed();
 
         Ok(stream)
     }
@@ -227,7 +288,7 @@ mod connection {
 /// Responsible for parsing websocket text messages to events and rate updates.
 mod wire {
     use super::*;
-    use bitcoin::util::amount::ParseAmountError;
+    use ::bitcoin::util::amount::ParseAmountError;
     use serde_json::Value;
 
     #[derive(Debug, Deserialize, PartialEq)]
@@ -257,7 +318,7 @@ mod wire {
     #[derive(Clone, Debug, Deserialize)]
     #[serde(try_from = "TickerUpdate")]
     pub struct PriceUpdate {
-        pub ask: bitcoin::Amount,
+        pub ask: Price,
     }
 
     #[derive(Debug, Deserialize)]
@@ -300,9 +361,10 @@ mod wire {
                 .ok_or(Error::DataFieldMissing)?;
             let ask = data.ask.first().ok_or(Error::MissingAskRateElementType)?;
             let ask = match ask {
-                RateElement::Text(ask) => {
-                    bitcoin::Amount::from_str_in(ask, ::bitcoin::Denomination::Bitcoin)?
-                }
+                RateElement::Text(ask) => Price::new(bitcoin::Amount::from_str_in(
+                    ask,
+                    ::bitcoin::Denomination::Bitcoin,
+                )?),
                 _ => return Err(Error::UnexpectedAskRateElementType),
             };
 
@@ -16,20 +16,31 @@
     missing_copy_implementations
 )]
 
+pub mod amounts;
 pub mod asb;
 pub mod bitcoin;
 pub mod cli;
+pub mod data;
 pub mod database;
 pub mod env;
+pub mod explorer;
+pub mod fiat;
 pub mod fs;
 pub mod kraken;
 pub mod libp2p_ext;
 pub mod monero;
 pub mod network;
+pub mod otlp;
+pub mod progress;
 pub mod protocol;
+pub mod reservations;
+pub mod secret;
 pub mod seed;
+pub mod sync_check;
 pub mod tor;
 pub mod tracing_ext;
+pub mod watchtower;
+pub mod version;
 
 mod monero_ext;
 
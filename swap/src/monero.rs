@@ -1,22 +1,28 @@
+mod light_wallet;
 pub mod wallet;
 mod wallet_rpc;
 
 pub use ::monero::network::Network;
 pub use ::monero::{Address, PrivateKey, PublicKey};
 pub use curve25519_dalek::scalar::Scalar;
-pub use wallet::Wallet;
+pub use light_wallet::LightWallet;
+pub use wallet::{MoneroWallet, Wallet, WalletExists};
 pub use wallet_rpc::{WalletRpc, WalletRpcProcess};
 
+use crate::amounts::{self, Xmr};
 use crate::bitcoin;
-use anyhow::Result;
+use crate::secret::Wipe;
+use ::monero::cryptonote::hash::keccak_256;
+use anyhow::{bail, Context, Result};
 use rand::{CryptoRng, RngCore};
 use rust_decimal::prelude::*;
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use std::convert::TryFrom;
 use std::fmt;
-use std::ops::{Add, Mul, Sub};
+use std::ops::{Add, Mul};
 use std::str::FromStr;
+use zeroize::Zeroize;
 
 pub const PICONERO_OFFSET: u64 = 1_000_000_000_000;
 
@@ -39,9 +45,72 @@ pub fn private_key_from_secp256k1_scalar(scalar: bitcoin::Scalar) -> PrivateKey
     PrivateKey::from_scalar(Scalar::from_bytes_mod_order(bytes))
 }
 
-#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+/// Derives the subaddress with the given major (account) and minor index of
+/// the wallet identified by `private_view_key` and `public_spend_key`.
+///
+/// This follows the derivation of `monero-wallet-rpc`, hence index `(0, 0)`
+/// refers to the main (standard) address of the wallet.
+pub fn subaddress(
+    network: Network,
+    private_view_key: PrivateKey,
+    public_spend_key: PublicKey,
+    major: u32,
+    minor: u32,
+) -> Address {
+    if major == 0 && minor == 0 {
+        return Address::standard(
+            network,
+            public_spend_key,
+            PublicKey::from_private_key(&private_view_key),
+        );
+    }
+
+    let mut data = b"SubAddr\0".to_vec();
+    data.extend_from_slice(&private_view_key.scalar.to_bytes());
+    data.extend_from_slice(&major.to_le_bytes());
+    data.extend_from_slice(&minor.to_le_bytes());
+    let m = PrivateKey::from_scalar(Scalar::from_bytes_mod_order(keccak_256(&data)));
+
+    let public_spend = public_spend_key + PublicKey::from_private_key(&m);
+    let public_view = PublicKey {
+        point: (public_spend
+            .point
+            .decompress()
+            .expect("sum of two valid points to be a valid point")
+            * private_view_key.scalar)
+            .compress(),
+    };
+
+    Address::subaddress(network, public_spend, public_view)
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq)]
 pub struct PrivateViewKey(#[serde(with = "monero_private_key")] PrivateKey);
 
+impl fmt::Debug for PrivateViewKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "PrivateViewKey(..)")
+    }
+}
+
+impl Wipe for PrivateViewKey {
+    fn wipe(&mut self) {
+        self.0.wipe();
+    }
+}
+
+impl Wipe for PrivateKey {
+    fn wipe(&mut self) {
+        self.scalar.wipe();
+    }
+}
+
+impl Wipe for Scalar {
+    fn wipe(&mut self) {
+        self.zeroize();
+    }
+}
+
 impl PrivateViewKey {
     pub fn new_random<R: RngCore + CryptoRng>(rng: &mut R) -> Self {
         let scalar = Scalar::random(rng);
@@ -78,34 +147,45 @@ impl From<PublicViewKey> for PublicKey {
 #[derive(Clone, Copy, Debug)]
 pub struct PublicViewKey(PublicKey);
 
-#[derive(Debug, Copy, Clone, Deserialize, Serialize, PartialEq, PartialOrd)]
-pub struct Amount(u64);
+pub type Amount = amounts::Amount<Xmr>;
 
 // Median tx fees on Monero as found here: https://www.monero.how/monero-transaction-fees, XMR 0.000_015 * 2 (to be on the safe side)
 pub const MONERO_FEE: Amount = Amount::from_piconero(30000000);
 
+/// The outputs of a transaction can be spent once it has this many
+/// confirmations.
+pub const UNLOCK_CONFIRMATIONS: u32 = 10;
+
 impl Amount {
-    pub const ZERO: Self = Self(0);
-    pub const ONE_XMR: Self = Self(PICONERO_OFFSET);
+    pub const ONE_XMR: Self = Self::from_piconero(PICONERO_OFFSET);
     /// Create an [Amount] with piconero precision and the given number of
     /// piconeros.
     ///
     /// A piconero (a.k.a atomic unit) is equal to 1e-12 XMR.
     pub const fn from_piconero(amount: u64) -> Self {
-        Amount(amount)
+        Self::from_atomic(amount)
     }
 
     pub fn as_piconero(&self) -> u64 {
-        self.0
+        self.as_atomic()
     }
 
+    /// Rounds to the nearest piconero.
+    ///
+    /// Most decimal amounts have no exact `f64` representation, e.g. `0.3` is
+    /// stored as `0.29999999999999998...`. Truncating those would be off by
+    /// one piconero.
     pub fn from_monero(amount: f64) -> Result<Self> {
         let decimal = Decimal::try_from(amount)?;
-        Self::from_decimal(decimal)
+        Self::from_decimal(decimal.round_dp(12))
     }
 
+    /// Parses an amount of XMR without denomination, e.g. `1.23`.
+    ///
+    /// Amounts that are more precise than one piconero are rejected instead of
+    /// being rounded.
     pub fn parse_monero(amount: &str) -> Result<Self> {
-        let decimal = Decimal::from_str(amount)?;
+        let decimal = Decimal::from_str(amount.trim())?;
         Self::from_decimal(decimal)
     }
 
@@ -116,50 +196,37 @@ impl Amount {
     fn from_decimal(amount: Decimal) -> Result<Self> {
         let piconeros_dec =
             amount.mul(Decimal::from_u64(PICONERO_OFFSET).expect("constant to fit into u64"));
+        if !piconeros_dec.fract().is_zero() {
+            return Err(TooPreciseError(amount.to_string()).into());
+        }
         let piconeros = piconeros_dec
             .to_u64()
             .ok_or_else(|| OverflowError(amount.to_string()))?;
-        Ok(Amount(piconeros))
+        Ok(Self::from_piconero(piconeros))
     }
 }
 
-impl Add for Amount {
-    type Output = Amount;
-
-    fn add(self, rhs: Self) -> Self::Output {
-        Self(self.0 + rhs.0)
-    }
-}
-
-impl Sub for Amount {
-    type Output = Amount;
-
-    fn sub(self, rhs: Self) -> Self::Output {
-        Self(self.0 - rhs.0)
-    }
-}
-
-impl Mul<u64> for Amount {
-    type Output = Amount;
-
-    fn mul(self, rhs: u64) -> Self::Output {
-        Self(self.0 * rhs)
-    }
-}
-
-impl From<Amount> for u64 {
-    fn from(from: Amount) -> u64 {
-        from.0
-    }
-}
-
-impl fmt::Display for Amount {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let mut decimal = Decimal::from(self.0);
-        decimal
-            .set_scale(12)
-            .expect("12 is smaller than max precision of 28");
-        write!(f, "{} XMR", decimal)
+/// Parses an amount as typed by a user, e.g. `1.23 XMR`, `1.23xmr` or
+/// `1230000000000 piconero`. An amount without a denomination is read as XMR.
+impl FromStr for Amount {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let split = s
+            .find(|c: char| c.is_ascii_alphabetic())
+            .unwrap_or_else(|| s.len());
+        let (amount, denomination) = s.split_at(split);
+
+        match denomination.to_lowercase().as_str() {
+            "" | "xmr" => Self::parse_monero(amount),
+            "piconero" => {
+                let piconero = u64::from_str(amount.trim())
+                    .with_context(|| format!("Invalid piconero amount {}", s))?;
+                Ok(Self::from_piconero(piconero))
+            }
+            _ => bail!("Unknown Monero denomination {}", denomination),
+        }
     }
 }
 
@@ -205,10 +272,26 @@ pub struct InsufficientFunds {
     pub actual: Amount,
 }
 
+/// The reasons for rejecting a Monero lock transaction.
+#[derive(Debug, Clone, Copy, thiserror::Error)]
+#[non_exhaustive]
+pub enum InvalidTransfer {
+    #[error("the transaction does not transfer the agreed amount: {0}")]
+    InsufficientFunds(#[from] InsufficientFunds),
+    #[error(
+        "the transaction is locked until {unlock_time}, expected it to be spendable right away"
+    )]
+    UnlockTimeNotZero { unlock_time: u64 },
+}
+
 #[derive(thiserror::Error, Debug, Clone, PartialEq)]
 #[error("Overflow, cannot convert {0} to u64")]
 pub struct OverflowError(pub String);
 
+#[derive(thiserror::Error, Debug, Clone, PartialEq)]
+#[error("{0} XMR is more precise than one piconero")]
+pub struct TooPreciseError(pub String);
+
 pub mod monero_private_key {
     use monero::consensus::{Decodable, Encodable};
     use monero::PrivateKey;
@@ -274,6 +357,29 @@ pub mod monero_private_key {
         };
         Ok(key)
     }
+
+    /// The same format for a private key wrapped in a [`Secret`].
+    pub mod secret {
+        use crate::secret::Secret;
+        use monero::PrivateKey;
+        use serde::{Deserializer, Serializer};
+
+        pub fn serialize<S>(x: &Secret<PrivateKey>, s: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            super::serialize(x, s)
+        }
+
+        pub fn deserialize<'de, D>(
+            deserializer: D,
+        ) -> Result<Secret<PrivateKey>, <D as Deserializer<'de>>::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            super::deserialize(deserializer).map(Secret::new)
+        }
+    }
 }
 
 pub mod monero_amount {
@@ -301,6 +407,70 @@ pub mod monero_amount {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use ::monero::util::address::AddressType;
+    use rand::rngs::OsRng;
+
+    fn random_wallet_keys() -> (PrivateKey, PublicKey) {
+        let private_view_key = PrivateKey::from_scalar(Scalar::random(&mut OsRng));
+        let private_spend_key = PrivateKey::from_scalar(Scalar::random(&mut OsRng));
+
+        (
+            private_view_key,
+            PublicKey::from_private_key(&private_spend_key),
+        )
+    }
+
+    #[test]
+    fn subaddress_at_index_zero_is_main_address() {
+        let (view, spend) = random_wallet_keys();
+
+        let address = subaddress(Network::Stagenet, view, spend, 0, 0);
+
+        assert_eq!(
+            address,
+            Address::standard(Network::Stagenet, spend, PublicKey::from_private_key(&view))
+        );
+    }
+
+    #[test]
+    fn subaddresses_differ_by_major_and_minor_index() {
+        let (view, spend) = random_wallet_keys();
+
+        let addresses = vec![
+            subaddress(Network::Mainnet, view, spend, 0, 1),
+            subaddress(Network::Mainnet, view, spend, 1, 0),
+            subaddress(Network::Mainnet, view, spend, 1, 1),
+            subaddress(Network::Mainnet, view, spend, 0, 2),
+        ];
+
+        for (i, address) in addresses.iter().enumerate() {
+            assert_eq!(address.addr_type, AddressType::SubAddress);
+            assert_eq!(address.network, Network::Mainnet);
+            assert!(addresses[i + 1..].iter().all(|other| other != address));
+        }
+    }
+
+    #[test]
+    fn subaddress_view_key_is_spend_key_times_private_view_key() {
+        let (view, spend) = random_wallet_keys();
+
+        let address = subaddress(Network::Stagenet, view, spend, 3, 7);
+
+        let expected_view = address.public_spend.point.decompress().unwrap() * view.scalar;
+        assert_eq!(address.public_view.point, expected_view.compress());
+    }
+
+    #[test]
+    fn stagenet_subaddress_roundtrips_through_string() {
+        let (view, spend) = random_wallet_keys();
+        let address = subaddress(Network::Stagenet, view, spend, 0, 1);
+
+        let parsed = Address::from_str(&address.to_string()).unwrap();
+
+        assert_eq!(parsed, address);
+        assert_eq!(parsed.network, Network::Stagenet);
+        assert_eq!(parsed.addr_type, AddressType::SubAddress);
+    }
 
     #[test]
     fn display_monero_min() {
@@ -330,7 +500,7 @@ mod tests {
     fn parse_monero_min() {
         let monero_min = "0.000000000001";
         let amount = Amount::parse_monero(monero_min).unwrap();
-        let pics = amount.0;
+        let pics = amount.as_piconero();
         assert_eq!(1, pics);
     }
 
@@ -338,7 +508,7 @@ mod tests {
     fn parse_monero() {
         let monero = "123";
         let amount = Amount::parse_monero(monero).unwrap();
-        let pics = amount.0;
+        let pics = amount.as_piconero();
         assert_eq!(123000000000000, pics);
     }
 
@@ -346,7 +516,7 @@ mod tests {
     fn parse_monero_max() {
         let monero = "18446744.073709551615";
         let amount = Amount::parse_monero(monero).unwrap();
-        let pics = amount.0;
+        let pics = amount.as_piconero();
         assert_eq!(18446744073709551615, pics);
     }
 
@@ -360,7 +530,46 @@ mod tests {
         );
     }
 
-    use rand::rngs::OsRng;
+    #[test]
+    fn parse_monero_too_precise() {
+        let too_precise = "0.0000000000015";
+        let error = Amount::parse_monero(too_precise).unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<TooPreciseError>().unwrap(),
+            &TooPreciseError(too_precise.to_owned())
+        );
+    }
+
+    #[test]
+    fn parse_monero_with_denomination() {
+        let amount = Amount::from_piconero(1_230_000_000_000);
+
+        assert_eq!("1.23".parse::<Amount>().unwrap(), amount);
+        assert_eq!("1.23 XMR".parse::<Amount>().unwrap(), amount);
+        assert_eq!(" 1.23xmr ".parse::<Amount>().unwrap(), amount);
+        assert_eq!("1230000000000 piconero".parse::<Amount>().unwrap(), amount);
+        assert!("1.5 piconero".parse::<Amount>().is_err());
+        assert!("1.23 BTC".parse::<Amount>().is_err());
+    }
+
+    #[test]
+    fn display_roundtrips_through_from_str() {
+        let amount = Amount::from_piconero(18_446_744_073_709_551_615);
+        assert_eq!(amount.to_string().parse::<Amount>().unwrap(), amount);
+    }
+
+    #[test]
+    fn from_monero_rounds_to_nearest_piconero() {
+        assert_eq!(
+            Amount::from_monero(0.3).unwrap(),
+            Amount::from_piconero(300_000_000_000)
+        );
+        assert_eq!(
+            Amount::from_monero(0.000_000_000_001).unwrap(),
+            Amount::from_piconero(1)
+        );
+    }
+
     use serde::{Deserialize, Serialize};
 
     #[derive(Debug, Serialize, Deserialize, PartialEq)]
@@ -0,0 +1,177 @@
+//! A Monero wallet backed by a light wallet server, for takers that do not
+//! run monero-wallet-rpc, e.g. on a phone.
+//!
+//! The server is handed the view key of the lock address, which Alice and
+//! Bob share anyway, and reports the transfers to it; the block height comes
+//! from the monero daemon. The server learns which output locks the Monero
+//! of the swap, use one you trust or run yourself.
+//!
+//! Sweeping the Monero means building and signing a transaction locally,
+//! which only monero-wallet-rpc does for us. Until that is done here as well
+//! the CLI and the embedded takers refuse to run swaps with a light wallet.
+
+use crate::env::Config;
+use crate::monero::wallet::{LockConfirmations, MoneroWallet, WatchRequest};
+use crate::monero::{Amount, InsufficientFunds, InvalidTransfer, PrivateViewKey, TxHash};
+use crate::progress::Progress;
+use ::monero::{Address, Network, PrivateKey};
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use monero_rpc::lws;
+use monero_rpc::monerod::{self, MonerodRpc as _};
+use monero_rpc::wallet::BlockHeight;
+use std::time::Duration;
+use url::Url;
+use uuid::Uuid;
+
+#[derive(Debug)]
+pub struct LightWallet {
+    lws: lws::Client,
+    monerod: monerod::Client,
+    network: Network,
+    sync_interval: Duration,
+    progress: Progress,
+}
+
+impl LightWallet {
+    pub fn new(url: Url, monerod: monerod::Client, env_config: Config) -> Result<Self> {
+        Ok(Self {
+            lws: lws::Client::new(url)?,
+            monerod,
+            network: env_config.monero_network,
+            sync_interval: env_config.monero_sync_interval(),
+            progress: Progress::default(),
+        })
+    }
+
+    /// Makes sure the server scans the lock address from the restore height
+    /// on, the Monero may have been locked before the account was created.
+    async fn register(&self, account: &lws::Account, restore_height: BlockHeight) -> Result<()> {
+        let login = self.lws.login(account).await?;
+        let restore_height = u64::from(restore_height.height);
+
+        let scans_too_late = login
+            .start_height
+            .map_or(false, |start_height| start_height > restore_height);
+
+        if login.new_address && scans_too_late {
+            let import = self.lws.import_request(account, restore_height).await?;
+            if !import.request_fulfilled {
+                tracing::warn!(
+                    status = ?import.status,
+                    "The light wallet server did not accept to scan from the restore height yet"
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl MoneroWallet for LightWallet {
+    async fn block_height(&self) -> Result<BlockHeight> {
+        let count = self.monerod.get_block_count().await?.count;
+
+        Ok(BlockHeight { height: count })
+    }
+
+    /// Unlike monero-wallet-rpc the server cannot check the transfer with its
+    /// key, it finds it by scanning for the outputs of the lock address.
+    async fn watch_for_transfer(
+        &self,
+        request: WatchRequest,
+        restore_height: BlockHeight,
+    ) -> Result<(), InvalidTransfer> {
+        let WatchRequest {
+            public_spend_key,
+            view_key,
+            transfer_proof,
+            conf_target,
+            expected,
+        } = request;
+
+        let address = Address::standard(self.network, public_spend_key, view_key.public().into());
+        let account = lws::Account {
+            address: address.to_string(),
+            view_key: PrivateKey::from(view_key).to_string(),
+        };
+        let txid = transfer_proof.tx_hash();
+
+        tracing::info!(
+            %txid,
+            target_confirmations = %conf_target,
+            "Waiting for Monero transaction finality through the light wallet server"
+        );
+
+        let mut check_interval = tokio::time::interval(self.sync_interval);
+        let mut registered = false;
+        let mut confirmations = LockConfirmations::new(&txid, conf_target, self.progress.clone());
+
+        while !confirmations.is_final() {
+            check_interval.tick().await;
+
+            // Treating every error as transient, like the wallet RPC
+            if !registered {
+                match self.register(&account, restore_height).await {
+                    Ok(()) => registered = true,
+                    Err(error) => {
+                        tracing::debug!("Failed to log into the light wallet server: {:#}", error);
+                        continue;
+                    }
+                }
+            }
+
+            let txs = match self.lws.get_address_txs(&account).await {
+                Ok(txs) => txs,
+                Err(error) => {
+                    tracing::debug!(%txid, "Failed to fetch the transfers of the lock address: {:#}", error);
+                    continue;
+                }
+            };
+            let tx = match txs.transactions.iter().find(|tx| tx.hash == txid.0) {
+                Some(tx) => tx,
+                None => {
+                    tracing::debug!(%txid, scanned_height = %txs.scanned_height, "Transaction not found by the light wallet server yet");
+                    confirmations.not_found();
+                    continue;
+                }
+            };
+
+            let received = Amount::from_piconero(tx.total_received);
+            if received != expected {
+                return Err(InvalidTransfer::InsufficientFunds(InsufficientFunds {
+                    expected,
+                    actual: received,
+                }));
+            }
+            if tx.unlock_time != 0 {
+                return Err(InvalidTransfer::UnlockTimeNotZero {
+                    unlock_time: tx.unlock_time,
+                });
+            }
+
+            confirmations.update(tx.confirmations(txs.blockchain_height));
+        }
+
+        Ok(())
+    }
+
+    async fn sweep_swap_wallet(
+        &self,
+        swap_id: Uuid,
+        _spend_key: PrivateKey,
+        _view_key: PrivateViewKey,
+        _restore_height: BlockHeight,
+        _address: Address,
+    ) -> Result<Vec<TxHash>> {
+        bail!(
+            "A light wallet server cannot sweep the Monero, resume the swap without `--monero-light-wallet-server` or import the keys printed by `swap monero-recovery --swap-id {}` into a wallet",
+            swap_id
+        )
+    }
+
+    fn progress(&self) -> Progress {
+        self.progress.clone()
+    }
+}
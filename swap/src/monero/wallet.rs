@@ -1,16 +1,60 @@
 use crate::env::Config;
 use crate::monero::{
-    Amount, InsufficientFunds, PrivateViewKey, PublicViewKey, TransferProof, TxHash,
+    Amount, InsufficientFunds, InvalidTransfer, PrivateViewKey, PublicViewKey, TransferProof,
+    TxHash,
 };
+use crate::progress::{Chain, Confirmations, Progress};
 use ::monero::{Address, Network, PrivateKey, PublicKey};
 use anyhow::{Context, Result};
+use async_trait::async_trait;
 use monero_rpc::wallet::{BlockHeight, MoneroWalletRpc as _, Refreshed};
-use monero_rpc::{jsonrpc, wallet};
+use monero_rpc::{jsonrpc, monerod, wallet};
+use std::cmp::Ordering;
+use std::future::Future;
 use std::str::FromStr;
 use std::time::Duration;
 use tokio::sync::Mutex;
 use tokio::time::Interval;
 use url::Url;
+use uuid::Uuid;
+
+/// What Bob needs of a Monero wallet to run a swap, either the [`Wallet`]
+/// behind monero-wallet-rpc or the
+/// [`LightWallet`](crate::monero::LightWallet) of a light wallet server.
+#[async_trait]
+pub trait MoneroWallet: Send + Sync {
+    async fn block_height(&self) -> Result<BlockHeight>;
+
+    /// Waits until the Monero of the request is locked, looking for it from
+    /// `restore_height` on.
+    async fn watch_for_transfer(
+        &self,
+        request: WatchRequest,
+        restore_height: BlockHeight,
+    ) -> Result<(), InvalidTransfer>;
+
+    /// Sweeps the Monero locked in the swap to `address`, given the keys of
+    /// the lock address.
+    async fn sweep_swap_wallet(
+        &self,
+        swap_id: Uuid,
+        spend_key: PrivateKey,
+        view_key: PrivateViewKey,
+        restore_height: BlockHeight,
+        address: Address,
+    ) -> Result<Vec<TxHash>>;
+
+    /// Where watched transfers report the confirmations they are waited for.
+    fn progress(&self) -> Progress;
+}
+
+/// The code monero-wallet-rpc answers the creation of a wallet with if a
+/// wallet of that name exists already.
+const WALLET_ALREADY_EXISTS: i64 = -21;
+
+#[derive(Debug, thiserror::Error)]
+#[error("Monero wallet {0} exists already")]
+pub struct WalletExists(pub String);
 
 #[derive(Debug)]
 pub struct Wallet {
@@ -19,6 +63,8 @@ pub struct Wallet {
     name: String,
     main_address: monero::Address,
     sync_interval: Duration,
+    monerod: Option<monerod::Client>,
+    progress: Progress,
 }
 
 impl Wallet {
@@ -40,6 +86,27 @@ impl Wallet {
         Self::connect(client, name, env_config).await
     }
 
+    /// Connect to a wallet RPC and create the given wallet, fails with
+    /// [`WalletExists`] if a wallet with that name exists already.
+    pub async fn create(url: Url, name: String, env_config: Config) -> Result<Self> {
+        let client = wallet::Client::new(url)?;
+
+        match client
+            .create_wallet(name.clone(), "English".to_owned())
+            .await
+        {
+            Ok(_) => {}
+            Err(jsonrpc::Error::JsonRpc(jsonrpc::JsonRpcError {
+                code: WALLET_ALREADY_EXISTS,
+                ..
+            })) => return Err(WalletExists(name).into()),
+            Err(error) => return Err(error).context("Unable to create Monero wallet"),
+        }
+        tracing::debug!(monero_wallet_name = %name, "Created Monero wallet");
+
+        Self::connect(client, name, env_config).await
+    }
+
     /// Connects to a wallet RPC where a wallet is already loaded.
     pub async fn connect(client: wallet::Client, name: String, env_config: Config) -> Result<Self> {
         let main_address =
@@ -50,9 +117,29 @@ impl Wallet {
             name,
             main_address,
             sync_interval: env_config.monero_sync_interval(),
+            monerod: None,
+            progress: Progress::default(),
         })
     }
 
+    /// Use the given monerod to look up transaction details that are not
+    /// exposed by the wallet RPC.
+    ///
+    /// Without a monerod, the unlock time of a watched transfer is looked up
+    /// in the transfers of the loaded wallet, which only works for transfers
+    /// that wallet sent itself.
+    pub fn with_monerod(self, monerod: monerod::Client) -> Self {
+        Self {
+            monerod: Some(monerod),
+            ..self
+        }
+    }
+
+    /// Where watched transfers report the confirmations they are waited for.
+    pub fn progress(&self) -> Progress {
+        self.progress.clone()
+    }
+
     /// Re-open the wallet using the internally stored name.
     pub async fn re_open(&self) -> Result<()> {
         self.inner
@@ -205,10 +292,18 @@ impl Wallet {
         ))
     }
 
-    pub async fn watch_for_transfer(&self, request: WatchRequest) -> Result<(), InsufficientFunds> {
+    /// Waits until the transfer given in the request has the requested amount
+    /// of confirmations.
+    ///
+    /// The transfer is only accepted if it sends exactly the expected amount to
+    /// the address derived from the public keys of the request and is
+    /// spendable right away (i.e. has an unlock time of 0). The unlock time is
+    /// asked for until it is known, callers that must give up at the cancel
+    /// timelock race this against it.
+    pub async fn watch_for_transfer(&self, request: WatchRequest) -> Result<(), InvalidTransfer> {
         let WatchRequest {
             conf_target,
-            public_view_key,
+            view_key,
             public_spend_key,
             transfer_proof,
             expected,
@@ -222,7 +317,7 @@ impl Wallet {
             "Waiting for Monero transaction finality"
         );
 
-        let address = Address::standard(self.network, public_spend_key, public_view_key.into());
+        let address = Address::standard(self.network, public_spend_key, view_key.public().into());
 
         let check_interval = tokio::time::interval(self.sync_interval);
 
@@ -234,12 +329,39 @@ impl Wallet {
             conf_target,
             check_interval,
             self.name.clone(),
+            &self.progress,
         )
         .await?;
 
+        let check_interval = tokio::time::interval(self.sync_interval);
+
+        match self.monerod.as_ref() {
+            Some(monerod) => {
+                verify_unlock_time(|| lookup_unlock_time(monerod, &txid), &txid, check_interval)
+                    .await?;
+            }
+            None => {
+                verify_unlock_time(|| self.lookup_own_unlock_time(&txid), &txid, check_interval)
+                    .await?;
+            }
+        }
+
         Ok(())
     }
 
+    async fn lookup_own_unlock_time(&self, txid: &TxHash) -> Result<Option<u64>> {
+        let transfer = self
+            .inner
+            .lock()
+            .await
+            .get_transfer_by_txid(txid.to_string())
+            .await
+            .context("Failed to fetch transfer from monero-wallet-rpc")?
+            .transfer;
+
+        Ok(Some(transfer.unlock_time))
+    }
+
     pub async fn sweep_all(&self, address: Address) -> Result<Vec<TxHash>> {
         let sweep_all = self
             .inner
@@ -259,10 +381,71 @@ impl Wallet {
         Ok(Amount::from_piconero(amount))
     }
 
+    /// Get the unlocked balance of the primary account, i.e. the part of the
+    /// balance that can be spent right away.
+    pub async fn get_unlocked_balance(&self) -> Result<Amount> {
+        let amount = self
+            .inner
+            .lock()
+            .await
+            .get_balance(0)
+            .await?
+            .unlocked_balance;
+
+        Ok(Amount::from_piconero(amount))
+    }
+
+    /// The balance and the unlocked balance of the primary account, as of the
+    /// same moment.
+    pub async fn get_balances(&self) -> Result<(Amount, Amount)> {
+        let balance = self.inner.lock().await.get_balance(0).await?;
+
+        Ok((
+            Amount::from_piconero(balance.balance),
+            Amount::from_piconero(balance.unlocked_balance),
+        ))
+    }
+
+    /// Waits up to `timeout` for the unlocked balance to cover `amount`, e.g.
+    /// for the change of a recent transfer to unlock. Returns whether it does.
+    pub async fn wait_for_unlocked_balance(
+        &self,
+        amount: Amount,
+        timeout: Duration,
+    ) -> Result<bool> {
+        let unlocked = async {
+            loop {
+                if self.get_unlocked_balance().await? >= amount {
+                    return Ok::<_, anyhow::Error>(());
+                }
+
+                tokio::time::sleep(self.sync_interval).await;
+                self.refresh().await?;
+            }
+        };
+
+        match tokio::time::timeout(timeout, unlocked).await {
+            Ok(result) => result.map(|()| true),
+            Err(_elapsed) => Ok(false),
+        }
+    }
+
     pub async fn block_height(&self) -> Result<BlockHeight> {
         Ok(self.inner.lock().await.get_height().await?)
     }
 
+    /// The mnemonic seed of the loaded wallet, the words it is restored
+    /// from.
+    pub async fn mnemonic(&self) -> Result<String> {
+        Ok(self
+            .inner
+            .lock()
+            .await
+            .query_key("mnemonic".to_owned())
+            .await?
+            .key)
+    }
+
     pub fn get_main_address(&self) -> Address {
         self.main_address
     }
@@ -270,6 +453,74 @@ impl Wallet {
     pub async fn refresh(&self) -> Result<Refreshed> {
         Ok(self.inner.lock().await.refresh().await?)
     }
+
+    /// Drop all blockchain data of the currently loaded wallet and scan again,
+    /// starting from the restore height the wallet was created with.
+    pub async fn rescan(&self) -> Result<()> {
+        self.inner
+            .lock()
+            .await
+            .rescan_blockchain()
+            .await
+            .context("Failed to rescan Monero wallet")?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl MoneroWallet for Wallet {
+    async fn block_height(&self) -> Result<BlockHeight> {
+        Wallet::block_height(self).await
+    }
+
+    /// The wallet RPC checks the transfer with its key, it does not need to
+    /// scan the blockchain from `restore_height`.
+    async fn watch_for_transfer(
+        &self,
+        request: WatchRequest,
+        _restore_height: BlockHeight,
+    ) -> Result<(), InvalidTransfer> {
+        Wallet::watch_for_transfer(self, request).await
+    }
+
+    async fn sweep_swap_wallet(
+        &self,
+        swap_id: Uuid,
+        spend_key: PrivateKey,
+        view_key: PrivateViewKey,
+        restore_height: BlockHeight,
+        address: Address,
+    ) -> Result<Vec<TxHash>> {
+        let wallet_file_name = swap_id.to_string();
+        if let Err(e) = self
+            .create_from_and_load(
+                wallet_file_name.clone(),
+                spend_key,
+                view_key,
+                restore_height,
+            )
+            .await
+        {
+            // In case we failed to refresh/sweep, when resuming the wallet might already
+            // exist! This is a very unlikely scenario, but if we don't take care of it we
+            // might not be able to ever transfer the Monero.
+            tracing::warn!("Failed to generate monero wallet from keys: {:#}", e);
+            tracing::info!(%wallet_file_name,
+                "Falling back to trying to open the the wallet if it already exists",
+            );
+            self.open(wallet_file_name).await?;
+        }
+
+        // Ensure that the generated wallet is synced so we have a proper balance
+        self.refresh().await?;
+        // Sweep (transfer all funds) to the given address
+        self.sweep_all(address).await
+    }
+
+    fn progress(&self) -> Progress {
+        Wallet::progress(self)
+    }
 }
 
 #[derive(Debug)]
@@ -282,12 +533,95 @@ pub struct TransferRequest {
 #[derive(Debug)]
 pub struct WatchRequest {
     pub public_spend_key: PublicKey,
-    pub public_view_key: PublicViewKey,
+    /// Shared by Alice and Bob, a light wallet server needs it to find the
+    /// transfer.
+    pub view_key: PrivateViewKey,
     pub transfer_proof: TransferProof,
     pub conf_target: u64,
     pub expected: Amount,
 }
 
+/// Counts the confirmations of a Monero lock transaction while it is waited
+/// for.
+///
+/// Only the latest check counts. A transaction that loses confirmations, or
+/// is no longer found once it was confirmed, moved to an alt chain and is
+/// waited for from scratch. Otherwise Bob could send his encrypted signature
+/// before the Monero is final.
+#[derive(Debug)]
+pub struct LockConfirmations {
+    txid: String,
+    seen: u64,
+    needed: u64,
+    progress: Progress,
+}
+
+impl LockConfirmations {
+    pub fn new(txid: &TxHash, needed: u64, progress: Progress) -> Self {
+        let confirmations = Self {
+            txid: txid.to_string(),
+            seen: 0,
+            needed,
+            progress,
+        };
+        confirmations.report();
+
+        confirmations
+    }
+
+    pub fn is_final(&self) -> bool {
+        self.seen >= self.needed
+    }
+
+    /// Records the confirmations of the latest check.
+    pub fn update(&mut self, confirmations: u64) {
+        let txid = &self.txid;
+
+        match confirmations.cmp(&self.seen) {
+            Ordering::Greater => tracing::info!(
+                %txid,
+                seen_confirmations = %confirmations,
+                needed_confirmations = %self.needed,
+                "Received new confirmation for Monero lock tx"
+            ),
+            Ordering::Less => tracing::warn!(
+                %txid,
+                seen_confirmations = %confirmations,
+                needed_confirmations = %self.needed,
+                "Monero lock tx lost confirmations, it may have moved to an alt chain"
+            ),
+            Ordering::Equal => return,
+        }
+
+        self.seen = confirmations;
+        self.report();
+    }
+
+    /// Records that the latest check did not find the transaction.
+    pub fn not_found(&mut self) {
+        if self.seen == 0 {
+            return;
+        }
+
+        tracing::warn!(
+            txid = %self.txid,
+            "Monero lock tx is no longer found, waiting for it to be confirmed again"
+        );
+        self.seen = 0;
+        self.report();
+    }
+
+    fn report(&self) {
+        self.progress.report(Confirmations {
+            chain: Chain::Monero,
+            txid: self.txid.clone(),
+            seen: self.seen,
+            needed: self.needed,
+        })
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 async fn wait_for_confirmations<C: monero_rpc::wallet::MoneroWalletRpc<reqwest::Client> + Sync>(
     client: &Mutex<C>,
     transfer_proof: TransferProof,
@@ -296,13 +630,15 @@ async fn wait_for_confirmations<C: monero_rpc::wallet::MoneroWalletRpc<reqwest::
     conf_target: u64,
     mut check_interval: Interval,
     wallet_name: String,
+    progress: &Progress,
 ) -> Result<(), InsufficientFunds> {
-    let mut seen_confirmations = 0u64;
+    let txid = transfer_proof.tx_hash().to_string();
+    let mut confirmations =
+        LockConfirmations::new(&transfer_proof.tx_hash(), conf_target, progress.clone());
 
-    while seen_confirmations < conf_target {
+    while !confirmations.is_final() {
         check_interval.tick().await; // tick() at the beginning of the loop so every `continue` tick()s as well
 
-        let txid = transfer_proof.tx_hash().to_string();
         let client = client.lock().await;
 
         let tx = match client
@@ -316,6 +652,8 @@ async fn wait_for_confirmations<C: monero_rpc::wallet::MoneroWalletRpc<reqwest::
             Ok(proof) => proof,
             Err(jsonrpc::Error::JsonRpc(jsonrpc::JsonRpcError { code: -1, .. })) => {
                 tracing::warn!(%txid, "`monero-wallet-rpc` failed to fetch transaction, may need to be restarted");
+                // Also what it reports for a transaction that is not part of the chain
+                confirmations.not_found();
                 continue;
             }
             // TODO: Implement this using a generic proxy for each function call once https://github.com/thomaseizinger/rust-jsonrpc-client/issues/47 is fixed.
@@ -348,20 +686,59 @@ async fn wait_for_confirmations<C: monero_rpc::wallet::MoneroWalletRpc<reqwest::
             });
         }
 
-        if tx.confirmations > seen_confirmations {
-            seen_confirmations = tx.confirmations;
-            tracing::info!(
-                %txid,
-                %seen_confirmations,
-                needed_confirmations = %conf_target,
-                "Received new confirmation for Monero lock tx"
-            );
-        }
+        confirmations.update(tx.confirmations);
     }
 
     Ok(())
 }
 
+/// Rejects the transfer unless `lookup` reports an unlock time of 0, failed
+/// lookups are retried on every tick of `check_interval`.
+///
+/// `lookup` returns `None` as long as the transaction is not known.
+async fn verify_unlock_time<L, F>(
+    mut lookup: L,
+    txid: &TxHash,
+    mut check_interval: Interval,
+) -> Result<(), InvalidTransfer>
+where
+    L: FnMut() -> F,
+    F: Future<Output = Result<Option<u64>>>,
+{
+    loop {
+        check_interval.tick().await;
+
+        match lookup().await {
+            Ok(Some(0)) => return Ok(()),
+            Ok(Some(unlock_time)) => {
+                return Err(InvalidTransfer::UnlockTimeNotZero { unlock_time })
+            }
+            Ok(None) => {
+                tracing::debug!(%txid, "Transaction not yet known to monerod");
+            }
+            Err(error) => {
+                tracing::warn!(%txid, "Failed to fetch unlock time of Monero lock tx: {:#}", error);
+            }
+        }
+    }
+}
+
+async fn lookup_unlock_time(monerod: &monerod::Client, txid: &TxHash) -> Result<Option<u64>> {
+    let response = monerod
+        .get_transactions(vec![txid.to_string()])
+        .await
+        .context("Failed to fetch transaction from monerod")?;
+
+    let transaction = match response.txs.iter().find(|tx| tx.tx_hash == txid.0) {
+        Some(tx) => tx
+            .transaction()
+            .context("Failed to decode transaction returned by monerod")?,
+        None => return Ok(None),
+    };
+
+    Ok(Some(transaction.prefix.unlock_time.0))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -386,7 +763,8 @@ mod tests {
             Amount::from_piconero(100),
             10,
             tokio::time::interval(Duration::from_millis(10)),
-            "foo-wallet".to_owned()
+            "foo-wallet".to_owned(),
+            &Progress::default(),
         )
         .await;
 
@@ -437,7 +815,8 @@ mod tests {
             Amount::from_piconero(100),
             5,
             tokio::time::interval(Duration::from_millis(10)),
-            "foo-wallet".to_owned()
+            "foo-wallet".to_owned(),
+            &Progress::default(),
         )
         .await
         .unwrap();
@@ -484,7 +863,8 @@ mod tests {
             Amount::from_piconero(100),
             5,
             tokio::time::interval(Duration::from_millis(10)),
-            "foo-wallet".to_owned()
+            "foo-wallet".to_owned(),
+            &Progress::default(),
         )
         .await
         .unwrap();
@@ -507,6 +887,115 @@ DEBUG swap::monero::wallet: Opening wallet `foo-wallet` because no wallet is loa
         );
     }
 
+    #[tokio::test]
+    async fn waits_again_for_a_transaction_that_moved_to_an_alt_chain() {
+        let writer = capture_logs(LevelFilter::INFO);
+
+        let client = Mutex::new(DummyClient::new(vec![
+            Ok(CheckTxKey {
+                confirmations: 3,
+                received: 100,
+            }),
+            Ok(CheckTxKey {
+                confirmations: 1,
+                received: 100,
+            }),
+            Err((-1, "Failed to get transaction from daemon".to_owned())),
+            Ok(CheckTxKey {
+                confirmations: 2,
+                received: 100,
+            }),
+            Ok(CheckTxKey {
+                confirmations: 5,
+                received: 100,
+            }),
+        ]));
+
+        wait_for_confirmations(
+            &client,
+            TransferProof::new(TxHash("<FOO>".to_owned()), PrivateKey {
+                scalar: crate::monero::Scalar::random(&mut rand::thread_rng())
+            }),
+            "53H3QthYLckeCXh9u38vohb2gZ4QgEG3FMWHNxccR6MqV1LdDVYwF1FKsRJPj4tTupWLf9JtGPBcn2MVN6c9oR7p5Uf7JdJ".parse().unwrap(),
+            Amount::from_piconero(100),
+            5,
+            tokio::time::interval(Duration::from_millis(10)),
+            "foo-wallet".to_owned(),
+            &Progress::default(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            writer.captured(),
+            r" INFO swap::monero::wallet: Received new confirmation for Monero lock tx txid=<FOO> seen_confirmations=3 needed_confirmations=5
+ WARN swap::monero::wallet: Monero lock tx lost confirmations, it may have moved to an alt chain txid=<FOO> seen_confirmations=1 needed_confirmations=5
+ WARN swap::monero::wallet: `monero-wallet-rpc` failed to fetch transaction, may need to be restarted txid=<FOO>
+ WARN swap::monero::wallet: Monero lock tx is no longer found, waiting for it to be confirmed again txid=<FOO>
+ INFO swap::monero::wallet: Received new confirmation for Monero lock tx txid=<FOO> seen_confirmations=2 needed_confirmations=5
+ INFO swap::monero::wallet: Received new confirmation for Monero lock tx txid=<FOO> seen_confirmations=5 needed_confirmations=5
+"
+        );
+    }
+
+    async fn verify_unlock_time_with(
+        responses: Vec<Result<Option<u64>>>,
+    ) -> (Result<(), InvalidTransfer>, u32) {
+        let responses = std::sync::Mutex::new(responses.into_iter());
+        let lookups = AtomicU32::new(0);
+
+        let result = verify_unlock_time(
+            || {
+                lookups.fetch_add(1, Ordering::SeqCst);
+                let response = responses
+                    .lock()
+                    .unwrap()
+                    .next()
+                    .unwrap_or_else(|| Err(anyhow::anyhow!("monerod unreachable")));
+                async move { response }
+            },
+            &TxHash("<FOO>".to_owned()),
+            tokio::time::interval(Duration::from_millis(1)),
+        )
+        .await;
+
+        (result, lookups.load(Ordering::SeqCst))
+    }
+
+    #[tokio::test]
+    async fn accepts_transfer_spendable_right_away() {
+        let (result, lookups) = verify_unlock_time_with(vec![Ok(None), Ok(Some(0))]).await;
+
+        assert!(result.is_ok());
+        assert_eq!(lookups, 2);
+    }
+
+    #[tokio::test]
+    async fn rejects_transfer_with_non_zero_unlock_time() {
+        let (result, _) = verify_unlock_time_with(vec![Ok(Some(2_500_000))]).await;
+
+        assert!(matches!(
+            result,
+            Err(InvalidTransfer::UnlockTimeNotZero {
+                unlock_time: 2_500_000
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn keeps_asking_for_unlock_time_until_it_is_known() {
+        let (result, lookups) = verify_unlock_time_with(vec![
+            Ok(None),
+            Err(anyhow::anyhow!("timeout")),
+            Err(anyhow::anyhow!("timeout")),
+            Ok(Some(0)),
+        ])
+        .await;
+
+        assert!(result.is_ok());
+        assert_eq!(lookups, 4);
+    }
+
     type ErrorCode = i64;
     type ErrorMessage = String;
 
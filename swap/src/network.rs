@@ -1,16 +1,26 @@
 mod impl_from_rr_event;
 
-pub mod cbor_request_response;
-pub mod encrypted_signature;
-pub mod json_pull_codec;
+pub mod address_family;
+pub(crate) mod codec;
+pub mod connection;
+pub(crate) mod cooperative_xmr_redeem_after_punish;
+pub mod duplex_transport;
+pub(crate) mod encrypted_signature;
+pub mod hole_punch;
+pub(crate) mod json_pull_codec;
+pub mod maker_key;
+pub mod outbox;
 pub mod quote;
-pub mod redial;
+pub(crate) mod receipt;
+pub(crate) mod redial;
 pub mod rendezvous;
-pub mod swap_setup;
+pub(crate) mod swap_setup;
+pub mod swap_status;
 pub mod swarm;
-pub mod tor_transport;
-pub mod transfer_proof;
-pub mod transport;
+pub(crate) mod tor_transport;
+pub(crate) mod transfer_proof;
+pub(crate) mod transport;
+pub mod watchtower;
 
 #[cfg(any(test, feature = "test"))]
 pub mod test;
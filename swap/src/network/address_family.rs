@@ -0,0 +1,197 @@
+//! Listening on and dialing IPv4 and IPv6 addresses.
+//!
+//! IPv6 listeners only accept IPv6 connections, so the ASB can listen on
+//! `/ip4/0.0.0.0/tcp/<port>` and `/ip6/::/tcp/<port>` at the same time. It
+//! advertises the external addresses of both families, a taker may know the
+//! seller under addresses it cannot reach, e.g. IPv6 addresses on a host
+//! without IPv6 connectivity. These are dialed last.
+
+use libp2p::multiaddr::Protocol;
+use libp2p::Multiaddr;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::net::UdpSocket;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AddressFamily {
+    Ipv4,
+    Ipv6,
+}
+
+impl AddressFamily {
+    /// The family of an IP or DNS address, `None` for addresses of neither
+    /// family, e.g. onion addresses, and for `/dns/` names that may resolve
+    /// to both.
+    pub fn of(address: &Multiaddr) -> Option<Self> {
+        match address.iter().next()? {
+            Protocol::Ip4(_) | Protocol::Dns4(_) => Some(AddressFamily::Ipv4),
+            Protocol::Ip6(_) | Protocol::Dns6(_) => Some(AddressFamily::Ipv6),
+            _ => None,
+        }
+    }
+
+    /// Whether the host has a route to the internet for this family.
+    ///
+    /// Connecting a UDP socket only looks up the route, nothing is sent.
+    pub fn is_routable(self) -> bool {
+        let (local, remote) = match self {
+            AddressFamily::Ipv4 => ("0.0.0.0:0", "192.0.2.1:9"),
+            AddressFamily::Ipv6 => ("[::]:0", "[2001:db8::1]:9"),
+        };
+
+        UdpSocket::bind(local)
+            .and_then(|socket| socket.connect(remote))
+            .is_ok()
+    }
+}
+
+impl fmt::Display for AddressFamily {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AddressFamily::Ipv4 => write!(f, "ipv4"),
+            AddressFamily::Ipv6 => write!(f, "ipv6"),
+        }
+    }
+}
+
+/// Orders `addresses` so the ones of a family the host has no route for come
+/// last, the order is kept otherwise.
+pub fn reachable_first(addresses: Vec<Multiaddr>) -> Vec<Multiaddr> {
+    let unroutable = [AddressFamily::Ipv4, AddressFamily::Ipv6]
+        .iter()
+        .copied()
+        .filter(|family| !family.is_routable())
+        .collect::<Vec<_>>();
+
+    unroutable_last(addresses, &unroutable)
+}
+
+fn unroutable_last(mut addresses: Vec<Multiaddr>, unroutable: &[AddressFamily]) -> Vec<Multiaddr> {
+    addresses.sort_by_key(|address| {
+        AddressFamily::of(address).map_or(false, |family| unroutable.contains(&family))
+    });
+
+    addresses
+}
+
+/// Keeps the addresses of `family` and those of neither family, all addresses
+/// if `family` is `None`.
+pub fn select(addresses: Vec<Multiaddr>, family: Option<AddressFamily>) -> Vec<Multiaddr> {
+    let family = match family {
+        Some(family) => family,
+        None => return addresses,
+    };
+
+    addresses
+        .into_iter()
+        .filter(|address| AddressFamily::of(address).map_or(true, |of| of == family))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::connection;
+    use crate::network::test::new_swarm;
+    use futures::StreamExt;
+    use libp2p::swarm::SwarmEvent;
+    use libp2p::Swarm;
+    use std::time::Duration;
+
+    fn addresses(addresses: &[&str]) -> Vec<Multiaddr> {
+        addresses
+            .iter()
+            .map(|address| address.parse().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn unroutable_addresses_are_dialed_last() {
+        let ordered = unroutable_last(
+            addresses(&[
+                "/ip6/2001:db8::1/tcp/9939",
+                "/ip4/192.0.2.1/tcp/9939",
+                "/dns6/example.com/tcp/9939",
+                "/dns/example.com/tcp/9939",
+            ]),
+            &[AddressFamily::Ipv6],
+        );
+
+        assert_eq!(
+            ordered,
+            addresses(&[
+                "/ip4/192.0.2.1/tcp/9939",
+                "/dns/example.com/tcp/9939",
+                "/ip6/2001:db8::1/tcp/9939",
+                "/dns6/example.com/tcp/9939",
+            ])
+        );
+    }
+
+    #[test]
+    fn only_addresses_of_selected_family_are_kept() {
+        let all = addresses(&[
+            "/ip4/0.0.0.0/tcp/9939",
+            "/ip6/::/tcp/9939",
+            "/onion3/oarchy4tamydxcitaki6bc2v4leza6v35iezmu2chg2bap63sv6f2did:1024",
+        ]);
+
+        assert_eq!(
+            select(all.clone(), Some(AddressFamily::Ipv4)),
+            addresses(&[
+                "/ip4/0.0.0.0/tcp/9939",
+                "/onion3/oarchy4tamydxcitaki6bc2v4leza6v35iezmu2chg2bap63sv6f2did:1024",
+            ])
+        );
+        assert_eq!(select(all.clone(), None), all);
+    }
+
+    #[tokio::test]
+    async fn listens_on_ipv4_and_ipv6_with_the_same_port() {
+        if std::net::TcpListener::bind("[::1]:0").is_err() {
+            // The host has no IPv6
+            return;
+        }
+
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+
+        let mut alice = new_swarm(|_, _| connection::ping());
+        alice
+            .listen_on(format!("/ip4/0.0.0.0/tcp/{}", port).parse().unwrap())
+            .unwrap();
+        alice
+            .listen_on(format!("/ip6/::/tcp/{}", port).parse().unwrap())
+            .unwrap();
+
+        for ip in &["/ip4/127.0.0.1", "/ip6/::1"] {
+            let address = format!("{}/tcp/{}", ip, port).parse().unwrap();
+
+            tokio::time::timeout(Duration::from_secs(10), connect(&mut alice, address))
+                .await
+                .unwrap();
+        }
+    }
+
+    async fn connect(alice: &mut Swarm<libp2p::ping::Ping>, address: Multiaddr) {
+        let mut bob = new_swarm(|_, _| connection::ping());
+        bob.dial(address.clone()).unwrap();
+
+        loop {
+            tokio::select! {
+                _ = alice.select_next_some() => {}
+                event = bob.select_next_some() => match event {
+                    SwarmEvent::ConnectionEstablished { .. } => return,
+                    SwarmEvent::OutgoingConnectionError { error, .. } => {
+                        panic!("Failed to dial {}: {}", address, error)
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,343 @@
+//! Encoding of the messages of our request-response protocols.
+//!
+//! Every protocol is offered in two versions. The legacy version sends bare
+//! CBOR encoded messages. The enveloped version prefixes every message with a
+//! byte indicating its [`Encoding`], so messages can also be sent as JSON
+//! which is easier to debug. Which version is used is negotiated when the
+//! substream is opened, the enveloped version is preferred if both peers
+//! support it. Either way both encodings are accepted when reading.
+
+use async_trait::async_trait;
+use futures::prelude::*;
+use libp2p::core::upgrade;
+use libp2p::request_response::{ProtocolName, ProtocolSupport, RequestResponseCodec};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::io;
+use std::marker::PhantomData;
+
+/// Message receive buffer.
+pub const BUF_SIZE: usize = 1024 * 1024;
+
+const ENVELOPE_SUFFIX: &str = "/envelope";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    Cbor,
+    Json,
+}
+
+impl Default for Encoding {
+    fn default() -> Self {
+        Encoding::Cbor
+    }
+}
+
+impl Encoding {
+    fn tag(self) -> u8 {
+        match self {
+            Encoding::Cbor => 0x01,
+            Encoding::Json => 0x02,
+        }
+    }
+
+    fn from_tag(tag: u8) -> io::Result<Self> {
+        match tag {
+            0x01 => Ok(Encoding::Cbor),
+            0x02 => Ok(Encoding::Json),
+            tag => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Unknown message encoding {:#04x}", tag),
+            )),
+        }
+    }
+
+    fn encode<M: Serialize>(self, message: &M) -> io::Result<Vec<u8>> {
+        match self {
+            Encoding::Cbor => serde_cbor::to_vec(message)
+                .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error)),
+            Encoding::Json => serde_json::to_vec(message)
+                .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error)),
+        }
+    }
+
+    fn decode<M: DeserializeOwned>(self, bytes: &[u8]) -> io::Result<M> {
+        match self {
+            Encoding::Cbor => {
+                let mut de = serde_cbor::Deserializer::from_slice(bytes);
+                M::deserialize(&mut de)
+                    .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+            }
+            Encoding::Json => serde_json::from_slice(bytes)
+                .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error)),
+        }
+    }
+}
+
+/// One of the two versions of the protocol `P`.
+#[derive(Clone, Debug)]
+pub struct Versioned<P> {
+    name: Vec<u8>,
+    enveloped: bool,
+    phantom: PhantomData<P>,
+}
+
+impl<P: ProtocolName> Versioned<P> {
+    pub fn legacy(protocol: P) -> Self {
+        Self {
+            name: protocol.protocol_name().to_vec(),
+            enveloped: false,
+            phantom: PhantomData,
+        }
+    }
+
+    pub fn enveloped(protocol: P) -> Self {
+        Self {
+            name: [protocol.protocol_name(), ENVELOPE_SUFFIX.as_bytes()].concat(),
+            enveloped: true,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<P> ProtocolName for Versioned<P> {
+    fn protocol_name(&self) -> &[u8] {
+        &self.name
+    }
+}
+
+/// Both versions of the protocol, the enveloped version is preferred.
+pub fn versions<P: ProtocolName + Clone>(
+    protocol: P,
+    support: ProtocolSupport,
+) -> Vec<(Versioned<P>, ProtocolSupport)> {
+    vec![
+        (Versioned::enveloped(protocol.clone()), support.clone()),
+        (Versioned::legacy(protocol), support),
+    ]
+}
+
+/// A [`RequestResponseCodec`] that writes messages in the configured
+/// [`Encoding`]. Peers only supporting the legacy version of the protocol are
+/// always sent CBOR.
+#[derive(Clone, Copy, Debug)]
+pub struct Codec<P, Req, Res> {
+    encoding: Encoding,
+    phantom: PhantomData<(P, Req, Res)>,
+}
+
+impl<P, Req, Res> Default for Codec<P, Req, Res> {
+    fn default() -> Self {
+        Self::new(Encoding::default())
+    }
+}
+
+impl<P, Req, Res> Codec<P, Req, Res> {
+    pub fn new(encoding: Encoding) -> Self {
+        Self {
+            encoding,
+            phantom: PhantomData::default(),
+        }
+    }
+}
+
+#[async_trait]
+impl<P, Req, Res> RequestResponseCodec for Codec<P, Req, Res>
+where
+    P: Send + Sync + Clone,
+    Req: DeserializeOwned + Serialize + Send,
+    Res: DeserializeOwned + Serialize + Send,
+{
+    type Protocol = Versioned<P>;
+    type Request = Req;
+    type Response = Res;
+
+    async fn read_request<T>(
+        &mut self,
+        protocol: &Self::Protocol,
+        io: &mut T,
+    ) -> io::Result<Self::Request>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        let message = upgrade::read_length_prefixed(io, BUF_SIZE).await?;
+
+        decode(protocol, &message)
+    }
+
+    async fn read_response<T>(
+        &mut self,
+        protocol: &Self::Protocol,
+        io: &mut T,
+    ) -> io::Result<Self::Response>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        let message = upgrade::read_length_prefixed(io, BUF_SIZE).await?;
+
+        decode(protocol, &message)
+    }
+
+    async fn write_request<T>(
+        &mut self,
+        protocol: &Self::Protocol,
+        io: &mut T,
+        req: Self::Request,
+    ) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        let bytes = encode(protocol, self.encoding, &req)?;
+        upgrade::write_length_prefixed(io, &bytes).await?;
+
+        Ok(())
+    }
+
+    async fn write_response<T>(
+        &mut self,
+        protocol: &Self::Protocol,
+        io: &mut T,
+        res: Self::Response,
+    ) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        let bytes = encode(protocol, self.encoding, &res)?;
+        upgrade::write_length_prefixed(io, &bytes).await?;
+
+        Ok(())
+    }
+}
+
+fn encode<P, M: Serialize>(
+    protocol: &Versioned<P>,
+    encoding: Encoding,
+    message: &M,
+) -> io::Result<Vec<u8>> {
+    if !protocol.enveloped {
+        return Encoding::Cbor.encode(message);
+    }
+
+    let mut bytes = vec![encoding.tag()];
+    bytes.extend(encoding.encode(message)?);
+
+    Ok(bytes)
+}
+
+fn decode<P, M: DeserializeOwned>(protocol: &Versioned<P>, bytes: &[u8]) -> io::Result<M> {
+    if !protocol.enveloped {
+        return Encoding::Cbor.decode(bytes);
+    }
+
+    let (tag, payload) = bytes
+        .split_first()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Empty message"))?;
+
+    Encoding::from_tag(*tag)?.decode(payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::monero;
+    use crate::network::swap_status::{Phase, Status, SwapStatusProtocol};
+    use crate::network::transfer_proof::{self, TransferProofProtocol};
+    use futures::io::Cursor;
+    use uuid::Uuid;
+
+    fn status() -> Status {
+        Status {
+            swap_id: Uuid::new_v4(),
+            phase: Phase::XmrLocked,
+            tx_lock_id: None,
+            xmr_lock_tx_hash: Some("abcd".to_owned()),
+        }
+    }
+
+    fn transfer_proof_request() -> transfer_proof::Request {
+        transfer_proof::Request {
+            swap_id: Uuid::new_v4(),
+            tx_lock_proof: monero::TransferProof::new(
+                monero::TxHash("abcd".to_owned()),
+                monero::PrivateKey {
+                    scalar: monero::Scalar::random(&mut rand::thread_rng()),
+                },
+            ),
+            nonce: Some(Uuid::new_v4()),
+        }
+    }
+
+    #[test]
+    fn both_encodings_agree() {
+        let protocol = Versioned::enveloped(TransferProofProtocol);
+        let request = transfer_proof_request();
+
+        for encoding in [Encoding::Cbor, Encoding::Json] {
+            let bytes = encode(&protocol, encoding, &request).unwrap();
+            let decoded: transfer_proof::Request = decode(&protocol, &bytes).unwrap();
+
+            assert_eq!(bytes[0], encoding.tag());
+            assert_eq!(decoded.swap_id, request.swap_id);
+            assert_eq!(decoded.tx_lock_proof, request.tx_lock_proof);
+            assert_eq!(decoded.nonce, request.nonce);
+        }
+    }
+
+    #[test]
+    fn legacy_version_sends_bare_cbor() {
+        let protocol = Versioned::legacy(TransferProofProtocol);
+        let request = transfer_proof_request();
+
+        assert_eq!(
+            encode(&protocol, Encoding::Json, &request).unwrap(),
+            serde_cbor::to_vec(&request).unwrap()
+        );
+    }
+
+    #[test]
+    fn unknown_encoding_is_rejected() {
+        let protocol = Versioned::enveloped(SwapStatusProtocol);
+
+        let error = decode::<_, Status>(&protocol, &[0x03, 0xf6]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(decode::<_, Status>(&protocol, &[]).is_err());
+    }
+
+    #[test]
+    fn enveloped_version_is_preferred() {
+        let versions = versions(SwapStatusProtocol, ProtocolSupport::Full);
+
+        assert_eq!(
+            versions[0].0.protocol_name(),
+            b"/comit/xmr/btc/swap_status/1.0.0/envelope"
+        );
+        assert_eq!(
+            versions[1].0.protocol_name(),
+            b"/comit/xmr/btc/swap_status/1.0.0"
+        );
+    }
+
+    #[tokio::test]
+    async fn peers_with_different_encodings_understand_each_other() {
+        let protocol = Versioned::enveloped(SwapStatusProtocol);
+        let mut json = Codec::<SwapStatusProtocol, Status, Status>::new(Encoding::Json);
+        let mut cbor = Codec::<SwapStatusProtocol, Status, Status>::new(Encoding::Cbor);
+        let request = status();
+
+        let mut io = Cursor::new(Vec::new());
+        json.write_request(&protocol, &mut io, request.clone())
+            .await
+            .unwrap();
+        io.set_position(0);
+        let received = cbor.read_request(&protocol, &mut io).await.unwrap();
+        assert_eq!(received, request);
+
+        let mut io = Cursor::new(Vec::new());
+        cbor.write_response(&protocol, &mut io, request.clone())
+            .await
+            .unwrap();
+        io.set_position(0);
+        let received = json.read_response(&protocol, &mut io).await.unwrap();
+        assert_eq!(received, request);
+    }
+}
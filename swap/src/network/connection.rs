@@ -0,0 +1,226 @@
+//! Keeping a single connection to the peer open for the whole swap.
+//!
+//! Re-establishing a connection means another Noise handshake and multiplexer
+//! negotiation, over Tor also building a new circuit. The ping protocol keeps
+//! connections open for as long as the peer answers its pings and tolerates
+//! a few pings that time out, e.g. while a Tor circuit is slow, before closing
+//! the connection. The request-response protocols keep an otherwise idle
+//! connection open for [`CONNECTION_KEEP_ALIVE`] after their last request, so
+//! a connection that was opened for one message is still there for the next.
+//!
+//! [`ConnectionStats`] counts how often a lost connection was re-established
+//! and how many of the open connections go through a circuit relay.
+
+use libp2p::ping::{Ping, PingConfig};
+use libp2p::request_response::RequestResponseConfig;
+use libp2p::PeerId;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::num::NonZeroU32;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// How long the request-response protocols keep a connection open after
+/// their last request.
+pub const CONNECTION_KEEP_ALIVE: Duration = Duration::from_secs(60);
+
+/// How many pings in a row may time out before the connection is closed.
+const MAX_PING_FAILURES: u32 = 3;
+
+/// A connection established within this time after the previous connection
+/// to the peer was lost counts as a reconnect.
+const RECONNECT_WINDOW: Duration = Duration::from_secs(60 * 60);
+
+pub fn request_response_config() -> RequestResponseConfig {
+    let mut config = RequestResponseConfig::default();
+    config.set_connection_keep_alive(CONNECTION_KEEP_ALIVE);
+
+    config
+}
+
+pub fn ping() -> Ping {
+    let max_failures = NonZeroU32::new(MAX_PING_FAILURES).expect("max ping failures not zero");
+
+    Ping::new(
+        PingConfig::new()
+            .with_keep_alive(true)
+            .with_max_failures(max_failures),
+    )
+}
+
+/// How many connections were established and lost since the start, and how
+/// many are open right now.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ConnectionCounts {
+    pub established: u64,
+    pub lost: u64,
+    pub reconnects: u64,
+    #[serde(default)]
+    pub open_direct: u64,
+    #[serde(default)]
+    pub open_relayed: u64,
+    /// Direct connections established to a peer a relayed connection was
+    /// open to, usually by hole punching.
+    #[serde(default)]
+    pub upgraded: u64,
+}
+
+impl ConnectionCounts {
+    fn open(&mut self, relayed: bool) -> &mut u64 {
+        if relayed {
+            &mut self.open_relayed
+        } else {
+            &mut self.open_direct
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    counts: ConnectionCounts,
+    /// The peers whose last connection was lost, with when it was lost.
+    lost: HashMap<PeerId, Instant>,
+    /// The number of open relayed connections per peer.
+    relayed: HashMap<PeerId, u32>,
+}
+
+/// A cheaply cloneable handle to the connection counts of a swarm.
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionStats {
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl ConnectionStats {
+    /// Records a new connection to `peer`, returns whether it replaces a
+    /// connection that was lost.
+    pub fn established(&self, peer: PeerId, relayed: bool, now: Instant) -> bool {
+        let mut inner = self.lock();
+        inner.counts.established += 1;
+        *inner.counts.open(relayed) += 1;
+        if relayed {
+            *inner.relayed.entry(peer).or_default() += 1;
+        } else if inner.relayed.contains_key(&peer) {
+            inner.counts.upgraded += 1;
+        }
+
+        let reconnect = match inner.lost.remove(&peer) {
+            Some(lost_at) => now.saturating_duration_since(lost_at) < RECONNECT_WINDOW,
+            None => false,
+        };
+        if reconnect {
+            inner.counts.reconnects += 1;
+        }
+
+        reconnect
+    }
+
+    /// Records that a connection to `peer` was closed, with `remaining`
+    /// connections to it still open. If it was the last one it was `lost`
+    /// if it was closed because of an error rather than on request.
+    pub fn closed(&self, peer: PeerId, relayed: bool, remaining: u32, lost: bool, now: Instant) {
+        let mut inner = self.lock();
+        let open = inner.counts.open(relayed);
+        *open = open.saturating_sub(1);
+        if relayed {
+            if let Some(count) = inner.relayed.get_mut(&peer) {
+                *count -= 1;
+                if *count == 0 {
+                    inner.relayed.remove(&peer);
+                }
+            }
+        }
+        if remaining > 0 {
+            return;
+        }
+
+        inner
+            .lost
+            .retain(|_, lost_at| now.saturating_duration_since(*lost_at) < RECONNECT_WINDOW);
+
+        if lost {
+            inner.counts.lost += 1;
+            inner.lost.insert(peer, now);
+        } else {
+            inner.lost.remove(&peer);
+        }
+    }
+
+    pub fn counts(&self) -> ConnectionCounts {
+        self.lock().counts
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        self.inner
+            .lock()
+            .expect("connection stats lock not poisoned")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn connection_after_a_lost_one_is_a_reconnect() {
+        let stats = ConnectionStats::default();
+        let peer = PeerId::random();
+        let now = Instant::now();
+
+        assert!(!stats.established(peer, false, now));
+        stats.closed(peer, false, 0, true, now);
+        assert!(stats.established(peer, false, now + Duration::from_secs(5)));
+
+        assert_eq!(
+            stats.counts(),
+            ConnectionCounts {
+                established: 2,
+                lost: 1,
+                reconnects: 1,
+                open_direct: 1,
+                ..ConnectionCounts::default()
+            }
+        );
+    }
+
+    #[test]
+    fn connection_after_a_requested_close_or_much_later_is_no_reconnect() {
+        let stats = ConnectionStats::default();
+        let peer = PeerId::random();
+        let now = Instant::now();
+
+        stats.closed(peer, false, 0, false, now);
+        assert!(!stats.established(peer, false, now));
+
+        stats.closed(peer, false, 0, true, now);
+        assert!(!stats.established(peer, false, now + RECONNECT_WINDOW));
+
+        assert_eq!(stats.counts().reconnects, 0);
+    }
+
+    #[test]
+    fn direct_connection_next_to_a_relayed_one_is_an_upgrade() {
+        let stats = ConnectionStats::default();
+        let peer = PeerId::random();
+        let now = Instant::now();
+
+        stats.established(peer, true, now);
+        assert_eq!(stats.counts().open_relayed, 1);
+
+        stats.established(peer, false, now);
+        stats.closed(peer, true, 1, false, now);
+        stats.closed(peer, false, 0, true, now);
+        stats.established(peer, false, now);
+
+        assert_eq!(
+            stats.counts(),
+            ConnectionCounts {
+                established: 3,
+                lost: 1,
+                reconnects: 1,
+                open_direct: 1,
+                upgraded: 1,
+                ..ConnectionCounts::default()
+            }
+        );
+    }
+}
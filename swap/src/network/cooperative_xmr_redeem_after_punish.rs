@@ -0,0 +1,141 @@
+//! Lets Bob ask Alice for her key share after she punished him.
+//!
+//! Once the punish transaction is published Alice has the Bitcoin and the
+//! Monero stays locked forever unless she hands out her share of the spend
+//! key. Nothing forces her to, but a maker may do so to keep the taker whole,
+//! e.g. when the taker only missed the refund window because they were
+//! offline. Whether Alice responds with her key share is up to her policy.
+
+use crate::monero::monero_private_key;
+use crate::network::codec::{self, Codec};
+use crate::network::connection;
+use crate::{asb, cli, monero};
+use libp2p::core::ProtocolName;
+use libp2p::request_response::{
+    ProtocolSupport, RequestResponse, RequestResponseEvent, RequestResponseMessage,
+};
+use libp2p::PeerId;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+const PROTOCOL: &str = "/comit/xmr/btc/cooperative_xmr_redeem_after_punish/1.0.0";
+type OutEvent = RequestResponseEvent<Request, Response>;
+type Message = RequestResponseMessage<Request, Response>;
+
+pub type Behaviour =
+    RequestResponse<Codec<CooperativeXmrRedeemAfterPunishProtocol, Request, Response>>;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CooperativeXmrRedeemAfterPunishProtocol;
+
+impl ProtocolName for CooperativeXmrRedeemAfterPunishProtocol {
+    fn protocol_name(&self) -> &[u8] {
+        PROTOCOL.as_bytes()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Request {
+    pub swap_id: Uuid,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Response {
+    Fulfilled {
+        swap_id: Uuid,
+        #[serde(with = "monero_private_key")]
+        s_a: monero::PrivateKey,
+    },
+    Rejected {
+        swap_id: Uuid,
+        reason: RejectReason,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, thiserror::Error)]
+pub enum RejectReason {
+    #[error("Alice does not know the swap")]
+    UnknownSwap,
+    /// Alice has not punished Bob (yet), the swap can still be refunded.
+    #[error("Alice has not punished the swap")]
+    NotPunished,
+    #[error("Alice declined to hand out her key share: {0}")]
+    Declined(String),
+    /// Alice could not decide whether to hand out her key share, e.g. because
+    /// her policy was unreachable. Bob may ask again.
+    #[error("Alice could not decide whether to hand out her key share: {0}")]
+    Unavailable(String),
+}
+
+pub fn alice() -> Behaviour {
+    Behaviour::new(
+        Codec::default(),
+        codec::versions(
+            CooperativeXmrRedeemAfterPunishProtocol,
+            ProtocolSupport::Inbound,
+        ),
+        connection::request_response_config(),
+    )
+}
+
+pub fn bob() -> Behaviour {
+    Behaviour::new(
+        Codec::default(),
+        codec::versions(
+            CooperativeXmrRedeemAfterPunishProtocol,
+            ProtocolSupport::Outbound,
+        ),
+        connection::request_response_config(),
+    )
+}
+
+impl From<(PeerId, Message)> for asb::OutEvent {
+    fn from((peer, message): (PeerId, Message)) -> Self {
+        match message {
+            Message::Request {
+                request, channel, ..
+            } => Self::CooperativeXmrRedeemRequested {
+                swap_id: request.swap_id,
+                channel,
+                peer,
+            },
+            Message::Response { .. } => Self::unexpected_response(peer),
+        }
+    }
+}
+crate::impl_from_rr_event!(OutEvent, asb::OutEvent, PROTOCOL);
+
+impl From<(PeerId, Message)> for cli::OutEvent {
+    fn from((peer, message): (PeerId, Message)) -> Self {
+        match message {
+            Message::Request { .. } => Self::unexpected_request(peer),
+            Message::Response {
+                response,
+                request_id,
+            } => Self::CooperativeXmrRedeemReceived {
+                id: request_id,
+                response,
+            },
+        }
+    }
+}
+crate::impl_from_rr_event!(OutEvent, cli::OutEvent, PROTOCOL);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_share_survives_roundtrip() {
+        let response = Response::Fulfilled {
+            swap_id: Uuid::new_v4(),
+            s_a: monero::PrivateKey::from_scalar(monero::Scalar::random(&mut rand::thread_rng())),
+        };
+
+        let json = serde_json::to_string(&response).unwrap();
+        let cbor = serde_cbor::to_vec(&response).unwrap();
+
+        assert_eq!(serde_json::from_str::<Response>(&json).unwrap(), response);
+        assert_eq!(serde_cbor::from_slice::<Response>(&cbor).unwrap(), response);
+    }
+}
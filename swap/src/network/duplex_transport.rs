@@ -0,0 +1,175 @@
+use futures::future::{self, Ready};
+use futures::stream::Pending;
+use futures::{AsyncRead, AsyncWrite};
+use libp2p::core::multiaddr::{Multiaddr, Protocol};
+use libp2p::core::transport::{ListenerEvent, TransportError};
+use libp2p::core::Transport;
+use std::io;
+use std::sync::{Arc, Mutex};
+
+/// The address to register for the peer at the other end of a
+/// [`DuplexTransport`].
+///
+/// The transport ignores the address, but the swarm only dials peers it knows
+/// an address of.
+pub fn address() -> Multiaddr {
+    Multiaddr::empty().with(Protocol::Memory(0))
+}
+
+/// A [`Transport`] that runs a single connection over a duplex stream
+/// provided by the host application, e.g. a channel to the maker that a
+/// wallet already established.
+///
+/// Every dial resolves to that stream regardless of the address, once it was
+/// used further dials fail, i.e. a lost connection cannot be re-established.
+/// Listening is not supported. The connection is still authenticated and
+/// multiplexed like any other, so the other end has to speak libp2p, e.g. by
+/// forwarding the stream to a listen address of the ASB.
+///
+/// Streams implementing tokio's `AsyncRead` and `AsyncWrite` have to be
+/// wrapped in a compatibility layer first.
+pub struct DuplexTransport<S> {
+    stream: Arc<Mutex<Option<S>>>,
+}
+
+impl<S> DuplexTransport<S> {
+    pub fn new(stream: S) -> Self {
+        Self {
+            stream: Arc::new(Mutex::new(Some(stream))),
+        }
+    }
+}
+
+// The transport is cloned for every dial, all clones share the same stream.
+impl<S> Clone for DuplexTransport<S> {
+    fn clone(&self) -> Self {
+        Self {
+            stream: self.stream.clone(),
+        }
+    }
+}
+
+impl<S> Transport for DuplexTransport<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    type Output = S;
+    type Error = io::Error;
+    #[allow(clippy::type_complexity)]
+    type Listener = Pending<Result<ListenerEvent<Self::ListenerUpgrade, Self::Error>, Self::Error>>;
+    type ListenerUpgrade = Ready<Result<Self::Output, Self::Error>>;
+    type Dial = Ready<Result<Self::Output, Self::Error>>;
+
+    fn listen_on(self, addr: Multiaddr) -> Result<Self::Listener, TransportError<Self::Error>> {
+        Err(TransportError::MultiaddrNotSupported(addr))
+    }
+
+    fn dial(self, _: Multiaddr) -> Result<Self::Dial, TransportError<Self::Error>> {
+        let stream = self
+            .stream
+            .lock()
+            .expect("stream lock not poisoned")
+            .take()
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotConnected,
+                    "The connection provided by the host application was already used",
+                )
+            });
+
+        Ok(future::ready(stream))
+    }
+
+    fn dial_as_listener(self, addr: Multiaddr) -> Result<Self::Dial, TransportError<Self::Error>> {
+        self.dial(addr)
+    }
+
+    fn address_translation(&self, _: &Multiaddr, _: &Multiaddr) -> Option<Multiaddr> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::amounts::{Price, Quote};
+    use crate::bitcoin;
+    use crate::network::quote;
+    use crate::network::swarm;
+    use crate::network::test::{new_swarm, SwarmExt};
+    use futures::StreamExt;
+    use libp2p::identity;
+    use libp2p::request_response::{RequestResponseEvent, RequestResponseMessage};
+    use libp2p::swarm::SwarmEvent;
+    use std::net::Ipv4Addr;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn swarm_runs_over_connection_of_host_application() {
+        let mut alice = new_swarm(|_, _| quote::asb());
+        let alice_address = alice.listen_on_tcp_localhost().await;
+        let alice_peer_id = *alice.local_peer_id();
+        let port = match alice_address.iter().last() {
+            Some(Protocol::Tcp(port)) => port,
+            _ => panic!("expected tcp address, got {}", alice_address),
+        };
+
+        // the host application established the connection
+        let connection = tokio::net::TcpStream::connect((Ipv4Addr::LOCALHOST, port))
+            .await
+            .unwrap();
+        let transport = DuplexTransport::new(libp2p::tcp::tokio::TcpStream(connection)).boxed();
+        let mut bob = swarm::cli_with_transport(
+            identity::Keypair::generate_ed25519(),
+            transport,
+            quote::cli(),
+        )
+        .unwrap();
+        bob.behaviour_mut().add_address(&alice_peer_id, address());
+        bob.behaviour_mut().send_request(&alice_peer_id, ());
+
+        let quote = Quote {
+            price: Price::new(bitcoin::Amount::from_sat(1337)),
+            min_quantity: bitcoin::Amount::from_sat(42),
+            max_quantity: bitcoin::Amount::from_sat(9001),
+        };
+        let received = async {
+            loop {
+                tokio::select! {
+                    event = alice.select_next_some() => {
+                        if let SwarmEvent::Behaviour(RequestResponseEvent::Message {
+                            message: RequestResponseMessage::Request { channel, .. },
+                            ..
+                        }) = event
+                        {
+                            alice.behaviour_mut().send_response(channel, quote.into()).unwrap();
+                        }
+                    }
+                    event = bob.select_next_some() => {
+                        if let SwarmEvent::Behaviour(RequestResponseEvent::Message {
+                            message: RequestResponseMessage::Response { response, .. },
+                            ..
+                        }) = event
+                        {
+                            return response;
+                        }
+                    }
+                }
+            }
+        };
+
+        let received = tokio::time::timeout(Duration::from_secs(10), received)
+            .await
+            .unwrap();
+        assert_eq!(received.quote, quote);
+    }
+
+    #[tokio::test]
+    async fn connection_is_only_used_once() {
+        let transport = DuplexTransport::new(futures::io::Cursor::new(Vec::new()));
+
+        assert!(transport.clone().dial(address()).unwrap().await.is_ok());
+        let second_dial = transport.dial(address()).unwrap().await;
+        assert_eq!(second_dial.unwrap_err().kind(), io::ErrorKind::NotConnected);
+    }
+}
@@ -1,9 +1,10 @@
-use crate::network::cbor_request_response::CborCodec;
+use crate::network::codec::{self, Codec};
+use crate::network::connection;
+use crate::protocol::transcript::TranscriptHash;
 use crate::{asb, cli};
 use libp2p::core::ProtocolName;
 use libp2p::request_response::{
-    ProtocolSupport, RequestResponse, RequestResponseConfig, RequestResponseEvent,
-    RequestResponseMessage,
+    ProtocolSupport, RequestResponse, RequestResponseEvent, RequestResponseMessage,
 };
 use libp2p::PeerId;
 use serde::{Deserialize, Serialize};
@@ -13,7 +14,7 @@ const PROTOCOL: &str = "/comit/xmr/btc/encrypted_signature/1.0.0";
 type OutEvent = RequestResponseEvent<Request, ()>;
 type Message = RequestResponseMessage<Request, ()>;
 
-pub type Behaviour = RequestResponse<CborCodec<EncryptedSignatureProtocol, Request, ()>>;
+pub type Behaviour = RequestResponse<Codec<EncryptedSignatureProtocol, Request, ()>>;
 
 #[derive(Debug, Clone, Copy, Default)]
 pub struct EncryptedSignatureProtocol;
@@ -28,21 +29,30 @@ impl ProtocolName for EncryptedSignatureProtocol {
 pub struct Request {
     pub swap_id: Uuid,
     pub tx_redeem_encsig: crate::bitcoin::EncryptedSignature,
+    /// The same for every delivery of the message, see
+    /// [`crate::network::outbox`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<Uuid>,
+    /// Bob's view of the swap parameters, see
+    /// [`crate::protocol::transcript`]. Not sent by Bob for swaps set up with
+    /// earlier versions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transcript: Option<TranscriptHash>,
 }
 
 pub fn alice() -> Behaviour {
     Behaviour::new(
-        CborCodec::default(),
-        vec![(EncryptedSignatureProtocol, ProtocolSupport::Inbound)],
-        RequestResponseConfig::default(),
+        Codec::default(),
+        codec::versions(EncryptedSignatureProtocol, ProtocolSupport::Inbound),
+        connection::request_response_config(),
     )
 }
 
 pub fn bob() -> Behaviour {
     Behaviour::new(
-        CborCodec::default(),
-        vec![(EncryptedSignatureProtocol, ProtocolSupport::Outbound)],
-        RequestResponseConfig::default(),
+        Codec::default(),
+        codec::versions(EncryptedSignatureProtocol, ProtocolSupport::Outbound),
+        connection::request_response_config(),
     )
 }
 
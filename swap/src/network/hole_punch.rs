@@ -0,0 +1,71 @@
+//! Upgrading relayed connections to direct ones.
+//!
+//! Once a connection through a circuit relay is established, the peer that
+//! accepted it sends the other one the addresses it was observed at over the
+//! direct connection upgrade through relay (DCUtR) protocol, the other peer
+//! answers with its own and both dial each other at the same time. If the
+//! NATs in between let one of the dials through, the peers continue over the
+//! direct connection and the relayed one is closed once it is idle. The
+//! observed addresses are learned from connected peers, e.g. the relay, over
+//! the identify protocol.
+
+use libp2p::dcutr::behaviour::{Behaviour as Dcutr, Event as DcutrEvent, UpgradeError};
+use libp2p::identify::{Identify, IdentifyConfig, IdentifyEvent};
+use libp2p::identity::PublicKey;
+use libp2p::{NetworkBehaviour, PeerId};
+
+const PROTOCOL_VERSION: &str = "/comit/xmr/btc/1.0.0";
+
+#[derive(Debug)]
+pub enum OutEvent {
+    Upgraded { peer: PeerId },
+    UpgradeFailed { peer: PeerId, error: UpgradeError },
+    Other,
+}
+
+#[derive(NetworkBehaviour)]
+#[behaviour(out_event = "OutEvent", event_process = false)]
+#[allow(missing_debug_implementations)]
+pub struct Behaviour {
+    identify: Identify,
+    dcutr: Dcutr,
+}
+
+impl Behaviour {
+    pub fn new(public_key: PublicKey) -> Self {
+        Self {
+            identify: Identify::new(IdentifyConfig::new(
+                PROTOCOL_VERSION.to_owned(),
+                public_key,
+            )),
+            dcutr: Dcutr::new(),
+        }
+    }
+}
+
+impl From<IdentifyEvent> for OutEvent {
+    fn from(_: IdentifyEvent) -> Self {
+        OutEvent::Other
+    }
+}
+
+impl From<DcutrEvent> for OutEvent {
+    fn from(event: DcutrEvent) -> Self {
+        match event {
+            DcutrEvent::DirectConnectionUpgradeSucceeded { remote_peer_id } => {
+                OutEvent::Upgraded {
+                    peer: remote_peer_id,
+                }
+            }
+            DcutrEvent::DirectConnectionUpgradeFailed {
+                remote_peer_id,
+                error,
+            } => OutEvent::UpgradeFailed {
+                peer: remote_peer_id,
+                error,
+            },
+            DcutrEvent::InitiatedDirectConnectionUpgrade { .. }
+            | DcutrEvent::RemoteInitiatedDirectConnectionUpgrade { .. } => OutEvent::Other,
+        }
+    }
+}
@@ -0,0 +1,200 @@
+//! The maker key, the long-term identity of a maker.
+//!
+//! The libp2p identity authenticates the transport and shows up as the peer
+//! id, the maker key signs the quotes on top of that. The maker key can be
+//! rotated without changing the peer id: the previous key signs a
+//! [`Rotation`] to the next one and the maker sends all rotations along with
+//! its quotes. Takers follow the rotations back to the key the maker started
+//! out with, the [`MakerKey::id`], that stays the same across rotations.
+
+use anyhow::{bail, Result};
+use libp2p::identity::ed25519;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use time::OffsetDateTime;
+
+/// Prepended to the signed rotation so the signature cannot be passed off as
+/// one over a different kind of message.
+const ROTATION_DOMAIN: &[u8] = b"/comit/xmr/btc/maker-key-rotation/1.0.0";
+
+/// The public part of a maker key, hex encoded.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct MakerPublicKey(#[serde(with = "hex::serde")] [u8; 32]);
+
+impl MakerPublicKey {
+    pub fn verify(&self, message: &[u8], signature: &[u8]) -> bool {
+        match ed25519::PublicKey::decode(&self.0) {
+            Ok(public_key) => public_key.verify(message, signature),
+            Err(_) => false,
+        }
+    }
+}
+
+impl From<ed25519::PublicKey> for MakerPublicKey {
+    fn from(public_key: ed25519::PublicKey) -> Self {
+        Self(public_key.encode())
+    }
+}
+
+impl fmt::Display for MakerPublicKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", hex::encode(self.0))
+    }
+}
+
+/// A statement of the `previous` key that the maker continues with the `next`
+/// one.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Rotation {
+    pub previous: MakerPublicKey,
+    pub next: MakerPublicKey,
+    /// The Unix timestamp of the rotation, for the record only.
+    pub rotated_at: i64,
+    /// The signature of the previous key.
+    #[serde(with = "hex::serde")]
+    pub signature: Vec<u8>,
+}
+
+impl Rotation {
+    fn signed_bytes(&self) -> Vec<u8> {
+        let mut bytes = ROTATION_DOMAIN.to_vec();
+        bytes.extend_from_slice(&self.previous.0);
+        bytes.extend_from_slice(&self.next.0);
+        bytes.extend_from_slice(&self.rotated_at.to_be_bytes());
+
+        bytes
+    }
+}
+
+/// The current maker key and the rotations that lead to it.
+#[derive(Debug, Clone)]
+pub struct MakerKey {
+    keypair: ed25519::Keypair,
+    rotations: Vec<Rotation>,
+}
+
+impl MakerKey {
+    /// A key that was never rotated.
+    pub fn new(keypair: ed25519::Keypair) -> Self {
+        Self {
+            keypair,
+            rotations: Vec::new(),
+        }
+    }
+
+    /// Continues the identity of `rotations` with `keypair`, fails if the
+    /// rotations do not lead to it.
+    pub fn with_rotations(keypair: ed25519::Keypair, rotations: Vec<Rotation>) -> Result<Self> {
+        verify_rotations(&rotations, keypair.public().into())?;
+
+        Ok(Self { keypair, rotations })
+    }
+
+    pub fn public(&self) -> MakerPublicKey {
+        self.keypair.public().into()
+    }
+
+    /// The key the maker started out with, it identifies the maker across
+    /// rotations.
+    pub fn id(&self) -> MakerPublicKey {
+        self.rotations
+            .first()
+            .map_or_else(|| self.public(), |rotation| rotation.previous)
+    }
+
+    pub fn rotations(&self) -> &[Rotation] {
+        &self.rotations
+    }
+
+    pub fn sign(&self, message: &[u8]) -> Vec<u8> {
+        self.keypair.sign(message)
+    }
+
+    /// Replaces the key with `next`, signing the rotation with the current
+    /// one.
+    pub fn rotate(&self, next: ed25519::Keypair, rotated_at: OffsetDateTime) -> Self {
+        let mut rotation = Rotation {
+            previous: self.public(),
+            next: next.public().into(),
+            rotated_at: rotated_at.unix_timestamp(),
+            signature: Vec::new(),
+        };
+        rotation.signature = self.sign(&rotation.signed_bytes());
+
+        let mut rotations = self.rotations.clone();
+        rotations.push(rotation);
+
+        Self {
+            keypair: next,
+            rotations,
+        }
+    }
+}
+
+/// Checks that `rotations` lead from one key to the next up to `current`,
+/// returning the key the maker started out with.
+pub fn verify_rotations(rotations: &[Rotation], current: MakerPublicKey) -> Result<MakerPublicKey> {
+    let mut key = match rotations.first() {
+        Some(rotation) => rotation.previous,
+        None => return Ok(current),
+    };
+    let id = key;
+
+    for rotation in rotations {
+        if rotation.previous != key {
+            bail!(
+                "Rotation to {} does not continue from {}",
+                rotation.next,
+                key
+            );
+        }
+        if !key.verify(&rotation.signed_bytes(), &rotation.signature) {
+            bail!("The rotation from {} has an invalid signature", key);
+        }
+        key = rotation.next;
+    }
+
+    if key != current {
+        bail!("The rotations end at {} instead of {}", key, current);
+    }
+
+    Ok(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn id_stays_the_same_across_rotations() {
+        let original = MakerKey::new(ed25519::Keypair::generate());
+        let rotated = original
+            .rotate(ed25519::Keypair::generate(), OffsetDateTime::now_utc())
+            .rotate(ed25519::Keypair::generate(), OffsetDateTime::now_utc());
+
+        assert_ne!(rotated.public(), original.public());
+        assert_eq!(rotated.id(), original.id());
+        assert_eq!(
+            verify_rotations(rotated.rotations(), rotated.public()).unwrap(),
+            original.public()
+        );
+    }
+
+    #[test]
+    fn forged_or_foreign_rotations_are_rejected() {
+        let original = MakerKey::new(ed25519::Keypair::generate());
+        let rotated = original.rotate(ed25519::Keypair::generate(), OffsetDateTime::now_utc());
+
+        let mut forged = rotated.rotations().to_vec();
+        forged[0].next = ed25519::Keypair::generate().public().into();
+        assert!(verify_rotations(&forged, forged[0].next).is_err());
+
+        let stranger = MakerKey::new(ed25519::Keypair::generate());
+        assert!(verify_rotations(rotated.rotations(), stranger.public()).is_err());
+        assert!(MakerKey::with_rotations(
+            ed25519::Keypair::generate(),
+            rotated.rotations().to_vec()
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,130 @@
+//! Delivering the messages that move a swap forward at least once, also
+//! across restarts.
+//!
+//! The transfer proof and the encrypted signature are stored in the outbox in
+//! the database before they are sent and are removed once the peer
+//! acknowledged them. Whatever is left in the outbox is sent again after
+//! reconnecting to the peer. Every message carries a nonce that stays the same
+//! across these deliveries, receivers record the `(swap id, message kind,
+//! nonce)` of the messages they processed and only acknowledge the ones they
+//! see again.
+//!
+//! Peers that predate the outbox send their messages without a nonce, these
+//! are processed as before.
+
+use crate::network::{encrypted_signature, transfer_proof};
+use crate::protocol::sequence::MessageKind;
+use crate::protocol::Database;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "request", rename_all = "snake_case")]
+pub enum Message {
+    TransferProof(transfer_proof::Request),
+    EncryptedSignature(encrypted_signature::Request),
+}
+
+impl Message {
+    pub fn swap_id(&self) -> Uuid {
+        match self {
+            Message::TransferProof(request) => request.swap_id,
+            Message::EncryptedSignature(request) => request.swap_id,
+        }
+    }
+
+    pub fn kind(&self) -> MessageKind {
+        match self {
+            Message::TransferProof(_) => MessageKind::TransferProof,
+            Message::EncryptedSignature(_) => MessageKind::EncryptedSignature,
+        }
+    }
+
+    pub fn nonce(&self) -> Option<Uuid> {
+        match self {
+            Message::TransferProof(request) => request.nonce,
+            Message::EncryptedSignature(request) => request.nonce,
+        }
+    }
+}
+
+impl From<transfer_proof::Request> for Message {
+    fn from(request: transfer_proof::Request) -> Self {
+        Message::TransferProof(request)
+    }
+}
+
+impl From<encrypted_signature::Request> for Message {
+    fn from(request: encrypted_signature::Request) -> Self {
+        Message::EncryptedSignature(request)
+    }
+}
+
+/// Whether a message of the peer was processed before, messages without a
+/// nonce never were. A failure to look it up is logged and the message
+/// processed.
+pub async fn was_received(
+    db: &(dyn Database + Send + Sync),
+    swap_id: Uuid,
+    kind: MessageKind,
+    nonce: Option<Uuid>,
+) -> bool {
+    let nonce = match nonce {
+        Some(nonce) => nonce,
+        None => return false,
+    };
+
+    db.has_received(swap_id, kind, nonce)
+        .await
+        .unwrap_or_else(|error| {
+            tracing::warn!(%swap_id, %kind, "Failed to look up whether message was received before: {:#}", error);
+            false
+        })
+}
+
+/// Records that a message of the peer was processed, so it is only
+/// acknowledged if it is delivered again.
+pub async fn record_received(
+    db: &(dyn Database + Send + Sync),
+    swap_id: Uuid,
+    kind: MessageKind,
+    nonce: Option<Uuid>,
+) {
+    let nonce = match nonce {
+        Some(nonce) => nonce,
+        None => return,
+    };
+
+    if let Err(error) = db.insert_received(swap_id, kind, nonce).await {
+        tracing::warn!(%swap_id, %kind, "Failed to record received message: {:#}", error);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::monero;
+
+    #[test]
+    fn stored_message_keeps_its_nonce() {
+        let nonce = Uuid::new_v4();
+        let message = Message::from(transfer_proof::Request {
+            swap_id: Uuid::new_v4(),
+            tx_lock_proof: monero::TransferProof::new(
+                monero::TxHash("abcd".to_owned()),
+                monero::PrivateKey {
+                    scalar: monero::Scalar::random(&mut rand::thread_rng()),
+                },
+            ),
+            nonce: Some(nonce),
+        });
+
+        let stored: Message =
+            serde_json::from_str(&serde_json::to_string(&message).unwrap()).unwrap();
+
+        assert_eq!(stored.kind(), MessageKind::TransferProof);
+        assert_eq!(stored.swap_id(), message.swap_id());
+        assert_eq!(stored.nonce(), Some(nonce));
+    }
+}
@@ -1,18 +1,35 @@
+use crate::amounts::Quote;
+use crate::bitcoin::{CancelTimelock, PunishTimelock};
+use crate::network::connection;
 use crate::network::json_pull_codec::JsonPullCodec;
-use crate::{asb, bitcoin, cli};
+use crate::network::maker_key::{self, MakerKey, MakerPublicKey, Rotation};
+use crate::network::swap_setup::BlockchainNetwork;
+use crate::{asb, bitcoin, cli, env};
+use anyhow::{bail, Context, Result};
 use libp2p::core::ProtocolName;
+use libp2p::identity::{self, PublicKey};
 use libp2p::request_response::{
-    ProtocolSupport, RequestResponse, RequestResponseConfig, RequestResponseEvent,
-    RequestResponseMessage,
+    ProtocolSupport, RequestResponse, RequestResponseEvent, RequestResponseMessage,
 };
 use libp2p::PeerId;
 use serde::{Deserialize, Serialize};
+use std::cmp::min;
+use std::fmt;
+use std::time::Duration;
+use time::OffsetDateTime;
 
 const PROTOCOL: &str = "/comit/xmr/btc/bid-quote/1.0.0";
-pub type OutEvent = RequestResponseEvent<(), BidQuote>;
-pub type Message = RequestResponseMessage<(), BidQuote>;
+pub type OutEvent = RequestResponseEvent<(), SignedQuote>;
+pub type Message = RequestResponseMessage<(), SignedQuote>;
 
-pub type Behaviour = RequestResponse<JsonPullCodec<BidQuoteProtocol, BidQuote>>;
+pub type Behaviour = RequestResponse<JsonPullCodec<BidQuoteProtocol, SignedQuote>>;
+
+/// Prepended to the signed quote so the signature cannot be passed off as one
+/// over a different kind of message.
+const SIGNATURE_DOMAIN: &[u8] = b"/comit/xmr/btc/bid-quote/signature/1.0.0";
+
+/// Prepended to the quote signed with the maker key.
+const MAKER_SIGNATURE_DOMAIN: &[u8] = b"/comit/xmr/btc/bid-quote/maker-signature/1.0.0";
 
 #[derive(Debug, Clone, Copy, Default)]
 pub struct BidQuoteProtocol;
@@ -23,18 +40,265 @@ impl ProtocolName for BidQuoteProtocol {
     }
 }
 
-/// Represents a quote for buying XMR.
+/// A quote as sent by Alice, signed with her libp2p identity key.
+///
+/// The signature is sent next to the fields of the quote, takers that only
+/// know unsigned quotes ignore it. Quotes of makers that predate signed quotes
+/// come without one.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SignedQuote {
+    #[serde(flatten)]
+    pub quote: Quote,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<QuoteSignature>,
+    /// How fast the maker can serve which part of the quote, smallest bucket
+    /// first. Not covered by the signature, the buckets are a hint for
+    /// picking a maker and not part of the terms.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub depth: Vec<DepthBucket>,
+}
+
+/// Swaps of up to `max_quantity` are served within `delay_secs`.
+///
+/// A maker serves the Monero it has unlocked right away, the Monero of
+/// outputs that are not unlocked yet only once they are. `delay_secs` is the
+/// longest the maker may take to lock the Monero on top of waiting for the
+/// Bitcoin lock transaction to be final.
 #[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq, Hash, Ord, PartialOrd)]
-pub struct BidQuote {
-    /// The price at which the maker is willing to buy at.
-    #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
-    pub price: bitcoin::Amount,
-    /// The minimum quantity the maker is willing to buy.
-    #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
-    pub min_quantity: bitcoin::Amount,
-    /// The maximum quantity the maker is willing to buy.
-    #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
+pub struct DepthBucket {
     pub max_quantity: bitcoin::Amount,
+    pub delay_secs: u64,
+}
+
+impl fmt::Display for DepthBucket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.delay_secs {
+            0 => write!(f, "{} now", self.max_quantity),
+            delay_secs => write!(
+                f,
+                "{} within {} min",
+                self.max_quantity,
+                (delay_secs + 59) / 60
+            ),
+        }
+    }
+}
+
+/// Splits the range of `quote` into what can be served from `instant` worth
+/// of unlocked Monero and what has to wait up to `unlock_delay` for the rest.
+/// A quote that offers nothing has no buckets.
+pub fn depth(
+    quote: &Quote,
+    instant: bitcoin::Amount,
+    unlock_delay: Duration,
+) -> Vec<DepthBucket> {
+    let mut buckets = Vec::new();
+    if quote.max_quantity == bitcoin::Amount::ZERO {
+        return buckets;
+    }
+
+    let instant = min(instant, quote.max_quantity);
+    if instant >= quote.min_quantity && instant > bitcoin::Amount::ZERO {
+        buckets.push(DepthBucket {
+            max_quantity: instant,
+            delay_secs: 0,
+        });
+    }
+    if instant < quote.max_quantity {
+        buckets.push(DepthBucket {
+            max_quantity: quote.max_quantity,
+            delay_secs: unlock_delay.as_secs(),
+        });
+    }
+
+    buckets
+}
+
+/// How long the maker may take to serve a swap of `btc`, `None` if `btc` is
+/// outside the range of `quote`. Makers that send no buckets serve their whole
+/// range right away.
+pub fn delay_for(
+    quote: &Quote,
+    depth: &[DepthBucket],
+    btc: bitcoin::Amount,
+) -> Option<Duration> {
+    if btc < quote.min_quantity || btc > quote.max_quantity {
+        return None;
+    }
+    if depth.is_empty() {
+        return Some(Duration::ZERO);
+    }
+
+    depth
+        .iter()
+        .find(|bucket| btc <= bucket.max_quantity)
+        .map(|bucket| Duration::from_secs(bucket.delay_secs))
+}
+
+/// Alice's signature over a quote and the terms it was made under.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct QuoteSignature {
+    pub terms: QuoteTerms,
+    /// Alice's public key in its protobuf encoding, her peer id is derived
+    /// from it.
+    #[serde(with = "hex::serde")]
+    pub public_key: Vec<u8>,
+    #[serde(with = "hex::serde")]
+    pub signature: Vec<u8>,
+    /// Quotes of makers that predate maker keys come without one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub maker: Option<MakerSignature>,
+}
+
+/// Alice's signature over a quote with her maker key, see
+/// [`maker_key`](crate::network::maker_key).
+///
+/// Besides the quote and its terms, the signature covers Alice's peer id so
+/// that another peer cannot pass the quote off as its own.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MakerSignature {
+    pub public_key: MakerPublicKey,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rotations: Vec<Rotation>,
+    #[serde(with = "hex::serde")]
+    pub signature: Vec<u8>,
+}
+
+/// The terms a quote was made under, besides its price and limits.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct QuoteTerms {
+    /// The Unix timestamp after which the quote is no longer repeated.
+    pub expires_at: i64,
+    pub blockchain_network: BlockchainNetwork,
+    pub cancel_timelock: CancelTimelock,
+    pub punish_timelock: PunishTimelock,
+}
+
+impl QuoteTerms {
+    pub fn new(env_config: &env::Config, expires_at: OffsetDateTime) -> Self {
+        Self {
+            expires_at: expires_at.unix_timestamp(),
+            blockchain_network: BlockchainNetwork {
+                bitcoin: env_config.bitcoin_network,
+                monero: env_config.monero_network,
+            },
+            cancel_timelock: env_config.bitcoin_cancel_timelock,
+            punish_timelock: env_config.bitcoin_punish_timelock,
+        }
+    }
+}
+
+impl SignedQuote {
+    pub fn unsigned(quote: Quote) -> Self {
+        Self {
+            quote,
+            signature: None,
+            depth: Vec::new(),
+        }
+    }
+
+    pub fn sign(
+        quote: Quote,
+        terms: QuoteTerms,
+        identity: &identity::Keypair,
+        maker_key: &MakerKey,
+    ) -> Result<Self> {
+        let signature = identity
+            .sign(&signed_bytes(&quote, &terms)?)
+            .context("Failed to sign quote")?;
+        let alice = PeerId::from(identity.public());
+
+        Ok(Self {
+            quote,
+            signature: Some(QuoteSignature {
+                terms,
+                public_key: identity.public().to_protobuf_encoding(),
+                signature,
+                maker: Some(MakerSignature {
+                    public_key: maker_key.public(),
+                    rotations: maker_key.rotations().to_vec(),
+                    signature: maker_key.sign(&maker_signed_bytes(&quote, &terms, &alice)?),
+                }),
+            }),
+            depth: Vec::new(),
+        })
+    }
+
+    pub fn with_depth(self, depth: Vec<DepthBucket>) -> Self {
+        Self { depth, ..self }
+    }
+
+    /// Checks that the quote was signed by `alice`, returning the terms it was
+    /// signed under. Unsigned quotes have none.
+    pub fn verify(&self, alice: &PeerId) -> Result<Option<&QuoteTerms>> {
+        let signature = match &self.signature {
+            Some(signature) => signature,
+            None => return Ok(None),
+        };
+
+        let public_key = PublicKey::from_protobuf_encoding(&signature.public_key)
+            .context("Failed to decode the public key the quote was signed with")?;
+        let signer = PeerId::from(public_key.clone());
+        if signer != *alice {
+            bail!(
+                "The quote was signed by {} instead of the seller {}",
+                signer,
+                alice
+            );
+        }
+
+        let bytes = signed_bytes(&self.quote, &signature.terms)?;
+        if !public_key.verify(&bytes, &signature.signature) {
+            bail!("The signature of the quote is invalid");
+        }
+
+        Ok(Some(&signature.terms))
+    }
+
+    /// Checks the maker signature of a quote [verified](Self::verify) to be
+    /// signed by `alice`, returning the key she started out with that
+    /// identifies her across rotations of the maker key. Quotes without a
+    /// maker signature have none.
+    pub fn maker_id(&self, alice: &PeerId) -> Result<Option<MakerPublicKey>> {
+        let (terms, maker) = match &self.signature {
+            Some(QuoteSignature {
+                terms,
+                maker: Some(maker),
+                ..
+            }) => (terms, maker),
+            _ => return Ok(None),
+        };
+
+        let bytes = maker_signed_bytes(&self.quote, terms, alice)?;
+        if !maker.public_key.verify(&bytes, &maker.signature) {
+            bail!("The maker signature of the quote is invalid");
+        }
+        let id = maker_key::verify_rotations(&maker.rotations, maker.public_key)
+            .context("Failed to follow the rotations of the maker key")?;
+
+        Ok(Some(id))
+    }
+}
+
+impl From<Quote> for SignedQuote {
+    fn from(quote: Quote) -> Self {
+        Self::unsigned(quote)
+    }
+}
+
+fn signed_bytes(quote: &Quote, terms: &QuoteTerms) -> Result<Vec<u8>> {
+    let mut bytes = SIGNATURE_DOMAIN.to_vec();
+    serde_json::to_writer(&mut bytes, &(quote, terms)).context("Failed to serialize quote")?;
+
+    Ok(bytes)
+}
+
+fn maker_signed_bytes(quote: &Quote, terms: &QuoteTerms, alice: &PeerId) -> Result<Vec<u8>> {
+    let mut bytes = MAKER_SIGNATURE_DOMAIN.to_vec();
+    serde_json::to_writer(&mut bytes, &(quote, terms, alice.to_string()))
+        .context("Failed to serialize quote")?;
+
+    Ok(bytes)
 }
 
 /// Constructs a new instance of the `quote` behaviour to be used by the ASB.
@@ -45,7 +309,7 @@ pub fn asb() -> Behaviour {
     Behaviour::new(
         JsonPullCodec::default(),
         vec![(BidQuoteProtocol, ProtocolSupport::Inbound)],
-        RequestResponseConfig::default(),
+        connection::request_response_config(),
     )
 }
 
@@ -57,7 +321,7 @@ pub fn cli() -> Behaviour {
     Behaviour::new(
         JsonPullCodec::default(),
         vec![(BidQuoteProtocol, ProtocolSupport::Outbound)],
-        RequestResponseConfig::default(),
+        connection::request_response_config(),
     )
 }
 
@@ -86,3 +350,215 @@ impl From<(PeerId, Message)> for cli::OutEvent {
     }
 }
 crate::impl_from_rr_event!(OutEvent, cli::OutEvent, PROTOCOL);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::amounts::Price;
+    use crate::env::GetConfig;
+    use libp2p::identity::ed25519;
+
+    fn quote(price: u64) -> Quote {
+        Quote {
+            price: Price::new(bitcoin::Amount::from_sat(price)),
+            min_quantity: bitcoin::Amount::ZERO,
+            max_quantity: bitcoin::Amount::from_sat(1_000_000),
+        }
+    }
+
+    fn signed(identity: &identity::Keypair) -> SignedQuote {
+        signed_with(identity, &MakerKey::new(ed25519::Keypair::generate()))
+    }
+
+    fn signed_with(identity: &identity::Keypair, maker_key: &MakerKey) -> SignedQuote {
+        let terms = QuoteTerms::new(&env::Testnet::get_config(), OffsetDateTime::now_utc());
+
+        SignedQuote::sign(quote(700_000), terms, identity, maker_key).unwrap()
+    }
+
+    #[test]
+    fn price_is_sent_in_satoshis() {
+        let quote = Quote {
+            price: Price::new(bitcoin::Amount::from_sat(700_000)),
+            min_quantity: bitcoin::Amount::ZERO,
+            max_quantity: bitcoin::Amount::from_sat(1_000_000),
+        };
+
+        let json = serde_json::to_string(&quote).unwrap();
+
+        assert_eq!(
+            json,
+            r#"{"price":700000,"min_quantity":0,"max_quantity":1000000}"#
+        );
+        assert_eq!(serde_json::from_str::<Quote>(&json).unwrap(), quote);
+    }
+
+    #[test]
+    fn unsigned_quote_is_sent_as_before() {
+        let json = serde_json::to_string(&SignedQuote::unsigned(quote(700_000))).unwrap();
+
+        assert_eq!(json, serde_json::to_string(&quote(700_000)).unwrap());
+        assert_eq!(
+            serde_json::from_str::<SignedQuote>(&json).unwrap(),
+            SignedQuote::unsigned(quote(700_000))
+        );
+    }
+
+    #[test]
+    fn signed_quote_can_be_read_as_bid_quote() {
+        let identity = identity::Keypair::generate_ed25519();
+        let json = serde_json::to_string(&signed(&identity)).unwrap();
+
+        assert_eq!(
+            serde_json::from_str::<Quote>(&json).unwrap(),
+            quote(700_000)
+        );
+    }
+
+    #[test]
+    fn quote_signed_by_seller_is_verified() {
+        let identity = identity::Keypair::generate_ed25519();
+        let signed = signed(&identity);
+
+        let json = serde_json::to_string(&signed).unwrap();
+        let received = serde_json::from_str::<SignedQuote>(&json).unwrap();
+
+        assert_eq!(
+            received.verify(&PeerId::from(identity.public())).unwrap(),
+            signed.signature.as_ref().map(|signature| &signature.terms)
+        );
+        assert_eq!(
+            SignedQuote::unsigned(quote(700_000))
+                .verify(&PeerId::random())
+                .unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn altered_or_foreign_quote_is_rejected() {
+        let identity = identity::Keypair::generate_ed25519();
+        let seller = PeerId::from(identity.public());
+
+        let mut altered = signed(&identity);
+        altered.quote.price = Price::new(bitcoin::Amount::from_sat(800_000));
+        assert_eq!(
+            altered.verify(&seller).unwrap_err().to_string(),
+            "The signature of the quote is invalid"
+        );
+
+        let foreign = signed(&identity::Keypair::generate_ed25519());
+        assert!(foreign.verify(&seller).is_err());
+    }
+
+    #[test]
+    fn maker_id_is_kept_across_rotations_and_peer_ids() {
+        let maker_key = MakerKey::new(ed25519::Keypair::generate());
+        let rotated = maker_key.rotate(ed25519::Keypair::generate(), OffsetDateTime::now_utc());
+        let identity = identity::Keypair::generate_ed25519();
+        let other_identity = identity::Keypair::generate_ed25519();
+
+        let before = signed_with(&identity, &maker_key);
+        let after = signed_with(&other_identity, &rotated);
+
+        assert_eq!(
+            before.maker_id(&PeerId::from(identity.public())).unwrap(),
+            Some(maker_key.id())
+        );
+        assert_eq!(
+            after
+                .maker_id(&PeerId::from(other_identity.public()))
+                .unwrap(),
+            Some(maker_key.id())
+        );
+    }
+
+    #[test]
+    fn depth_splits_the_quote_at_the_unlocked_monero() {
+        let quote = quote(700_000);
+        let delay = Duration::from_secs(1_200);
+        let sats = bitcoin::Amount::from_sat;
+
+        assert_eq!(
+            depth(&quote, sats(1_000_000), delay),
+            vec![DepthBucket {
+                max_quantity: sats(1_000_000),
+                delay_secs: 0,
+            }]
+        );
+        assert_eq!(
+            depth(&quote, sats(100_000), delay),
+            vec![
+                DepthBucket {
+                    max_quantity: sats(100_000),
+                    delay_secs: 0,
+                },
+                DepthBucket {
+                    max_quantity: sats(1_000_000),
+                    delay_secs: 1_200,
+                }
+            ]
+        );
+        assert_eq!(
+            depth(&quote, bitcoin::Amount::ZERO, delay),
+            vec![DepthBucket {
+                max_quantity: sats(1_000_000),
+                delay_secs: 1_200,
+            }]
+        );
+
+        let paused = Quote {
+            max_quantity: bitcoin::Amount::ZERO,
+            ..quote
+        };
+        assert!(depth(&paused, sats(100_000), delay).is_empty());
+    }
+
+    #[test]
+    fn delay_is_the_one_of_the_smallest_bucket_serving_the_amount() {
+        let quote = quote(700_000);
+        let sats = bitcoin::Amount::from_sat;
+        let buckets = depth(&quote, sats(100_000), Duration::from_secs(1_200));
+
+        assert_eq!(
+            delay_for(&quote, &buckets, sats(100_000)),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(
+            delay_for(&quote, &buckets, sats(100_001)),
+            Some(Duration::from_secs(1_200))
+        );
+        assert_eq!(delay_for(&quote, &buckets, sats(1_000_001)), None);
+        assert_eq!(
+            delay_for(&quote, &[], sats(1_000_000)),
+            Some(Duration::ZERO)
+        );
+    }
+
+    #[test]
+    fn depth_is_not_sent_if_there_is_none() {
+        let identity = identity::Keypair::generate_ed25519();
+        let signed = signed(&identity);
+        let with_depth = signed.clone().with_depth(vec![DepthBucket {
+            max_quantity: bitcoin::Amount::from_sat(100_000),
+            delay_secs: 0,
+        }]);
+
+        assert!(!serde_json::to_string(&signed).unwrap().contains("depth"));
+        let json = serde_json::to_string(&with_depth).unwrap();
+        let received = serde_json::from_str::<SignedQuote>(&json).unwrap();
+        assert_eq!(received, with_depth);
+        assert!(received.verify(&PeerId::from(identity.public())).is_ok());
+    }
+
+    #[test]
+    fn maker_signature_is_bound_to_the_peer_id() {
+        let identity = identity::Keypair::generate_ed25519();
+        let signed = signed(&identity);
+
+        assert_eq!(
+            signed.maker_id(&PeerId::random()).unwrap_err().to_string(),
+            "The maker signature of the quote is invalid"
+        );
+    }
+}
@@ -0,0 +1,97 @@
+//! Lets Bob ask Alice to countersign the receipt of a completed swap, see
+//! [`crate::protocol::receipt`].
+
+use crate::network::codec::{self, Codec};
+use crate::network::connection;
+use crate::{asb, cli};
+use libp2p::core::ProtocolName;
+use libp2p::request_response::{
+    ProtocolSupport, RequestResponse, RequestResponseEvent, RequestResponseMessage,
+};
+use libp2p::PeerId;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+pub use crate::protocol::receipt::{Signature, Terms};
+
+const PROTOCOL: &str = "/comit/xmr/btc/receipt/1.0.0";
+type OutEvent = RequestResponseEvent<Request, Response>;
+type Message = RequestResponseMessage<Request, Response>;
+
+pub type Behaviour = RequestResponse<Codec<ReceiptProtocol, Request, Response>>;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ReceiptProtocol;
+
+impl ProtocolName for ReceiptProtocol {
+    fn protocol_name(&self) -> &[u8] {
+        PROTOCOL.as_bytes()
+    }
+}
+
+/// The terms of the swap as Bob saw them, signed by him.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Request {
+    pub terms: Terms,
+    pub signature: Signature,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Response {
+    Signed {
+        swap_id: Uuid,
+        signature: Signature,
+    },
+    Rejected {
+        swap_id: Uuid,
+        reason: String,
+    },
+}
+
+pub fn alice() -> Behaviour {
+    Behaviour::new(
+        Codec::default(),
+        codec::versions(ReceiptProtocol, ProtocolSupport::Inbound),
+        connection::request_response_config(),
+    )
+}
+
+pub fn bob() -> Behaviour {
+    Behaviour::new(
+        Codec::default(),
+        codec::versions(ReceiptProtocol, ProtocolSupport::Outbound),
+        connection::request_response_config(),
+    )
+}
+
+impl From<(PeerId, Message)> for asb::OutEvent {
+    fn from((peer, message): (PeerId, Message)) -> Self {
+        match message {
+            Message::Request {
+                request, channel, ..
+            } => Self::ReceiptRequested {
+                request: Box::new(request),
+                channel,
+                peer,
+            },
+            Message::Response { .. } => Self::unexpected_response(peer),
+        }
+    }
+}
+crate::impl_from_rr_event!(OutEvent, asb::OutEvent, PROTOCOL);
+
+impl From<(PeerId, Message)> for cli::OutEvent {
+    fn from((peer, message): (PeerId, Message)) -> Self {
+        match message {
+            Message::Request { .. } => Self::unexpected_request(peer),
+            Message::Response {
+                response,
+                request_id,
+            } => Self::ReceiptReceived {
+                id: request_id,
+                response,
+            },
+        }
+    }
+}
+crate::impl_from_rr_event!(OutEvent, cli::OutEvent, PROTOCOL);
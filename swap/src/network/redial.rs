@@ -2,10 +2,11 @@ use crate::cli;
 use backoff::backoff::Backoff;
 use backoff::ExponentialBackoff;
 use futures::future::FutureExt;
-use libp2p::core::connection::ConnectionId;
+use libp2p::core::connection::{ConnectedPoint, ConnectionId};
 use libp2p::core::Multiaddr;
-use libp2p::swarm::protocols_handler::DummyProtocolsHandler;
-use libp2p::swarm::{DialPeerCondition, NetworkBehaviour, NetworkBehaviourAction, PollParameters};
+use libp2p::swarm::dial_opts::{DialOpts, PeerCondition};
+use libp2p::swarm::handler::DummyConnectionHandler;
+use libp2p::swarm::{NetworkBehaviour, NetworkBehaviourAction, PollParameters};
 use libp2p::PeerId;
 use std::pin::Pin;
 use std::task::{Context, Poll};
@@ -56,18 +57,25 @@ impl Behaviour {
 }
 
 impl NetworkBehaviour for Behaviour {
-    type ProtocolsHandler = DummyProtocolsHandler;
+    type ConnectionHandler = DummyConnectionHandler;
     type OutEvent = OutEvent;
 
-    fn new_handler(&mut self) -> Self::ProtocolsHandler {
-        DummyProtocolsHandler::default()
+    fn new_handler(&mut self) -> Self::ConnectionHandler {
+        DummyConnectionHandler::default()
     }
 
     fn addresses_of_peer(&mut self, _: &PeerId) -> Vec<Multiaddr> {
         Vec::new()
     }
 
-    fn inject_connected(&mut self, peer_id: &PeerId) {
+    fn inject_connection_established(
+        &mut self,
+        peer_id: &PeerId,
+        _: &ConnectionId,
+        _: &ConnectedPoint,
+        _: Option<&Vec<Multiaddr>>,
+        _: usize,
+    ) {
         if peer_id != &self.peer {
             return;
         }
@@ -76,8 +84,15 @@ impl NetworkBehaviour for Behaviour {
         self.sleep = None;
     }
 
-    fn inject_disconnected(&mut self, peer_id: &PeerId) {
-        if peer_id != &self.peer {
+    fn inject_connection_closed(
+        &mut self,
+        peer_id: &PeerId,
+        _: &ConnectionId,
+        _: &ConnectedPoint,
+        _: DummyConnectionHandler,
+        remaining_established: usize,
+    ) {
+        if peer_id != &self.peer || remaining_established > 0 {
             return;
         }
 
@@ -93,7 +108,7 @@ impl NetworkBehaviour for Behaviour {
         &mut self,
         cx: &mut Context<'_>,
         _: &mut impl PollParameters,
-    ) -> Poll<NetworkBehaviourAction<Self::OutEvent, Self::ProtocolsHandler>> {
+    ) -> Poll<NetworkBehaviourAction<Self::OutEvent, Self::ConnectionHandler>> {
         let sleep = match self.sleep.as_mut() {
             None => return Poll::Pending, // early exit if we shouldn't be re-dialling
             Some(future) => future,
@@ -112,10 +127,11 @@ impl NetworkBehaviour for Behaviour {
 
         self.sleep = Some(Box::pin(tokio::time::sleep(next_dial_in)));
 
-        Poll::Ready(NetworkBehaviourAction::DialPeer {
-            peer_id: self.peer,
-            condition: DialPeerCondition::Disconnected,
-            handler: Self::ProtocolsHandler::default(),
+        Poll::Ready(NetworkBehaviourAction::Dial {
+            opts: DialOpts::peer_id(self.peer)
+                .condition(PeerCondition::Disconnected)
+                .build(),
+            handler: Self::ConnectionHandler::default(),
         })
     }
 }
@@ -1,5 +1,8 @@
+use crate::bitcoin;
 use crate::monero;
-use anyhow::{Context, Result};
+use crate::protocol::sequence::{Message, MessageKind, Sequence};
+use crate::protocol::{Message0, Message1, Message2, Message3, Message4};
+use anyhow::{bail, Context, Result};
 use libp2p::core::upgrade;
 use libp2p::swarm::NegotiatedSubstream;
 use serde::de::DeserializeOwned;
@@ -17,9 +20,11 @@ pub mod protocol {
     use libp2p::swarm::NegotiatedSubstream;
     use void::Void;
 
+    pub const PROTOCOL: &str = "/comit/xmr/btc/swap_setup/2.0.0";
+
     pub fn new() -> SwapSetup {
         from_fn(
-            b"/comit/xmr/btc/swap_setup/1.0.0",
+            PROTOCOL.as_bytes(),
             Box::new(|socket, _| future::ready(Ok(socket))),
         )
     }
@@ -47,9 +52,14 @@ pub struct BlockchainNetwork {
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct SpotPriceRequest {
-    #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
     pub btc: bitcoin::Amount,
     pub blockchain_network: BlockchainNetwork,
+    /// The exact amount of XMR Bob wants for `btc`, if he asked for a swap
+    /// denominated in XMR.
+    ///
+    /// Left out by takers that only know swaps denominated in BTC.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub xmr: Option<monero::Amount>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -62,19 +72,18 @@ pub enum SpotPriceResponse {
 pub enum SpotPriceError {
     NoSwapsAccepted,
     AmountBelowMinimum {
-        #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
         min: bitcoin::Amount,
-        #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
         buy: bitcoin::Amount,
     },
     AmountAboveMaximum {
-        #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
         max: bitcoin::Amount,
-        #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
         buy: bitcoin::Amount,
     },
     BalanceTooLow {
-        #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
+        buy: bitcoin::Amount,
+    },
+    XmrAmountNotCovered {
+        xmr: monero::Amount,
         buy: bitcoin::Amount,
     },
     BlockchainNetworkMismatch {
@@ -86,24 +95,47 @@ pub enum SpotPriceError {
     Other,
 }
 
-pub async fn read_cbor_message<T>(substream: &mut NegotiatedSubstream) -> Result<T>
+/// Reads the next message of the swap setup, which must be a `T`.
+///
+/// A message that cannot be decoded as `T` but is another message of the swap
+/// setup is reported as such, as a peer that repeats a message or skips ahead
+/// would otherwise only show up as a decoding failure.
+pub async fn read_cbor_message<T>(
+    substream: &mut NegotiatedSubstream,
+    sequence: &mut Sequence,
+) -> Result<T>
 where
-    T: DeserializeOwned,
+    T: Message + DeserializeOwned,
 {
     let bytes = upgrade::read_length_prefixed(substream, BUF_SIZE)
         .await
         .context("Failed to read length-prefixed message from stream")?;
-    let mut de = serde_cbor::Deserializer::from_slice(&bytes);
-    let message =
-        T::deserialize(&mut de).context("Failed to deserialize bytes into message using CBOR")?;
 
-    Ok(message)
+    match decode::<T>(&bytes) {
+        Ok(message) => {
+            sequence.receive::<T>()?;
+            Ok(message)
+        }
+        Err(e) => match identify(&bytes) {
+            Some(kind) => {
+                sequence.receive_kind(kind)?;
+                bail!("Received {} instead of {}", kind, T::KIND)
+            }
+            None => Err(e).context("Failed to deserialize bytes into message using CBOR"),
+        },
+    }
 }
 
-pub async fn write_cbor_message<T>(substream: &mut NegotiatedSubstream, message: T) -> Result<()>
+pub async fn write_cbor_message<T>(
+    substream: &mut NegotiatedSubstream,
+    sequence: &mut Sequence,
+    message: T,
+) -> Result<()>
 where
-    T: Serialize,
+    T: Message + Serialize,
 {
+    sequence.send::<T>()?;
+
     let bytes =
         serde_cbor::to_vec(&message).context("Failed to serialize message as bytes using CBOR")?;
     upgrade::write_length_prefixed(substream, &bytes)
@@ -112,3 +144,39 @@ where
 
     Ok(())
 }
+
+/// Runs a step of the swap setup that proves, signs or verifies on the
+/// blocking thread pool.
+///
+/// The handlers are polled on the worker threads of the runtime, a burst of
+/// swap setups doing their cryptography there stalls the event loop and every
+/// other connection.
+pub async fn blocking<T, F>(step: F) -> Result<T>
+where
+    F: FnOnce() -> Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(step)
+        .await
+        .context("Swap setup step did not run to completion")?
+}
+
+fn decode<T: DeserializeOwned>(bytes: &[u8]) -> serde_cbor::Result<T> {
+    let mut de = serde_cbor::Deserializer::from_slice(bytes);
+    T::deserialize(&mut de)
+}
+
+/// Which message of the swap setup `bytes` decode as, if any.
+fn identify(bytes: &[u8]) -> Option<MessageKind> {
+    fn is<T: Message + DeserializeOwned>(bytes: &[u8]) -> Option<MessageKind> {
+        decode::<T>(bytes).ok().map(|_| T::KIND)
+    }
+
+    is::<SpotPriceRequest>(bytes)
+        .or_else(|| is::<SpotPriceResponse>(bytes))
+        .or_else(|| is::<Message0>(bytes))
+        .or_else(|| is::<Message1>(bytes))
+        .or_else(|| is::<Message2>(bytes))
+        .or_else(|| is::<Message3>(bytes))
+        .or_else(|| is::<Message4>(bytes))
+}
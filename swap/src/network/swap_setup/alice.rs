@@ -1,10 +1,14 @@
-use crate::asb::LatestRate;
+use crate::amounts::CheckedArithmetic;
+use crate::asb::policy::Verdict;
+use crate::asb::{LatestRate, TradingControls, TradingParameters};
 use crate::network::swap_setup;
 use crate::network::swap_setup::{
     protocol, BlockchainNetwork, SpotPriceError, SpotPriceRequest, SpotPriceResponse,
 };
 use crate::protocol::alice::{State0, State3};
+use crate::protocol::sequence::{self, MessageKind, Role, Sequence};
 use crate::protocol::{Message0, Message2, Message4};
+use crate::reservations::{Reservation, Reservations};
 use crate::{asb, bitcoin, env, monero};
 use anyhow::{anyhow, Context, Result};
 use futures::future::{BoxFuture, OptionFuture};
@@ -12,8 +16,9 @@ use futures::{AsyncWriteExt, FutureExt};
 use libp2p::core::connection::ConnectionId;
 use libp2p::core::upgrade;
 use libp2p::swarm::{
-    KeepAlive, NegotiatedSubstream, NetworkBehaviour, NetworkBehaviourAction, PollParameters,
-    ProtocolsHandler, ProtocolsHandlerEvent, ProtocolsHandlerUpgrErr, SubstreamProtocol,
+    ConnectionHandler, ConnectionHandlerEvent, ConnectionHandlerUpgrErr, KeepAlive,
+    NegotiatedSubstream, NetworkBehaviour, NetworkBehaviourAction, PollParameters,
+    SubstreamProtocol,
 };
 use libp2p::{Multiaddr, PeerId};
 use std::collections::VecDeque;
@@ -27,12 +32,16 @@ use void::Void;
 #[allow(clippy::large_enum_variant)]
 pub enum OutEvent {
     Initiated {
-        send_wallet_snapshot: bmrng::RequestReceiver<bitcoin::Amount, WalletSnapshot>,
+        peer_id: PeerId,
+        send_wallet_snapshot: bmrng::RequestReceiver<bitcoin::Amount, (WalletSnapshot, Verdict)>,
+        /// Asks whether the swap of Bob's first message was set up before.
+        set_up_before: bmrng::RequestReceiver<Uuid, bool>,
     },
     Completed {
         peer_id: PeerId,
         swap_id: Uuid,
         state3: State3,
+        reservation: Reservation<monero::Amount>,
     },
     Error {
         peer_id: PeerId,
@@ -44,6 +53,8 @@ pub enum OutEvent {
 pub struct WalletSnapshot {
     balance: monero::Amount,
     lock_fee: monero::Amount,
+    /// The Monero of the other swaps that is not locked yet.
+    reservations: Reservations<monero::Amount>,
 
     // TODO: Consider using the same address for punish and redeem (they are mutually exclusive, so
     // effectively the address will only be used once)
@@ -58,6 +69,7 @@ impl WalletSnapshot {
     pub async fn capture(
         bitcoin_wallet: &bitcoin::Wallet,
         monero_wallet: &monero::Wallet,
+        reservations: &Reservations<monero::Amount>,
         transfer_amount: bitcoin::Amount,
     ) -> Result<Self> {
         let balance = monero_wallet.get_balance().await?;
@@ -73,6 +85,7 @@ impl WalletSnapshot {
         Ok(Self {
             balance,
             lock_fee: monero::MONERO_FEE,
+            reservations: reservations.clone(),
             redeem_address,
             punish_address,
             redeem_fee,
@@ -85,22 +98,35 @@ impl From<OutEvent> for asb::OutEvent {
     fn from(event: OutEvent) -> Self {
         match event {
             OutEvent::Initiated {
+                peer_id,
                 send_wallet_snapshot,
+                set_up_before,
             } => asb::OutEvent::SwapSetupInitiated {
+                peer: peer_id,
                 send_wallet_snapshot,
+                set_up_before,
             },
             OutEvent::Completed {
                 peer_id: bob_peer_id,
                 swap_id,
                 state3,
+                reservation,
             } => asb::OutEvent::SwapSetupCompleted {
                 peer_id: bob_peer_id,
                 swap_id,
                 state3,
+                reservation,
             },
-            OutEvent::Error { peer_id, error } => asb::OutEvent::Failure {
-                peer: peer_id,
-                error: anyhow!(error),
+            // Rejections are told apart to record what became of the quote
+            OutEvent::Error { peer_id, error } => match error.downcast::<Error>() {
+                Ok(error) => asb::OutEvent::SwapDeclined {
+                    peer: peer_id,
+                    error,
+                },
+                Err(error) => asb::OutEvent::Failure {
+                    peer: peer_id,
+                    error: anyhow!(error),
+                },
             },
         }
     }
@@ -109,29 +135,19 @@ impl From<OutEvent> for asb::OutEvent {
 #[allow(missing_debug_implementations)]
 pub struct Behaviour<LR> {
     events: VecDeque<OutEvent>,
-    min_buy: bitcoin::Amount,
-    max_buy: bitcoin::Amount,
+    controls: TradingControls,
     env_config: env::Config,
 
     latest_rate: LR,
-    resume_only: bool,
 }
 
 impl<LR> Behaviour<LR> {
-    pub fn new(
-        min_buy: bitcoin::Amount,
-        max_buy: bitcoin::Amount,
-        env_config: env::Config,
-        latest_rate: LR,
-        resume_only: bool,
-    ) -> Self {
+    pub fn new(controls: TradingControls, env_config: env::Config, latest_rate: LR) -> Self {
         Self {
             events: Default::default(),
-            min_buy,
-            max_buy,
+            controls,
             env_config,
             latest_rate,
-            resume_only,
         }
     }
 }
@@ -140,16 +156,14 @@ impl<LR> NetworkBehaviour for Behaviour<LR>
 where
     LR: LatestRate + Send + 'static + Clone,
 {
-    type ProtocolsHandler = Handler<LR>;
+    type ConnectionHandler = Handler<LR>;
     type OutEvent = OutEvent;
 
-    fn new_handler(&mut self) -> Self::ProtocolsHandler {
+    fn new_handler(&mut self) -> Self::ConnectionHandler {
         Handler::new(
-            self.min_buy,
-            self.max_buy,
+            self.controls.clone(),
             self.env_config,
             self.latest_rate.clone(),
-            self.resume_only,
         )
     }
 
@@ -157,22 +171,22 @@ where
         Vec::new()
     }
 
-    fn inject_connected(&mut self, _: &PeerId) {}
-
-    fn inject_disconnected(&mut self, _: &PeerId) {}
-
     fn inject_event(&mut self, peer_id: PeerId, _: ConnectionId, event: HandlerOutEvent) {
         match event {
-            HandlerOutEvent::Initiated(send_wallet_snapshot) => {
-                self.events.push_back(OutEvent::Initiated {
-                    send_wallet_snapshot,
-                })
-            }
-            HandlerOutEvent::Completed(Ok((swap_id, state3))) => {
+            HandlerOutEvent::Initiated {
+                send_wallet_snapshot,
+                set_up_before,
+            } => self.events.push_back(OutEvent::Initiated {
+                peer_id,
+                send_wallet_snapshot,
+                set_up_before,
+            }),
+            HandlerOutEvent::Completed(Ok((swap_id, state3, reservation))) => {
                 self.events.push_back(OutEvent::Completed {
                     peer_id,
                     swap_id,
                     state3,
+                    reservation,
                 })
             }
             HandlerOutEvent::Completed(Err(error)) => {
@@ -185,7 +199,7 @@ where
         &mut self,
         _cx: &mut std::task::Context<'_>,
         _params: &mut impl PollParameters,
-    ) -> Poll<NetworkBehaviourAction<Self::OutEvent, Self::ProtocolsHandler>> {
+    ) -> Poll<NetworkBehaviourAction<Self::OutEvent, Self::ConnectionHandler>> {
         if let Some(event) = self.events.pop_front() {
             return Poll::Ready(NetworkBehaviourAction::GenerateEvent(event));
         }
@@ -194,39 +208,29 @@ where
     }
 }
 
-type InboundStream = BoxFuture<'static, Result<(Uuid, State3)>>;
+type InboundStream = BoxFuture<'static, Result<(Uuid, State3, Reservation<monero::Amount>)>>;
 
 pub struct Handler<LR> {
     inbound_stream: OptionFuture<InboundStream>,
     events: VecDeque<HandlerOutEvent>,
 
-    min_buy: bitcoin::Amount,
-    max_buy: bitcoin::Amount,
+    controls: TradingControls,
     env_config: env::Config,
 
     latest_rate: LR,
-    resume_only: bool,
 
     timeout: Duration,
     keep_alive: KeepAlive,
 }
 
 impl<LR> Handler<LR> {
-    fn new(
-        min_buy: bitcoin::Amount,
-        max_buy: bitcoin::Amount,
-        env_config: env::Config,
-        latest_rate: LR,
-        resume_only: bool,
-    ) -> Self {
+    fn new(controls: TradingControls, env_config: env::Config, latest_rate: LR) -> Self {
         Self {
             inbound_stream: OptionFuture::from(None),
             events: Default::default(),
-            min_buy,
-            max_buy,
+            controls,
             env_config,
             latest_rate,
-            resume_only,
             timeout: Duration::from_secs(120),
             keep_alive: KeepAlive::Until(Instant::now() + Duration::from_secs(10)),
         }
@@ -236,11 +240,14 @@ impl<LR> Handler<LR> {
 #[allow(clippy::large_enum_variant)]
 #[derive(Debug)]
 pub enum HandlerOutEvent {
-    Initiated(bmrng::RequestReceiver<bitcoin::Amount, WalletSnapshot>),
-    Completed(Result<(Uuid, State3)>),
+    Initiated {
+        send_wallet_snapshot: bmrng::RequestReceiver<bitcoin::Amount, (WalletSnapshot, Verdict)>,
+        set_up_before: bmrng::RequestReceiver<Uuid, bool>,
+    },
+    Completed(Result<(Uuid, State3, Reservation<monero::Amount>)>),
 }
 
-impl<LR> ProtocolsHandler for Handler<LR>
+impl<LR> ConnectionHandler for Handler<LR>
 where
     LR: LatestRate + Send + 'static,
 {
@@ -263,22 +270,40 @@ where
     ) {
         self.keep_alive = KeepAlive::Yes;
 
-        let (sender, receiver) = bmrng::channel_with_timeout::<bitcoin::Amount, WalletSnapshot>(
-            1,
-            Duration::from_secs(5),
-        );
-        let resume_only = self.resume_only;
-        let min_buy = self.min_buy;
-        let max_buy = self.max_buy;
+        let (sender, receiver) = bmrng::channel_with_timeout::<
+            bitcoin::Amount,
+            (WalletSnapshot, Verdict),
+        >(1, Duration::from_secs(5));
+        let (set_up_before_sender, set_up_before) =
+            bmrng::channel_with_timeout::<Uuid, bool>(1, Duration::from_secs(5));
+        let controls = self.controls.clone();
         let latest_rate = self.latest_rate.latest_rate();
         let env_config = self.env_config;
 
         let protocol = tokio::time::timeout(self.timeout, async move {
-            let request = swap_setup::read_cbor_message::<SpotPriceRequest>(&mut substream)
+            let mut sequence = Sequence::new(Role::Alice);
+
+            let request =
+                swap_setup::read_cbor_message::<SpotPriceRequest>(&mut substream, &mut sequence)
+                    .await
+                    .context("Failed to read spot price request")?;
+
+            // Refused before asking for the wallet snapshot, which needs both
+            // wallets
+            if let Some(reason) = controls.overloaded() {
+                let error = Error::Overloaded(reason);
+                swap_setup::write_cbor_message(
+                    &mut substream,
+                    &mut sequence,
+                    SpotPriceResponse::from_result_ref(Err(&error)),
+                )
                 .await
-                .context("Failed to read spot price request")?;
+                .context("Failed to write spot price response")?;
 
-            let wallet_snapshot = sender
+                return Err(error.into());
+            }
+
+            let (wallet_snapshot, verdict) = sender
                 .send_receive(request.btc)
                 .await
                 .context("Failed to receive wallet snapshot")?;
@@ -286,10 +311,22 @@ where
             // wrap all of these into another future so we can `return` from all the
             // different blocks
             let validate = async {
-                if resume_only {
+                let TradingParameters {
+                    paused,
+                    min_buy,
+                    max_buy,
+                    ..
+                } = controls.parameters();
+
+                // Starting in resume-only mode is the same as starting paused.
+                if paused {
                     return Err(Error::ResumeOnlyMode);
                 };
 
+                if let Some(reason) = controls.degraded() {
+                    return Err(Error::BackendDegraded(reason));
+                }
+
                 let blockchain_network = BlockchainNetwork {
                     bitcoin: env_config.bitcoin_network,
                     monero: env_config.monero_network,
@@ -319,70 +356,140 @@ where
                 }
 
                 let rate = latest_rate.map_err(|e| Error::LatestRateFetchFailed(Box::new(e)))?;
+                let rate = match verdict {
+                    Verdict::Accept => rate,
+                    Verdict::Reject { reason } => return Err(Error::RefusedByPolicy(reason)),
+                    Verdict::Modify { ask_spread } => rate.with_spread(ask_spread),
+                };
                 let xmr = rate
                     .sell_quote(btc)
                     .map_err(Error::SellQuoteCalculationFailed)?;
 
-                if wallet_snapshot.balance < xmr + wallet_snapshot.lock_fee {
-                    return Err(Error::BalanceTooLow {
-                        balance: wallet_snapshot.balance,
-                        buy: btc,
+                // Bob pays at least what the XMR he asked for costs at our price and
+                // gets exactly that amount, the surplus of rounding up stays with us
+                let xmr = match request.xmr {
+                    Some(requested) if requested > xmr => {
+                        return Err(Error::XmrAmountNotCovered {
+                            xmr: requested,
+                            buy: btc,
+                        });
+                    }
+                    Some(requested) => requested,
+                    None => xmr,
+                };
+
+                // The Monero is reserved right away, so swaps set up at the same
+                // time cannot both count on the same balance. An amount that does
+                // not even fit into a u64 exceeds any balance.
+                let reservation = xmr
+                    .try_add(wallet_snapshot.lock_fee)
+                    .ok()
+                    .and_then(|needed| {
+                        wallet_snapshot
+                            .reservations
+                            .reserve(needed, wallet_snapshot.balance)
                     });
-                }
 
-                Ok(xmr)
+                match reservation {
+                    Some(reservation) => Ok((xmr, reservation)),
+                    None => Err(Error::BalanceTooLow {
+                        balance: wallet_snapshot
+                            .reservations
+                            .available(wallet_snapshot.balance)
+                            .unwrap_or(monero::Amount::ZERO),
+                        buy: btc,
+                    }),
+                }
             };
 
             let result = validate.await;
 
             swap_setup::write_cbor_message(
                 &mut substream,
-                SpotPriceResponse::from_result_ref(&result),
+                &mut sequence,
+                SpotPriceResponse::from_result_ref(result.as_ref().map(|(xmr, _)| *xmr)),
             )
             .await
             .context("Failed to write spot price response")?;
 
-            let xmr = result?;
+            let (xmr, reservation) = result?;
+
+            // Proving the DLEQ relation does not depend on Bob's first message,
+            // it runs while we wait for it
+            let create_state0 = swap_setup::blocking(move || {
+                Ok(State0::new(
+                    request.btc,
+                    xmr,
+                    env_config,
+                    wallet_snapshot.redeem_address,
+                    wallet_snapshot.punish_address,
+                    wallet_snapshot.redeem_fee,
+                    wallet_snapshot.punish_fee,
+                    &mut rand::thread_rng(),
+                ))
+            });
+            let read_message0 = async {
+                swap_setup::read_cbor_message::<Message0>(&mut substream, &mut sequence)
+                    .await
+                    .context("Failed to read message0")
+            };
+            let (state0, message0) = tokio::try_join!(create_state0, read_message0)?;
+
+            // A swap that was set up before is never set up again, doing so would
+            // replace the transactions we presigned for it. Message1 to Message4
+            // follow on the same substream, so this also rejects them.
+            let swap_id = message0.swap_id();
+            let set_up_before = set_up_before_sender
+                .send_receive(swap_id)
+                .await
+                .context("Failed to check whether the swap was set up before")?;
+            if set_up_before {
+                return Err(sequence::Error::Duplicate {
+                    message: MessageKind::Message0,
+                })
+                .with_context(|| format!("Swap {} was set up before", swap_id));
+            }
 
-            let state0 = State0::new(
-                request.btc,
-                xmr,
-                env_config,
-                wallet_snapshot.redeem_address,
-                wallet_snapshot.punish_address,
-                wallet_snapshot.redeem_fee,
-                wallet_snapshot.punish_fee,
-                &mut rand::thread_rng(),
-            );
+            let (swap_id, state1, message1) = swap_setup::blocking(move || {
+                let (swap_id, state1) = state0
+                    .receive(message0)
+                    .context("Failed to transition state0 -> state1 using message0")?;
+                let message1 = state1.next_message();
 
-            let message0 = swap_setup::read_cbor_message::<Message0>(&mut substream)
-                .await
-                .context("Failed to read message0")?;
-            let (swap_id, state1) = state0
-                .receive(message0)
-                .context("Failed to transition state0 -> state1 using message0")?;
+                Ok((swap_id, state1, message1))
+            })
+            .await?;
 
-            swap_setup::write_cbor_message(&mut substream, state1.next_message())
+            swap_setup::write_cbor_message(&mut substream, &mut sequence, message1)
                 .await
                 .context("Failed to send message1")?;
 
-            let message2 = swap_setup::read_cbor_message::<Message2>(&mut substream)
+            let message2 = swap_setup::read_cbor_message::<Message2>(&mut substream, &mut sequence)
                 .await
                 .context("Failed to read message2")?;
-            let state2 = state1
-                .receive(message2)
-                .context("Failed to transition state1 -> state2 using message2")?;
+            let (state2, message3) = swap_setup::blocking(move || {
+                let state2 = state1
+                    .receive(message2)
+                    .context("Failed to transition state1 -> state2 using message2")?;
+                let message3 = state2.next_message();
+
+                Ok((state2, message3))
+            })
+            .await?;
 
-            swap_setup::write_cbor_message(&mut substream, state2.next_message())
+            swap_setup::write_cbor_message(&mut substream, &mut sequence, message3)
                 .await
                 .context("Failed to send message3")?;
 
-            let message4 = swap_setup::read_cbor_message::<Message4>(&mut substream)
+            let message4 = swap_setup::read_cbor_message::<Message4>(&mut substream, &mut sequence)
                 .await
                 .context("Failed to read message4")?;
-            let state3 = state2
-                .receive(message4)
-                .context("Failed to transition state2 -> state3 using message4")?;
+            let state3 = swap_setup::blocking(move || {
+                state2
+                    .receive(message4)
+                    .context("Failed to transition state2 -> state3 using message4")
+            })
+            .await?;
 
             substream
                 .flush()
@@ -393,7 +500,7 @@ where
                 .await
                 .context("Failed to close substream after all messages were sent")?;
 
-            Ok((swap_id, state3))
+            Ok((swap_id, state3, reservation))
         });
 
         let max_seconds = self.timeout.as_secs();
@@ -406,7 +513,10 @@ where
             .boxed(),
         ));
 
-        self.events.push_back(HandlerOutEvent::Initiated(receiver));
+        self.events.push_back(HandlerOutEvent::Initiated {
+            send_wallet_snapshot: receiver,
+            set_up_before,
+        });
     }
 
     fn inject_fully_negotiated_outbound(&mut self, _: Void, _: Self::OutboundOpenInfo) {
@@ -420,7 +530,7 @@ where
     fn inject_dial_upgrade_error(
         &mut self,
         _: Self::OutboundOpenInfo,
-        _: ProtocolsHandlerUpgrErr<Void>,
+        _: ConnectionHandlerUpgrErr<Void>,
     ) {
         unreachable!("Alice does not dial")
     }
@@ -434,7 +544,7 @@ where
         &mut self,
         cx: &mut std::task::Context<'_>,
     ) -> Poll<
-        ProtocolsHandlerEvent<
+        ConnectionHandlerEvent<
             Self::OutboundProtocol,
             Self::OutboundOpenInfo,
             Self::OutEvent,
@@ -442,12 +552,12 @@ where
         >,
     > {
         if let Some(event) = self.events.pop_front() {
-            return Poll::Ready(ProtocolsHandlerEvent::Custom(event));
+            return Poll::Ready(ConnectionHandlerEvent::Custom(event));
         }
 
         if let Some(result) = futures::ready!(self.inbound_stream.poll_unpin(cx)) {
             self.inbound_stream = OptionFuture::from(None);
-            return Poll::Ready(ProtocolsHandlerEvent::Custom(HandlerOutEvent::Completed(
+            return Poll::Ready(ConnectionHandlerEvent::Custom(HandlerOutEvent::Completed(
                 result,
             )));
         }
@@ -457,9 +567,9 @@ where
 }
 
 impl SpotPriceResponse {
-    pub fn from_result_ref(result: &Result<monero::Amount, Error>) -> Self {
+    pub fn from_result_ref(result: Result<monero::Amount, &Error>) -> Self {
         match result {
-            Ok(amount) => SpotPriceResponse::Xmr(*amount),
+            Ok(amount) => SpotPriceResponse::Xmr(amount),
             Err(error) => SpotPriceResponse::Error(error.to_error_response()),
         }
     }
@@ -467,8 +577,14 @@ impl SpotPriceResponse {
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
-    #[error("ASB is running in resume-only mode")]
+    #[error("ASB is running in resume-only mode or trading is paused")]
     ResumeOnlyMode,
+    #[error("Swap refused by policy: {0}")]
+    RefusedByPolicy(String),
+    #[error("Trading is suspended while a backend is degraded: {0}")]
+    BackendDegraded(String),
+    #[error("Trading is suspended while the ASB is overloaded: {0}")]
+    Overloaded(String),
     #[error("Amount {buy} below minimum {min}")]
     AmountBelowMinimum {
         min: bitcoin::Amount,
@@ -484,6 +600,11 @@ pub enum Error {
         balance: monero::Amount,
         buy: bitcoin::Amount,
     },
+    #[error("Amount {buy} does not cover the requested {xmr}")]
+    XmrAmountNotCovered {
+        xmr: monero::Amount,
+        buy: bitcoin::Amount,
+    },
     #[error("Failed to fetch latest rate")]
     LatestRateFetchFailed(#[source] Box<dyn std::error::Error + Send + Sync + 'static>),
     #[error("Failed to calculate quote")]
@@ -498,7 +619,11 @@ pub enum Error {
 impl Error {
     pub fn to_error_response(&self) -> SpotPriceError {
         match self {
-            Error::ResumeOnlyMode => SpotPriceError::NoSwapsAccepted,
+            // The reason is meant for the operator, not for the taker
+            Error::ResumeOnlyMode
+            | Error::RefusedByPolicy(_)
+            | Error::BackendDegraded(_)
+            | Error::Overloaded(_) => SpotPriceError::NoSwapsAccepted,
             Error::AmountBelowMinimum { min, buy } => SpotPriceError::AmountBelowMinimum {
                 min: *min,
                 buy: *buy,
@@ -508,6 +633,10 @@ impl Error {
                 buy: *buy,
             },
             Error::BalanceTooLow { buy, .. } => SpotPriceError::BalanceTooLow { buy: *buy },
+            Error::XmrAmountNotCovered { xmr, buy } => SpotPriceError::XmrAmountNotCovered {
+                xmr: *xmr,
+                buy: *buy,
+            },
             Error::BlockchainNetworkMismatch { cli, asb } => {
                 SpotPriceError::BlockchainNetworkMismatch {
                     cli: *cli,
@@ -1,18 +1,20 @@
+use crate::amounts::Quote;
 use crate::network::swap_setup::{
-    protocol, read_cbor_message, write_cbor_message, BlockchainNetwork, SpotPriceError,
+    blocking, protocol, read_cbor_message, write_cbor_message, BlockchainNetwork, SpotPriceError,
     SpotPriceRequest, SpotPriceResponse,
 };
 use crate::protocol::bob::{State0, State2};
+use crate::protocol::sequence::{Role, Sequence};
 use crate::protocol::{Message1, Message3};
 use crate::{bitcoin, cli, env, monero};
-use anyhow::Result;
-use futures::future::{BoxFuture, OptionFuture};
+use anyhow::{anyhow, Result};
+use futures::future::{self, BoxFuture, OptionFuture};
 use futures::{AsyncWriteExt, FutureExt};
 use libp2p::core::connection::ConnectionId;
 use libp2p::core::upgrade;
 use libp2p::swarm::{
-    KeepAlive, NegotiatedSubstream, NetworkBehaviour, NetworkBehaviourAction, NotifyHandler,
-    PollParameters, ProtocolsHandler, ProtocolsHandlerEvent, ProtocolsHandlerUpgrErr,
+    ConnectionHandler, ConnectionHandlerEvent, ConnectionHandlerUpgrErr, KeepAlive,
+    NegotiatedSubstream, NetworkBehaviour, NetworkBehaviourAction, NotifyHandler, PollParameters,
     SubstreamProtocol,
 };
 use libp2p::{Multiaddr, PeerId};
@@ -53,10 +55,10 @@ impl From<Completed> for cli::OutEvent {
 }
 
 impl NetworkBehaviour for Behaviour {
-    type ProtocolsHandler = Handler;
+    type ConnectionHandler = Handler;
     type OutEvent = Completed;
 
-    fn new_handler(&mut self) -> Self::ProtocolsHandler {
+    fn new_handler(&mut self) -> Self::ConnectionHandler {
         Handler::new(self.env_config, self.bitcoin_wallet.clone())
     }
 
@@ -64,10 +66,6 @@ impl NetworkBehaviour for Behaviour {
         Vec::new()
     }
 
-    fn inject_connected(&mut self, _: &PeerId) {}
-
-    fn inject_disconnected(&mut self, _: &PeerId) {}
-
     fn inject_event(&mut self, peer: PeerId, _: ConnectionId, completed: Completed) {
         self.completed_swaps.push_back((peer, completed));
     }
@@ -76,7 +74,7 @@ impl NetworkBehaviour for Behaviour {
         &mut self,
         _cx: &mut Context<'_>,
         _params: &mut impl PollParameters,
-    ) -> Poll<NetworkBehaviourAction<Self::OutEvent, Self::ProtocolsHandler>> {
+    ) -> Poll<NetworkBehaviourAction<Self::OutEvent, Self::ConnectionHandler>> {
         if let Some((_, event)) = self.completed_swaps.pop_front() {
             return Poll::Ready(NetworkBehaviourAction::GenerateEvent(event));
         }
@@ -121,6 +119,8 @@ impl Handler {
 pub struct NewSwap {
     pub swap_id: Uuid,
     pub btc: bitcoin::Amount,
+    /// The exact amount of XMR to ask for, if the swap is denominated in XMR.
+    pub xmr: Option<monero::Amount>,
     pub tx_refund_fee: bitcoin::Amount,
     pub tx_cancel_fee: bitcoin::Amount,
     pub bitcoin_refund_address: bitcoin::Address,
@@ -129,7 +129,7 @@ pub struct NewSwap {
 #[derive(Debug)]
 pub struct Completed(Result<State2>);
 
-impl ProtocolsHandler for Handler {
+impl ConnectionHandler for Handler {
     type InEvent = NewSwap;
     type OutEvent = Completed;
     type Error = Void;
@@ -155,16 +155,33 @@ impl ProtocolsHandler for Handler {
         let env_config = self.env_config;
 
         let protocol = tokio::time::timeout(self.timeout, async move {
-            write_cbor_message(&mut substream, SpotPriceRequest {
+            let mut sequence = Sequence::new(Role::Bob);
+
+            write_cbor_message(&mut substream, &mut sequence, SpotPriceRequest {
                 btc: info.btc,
                 blockchain_network: BlockchainNetwork {
                     bitcoin: env_config.bitcoin_network,
                     monero: env_config.monero_network,
                 },
+                xmr: info.xmr,
             })
             .await?;
 
-            let xmr = Result::from(read_cbor_message::<SpotPriceResponse>(&mut substream).await?)?;
+            let xmr = Result::from(
+                read_cbor_message::<SpotPriceResponse>(&mut substream, &mut sequence).await?,
+            )?;
+
+            // A seller that does not know about swaps denominated in XMR quotes `btc` at its
+            // current price, which may since have moved against us
+            if let Some(requested) = info.xmr {
+                if xmr < requested {
+                    return Err(Error::XmrAmountNotCovered {
+                        xmr: requested,
+                        buy: info.btc,
+                    }
+                    .into());
+                }
+            }
 
             let state0 = State0::new(
                 info.swap_id,
@@ -179,15 +196,21 @@ impl ProtocolsHandler for Handler {
                 info.tx_cancel_fee,
             );
 
-            write_cbor_message(&mut substream, state0.next_message()).await?;
-            let message1 = read_cbor_message::<Message1>(&mut substream).await?;
+            write_cbor_message(&mut substream, &mut sequence, state0.next_message()).await?;
+            let message1 = read_cbor_message::<Message1>(&mut substream, &mut sequence).await?;
             let state1 = state0.receive(bitcoin_wallet.as_ref(), message1).await?;
 
-            write_cbor_message(&mut substream, state1.next_message()).await?;
-            let message3 = read_cbor_message::<Message3>(&mut substream).await?;
-            let state2 = state1.receive(message3)?;
+            write_cbor_message(&mut substream, &mut sequence, state1.next_message()).await?;
+            let message3 = read_cbor_message::<Message3>(&mut substream, &mut sequence).await?;
+            let (state2, message4) = blocking(move || {
+                let state2 = state1.receive(message3)?;
+                let message4 = state2.next_message();
+
+                Ok((state2, message4))
+            })
+            .await?;
 
-            write_cbor_message(&mut substream, state2.next_message()).await?;
+            write_cbor_message(&mut substream, &mut sequence, message4).await?;
 
             substream.flush().await?;
             substream.close().await?;
@@ -213,8 +236,10 @@ impl ProtocolsHandler for Handler {
     fn inject_dial_upgrade_error(
         &mut self,
         _: Self::OutboundOpenInfo,
-        _: ProtocolsHandlerUpgrErr<Void>,
+        error: ConnectionHandlerUpgrErr<Void>,
     ) {
+        let error = anyhow!("Failed to open swap setup substream: {}", error);
+        self.outbound_stream = OptionFuture::from(Some(future::ready(Err(error)).boxed()));
     }
 
     fn connection_keep_alive(&self) -> KeepAlive {
@@ -226,7 +251,7 @@ impl ProtocolsHandler for Handler {
         &mut self,
         cx: &mut Context<'_>,
     ) -> Poll<
-        ProtocolsHandlerEvent<
+        ConnectionHandlerEvent<
             Self::OutboundProtocol,
             Self::OutboundOpenInfo,
             Self::OutEvent,
@@ -235,14 +260,14 @@ impl ProtocolsHandler for Handler {
     > {
         if let Some(new_swap) = self.new_swaps.pop_front() {
             self.keep_alive = KeepAlive::Yes;
-            return Poll::Ready(ProtocolsHandlerEvent::OutboundSubstreamRequest {
+            return Poll::Ready(ConnectionHandlerEvent::OutboundSubstreamRequest {
                 protocol: SubstreamProtocol::new(protocol::new(), new_swap),
             });
         }
 
         if let Some(result) = futures::ready!(self.outbound_stream.poll_unpin(cx)) {
             self.outbound_stream = OptionFuture::from(None);
-            return Poll::Ready(ProtocolsHandlerEvent::Custom(Completed(result)));
+            return Poll::Ready(ConnectionHandlerEvent::Custom(Completed(result)));
         }
 
         Poll::Pending
@@ -274,6 +299,11 @@ pub enum Error {
     },
     #[error("Seller's XMR balance is currently too low to fulfill the swap request to buy {buy}, please try again later")]
     BalanceTooLow { buy: bitcoin::Amount },
+    #[error("Seller refused to sell {xmr} for {buy}, the price has likely risen since the quote, please try again")]
+    XmrAmountNotCovered {
+        xmr: monero::Amount,
+        buy: bitcoin::Amount,
+    },
 
     #[error("Seller blockchain network {asb:?} setup did not match your blockchain network setup {cli:?}")]
     BlockchainNetworkMismatch {
@@ -290,6 +320,38 @@ pub enum Error {
     Other,
 }
 
+impl Error {
+    /// Whether the seller refused the swap, which does not change by retrying
+    /// right away.
+    pub fn is_rejection(&self) -> bool {
+        !matches!(self, Error::Timeout { .. } | Error::Other)
+    }
+}
+
+/// Fails with the rejection the seller would answer a swap setup for `btc`
+/// with, judging by its latest quote.
+pub fn check_quote(quote: &Quote, btc: bitcoin::Amount) -> Result<(), Error> {
+    if quote.max_quantity == bitcoin::Amount::ZERO {
+        return Err(Error::NoSwapsAccepted);
+    }
+
+    if btc < quote.min_quantity {
+        return Err(Error::AmountBelowMinimum {
+            min: quote.min_quantity,
+            buy: btc,
+        });
+    }
+
+    if btc > quote.max_quantity {
+        return Err(Error::AmountAboveMaximum {
+            max: quote.max_quantity,
+            buy: btc,
+        });
+    }
+
+    Ok(())
+}
+
 impl From<SpotPriceError> for Error {
     fn from(error: SpotPriceError) -> Self {
         match error {
@@ -301,6 +363,9 @@ impl From<SpotPriceError> for Error {
                 Error::AmountAboveMaximum { max, buy }
             }
             SpotPriceError::BalanceTooLow { buy } => Error::BalanceTooLow { buy },
+            SpotPriceError::XmrAmountNotCovered { xmr, buy } => {
+                Error::XmrAmountNotCovered { xmr, buy }
+            }
             SpotPriceError::BlockchainNetworkMismatch { cli, asb } => {
                 Error::BlockchainNetworkMismatch { cli, asb }
             }
@@ -308,3 +373,39 @@ impl From<SpotPriceError> for Error {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::amounts::Price;
+
+    #[test]
+    fn latest_quote_predicts_rejection() {
+        let quote = |min, max| Quote {
+            price: Price::new(bitcoin::Amount::from_sat(500_000)),
+            min_quantity: bitcoin::Amount::from_sat(min),
+            max_quantity: bitcoin::Amount::from_sat(max),
+        };
+        let btc = bitcoin::Amount::from_sat(20_000);
+
+        assert_eq!(
+            check_quote(&quote(10_000, 0), btc),
+            Err(Error::NoSwapsAccepted)
+        );
+        assert_eq!(
+            check_quote(&quote(30_000, 100_000), btc),
+            Err(Error::AmountBelowMinimum {
+                min: bitcoin::Amount::from_sat(30_000),
+                buy: btc,
+            })
+        );
+        assert_eq!(
+            check_quote(&quote(10_000, 15_000), btc),
+            Err(Error::AmountAboveMaximum {
+                max: bitcoin::Amount::from_sat(15_000),
+                buy: btc,
+            })
+        );
+        assert_eq!(check_quote(&quote(10_000, 100_000), btc), Ok(()));
+    }
+}
@@ -0,0 +1,326 @@
+//! Resynchronization of a swap after Bob reconnected to Alice.
+//!
+//! Both sides exchange a compact [`Status`] of the swap: the phase they
+//! consider completed and the ids of the lock transactions they know about.
+//! Each side then [`resolve`]s its own status against the one of the peer to
+//! find out whether a message got lost while they were disconnected. When the
+//! statuses contradict each other the swap is resolved towards the safer
+//! state: no further secrets are handed out and the timelocks decide.
+
+use crate::network::codec::{self, Codec};
+use crate::network::connection;
+use crate::protocol::alice::AliceState;
+use crate::protocol::bob::BobState;
+use crate::{asb, bitcoin, cli};
+use libp2p::core::ProtocolName;
+use libp2p::request_response::{
+    ProtocolSupport, RequestResponse, RequestResponseEvent, RequestResponseMessage,
+};
+use libp2p::PeerId;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+const PROTOCOL: &str = "/comit/xmr/btc/swap_status/1.0.0";
+type OutEvent = RequestResponseEvent<Status, Status>;
+type Message = RequestResponseMessage<Status, Status>;
+
+pub type Behaviour = RequestResponse<Codec<SwapStatusProtocol, Status, Status>>;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SwapStatusProtocol;
+
+impl ProtocolName for SwapStatusProtocol {
+    fn protocol_name(&self) -> &[u8] {
+        PROTOCOL.as_bytes()
+    }
+}
+
+/// The last phase of the protocol a party completed.
+///
+/// The phases of the happy path are ordered by their progress, the phases
+/// after [`Phase::BtcRedeemed`] belong to the cancel path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    /// The party does not know the swap.
+    Unknown,
+    SetupCompleted,
+    BtcLocked,
+    /// Alice locked the Monero and sent the transfer proof.
+    XmrLocked,
+    /// Bob sent the encrypted signature.
+    EncSigExchanged,
+    BtcRedeemed,
+    Cancelled,
+    Refunded,
+    Punished,
+    Aborted,
+}
+
+impl Phase {
+    fn is_happy_path(self) -> bool {
+        self <= Phase::BtcRedeemed
+    }
+}
+
+impl From<&AliceState> for Phase {
+    fn from(state: &AliceState) -> Self {
+        match state {
+            AliceState::Started { .. } | AliceState::BtcLockTransactionSeen { .. } => {
+                Phase::SetupCompleted
+            }
+            AliceState::BtcLocked { .. } => Phase::BtcLocked,
+            AliceState::XmrLockTransactionSent { .. }
+            | AliceState::XmrLocked { .. }
+            | AliceState::XmrLockTransferProofSent { .. } => Phase::XmrLocked,
+            AliceState::EncSigLearned { .. } => Phase::EncSigExchanged,
+            AliceState::BtcRedeemTransactionPublished { .. } | AliceState::BtcRedeemed => {
+                Phase::BtcRedeemed
+            }
+            AliceState::CancelTimelockExpired { .. }
+            | AliceState::BtcCancelled { .. }
+            | AliceState::BtcPunishable { .. } => Phase::Cancelled,
+            AliceState::BtcRefunded { .. } | AliceState::XmrRefunded => Phase::Refunded,
+            AliceState::BtcPunished { .. } => Phase::Punished,
+            AliceState::SafelyAborted => Phase::Aborted,
+        }
+    }
+}
+
+impl From<&BobState> for Phase {
+    fn from(state: &BobState) -> Self {
+        match state {
+            BobState::Started { .. } => Phase::Unknown,
+            BobState::SwapSetupCompleted(..) => Phase::SetupCompleted,
+            BobState::BtcLocked { .. } => Phase::BtcLocked,
+            BobState::XmrLockProofReceived { .. } | BobState::XmrLocked(..) => Phase::XmrLocked,
+            BobState::EncSigSent(..) => Phase::EncSigExchanged,
+            BobState::BtcRedeemed(..) | BobState::XmrRedeemed { .. } => Phase::BtcRedeemed,
+            BobState::CancelTimelockExpired(..) | BobState::BtcCancelled(..) => Phase::Cancelled,
+            BobState::BtcRefunded(..) => Phase::Refunded,
+            BobState::BtcPunished { .. } => Phase::Punished,
+            BobState::SafelyAborted => Phase::Aborted,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Status {
+    pub swap_id: Uuid,
+    pub phase: Phase,
+    pub tx_lock_id: Option<bitcoin::Txid>,
+    pub xmr_lock_tx_hash: Option<String>,
+}
+
+impl Status {
+    pub fn unknown(swap_id: Uuid) -> Self {
+        Self {
+            swap_id,
+            phase: Phase::Unknown,
+            tx_lock_id: None,
+            xmr_lock_tx_hash: None,
+        }
+    }
+
+    pub fn alice(swap_id: Uuid, state: &AliceState) -> Self {
+        Self {
+            swap_id,
+            phase: state.into(),
+            tx_lock_id: state.tx_lock_id(),
+            xmr_lock_tx_hash: state.transfer_proof().map(|proof| proof.tx_hash().0),
+        }
+    }
+
+    /// Bob forgets the transfer proof once the Monero is locked, his status
+    /// only contains its hash while he waits for the confirmations.
+    pub fn bob(swap_id: Uuid, state: &BobState) -> Self {
+        Self {
+            swap_id,
+            phase: state.into(),
+            tx_lock_id: state.tx_lock_id(),
+            xmr_lock_tx_hash: state.xmr_lock_proof().map(|proof| proof.tx_hash().0),
+        }
+    }
+
+    /// Whether the swap is still ongoing and worth resynchronizing.
+    pub fn is_ongoing(&self) -> bool {
+        self.phase != Phase::Unknown && self.phase < Phase::BtcRedeemed
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Resolution {
+    InSync,
+    /// The peer missed a message we sent, it still is in the given phase.
+    PeerBehind(Phase),
+    /// We missed a message of the peer, which already is in the given phase.
+    PeerAhead(Phase),
+    /// The peer is about to cancel the swap, so do not make further progress
+    /// on the happy path.
+    Cancel,
+    /// The statuses contradict each other.
+    Conflict(&'static str),
+}
+
+impl Resolution {
+    /// Whether the swap must not make further progress on the happy path.
+    pub fn is_unsafe(&self) -> bool {
+        matches!(self, Resolution::Cancel | Resolution::Conflict(_))
+    }
+}
+
+/// Resolves our status against the one of the peer.
+pub fn resolve(own: &Status, peer: &Status) -> Resolution {
+    if own.swap_id != peer.swap_id {
+        return Resolution::Conflict("status belongs to another swap");
+    }
+    if peer.phase == Phase::Unknown {
+        return Resolution::Conflict("peer does not know the swap");
+    }
+    if contradict(&own.tx_lock_id, &peer.tx_lock_id) {
+        return Resolution::Conflict("peer knows a different Bitcoin lock transaction");
+    }
+    if contradict(&own.xmr_lock_tx_hash, &peer.xmr_lock_tx_hash) {
+        return Resolution::Conflict("peer knows a different Monero lock transaction");
+    }
+    if peer.phase == Phase::Aborted && own.phase != Phase::Aborted {
+        return Resolution::Conflict("peer aborted the swap");
+    }
+    if !peer.phase.is_happy_path() && own.phase < Phase::BtcRedeemed {
+        return Resolution::Cancel;
+    }
+    // Phases of different paths cannot be compared by their progress
+    if !own.phase.is_happy_path() || !peer.phase.is_happy_path() || own.phase == peer.phase {
+        return Resolution::InSync;
+    }
+
+    if own.phase > peer.phase {
+        Resolution::PeerBehind(peer.phase)
+    } else {
+        Resolution::PeerAhead(peer.phase)
+    }
+}
+
+fn contradict<T: PartialEq>(own: &Option<T>, peer: &Option<T>) -> bool {
+    matches!((own, peer), (Some(own), Some(peer)) if own != peer)
+}
+
+pub fn alice() -> Behaviour {
+    Behaviour::new(
+        Codec::default(),
+        codec::versions(SwapStatusProtocol, ProtocolSupport::Inbound),
+        connection::request_response_config(),
+    )
+}
+
+pub fn bob() -> Behaviour {
+    Behaviour::new(
+        Codec::default(),
+        codec::versions(SwapStatusProtocol, ProtocolSupport::Outbound),
+        connection::request_response_config(),
+    )
+}
+
+impl From<(PeerId, Message)> for asb::OutEvent {
+    fn from((peer, message): (PeerId, Message)) -> Self {
+        match message {
+            Message::Request {
+                request, channel, ..
+            } => Self::SwapStatusRequested {
+                status: request,
+                channel,
+                peer,
+            },
+            Message::Response { .. } => Self::unexpected_response(peer),
+        }
+    }
+}
+crate::impl_from_rr_event!(OutEvent, asb::OutEvent, PROTOCOL);
+
+impl From<(PeerId, Message)> for cli::OutEvent {
+    fn from((peer, message): (PeerId, Message)) -> Self {
+        match message {
+            Message::Request { .. } => Self::unexpected_request(peer),
+            Message::Response { response, .. } => Self::SwapStatusReceived(Box::new(response)),
+        }
+    }
+}
+crate::impl_from_rr_event!(OutEvent, cli::OutEvent, PROTOCOL);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn status(phase: Phase) -> Status {
+        Status {
+            swap_id: Uuid::nil(),
+            phase,
+            tx_lock_id: Some(txid(1)),
+            xmr_lock_tx_hash: None,
+        }
+    }
+
+    fn txid(byte: u8) -> bitcoin::Txid {
+        bitcoin::Txid::from_str(&format!("{:02x}", byte).repeat(32)).unwrap()
+    }
+
+    #[test]
+    fn lost_messages_are_detected() {
+        assert_eq!(
+            resolve(&status(Phase::XmrLocked), &status(Phase::BtcLocked)),
+            Resolution::PeerBehind(Phase::BtcLocked)
+        );
+        assert_eq!(
+            resolve(&status(Phase::XmrLocked), &status(Phase::EncSigExchanged)),
+            Resolution::PeerAhead(Phase::EncSigExchanged)
+        );
+        assert_eq!(
+            resolve(&status(Phase::XmrLocked), &status(Phase::XmrLocked)),
+            Resolution::InSync
+        );
+    }
+
+    #[test]
+    fn peer_on_cancel_path_cancels_the_swap() {
+        assert_eq!(
+            resolve(&status(Phase::XmrLocked), &status(Phase::Cancelled)),
+            Resolution::Cancel
+        );
+        assert_eq!(
+            resolve(&status(Phase::Cancelled), &status(Phase::XmrLocked)),
+            Resolution::InSync
+        );
+        assert_eq!(
+            resolve(&status(Phase::BtcRedeemed), &status(Phase::Cancelled)),
+            Resolution::InSync
+        );
+    }
+
+    #[test]
+    fn contradicting_statuses_are_conflicts() {
+        let other_lock = Status {
+            tx_lock_id: Some(txid(2)),
+            ..status(Phase::XmrLocked)
+        };
+        let without_lock = Status {
+            tx_lock_id: None,
+            ..status(Phase::SetupCompleted)
+        };
+
+        assert!(resolve(&status(Phase::XmrLocked), &other_lock).is_unsafe());
+        assert!(resolve(&status(Phase::XmrLocked), &status(Phase::Unknown)).is_unsafe());
+        assert!(resolve(&status(Phase::XmrLocked), &status(Phase::Aborted)).is_unsafe());
+        assert_eq!(
+            resolve(&status(Phase::BtcLocked), &without_lock),
+            Resolution::PeerBehind(Phase::SetupCompleted)
+        );
+    }
+
+    #[test]
+    fn only_swaps_in_progress_are_resynchronized() {
+        assert!(status(Phase::EncSigExchanged).is_ongoing());
+        assert!(!status(Phase::Unknown).is_ongoing());
+        assert!(!status(Phase::Cancelled).is_ongoing());
+    }
+}
@@ -1,28 +1,31 @@
-use crate::asb::LatestRate;
+use crate::asb::{LatestRate, TradingControls};
 use crate::libp2p_ext::MultiAddrExt;
+use crate::network::hole_punch;
 use crate::network::rendezvous::XmrBtcNamespace;
-use crate::seed::Seed;
-use crate::{asb, bitcoin, cli, env, tor};
+use crate::network::transport::authenticate_and_multiplex;
+use crate::{asb, cli, env, tor};
 use anyhow::{Context, Result};
+use futures::{AsyncRead, AsyncWrite};
+use libp2p::core::transport::Boxed;
+use libp2p::relay::v2::client::Client;
 use libp2p::swarm::{NetworkBehaviour, SwarmBuilder};
 use libp2p::{identity, Multiaddr, Swarm};
 use std::fmt::Debug;
 
-#[allow(clippy::too_many_arguments)]
+/// Creates the swarm of the ASB, whose transport can also listen through
+/// circuit relays if any `relays` are given. Connections through the relays
+/// are upgraded to direct ones where the NATs allow it.
 pub fn asb<LR>(
-    seed: &Seed,
-    min_buy: bitcoin::Amount,
-    max_buy: bitcoin::Amount,
+    identity: identity::Keypair,
+    controls: TradingControls,
     latest_rate: LR,
-    resume_only: bool,
     env_config: env::Config,
     rendezvous_params: Option<(Multiaddr, XmrBtcNamespace)>,
+    relays: &[Multiaddr],
 ) -> Result<Swarm<asb::Behaviour<LR>>>
 where
     LR: LatestRate + Send + 'static + Debug + Clone,
 {
-    let identity = seed.derive_libp2p_identity();
-
     let rendezvous_params = if let Some((address, namespace)) = rendezvous_params {
         let peer_id = address
             .extract_peer_id()
@@ -33,16 +36,28 @@ where
         None
     };
 
+    for relay in relays {
+        relay
+            .extract_peer_id()
+            .with_context(|| format!("Relay address {} must contain peer ID", relay))?;
+    }
+
+    let (transport, relay, hole_punch) = if relays.is_empty() {
+        (asb::transport::new(&identity)?, None, None)
+    } else {
+        let (transport, relay) = asb::transport::new_with_relay(&identity)?;
+        let hole_punch = hole_punch::Behaviour::new(identity.public());
+        (transport, Some(relay), Some(hole_punch))
+    };
+
     let behaviour = asb::Behaviour::new(
-        min_buy,
-        max_buy,
+        controls,
         latest_rate,
-        resume_only,
         env_config,
         rendezvous_params,
+        relay,
+        hole_punch,
     );
-
-    let transport = asb::transport::new(&identity)?;
     let peer_id = identity.public().into();
 
     let swarm = SwarmBuilder::new(transport, behaviour, peer_id)
@@ -54,20 +69,59 @@ where
     Ok(swarm)
 }
 
-pub async fn cli<T>(
+/// Creates a swarm for the CLI with the behaviour returned by `behaviour`,
+/// which can dial sellers through circuit relays if it includes the given
+/// relay [`Client`].
+///
+/// Without Tor the behaviour is also given a [`hole_punch::Behaviour`] that
+/// upgrades relayed connections to direct ones, for which the swarm listens
+/// on a TCP port to make its outgoing connections from. Over Tor the CLI
+/// never connects to sellers directly.
+pub async fn cli<T, F>(
     identity: identity::Keypair,
     tor_socks5_port: u16,
-    behaviour: T,
+    behaviour: F,
 ) -> Result<Swarm<T>>
 where
     T: NetworkBehaviour,
+    F: FnOnce(Client, Option<hole_punch::Behaviour>) -> T,
 {
     let maybe_tor_socks5_port = match tor::Client::new(tor_socks5_port).assert_tor_running().await {
         Ok(()) => Some(tor_socks5_port),
         Err(_) => None,
     };
 
-    let transport = cli::transport::new(&identity, maybe_tor_socks5_port)?;
+    let (transport, relay) = cli::transport::new(&identity, maybe_tor_socks5_port)?;
+    let hole_punch = match maybe_tor_socks5_port {
+        Some(_) => None,
+        None => Some(hole_punch::Behaviour::new(identity.public())),
+    };
+    let hole_punching = hole_punch.is_some();
+    let peer_id = identity.public().into();
+
+    let mut swarm = SwarmBuilder::new(transport, behaviour(relay, hole_punch), peer_id)
+        .executor(Box::new(|f| {
+            tokio::spawn(f);
+        }))
+        .build();
+
+    if hole_punching {
+        let address = "/ip4/0.0.0.0/tcp/0".parse().expect("valid multiaddr");
+        if let Err(error) = swarm.listen_on(address) {
+            tracing::warn!("Failed to listen on a TCP port for hole punching: {}", error);
+        }
+    }
+
+    Ok(swarm)
+}
+
+/// Creates a swarm with the TCP and websocket transport of the ASB, for the
+/// watchtower and for registering with one without Tor.
+pub fn watchtower<T>(identity: identity::Keypair, behaviour: T) -> Result<Swarm<T>>
+where
+    T: NetworkBehaviour,
+{
+    let transport = asb::transport::new(&identity)?;
     let peer_id = identity.public().into();
 
     let swarm = SwarmBuilder::new(transport, behaviour, peer_id)
@@ -78,3 +132,223 @@ where
 
     Ok(swarm)
 }
+
+/// Creates a swarm for the CLI whose connections are established by the given
+/// transport instead of TCP or Tor, e.g. a
+/// [`DuplexTransport`](crate::network::duplex_transport::DuplexTransport) over
+/// a connection of the host application.
+pub fn cli_with_transport<T, S>(
+    identity: identity::Keypair,
+    transport: Boxed<S>,
+    behaviour: T,
+) -> Result<Swarm<T>>
+where
+    T: NetworkBehaviour,
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let transport = authenticate_and_multiplex(transport, &identity)?;
+    let peer_id = identity.public().into();
+
+    let swarm = SwarmBuilder::new(transport, behaviour, peer_id)
+        .executor(Box::new(|f| {
+            tokio::spawn(f);
+        }))
+        .build();
+
+    Ok(swarm)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asb::{FixedRate, TradingParameters};
+    use crate::bitcoin;
+    use crate::env::GetConfig;
+    use crate::network::test::SwarmExt;
+    use futures::StreamExt;
+    use libp2p::multiaddr::Protocol;
+    use libp2p::relay::v2::client;
+    use libp2p::relay::v2::relay::Relay;
+    use libp2p::swarm::{AddressScore, SwarmEvent};
+    use libp2p::NetworkBehaviour;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn given_asb_listening_through_relay_then_cli_connects_through_it() {
+        let relay_address = spawn_relay().await;
+        let (mut asb, relayed_address) = asb_listening_through(relay_address).await;
+        let asb_peer_id = *asb.local_peer_id();
+
+        let mut cli = cli(
+            identity::Keypair::generate_ed25519(),
+            no_tor_socks5_port().await,
+            |relay, _| relay,
+        )
+        .await
+        .unwrap();
+        let cli_peer_id = *cli.local_peer_id();
+        cli.dial(relayed_address).unwrap();
+
+        let connected = async {
+            let mut asb_connected = false;
+            let mut cli_connected = false;
+
+            while !(asb_connected && cli_connected) {
+                tokio::select! {
+                    event = asb.select_next_some() => {
+                        if let SwarmEvent::ConnectionEstablished { peer_id, .. } = event {
+                            asb_connected |= peer_id == cli_peer_id;
+                        }
+                    }
+                    event = cli.select_next_some() => {
+                        if let SwarmEvent::ConnectionEstablished { peer_id, .. } = event {
+                            cli_connected |= peer_id == asb_peer_id;
+                        }
+                    }
+                }
+            }
+        };
+
+        tokio::time::timeout(Duration::from_secs(30), connected)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn given_relayed_connection_then_cli_upgrades_it_to_a_direct_one() {
+        let relay_address = spawn_relay().await;
+        let (mut asb, relayed_address) = asb_listening_through(relay_address).await;
+        let asb_peer_id = *asb.local_peer_id();
+        // Usually learned from the relay over identify
+        let asb_address = asb.listen_on_tcp_localhost().await;
+        asb.add_external_address(asb_address, AddressScore::Infinite);
+
+        let mut cli = cli(
+            identity::Keypair::generate_ed25519(),
+            no_tor_socks5_port().await,
+            |relay, hole_punch| RelayedCli {
+                relay,
+                hole_punch: hole_punch.expect("hole punching without Tor"),
+            },
+        )
+        .await
+        .unwrap();
+        let cli_address = cli.listen_on_tcp_localhost().await;
+        cli.add_external_address(cli_address, AddressScore::Infinite);
+        cli.dial(relayed_address).unwrap();
+
+        let upgraded = async {
+            let mut relayed = false;
+
+            loop {
+                tokio::select! {
+                    _ = asb.select_next_some() => {}
+                    event = cli.select_next_some() => match event {
+                        SwarmEvent::ConnectionEstablished { peer_id, endpoint, .. } if peer_id == asb_peer_id => {
+                            relayed |= endpoint.is_relayed();
+                        }
+                        SwarmEvent::Behaviour(RelayedCliEvent::HolePunch(hole_punch::OutEvent::Upgraded { peer })) => {
+                            assert_eq!(peer, asb_peer_id);
+                            break;
+                        }
+                        _ => {}
+                    }
+                }
+            }
+
+            relayed
+        };
+
+        let relayed = tokio::time::timeout(Duration::from_secs(30), upgraded)
+            .await
+            .unwrap();
+        assert!(relayed, "expected the first connection to go through the relay");
+    }
+
+    #[derive(NetworkBehaviour)]
+    #[behaviour(out_event = "RelayedCliEvent", event_process = false)]
+    #[allow(missing_debug_implementations)]
+    struct RelayedCli {
+        relay: Client,
+        hole_punch: hole_punch::Behaviour,
+    }
+
+    #[derive(Debug)]
+    enum RelayedCliEvent {
+        Relay,
+        HolePunch(hole_punch::OutEvent),
+    }
+
+    impl From<client::Event> for RelayedCliEvent {
+        fn from(_: client::Event) -> Self {
+            RelayedCliEvent::Relay
+        }
+    }
+
+    impl From<hole_punch::OutEvent> for RelayedCliEvent {
+        fn from(event: hole_punch::OutEvent) -> Self {
+            RelayedCliEvent::HolePunch(event)
+        }
+    }
+
+    /// Spawns a relay listening on localhost and returns its address.
+    async fn spawn_relay() -> Multiaddr {
+        let relay_identity = identity::Keypair::generate_ed25519();
+        let relay_peer_id = relay_identity.public().to_peer_id();
+        let transport = asb::transport::new(&relay_identity).unwrap();
+        let relay = Relay::new(relay_peer_id, Default::default());
+        let mut relay_node = SwarmBuilder::new(transport, relay, relay_peer_id).build();
+        let relay_tcp_address = relay_node.listen_on_tcp_localhost().await;
+        // The relay hands out its external addresses with a reservation
+        relay_node.add_external_address(relay_tcp_address.clone(), AddressScore::Infinite);
+        tokio::spawn(async move {
+            loop {
+                relay_node.next().await;
+            }
+        });
+
+        relay_tcp_address.with(Protocol::P2p(relay_peer_id.into()))
+    }
+
+    /// Creates an ASB that reserved a slot on the relay and returns it with
+    /// the address it can be dialed at through the relay.
+    async fn asb_listening_through(
+        relay_address: Multiaddr,
+    ) -> (Swarm<asb::Behaviour<FixedRate>>, Multiaddr) {
+        let controls = TradingControls::new(TradingParameters {
+            paused: false,
+            ask_spread: Default::default(),
+            min_buy: bitcoin::Amount::from_sat(u64::MIN),
+            max_buy: bitcoin::Amount::from_sat(u64::MAX),
+        });
+        let mut asb = asb(
+            identity::Keypair::generate_ed25519(),
+            controls,
+            FixedRate::default(),
+            env::Testnet::get_config(),
+            None,
+            &[relay_address.clone()],
+        )
+        .unwrap();
+        let asb_peer_id = *asb.local_peer_id();
+        asb.listen_on(relay_address.clone().with(Protocol::P2pCircuit))
+            .unwrap();
+        let relayed_address = relay_address
+            .with(Protocol::P2pCircuit)
+            .with(Protocol::P2p(asb_peer_id.into()));
+        loop {
+            if let SwarmEvent::NewListenAddr { address, .. } = asb.select_next_some().await {
+                if address == relayed_address {
+                    break;
+                }
+            }
+        }
+
+        (asb, relayed_address)
+    }
+
+    async fn no_tor_socks5_port() -> u16 {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        listener.local_addr().unwrap().port()
+    }
+}
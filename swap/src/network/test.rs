@@ -130,7 +130,7 @@ where
     {
         let addr_to_dial = other.external_addresses().next().unwrap().addr.clone();
 
-        self.dial_addr(addr_to_dial.clone()).unwrap();
+        self.dial(addr_to_dial.clone()).unwrap();
 
         let mut dialer_done = false;
         let mut listener_done = false;
@@ -144,7 +144,7 @@ where
                         SwarmEvent::ConnectionEstablished { .. } => {
                             dialer_done = true;
                         }
-                        SwarmEvent::UnknownPeerUnreachableAddr { address, error } if address == addr_to_dial => {
+                        SwarmEvent::OutgoingConnectionError { error, .. } => {
                             panic!("Failed to dial address {}: {}", addr_to_dial, error)
                         }
                         other => {
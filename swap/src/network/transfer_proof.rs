@@ -1,9 +1,9 @@
-use crate::network::cbor_request_response::CborCodec;
+use crate::network::codec::{self, Codec};
+use crate::network::connection;
 use crate::{asb, cli, monero};
 use libp2p::core::ProtocolName;
 use libp2p::request_response::{
-    ProtocolSupport, RequestResponse, RequestResponseConfig, RequestResponseEvent,
-    RequestResponseMessage,
+    ProtocolSupport, RequestResponse, RequestResponseEvent, RequestResponseMessage,
 };
 use libp2p::PeerId;
 use serde::{Deserialize, Serialize};
@@ -13,7 +13,7 @@ const PROTOCOL: &str = "/comit/xmr/btc/transfer_proof/1.0.0";
 type OutEvent = RequestResponseEvent<Request, ()>;
 type Message = RequestResponseMessage<Request, ()>;
 
-pub type Behaviour = RequestResponse<CborCodec<TransferProofProtocol, Request, ()>>;
+pub type Behaviour = RequestResponse<Codec<TransferProofProtocol, Request, ()>>;
 
 #[derive(Debug, Clone, Copy, Default)]
 pub struct TransferProofProtocol;
@@ -28,21 +28,25 @@ impl ProtocolName for TransferProofProtocol {
 pub struct Request {
     pub swap_id: Uuid,
     pub tx_lock_proof: monero::TransferProof,
+    /// The same for every delivery of the message, see
+    /// [`crate::network::outbox`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<Uuid>,
 }
 
 pub fn alice() -> Behaviour {
     Behaviour::new(
-        CborCodec::default(),
-        vec![(TransferProofProtocol, ProtocolSupport::Outbound)],
-        RequestResponseConfig::default(),
+        Codec::default(),
+        codec::versions(TransferProofProtocol, ProtocolSupport::Outbound),
+        connection::request_response_config(),
     )
 }
 
 pub fn bob() -> Behaviour {
     Behaviour::new(
-        CborCodec::default(),
-        vec![(TransferProofProtocol, ProtocolSupport::Inbound)],
-        RequestResponseConfig::default(),
+        Codec::default(),
+        codec::versions(TransferProofProtocol, ProtocolSupport::Inbound),
+        connection::request_response_config(),
     )
 }
 
@@ -0,0 +1,265 @@
+//! Lets Alice and Bob hand the presigned transactions of a swap to a
+//! watchtower.
+//!
+//! A watchtower is a semi-trusted third party that publishes the cancel
+//! transaction of a swap and, for Alice, the punish transaction spending it
+//! once their timelocks expired, in case the party that registered them is
+//! offline by then. The watchtower learns the amounts and addresses of the swap
+//! and may publish nothing at all.
+//!
+//! Bob only registers the cancel transaction. His signed refund transaction
+//! together with the encrypted signature Alice made for it reveals his key
+//! share of the Monero, a watchtower handing it to Alice would let her take the
+//! Monero back and punish Bob while it withholds the refund. Until the CLI
+//! refunds itself, that is what Bob would have to trust the watchtower with.
+//!
+//! Registering is optional and best effort, a swap never waits for the
+//! watchtower. The tower side is the `watchtower` binary, see
+//! [`crate::watchtower`], which is built with the `watchtower` feature.
+
+use crate::libp2p_ext::MultiAddrExt;
+use crate::network::codec::{self, Codec};
+use crate::network::{connection, swarm};
+use crate::protocol::presigned::{Kind, PresignedTransaction};
+use anyhow::{bail, Context, Result};
+use futures::StreamExt;
+use libp2p::core::ProtocolName;
+use libp2p::request_response::{
+    ProtocolSupport, RequestResponse, RequestResponseEvent, RequestResponseMessage,
+};
+use libp2p::swarm::SwarmEvent;
+use libp2p::{identity, Multiaddr, PeerId};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::Duration;
+use uuid::Uuid;
+
+const PROTOCOL: &str = "/comit/xmr/btc/watchtower/1.0.0";
+pub type OutEvent = RequestResponseEvent<Request, Response>;
+pub type Message = RequestResponseMessage<Request, Response>;
+
+pub type Behaviour = RequestResponse<Codec<WatchtowerProtocol, Request, Response>>;
+
+/// How long registering may take, including dialing the watchtower.
+const REGISTER_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WatchtowerProtocol;
+
+impl ProtocolName for WatchtowerProtocol {
+    fn protocol_name(&self) -> &[u8] {
+        PROTOCOL.as_bytes()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Request {
+    pub swap_id: Uuid,
+    pub transactions: Vec<PresignedTransaction>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Response {
+    Registered { swap_id: Uuid },
+    Rejected { swap_id: Uuid, reason: String },
+}
+
+pub fn tower() -> Behaviour {
+    Behaviour::new(
+        Codec::default(),
+        codec::versions(WatchtowerProtocol, ProtocolSupport::Inbound),
+        connection::request_response_config(),
+    )
+}
+
+pub fn client() -> Behaviour {
+    Behaviour::new(
+        Codec::default(),
+        codec::versions(WatchtowerProtocol, ProtocolSupport::Outbound),
+        connection::request_response_config(),
+    )
+}
+
+/// The presigned transactions of Bob that he hands to a watchtower, only the
+/// cancel transaction.
+pub fn of_bob(presigned: Vec<PresignedTransaction>) -> Vec<PresignedTransaction> {
+    presigned
+        .into_iter()
+        .filter(|transaction| transaction.kind == Kind::Cancel)
+        .collect()
+}
+
+/// Checks that the transactions are what a watchtower watches for: the cancel
+/// transaction and optionally the refund or the punish transaction spending it.
+pub fn validate(transactions: &[PresignedTransaction]) -> Result<()> {
+    let (cancel, spending) = match transactions {
+        [cancel] if cancel.kind == Kind::Cancel => return Ok(()),
+        [first, second] if first.kind == Kind::Cancel => (first, second),
+        [first, second] if second.kind == Kind::Cancel => (second, first),
+        _ => {
+            bail!("Expected the cancel transaction and optionally the refund or punish transaction")
+        }
+    };
+
+    if spending.kind == Kind::Cancel {
+        bail!("Expected the refund or punish transaction next to the cancel transaction")
+    }
+    if spending.spends() != Some(cancel.txid()) {
+        bail!(
+            "The {} transaction does not spend the cancel transaction",
+            spending.kind
+        )
+    }
+
+    Ok(())
+}
+
+/// Registers the presigned transactions of swaps with a watchtower.
+#[derive(Clone)]
+pub struct Client {
+    peer_id: PeerId,
+    address: Multiaddr,
+    identity: identity::Keypair,
+    tor_socks5_port: Option<u16>,
+}
+
+impl fmt::Debug for Client {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Client")
+            .field("peer_id", &self.peer_id)
+            .field("address", &self.address)
+            .field("tor_socks5_port", &self.tor_socks5_port)
+            .finish()
+    }
+}
+
+impl Client {
+    pub fn new(address: Multiaddr, identity: identity::Keypair) -> Result<Self> {
+        let peer_id = address
+            .extract_peer_id()
+            .context("The watchtower address must contain its peer id")?;
+
+        Ok(Self {
+            peer_id,
+            address,
+            identity,
+            tor_socks5_port: None,
+        })
+    }
+
+    /// Dials the watchtower through Tor if it is running on this port.
+    pub fn with_tor(mut self, tor_socks5_port: u16) -> Self {
+        self.tor_socks5_port = Some(tor_socks5_port);
+        self
+    }
+
+    pub async fn register(
+        &self,
+        swap_id: Uuid,
+        transactions: Vec<PresignedTransaction>,
+    ) -> Result<()> {
+        let mut swarm = match self.tor_socks5_port {
+            Some(tor_socks5_port) => {
+                swarm::cli(self.identity.clone(), tor_socks5_port, |_, _| client()).await?
+            }
+            None => swarm::watchtower(self.identity.clone(), client())?,
+        };
+
+        swarm
+            .behaviour_mut()
+            .add_address(&self.peer_id, self.address.clone());
+        swarm.behaviour_mut().send_request(&self.peer_id, Request {
+            swap_id,
+            transactions,
+        });
+
+        let response = async {
+            loop {
+                match swarm.select_next_some().await {
+                    SwarmEvent::Behaviour(RequestResponseEvent::Message {
+                        message: RequestResponseMessage::Response { response, .. },
+                        ..
+                    }) => return Ok(response),
+                    SwarmEvent::Behaviour(RequestResponseEvent::OutboundFailure {
+                        error, ..
+                    }) => bail!("Failed to reach the watchtower: {}", error),
+                    _ => {}
+                }
+            }
+        };
+
+        match tokio::time::timeout(REGISTER_TIMEOUT, response)
+            .await
+            .context("The watchtower did not respond in time")??
+        {
+            Response::Registered { .. } => Ok(()),
+            Response::Rejected { reason, .. } => {
+                bail!("The watchtower rejected the transactions: {}", reason)
+            }
+        }
+    }
+
+    /// Registers in a task of its own, a failure is only logged.
+    pub fn spawn_register(&self, swap_id: Uuid, transactions: Vec<PresignedTransaction>) {
+        let client = self.clone();
+
+        tokio::spawn(async move {
+            match client.register(swap_id, transactions).await {
+                Ok(()) => tracing::info!(
+                    %swap_id,
+                    watchtower = %client.peer_id,
+                    "Registered presigned transactions with watchtower"
+                ),
+                Err(error) => tracing::warn!(
+                    %swap_id,
+                    watchtower = %client.peer_id,
+                    "Failed to register presigned transactions with watchtower: {:#}",
+                    error
+                ),
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitcoin::Transaction;
+    use ::bitcoin::{OutPoint, TxIn};
+
+    fn transaction(kind: Kind, spends: OutPoint) -> PresignedTransaction {
+        PresignedTransaction::new(kind, 0u32, Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![TxIn {
+                previous_output: spends,
+                ..Default::default()
+            }],
+            output: vec![],
+        })
+    }
+
+    #[test]
+    fn only_a_cancel_transaction_and_one_spending_it_are_accepted() {
+        let cancel = transaction(Kind::Cancel, OutPoint::default());
+        let refund = transaction(Kind::Refund, OutPoint::new(cancel.txid(), 0));
+        let unrelated = transaction(Kind::Punish, OutPoint::default());
+
+        assert!(validate(&[cancel.clone(), refund.clone()]).is_ok());
+        assert!(validate(&[refund.clone(), cancel.clone()]).is_ok());
+        assert!(validate(&[cancel.clone()]).is_ok());
+
+        assert!(validate(&[refund.clone()]).is_err());
+        assert!(validate(&[cancel.clone(), cancel.clone()]).is_err());
+        assert!(validate(&[cancel.clone(), unrelated]).is_err());
+        assert!(validate(&[cancel, refund.clone(), refund]).is_err());
+    }
+
+    #[test]
+    fn bob_only_registers_the_cancel_transaction() {
+        let cancel = transaction(Kind::Cancel, OutPoint::default());
+        let refund = transaction(Kind::Refund, OutPoint::new(cancel.txid(), 0));
+
+        assert_eq!(of_bob(vec![cancel.clone(), refund]), vec![cancel]);
+    }
+}
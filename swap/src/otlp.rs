@@ -0,0 +1,48 @@
+//! Exporting the tracing spans of the ASB and the CLI to an OpenTelemetry
+//! collector such as Jaeger or Tempo.
+//!
+//! Both record a `swap` span with the id of the swap in its `id` field, so the
+//! spans of Alice and Bob of the same swap can be looked up side by side when
+//! both export to the same collector.
+
+use anyhow::{Context, Result};
+use opentelemetry::sdk::trace::{self, Tracer};
+use opentelemetry::sdk::Resource;
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::WithExportConfig;
+use tracing::Subscriber;
+use tracing_opentelemetry::OpenTelemetryLayer;
+use tracing_subscriber::registry::LookupSpan;
+use url::Url;
+
+/// Exports spans in batches to the collector at `endpoint` using OTLP over
+/// gRPC, reporting them as coming from `service`.
+///
+/// Has to be called from within the Tokio runtime.
+pub fn tracer(endpoint: &Url, service: &'static str) -> Result<Tracer> {
+    let resource = Resource::new(vec![KeyValue::new("service.name", service)]);
+
+    opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(
+            opentelemetry_otlp::new_exporter()
+                .tonic()
+                .with_endpoint(endpoint.as_str()),
+        )
+        .with_trace_config(trace::config().with_resource(resource))
+        .install_batch(opentelemetry::runtime::Tokio)
+        .with_context(|| format!("Failed to export spans to {}", endpoint))
+}
+
+/// The layer passing spans to `tracer`, none if spans are not exported.
+pub fn layer<S>(tracer: Option<&Tracer>) -> Option<OpenTelemetryLayer<S, Tracer>>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    tracer.map(|tracer| tracing_opentelemetry::layer().with_tracer(tracer.clone()))
+}
+
+/// Exports the spans that are still buffered, to be called before exiting.
+pub fn shutdown() {
+    opentelemetry::global::shutdown_tracer_provider();
+}
@@ -0,0 +1,90 @@
+//! Progress of the waits for confirmations of a swap.
+//!
+//! The wallets report every confirmation they see of a transaction that is
+//! waited for, the CLI shows them as progress bars, see
+//! [`crate::cli::progress`].
+
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+/// Reports that are not received in time are dropped, only the latest one of
+/// a transaction matters.
+const CAPACITY: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, strum::Display)]
+pub enum Chain {
+    Bitcoin,
+    Monero,
+}
+
+/// The confirmations a transaction has and the ones it is waited for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Confirmations {
+    pub chain: Chain,
+    pub txid: String,
+    pub seen: u64,
+    pub needed: u64,
+}
+
+impl Confirmations {
+    pub fn is_complete(&self) -> bool {
+        self.seen >= self.needed
+    }
+
+    /// How long it takes until the needed confirmations are reached if blocks
+    /// are mined every `block_time`.
+    pub fn remaining(&self, block_time: Duration) -> Duration {
+        let blocks = self.needed.saturating_sub(self.seen);
+
+        Duration::from_secs(block_time.as_secs().saturating_mul(blocks))
+    }
+}
+
+/// A cheaply cloneable handle the confirmations of a wallet are reported to.
+///
+/// Reports are dropped as long as nobody subscribed.
+#[derive(Debug, Clone)]
+pub struct Progress {
+    sender: broadcast::Sender<Confirmations>,
+}
+
+impl Default for Progress {
+    fn default() -> Self {
+        let (sender, _) = broadcast::channel(CAPACITY);
+
+        Self { sender }
+    }
+}
+
+impl Progress {
+    pub fn report(&self, confirmations: Confirmations) {
+        let _ = self.sender.send(confirmations);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Confirmations> {
+        self.sender.subscribe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remaining_time_counts_the_missing_blocks() {
+        let confirmations = |seen| Confirmations {
+            chain: Chain::Monero,
+            txid: "abc".to_owned(),
+            seen,
+            needed: 10,
+        };
+        let block_time = Duration::from_secs(2 * 60);
+
+        assert_eq!(
+            confirmations(4).remaining(block_time),
+            Duration::from_secs(12 * 60)
+        );
+        assert_eq!(confirmations(12).remaining(block_time), Duration::ZERO);
+        assert!(confirmations(10).is_complete());
+    }
+}
@@ -1,9 +1,19 @@
+use crate::amounts::remaining_after_fees;
+use crate::asb::quotes::QuoteRecord;
+use crate::database::{Generation, SwapFilter, SwapPage, SwapSummary};
+use crate::fiat::FiatValue;
+use crate::network::outbox;
+use crate::network::quote::SignedQuote;
 use crate::protocol::alice::swap::is_complete as alice_is_complete;
 use crate::protocol::alice::AliceState;
 use crate::protocol::bob::swap::is_complete as bob_is_complete;
 use crate::protocol::bob::BobState;
+use crate::protocol::presigned::PresignedTransaction;
+use crate::protocol::receipt::Receipt;
+use crate::protocol::sequence::MessageKind;
+use crate::protocol::transcript::TranscriptHash;
 use crate::{bitcoin, monero};
-use anyhow::Result;
+use anyhow::{Context, Result};
 use async_trait::async_trait;
 use conquer_once::Lazy;
 use ecdsa_fun::fun::marker::Mark;
@@ -13,10 +23,16 @@ use sha2::Sha256;
 use sigma_fun::ext::dl_secp256k1_ed25519_eq::{CrossCurveDLEQ, CrossCurveDLEQProof};
 use sigma_fun::HashTranscript;
 use std::convert::TryInto;
+use time::OffsetDateTime;
 use uuid::Uuid;
 
 pub mod alice;
 pub mod bob;
+pub mod deadline;
+pub mod presigned;
+pub mod receipt;
+pub mod sequence;
+pub mod transcript;
 
 pub static CROSS_CURVE_PROOF_SYSTEM: Lazy<
     CrossCurveDLEQ<HashTranscript<Sha256, rand_chacha::ChaCha20Rng>>,
@@ -27,6 +43,72 @@ pub static CROSS_CURVE_PROOF_SYSTEM: Lazy<
     )
 });
 
+/// Ensures that every transaction spending the lock output can pay its fee,
+/// the fees of one party are chosen by the other.
+pub fn check_fees(
+    btc: bitcoin::Amount,
+    tx_redeem_fee: bitcoin::Amount,
+    tx_cancel_fee: bitcoin::Amount,
+    tx_refund_fee: bitcoin::Amount,
+    tx_punish_fee: bitcoin::Amount,
+) -> Result<()> {
+    remaining_after_fees(btc, &[tx_redeem_fee]).context("Redeem fee exceeds the lock amount")?;
+    remaining_after_fees(btc, &[tx_cancel_fee, tx_refund_fee])
+        .context("Cancel and refund fee exceed the lock amount")?;
+    remaining_after_fees(btc, &[tx_cancel_fee, tx_punish_fee])
+        .context("Cancel and punish fee exceed the lock amount")?;
+
+    Ok(())
+}
+
+/// A proof or signature sent by the other party that does not verify, the
+/// message it came with is rejected.
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq)]
+pub enum InvalidMessage {
+    #[error("The Monero key received in {message} is not a point on the ed25519 curve")]
+    MoneroKeyNotOnCurve { message: MessageKind },
+    #[error("The DLEQ proof received in {message} does not prove that its Bitcoin and Monero key share the secret")]
+    DleqProof { message: MessageKind },
+    #[error(
+        "The signature of the {transaction} transaction received in {message} does not verify"
+    )]
+    Signature {
+        message: MessageKind,
+        transaction: &'static str,
+    },
+    #[error(
+        "The encrypted signature of the {transaction} transaction received in {message} does not verify"
+    )]
+    EncryptedSignature {
+        message: MessageKind,
+        transaction: &'static str,
+    },
+    #[error("The swap parameters hashed in {message} differ from ours")]
+    TranscriptMismatch { message: MessageKind },
+    #[error("The signature of the swap parameters hashed in {message} does not verify")]
+    TranscriptSignature { message: MessageKind },
+}
+
+/// Verifies that the Bitcoin and the Monero key sent in `message` belong to
+/// the same secret, the one the adaptor signatures are encrypted to.
+pub fn verify_dleq_proof(
+    proof: &CrossCurveDLEQProof,
+    S_bitcoin: bitcoin::PublicKey,
+    S_monero: monero::PublicKey,
+    message: MessageKind,
+) -> Result<(), InvalidMessage> {
+    let S_monero = S_monero
+        .point
+        .decompress()
+        .ok_or(InvalidMessage::MoneroKeyNotOnCurve { message })?;
+
+    if CROSS_CURVE_PROOF_SYSTEM.verify(proof, (S_bitcoin.into(), S_monero)) {
+        Ok(())
+    } else {
+        Err(InvalidMessage::DleqProof { message })
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Message0 {
     swap_id: Uuid,
@@ -36,12 +118,16 @@ pub struct Message0 {
     dleq_proof_s_b: CrossCurveDLEQProof,
     v_b: monero::PrivateViewKey,
     refund_address: bitcoin::Address,
-    #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
     tx_refund_fee: bitcoin::Amount,
-    #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
     tx_cancel_fee: bitcoin::Amount,
 }
 
+impl Message0 {
+    pub fn swap_id(&self) -> Uuid {
+        self.swap_id
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Message1 {
     A: bitcoin::PublicKey,
@@ -51,9 +137,7 @@ pub struct Message1 {
     v_a: monero::PrivateViewKey,
     redeem_address: bitcoin::Address,
     punish_address: bitcoin::Address,
-    #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
     tx_redeem_fee: bitcoin::Amount,
-    #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
     tx_punish_fee: bitcoin::Amount,
 }
 
@@ -66,12 +150,18 @@ pub struct Message2 {
 pub struct Message3 {
     tx_cancel_sig: bitcoin::Signature,
     tx_refund_encsig: bitcoin::EncryptedSignature,
+    /// Alice's view of the swap parameters, signed with `a`.
+    transcript: TranscriptHash,
+    transcript_sig: bitcoin::Signature,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Message4 {
     tx_punish_sig: bitcoin::Signature,
     tx_cancel_sig: bitcoin::Signature,
+    /// Bob's view of the swap parameters, signed with `b`.
+    transcript: TranscriptHash,
+    transcript_sig: bitcoin::Signature,
 }
 
 #[allow(clippy::large_enum_variant)]
@@ -139,8 +229,783 @@ pub trait Database {
     async fn insert_monero_address(&self, swap_id: Uuid, address: monero::Address) -> Result<()>;
     async fn get_monero_address(&self, swap_id: Uuid) -> Result<monero::Address>;
     async fn insert_address(&self, peer_id: PeerId, address: Multiaddr) -> Result<()>;
+    /// The known addresses of the peer, the ones it was last reachable at
+    /// first.
     async fn get_addresses(&self, peer_id: PeerId) -> Result<Vec<Multiaddr>>;
+    /// Records whether connecting to the peer at a known address succeeded.
+    async fn record_address_outcome(
+        &self,
+        peer_id: PeerId,
+        address: Multiaddr,
+        reachable: bool,
+        at: OffsetDateTime,
+    ) -> Result<()>;
+    /// Removes the addresses that failed at least `failures` times in a row
+    /// and were not reachable since `unreachable_since`, returns the number
+    /// of removed addresses.
+    async fn prune_addresses(
+        &self,
+        unreachable_since: OffsetDateTime,
+        failures: u32,
+    ) -> Result<u64>;
     async fn insert_latest_state(&self, swap_id: Uuid, state: State) -> Result<()>;
     async fn get_state(&self, swap_id: Uuid) -> Result<State>;
+    /// All states the swap went through, starting with the first one.
+    ///
+    /// Pruning removes all but the latest state of a finished swap.
+    async fn get_states(&self, swap_id: Uuid) -> Result<Vec<State>>;
+    /// Like [`Database::get_states`], together with the time each state was
+    /// entered.
+    async fn get_state_history(&self, swap_id: Uuid) -> Result<Vec<(OffsetDateTime, State)>>;
     async fn all(&self) -> Result<Vec<(Uuid, State)>>;
+    /// The swaps matching `filter`, ordered and paginated by `page`.
+    async fn list_swaps(&self, filter: &SwapFilter, page: &SwapPage) -> Result<Vec<SwapSummary>>;
+    async fn get_summary(&self, swap_id: Uuid) -> Result<SwapSummary>;
+    /// Removes all but the latest state of every finished swap whose latest
+    /// state was entered before `finished_before`, returns the number of
+    /// removed states.
+    async fn prune(&self, finished_before: OffsetDateTime) -> Result<u64>;
+    /// Rebuilds the database file to reclaim the space freed by pruning.
+    async fn compact(&self) -> Result<()>;
+    async fn insert_generation(&self, swap_id: Uuid, generation: Generation) -> Result<()>;
+    async fn get_generation(&self, swap_id: Uuid) -> Result<Option<Generation>>;
+    /// Records which maker identity of the ASB the swap was set up with.
+    async fn insert_identity(&self, swap_id: Uuid, identity: &str) -> Result<()>;
+    async fn get_identity(&self, swap_id: Uuid) -> Result<Option<String>>;
+    /// Records the estimated fiat value of the swap at the time it was set up.
+    async fn insert_fiat_value(&self, swap_id: Uuid, value: FiatValue) -> Result<()>;
+    async fn get_fiat_value(&self, swap_id: Uuid) -> Result<Option<FiatValue>>;
+    /// Records a quote of the ASB once it is known what became of it.
+    async fn insert_quote(&self, quote: QuoteRecord) -> Result<()>;
+    /// All recorded quotes, oldest first.
+    async fn get_quotes(&self) -> Result<Vec<QuoteRecord>>;
+    /// Keeps a quote signed by the seller `peer_id` as evidence of the terms
+    /// offered for the swap.
+    async fn insert_signed_quote(
+        &self,
+        swap_id: Uuid,
+        peer_id: PeerId,
+        quote: &SignedQuote,
+    ) -> Result<()>;
+    /// The signed quotes received for the swap, oldest first.
+    async fn get_signed_quotes(&self, swap_id: Uuid) -> Result<Vec<SignedQuote>>;
+    /// Records why the swap stopped waiting for the counterparty, the first
+    /// recorded reason is kept.
+    async fn insert_wind_down(&self, swap_id: Uuid, reason: &str) -> Result<()>;
+    async fn get_wind_down(&self, swap_id: Uuid) -> Result<Option<String>>;
+    /// Records an error that stopped the swap.
+    async fn insert_error(&self, swap_id: Uuid, error: &str) -> Result<()>;
+    /// The errors that stopped the swap with the time they occurred, oldest
+    /// first.
+    async fn get_errors(&self, swap_id: Uuid) -> Result<Vec<(OffsetDateTime, String)>>;
+    /// Keeps a fully signed transaction of the swap, replacing the one of the
+    /// same kind.
+    async fn insert_presigned_transaction(
+        &self,
+        swap_id: Uuid,
+        transaction: &PresignedTransaction,
+    ) -> Result<()>;
+    /// The presigned transactions of the swap, ordered by kind.
+    async fn get_presigned_transactions(&self, swap_id: Uuid) -> Result<Vec<PresignedTransaction>>;
+    /// Keeps the receipt of the swap signed by both parties.
+    async fn insert_receipt(&self, swap_id: Uuid, receipt: &Receipt) -> Result<()>;
+    async fn get_receipt(&self, swap_id: Uuid) -> Result<Option<Receipt>>;
+    /// Puts `message` into the outbox for `peer_id`, unless the outbox already
+    /// holds a message of the same kind for the swap. Returns the message in
+    /// the outbox, i.e. the one with the nonce the peer is told.
+    async fn insert_outbound(
+        &self,
+        peer_id: PeerId,
+        message: outbox::Message,
+    ) -> Result<outbox::Message>;
+    /// The messages in the outbox for `peer_id`, oldest first.
+    async fn get_outbound(&self, peer_id: PeerId) -> Result<Vec<outbox::Message>>;
+    async fn remove_outbound(&self, swap_id: Uuid, kind: MessageKind) -> Result<()>;
+    /// Records that a message of the peer was processed.
+    async fn insert_received(&self, swap_id: Uuid, kind: MessageKind, nonce: Uuid) -> Result<()>;
+    async fn has_received(&self, swap_id: Uuid, kind: MessageKind, nonce: Uuid) -> Result<bool>;
+}
+
+/// Records the error that stopped the swap, failing to do so is only logged.
+pub async fn record_error(db: &(dyn Database + Send + Sync), swap_id: Uuid, error: &anyhow::Error) {
+    if let Err(db_error) = db.insert_error(swap_id, &format!("{:#}", error)).await {
+        tracing::warn!(%swap_id, "Failed to record the error of the swap: {:#}", db_error);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitcoin::wallet::StaticFeeRate;
+    use crate::bitcoin::{TxPunish, TxRedeem, WalletBuilder};
+    use crate::env::{GetConfig, Regtest};
+    use crate::secret::Secret;
+    use monero_rpc::wallet::BlockHeight;
+    use rand::rngs::OsRng;
+
+    type TestWallet = bitcoin::Wallet<(), bdk::database::MemoryDatabase, StaticFeeRate>;
+
+    /// The initial states of Alice and Bob, together with the wallet funding
+    /// the Bitcoin of Bob.
+    async fn swap_setup() -> (alice::State0, bob::State0, TestWallet) {
+        let alice_wallet = WalletBuilder::new(bitcoin::Amount::ONE_BTC.as_sat()).build();
+        let bob_wallet = WalletBuilder::new(bitcoin::Amount::ONE_BTC.as_sat()).build();
+        let spending_fee = bitcoin::Amount::from_sat(1_000);
+        let btc_amount = bitcoin::Amount::from_sat(500_000);
+        let xmr_amount = monero::Amount::from_piconero(10000);
+
+        let tx_redeem_fee = alice_wallet
+            .estimate_fee(TxRedeem::weight(), btc_amount)
+            .await
+            .unwrap();
+        let tx_punish_fee = alice_wallet
+            .estimate_fee(TxPunish::weight(), btc_amount)
+            .await
+            .unwrap();
+
+        let config = Regtest::get_config();
+        let redeem_address = alice_wallet.new_address().await.unwrap();
+        let punish_address = alice_wallet.new_address().await.unwrap();
+        let alice_state0 = alice::State0::new(
+            btc_amount,
+            xmr_amount,
+            config,
+            redeem_address,
+            punish_address,
+            tx_redeem_fee,
+            tx_punish_fee,
+            &mut OsRng,
+        );
+        let bob_state0 = bob::State0::new(
+            Uuid::new_v4(),
+            &mut OsRng,
+            btc_amount,
+            xmr_amount,
+            config.bitcoin_cancel_timelock,
+            config.bitcoin_punish_timelock,
+            bob_wallet.new_address().await.unwrap(),
+            config.monero_finality_confirmations,
+            spending_fee,
+            spending_fee,
+        );
+
+        (alice_state0, bob_state0, bob_wallet)
+    }
+
+    /// The states of Alice and Bob right after Bob locked the Bitcoin.
+    async fn locked_swap() -> (alice::State3, bob::State3) {
+        let (alice_state0, bob_state0, bob_wallet) = swap_setup().await;
+
+        let (_, alice_state1) = alice_state0.receive(bob_state0.next_message()).unwrap();
+        let bob_state1 = bob_state0
+            .receive(&bob_wallet, alice_state1.next_message())
+            .await
+            .unwrap();
+        let alice_state2 = alice_state1.receive(bob_state1.next_message()).unwrap();
+        let bob_state2 = bob_state1.receive(alice_state2.next_message()).unwrap();
+        let alice_state3 = alice_state2.receive(bob_state2.next_message()).unwrap();
+        let (bob_state3, _) = bob_state2.lock_btc().await.unwrap();
+
+        (alice_state3, bob_state3)
+    }
+
+    /// The messages of a swap between another Alice and Bob, for a malicious
+    /// party to send instead of its own. They are well formed but belong to
+    /// other keys and transactions.
+    async fn messages_of_another_swap() -> (Message0, Message1, Message2, Message3, Message4) {
+        let (alice_state0, bob_state0, bob_wallet) = swap_setup().await;
+
+        let message0 = bob_state0.next_message();
+        let (_, alice_state1) = alice_state0.receive(message0.clone()).unwrap();
+        let message1 = alice_state1.next_message();
+        let bob_state1 = bob_state0
+            .receive(&bob_wallet, message1.clone())
+            .await
+            .unwrap();
+        let message2 = bob_state1.next_message();
+        let alice_state2 = alice_state1.receive(message2.clone()).unwrap();
+        let message3 = alice_state2.next_message();
+        let bob_state2 = bob_state1.receive(message3.clone()).unwrap();
+        let message4 = bob_state2.next_message();
+
+        (message0, message1, message2, message3, message4)
+    }
+
+    /// Alice about to receive Message2 and Message4, Bob about to receive
+    /// Message3.
+    async fn exchanged_first_messages() -> (alice::State1, bob::State1) {
+        let (alice_state0, bob_state0, bob_wallet) = swap_setup().await;
+
+        let (_, alice_state1) = alice_state0.receive(bob_state0.next_message()).unwrap();
+        let bob_state1 = bob_state0
+            .receive(&bob_wallet, alice_state1.next_message())
+            .await
+            .unwrap();
+
+        (alice_state1, bob_state1)
+    }
+
+    async fn bob_receiving_message1(tamper: impl FnOnce(&mut Message1)) -> anyhow::Error {
+        let (alice_state0, bob_state0, bob_wallet) = swap_setup().await;
+        let (_, alice_state1) = alice_state0.receive(bob_state0.next_message()).unwrap();
+
+        let mut message1 = alice_state1.next_message();
+        tamper(&mut message1);
+
+        bob_state0.receive(&bob_wallet, message1).await.unwrap_err()
+    }
+
+    /// Bob locks the Bitcoin only after receiving Message3, failing to receive
+    /// it aborts the swap before anything is locked.
+    async fn bob_receiving_message3(tamper: impl FnOnce(&mut Message3)) -> anyhow::Error {
+        let (alice_state1, bob_state1) = exchanged_first_messages().await;
+        let alice_state2 = alice_state1.receive(bob_state1.next_message()).unwrap();
+
+        let mut message3 = alice_state2.next_message();
+        tamper(&mut message3);
+
+        bob_state1.receive(message3).unwrap_err()
+    }
+
+    async fn alice_receiving_message0(tamper: impl FnOnce(&mut Message0)) -> anyhow::Error {
+        let (alice_state0, bob_state0, _) = swap_setup().await;
+
+        let mut message0 = bob_state0.next_message();
+        tamper(&mut message0);
+
+        alice_state0.receive(message0).unwrap_err()
+    }
+
+    async fn alice_receiving_message2(tamper: impl FnOnce(&mut Message2)) -> anyhow::Error {
+        let (alice_state1, bob_state1) = exchanged_first_messages().await;
+
+        let mut message2 = bob_state1.next_message();
+        tamper(&mut message2);
+
+        alice_state1.receive(message2).unwrap_err()
+    }
+
+    async fn alice_receiving_message4(tamper: impl FnOnce(&mut Message4)) -> anyhow::Error {
+        let (alice_state1, bob_state1) = exchanged_first_messages().await;
+        let alice_state2 = alice_state1.receive(bob_state1.next_message()).unwrap();
+        let bob_state2 = bob_state1.receive(alice_state2.next_message()).unwrap();
+
+        let mut message4 = bob_state2.next_message();
+        tamper(&mut message4);
+
+        alice_state2.receive(message4).unwrap_err()
+    }
+
+    fn transfer_proof() -> monero::TransferProof {
+        monero::TransferProof::new(
+            monero::TxHash("abcd".to_owned()),
+            monero::PrivateKey {
+                scalar: monero::Scalar::random(&mut OsRng),
+            },
+        )
+    }
+
+    #[tokio::test]
+    async fn cooperative_redeem_only_accepts_the_key_share_alice_committed_to() {
+        let (alice_state3, bob_state3) = locked_swap().await;
+        let bob_state6 = bob_state3.cancel(BlockHeight { height: 0 });
+
+        let wrong_key_share = monero::PrivateKey::from_scalar(monero::Scalar::random(&mut OsRng));
+        assert!(bob_state6.cooperative_redeem(wrong_key_share).is_err());
+
+        let state5 = bob_state6.cooperative_redeem(alice_state3.s_a()).unwrap();
+        let (spend_key, view_key) = state5.xmr_keys();
+        let lock_address = bob_state3.lock_xmr_watch_request(transfer_proof());
+
+        assert_eq!(
+            monero::PublicKey::from_private_key(&spend_key),
+            lock_address.public_spend_key
+        );
+        assert_eq!(view_key, lock_address.view_key);
+    }
+
+    #[tokio::test]
+    async fn transfer_proof_received_twice_is_applied_once() {
+        let (_, bob_state3) = locked_swap().await;
+        let transfer_proof = transfer_proof();
+        let state = BobState::BtcLocked {
+            state3: bob_state3,
+            monero_wallet_restore_blockheight: BlockHeight { height: 0 },
+        };
+
+        let once = state
+            .receive_transfer_proof(transfer_proof.clone())
+            .unwrap();
+        let twice = once.clone().receive_transfer_proof(transfer_proof).unwrap();
+
+        assert!(once.received(MessageKind::TransferProof));
+        assert_eq!(twice, once);
+    }
+
+    #[tokio::test]
+    async fn encrypted_signature_received_twice_is_applied_once() {
+        let (alice_state3, bob_state3) = locked_swap().await;
+        let bob_state4 = bob_state3.xmr_locked(BlockHeight { height: 0 });
+        let encrypted_signature = bob_state4.tx_redeem_encsig();
+        let state = AliceState::XmrLockTransferProofSent {
+            monero_wallet_restore_blockheight: BlockHeight { height: 0 },
+            transfer_proof: transfer_proof(),
+            state3: Box::new(alice_state3),
+        };
+
+        let once = state
+            .receive_encrypted_signature(encrypted_signature.clone(), bob_state4.transcript())
+            .unwrap();
+        let twice = once
+            .clone()
+            .receive_encrypted_signature(encrypted_signature, bob_state4.transcript())
+            .unwrap();
+
+        assert!(once.received(MessageKind::EncryptedSignature));
+        assert_eq!(twice, once);
+    }
+
+    #[tokio::test]
+    async fn redeeming_the_bitcoin_reveals_the_monero_spend_key_to_bob() {
+        let (alice_state3, bob_state3) = locked_swap().await;
+        let bob_state4 = bob_state3.xmr_locked(BlockHeight { height: 0 });
+
+        let tx_redeem = alice_state3
+            .signed_redeem_transaction(&bob_state4.tx_redeem_encsig())
+            .unwrap();
+        let (spend_key, _) = bob_state4.redeem_btc_seen(tx_redeem).unwrap().xmr_keys();
+
+        assert_eq!(
+            monero::PublicKey::from_private_key(&spend_key),
+            alice_state3.lock_xmr_transfer_request().public_spend_key
+        );
+    }
+
+    #[tokio::test]
+    async fn refunding_the_bitcoin_reveals_the_monero_spend_key_to_alice() {
+        let (alice_state3, bob_state3) = locked_swap().await;
+        let bob_state6 = bob_state3.cancel(BlockHeight { height: 0 });
+
+        let tx_refund = bob_state6.signed_refund_transaction().unwrap();
+        let spend_key = alice_state3.extract_monero_private_key(tx_refund).unwrap();
+
+        assert_eq!(
+            monero::PublicKey::from_private_key(&spend_key),
+            alice_state3.lock_xmr_transfer_request().public_spend_key
+        );
+    }
+
+    #[tokio::test]
+    async fn alice_can_cancel_and_punish_without_bob() {
+        let (alice_state3, bob_state3) = locked_swap().await;
+        let bob_state6 = bob_state3.cancel(BlockHeight { height: 0 });
+
+        let tx_cancel = alice_state3.signed_cancel_transaction().unwrap();
+
+        assert_eq!(tx_cancel.txid(), bob_state6.tx_cancel_id());
+        assert!(alice_state3.signed_punish_transaction().is_ok());
+    }
+
+    #[tokio::test]
+    async fn messages_of_a_cancelled_swap_are_rejected() {
+        let (alice_state3, bob_state3) = locked_swap().await;
+        let bob_state4 = bob_state3.clone().xmr_locked(BlockHeight { height: 0 });
+        let bob = BobState::CancelTimelockExpired(bob_state3.cancel(BlockHeight { height: 0 }));
+        let alice = AliceState::CancelTimelockExpired {
+            monero_wallet_restore_blockheight: BlockHeight { height: 0 },
+            transfer_proof: transfer_proof(),
+            state3: Box::new(alice_state3),
+        };
+
+        assert!(bob.receive_transfer_proof(transfer_proof()).is_err());
+        assert!(alice
+            .receive_encrypted_signature(bob_state4.tx_redeem_encsig(), bob_state4.transcript())
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn alices_presigned_transactions_spend_the_lock_and_cancel_transactions() {
+        let (alice_state3, _) = locked_swap().await;
+
+        let transactions = alice_state3.presigned_transactions().unwrap();
+        let (cancel, punish) = (&transactions[0], &transactions[1]);
+
+        assert_eq!(cancel.kind, presigned::Kind::Cancel);
+        assert_eq!(cancel.spends(), Some(alice_state3.tx_lock.txid()));
+        assert_eq!(punish.kind, presigned::Kind::Punish);
+        assert_eq!(punish.spends(), Some(cancel.txid()));
+
+        let serialized = serde_json::to_string(&transactions).unwrap();
+        let deserialized = serde_json::from_str::<Vec<PresignedTransaction>>(&serialized).unwrap();
+        assert_eq!(deserialized, transactions);
+    }
+
+    #[tokio::test]
+    async fn bobs_presigned_transactions_spend_the_lock_and_cancel_transactions() {
+        let (_, bob_state3) = locked_swap().await;
+
+        let transactions = bob_state3.presigned_transactions().unwrap();
+        let (cancel, refund) = (&transactions[0], &transactions[1]);
+
+        assert_eq!(cancel.kind, presigned::Kind::Cancel);
+        assert_eq!(cancel.spends(), Some(bob_state3.tx_lock_id()));
+        assert_eq!(refund.kind, presigned::Kind::Refund);
+        assert_eq!(refund.spends(), Some(cancel.txid()));
+        assert_eq!(
+            cancel.txid(),
+            bob_state3.cancel(BlockHeight { height: 0 }).tx_cancel_id()
+        );
+    }
+
+    #[tokio::test]
+    async fn bob_rejects_a_dleq_proof_of_another_key() {
+        let (_, other_message1, ..) = messages_of_another_swap().await;
+
+        let error = bob_receiving_message1(|message1| {
+            message1.dleq_proof_s_a = other_message1.dleq_proof_s_a;
+        })
+        .await;
+
+        assert_eq!(
+            error.downcast_ref::<InvalidMessage>(),
+            Some(&InvalidMessage::DleqProof {
+                message: MessageKind::Message1
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn bob_rejects_keys_that_do_not_match_the_dleq_proof() {
+        let (_, other_message1, ..) = messages_of_another_swap().await;
+
+        let error = bob_receiving_message1(|message1| {
+            message1.S_a_bitcoin = other_message1.S_a_bitcoin;
+            message1.S_a_monero = other_message1.S_a_monero;
+        })
+        .await;
+
+        assert_eq!(
+            error.downcast_ref::<InvalidMessage>(),
+            Some(&InvalidMessage::DleqProof {
+                message: MessageKind::Message1
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn bob_rejects_a_redeem_fee_exceeding_the_lock_amount() {
+        let error = bob_receiving_message1(|message1| {
+            message1.tx_redeem_fee = bitcoin::Amount::ONE_BTC;
+        })
+        .await;
+
+        assert!(format!("{:#}", error)
+            .starts_with("Alice's fees are invalid: Redeem fee exceeds the lock amount"));
+    }
+
+    #[tokio::test]
+    async fn bob_rejects_a_cancel_signature_of_another_swap() {
+        let (.., other_message3, _) = messages_of_another_swap().await;
+
+        let error = bob_receiving_message3(|message3| {
+            message3.tx_cancel_sig = other_message3.tx_cancel_sig;
+        })
+        .await;
+
+        assert_eq!(
+            error.downcast_ref::<InvalidMessage>(),
+            Some(&InvalidMessage::Signature {
+                message: MessageKind::Message3,
+                transaction: "cancel"
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn bob_rejects_a_refund_encsig_of_another_swap() {
+        let (.., other_message3, _) = messages_of_another_swap().await;
+
+        let error = bob_receiving_message3(|message3| {
+            message3.tx_refund_encsig = other_message3.tx_refund_encsig;
+        })
+        .await;
+
+        assert_eq!(
+            error.downcast_ref::<InvalidMessage>(),
+            Some(&InvalidMessage::EncryptedSignature {
+                message: MessageKind::Message3,
+                transaction: "refund"
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn alice_rejects_a_dleq_proof_of_another_key() {
+        let (other_message0, ..) = messages_of_another_swap().await;
+
+        let error = alice_receiving_message0(|message0| {
+            message0.dleq_proof_s_b = other_message0.dleq_proof_s_b;
+        })
+        .await;
+
+        assert_eq!(
+            error.downcast_ref::<InvalidMessage>(),
+            Some(&InvalidMessage::DleqProof {
+                message: MessageKind::Message0
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn alice_rejects_keys_that_do_not_match_the_dleq_proof() {
+        let (other_message0, ..) = messages_of_another_swap().await;
+
+        let error = alice_receiving_message0(|message0| {
+            message0.S_b_bitcoin = other_message0.S_b_bitcoin;
+            message0.S_b_monero = other_message0.S_b_monero;
+        })
+        .await;
+
+        assert_eq!(
+            error.downcast_ref::<InvalidMessage>(),
+            Some(&InvalidMessage::DleqProof {
+                message: MessageKind::Message0
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn alice_rejects_a_cancel_fee_exceeding_the_lock_amount() {
+        let error = alice_receiving_message0(|message0| {
+            message0.tx_cancel_fee = bitcoin::Amount::ONE_BTC;
+        })
+        .await;
+
+        assert!(format!("{:#}", error)
+            .starts_with("Bob's fees are invalid: Cancel and refund fee exceed the lock amount"));
+    }
+
+    #[tokio::test]
+    async fn alice_rejects_a_lock_transaction_paying_another_amount() {
+        let error = alice_receiving_message2(|message2| {
+            for output in &mut message2.psbt.global.unsigned_tx.output {
+                output.value -= 1;
+            }
+        })
+        .await;
+
+        assert_eq!(
+            error.root_cause().to_string(),
+            "Neither of the two provided outputs pays the right amount!"
+        );
+    }
+
+    #[tokio::test]
+    async fn alice_rejects_a_lock_transaction_of_another_swap() {
+        let (.., other_message2, _, _) = messages_of_another_swap().await;
+
+        let error = alice_receiving_message2(|message2| {
+            message2.psbt = other_message2.psbt;
+        })
+        .await;
+
+        assert_eq!(
+            error.root_cause().to_string(),
+            "Output script is not a shared output"
+        );
+    }
+
+    #[tokio::test]
+    async fn alice_rejects_a_cancel_signature_of_another_swap() {
+        let (.., other_message4) = messages_of_another_swap().await;
+
+        let error = alice_receiving_message4(|message4| {
+            message4.tx_cancel_sig = other_message4.tx_cancel_sig;
+        })
+        .await;
+
+        assert_eq!(
+            error.downcast_ref::<InvalidMessage>(),
+            Some(&InvalidMessage::Signature {
+                message: MessageKind::Message4,
+                transaction: "cancel"
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn alice_rejects_a_punish_signature_of_another_swap() {
+        let (.., other_message4) = messages_of_another_swap().await;
+
+        let error = alice_receiving_message4(|message4| {
+            message4.tx_punish_sig = other_message4.tx_punish_sig;
+        })
+        .await;
+
+        assert_eq!(
+            error.downcast_ref::<InvalidMessage>(),
+            Some(&InvalidMessage::Signature {
+                message: MessageKind::Message4,
+                transaction: "punish"
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn bob_rejects_the_transcript_of_another_swap() {
+        let (.., other_message3, _) = messages_of_another_swap().await;
+
+        let error = bob_receiving_message3(|message3| {
+            message3.transcript = other_message3.transcript;
+        })
+        .await;
+
+        assert_eq!(
+            error.downcast_ref::<InvalidMessage>(),
+            Some(&InvalidMessage::TranscriptMismatch {
+                message: MessageKind::Message3
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn alice_rejects_the_transcript_of_another_swap() {
+        let (.., other_message4) = messages_of_another_swap().await;
+
+        let error = alice_receiving_message4(|message4| {
+            message4.transcript = other_message4.transcript;
+        })
+        .await;
+
+        assert_eq!(
+            error.downcast_ref::<InvalidMessage>(),
+            Some(&InvalidMessage::TranscriptMismatch {
+                message: MessageKind::Message4
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn bob_rejects_a_transcript_not_signed_by_alice() {
+        let error = bob_receiving_message3(|message3| {
+            message3.transcript_sig = message3
+                .transcript
+                .sign(&bitcoin::SecretKey::new_random(&mut OsRng));
+        })
+        .await;
+
+        assert_eq!(
+            error.downcast_ref::<InvalidMessage>(),
+            Some(&InvalidMessage::TranscriptSignature {
+                message: MessageKind::Message3
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn alice_rejects_a_transcript_not_signed_by_bob() {
+        let error = alice_receiving_message4(|message4| {
+            message4.transcript_sig = message4
+                .transcript
+                .sign(&bitcoin::SecretKey::new_random(&mut OsRng));
+        })
+        .await;
+
+        assert_eq!(
+            error.downcast_ref::<InvalidMessage>(),
+            Some(&InvalidMessage::TranscriptSignature {
+                message: MessageKind::Message4
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn alice_rejects_an_encrypted_signature_of_another_swap() {
+        let (alice_state3, bob_state3) = locked_swap().await;
+        let (_, other_bob_state3) = locked_swap().await;
+        let state = AliceState::XmrLockTransferProofSent {
+            monero_wallet_restore_blockheight: BlockHeight { height: 0 },
+            transfer_proof: transfer_proof(),
+            state3: Box::new(alice_state3),
+        };
+
+        let error = state
+            .receive_encrypted_signature(
+                other_bob_state3
+                    .xmr_locked(BlockHeight { height: 0 })
+                    .tx_redeem_encsig(),
+                bob_state3.xmr_locked(BlockHeight { height: 0 }).transcript(),
+            )
+            .unwrap_err();
+
+        assert_eq!(
+            error.downcast_ref::<InvalidMessage>(),
+            Some(&InvalidMessage::EncryptedSignature {
+                message: MessageKind::EncryptedSignature,
+                transaction: "redeem"
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn alice_rejects_an_encrypted_signature_sent_with_another_transcript() {
+        let (alice_state3, bob_state3) = locked_swap().await;
+        let (_, other_bob_state3) = locked_swap().await;
+        let state = AliceState::XmrLockTransferProofSent {
+            monero_wallet_restore_blockheight: BlockHeight { height: 0 },
+            transfer_proof: transfer_proof(),
+            state3: Box::new(alice_state3),
+        };
+        let encrypted_signature = bob_state3
+            .xmr_locked(BlockHeight { height: 0 })
+            .tx_redeem_encsig();
+        let other_transcript = other_bob_state3
+            .xmr_locked(BlockHeight { height: 0 })
+            .transcript();
+
+        for transcript in [other_transcript, None].iter().copied() {
+            let error = state
+                .clone()
+                .receive_encrypted_signature(encrypted_signature.clone(), transcript)
+                .unwrap_err();
+
+            assert_eq!(
+                error.downcast_ref::<InvalidMessage>(),
+                Some(&InvalidMessage::TranscriptMismatch {
+                    message: MessageKind::EncryptedSignature
+                })
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn secret_keys_are_redacted_in_the_debug_output_of_states() {
+        let (alice_state3, bob_state3) = locked_swap().await;
+        let bob_state4 = bob_state3.clone().xmr_locked(BlockHeight { height: 0 });
+        let tx_redeem = alice_state3
+            .signed_redeem_transaction(&bob_state4.tx_redeem_encsig())
+            .unwrap();
+        let bob_state5 = bob_state4.redeem_btc_seen(tx_redeem).unwrap();
+        let spend_key = monero::PrivateKey {
+            scalar: monero::Scalar::random(&mut OsRng),
+        };
+        let alice = AliceState::BtcRefunded {
+            monero_wallet_restore_blockheight: BlockHeight { height: 0 },
+            transfer_proof: transfer_proof(),
+            spend_key: Secret::new(spend_key),
+            state3: Box::new(alice_state3),
+        };
+
+        let debug = format!("{:?} {:?} {:?}", alice, bob_state3, bob_state5);
+
+        for field in &["spend_key:", " a:", " b:", " s_a:", " s_b:", " v:"] {
+            assert!(
+                debug.contains(&format!("{} Secret(..)", field)),
+                "{} is not redacted",
+                field
+            );
+        }
+        assert!(!debug.contains(&spend_key.to_string()));
+        assert!(!debug.contains(&bob_state5.xmr_keys().0.to_string()));
+    }
 }
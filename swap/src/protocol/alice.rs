@@ -1,7 +1,9 @@
 //! Run an XMR/BTC swap in the role of Alice.
 //! Alice holds XMR and wishes receive BTC.
+use crate::asb::policy::Policy;
 use crate::env::Config;
 use crate::protocol::Database;
+use crate::reservations::{Reservation, Reservations};
 use crate::{asb, bitcoin, monero};
 use std::sync::Arc;
 use uuid::Uuid;
@@ -9,6 +11,7 @@ use uuid::Uuid;
 pub use self::state::*;
 pub use self::swap::{run, run_until};
 
+pub mod punish_guard;
 pub mod state;
 pub mod swap;
 
@@ -20,4 +23,16 @@ pub struct Swap {
     pub env_config: Config,
     pub swap_id: Uuid,
     pub db: Arc<dyn Database + Send + Sync>,
+    pub notifier: Option<asb::notifications::Notifier>,
+    /// Whether to bump a stuck redeem transaction with a child paying for it.
+    pub cpfp: bool,
+    /// Holds back the Monero lock of high-value swaps until approved.
+    pub approvals: asb::Approvals,
+    /// Vets the funding of the Bitcoin lock transaction.
+    pub policy: Arc<dyn Policy>,
+    /// The Monero of all swaps that is not locked yet.
+    pub reservations: Reservations<monero::Amount>,
+    /// The Monero of this swap, held until it is locked. Swaps resumed
+    /// without one reserve it right before locking.
+    pub reservation: Option<Reservation<monero::Amount>>,
 }
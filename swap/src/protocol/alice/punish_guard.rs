@@ -0,0 +1,133 @@
+//! The last check before Alice punishes Bob.
+//!
+//! Alice treats the swap as punishable once the cancel transaction has
+//! [`PunishTimelock`](crate::bitcoin::PunishTimelock) confirmations. A reorg
+//! can take some of them back and Bob may have published his refund
+//! transaction since. Punishing then fails at best and at worst punishes an
+//! honest Bob whose refund is dropped along with a reorged block. Before the
+//! punish transaction is published, the cancel transaction therefore has to be
+//! buried by the finality confirmations on top of the timelock, and no refund
+//! transaction may have been seen.
+
+use crate::bitcoin::wallet::ScriptStatus;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Guard {
+    /// The cancel transaction is deep enough and no refund was seen.
+    Punish,
+    /// Bob's refund transaction was seen, Alice has to refund the Monero.
+    Refunded,
+    /// The cancel transaction is no longer confirmed, e.g. after a reorg.
+    CancelUnconfirmed,
+    /// The cancel transaction needs more confirmations.
+    Wait { confirmations: u32, needed: u32 },
+}
+
+/// Decides whether Alice may punish, given the status of the cancel and the
+/// refund transaction. `needed` is the depth the cancel transaction must have.
+pub fn check(cancel: ScriptStatus, refund: ScriptStatus, needed: u32) -> Guard {
+    if refund.has_been_seen() {
+        return Guard::Refunded;
+    }
+
+    match cancel {
+        ScriptStatus::Confirmed(confirmed) if confirmed.confirmations() >= needed => Guard::Punish,
+        ScriptStatus::Confirmed(confirmed) => Guard::Wait {
+            confirmations: confirmed.confirmations(),
+            needed,
+        },
+        // The status could not be fetched, try again on the next block
+        ScriptStatus::Retrying => Guard::Wait {
+            confirmations: 0,
+            needed,
+        },
+        ScriptStatus::Unseen | ScriptStatus::InMempool => Guard::CancelUnconfirmed,
+    }
+}
+
+impl fmt::Display for Guard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Guard::Punish => write!(f, "cancel transaction is final"),
+            Guard::Refunded => write!(f, "refund transaction was seen"),
+            Guard::CancelUnconfirmed => write!(f, "cancel transaction is no longer confirmed"),
+            Guard::Wait {
+                confirmations,
+                needed,
+            } => write!(
+                f,
+                "cancel transaction has {} of {} confirmations",
+                confirmations, needed
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitcoin::wallet::Confirmed;
+
+    fn confirmed(confirmations: u32) -> ScriptStatus {
+        ScriptStatus::from_confirmations(confirmations)
+    }
+
+    #[test]
+    fn punishes_once_the_cancel_transaction_is_deep_enough() {
+        assert_eq!(
+            check(confirmed(12), ScriptStatus::Unseen, 12),
+            Guard::Punish
+        );
+        assert_eq!(
+            check(confirmed(13), ScriptStatus::Unseen, 12),
+            Guard::Punish
+        );
+        assert_eq!(
+            check(confirmed(11), ScriptStatus::Unseen, 12),
+            Guard::Wait {
+                confirmations: 11,
+                needed: 12
+            }
+        );
+    }
+
+    #[test]
+    fn never_punishes_once_a_refund_was_seen() {
+        assert_eq!(
+            check(confirmed(20), ScriptStatus::InMempool, 12),
+            Guard::Refunded
+        );
+        assert_eq!(
+            check(
+                confirmed(20),
+                ScriptStatus::Confirmed(Confirmed::new(0)),
+                12
+            ),
+            Guard::Refunded
+        );
+    }
+
+    #[test]
+    fn reorged_cancel_transaction_is_not_punishable() {
+        assert_eq!(
+            check(ScriptStatus::InMempool, ScriptStatus::Unseen, 12),
+            Guard::CancelUnconfirmed
+        );
+        assert_eq!(
+            check(ScriptStatus::Unseen, ScriptStatus::Unseen, 12),
+            Guard::CancelUnconfirmed
+        );
+    }
+
+    #[test]
+    fn unknown_status_waits() {
+        assert_eq!(
+            check(ScriptStatus::Retrying, ScriptStatus::Unseen, 12),
+            Guard::Wait {
+                confirmations: 0,
+                needed: 12
+            }
+        );
+    }
+}
@@ -6,9 +6,16 @@ use crate::env::Config;
 use crate::monero::wallet::{TransferRequest, WatchRequest};
 use crate::monero::TransferProof;
 use crate::monero_ext::ScalarExt;
-use crate::protocol::{Message0, Message1, Message2, Message3, Message4, CROSS_CURVE_PROOF_SYSTEM};
+use crate::protocol::presigned::{self, PresignedTransaction};
+use crate::protocol::sequence::{MessageKind, Role, Sequence};
+use crate::protocol::transcript::{self, TranscriptHash};
+use crate::protocol::{
+    check_fees, verify_dleq_proof, InvalidMessage, Message0, Message1, Message2, Message3,
+    Message4, CROSS_CURVE_PROOF_SYSTEM,
+};
+use crate::secret::Secret;
 use crate::{bitcoin, monero};
-use anyhow::{anyhow, bail, Context, Result};
+use anyhow::{bail, Context, Result};
 use monero_rpc::wallet::BlockHeight;
 use rand::{CryptoRng, RngCore};
 use serde::{Deserialize, Serialize};
@@ -45,7 +52,7 @@ pub enum AliceState {
     EncSigLearned {
         monero_wallet_restore_blockheight: BlockHeight,
         transfer_proof: TransferProof,
-        encrypted_signature: Box<bitcoin::EncryptedSignature>,
+        encrypted_signature: Box<Secret<bitcoin::EncryptedSignature>>,
         state3: Box<State3>,
     },
     BtcRedeemTransactionPublished {
@@ -60,7 +67,7 @@ pub enum AliceState {
     BtcRefunded {
         monero_wallet_restore_blockheight: BlockHeight,
         transfer_proof: TransferProof,
-        spend_key: monero::PrivateKey,
+        spend_key: Secret<monero::PrivateKey>,
         state3: Box<State3>,
     },
     BtcPunishable {
@@ -74,7 +81,11 @@ pub enum AliceState {
         transfer_proof: TransferProof,
         state3: Box<State3>,
     },
-    BtcPunished,
+    /// Alice keeps her key share so she can still hand it to Bob, it is not
+    /// known for swaps punished by earlier versions.
+    BtcPunished {
+        state3: Option<Box<State3>>,
+    },
     SafelyAborted,
 }
 
@@ -98,7 +109,7 @@ impl fmt::Display for AliceState {
             AliceState::BtcRedeemed => write!(f, "btc is redeemed"),
             AliceState::BtcCancelled { .. } => write!(f, "btc is cancelled"),
             AliceState::BtcRefunded { .. } => write!(f, "btc is refunded"),
-            AliceState::BtcPunished => write!(f, "btc is punished"),
+            AliceState::BtcPunished { .. } => write!(f, "btc is punished"),
             AliceState::SafelyAborted => write!(f, "safely aborted"),
             AliceState::BtcPunishable { .. } => write!(f, "btc is punishable"),
             AliceState::XmrRefunded => write!(f, "xmr is refunded"),
@@ -107,11 +118,148 @@ impl fmt::Display for AliceState {
     }
 }
 
+impl AliceState {
+    /// The id of the Bitcoin lock transaction, as long as the state knows it.
+    pub fn tx_lock_id(&self) -> Option<Txid> {
+        self.state3().map(|state3| state3.tx_lock.txid())
+    }
+
+    /// The Bitcoin Bob locks, as long as the state knows it.
+    pub fn btc_amount(&self) -> Option<bitcoin::Amount> {
+        self.state3().map(|state3| state3.btc())
+    }
+
+    /// The Monero locked by the swap, as long as the state knows it.
+    pub fn xmr_amount(&self) -> Option<monero::Amount> {
+        self.state3().map(|state3| state3.xmr())
+    }
+
+    /// The Monero the swap is yet to lock, including the fee of the lock
+    /// transaction. `None` once it was sent or if the swap stopped before.
+    pub fn xmr_to_lock(&self) -> Option<monero::Amount> {
+        match self {
+            AliceState::Started { state3 }
+            | AliceState::BtcLockTransactionSeen { state3 }
+            | AliceState::BtcLocked { state3 } => state3.xmr().checked_add(monero::MONERO_FEE),
+            _ => None,
+        }
+    }
+
+    /// The messages exchanged with Bob so far. `None` once the swap left the
+    /// happy path, after which Bob's messages are no longer of any use.
+    pub fn sequence(&self) -> Option<Sequence> {
+        let last = match self {
+            AliceState::Started { .. }
+            | AliceState::BtcLockTransactionSeen { .. }
+            | AliceState::BtcLocked { .. }
+            | AliceState::XmrLockTransactionSent { .. }
+            | AliceState::XmrLocked { .. } => MessageKind::Message4,
+            AliceState::XmrLockTransferProofSent { .. } => MessageKind::TransferProof,
+            AliceState::EncSigLearned { .. }
+            | AliceState::BtcRedeemTransactionPublished { .. }
+            | AliceState::BtcRedeemed => MessageKind::EncryptedSignature,
+            AliceState::BtcCancelled { .. }
+            | AliceState::BtcRefunded { .. }
+            | AliceState::BtcPunishable { .. }
+            | AliceState::XmrRefunded
+            | AliceState::CancelTimelockExpired { .. }
+            | AliceState::BtcPunished { .. }
+            | AliceState::SafelyAborted => return None,
+        };
+
+        Some(Sequence::after(Role::Alice, last))
+    }
+
+    /// Whether `message` of Bob was applied to the state already.
+    pub fn received(&self, message: MessageKind) -> bool {
+        self.sequence()
+            .map_or(false, |sequence| sequence.exchanged(message))
+    }
+
+    /// Applies Bob's encrypted signature once it verifies. A signature that
+    /// was applied already, e.g. because Bob sent it again after
+    /// reconnecting, leaves the state as it is.
+    pub fn receive_encrypted_signature(
+        self,
+        encrypted_signature: bitcoin::EncryptedSignature,
+        transcript: Option<TranscriptHash>,
+    ) -> Result<AliceState> {
+        match self {
+            AliceState::XmrLockTransferProofSent {
+                monero_wallet_restore_blockheight,
+                transfer_proof,
+                state3,
+            } => {
+                TranscriptHash::verify_repeated(
+                    state3.transcript,
+                    transcript,
+                    MessageKind::EncryptedSignature,
+                )?;
+                state3.verify_encrypted_signature(&encrypted_signature)?;
+
+                Ok(AliceState::EncSigLearned {
+                    monero_wallet_restore_blockheight,
+                    transfer_proof,
+                    encrypted_signature: Box::new(Secret::new(encrypted_signature)),
+                    state3,
+                })
+            }
+            state if state.received(MessageKind::EncryptedSignature) => Ok(state),
+            state => bail!(
+                "The swap is {}, it does not expect an encrypted signature",
+                state
+            ),
+        }
+    }
+
+    /// The transfer proof of the Monero lock transaction, once it was sent.
+    pub fn transfer_proof(&self) -> Option<&TransferProof> {
+        match self {
+            AliceState::XmrLockTransactionSent { transfer_proof, .. }
+            | AliceState::XmrLocked { transfer_proof, .. }
+            | AliceState::XmrLockTransferProofSent { transfer_proof, .. }
+            | AliceState::EncSigLearned { transfer_proof, .. }
+            | AliceState::BtcCancelled { transfer_proof, .. }
+            | AliceState::BtcRefunded { transfer_proof, .. }
+            | AliceState::BtcPunishable { transfer_proof, .. }
+            | AliceState::CancelTimelockExpired { transfer_proof, .. } => Some(transfer_proof),
+            AliceState::Started { .. }
+            | AliceState::BtcLockTransactionSeen { .. }
+            | AliceState::BtcLocked { .. }
+            | AliceState::BtcRedeemTransactionPublished { .. }
+            | AliceState::BtcRedeemed
+            | AliceState::XmrRefunded
+            | AliceState::BtcPunished { .. }
+            | AliceState::SafelyAborted => None,
+        }
+    }
+
+    /// The state of the swap setup, as long as the state keeps it.
+    pub fn state3(&self) -> Option<&State3> {
+        match self {
+            AliceState::Started { state3 }
+            | AliceState::BtcLockTransactionSeen { state3 }
+            | AliceState::BtcLocked { state3 }
+            | AliceState::XmrLockTransactionSent { state3, .. }
+            | AliceState::XmrLocked { state3, .. }
+            | AliceState::XmrLockTransferProofSent { state3, .. }
+            | AliceState::EncSigLearned { state3, .. }
+            | AliceState::BtcRedeemTransactionPublished { state3 }
+            | AliceState::BtcCancelled { state3, .. }
+            | AliceState::BtcRefunded { state3, .. }
+            | AliceState::BtcPunishable { state3, .. }
+            | AliceState::CancelTimelockExpired { state3, .. } => Some(state3.as_ref()),
+            AliceState::BtcPunished { state3 } => state3.as_deref(),
+            AliceState::BtcRedeemed | AliceState::XmrRefunded | AliceState::SafelyAborted => None,
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct State0 {
-    a: bitcoin::SecretKey,
-    s_a: monero::Scalar,
-    v_a: monero::PrivateViewKey,
+    a: Secret<bitcoin::SecretKey>,
+    s_a: Secret<monero::Scalar>,
+    v_a: Secret<monero::PrivateViewKey>,
     S_a_monero: monero::PublicKey,
     S_a_bitcoin: bitcoin::PublicKey,
     dleq_proof_s_a: CrossCurveDLEQProof,
@@ -147,9 +295,9 @@ impl State0 {
         let (dleq_proof_s_a, (S_a_bitcoin, S_a_monero)) = CROSS_CURVE_PROOF_SYSTEM.prove(&s_a, rng);
 
         Self {
-            a,
-            s_a,
-            v_a,
+            a: Secret::new(a),
+            s_a: Secret::new(s_a),
+            v_a: Secret::new(v_a),
             S_a_bitcoin: S_a_bitcoin.into(),
             S_a_monero: monero::PublicKey {
                 point: S_a_monero.compress(),
@@ -167,22 +315,23 @@ impl State0 {
     }
 
     pub fn receive(self, msg: Message0) -> Result<(Uuid, State1)> {
-        let valid = CROSS_CURVE_PROOF_SYSTEM.verify(
+        verify_dleq_proof(
             &msg.dleq_proof_s_b,
-            (
-                msg.S_b_bitcoin.into(),
-                msg.S_b_monero
-                    .point
-                    .decompress()
-                    .ok_or_else(|| anyhow!("S_b is not a monero curve point"))?,
-            ),
-        );
-
-        if !valid {
-            bail!("Bob's dleq proof doesn't verify")
-        }
+            msg.S_b_bitcoin,
+            msg.S_b_monero,
+            MessageKind::Message0,
+        )?;
+
+        check_fees(
+            self.btc,
+            self.tx_redeem_fee,
+            msg.tx_cancel_fee,
+            msg.tx_refund_fee,
+            self.tx_punish_fee,
+        )
+        .context("Bob's fees are invalid")?;
 
-        let v = self.v_a + msg.v_b;
+        let v = Secret::new(*self.v_a + msg.v_b);
 
         Ok((msg.swap_id, State1 {
             a: self.a,
@@ -212,15 +361,15 @@ impl State0 {
 
 #[derive(Clone, Debug)]
 pub struct State1 {
-    a: bitcoin::SecretKey,
+    a: Secret<bitcoin::SecretKey>,
     B: bitcoin::PublicKey,
-    s_a: monero::Scalar,
+    s_a: Secret<monero::Scalar>,
     S_a_monero: monero::PublicKey,
     S_a_bitcoin: bitcoin::PublicKey,
     S_b_monero: monero::PublicKey,
     S_b_bitcoin: bitcoin::PublicKey,
-    v: monero::PrivateViewKey,
-    v_a: monero::PrivateViewKey,
+    v: Secret<monero::PrivateViewKey>,
+    v_a: Secret<monero::PrivateViewKey>,
     dleq_proof_s_a: CrossCurveDLEQProof,
     btc: bitcoin::Amount,
     xmr: monero::Amount,
@@ -242,7 +391,7 @@ impl State1 {
             S_a_monero: self.S_a_monero,
             S_a_bitcoin: self.S_a_bitcoin,
             dleq_proof_s_a: self.dleq_proof_s_a.clone(),
-            v_a: self.v_a,
+            v_a: *self.v_a,
             redeem_address: self.redeem_address.clone(),
             punish_address: self.punish_address.clone(),
             tx_redeem_fee: self.tx_redeem_fee,
@@ -279,12 +428,12 @@ impl State1 {
 
 #[derive(Clone, Debug)]
 pub struct State2 {
-    a: bitcoin::SecretKey,
+    a: Secret<bitcoin::SecretKey>,
     B: bitcoin::PublicKey,
-    s_a: monero::Scalar,
+    s_a: Secret<monero::Scalar>,
     S_b_monero: monero::PublicKey,
     S_b_bitcoin: bitcoin::PublicKey,
-    v: monero::PrivateViewKey,
+    v: Secret<monero::PrivateViewKey>,
     btc: bitcoin::Amount,
     xmr: monero::Amount,
     cancel_timelock: CancelTimelock,
@@ -319,13 +468,24 @@ impl State2 {
         let tx_refund_encsig = self.a.encsign(self.S_b_bitcoin, tx_refund.digest());
 
         let tx_cancel_sig = self.a.sign(tx_cancel.digest());
+        let transcript = self.transcript_hash();
         Message3 {
             tx_cancel_sig,
             tx_refund_encsig,
+            transcript,
+            transcript_sig: transcript.sign(&self.a),
         }
     }
 
     pub fn receive(self, msg: Message4) -> Result<State3> {
+        let transcript = self.transcript_hash();
+        transcript.verify(
+            msg.transcript,
+            &msg.transcript_sig,
+            &self.B,
+            MessageKind::Message4,
+        )?;
+
         let tx_cancel = bitcoin::TxCancel::new(
             &self.tx_lock,
             self.cancel_timelock,
@@ -333,16 +493,24 @@ impl State2 {
             self.B,
             self.tx_cancel_fee,
         );
-        bitcoin::verify_sig(&self.B, &tx_cancel.digest(), &msg.tx_cancel_sig)
-            .context("Failed to verify cancel transaction")?;
+        bitcoin::verify_sig(&self.B, &tx_cancel.digest(), &msg.tx_cancel_sig).context(
+            InvalidMessage::Signature {
+                message: MessageKind::Message4,
+                transaction: "cancel",
+            },
+        )?;
         let tx_punish = bitcoin::TxPunish::new(
             &tx_cancel,
             &self.punish_address,
             self.punish_timelock,
             self.tx_punish_fee,
         );
-        bitcoin::verify_sig(&self.B, &tx_punish.digest(), &msg.tx_punish_sig)
-            .context("Failed to verify punish transaction")?;
+        bitcoin::verify_sig(&self.B, &tx_punish.digest(), &msg.tx_punish_sig).context(
+            InvalidMessage::Signature {
+                message: MessageKind::Message4,
+                transaction: "punish",
+            },
+        )?;
 
         Ok(State3 {
             a: self.a,
@@ -365,19 +533,46 @@ impl State2 {
             tx_punish_fee: self.tx_punish_fee,
             tx_refund_fee: self.tx_refund_fee,
             tx_cancel_fee: self.tx_cancel_fee,
+            transcript: Some(transcript),
         })
     }
+
+    fn transcript_hash(&self) -> TranscriptHash {
+        transcript::Parameters {
+            btc: self.btc,
+            xmr: self.xmr,
+            cancel_timelock: self.cancel_timelock,
+            punish_timelock: self.punish_timelock,
+            A: self.a.public(),
+            B: self.B,
+            S_a_bitcoin: bitcoin::PublicKey::from(self.s_a.to_secpfun_scalar()),
+            S_b_bitcoin: self.S_b_bitcoin,
+            S_a_monero: monero::PublicKey::from_private_key(&monero::PrivateKey {
+                scalar: *self.s_a,
+            }),
+            S_b_monero: self.S_b_monero,
+            v: self.v.public(),
+            refund_address: &self.refund_address,
+            redeem_address: &self.redeem_address,
+            punish_address: &self.punish_address,
+            tx_lock: self.tx_lock.txid(),
+            tx_redeem_fee: self.tx_redeem_fee,
+            tx_refund_fee: self.tx_refund_fee,
+            tx_punish_fee: self.tx_punish_fee,
+            tx_cancel_fee: self.tx_cancel_fee,
+        }
+        .hash()
+    }
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
 pub struct State3 {
-    a: bitcoin::SecretKey,
+    a: Secret<bitcoin::SecretKey>,
     B: bitcoin::PublicKey,
-    s_a: monero::Scalar,
+    s_a: Secret<monero::Scalar>,
     S_b_monero: monero::PublicKey,
     S_b_bitcoin: bitcoin::PublicKey,
-    pub v: monero::PrivateViewKey,
-    #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
+    pub v: Secret<monero::PrivateViewKey>,
     btc: bitcoin::Amount,
     xmr: monero::Amount,
     pub cancel_timelock: CancelTimelock,
@@ -388,14 +583,14 @@ pub struct State3 {
     pub tx_lock: bitcoin::TxLock,
     tx_punish_sig_bob: bitcoin::Signature,
     tx_cancel_sig_bob: bitcoin::Signature,
-    #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
     tx_redeem_fee: bitcoin::Amount,
-    #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
     tx_punish_fee: bitcoin::Amount,
-    #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
     tx_refund_fee: bitcoin::Amount,
-    #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
     tx_cancel_fee: bitcoin::Amount,
+    /// Checks the one Bob sends along with the encrypted signature, not known
+    /// for swaps set up with earlier versions.
+    #[serde(default)]
+    transcript: Option<TranscriptHash>,
 }
 
 impl State3 {
@@ -416,8 +611,26 @@ impl State3 {
         ))
     }
 
+    pub fn btc(&self) -> bitcoin::Amount {
+        self.btc
+    }
+
+    pub fn xmr(&self) -> monero::Amount {
+        self.xmr
+    }
+
+    pub fn tx_redeem_fee(&self) -> bitcoin::Amount {
+        self.tx_redeem_fee
+    }
+
+    /// Alice's share of the Monero spend key, which she only hands out to let
+    /// Bob redeem the Monero after she punished him.
+    pub fn s_a(&self) -> monero::PrivateKey {
+        monero::PrivateKey::from_scalar(*self.s_a)
+    }
+
     pub fn lock_xmr_transfer_request(&self) -> TransferRequest {
-        let S_a = monero::PublicKey::from_private_key(&monero::PrivateKey { scalar: self.s_a });
+        let S_a = monero::PublicKey::from_private_key(&monero::PrivateKey { scalar: *self.s_a });
 
         let public_spend_key = S_a + self.S_b_monero;
         let public_view_key = self.v.public();
@@ -434,13 +647,12 @@ impl State3 {
         transfer_proof: TransferProof,
         conf_target: u64,
     ) -> WatchRequest {
-        let S_a = monero::PublicKey::from_private_key(&monero::PrivateKey { scalar: self.s_a });
+        let S_a = monero::PublicKey::from_private_key(&monero::PrivateKey { scalar: *self.s_a });
 
         let public_spend_key = S_a + self.S_b_monero;
-        let public_view_key = self.v.public();
         WatchRequest {
             public_spend_key,
-            public_view_key,
+            view_key: *self.v,
             transfer_proof,
             conf_target,
             expected: self.xmr,
@@ -468,13 +680,15 @@ impl State3 {
     pub fn extract_monero_private_key(
         &self,
         published_refund_tx: bitcoin::Transaction,
-    ) -> Result<monero::PrivateKey> {
-        self.tx_refund().extract_monero_private_key(
+    ) -> Result<Secret<monero::PrivateKey>> {
+        let spend_key = self.tx_refund().extract_monero_private_key(
             published_refund_tx,
-            self.s_a,
-            self.a.clone(),
+            *self.s_a,
+            (*self.a).clone(),
             self.S_b_bitcoin,
-        )
+        )?;
+
+        Ok(Secret::new(spend_key))
     }
 
     pub async fn check_for_tx_cancel(
@@ -506,7 +720,7 @@ impl State3 {
         spend_key: monero::PrivateKey,
         transfer_proof: TransferProof,
     ) -> Result<()> {
-        let view_key = self.v;
+        let view_key = *self.v;
 
         // Ensure that the XMR to be refunded are spendable by awaiting 10 confirmations
         // on the lock transaction
@@ -535,27 +749,64 @@ impl State3 {
         Ok(txid)
     }
 
+    /// Verifies that Bob's encrypted signature of the redeem transaction can
+    /// be decrypted with our Monero key share.
+    pub fn verify_encrypted_signature(&self, encsig: &bitcoin::EncryptedSignature) -> Result<()> {
+        bitcoin::verify_encsig(
+            self.B,
+            bitcoin::PublicKey::from(self.s_a.to_secpfun_scalar()),
+            &self.tx_redeem().digest(),
+            encsig,
+        )
+        .context(InvalidMessage::EncryptedSignature {
+            message: MessageKind::EncryptedSignature,
+            transaction: "redeem",
+        })
+    }
+
     pub fn signed_redeem_transaction(
         &self,
-        sig: bitcoin::EncryptedSignature,
+        sig: &bitcoin::EncryptedSignature,
     ) -> Result<bitcoin::Transaction> {
         bitcoin::TxRedeem::new(&self.tx_lock, &self.redeem_address, self.tx_redeem_fee)
-            .complete(sig, self.a.clone(), self.s_a.to_secpfun_scalar(), self.B)
+            .complete(
+                sig.clone(),
+                (*self.a).clone(),
+                self.s_a.to_secpfun_scalar(),
+                self.B,
+            )
             .context("Failed to complete Bitcoin redeem transaction")
     }
 
     pub fn signed_cancel_transaction(&self) -> Result<bitcoin::Transaction> {
         self.tx_cancel()
-            .complete_as_alice(self.a.clone(), self.B, self.tx_cancel_sig_bob.clone())
+            .complete_as_alice((*self.a).clone(), self.B, self.tx_cancel_sig_bob.clone())
             .context("Failed to complete Bitcoin cancel transaction")
     }
 
     pub fn signed_punish_transaction(&self) -> Result<bitcoin::Transaction> {
         self.tx_punish()
-            .complete(self.tx_punish_sig_bob.clone(), self.a.clone(), self.B)
+            .complete(self.tx_punish_sig_bob.clone(), (*self.a).clone(), self.B)
             .context("Failed to complete Bitcoin punish transaction")
     }
 
+    /// The fully signed cancel and punish transactions, valid once the
+    /// respective timelock expired.
+    pub fn presigned_transactions(&self) -> Result<Vec<PresignedTransaction>> {
+        Ok(vec![
+            PresignedTransaction::new(
+                presigned::Kind::Cancel,
+                self.cancel_timelock,
+                self.signed_cancel_transaction()?,
+            ),
+            PresignedTransaction::new(
+                presigned::Kind::Punish,
+                self.punish_timelock,
+                self.signed_punish_transaction()?,
+            ),
+        ])
+    }
+
     fn tx_punish(&self) -> TxPunish {
         bitcoin::TxPunish::new(
             &self.tx_cancel(),
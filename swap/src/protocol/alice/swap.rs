@@ -1,15 +1,28 @@
 //! Run an XMR/BTC swap in the role of Alice.
 //! Alice holds XMR and wishes receive BTC.
-use crate::asb::{EventLoopHandle, LatestRate};
+use crate::amounts::CheckedArithmetic;
+use crate::asb::notifications::Event;
+use crate::asb::policy::{Funding, Policy, Verdict};
+use crate::asb::{Approvals, Decision, EventLoopHandle, LatestRate, PendingApproval};
+use crate::bitcoin::wallet::Watchable;
 use crate::bitcoin::ExpiredTimelocks;
 use crate::env::Config;
-use crate::protocol::alice::{AliceState, Swap};
+use crate::protocol::alice::punish_guard::{self, Guard};
+use crate::protocol::alice::{AliceState, State3, Swap};
+use crate::protocol::deadline::Deadline;
+use crate::protocol::{record_error, Database};
+use crate::reservations::{Reservation, Reservations};
 use crate::{bitcoin, monero};
 use anyhow::{bail, Context, Result};
+use std::convert::TryInto;
 use tokio::select;
 use tokio::time::timeout;
 use uuid::Uuid;
 
+/// How many blocks the redeem transaction may stay unconfirmed before it is
+/// bumped, if enabled.
+const CPFP_AFTER_BLOCKS: u32 = 3;
+
 pub async fn run<LR>(swap: Swap, rate_service: LR) -> Result<AliceState>
 where
     LR: LatestRate + Clone,
@@ -27,27 +40,56 @@ where
     LR: LatestRate + Clone,
 {
     let mut current_state = swap.state;
+    let deadline = Deadline::load(
+        swap.db.clone(),
+        swap.swap_id,
+        swap.env_config.max_swap_duration,
+    )
+    .await?;
 
     while !is_complete(&current_state) && !exit_early(&current_state) {
-        current_state = next_state(
+        let next = next_state(
             swap.swap_id,
             current_state,
             &mut swap.event_loop_handle,
             swap.bitcoin_wallet.as_ref(),
             swap.monero_wallet.as_ref(),
             &swap.env_config,
+            swap.cpfp,
+            &swap.approvals,
+            swap.policy.as_ref(),
+            swap.db.as_ref(),
+            &swap.reservations,
+            &mut swap.reservation,
+            &deadline,
             rate_service.clone(),
         )
-        .await?;
+        .await;
+        current_state = match next {
+            Ok(state) => state,
+            Err(error) => {
+                record_error(swap.db.as_ref(), swap.swap_id, &error).await;
+                return Err(error);
+            }
+        };
+
+        if current_state.xmr_to_lock().is_none() {
+            swap.reservation = None;
+        }
 
         swap.db
             .insert_latest_state(swap.swap_id, current_state.clone().into())
             .await?;
+
+        if let (Some(notifier), Some(event)) = (&swap.notifier, Event::reached(&current_state)) {
+            notifier.notify(swap.swap_id, event);
+        }
     }
 
     Ok(current_state)
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn next_state<LR>(
     swap_id: Uuid,
     state: AliceState,
@@ -55,6 +97,13 @@ async fn next_state<LR>(
     bitcoin_wallet: &bitcoin::Wallet,
     monero_wallet: &monero::Wallet,
     env_config: &Config,
+    cpfp: bool,
+    approvals: &Approvals,
+    policy: &dyn Policy,
+    db: &(dyn Database + Send + Sync),
+    reservations: &Reservations<monero::Amount>,
+    reservation: &mut Option<Reservation<monero::Amount>>,
+    deadline: &Deadline,
     mut rate_service: LR,
 ) -> Result<AliceState>
 where
@@ -113,6 +162,84 @@ where
         AliceState::BtcLocked { state3 } => {
             match state3.expired_timelocks(bitcoin_wallet).await? {
                 ExpiredTimelocks::None => {
+                    if deadline.check("aborting before locking the Monero").await? {
+                        return Ok(AliceState::SafelyAborted);
+                    }
+
+                    let vetted = vetted_before(swap_id, db).await?;
+                    if vetted {
+                        tracing::debug!("Funding and Monero lock were vetted before the reorg");
+                    }
+
+                    if !vetted {
+                        if let Verdict::Reject { reason } =
+                            vet_funding(swap_id, &state3, policy, db).await?
+                        {
+                            tracing::info!(
+                                "Funding of the Bitcoin lock refused by policy, aborting the swap: {}",
+                                reason
+                            );
+
+                            return Ok(AliceState::SafelyAborted);
+                        }
+                    }
+
+                    if !vetted && approvals.required(state3.btc()) {
+                        let decision = approve_xmr_lock(
+                            swap_id,
+                            &state3,
+                            approvals,
+                            db,
+                            bitcoin_wallet,
+                            deadline,
+                        )
+                        .await?;
+
+                        if decision == Decision::Reject {
+                            return Ok(AliceState::SafelyAborted);
+                        }
+                    }
+
+                    if reservation.is_none() {
+                        *reservation =
+                            Some(reserve_xmr(&state3, reservations, monero_wallet).await?);
+                    }
+
+                    // Quotes offer the Monero of outputs that are not unlocked yet, see
+                    // `quote::depth`. Waiting for them happens before the lock transaction is
+                    // checked again, a reorg while waiting is caught by that check.
+                    let needed = state3.xmr().try_add(monero::MONERO_FEE)?;
+                    let tx_lock_status = bitcoin_wallet.subscribe_to(state3.tx_lock.clone()).await;
+                    select! {
+                        unlocked = monero_wallet.wait_for_unlocked_balance(needed, env_config.monero_unlock_delay()) => {
+                            if !unlocked? {
+                                tracing::warn!(%needed, "Monero did not unlock in time, aborting the swap");
+
+                                return Ok(AliceState::SafelyAborted);
+                            }
+                        }
+                        result = tx_lock_status.wait_until_confirmed_with(state3.cancel_timelock) => {
+                            result?;
+                            tracing::info!("Cancel timelock expired while waiting for the Monero to unlock, aborting the swap");
+
+                            return Ok(AliceState::SafelyAborted);
+                        }
+                    }
+
+                    // A reorg since the lock transaction became final can have taken back its
+                    // confirmations, the Monero must only be locked on top of a final lock
+                    let tx_lock_status = bitcoin_wallet.status_of_script(&state3.tx_lock).await?;
+                    if !tx_lock_status.is_confirmed_with(env_config.bitcoin_finality_confirmations)
+                    {
+                        tracing::warn!(
+                            txid = %state3.tx_lock.txid(),
+                            status = %tx_lock_status,
+                            "Bitcoin lock transaction is no longer final, waiting for it again"
+                        );
+
+                        return Ok(AliceState::BtcLockTransactionSeen { state3 });
+                    }
+
                     // Record the current monero wallet block height so we don't have to scan from
                     // block 0 for scenarios where we create a refund wallet.
                     let monero_wallet_restore_blockheight = monero_wallet.block_height().await?;
@@ -166,6 +293,18 @@ where
             let tx_lock_status = bitcoin_wallet.subscribe_to(state3.tx_lock.clone()).await;
 
             tokio::select! {
+                biased; // make sure a passed deadline stops sending the transfer proof
+
+                result = deadline.passed("no longer sending the transfer proof, waiting for the cancel timelock") => {
+                    result?;
+                    tx_lock_status.wait_until_confirmed_with(state3.cancel_timelock).await?;
+
+                    AliceState::CancelTimelockExpired {
+                        monero_wallet_restore_blockheight,
+                        transfer_proof,
+                        state3,
+                    }
+                }
                 result = event_loop_handle.send_transfer_proof(transfer_proof.clone()) => {
                    result?;
 
@@ -203,14 +342,23 @@ where
                         state3,
                     }
                 }
-                enc_sig = event_loop_handle.recv_encrypted_signature() => {
+                msg = event_loop_handle.recv_encrypted_signature() => {
                     tracing::info!("Received encrypted signature");
+                    let msg = msg?;
 
-                    AliceState::EncSigLearned {
+                    let state = AliceState::XmrLockTransferProofSent {
                         monero_wallet_restore_blockheight,
                         transfer_proof,
-                        encrypted_signature: Box::new(enc_sig?),
                         state3,
+                    };
+
+                    // Bob can still send a valid one, until then we wait for the cancel timelock
+                    match state.clone().receive_encrypted_signature(msg.tx_redeem_encsig, msg.transcript) {
+                        Ok(state) => state,
+                        Err(error) => {
+                            tracing::warn!("Ignoring encrypted signature: {:#}", error);
+                            state
+                        }
                     }
                 }
             }
@@ -223,7 +371,7 @@ where
         } => match state3.expired_timelocks(bitcoin_wallet).await? {
             ExpiredTimelocks::None => {
                 let tx_lock_status = bitcoin_wallet.subscribe_to(state3.tx_lock.clone()).await;
-                match state3.signed_redeem_transaction(*encrypted_signature) {
+                match state3.signed_redeem_transaction(&encrypted_signature) {
                     Ok(tx) => match bitcoin_wallet.broadcast(tx, "redeem").await {
                         Ok((_, subscription)) => match subscription.wait_until_seen().await {
                             Ok(_) => AliceState::BtcRedeemTransactionPublished { state3 },
@@ -272,7 +420,19 @@ where
         AliceState::BtcRedeemTransactionPublished { state3 } => {
             let subscription = bitcoin_wallet.subscribe_to(state3.tx_redeem()).await;
 
-            match subscription.wait_until_final().await {
+            let finality = subscription.wait_until_final();
+            tokio::pin!(finality);
+            let result = if cpfp {
+                let tx_redeem = state3.tx_redeem();
+                select! {
+                    result = &mut finality => result,
+                    _ = cpfp_if_stuck(bitcoin_wallet, &tx_redeem) => finality.await,
+                }
+            } else {
+                finality.await
+            };
+
+            match result {
                 Ok(_) => AliceState::BtcRedeemed,
                 Err(e) => {
                     bail!("The Bitcoin redeem transaction was seen in mempool, but waiting for finality timed out with {}. Manual investigation might be needed to ensure that the transaction was included.", e)
@@ -313,7 +473,8 @@ where
 
             select! {
                 seen_refund = tx_refund_status.wait_until_seen() => {
-                    seen_refund.context("Failed to monitor refund transaction")?;
+                    let status = seen_refund.context("Failed to monitor refund transaction")?;
+                    tracing::info!(txid = %state3.tx_refund().txid(), %status, "Found Bitcoin refund transaction");
 
                     let published_refund_tx = bitcoin_wallet.get_raw_transaction(state3.tx_refund().txid()).await?;
                     let spend_key = state3.extract_monero_private_key(published_refund_tx)?;
@@ -347,7 +508,7 @@ where
                     monero_wallet,
                     monero_wallet_restore_blockheight,
                     swap_id.to_string(),
-                    spend_key,
+                    *spend_key,
                     transfer_proof,
                 )
                 .await?;
@@ -359,10 +520,37 @@ where
             transfer_proof,
             state3,
         } => {
+            let needed =
+                u32::from(state3.punish_timelock) + env_config.bitcoin_finality_confirmations;
+            match guard_punish(&state3, bitcoin_wallet, needed).await? {
+                Guard::Punish => {}
+                Guard::Refunded => {
+                    let published_refund_tx = state3.fetch_tx_refund(bitcoin_wallet).await?;
+                    let spend_key = state3.extract_monero_private_key(published_refund_tx)?;
+
+                    return Ok(AliceState::BtcRefunded {
+                        monero_wallet_restore_blockheight,
+                        transfer_proof,
+                        spend_key,
+                        state3,
+                    });
+                }
+                // Publishes the cancel transaction again if it was dropped
+                Guard::CancelUnconfirmed | Guard::Wait { .. } => {
+                    return Ok(AliceState::CancelTimelockExpired {
+                        monero_wallet_restore_blockheight,
+                        transfer_proof,
+                        state3,
+                    });
+                }
+            }
+
             let punish = state3.punish_btc(bitcoin_wallet).await;
 
             match punish {
-                Ok(_) => AliceState::BtcPunished,
+                Ok(_) => AliceState::BtcPunished {
+                    state3: Some(state3),
+                },
                 Err(error) => {
                     tracing::warn!("Failed to publish punish transaction: {:#}", error);
 
@@ -392,17 +580,194 @@ where
         }
         AliceState::XmrRefunded => AliceState::XmrRefunded,
         AliceState::BtcRedeemed => AliceState::BtcRedeemed,
-        AliceState::BtcPunished => AliceState::BtcPunished,
+        AliceState::BtcPunished { state3 } => AliceState::BtcPunished { state3 },
         AliceState::SafelyAborted => AliceState::SafelyAborted,
     })
 }
 
+/// Waits until the cancel transaction has `needed` confirmations, re-checking
+/// it and the refund transaction on every block, see [`punish_guard`].
+///
+/// Never returns [`Guard::Wait`].
+async fn guard_punish(
+    state3: &State3,
+    bitcoin_wallet: &bitcoin::Wallet,
+    needed: u32,
+) -> Result<Guard> {
+    let mut blocks = bitcoin_wallet.subscribe_to_blocks().await;
+
+    loop {
+        let cancel = bitcoin_wallet.status_of_script(&state3.tx_cancel()).await?;
+        let refund = bitcoin_wallet.status_of_script(&state3.tx_refund()).await?;
+
+        match punish_guard::check(cancel, refund, needed) {
+            guard @ Guard::Wait { .. } => {
+                tracing::info!(%guard, "Waiting before punishing");
+
+                blocks
+                    .changed()
+                    .await
+                    .context("Stopped receiving new blocks")?;
+            }
+            Guard::Punish => return Ok(Guard::Punish),
+            guard => {
+                tracing::warn!(%guard, "Not punishing");
+
+                return Ok(guard);
+            }
+        }
+    }
+}
+
+/// Bumps the fee of `tx` with a child paying for it if it is still unconfirmed
+/// after [`CPFP_AFTER_BLOCKS`] blocks.
+async fn cpfp_if_stuck(bitcoin_wallet: &bitcoin::Wallet, tx: &impl Watchable) {
+    let mut blocks = bitcoin_wallet.subscribe_to_blocks().await;
+    let bump_at = *blocks.borrow() + CPFP_AFTER_BLOCKS;
+    while *blocks.borrow() < bump_at {
+        if blocks.changed().await.is_err() {
+            return;
+        }
+    }
+
+    match bitcoin_wallet.status_of_script(tx).await {
+        Ok(status) if status.is_confirmed() => {}
+        Ok(_) => match bitcoin_wallet.cpfp(tx.id()).await {
+            Ok(child) => {
+                tracing::info!(txid = %tx.id(), %child, "Bumped the fee of the unconfirmed transaction")
+            }
+            Err(error) => {
+                tracing::warn!(txid = %tx.id(), "Failed to bump the fee of the unconfirmed transaction: {:#}", error)
+            }
+        },
+        Err(error) => {
+            tracing::warn!(txid = %tx.id(), "Failed to check whether the transaction confirmed: {:#}", error)
+        }
+    }
+}
+
+/// Reserves the Monero of a swap that was resumed without a reservation,
+/// failing if the balance is reserved for other swaps.
+async fn reserve_xmr(
+    state3: &State3,
+    reservations: &Reservations<monero::Amount>,
+    monero_wallet: &monero::Wallet,
+) -> Result<Reservation<monero::Amount>> {
+    let needed = state3.xmr().try_add(monero::MONERO_FEE)?;
+    let balance = monero_wallet.get_balance().await?;
+
+    reservations.reserve(needed, balance).with_context(|| {
+        format!(
+            "Balance of {} does not cover locking {} next to the Monero reserved for other swaps",
+            balance, needed
+        )
+    })
+}
+
+/// Shows the outputs that funded the Bitcoin lock transaction to the policy, a
+/// policy that fails refuses the funding.
+async fn vet_funding(
+    swap_id: Uuid,
+    state3: &State3,
+    policy: &dyn Policy,
+    db: &(dyn Database + Send + Sync),
+) -> Result<Verdict> {
+    let funding = Funding {
+        peer: db.get_peer_id(swap_id).await?,
+        swap_id,
+        outpoints: state3.tx_lock.funding(),
+    };
+
+    Ok(policy
+        .vet_funding(funding)
+        .await
+        .unwrap_or_else(|error| Verdict::Reject {
+            reason: format!("{:#}", error),
+        }))
+}
+
+/// Whether the funding of the swap was vetted and locking its Monero approved
+/// before. That is the case once the swap went back from `BtcLocked` to
+/// waiting for the lock transaction after a reorg, which only happens after
+/// both passed. The lock transaction and the amounts are the same after a
+/// reorg, so the verdicts still hold.
+async fn vetted_before(swap_id: Uuid, db: &(dyn Database + Send + Sync)) -> Result<bool> {
+    let mut locked = false;
+
+    for state in db.get_states(swap_id).await? {
+        match state.try_into()? {
+            AliceState::BtcLocked { .. } => locked = true,
+            AliceState::BtcLockTransactionSeen { .. } if locked => return Ok(true),
+            _ => {}
+        }
+    }
+
+    Ok(false)
+}
+
+/// Waits for the operator to decide about locking the Monero of a swap that
+/// requires approval. A swap that is still undecided when the cancel timelock
+/// expires or the deadline passes is rejected.
+async fn approve_xmr_lock(
+    swap_id: Uuid,
+    state3: &State3,
+    approvals: &Approvals,
+    db: &(dyn Database + Send + Sync),
+    bitcoin_wallet: &bitcoin::Wallet,
+    deadline: &Deadline,
+) -> Result<Decision> {
+    let pending = PendingApproval {
+        swap_id,
+        peer_id: db
+            .get_peer_id(swap_id)
+            .await
+            .ok()
+            .map(|peer_id| peer_id.to_string()),
+        btc: state3.btc(),
+        xmr: state3.xmr(),
+        xmr_lock_fee: monero::MONERO_FEE,
+        btc_redeem_fee: state3.tx_redeem_fee(),
+    };
+
+    tracing::info!(
+        btc = %pending.btc,
+        xmr = %pending.xmr,
+        "Waiting for approval to lock the Monero, see `asb admin approvals`"
+    );
+
+    let tx_lock_status = bitcoin_wallet.subscribe_to(state3.tx_lock.clone()).await;
+
+    select! {
+        decision = approvals.request(pending) => {
+            match decision {
+                Decision::Approve => tracing::info!("Locking the Monero was approved"),
+                Decision::Reject => tracing::info!("Locking the Monero was rejected, aborting the swap"),
+            }
+
+            Ok(decision)
+        }
+        result = tx_lock_status.wait_until_confirmed_with(state3.cancel_timelock) => {
+            result?;
+            approvals.withdraw(swap_id);
+            tracing::info!("Cancel timelock expired before locking the Monero was approved, aborting the swap");
+
+            Ok(Decision::Reject)
+        }
+        result = deadline.passed("aborting before locking the Monero was approved") => {
+            result?;
+            approvals.withdraw(swap_id);
+
+            Ok(Decision::Reject)
+        }
+    }
+}
+
 pub(crate) fn is_complete(state: &AliceState) -> bool {
     matches!(
         state,
         AliceState::XmrRefunded
             | AliceState::BtcRedeemed
-            | AliceState::BtcPunished
+            | AliceState::BtcPunished { .. }
             | AliceState::SafelyAborted
     )
 }
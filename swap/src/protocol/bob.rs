@@ -1,10 +1,15 @@
 use std::sync::Arc;
 
 use anyhow::Result;
+use async_trait::async_trait;
 use uuid::Uuid;
 
+use crate::bitcoin::CancelTimelock;
+use crate::network::watchtower;
 use crate::protocol::Database;
+use crate::reservations::Reservation;
 use crate::{bitcoin, cli, env, monero};
+use libp2p::identity;
 
 pub use self::state::*;
 pub use self::swap::{run, run_until};
@@ -18,10 +23,43 @@ pub struct Swap {
     pub event_loop_handle: cli::EventLoopHandle,
     pub db: Arc<dyn Database + Send + Sync>,
     pub bitcoin_wallet: Arc<bitcoin::Wallet>,
-    pub monero_wallet: Arc<monero::Wallet>,
+    pub monero_wallet: Arc<dyn monero::MoneroWallet>,
     pub env_config: env::Config,
     pub id: Uuid,
     pub monero_receive_address: monero::Address,
+    /// Asked in order before the Bitcoin is locked, the lock only goes ahead
+    /// if all of them approve it.
+    pub lock_approvals: Vec<Arc<dyn LockApproval>>,
+    /// The Bitcoin of this swap, held until it is locked.
+    pub reservation: Option<Reservation<bitcoin::Amount>>,
+    /// Gets the presigned cancel and refund transactions when the Bitcoin is
+    /// locked.
+    pub watchtower: Option<watchtower::Client>,
+    /// Signs the receipt of the swap once the Monero is redeemed, see
+    /// [`receipt`](crate::protocol::receipt).
+    pub receipt_identity: Option<identity::Keypair>,
+}
+
+/// The Bitcoin lock of a swap as it is about to be broadcast.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LockDetails {
+    pub swap_id: Uuid,
+    pub btc: bitcoin::Amount,
+    /// `None` if the wallet did not include the spent outputs in the lock
+    /// transaction.
+    pub btc_lock_fee: Option<bitcoin::Amount>,
+    /// Paid out of the locked Bitcoin if the swap is refunded.
+    pub btc_cancel_fee: bitcoin::Amount,
+    pub btc_refund_fee: bitcoin::Amount,
+    pub xmr: monero::Amount,
+    pub cancel_timelock: CancelTimelock,
+}
+
+/// Decides whether the Bitcoin of a swap is locked, e.g. by asking the user.
+/// A declined swap is aborted, nothing was locked yet.
+#[async_trait]
+pub trait LockApproval: Send + Sync {
+    async fn approve(&self, lock: &LockDetails) -> Result<bool>;
 }
 
 impl Swap {
@@ -30,17 +68,19 @@ impl Swap {
         db: Arc<dyn Database + Send + Sync>,
         id: Uuid,
         bitcoin_wallet: Arc<bitcoin::Wallet>,
-        monero_wallet: Arc<monero::Wallet>,
+        monero_wallet: Arc<dyn monero::MoneroWallet>,
         env_config: env::Config,
         event_loop_handle: cli::EventLoopHandle,
         monero_receive_address: monero::Address,
         bitcoin_change_address: bitcoin::Address,
         btc_amount: bitcoin::Amount,
+        xmr_amount: Option<monero::Amount>,
     ) -> Self {
         Self {
             state: BobState::Started {
                 btc_amount,
                 change_address: bitcoin_change_address,
+                xmr_amount,
             },
             event_loop_handle,
             db,
@@ -49,6 +89,10 @@ impl Swap {
             env_config,
             id,
             monero_receive_address,
+            lock_approvals: Vec::new(),
+            reservation: None,
+            watchtower: None,
+            receipt_identity: None,
         }
     }
 
@@ -57,7 +101,7 @@ impl Swap {
         db: Arc<dyn Database + Send + Sync>,
         id: Uuid,
         bitcoin_wallet: Arc<bitcoin::Wallet>,
-        monero_wallet: Arc<monero::Wallet>,
+        monero_wallet: Arc<dyn monero::MoneroWallet>,
         env_config: env::Config,
         event_loop_handle: cli::EventLoopHandle,
         monero_receive_address: monero::Address,
@@ -73,6 +117,32 @@ impl Swap {
             env_config,
             id,
             monero_receive_address,
+            lock_approvals: Vec::new(),
+            reservation: None,
+            watchtower: None,
+            receipt_identity: None,
         })
     }
+
+    pub fn with_lock_approval(mut self, lock_approval: Arc<dyn LockApproval>) -> Self {
+        self.lock_approvals.push(lock_approval);
+        self
+    }
+
+    pub fn with_reservation(mut self, reservation: Reservation<bitcoin::Amount>) -> Self {
+        self.reservation = Some(reservation);
+        self
+    }
+
+    pub fn with_watchtower(mut self, watchtower: watchtower::Client) -> Self {
+        self.watchtower = Some(watchtower);
+        self
+    }
+
+    /// Asks Alice to countersign a receipt signed with `identity` once the
+    /// Monero is redeemed.
+    pub fn with_receipt(mut self, identity: identity::Keypair) -> Self {
+        self.receipt_identity = Some(identity);
+        self
+    }
 }
@@ -7,8 +7,16 @@ use crate::monero;
 use crate::monero::wallet::WatchRequest;
 use crate::monero::{monero_private_key, TransferProof};
 use crate::monero_ext::ScalarExt;
-use crate::protocol::{Message0, Message1, Message2, Message3, Message4, CROSS_CURVE_PROOF_SYSTEM};
-use anyhow::{anyhow, bail, Context, Result};
+use crate::protocol::bob::LockDetails;
+use crate::protocol::presigned::{self, PresignedTransaction};
+use crate::protocol::sequence::{MessageKind, Role, Sequence};
+use crate::protocol::transcript::{self, TranscriptHash};
+use crate::protocol::{
+    check_fees, verify_dleq_proof, InvalidMessage, Message0, Message1, Message2, Message3,
+    Message4, CROSS_CURVE_PROOF_SYSTEM,
+};
+use crate::secret::Secret;
+use anyhow::{bail, Context, Result};
 use bdk::database::BatchDatabase;
 use ecdsa_fun::adaptor::{Adaptor, HashTranscript};
 use ecdsa_fun::nonce::Deterministic;
@@ -26,6 +34,8 @@ pub enum BobState {
     Started {
         btc_amount: bitcoin::Amount,
         change_address: bitcoin::Address,
+        /// The exact amount of XMR asked for, if the swap is denominated in XMR.
+        xmr_amount: Option<monero::Amount>,
     },
     SwapSetupCompleted(State2),
     BtcLocked {
@@ -74,14 +84,137 @@ impl fmt::Display for BobState {
     }
 }
 
+impl BobState {
+    /// The id of the Bitcoin lock transaction, once Bob locked the Bitcoin.
+    pub fn tx_lock_id(&self) -> Option<Txid> {
+        match self {
+            BobState::Started { .. }
+            | BobState::SwapSetupCompleted(_)
+            | BobState::SafelyAborted => None,
+            BobState::BtcLocked { state3, .. }
+            | BobState::XmrLockProofReceived { state: state3, .. } => Some(state3.tx_lock_id()),
+            BobState::XmrLocked(state4) | BobState::EncSigSent(state4) => {
+                Some(state4.tx_lock.txid())
+            }
+            BobState::BtcRedeemed(state5) => Some(state5.tx_lock_id()),
+            BobState::CancelTimelockExpired(state6)
+            | BobState::BtcCancelled(state6)
+            | BobState::BtcRefunded(state6) => Some(state6.tx_lock_id()),
+            BobState::XmrRedeemed { tx_lock_id } | BobState::BtcPunished { tx_lock_id } => {
+                Some(*tx_lock_id)
+            }
+        }
+    }
+
+    /// The Bitcoin locked by the swap, as long as the state knows it.
+    pub fn btc_amount(&self) -> Option<bitcoin::Amount> {
+        match self {
+            BobState::Started { btc_amount, .. } => Some(*btc_amount),
+            BobState::SwapSetupCompleted(state2) => Some(state2.tx_lock.lock_amount()),
+            BobState::BtcLocked { state3, .. }
+            | BobState::XmrLockProofReceived { state: state3, .. } => {
+                Some(state3.tx_lock.lock_amount())
+            }
+            BobState::XmrLocked(state4) | BobState::EncSigSent(state4) => {
+                Some(state4.tx_lock.lock_amount())
+            }
+            BobState::BtcRedeemed(state5) => Some(state5.tx_lock.lock_amount()),
+            BobState::CancelTimelockExpired(state6)
+            | BobState::BtcCancelled(state6)
+            | BobState::BtcRefunded(state6) => Some(state6.tx_lock.lock_amount()),
+            BobState::XmrRedeemed { .. }
+            | BobState::BtcPunished { .. }
+            | BobState::SafelyAborted => None,
+        }
+    }
+
+    /// The Monero Alice locks, as long as the state knows it. Bob forgets the
+    /// amount once the Monero is locked.
+    pub fn xmr_amount(&self) -> Option<monero::Amount> {
+        match self {
+            BobState::Started { xmr_amount, .. } => *xmr_amount,
+            BobState::SwapSetupCompleted(state2) => Some(state2.xmr),
+            BobState::BtcLocked { state3, .. }
+            | BobState::XmrLockProofReceived { state: state3, .. } => Some(state3.xmr),
+            BobState::XmrLocked(_)
+            | BobState::EncSigSent(_)
+            | BobState::BtcRedeemed(_)
+            | BobState::CancelTimelockExpired(_)
+            | BobState::BtcCancelled(_)
+            | BobState::BtcRefunded(_)
+            | BobState::XmrRedeemed { .. }
+            | BobState::BtcPunished { .. }
+            | BobState::SafelyAborted => None,
+        }
+    }
+
+    /// The messages exchanged with Alice so far. `None` once the swap left
+    /// the happy path, after which Alice's messages are no longer of any use.
+    pub fn sequence(&self) -> Option<Sequence> {
+        let last = match self {
+            BobState::Started { .. } => return Some(Sequence::new(Role::Bob)),
+            BobState::SwapSetupCompleted(_) | BobState::BtcLocked { .. } => MessageKind::Message4,
+            BobState::XmrLockProofReceived { .. } | BobState::XmrLocked(_) => {
+                MessageKind::TransferProof
+            }
+            BobState::EncSigSent(_) | BobState::BtcRedeemed(_) | BobState::XmrRedeemed { .. } => {
+                MessageKind::EncryptedSignature
+            }
+            BobState::CancelTimelockExpired(_)
+            | BobState::BtcCancelled(_)
+            | BobState::BtcRefunded(_)
+            | BobState::BtcPunished { .. }
+            | BobState::SafelyAborted => return None,
+        };
+
+        Some(Sequence::after(Role::Bob, last))
+    }
+
+    /// Whether `message` of Alice was applied to the state already.
+    pub fn received(&self, message: MessageKind) -> bool {
+        self.sequence()
+            .map_or(false, |sequence| sequence.exchanged(message))
+    }
+
+    /// Applies Alice's transfer proof. A transfer proof that was applied
+    /// already, e.g. because Alice sent it again after reconnecting, leaves
+    /// the state as it is.
+    pub fn receive_transfer_proof(self, transfer_proof: TransferProof) -> Result<BobState> {
+        match self {
+            BobState::BtcLocked {
+                state3,
+                monero_wallet_restore_blockheight,
+            } => Ok(BobState::XmrLockProofReceived {
+                state: state3,
+                lock_transfer_proof: transfer_proof,
+                monero_wallet_restore_blockheight,
+            }),
+            state if state.received(MessageKind::TransferProof) => Ok(state),
+            state => bail!("The swap is {}, it does not expect a transfer proof", state),
+        }
+    }
+
+    /// The transfer proof of the Monero lock transaction, which is only kept
+    /// until the transaction is confirmed.
+    pub fn xmr_lock_proof(&self) -> Option<&TransferProof> {
+        match self {
+            BobState::XmrLockProofReceived {
+                lock_transfer_proof,
+                ..
+            } => Some(lock_transfer_proof),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct State0 {
     swap_id: Uuid,
-    b: bitcoin::SecretKey,
-    s_b: monero::Scalar,
+    b: Secret<bitcoin::SecretKey>,
+    s_b: Secret<monero::Scalar>,
     S_b_monero: monero::PublicKey,
     S_b_bitcoin: bitcoin::PublicKey,
-    v_b: monero::PrivateViewKey,
+    v_b: Secret<monero::PrivateViewKey>,
     dleq_proof_s_b: CrossCurveDLEQProof,
     btc: bitcoin::Amount,
     xmr: monero::Amount,
@@ -116,9 +249,9 @@ impl State0 {
 
         Self {
             swap_id,
-            b,
-            s_b,
-            v_b,
+            b: Secret::new(b),
+            s_b: Secret::new(s_b),
+            v_b: Secret::new(v_b),
             S_b_bitcoin: bitcoin::PublicKey::from(S_b_bitcoin),
             S_b_monero: monero::PublicKey {
                 point: S_b_monero.compress(),
@@ -142,7 +275,7 @@ impl State0 {
             S_b_monero: self.S_b_monero,
             S_b_bitcoin: self.S_b_bitcoin,
             dleq_proof_s_b: self.dleq_proof_s_b.clone(),
-            v_b: self.v_b,
+            v_b: *self.v_b,
             refund_address: self.refund_address.clone(),
             tx_refund_fee: self.tx_refund_fee,
             tx_cancel_fee: self.tx_cancel_fee,
@@ -158,20 +291,21 @@ impl State0 {
         C: EstimateFeeRate,
         D: BatchDatabase,
     {
-        let valid = CROSS_CURVE_PROOF_SYSTEM.verify(
+        verify_dleq_proof(
             &msg.dleq_proof_s_a,
-            (
-                msg.S_a_bitcoin.into(),
-                msg.S_a_monero
-                    .point
-                    .decompress()
-                    .ok_or_else(|| anyhow!("S_a is not a monero curve point"))?,
-            ),
-        );
+            msg.S_a_bitcoin,
+            msg.S_a_monero,
+            MessageKind::Message1,
+        )?;
 
-        if !valid {
-            bail!("Alice's dleq proof doesn't verify")
-        }
+        check_fees(
+            self.btc,
+            msg.tx_redeem_fee,
+            self.tx_cancel_fee,
+            self.tx_refund_fee,
+            msg.tx_punish_fee,
+        )
+        .context("Alice's fees are invalid")?;
 
         let tx_lock = bitcoin::TxLock::new(
             wallet,
@@ -181,7 +315,7 @@ impl State0 {
             self.refund_address.clone(),
         )
         .await?;
-        let v = msg.v_a + self.v_b;
+        let v = Secret::new(msg.v_a + *self.v_b);
 
         Ok(State1 {
             A: msg.A,
@@ -209,11 +343,11 @@ impl State0 {
 #[derive(Debug)]
 pub struct State1 {
     A: bitcoin::PublicKey,
-    b: bitcoin::SecretKey,
-    s_b: monero::Scalar,
+    b: Secret<bitcoin::SecretKey>,
+    s_b: Secret<monero::Scalar>,
     S_a_monero: monero::PublicKey,
     S_a_bitcoin: bitcoin::PublicKey,
-    v: monero::PrivateViewKey,
+    v: Secret<monero::PrivateViewKey>,
     xmr: monero::Amount,
     cancel_timelock: CancelTimelock,
     punish_timelock: PunishTimelock,
@@ -236,6 +370,13 @@ impl State1 {
     }
 
     pub fn receive(self, msg: Message3) -> Result<State2> {
+        self.transcript_hash().verify(
+            msg.transcript,
+            &msg.transcript_sig,
+            &self.A,
+            MessageKind::Message3,
+        )?;
+
         let tx_cancel = TxCancel::new(
             &self.tx_lock,
             self.cancel_timelock,
@@ -246,13 +387,22 @@ impl State1 {
         let tx_refund =
             bitcoin::TxRefund::new(&tx_cancel, &self.refund_address, self.tx_refund_fee);
 
-        bitcoin::verify_sig(&self.A, &tx_cancel.digest(), &msg.tx_cancel_sig)?;
+        bitcoin::verify_sig(&self.A, &tx_cancel.digest(), &msg.tx_cancel_sig).context(
+            InvalidMessage::Signature {
+                message: MessageKind::Message3,
+                transaction: "cancel",
+            },
+        )?;
         bitcoin::verify_encsig(
             self.A,
             bitcoin::PublicKey::from(self.s_b.to_secpfun_scalar()),
             &tx_refund.digest(),
             &msg.tx_refund_encsig,
-        )?;
+        )
+        .context(InvalidMessage::EncryptedSignature {
+            message: MessageKind::Message3,
+            transaction: "refund",
+        })?;
 
         Ok(State2 {
             A: self.A,
@@ -269,7 +419,7 @@ impl State1 {
             punish_address: self.punish_address,
             tx_lock: self.tx_lock,
             tx_cancel_sig_a: msg.tx_cancel_sig,
-            tx_refund_encsig: msg.tx_refund_encsig,
+            tx_refund_encsig: Secret::new(msg.tx_refund_encsig),
             min_monero_confirmations: self.min_monero_confirmations,
             tx_redeem_fee: self.tx_redeem_fee,
             tx_refund_fee: self.tx_refund_fee,
@@ -277,16 +427,43 @@ impl State1 {
             tx_cancel_fee: self.tx_cancel_fee,
         })
     }
+
+    fn transcript_hash(&self) -> TranscriptHash {
+        transcript::Parameters {
+            btc: self.tx_lock.lock_amount(),
+            xmr: self.xmr,
+            cancel_timelock: self.cancel_timelock,
+            punish_timelock: self.punish_timelock,
+            A: self.A,
+            B: self.b.public(),
+            S_a_bitcoin: self.S_a_bitcoin,
+            S_b_bitcoin: bitcoin::PublicKey::from(self.s_b.to_secpfun_scalar()),
+            S_a_monero: self.S_a_monero,
+            S_b_monero: monero::PublicKey::from_private_key(&monero::PrivateKey {
+                scalar: *self.s_b,
+            }),
+            v: self.v.public(),
+            refund_address: &self.refund_address,
+            redeem_address: &self.redeem_address,
+            punish_address: &self.punish_address,
+            tx_lock: self.tx_lock.txid(),
+            tx_redeem_fee: self.tx_redeem_fee,
+            tx_refund_fee: self.tx_refund_fee,
+            tx_punish_fee: self.tx_punish_fee,
+            tx_cancel_fee: self.tx_cancel_fee,
+        }
+        .hash()
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
 pub struct State2 {
     A: bitcoin::PublicKey,
-    b: bitcoin::SecretKey,
-    s_b: monero::Scalar,
+    b: Secret<bitcoin::SecretKey>,
+    s_b: Secret<monero::Scalar>,
     S_a_monero: monero::PublicKey,
     S_a_bitcoin: bitcoin::PublicKey,
-    v: monero::PrivateViewKey,
+    v: Secret<monero::PrivateViewKey>,
     xmr: monero::Amount,
     cancel_timelock: CancelTimelock,
     punish_timelock: PunishTimelock,
@@ -295,15 +472,11 @@ pub struct State2 {
     punish_address: bitcoin::Address,
     tx_lock: bitcoin::TxLock,
     tx_cancel_sig_a: Signature,
-    tx_refund_encsig: bitcoin::EncryptedSignature,
+    tx_refund_encsig: Secret<bitcoin::EncryptedSignature>,
     min_monero_confirmations: u64,
-    #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
     tx_redeem_fee: bitcoin::Amount,
-    #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
     tx_punish_fee: bitcoin::Amount,
-    #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
     tx_refund_fee: bitcoin::Amount,
-    #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
     tx_cancel_fee: bitcoin::Amount,
 }
 
@@ -324,14 +497,59 @@ impl State2 {
             self.tx_punish_fee,
         );
         let tx_punish_sig = self.b.sign(tx_punish.digest());
+        let transcript = self.transcript_hash();
 
         Message4 {
             tx_punish_sig,
             tx_cancel_sig,
+            transcript,
+            transcript_sig: transcript.sign(&self.b),
+        }
+    }
+
+    fn transcript_hash(&self) -> TranscriptHash {
+        transcript::Parameters {
+            btc: self.tx_lock.lock_amount(),
+            xmr: self.xmr,
+            cancel_timelock: self.cancel_timelock,
+            punish_timelock: self.punish_timelock,
+            A: self.A,
+            B: self.b.public(),
+            S_a_bitcoin: self.S_a_bitcoin,
+            S_b_bitcoin: bitcoin::PublicKey::from(self.s_b.to_secpfun_scalar()),
+            S_a_monero: self.S_a_monero,
+            S_b_monero: monero::PublicKey::from_private_key(&monero::PrivateKey {
+                scalar: *self.s_b,
+            }),
+            v: self.v.public(),
+            refund_address: &self.refund_address,
+            redeem_address: &self.redeem_address,
+            punish_address: &self.punish_address,
+            tx_lock: self.tx_lock.txid(),
+            tx_redeem_fee: self.tx_redeem_fee,
+            tx_refund_fee: self.tx_refund_fee,
+            tx_punish_fee: self.tx_punish_fee,
+            tx_cancel_fee: self.tx_cancel_fee,
+        }
+        .hash()
+    }
+
+    /// What [`lock_btc`](Self::lock_btc) locks and what it costs.
+    pub fn lock_details(&self, swap_id: Uuid) -> LockDetails {
+        LockDetails {
+            swap_id,
+            btc: self.tx_lock.lock_amount(),
+            btc_lock_fee: self.tx_lock.fee(),
+            btc_cancel_fee: self.tx_cancel_fee,
+            btc_refund_fee: self.tx_refund_fee,
+            xmr: self.xmr,
+            cancel_timelock: self.cancel_timelock,
         }
     }
 
     pub async fn lock_btc(self) -> Result<(State3, TxLock)> {
+        let transcript = self.transcript_hash();
+
         Ok((
             State3 {
                 A: self.A,
@@ -352,6 +570,7 @@ impl State2 {
                 tx_redeem_fee: self.tx_redeem_fee,
                 tx_refund_fee: self.tx_refund_fee,
                 tx_cancel_fee: self.tx_cancel_fee,
+                transcript: Some(transcript),
             },
             self.tx_lock,
         ))
@@ -361,11 +580,11 @@ impl State2 {
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct State3 {
     A: bitcoin::PublicKey,
-    b: bitcoin::SecretKey,
-    s_b: monero::Scalar,
+    b: Secret<bitcoin::SecretKey>,
+    s_b: Secret<monero::Scalar>,
     S_a_monero: monero::PublicKey,
     S_a_bitcoin: bitcoin::PublicKey,
-    v: monero::PrivateViewKey,
+    v: Secret<monero::PrivateViewKey>,
     xmr: monero::Amount,
     pub cancel_timelock: CancelTimelock,
     punish_timelock: PunishTimelock,
@@ -373,25 +592,26 @@ pub struct State3 {
     redeem_address: bitcoin::Address,
     pub tx_lock: bitcoin::TxLock,
     tx_cancel_sig_a: Signature,
-    tx_refund_encsig: bitcoin::EncryptedSignature,
+    tx_refund_encsig: Secret<bitcoin::EncryptedSignature>,
     min_monero_confirmations: u64,
-    #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
     tx_redeem_fee: bitcoin::Amount,
-    #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
     tx_refund_fee: bitcoin::Amount,
-    #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
     tx_cancel_fee: bitcoin::Amount,
+    /// Sent along with the encrypted signature, not known for swaps set up
+    /// with earlier versions.
+    #[serde(default)]
+    transcript: Option<TranscriptHash>,
 }
 
 impl State3 {
     pub fn lock_xmr_watch_request(&self, transfer_proof: TransferProof) -> WatchRequest {
         let S_b_monero =
-            monero::PublicKey::from_private_key(&monero::PrivateKey::from_scalar(self.s_b));
+            monero::PublicKey::from_private_key(&monero::PrivateKey::from_scalar(*self.s_b));
         let S = self.S_a_monero + S_b_monero;
 
         WatchRequest {
             public_spend_key: S,
-            public_view_key: self.v.public(),
+            view_key: *self.v,
             transfer_proof,
             conf_target: self.min_monero_confirmations,
             expected: self.xmr,
@@ -404,6 +624,7 @@ impl State3 {
             b: self.b,
             s_b: self.s_b,
             S_a_bitcoin: self.S_a_bitcoin,
+            S_a_monero: Some(self.S_a_monero),
             v: self.v,
             cancel_timelock: self.cancel_timelock,
             punish_timelock: self.punish_timelock,
@@ -416,20 +637,24 @@ impl State3 {
             tx_redeem_fee: self.tx_redeem_fee,
             tx_refund_fee: self.tx_refund_fee,
             tx_cancel_fee: self.tx_cancel_fee,
+            transcript: self.transcript,
         }
     }
 
-    pub fn cancel(&self) -> State6 {
+    pub fn cancel(&self, monero_wallet_restore_blockheight: BlockHeight) -> State6 {
         State6 {
             A: self.A,
             b: self.b.clone(),
-            s_b: self.s_b,
+            s_b: self.s_b.clone(),
+            S_a_monero: Some(self.S_a_monero),
+            v: Some(self.v.clone()),
             cancel_timelock: self.cancel_timelock,
             punish_timelock: self.punish_timelock,
             refund_address: self.refund_address.clone(),
             tx_lock: self.tx_lock.clone(),
             tx_cancel_sig_a: self.tx_cancel_sig_a.clone(),
             tx_refund_encsig: self.tx_refund_encsig.clone(),
+            monero_wallet_restore_blockheight: Some(monero_wallet_restore_blockheight),
             tx_refund_fee: self.tx_refund_fee,
             tx_cancel_fee: self.tx_cancel_fee,
         }
@@ -439,17 +664,46 @@ impl State3 {
         self.tx_lock.txid()
     }
 
+    pub fn xmr(&self) -> monero::Amount {
+        self.xmr
+    }
+
+    /// The fully signed cancel and refund transactions. The cancel transaction
+    /// is valid once the cancel timelock expired, the refund transaction as
+    /// soon as the cancel transaction is confirmed.
+    pub fn presigned_transactions(&self) -> Result<Vec<PresignedTransaction>> {
+        let tx_cancel = self.tx_cancel();
+        let tx_refund =
+            bitcoin::TxRefund::new(&tx_cancel, &self.refund_address, self.tx_refund_fee);
+
+        let signed_tx_cancel = tx_cancel
+            .complete_as_bob(self.A, (*self.b).clone(), self.tx_cancel_sig_a.clone())
+            .context("Failed to complete Bitcoin cancel transaction")?;
+
+        let adaptor = Adaptor::<HashTranscript<Sha256>, Deterministic<Sha256>>::default();
+        let sig_b = self.b.sign(tx_refund.digest());
+        let sig_a = adaptor.decrypt_signature(
+            &self.s_b.to_secpfun_scalar(),
+            (*self.tx_refund_encsig).clone(),
+        );
+        let signed_tx_refund =
+            tx_refund.add_signatures((self.A, sig_a), (self.b.public(), sig_b))?;
+
+        Ok(vec![
+            PresignedTransaction::new(
+                presigned::Kind::Cancel,
+                self.cancel_timelock,
+                signed_tx_cancel,
+            ),
+            PresignedTransaction::new(presigned::Kind::Refund, 0u32, signed_tx_refund),
+        ])
+    }
+
     pub async fn current_epoch(
         &self,
         bitcoin_wallet: &bitcoin::Wallet,
     ) -> Result<ExpiredTimelocks> {
-        let tx_cancel = TxCancel::new(
-            &self.tx_lock,
-            self.cancel_timelock,
-            self.A,
-            self.b.public(),
-            self.tx_cancel_fee,
-        );
+        let tx_cancel = self.tx_cancel();
 
         let tx_lock_status = bitcoin_wallet.status_of_script(&self.tx_lock).await?;
         let tx_cancel_status = bitcoin_wallet.status_of_script(&tx_cancel).await?;
@@ -461,60 +715,93 @@ impl State3 {
             tx_cancel_status,
         ))
     }
+
+    fn tx_cancel(&self) -> TxCancel {
+        TxCancel::new(
+            &self.tx_lock,
+            self.cancel_timelock,
+            self.A,
+            self.b.public(),
+            self.tx_cancel_fee,
+        )
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
 pub struct State4 {
     A: bitcoin::PublicKey,
-    b: bitcoin::SecretKey,
-    s_b: monero::Scalar,
+    b: Secret<bitcoin::SecretKey>,
+    s_b: Secret<monero::Scalar>,
     S_a_bitcoin: bitcoin::PublicKey,
-    v: monero::PrivateViewKey,
+    /// Checks the key share Alice hands out after punishing us, not known for
+    /// swaps that locked the Monero with earlier versions.
+    #[serde(default)]
+    S_a_monero: Option<monero::PublicKey>,
+    v: Secret<monero::PrivateViewKey>,
     pub cancel_timelock: CancelTimelock,
     punish_timelock: PunishTimelock,
     refund_address: bitcoin::Address,
     redeem_address: bitcoin::Address,
     pub tx_lock: bitcoin::TxLock,
     tx_cancel_sig_a: Signature,
-    tx_refund_encsig: bitcoin::EncryptedSignature,
+    tx_refund_encsig: Secret<bitcoin::EncryptedSignature>,
     monero_wallet_restore_blockheight: BlockHeight,
-    #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
     tx_redeem_fee: bitcoin::Amount,
-    #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
     tx_refund_fee: bitcoin::Amount,
-    #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
     tx_cancel_fee: bitcoin::Amount,
+    /// Sent along with the encrypted signature, not known for swaps set up
+    /// with earlier versions.
+    #[serde(default)]
+    transcript: Option<TranscriptHash>,
 }
 
 impl State4 {
+    pub fn tx_redeem_id(&self) -> Txid {
+        bitcoin::TxRedeem::new(&self.tx_lock, &self.redeem_address, self.tx_redeem_fee).txid()
+    }
+
     pub fn tx_redeem_encsig(&self) -> bitcoin::EncryptedSignature {
         let tx_redeem =
             bitcoin::TxRedeem::new(&self.tx_lock, &self.redeem_address, self.tx_redeem_fee);
         self.b.encsign(self.S_a_bitcoin, tx_redeem.digest())
     }
 
+    pub fn transcript(&self) -> Option<TranscriptHash> {
+        self.transcript
+    }
+
     pub async fn watch_for_redeem_btc(&self, bitcoin_wallet: &bitcoin::Wallet) -> Result<State5> {
         let tx_redeem =
             bitcoin::TxRedeem::new(&self.tx_lock, &self.redeem_address, self.tx_redeem_fee);
-        let tx_redeem_encsig = self.b.encsign(self.S_a_bitcoin, tx_redeem.digest());
 
-        bitcoin_wallet
+        let status = bitcoin_wallet
             .subscribe_to(tx_redeem.clone())
             .await
             .wait_until_seen()
             .await?;
+        tracing::info!(txid = %tx_redeem.txid(), %status, "Found Bitcoin redeem transaction");
 
         let tx_redeem_candidate = bitcoin_wallet.get_raw_transaction(tx_redeem.txid()).await?;
 
+        self.redeem_btc_seen(tx_redeem_candidate)
+    }
+
+    /// Learns Alice's share of the Monero spend key from the redeem
+    /// transaction she published.
+    pub fn redeem_btc_seen(&self, tx_redeem_candidate: Transaction) -> Result<State5> {
+        let tx_redeem =
+            bitcoin::TxRedeem::new(&self.tx_lock, &self.redeem_address, self.tx_redeem_fee);
+        let tx_redeem_encsig = self.b.encsign(self.S_a_bitcoin, tx_redeem.digest());
+
         let tx_redeem_sig =
             tx_redeem.extract_signature_by_key(tx_redeem_candidate, self.b.public())?;
         let s_a = bitcoin::recover(self.S_a_bitcoin, tx_redeem_sig, tx_redeem_encsig)?;
         let s_a = monero::private_key_from_secp256k1_scalar(s_a.into());
 
         Ok(State5 {
-            s_a,
-            s_b: self.s_b,
-            v: self.v,
+            s_a: Secret::new(s_a),
+            s_b: self.s_b.clone(),
+            v: self.v.clone(),
             tx_lock: self.tx_lock.clone(),
             monero_wallet_restore_blockheight: self.monero_wallet_restore_blockheight,
         })
@@ -548,12 +835,15 @@ impl State4 {
             A: self.A,
             b: self.b,
             s_b: self.s_b,
+            S_a_monero: self.S_a_monero,
+            v: Some(self.v),
             cancel_timelock: self.cancel_timelock,
             punish_timelock: self.punish_timelock,
             refund_address: self.refund_address,
             tx_lock: self.tx_lock,
             tx_cancel_sig_a: self.tx_cancel_sig_a,
             tx_refund_encsig: self.tx_refund_encsig,
+            monero_wallet_restore_blockheight: Some(self.monero_wallet_restore_blockheight),
             tx_refund_fee: self.tx_refund_fee,
             tx_cancel_fee: self.tx_cancel_fee,
         }
@@ -562,20 +852,20 @@ impl State4 {
 
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
 pub struct State5 {
-    #[serde(with = "monero_private_key")]
-    s_a: monero::PrivateKey,
-    s_b: monero::Scalar,
-    v: monero::PrivateViewKey,
+    #[serde(with = "monero_private_key::secret")]
+    s_a: Secret<monero::PrivateKey>,
+    s_b: Secret<monero::Scalar>,
+    v: Secret<monero::PrivateViewKey>,
     tx_lock: bitcoin::TxLock,
     pub monero_wallet_restore_blockheight: BlockHeight,
 }
 
 impl State5 {
     pub fn xmr_keys(&self) -> (monero::PrivateKey, monero::PrivateViewKey) {
-        let s_b = monero::PrivateKey { scalar: self.s_b };
-        let s = self.s_a + s_b;
+        let s_b = monero::PrivateKey { scalar: *self.s_b };
+        let s = *self.s_a + s_b;
 
-        (s, self.v)
+        (s, *self.v)
     }
 
     pub fn tx_lock_id(&self) -> bitcoin::Txid {
@@ -586,17 +876,26 @@ impl State5 {
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
 pub struct State6 {
     A: bitcoin::PublicKey,
-    b: bitcoin::SecretKey,
-    s_b: monero::Scalar,
+    b: Secret<bitcoin::SecretKey>,
+    s_b: Secret<monero::Scalar>,
+    /// Together with the view key and the restore height only needed to
+    /// redeem the Monero cooperatively, not known for swaps cancelled by
+    /// earlier versions.
+    #[serde(default)]
+    S_a_monero: Option<monero::PublicKey>,
+    /// Together with the restore height only needed to redeem the Monero
+    /// cooperatively, not known for swaps cancelled by earlier versions.
+    #[serde(default)]
+    v: Option<Secret<monero::PrivateViewKey>>,
     cancel_timelock: CancelTimelock,
     punish_timelock: PunishTimelock,
     refund_address: bitcoin::Address,
     tx_lock: bitcoin::TxLock,
     tx_cancel_sig_a: Signature,
-    tx_refund_encsig: bitcoin::EncryptedSignature,
-    #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
+    tx_refund_encsig: Secret<bitcoin::EncryptedSignature>,
+    #[serde(default)]
+    monero_wallet_restore_blockheight: Option<BlockHeight>,
     pub tx_refund_fee: bitcoin::Amount,
-    #[serde(with = "::bitcoin::util::amount::serde::as_sat")]
     pub tx_cancel_fee: bitcoin::Amount,
 }
 
@@ -649,7 +948,7 @@ impl State6 {
             self.b.public(),
             self.tx_cancel_fee,
         )
-        .complete_as_bob(self.A, self.b.clone(), self.tx_cancel_sig_a.clone())
+        .complete_as_bob(self.A, (*self.b).clone(), self.tx_cancel_sig_a.clone())
         .context("Failed to complete Bitcoin cancel transaction")?;
 
         let (tx_id, _) = bitcoin_wallet.broadcast(transaction, "cancel").await?;
@@ -679,8 +978,10 @@ impl State6 {
         let adaptor = Adaptor::<HashTranscript<Sha256>, Deterministic<Sha256>>::default();
 
         let sig_b = self.b.sign(tx_refund.digest());
-        let sig_a =
-            adaptor.decrypt_signature(&self.s_b.to_secpfun_scalar(), self.tx_refund_encsig.clone());
+        let sig_a = adaptor.decrypt_signature(
+            &self.s_b.to_secpfun_scalar(),
+            (*self.tx_refund_encsig).clone(),
+        );
 
         let signed_tx_refund =
             tx_refund.add_signatures((self.A, sig_a), (self.b.public(), sig_b))?;
@@ -690,4 +991,50 @@ impl State6 {
     pub fn tx_lock_id(&self) -> bitcoin::Txid {
         self.tx_lock.txid()
     }
+
+    /// Combines our key share with the one Alice handed out after punishing
+    /// us, so the Monero can be redeemed like on the happy path.
+    ///
+    /// Fails if `s_a` is not the key share Alice committed to during the swap
+    /// setup.
+    pub fn cooperative_redeem(&self, s_a: monero::PrivateKey) -> Result<State5> {
+        let (S_a_monero, v, monero_wallet_restore_blockheight) = match (
+            self.S_a_monero,
+            self.v.clone(),
+            self.monero_wallet_restore_blockheight,
+        ) {
+            (Some(S_a_monero), Some(v), Some(restore_height)) => (S_a_monero, v, restore_height),
+            _ => bail!("The swap was cancelled by a version that did not keep the Monero keys"),
+        };
+
+        if monero::PublicKey::from_private_key(&s_a) != S_a_monero {
+            bail!("The key share does not match the one Alice committed to")
+        }
+
+        Ok(State5 {
+            s_a: Secret::new(s_a),
+            s_b: self.s_b.clone(),
+            v,
+            tx_lock: self.tx_lock.clone(),
+            monero_wallet_restore_blockheight,
+        })
+    }
+
+    pub fn tx_cancel_id(&self) -> Txid {
+        self.tx_cancel().txid()
+    }
+
+    pub fn tx_refund_id(&self) -> Txid {
+        bitcoin::TxRefund::new(&self.tx_cancel(), &self.refund_address, self.tx_refund_fee).txid()
+    }
+
+    fn tx_cancel(&self) -> TxCancel {
+        TxCancel::new(
+            &self.tx_lock,
+            self.cancel_timelock,
+            self.A,
+            self.b.public(),
+            self.tx_cancel_fee,
+        )
+    }
 }
@@ -1,13 +1,41 @@
 use crate::bitcoin::{ExpiredTimelocks, TxCancel, TxRefund};
 use crate::cli::EventLoopHandle;
+use crate::network::cooperative_xmr_redeem_after_punish::{RejectReason, Response};
+use crate::network::receipt;
+use crate::network::swap_setup;
 use crate::network::swap_setup::bob::NewSwap;
+use crate::network::watchtower;
 use crate::protocol::bob;
 use crate::protocol::bob::state::*;
+use crate::protocol::bob::LockApproval;
+use crate::protocol::deadline::Deadline;
+use crate::protocol::receipt::{Receipt, Signature, Terms};
+use crate::protocol::{record_error, Database};
 use crate::{bitcoin, monero};
 use anyhow::{bail, Context, Result};
+use libp2p::{identity, PeerId};
+use std::convert::TryInto;
+use std::sync::Arc;
+use std::time::Duration;
+use time::OffsetDateTime;
 use tokio::select;
 use uuid::Uuid;
 
+/// How often the swap setup is attempted before giving up, unless the seller
+/// rejects it. Nothing is locked before the setup completed, so it is safe to
+/// start over.
+const MAX_SETUP_ATTEMPTS: usize = 3;
+
+const SETUP_RETRY_DELAY: Duration = Duration::from_secs(10);
+
+/// Every attempt of the swap setup failed, e.g. because the connection to the
+/// seller was lost. The swap can be resumed to try again.
+#[derive(Debug, thiserror::Error)]
+#[error("Swap setup failed {} times: {}", .failures.len(), .failures.join("; "))]
+pub struct SetupFailed {
+    pub failures: Vec<String>,
+}
+
 pub fn is_complete(state: &BobState) -> bool {
     matches!(
         state,
@@ -23,38 +51,70 @@ pub async fn run(swap: bob::Swap) -> Result<BobState> {
     run_until(swap, is_complete).await
 }
 
+#[tracing::instrument(name = "swap", skip(swap, is_target_state), fields(id = %swap.id))]
 pub async fn run_until(
     mut swap: bob::Swap,
     is_target_state: fn(&BobState) -> bool,
 ) -> Result<BobState> {
     let mut current_state = swap.state;
+    swap.event_loop_handle.update_state(&current_state);
+    let deadline =
+        Deadline::load(swap.db.clone(), swap.id, swap.env_config.max_swap_duration).await?;
 
     while !is_target_state(&current_state) {
-        current_state = next_state(
+        let next = next_state(
             swap.id,
             current_state.clone(),
             &mut swap.event_loop_handle,
+            swap.db.as_ref(),
             swap.bitcoin_wallet.as_ref(),
             swap.monero_wallet.as_ref(),
             swap.monero_receive_address,
+            &swap.lock_approvals,
+            swap.watchtower.as_ref(),
+            swap.receipt_identity.as_ref(),
+            &deadline,
         )
-        .await?;
+        .await;
+        current_state = match next {
+            Ok(state) => state,
+            Err(error) => {
+                record_error(swap.db.as_ref(), swap.id, &error).await;
+                return Err(error);
+            }
+        };
+
+        if !matches!(
+            current_state,
+            BobState::Started { .. } | BobState::SwapSetupCompleted(_)
+        ) {
+            swap.reservation = None;
+        }
 
         swap.db
             .insert_latest_state(swap.id, current_state.clone().into())
             .await?;
+        swap.event_loop_handle.update_state(&current_state);
+
+        tracing::info!(state = %current_state, "Swap reached new state");
     }
 
     Ok(current_state)
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn next_state(
     swap_id: Uuid,
     state: BobState,
     event_loop_handle: &mut EventLoopHandle,
+    db: &(dyn Database + Send + Sync),
     bitcoin_wallet: &bitcoin::Wallet,
-    monero_wallet: &monero::Wallet,
+    monero_wallet: &dyn monero::MoneroWallet,
     monero_receive_address: monero::Address,
+    lock_approvals: &[Arc<dyn LockApproval>],
+    watchtower: Option<&watchtower::Client>,
+    receipt_identity: Option<&identity::Keypair>,
+    deadline: &Deadline,
 ) -> Result<BobState> {
     tracing::debug!(%state, "Advancing state");
 
@@ -62,6 +122,7 @@ async fn next_state(
         BobState::Started {
             btc_amount,
             change_address,
+            xmr_amount,
         } => {
             let tx_refund_fee = bitcoin_wallet
                 .estimate_fee(TxRefund::weight(), btc_amount)
@@ -70,21 +131,88 @@ async fn next_state(
                 .estimate_fee(TxCancel::weight(), btc_amount)
                 .await?;
 
-            let state2 = event_loop_handle
-                .setup_swap(NewSwap {
-                    swap_id,
-                    btc: btc_amount,
-                    tx_refund_fee,
-                    tx_cancel_fee,
-                    bitcoin_refund_address: change_address,
-                })
-                .await?;
+            let mut failures = Vec::new();
+            loop {
+                let attempt = failures.len() + 1;
+                let setup = async {
+                    // The quote the amount was determined with is likely outdated by now
+                    if attempt > 1 {
+                        let quote = event_loop_handle
+                            .request_quote()
+                            .await
+                            .context("Failed to request a fresh quote")?;
+                        swap_setup::bob::check_quote(&quote, btc_amount)?;
+                    }
+
+                    event_loop_handle
+                        .setup_swap(NewSwap {
+                            swap_id,
+                            btc: btc_amount,
+                            xmr: xmr_amount,
+                            tx_refund_fee,
+                            tx_cancel_fee,
+                            bitcoin_refund_address: change_address.clone(),
+                        })
+                        .await
+                };
+
+                let setup = select! {
+                    setup = setup => setup,
+                    result = deadline.passed("aborting the swap setup") => {
+                        result?;
+                        return Ok(BobState::SafelyAborted);
+                    }
+                };
+
+                match setup {
+                    Ok(state2) => {
+                        tracing::info!(%swap_id, "Starting new swap");
 
-            tracing::info!(%swap_id, "Starting new swap");
+                        break BobState::SwapSetupCompleted(state2);
+                    }
+                    Err(error) => match error.downcast_ref::<swap_setup::bob::Error>() {
+                        // Nothing was locked yet, the deposit stays in the wallet
+                        Some(rejection) if rejection.is_rejection() => {
+                            tracing::warn!(%swap_id, "Seller rejected the swap: {}", rejection);
+
+                            break BobState::SafelyAborted;
+                        }
+                        _ => {
+                            tracing::warn!(%swap_id, %attempt, "Swap setup failed: {:#}", error);
+                            failures.push(format!("{:#}", error));
 
-            BobState::SwapSetupCompleted(state2)
+                            if failures.len() >= MAX_SETUP_ATTEMPTS {
+                                return Err(SetupFailed { failures }.into());
+                            }
+
+                            tracing::info!(
+                                %swap_id,
+                                "Retrying the swap setup with a fresh quote in {}s",
+                                SETUP_RETRY_DELAY.as_secs()
+                            );
+                            tokio::time::sleep(SETUP_RETRY_DELAY).await;
+                        }
+                    },
+                }
+            }
         }
         BobState::SwapSetupCompleted(state2) => {
+            let lock_details = state2.lock_details(swap_id);
+            for lock_approval in lock_approvals {
+                if !lock_approval.approve(&lock_details).await? {
+                    tracing::info!(%swap_id, "Locking the Bitcoin was declined, aborting the swap");
+
+                    return Ok(BobState::SafelyAborted);
+                }
+            }
+
+            if deadline
+                .check("aborting before locking the Bitcoin")
+                .await?
+            {
+                return Ok(BobState::SafelyAborted);
+            }
+
             // Record the current monero wallet block height so we don't have to scan from
             // block 0 once we create the redeem wallet.
             // This has to be done **before** the Bitcoin is locked in order to ensure that
@@ -98,6 +226,19 @@ async fn next_state(
 
             // Alice and Bob have exchanged info
             let (state3, tx_lock) = state2.lock_btc().await?;
+
+            // Kept before the Bitcoin is locked, so the swap can be cancelled and refunded
+            // even if its state is lost
+            let presigned = state3.presigned_transactions()?;
+            for transaction in &presigned {
+                db.insert_presigned_transaction(swap_id, transaction)
+                    .await
+                    .context("Failed to save presigned transactions in database")?;
+            }
+            if let Some(watchtower) = watchtower {
+                watchtower.spawn_register(swap_id, watchtower::of_bob(presigned));
+            }
+
             let signed_tx = bitcoin_wallet
                 .sign_and_finalize(tx_lock.clone().into())
                 .await
@@ -125,27 +266,36 @@ async fn next_state(
                 tracing::info!("Waiting for Alice to lock Monero");
 
                 select! {
+                    biased; // make sure a passed deadline ignores a transfer proof
+
+                    result = deadline.passed("no longer waiting for Alice to lock the Monero, waiting for the cancel timelock") => {
+                        result?;
+                        tx_lock_status.wait_until_confirmed_with(state3.cancel_timelock).await?;
+
+                        let state4 = state3.cancel(monero_wallet_restore_blockheight);
+                        BobState::CancelTimelockExpired(state4)
+                    },
                     transfer_proof = transfer_proof_watcher => {
                         let transfer_proof = transfer_proof?;
 
                         tracing::info!(txid = %transfer_proof.tx_hash(), "Alice locked Monero");
 
-                        BobState::XmrLockProofReceived {
-                            state: state3,
-                            lock_transfer_proof: transfer_proof,
+                        BobState::BtcLocked {
+                            state3,
                             monero_wallet_restore_blockheight
                         }
+                        .receive_transfer_proof(transfer_proof)?
                     },
                     result = cancel_timelock_expires => {
                         let _ = result?;
                         tracing::info!("Alice took too long to lock Monero, cancelling the swap");
 
-                        let state4 = state3.cancel();
+                        let state4 = state3.cancel(monero_wallet_restore_blockheight);
                         BobState::CancelTimelockExpired(state4)
                     },
                 }
             } else {
-                let state4 = state3.cancel();
+                let state4 = state3.cancel(monero_wallet_restore_blockheight);
                 BobState::CancelTimelockExpired(state4)
             }
         }
@@ -160,37 +310,65 @@ async fn next_state(
                 let watch_request = state.lock_xmr_watch_request(lock_transfer_proof);
 
                 select! {
-                    received_xmr = monero_wallet.watch_for_transfer(watch_request) => {
+                    received_xmr = monero_wallet.watch_for_transfer(watch_request, monero_wallet_restore_blockheight) => {
                         match received_xmr {
                             Ok(()) => BobState::XmrLocked(state.xmr_locked(monero_wallet_restore_blockheight)),
-                            Err(monero::InsufficientFunds { expected, actual }) => {
-                                tracing::warn!(%expected, %actual, "Insufficient Monero have been locked!");
+                            Err(error) => {
+                                match error {
+                                    monero::InvalidTransfer::InsufficientFunds(monero::InsufficientFunds { expected, actual }) => {
+                                        tracing::warn!(%expected, %actual, "Insufficient Monero have been locked!");
+                                    }
+                                    monero::InvalidTransfer::UnlockTimeNotZero { unlock_time } => {
+                                        tracing::warn!(%unlock_time, "Monero has been locked with an unlock time, the funds would not be spendable right away!");
+                                    }
+                                }
                                 tracing::info!(timelock = %state.cancel_timelock, "Waiting for cancel timelock to expire");
 
                                 tx_lock_status.wait_until_confirmed_with(state.cancel_timelock).await?;
 
-                                BobState::CancelTimelockExpired(state.cancel())
+                                BobState::CancelTimelockExpired(state.cancel(monero_wallet_restore_blockheight))
                             },
                         }
                     }
                     result = tx_lock_status.wait_until_confirmed_with(state.cancel_timelock) => {
                         let _ = result?;
-                        BobState::CancelTimelockExpired(state.cancel())
+                        BobState::CancelTimelockExpired(state.cancel(monero_wallet_restore_blockheight))
                     }
                 }
             } else {
-                BobState::CancelTimelockExpired(state.cancel())
+                BobState::CancelTimelockExpired(state.cancel(monero_wallet_restore_blockheight))
             }
         }
         BobState::XmrLocked(state) => {
             let tx_lock_status = bitcoin_wallet.subscribe_to(state.tx_lock.clone()).await;
 
             if let ExpiredTimelocks::None = state.expired_timelock(bitcoin_wallet).await? {
+                // A reorg since the lock transaction became final can have taken back its
+                // confirmations, Alice must only learn the encrypted signature once it is
+                // final again
+                if !bitcoin_wallet.is_final(&state.tx_lock).await? {
+                    tracing::warn!(
+                        txid = %state.tx_lock.txid(),
+                        "Bitcoin lock transaction is no longer final, waiting for it again before sending the encrypted signature"
+                    );
+                    tx_lock_status.wait_until_final().await?;
+
+                    return Ok(BobState::XmrLocked(state));
+                }
+
                 // Alice has locked Xmr
                 // Bob sends Alice his key
 
                 select! {
-                    result = event_loop_handle.send_encrypted_signature(state.tx_redeem_encsig()) => {
+                    biased; // make sure a passed deadline withholds the encrypted signature
+
+                    result = deadline.passed("withholding the encrypted signature, waiting for the cancel timelock") => {
+                        result?;
+                        tx_lock_status.wait_until_confirmed_with(state.cancel_timelock).await?;
+
+                        BobState::CancelTimelockExpired(state.cancel())
+                    },
+                    result = event_loop_handle.send_encrypted_signature(state.tx_redeem_encsig(), state.transcript()) => {
                         match result {
                             Ok(_) => BobState::EncSigSent(state),
                             Err(bmrng::error::RequestError::RecvError | bmrng::error::RequestError::SendError(_)) => bail!("Failed to communicate encrypted signature through event loop channel"),
@@ -224,35 +402,16 @@ async fn next_state(
             }
         }
         BobState::BtcRedeemed(state) => {
-            let (spend_key, view_key) = state.xmr_keys();
-
-            let wallet_file_name = swap_id.to_string();
-            if let Err(e) = monero_wallet
-                .create_from_and_load(
-                    wallet_file_name.clone(),
-                    spend_key,
-                    view_key,
-                    state.monero_wallet_restore_blockheight,
-                )
-                .await
-            {
-                // In case we failed to refresh/sweep, when resuming the wallet might already
-                // exist! This is a very unlikely scenario, but if we don't take care of it we
-                // might not be able to ever transfer the Monero.
-                tracing::warn!("Failed to generate monero wallet from keys: {:#}", e);
-                tracing::info!(%wallet_file_name,
-                    "Falling back to trying to open the the wallet if it already exists",
-                );
-                monero_wallet.open(wallet_file_name).await?;
-            }
-
-            // Ensure that the generated wallet is synced so we have a proper balance
-            monero_wallet.refresh().await?;
-            // Sweep (transfer all funds) to the given address
-            let tx_hashes = monero_wallet.sweep_all(monero_receive_address).await?;
+            redeem_xmr(swap_id, &state, monero_wallet, monero_receive_address).await?;
 
-            for tx_hash in tx_hashes {
-                tracing::info!(%monero_receive_address, txid=%tx_hash.0, "Successfully transferred XMR to wallet");
+            if let Some(identity) = receipt_identity {
+                match request_receipt(swap_id, identity, event_loop_handle, db).await {
+                    Ok(()) => tracing::info!(
+                        "Alice countersigned the receipt of the swap, print it with `swap receipt --swap-id {}`",
+                        swap_id
+                    ),
+                    Err(error) => tracing::warn!("Failed to get a receipt of the swap: {:#}", error),
+                }
             }
 
             BobState::XmrRedeemed {
@@ -278,9 +437,55 @@ async fn next_state(
                     state.publish_refund_btc(bitcoin_wallet).await?;
                     BobState::BtcRefunded(state)
                 }
-                ExpiredTimelocks::Punish => BobState::BtcPunished {
-                    tx_lock_id: state.tx_lock_id(),
-                },
+                ExpiredTimelocks::Punish => {
+                    tracing::info!(
+                        "Punish timelock expired, asking Alice to let us redeem the Monero anyway"
+                    );
+
+                    // Only an explicit refusal ends the swap, Alice may still hand out her key
+                    // share when she can be asked again
+                    match event_loop_handle
+                        .request_cooperative_xmr_redeem()
+                        .await
+                        .context(
+                            "Failed to ask Alice for her key share, resume the swap to ask again",
+                        )? {
+                        Response::Fulfilled { s_a, .. } => {
+                            let state5 = state
+                                .cooperative_redeem(s_a)
+                                .context("Cannot redeem the Monero with the key share of Alice")?;
+                            tracing::info!("Alice handed out her key share, redeeming the Monero");
+                            redeem_xmr(swap_id, &state5, monero_wallet, monero_receive_address)
+                                .await?;
+
+                            BobState::XmrRedeemed {
+                                tx_lock_id: state.tx_lock_id(),
+                            }
+                        }
+                        Response::Rejected {
+                            reason: RejectReason::Declined(reason),
+                            ..
+                        } => {
+                            tracing::info!("Alice refused to let us redeem the Monero: {}", reason);
+
+                            BobState::BtcPunished {
+                                tx_lock_id: state.tx_lock_id(),
+                            }
+                        }
+                        Response::Rejected {
+                            reason: RejectReason::NotPunished,
+                            ..
+                        } => {
+                            bail!("Alice has not punished the swap yet, resume the swap later to ask for her key share again")
+                        }
+                        Response::Rejected { reason, .. } => {
+                            bail!(
+                                "{}, resume the swap later to ask for her key share again",
+                                reason
+                            )
+                        }
+                    }
+                }
             }
         }
         BobState::BtcRefunded(state4) => BobState::BtcRefunded(state4),
@@ -289,3 +494,76 @@ async fn next_state(
         BobState::XmrRedeemed { tx_lock_id } => BobState::XmrRedeemed { tx_lock_id },
     })
 }
+
+/// Signs the terms of the swap, asks Alice to countersign them and stores the
+/// receipt.
+async fn request_receipt(
+    swap_id: Uuid,
+    identity: &identity::Keypair,
+    event_loop_handle: &mut EventLoopHandle,
+    db: &(dyn Database + Send + Sync),
+) -> Result<()> {
+    let states = db
+        .get_states(swap_id)
+        .await?
+        .into_iter()
+        .map(TryInto::try_into)
+        .collect::<Result<Vec<BobState>, _>>()?;
+    let terms = Terms::of_bob(
+        swap_id,
+        db.get_peer_id(swap_id).await?,
+        PeerId::from(identity.public()),
+        &states,
+        OffsetDateTime::now_utc(),
+    )?;
+    let bob_signature = Signature::sign(&terms, identity)?;
+
+    let response = event_loop_handle
+        .request_receipt(receipt::Request {
+            terms: terms.clone(),
+            signature: bob_signature.clone(),
+        })
+        .await?;
+    let alice_signature = match response {
+        receipt::Response::Signed { signature, .. } => signature,
+        receipt::Response::Rejected { reason, .. } => {
+            bail!("Alice refused to sign the receipt: {}", reason)
+        }
+    };
+
+    let receipt = Receipt {
+        terms,
+        alice_signature,
+        bob_signature,
+    };
+    receipt.verify()?;
+    db.insert_receipt(swap_id, &receipt).await?;
+
+    Ok(())
+}
+
+/// Sweeps the Monero locked by Alice into our wallet.
+async fn redeem_xmr(
+    swap_id: Uuid,
+    state: &State5,
+    monero_wallet: &dyn monero::MoneroWallet,
+    monero_receive_address: monero::Address,
+) -> Result<()> {
+    let (spend_key, view_key) = state.xmr_keys();
+
+    let tx_hashes = monero_wallet
+        .sweep_swap_wallet(
+            swap_id,
+            spend_key,
+            view_key,
+            state.monero_wallet_restore_blockheight,
+            monero_receive_address,
+        )
+        .await?;
+
+    for tx_hash in tx_hashes {
+        tracing::info!(%monero_receive_address, txid=%tx_hash.0, "Successfully transferred XMR to wallet");
+    }
+
+    Ok(())
+}
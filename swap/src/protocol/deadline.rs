@@ -0,0 +1,127 @@
+//! Bounding how long a swap may run.
+//!
+//! With a maximum swap duration in the [`env::Config`](crate::env::Config) a
+//! swap that started longer ago than that stops waiting for the counterparty.
+//! A swap that did not lock anything yet is aborted, one that did waits for the
+//! cancel timelock and ends in the refund or punishment the timelocks allow.
+//! A swap starts when its first state is entered, so resuming it does not
+//! extend the deadline. Why the swap was wound down is recorded in the
+//! database the first time the deadline takes effect.
+
+use crate::protocol::Database;
+use anyhow::Result;
+use std::convert::TryFrom;
+use std::sync::Arc;
+use std::time::Duration;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+#[allow(missing_debug_implementations)]
+pub struct Deadline {
+    swap_id: Uuid,
+    db: Arc<dyn Database + Send + Sync>,
+    /// When the deadline passes, together with the maximum duration it was
+    /// derived from.
+    at: Option<(OffsetDateTime, Duration)>,
+}
+
+impl Deadline {
+    /// The deadline of the swap `swap_id`, a swap without any state in the
+    /// database starts now. `None` as `max_duration` never passes.
+    pub async fn load(
+        db: Arc<dyn Database + Send + Sync>,
+        swap_id: Uuid,
+        max_duration: Option<Duration>,
+    ) -> Result<Self> {
+        let at = match max_duration {
+            Some(max_duration) => {
+                let started_at = db
+                    .get_state_history(swap_id)
+                    .await?
+                    .first()
+                    .map_or_else(OffsetDateTime::now_utc, |(entered_at, _)| *entered_at);
+
+                Some((started_at + max_duration, max_duration))
+            }
+            None => None,
+        };
+
+        Ok(Self { swap_id, db, at })
+    }
+
+    /// Whether the deadline passed. If so, records that the swap was wound
+    /// down with `action`, a description of what is done instead.
+    pub async fn check(&self, action: &str) -> Result<bool> {
+        match self.at {
+            Some((at, max_duration)) if OffsetDateTime::now_utc() >= at => {
+                self.record(max_duration, action).await?;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Resolves once the deadline passed, after recording it like
+    /// [`Deadline::check`]. Never resolves without a maximum duration.
+    pub async fn passed(&self, action: &str) -> Result<()> {
+        let (at, max_duration) = match self.at {
+            Some(at) => at,
+            None => return std::future::pending().await,
+        };
+
+        // Negative if the deadline already passed
+        let left = Duration::try_from(at - OffsetDateTime::now_utc()).unwrap_or_default();
+        tokio::time::sleep(left).await;
+
+        self.record(max_duration, action).await
+    }
+
+    async fn record(&self, max_duration: Duration, action: &str) -> Result<()> {
+        let reason = format!(
+            "Exceeded the maximum swap duration of {} minutes, {}",
+            max_duration.as_secs() / 60,
+            action
+        );
+        tracing::warn!(swap_id = %self.swap_id, "{}", reason);
+
+        self.db.insert_wind_down(self.swap_id, &reason).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::open_db;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn passed_deadline_is_recorded_once() -> Result<()> {
+        let dir = tempdir()?;
+        let db = open_db(dir.path().join("sqlite")).await?;
+        let swap_id = Uuid::new_v4();
+
+        let deadline = Deadline::load(db.clone(), swap_id, Some(Duration::ZERO)).await?;
+        assert!(deadline.check("aborting the swap").await?);
+        deadline.passed("waiting for the cancel timelock").await?;
+
+        assert_eq!(
+            db.get_wind_down(swap_id).await?.as_deref(),
+            Some("Exceeded the maximum swap duration of 0 minutes, aborting the swap")
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn without_maximum_duration_deadline_never_passes() -> Result<()> {
+        let dir = tempdir()?;
+        let db = open_db(dir.path().join("sqlite")).await?;
+        let swap_id = Uuid::new_v4();
+
+        let deadline = Deadline::load(db.clone(), swap_id, None).await?;
+        assert!(!deadline.check("aborting the swap").await?);
+        assert!(db.get_wind_down(swap_id).await?.is_none());
+
+        Ok(())
+    }
+}
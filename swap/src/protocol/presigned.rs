@@ -0,0 +1,89 @@
+//! Fully signed Bitcoin transactions of a swap that only become valid once a
+//! timelock expires.
+//!
+//! They are stored as soon as the signatures of the counterparty are known,
+//! so the swap can be wound down by publishing them, without the state of the
+//! swap or its keys.
+
+use crate::bitcoin::{Transaction, Txid};
+use serde::{Deserialize, Serialize};
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+    strum::Display,
+    strum::EnumString,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum Kind {
+    Cancel,
+    Punish,
+    Refund,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PresignedTransaction {
+    pub kind: Kind,
+    /// How many blocks the spent transaction has to be confirmed for before
+    /// this one is valid.
+    pub timelock: u32,
+    #[serde(with = "transaction_hex")]
+    pub transaction: Transaction,
+}
+
+impl PresignedTransaction {
+    pub fn new(kind: Kind, timelock: impl Into<u32>, transaction: Transaction) -> Self {
+        Self {
+            kind,
+            timelock: timelock.into(),
+            transaction,
+        }
+    }
+
+    pub fn txid(&self) -> Txid {
+        self.transaction.txid()
+    }
+
+    /// The transaction whose output this one spends.
+    pub fn spends(&self) -> Option<Txid> {
+        self.transaction
+            .input
+            .first()
+            .map(|input| input.previous_output.txid)
+    }
+}
+
+/// Serializes a transaction as its consensus encoding in hex, as understood
+/// by `sendrawtransaction` of any Bitcoin node.
+mod transaction_hex {
+    use crate::bitcoin::Transaction;
+    use ::bitcoin::consensus::encode;
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S>(transaction: &Transaction, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&encode::serialize_hex(transaction))
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Transaction, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let hex = String::deserialize(deserializer)?;
+        let bytes = hex::decode(hex).map_err(D::Error::custom)?;
+
+        encode::deserialize(&bytes).map_err(D::Error::custom)
+    }
+}
@@ -0,0 +1,273 @@
+//! Receipts of completed swaps, signed by both Alice and Bob.
+//!
+//! Once Bob redeemed the Monero he signs the terms of the swap, i.e. the
+//! amounts, the price and the ids of the transactions, and asks Alice to
+//! countersign them. Alice only does so if the terms match her own record of
+//! the swap. The receipt is self-contained: the public keys of both peers are
+//! part of it, anyone can check it with [`Receipt::verify`] without knowing
+//! anything else about the swap.
+
+use crate::amounts::Price;
+use crate::protocol::alice::AliceState;
+use crate::protocol::bob::BobState;
+use crate::{bitcoin, monero};
+use anyhow::{bail, Context, Result};
+use libp2p::identity::{self, PublicKey};
+use libp2p::PeerId;
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DisplayFromStr};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+/// Prepended to the signed terms so the signature cannot be passed off as one
+/// over a different kind of message.
+const SIGNATURE_DOMAIN: &[u8] = b"/comit/xmr/btc/receipt/signature/1.0.0";
+
+/// What both parties agree the swap was.
+#[serde_as]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Terms {
+    pub swap_id: Uuid,
+    #[serde_as(as = "DisplayFromStr")]
+    pub alice_peer_id: PeerId,
+    #[serde_as(as = "DisplayFromStr")]
+    pub bob_peer_id: PeerId,
+    pub btc_amount: bitcoin::Amount,
+    /// In piconero.
+    pub xmr_amount: monero::Amount,
+    pub price: Price,
+    pub tx_lock_id: bitcoin::Txid,
+    pub tx_redeem_id: bitcoin::Txid,
+    pub xmr_lock_tx_hash: monero::TxHash,
+    /// The Unix timestamp at which Bob redeemed the Monero.
+    pub completed_at: i64,
+}
+
+impl Terms {
+    /// The terms of a swap in which Bob redeemed the Monero, taken from the
+    /// states it went through.
+    pub fn of_bob(
+        swap_id: Uuid,
+        alice_peer_id: PeerId,
+        bob_peer_id: PeerId,
+        states: &[BobState],
+        completed_at: OffsetDateTime,
+    ) -> Result<Self> {
+        if !states
+            .iter()
+            .any(|state| matches!(state, BobState::BtcRedeemed(_)))
+        {
+            bail!("Alice did not redeem the Bitcoin of the swap")
+        }
+
+        let state3 = states
+            .iter()
+            .find_map(|state| match state {
+                BobState::BtcLocked { state3, .. }
+                | BobState::XmrLockProofReceived { state: state3, .. } => Some(state3),
+                _ => None,
+            })
+            .context("The swap never locked the Bitcoin")?;
+        let transfer_proof = states
+            .iter()
+            .find_map(BobState::xmr_lock_proof)
+            .context("The swap never received the transfer proof of the Monero")?;
+        let state4 = states
+            .iter()
+            .find_map(|state| match state {
+                BobState::XmrLocked(state4) | BobState::EncSigSent(state4) => Some(state4),
+                _ => None,
+            })
+            .context("The swap never saw the Monero locked")?;
+
+        let btc_amount = state3.tx_lock.lock_amount();
+        let xmr_amount = state3.xmr();
+
+        Ok(Self {
+            swap_id,
+            alice_peer_id,
+            bob_peer_id,
+            btc_amount,
+            xmr_amount,
+            price: Price::of(btc_amount, xmr_amount)?,
+            tx_lock_id: state3.tx_lock_id(),
+            tx_redeem_id: state4.tx_redeem_id(),
+            xmr_lock_tx_hash: transfer_proof.tx_hash(),
+            completed_at: completed_at.unix_timestamp(),
+        })
+    }
+
+    /// The terms of a swap in which Alice published the redeem transaction,
+    /// taken from the states it went through. Alice cannot know when Bob
+    /// redeemed the Monero, `completed_at` is taken as is.
+    pub fn of_alice(
+        swap_id: Uuid,
+        alice_peer_id: PeerId,
+        bob_peer_id: PeerId,
+        states: &[AliceState],
+        completed_at: i64,
+    ) -> Result<Self> {
+        if !states
+            .iter()
+            .any(|state| matches!(state, AliceState::BtcRedeemTransactionPublished { .. }))
+        {
+            bail!("The redeem transaction of the swap was not published")
+        }
+
+        let state3 = states
+            .iter()
+            .find_map(AliceState::state3)
+            .context("The swap was never set up")?;
+        let transfer_proof = states
+            .iter()
+            .find_map(AliceState::transfer_proof)
+            .context("The swap never sent the transfer proof of the Monero")?;
+
+        Ok(Self {
+            swap_id,
+            alice_peer_id,
+            bob_peer_id,
+            btc_amount: state3.btc(),
+            xmr_amount: state3.xmr(),
+            price: Price::of(state3.btc(), state3.xmr())?,
+            tx_lock_id: state3.tx_lock.txid(),
+            tx_redeem_id: state3.tx_redeem().txid(),
+            xmr_lock_tx_hash: transfer_proof.tx_hash(),
+            completed_at,
+        })
+    }
+
+    fn signed_bytes(&self) -> Result<Vec<u8>> {
+        let mut bytes = SIGNATURE_DOMAIN.to_vec();
+        serde_json::to_writer(&mut bytes, self).context("Failed to serialize receipt")?;
+
+        Ok(bytes)
+    }
+}
+
+/// The signature of one of the peers over the terms.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Signature {
+    /// The public key in its protobuf encoding, the peer id is derived from
+    /// it.
+    #[serde(with = "hex::serde")]
+    pub public_key: Vec<u8>,
+    #[serde(with = "hex::serde")]
+    pub signature: Vec<u8>,
+}
+
+impl Signature {
+    pub fn sign(terms: &Terms, identity: &identity::Keypair) -> Result<Self> {
+        let signature = identity
+            .sign(&terms.signed_bytes()?)
+            .context("Failed to sign receipt")?;
+
+        Ok(Self {
+            public_key: identity.public().to_protobuf_encoding(),
+            signature,
+        })
+    }
+
+    /// Checks that `signer` signed the terms.
+    pub fn verify(&self, terms: &Terms, signer: PeerId) -> Result<()> {
+        let public_key = PublicKey::from_protobuf_encoding(&self.public_key)
+            .context("Failed to decode the public key the receipt was signed with")?;
+        let actual = PeerId::from(public_key.clone());
+        if actual != signer {
+            bail!("The receipt was signed by {} instead of {}", actual, signer);
+        }
+
+        if !public_key.verify(&terms.signed_bytes()?, &self.signature) {
+            bail!("The signature of {} is invalid", signer);
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Receipt {
+    pub terms: Terms,
+    pub alice_signature: Signature,
+    pub bob_signature: Signature,
+}
+
+impl Receipt {
+    /// Checks that both peers of the swap signed the terms.
+    pub fn verify(&self) -> Result<()> {
+        self.alice_signature
+            .verify(&self.terms, self.terms.alice_peer_id)
+            .context("Invalid signature of Alice")?;
+        self.bob_signature
+            .verify(&self.terms, self.terms.bob_peer_id)
+            .context("Invalid signature of Bob")?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitcoin::Txid;
+    use ::bitcoin::hashes::Hash;
+    use std::str::FromStr;
+
+    fn terms(alice: &identity::Keypair, bob: &identity::Keypair) -> Terms {
+        Terms {
+            swap_id: Uuid::new_v4(),
+            alice_peer_id: PeerId::from(alice.public()),
+            bob_peer_id: PeerId::from(bob.public()),
+            btc_amount: bitcoin::Amount::from_sat(1_000_000),
+            xmr_amount: monero::Amount::from_piconero(1_000_000_000_000),
+            price: Price::new(bitcoin::Amount::from_sat(1_000_000)),
+            tx_lock_id: Txid::from_inner([1; 32]),
+            tx_redeem_id: Txid::from_inner([2; 32]),
+            xmr_lock_tx_hash: monero::TxHash("abcd".to_owned()),
+            completed_at: 1_638_316_800,
+        }
+    }
+
+    #[test]
+    fn receipt_signed_by_both_peers_survives_roundtrip_and_verifies() {
+        let alice = identity::Keypair::generate_ed25519();
+        let bob = identity::Keypair::generate_ed25519();
+        let terms = terms(&alice, &bob);
+
+        let receipt = Receipt {
+            alice_signature: Signature::sign(&terms, &alice).unwrap(),
+            bob_signature: Signature::sign(&terms, &bob).unwrap(),
+            terms,
+        };
+        let json = serde_json::to_string_pretty(&receipt).unwrap();
+        let parsed = serde_json::from_str::<Receipt>(&json).unwrap();
+
+        assert_eq!(parsed, receipt);
+        assert!(parsed.verify().is_ok());
+    }
+
+    #[test]
+    fn altered_or_misattributed_receipts_are_rejected() {
+        let alice = identity::Keypair::generate_ed25519();
+        let bob = identity::Keypair::generate_ed25519();
+        let terms = terms(&alice, &bob);
+        let receipt = Receipt {
+            alice_signature: Signature::sign(&terms, &alice).unwrap(),
+            bob_signature: Signature::sign(&terms, &bob).unwrap(),
+            terms,
+        };
+
+        let mut altered = receipt.clone();
+        altered.terms.btc_amount = bitcoin::Amount::from_sat(2_000_000);
+        assert!(altered.verify().is_err());
+
+        let mut misattributed = receipt.clone();
+        misattributed.terms.alice_peer_id =
+            PeerId::from_str("12D3KooWCdMKjesXMJz1SiZ7HgotrxuqhQJbP5sgBm2BwP1cqThi").unwrap();
+        assert!(misattributed.verify().is_err());
+
+        let mut swapped = receipt;
+        std::mem::swap(&mut swapped.alice_signature, &mut swapped.bob_signature);
+        assert!(swapped.verify().is_err());
+    }
+}
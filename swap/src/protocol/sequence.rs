@@ -0,0 +1,270 @@
+//! The order in which Alice and Bob exchange the messages of a swap.
+//!
+//! A swap is a fixed sequence of messages, each sent by one of the two roles.
+//! [`Sequence`] tracks how far into it a swap got from the perspective of
+//! either role and rejects messages that arrive out of order, twice or from
+//! the wrong role, so a misbehaving peer is reported as such instead of
+//! surfacing as a failure to decode or to verify a message.
+
+use crate::network::swap_setup::{SpotPriceRequest, SpotPriceResponse};
+use crate::network::{encrypted_signature, transfer_proof};
+use crate::protocol::{Message0, Message1, Message2, Message3, Message4};
+use serde::{Deserialize, Serialize};
+
+/// The messages of a swap in the order they are exchanged.
+pub const MESSAGES: [MessageKind; 9] = [
+    MessageKind::SpotPriceRequest,
+    MessageKind::SpotPriceResponse,
+    MessageKind::Message0,
+    MessageKind::Message1,
+    MessageKind::Message2,
+    MessageKind::Message3,
+    MessageKind::Message4,
+    MessageKind::TransferProof,
+    MessageKind::EncryptedSignature,
+];
+
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, strum::Display, strum::EnumString,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Alice,
+    Bob,
+}
+
+impl Role {
+    pub fn counterpart(self) -> Self {
+        match self {
+            Role::Alice => Role::Bob,
+            Role::Bob => Role::Alice,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, strum::Display)]
+#[strum(serialize_all = "snake_case")]
+pub enum MessageKind {
+    SpotPriceRequest,
+    SpotPriceResponse,
+    Message0,
+    Message1,
+    Message2,
+    Message3,
+    Message4,
+    TransferProof,
+    EncryptedSignature,
+}
+
+impl MessageKind {
+    pub fn sender(self) -> Role {
+        match self {
+            MessageKind::SpotPriceRequest
+            | MessageKind::Message0
+            | MessageKind::Message2
+            | MessageKind::Message4
+            | MessageKind::EncryptedSignature => Role::Bob,
+            MessageKind::SpotPriceResponse
+            | MessageKind::Message1
+            | MessageKind::Message3
+            | MessageKind::TransferProof => Role::Alice,
+        }
+    }
+
+    fn position(self) -> usize {
+        MESSAGES
+            .iter()
+            .position(|kind| *kind == self)
+            .expect("every kind is part of the sequence")
+    }
+}
+
+/// A message exchanged between Alice and Bob.
+pub trait Message {
+    const KIND: MessageKind;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, thiserror::Error)]
+#[non_exhaustive]
+pub enum Error {
+    #[error("{message} is never sent by {sender}")]
+    UnexpectedSender { message: MessageKind, sender: Role },
+    #[error("Expected {expected} but got {message}")]
+    OutOfOrder {
+        message: MessageKind,
+        expected: MessageKind,
+    },
+    #[error("{message} was already exchanged")]
+    Duplicate { message: MessageKind },
+}
+
+/// How far into the swap Alice or Bob are.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sequence {
+    role: Role,
+    exchanged: usize,
+}
+
+impl Sequence {
+    pub fn new(role: Role) -> Self {
+        Self { role, exchanged: 0 }
+    }
+
+    /// A sequence in which all messages up to and including `last` were
+    /// exchanged, e.g. for a swap resumed from the database.
+    pub fn after(role: Role, last: MessageKind) -> Self {
+        Self {
+            role,
+            exchanged: last.position() + 1,
+        }
+    }
+
+    /// Whether `message` was exchanged already.
+    pub fn exchanged(&self, message: MessageKind) -> bool {
+        message.position() < self.exchanged
+    }
+
+    /// The next message to be exchanged, `None` once all were.
+    pub fn expected(&self) -> Option<MessageKind> {
+        MESSAGES.get(self.exchanged).copied()
+    }
+
+    pub fn send<M: Message>(&mut self) -> Result<(), Error> {
+        self.advance(M::KIND, self.role)
+    }
+
+    pub fn receive<M: Message>(&mut self) -> Result<(), Error> {
+        self.receive_kind(M::KIND)
+    }
+
+    pub fn receive_kind(&mut self, message: MessageKind) -> Result<(), Error> {
+        self.advance(message, self.role.counterpart())
+    }
+
+    fn advance(&mut self, message: MessageKind, sender: Role) -> Result<(), Error> {
+        if message.sender() != sender {
+            return Err(Error::UnexpectedSender { message, sender });
+        }
+
+        match self.expected() {
+            Some(expected) if expected == message => {
+                self.exchanged += 1;
+                Ok(())
+            }
+            Some(expected) if message.position() > self.exchanged => {
+                Err(Error::OutOfOrder { message, expected })
+            }
+            _ => Err(Error::Duplicate { message }),
+        }
+    }
+}
+
+impl Message for SpotPriceRequest {
+    const KIND: MessageKind = MessageKind::SpotPriceRequest;
+}
+
+impl Message for SpotPriceResponse {
+    const KIND: MessageKind = MessageKind::SpotPriceResponse;
+}
+
+impl Message for Message0 {
+    const KIND: MessageKind = MessageKind::Message0;
+}
+
+impl Message for Message1 {
+    const KIND: MessageKind = MessageKind::Message1;
+}
+
+impl Message for Message2 {
+    const KIND: MessageKind = MessageKind::Message2;
+}
+
+impl Message for Message3 {
+    const KIND: MessageKind = MessageKind::Message3;
+}
+
+impl Message for Message4 {
+    const KIND: MessageKind = MessageKind::Message4;
+}
+
+impl Message for transfer_proof::Request {
+    const KIND: MessageKind = MessageKind::TransferProof;
+}
+
+impl Message for encrypted_signature::Request {
+    const KIND: MessageKind = MessageKind::EncryptedSignature;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn both_roles_agree_on_a_complete_swap() {
+        let mut alice = Sequence::new(Role::Alice);
+        let mut bob = Sequence::new(Role::Bob);
+
+        for message in MESSAGES.iter().copied() {
+            let (sender, receiver) = match message.sender() {
+                Role::Alice => (&mut alice, &mut bob),
+                Role::Bob => (&mut bob, &mut alice),
+            };
+            sender.advance(message, sender.role).unwrap();
+            receiver.receive_kind(message).unwrap();
+        }
+
+        assert_eq!(alice.expected(), None);
+        assert_eq!(bob.expected(), None);
+    }
+
+    #[test]
+    fn rejects_messages_out_of_order() {
+        let mut alice = Sequence::after(Role::Alice, MessageKind::Message1);
+
+        let error = alice.receive::<Message4>().unwrap_err();
+
+        assert_eq!(error, Error::OutOfOrder {
+            message: MessageKind::Message4,
+            expected: MessageKind::Message2
+        });
+        assert_eq!(alice.expected(), Some(MessageKind::Message2));
+    }
+
+    #[test]
+    fn rejects_duplicate_messages() {
+        let mut alice = Sequence::after(Role::Alice, MessageKind::TransferProof);
+        alice.receive::<encrypted_signature::Request>().unwrap();
+
+        let error = alice.receive::<encrypted_signature::Request>().unwrap_err();
+
+        assert_eq!(error, Error::Duplicate {
+            message: MessageKind::EncryptedSignature
+        });
+        assert!(alice.exchanged(MessageKind::EncryptedSignature));
+    }
+
+    #[test]
+    fn every_message_replayed_is_a_duplicate() {
+        for message in MESSAGES.iter().copied() {
+            let receiver = message.sender().counterpart();
+            let mut sequence = Sequence::after(receiver, message);
+
+            let error = sequence.receive_kind(message).unwrap_err();
+
+            assert_eq!(error, Error::Duplicate { message });
+            assert_eq!(sequence, Sequence::after(receiver, message));
+        }
+    }
+
+    #[test]
+    fn rejects_messages_of_the_own_role() {
+        let mut bob = Sequence::after(Role::Bob, MessageKind::Message0);
+
+        let error = bob.receive::<Message2>().unwrap_err();
+
+        assert_eq!(error, Error::UnexpectedSender {
+            message: MessageKind::Message2,
+            sender: Role::Alice
+        });
+    }
+}
@@ -0,0 +1,317 @@
+//! A hash of everything Alice and Bob agreed on during the swap setup.
+//!
+//! The signatures exchanged during the setup only commit to the transactions
+//! they sign, a peer or a man in the middle substituting e.g. the Monero
+//! amount goes unnoticed until the Monero does not arrive. Both parties
+//! therefore send the hash of their view of the parameters, signed with their
+//! key of the lock output, alongside their signatures and the other one
+//! refuses to continue if it differs from its own. Bob sends it once more with
+//! the encrypted signature so Alice only redeems with the swap she set up.
+
+use crate::bitcoin::{CancelTimelock, PunishTimelock};
+use crate::protocol::sequence::MessageKind;
+use crate::protocol::InvalidMessage;
+use crate::{bitcoin, monero};
+use ::bitcoin::hashes::Hash;
+use ::bitcoin::SigHash;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+const DOMAIN: &[u8] = b"xmr-btc-swap/transcript/1";
+const SIGNATURE_DOMAIN: &[u8] = b"xmr-btc-swap/transcript-signature/1";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TranscriptHash([u8; 32]);
+
+impl TranscriptHash {
+    pub fn sign(self, key: &bitcoin::SecretKey) -> bitcoin::Signature {
+        key.sign(self.digest())
+    }
+
+    /// Compares the hash received in `message` to ours and checks that
+    /// `signer` signed it.
+    pub fn verify(
+        self,
+        theirs: TranscriptHash,
+        signature: &bitcoin::Signature,
+        signer: &bitcoin::PublicKey,
+        message: MessageKind,
+    ) -> Result<(), InvalidMessage> {
+        if theirs != self {
+            return Err(InvalidMessage::TranscriptMismatch { message });
+        }
+
+        bitcoin::verify_sig(signer, &self.digest(), signature)
+            .map_err(|_| InvalidMessage::TranscriptSignature { message })
+    }
+
+    /// Compares the hash repeated in `message` to the one agreed on during the
+    /// setup. Swaps set up before the hash was agreed on have none to compare
+    /// to.
+    pub fn verify_repeated(
+        ours: Option<TranscriptHash>,
+        theirs: Option<TranscriptHash>,
+        message: MessageKind,
+    ) -> Result<(), InvalidMessage> {
+        match ours {
+            Some(ours) if theirs != Some(ours) => {
+                Err(InvalidMessage::TranscriptMismatch { message })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn digest(self) -> SigHash {
+        let mut hasher = Sha256::new();
+        hasher.update(SIGNATURE_DOMAIN);
+        hasher.update(self.0);
+
+        SigHash::from_inner(hasher.finalize().into())
+    }
+}
+
+/// The parameters of a swap once Bob sent the lock transaction.
+#[derive(Clone, Copy, Debug)]
+pub struct Parameters<'a> {
+    pub btc: bitcoin::Amount,
+    pub xmr: monero::Amount,
+    pub cancel_timelock: CancelTimelock,
+    pub punish_timelock: PunishTimelock,
+    pub A: bitcoin::PublicKey,
+    pub B: bitcoin::PublicKey,
+    pub S_a_bitcoin: bitcoin::PublicKey,
+    pub S_b_bitcoin: bitcoin::PublicKey,
+    pub S_a_monero: monero::PublicKey,
+    pub S_b_monero: monero::PublicKey,
+    pub v: monero::PublicViewKey,
+    pub refund_address: &'a bitcoin::Address,
+    pub redeem_address: &'a bitcoin::Address,
+    pub punish_address: &'a bitcoin::Address,
+    pub tx_lock: bitcoin::Txid,
+    pub tx_redeem_fee: bitcoin::Amount,
+    pub tx_refund_fee: bitcoin::Amount,
+    pub tx_punish_fee: bitcoin::Amount,
+    pub tx_cancel_fee: bitcoin::Amount,
+}
+
+impl Parameters<'_> {
+    pub fn hash(&self) -> TranscriptHash {
+        let mut transcript = Transcript::new();
+
+        transcript.append("btc", &self.btc.as_sat().to_be_bytes());
+        transcript.append("xmr", &self.xmr.as_piconero().to_be_bytes());
+        transcript.append(
+            "cancel_timelock",
+            &u32::from(self.cancel_timelock).to_be_bytes(),
+        );
+        transcript.append(
+            "punish_timelock",
+            &u32::from(self.punish_timelock).to_be_bytes(),
+        );
+        transcript.append("A", &bitcoin_key(self.A));
+        transcript.append("B", &bitcoin_key(self.B));
+        transcript.append("S_a_bitcoin", &bitcoin_key(self.S_a_bitcoin));
+        transcript.append("S_b_bitcoin", &bitcoin_key(self.S_b_bitcoin));
+        transcript.append("S_a_monero", self.S_a_monero.as_bytes());
+        transcript.append("S_b_monero", self.S_b_monero.as_bytes());
+        transcript.append("v", monero::PublicKey::from(self.v).as_bytes());
+        transcript.append("refund_address", self.refund_address.to_string().as_bytes());
+        transcript.append("redeem_address", self.redeem_address.to_string().as_bytes());
+        transcript.append("punish_address", self.punish_address.to_string().as_bytes());
+        transcript.append("tx_lock", self.tx_lock.to_string().as_bytes());
+        transcript.append("tx_redeem_fee", &self.tx_redeem_fee.as_sat().to_be_bytes());
+        transcript.append("tx_refund_fee", &self.tx_refund_fee.as_sat().to_be_bytes());
+        transcript.append("tx_punish_fee", &self.tx_punish_fee.as_sat().to_be_bytes());
+        transcript.append("tx_cancel_fee", &self.tx_cancel_fee.as_sat().to_be_bytes());
+
+        transcript.finish()
+    }
+}
+
+fn bitcoin_key(key: bitcoin::PublicKey) -> Vec<u8> {
+    ::bitcoin::PublicKey::from(key).to_bytes()
+}
+
+/// Every value is framed by its label and both their lengths, so no two
+/// different sequences of values hash the same.
+struct Transcript(Sha256);
+
+impl Transcript {
+    fn new() -> Self {
+        let mut hasher = Sha256::new();
+        hasher.update(DOMAIN);
+
+        Self(hasher)
+    }
+
+    fn append(&mut self, label: &str, value: &[u8]) {
+        for part in &[label.as_bytes(), value] {
+            self.0.update(&(part.len() as u64).to_be_bytes());
+            self.0.update(part);
+        }
+    }
+
+    fn finish(self) -> TranscriptHash {
+        TranscriptHash(self.0.finalize().into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::env::{GetConfig, Regtest};
+    use rand::rngs::OsRng;
+
+    struct Addresses {
+        refund: bitcoin::Address,
+        redeem: bitcoin::Address,
+        punish: bitcoin::Address,
+    }
+
+    fn address() -> bitcoin::Address {
+        bitcoin::Address::p2wpkh(
+            &::bitcoin::PublicKey::from(bitcoin::PublicKey::random()),
+            ::bitcoin::Network::Regtest,
+        )
+        .unwrap()
+    }
+
+    fn addresses() -> Addresses {
+        Addresses {
+            refund: address(),
+            redeem: address(),
+            punish: address(),
+        }
+    }
+
+    fn monero_key() -> monero::PublicKey {
+        monero::PublicKey::from_private_key(&monero::PrivateKey {
+            scalar: monero::Scalar::random(&mut OsRng),
+        })
+    }
+
+    fn parameters(addresses: &Addresses) -> Parameters<'_> {
+        let config = Regtest::get_config();
+
+        Parameters {
+            btc: bitcoin::Amount::from_sat(500_000),
+            xmr: monero::Amount::from_piconero(10_000),
+            cancel_timelock: config.bitcoin_cancel_timelock,
+            punish_timelock: config.bitcoin_punish_timelock,
+            A: bitcoin::PublicKey::random(),
+            B: bitcoin::PublicKey::random(),
+            S_a_bitcoin: bitcoin::PublicKey::random(),
+            S_b_bitcoin: bitcoin::PublicKey::random(),
+            S_a_monero: monero_key(),
+            S_b_monero: monero_key(),
+            v: monero::PrivateViewKey::new_random(&mut OsRng).public(),
+            refund_address: &addresses.refund,
+            redeem_address: &addresses.redeem,
+            punish_address: &addresses.punish,
+            tx_lock: bitcoin::Txid::from_inner([1; 32]),
+            tx_redeem_fee: bitcoin::Amount::from_sat(1_000),
+            tx_refund_fee: bitcoin::Amount::from_sat(1_000),
+            tx_punish_fee: bitcoin::Amount::from_sat(1_000),
+            tx_cancel_fee: bitcoin::Amount::from_sat(1_000),
+        }
+    }
+
+    #[test]
+    fn same_parameters_hash_the_same() {
+        let addresses = addresses();
+        let parameters = parameters(&addresses);
+
+        assert_eq!(parameters.hash(), parameters.hash());
+    }
+
+    #[test]
+    fn substituting_any_parameter_changes_the_hash() {
+        let (ours, theirs) = (addresses(), addresses());
+        let original = parameters(&ours);
+        let other = parameters(&theirs);
+
+        let substitutions: Vec<fn(&mut Parameters<'_>, &Parameters<'_>)> = vec![
+            |p, _| p.btc += bitcoin::Amount::from_sat(1),
+            |p, _| p.xmr = monero::Amount::from_piconero(p.xmr.as_piconero() + 1),
+            |p, _| p.cancel_timelock = CancelTimelock::new(u32::from(p.cancel_timelock) + 1),
+            |p, _| p.punish_timelock = PunishTimelock::new(u32::from(p.punish_timelock) + 1),
+            |p, o| p.A = o.A,
+            |p, o| p.B = o.B,
+            |p, o| p.S_a_bitcoin = o.S_a_bitcoin,
+            |p, o| p.S_b_bitcoin = o.S_b_bitcoin,
+            |p, o| p.S_a_monero = o.S_a_monero,
+            |p, o| p.S_b_monero = o.S_b_monero,
+            |p, o| p.v = o.v,
+            |p, _| p.tx_lock = bitcoin::Txid::from_inner([2; 32]),
+            |p, _| p.tx_redeem_fee += bitcoin::Amount::from_sat(1),
+            |p, _| p.tx_refund_fee += bitcoin::Amount::from_sat(1),
+            |p, _| p.tx_punish_fee += bitcoin::Amount::from_sat(1),
+            |p, _| p.tx_cancel_fee += bitcoin::Amount::from_sat(1),
+        ];
+
+        for (i, substitute) in substitutions.iter().enumerate() {
+            let mut substituted = original;
+            substitute(&mut substituted, &other);
+
+            assert_ne!(substituted.hash(), original.hash(), "substitution {}", i);
+        }
+
+        let mut substituted = original;
+        substituted.refund_address = other.refund_address;
+        assert_ne!(substituted.hash(), original.hash());
+
+        let mut swapped = original;
+        swapped.redeem_address = original.punish_address;
+        swapped.punish_address = original.redeem_address;
+        assert_ne!(swapped.hash(), original.hash());
+    }
+
+    #[test]
+    fn values_are_framed_by_their_labels() {
+        let mut first = Transcript::new();
+        first.append("a", b"bc");
+        let mut second = Transcript::new();
+        second.append("ab", b"c");
+
+        assert_ne!(first.finish(), second.finish());
+    }
+
+    #[test]
+    fn hash_signed_by_another_key_is_rejected() {
+        let addresses = addresses();
+        let hash = parameters(&addresses).hash();
+        let signer = bitcoin::SecretKey::new_random(&mut OsRng);
+        let other = bitcoin::SecretKey::new_random(&mut OsRng);
+
+        assert!(hash
+            .verify(
+                hash,
+                &hash.sign(&signer),
+                &signer.public(),
+                MessageKind::Message3
+            )
+            .is_ok());
+        assert!(matches!(
+            hash.verify(
+                hash,
+                &hash.sign(&other),
+                &signer.public(),
+                MessageKind::Message3
+            ),
+            Err(InvalidMessage::TranscriptSignature { .. })
+        ));
+    }
+
+    #[test]
+    fn repeated_hash_must_match_unless_none_was_agreed_on() {
+        let (ours, theirs) = (addresses(), addresses());
+        let hash = parameters(&ours).hash();
+        let other = parameters(&theirs).hash();
+        let message = MessageKind::EncryptedSignature;
+
+        assert!(TranscriptHash::verify_repeated(Some(hash), Some(hash), message).is_ok());
+        assert!(TranscriptHash::verify_repeated(Some(hash), Some(other), message).is_err());
+        assert!(TranscriptHash::verify_repeated(Some(hash), None, message).is_err());
+        assert!(TranscriptHash::verify_repeated(None, None, message).is_ok());
+    }
+}
@@ -0,0 +1,166 @@
+//! Earmarking the funds of swaps that did not lock them yet.
+//!
+//! Swaps running next to each other draw on the same wallet, without a ledger
+//! each of them counts the whole balance as its own. A swap reserves what it
+//! is going to lock as soon as the amounts are agreed on and holds the
+//! [`Reservation`] until the funds are locked, the reservation is released
+//! when it is dropped, also if the swap fails or stops early. Quotes and new
+//! swaps only count what is not reserved.
+//!
+//! The ASB reserves the Monero of its swaps during the swap setup, the CLI the
+//! Bitcoin of the swaps of a [`SwapFactory`](crate::cli::SwapFactory).
+
+use crate::amounts::CheckedArithmetic;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+type Ledger<A> = HashMap<u64, A>;
+
+#[derive(Debug)]
+struct Inner<A> {
+    next_id: u64,
+    reserved: Ledger<A>,
+}
+
+/// A cheaply cloneable handle to the reservations on a wallet.
+#[derive(Debug)]
+pub struct Reservations<A> {
+    inner: Arc<Mutex<Inner<A>>>,
+}
+
+impl<A> Clone for Reservations<A> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<A> Default for Reservations<A> {
+    fn default() -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Inner {
+                next_id: 0,
+                reserved: HashMap::new(),
+            })),
+        }
+    }
+}
+
+impl<A> Reservations<A>
+where
+    A: CheckedArithmetic + PartialOrd + Debug,
+{
+    /// Reserves `amount` if what is left of `balance` after all other
+    /// reservations covers it.
+    pub fn reserve(&self, amount: A, balance: A) -> Option<Reservation<A>> {
+        let mut inner = self.lock();
+
+        match available(&inner.reserved, balance) {
+            Some(available) if amount <= available => Some(self.insert(&mut inner, amount)),
+            _ => None,
+        }
+    }
+
+    /// What is left of `balance` after all reservations, `None` if they
+    /// exceed it.
+    pub fn available(&self, balance: A) -> Option<A> {
+        available(&self.lock().reserved, balance)
+    }
+
+    fn insert(&self, inner: &mut Inner<A>, amount: A) -> Reservation<A> {
+        let id = inner.next_id;
+        inner.next_id += 1;
+        inner.reserved.insert(id, amount);
+
+        Reservation {
+            id,
+            amount,
+            reservations: self.clone(),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Inner<A>> {
+        self.inner.lock().expect("reservations lock not poisoned")
+    }
+}
+
+fn available<A>(reserved: &Ledger<A>, balance: A) -> Option<A>
+where
+    A: CheckedArithmetic,
+{
+    reserved
+        .values()
+        .try_fold(balance, |left, amount| left.checked_sub(*amount))
+}
+
+/// The funds earmarked for one swap, released when dropped.
+#[derive(Debug)]
+pub struct Reservation<A> {
+    id: u64,
+    amount: A,
+    reservations: Reservations<A>,
+}
+
+impl<A: Copy> Reservation<A> {
+    pub fn amount(&self) -> A {
+        self.amount
+    }
+}
+
+impl<A> Drop for Reservation<A> {
+    fn drop(&mut self) {
+        // Not panicking while dropping, a poisoned lock fails the next
+        // reservation anyway
+        if let Ok(mut inner) = self.reservations.inner.lock() {
+            inner.reserved.remove(&self.id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::monero;
+
+    fn xmr(amount: &str) -> monero::Amount {
+        monero::Amount::parse_monero(amount).unwrap()
+    }
+
+    #[test]
+    fn concurrent_swaps_cannot_reserve_the_same_balance() {
+        let reservations = Reservations::default();
+        let balance = xmr("1.5");
+
+        let first = reservations.reserve(xmr("1"), balance);
+
+        assert!(first.is_some());
+        assert!(reservations.reserve(xmr("1"), balance).is_none());
+        assert_eq!(reservations.available(balance), Some(xmr("0.5")));
+        assert!(reservations.reserve(xmr("0.5"), balance).is_some());
+    }
+
+    #[test]
+    fn dropped_reservation_is_released() {
+        let reservations = Reservations::default();
+        let balance = xmr("1");
+
+        let reservation = reservations.reserve(xmr("1"), balance).unwrap();
+        assert_eq!(reservations.available(balance), Some(monero::Amount::ZERO));
+
+        drop(reservation);
+
+        assert_eq!(reservations.available(balance), Some(balance));
+        assert!(reservations.reserve(xmr("1"), balance).is_some());
+    }
+
+    #[test]
+    fn reservations_exceeding_a_shrunk_balance_leave_nothing() {
+        let reservations = Reservations::default();
+        let _reservation = reservations.reserve(xmr("1"), xmr("1")).unwrap();
+
+        assert_eq!(reservations.available(xmr("0.5")), None);
+        assert!(reservations.reserve(xmr("0.1"), xmr("0.5")).is_none());
+    }
+}
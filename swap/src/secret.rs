@@ -0,0 +1,85 @@
+//! Key material the swap states hold on to.
+//!
+//! The states of both parties end up in logs, error messages and test
+//! failures through their `Debug` output and are kept in memory for the whole
+//! swap. Wrapping the secret parts in [`Secret`] keeps them out of the former
+//! and overwrites them once a state is dropped.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::ops::Deref;
+
+/// A secret that prints as `Secret(..)` and is wiped when dropped.
+///
+/// It is serialized as the value it wraps, wrapping a field does not change
+/// the format of the database or of the messages the field is part of.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Secret<T: Wipe>(T);
+
+impl<T: Wipe> Secret<T> {
+    pub fn new(value: T) -> Self {
+        Self(value)
+    }
+}
+
+impl<T: Wipe> From<T> for Secret<T> {
+    fn from(value: T) -> Self {
+        Self(value)
+    }
+}
+
+impl<T: Wipe> Deref for Secret<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: Wipe> fmt::Debug for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Secret(..)")
+    }
+}
+
+impl<T: Wipe> Drop for Secret<T> {
+    fn drop(&mut self) {
+        self.0.wipe();
+    }
+}
+
+/// Overwrites a secret in place, implemented next to the types it is used
+/// with.
+pub trait Wipe {
+    fn wipe(&mut self);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::monero;
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn debug_output_does_not_contain_the_secret() {
+        let key = monero::PrivateKey::from_scalar(monero::Scalar::random(&mut OsRng));
+        let secret = Secret::new(key);
+
+        assert_eq!(format!("{:?}", secret), "Secret(..)");
+    }
+
+    #[test]
+    fn serializes_as_the_wrapped_value() {
+        let scalar = monero::Scalar::random(&mut OsRng);
+        let secret = Secret::new(scalar);
+
+        assert_eq!(
+            serde_json::to_string(&secret).unwrap(),
+            serde_json::to_string(&scalar).unwrap()
+        );
+        let deserialized: Secret<monero::Scalar> =
+            serde_json::from_str(&serde_json::to_string(&scalar).unwrap()).unwrap();
+        assert_eq!(deserialized, secret);
+    }
+}
@@ -1,22 +1,26 @@
+use crate::data::DataDir;
 use crate::fs::ensure_directory_exists;
 use ::bitcoin::secp256k1::constants::SECRET_KEY_SIZE;
 use ::bitcoin::secp256k1::{self, SecretKey};
 use anyhow::{Context, Result};
 use bdk::bitcoin::util::bip32::ExtendedPrivKey;
+use bip39::Mnemonic;
 use bitcoin::hashes::{sha256, Hash, HashEngine};
 use libp2p::identity;
 use pem::{encode, Pem};
 use rand::prelude::*;
+use std::convert::TryFrom;
 use std::ffi::OsStr;
 use std::fmt;
 use std::fs::{self, File};
 use std::io::{self, Write};
 use std::path::{Path, PathBuf};
+use subtle::ConstantTimeEq;
 use torut::onion::TorSecretKeyV3;
+use zeroize::{Zeroize, Zeroizing};
 
 pub const SEED_LENGTH: usize = 32;
 
-#[derive(Eq, PartialEq)]
 pub struct Seed([u8; SEED_LENGTH]);
 
 impl Seed {
@@ -35,7 +39,7 @@ impl Seed {
         network: bitcoin::Network,
     ) -> Result<ExtendedPrivKey> {
         let seed = self.derive(b"BITCOIN_EXTENDED_PRIVATE_KEY").bytes();
-        let private_key = ExtendedPrivKey::new_master(network, &seed)
+        let private_key = ExtendedPrivKey::new_master(network, &seed[..])
             .context("Failed to create new master extended private key")?;
 
         Ok(private_key)
@@ -43,21 +47,88 @@ impl Seed {
 
     pub fn derive_libp2p_identity(&self) -> identity::Keypair {
         let bytes = self.derive(b"NETWORK").derive(b"LIBP2P_IDENTITY").bytes();
-        let key = identity::ed25519::SecretKey::from_bytes(bytes).expect("we always pass 32 bytes");
+        let key =
+            identity::ed25519::SecretKey::from_bytes(*bytes).expect("we always pass 32 bytes");
 
         identity::Keypair::Ed25519(key.into())
     }
 
+    /// Derives the libp2p identity of an additional maker identity. Every name
+    /// results in a different identity, none of which can be linked to the
+    /// default one.
+    pub fn derive_libp2p_identity_for(&self, name: &str) -> identity::Keypair {
+        let bytes = self
+            .derive(b"NETWORK")
+            .derive(b"LIBP2P_IDENTITY")
+            .derive(name.as_bytes())
+            .bytes();
+        let key =
+            identity::ed25519::SecretKey::from_bytes(*bytes).expect("we always pass 32 bytes");
+
+        identity::Keypair::Ed25519(key.into())
+    }
+
+    /// Derives the maker key the default maker identity starts out with, see
+    /// [`maker_key`](crate::network::maker_key).
+    pub fn derive_maker_key(&self) -> identity::ed25519::Keypair {
+        let bytes = self.derive(b"NETWORK").derive(b"MAKER_KEY").bytes();
+        let key =
+            identity::ed25519::SecretKey::from_bytes(*bytes).expect("we always pass 32 bytes");
+
+        key.into()
+    }
+
+    /// Derives the maker key of an additional maker identity, unlinkable to
+    /// the default one like its libp2p identity.
+    pub fn derive_maker_key_for(&self, name: &str) -> identity::ed25519::Keypair {
+        let bytes = self
+            .derive(b"NETWORK")
+            .derive(b"MAKER_KEY")
+            .derive(name.as_bytes())
+            .bytes();
+        let key =
+            identity::ed25519::SecretKey::from_bytes(*bytes).expect("we always pass 32 bytes");
+
+        key.into()
+    }
+
     pub fn derive_torv3_key(&self) -> TorSecretKeyV3 {
         let bytes = self.derive(b"TOR").bytes();
-        let sk = ed25519_dalek::SecretKey::from_bytes(&bytes)
+        let sk = ed25519_dalek::SecretKey::from_bytes(&bytes[..])
             .expect("Failed to create a new extended secret key for Tor.");
         let esk = ed25519_dalek::ExpandedSecretKey::from(&sk);
         esk.to_bytes().into()
     }
 
+    /// The seed as the 24 words of a BIP39 mnemonic, to back it up on paper.
+    pub fn to_mnemonic(&self) -> Mnemonic {
+        Mnemonic::from_entropy(&self.0).expect("32 bytes are valid BIP39 entropy")
+    }
+
+    /// Restores a seed from the words of [`Seed::to_mnemonic`].
+    pub fn from_mnemonic(words: &str) -> Result<Self, Error> {
+        let entropy = Zeroizing::new(Mnemonic::parse(words)?.to_entropy());
+        let bytes = <[u8; SEED_LENGTH]>::try_from(entropy.as_slice())
+            .map_err(|_| Error::IncorrectLength(entropy.len()))?;
+        let _ = SecretKey::from_slice(&bytes)?;
+
+        Ok(Seed(bytes))
+    }
+
+    /// Writes the seed to the seed file in `data_dir`, fails if that already
+    /// holds a seed.
+    pub fn write_new(&self, data_dir: &Path) -> Result<(), Error> {
+        let file_path = DataDir::new(data_dir).seed();
+
+        if file_path.exists() {
+            return Err(Error::AlreadyExists(file_path));
+        }
+
+        self.write_to(file_path)
+    }
+
     pub fn from_file_or_generate(data_dir: &Path) -> Result<Self, Error> {
-        let file_path_buf = data_dir.join("seed.pem");
+        let file_path_buf = DataDir::new(data_dir).seed();
         let file_path = Path::new(&file_path_buf);
 
         if file_path.exists() {
@@ -80,7 +151,7 @@ impl Seed {
     fn derive(&self, scope: &[u8]) -> Self {
         let mut engine = sha256::HashEngine::default();
 
-        engine.input(&self.bytes());
+        engine.input(&self.0);
         engine.input(scope);
 
         let hash = sha256::Hash::from_engine(engine);
@@ -88,8 +159,8 @@ impl Seed {
         Self(hash.into_inner())
     }
 
-    fn bytes(&self) -> [u8; SEED_LENGTH] {
-        self.0
+    fn bytes(&self) -> Zeroizing<[u8; SEED_LENGTH]> {
+        Zeroizing::new(self.0)
     }
 
     fn from_file<D>(seed_file: D) -> Result<Self, Error>
@@ -105,7 +176,7 @@ impl Seed {
         Self::from_pem(pem)
     }
 
-    fn from_pem(pem: pem::Pem) -> Result<Self, Error> {
+    fn from_pem(mut pem: pem::Pem) -> Result<Self, Error> {
         if pem.contents.len() != SEED_LENGTH {
             Err(Error::IncorrectLength(pem.contents.len()))
         } else {
@@ -113,6 +184,7 @@ impl Seed {
             for (i, b) in pem.contents.iter().enumerate() {
                 array[i] = *b;
             }
+            pem.contents.zeroize();
 
             Ok(Self::from(array))
         }
@@ -121,13 +193,13 @@ impl Seed {
     fn write_to(&self, seed_file: PathBuf) -> Result<(), Error> {
         ensure_directory_exists(&seed_file)?;
 
-        let data = self.bytes();
-        let pem = Pem {
+        let mut pem = Pem {
             tag: String::from("SEED"),
-            contents: data.to_vec(),
+            contents: self.0.to_vec(),
         };
 
-        let pem_string = encode(&pem);
+        let pem_string = Zeroizing::new(encode(&pem));
+        pem.contents.zeroize();
 
         let mut file = File::create(seed_file)?;
         file.write_all(pem_string.as_bytes())?;
@@ -136,6 +208,20 @@ impl Seed {
     }
 }
 
+impl PartialEq for Seed {
+    fn eq(&self, other: &Self) -> bool {
+        self.0[..].ct_eq(&other.0[..]).into()
+    }
+}
+
+impl Eq for Seed {}
+
+impl Drop for Seed {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
 impl fmt::Debug for Seed {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "Seed([*****])")
@@ -155,6 +241,7 @@ impl From<[u8; SEED_LENGTH]> for Seed {
 }
 
 #[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
 pub enum Error {
     #[error("Secp256k1: ")]
     Secp256k1(#[from] secp256k1::Error),
@@ -168,6 +255,10 @@ pub enum Error {
     Rand(#[from] rand::Error),
     #[error("no default path")]
     NoDefaultPath,
+    #[error("BIP39 mnemonic: {0}")]
+    Mnemonic(#[from] bip39::Error),
+    #[error("seed file {} already exists", .0.display())]
+    AlreadyExists(PathBuf),
 }
 
 #[cfg(test)]
@@ -199,7 +290,21 @@ syl9wSYaruvgxg9P5Q1qkZaq5YkM6GvXkxe+VYrL/XM=
         let pem = pem::parse(pem_string).unwrap();
         let got = Seed::from_pem(pem).unwrap();
 
-        assert_eq!(got.bytes(), *want);
+        assert_eq!(*got.bytes(), *want);
+    }
+
+    #[test]
+    fn named_libp2p_identities_differ_from_default_and_each_other() {
+        let seed = Seed::from(*b"this string is exactly 32 bytes!");
+
+        let default = seed.derive_libp2p_identity().public();
+        let first = seed.derive_libp2p_identity_for("first").public();
+        let second = seed.derive_libp2p_identity_for("second").public();
+
+        assert_ne!(first, default);
+        assert_ne!(second, default);
+        assert_ne!(first, second);
+        assert_eq!(seed.derive_libp2p_identity_for("first").public(), first);
     }
 
     #[test]
@@ -240,6 +345,29 @@ mbKANv2qKGmNVg1qtquj6Hx1pFPelpqOfE2JaJJAMEg1FlFhNRNlFlE=
         }
     }
 
+    #[test]
+    fn round_trip_through_mnemonic() {
+        let seed = Seed::random().unwrap();
+
+        let words = seed.to_mnemonic().to_string();
+        assert_eq!(words.split_whitespace().count(), 24);
+
+        assert_eq!(Seed::from_mnemonic(&words).unwrap(), seed);
+        assert!(Seed::from_mnemonic("abandon abandon abandon").is_err());
+    }
+
+    #[test]
+    fn new_seed_does_not_overwrite_existing_one() {
+        let dir = tempfile::tempdir().unwrap();
+
+        Seed::random().unwrap().write_new(dir.path()).unwrap();
+
+        assert!(matches!(
+            Seed::random().unwrap().write_new(dir.path()),
+            Err(Error::AlreadyExists(_))
+        ));
+    }
+
     #[test]
     fn round_trip_through_file_write_read() {
         let tmpfile = temp_dir().join("seed.pem");
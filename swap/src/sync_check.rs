@@ -0,0 +1,385 @@
+//! Detects a local clock or blockchain backends that are out of sync with the
+//! rest of the world.
+//!
+//! The timelocks of a swap are expressed in blocks, hence our view of the
+//! current block height has to be accurate for us to act in time. The local
+//! clock is compared against a set of NTP servers and the block heights of
+//! our backends against other public Electrum servers and monero daemons.
+//! Sources that cannot be reached are ignored, the median of the remaining
+//! ones is used as reference.
+
+use crate::{bitcoin, env, monero};
+use anyhow::{bail, Context, Result};
+use bdk::electrum_client::ElectrumApi;
+use monero_rpc::monerod;
+use monero_rpc::monerod::MonerodRpc as _;
+use std::convert::TryFrom;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::net::UdpSocket;
+
+pub const DEFAULT_NTP_SERVERS: &[&str] = &[
+    "pool.ntp.org:123",
+    "time.cloudflare.com:123",
+    "time.google.com:123",
+];
+
+const BITCOIN_REFERENCE_ELECTRUM_RPC_URLS: &[&str] = &[
+    "ssl://electrum.blockstream.info:50002",
+    "ssl://electrum.emzy.de:50002",
+    "ssl://bitcoin.lukechilds.co:50002",
+];
+const BITCOIN_TESTNET_REFERENCE_ELECTRUM_RPC_URLS: &[&str] = &[
+    "ssl://electrum.blockstream.info:60002",
+    "ssl://testnet.aranguren.org:51002",
+    "ssl://blackie.c3-soft.com:57006",
+];
+
+const MONERO_REFERENCE_DAEMONS: &[&str] = &[
+    "xmr-node.cakewallet.com:18081",
+    "node.sethforprivacy.com:18089",
+    "nodes.hashvault.pro:18081",
+];
+const MONERO_STAGENET_REFERENCE_DAEMONS: &[&str] = &[
+    "stagenet.xmr-tw.org:38081",
+    "stagenet.community.rino.io:38081",
+    "node.sethforprivacy.com:38089",
+];
+
+/// How long we wait for a single source to respond.
+const SOURCE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Seconds between the NTP epoch (1900) and the unix epoch (1970).
+const NTP_UNIX_EPOCH_OFFSET: i128 = 2_208_988_800;
+const NANOS_PER_SEC: i128 = 1_000_000_000;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Thresholds {
+    pub max_clock_skew: Duration,
+    pub max_bitcoin_height_lag: u32,
+    pub max_monero_height_lag: u32,
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Self {
+            max_clock_skew: Duration::from_secs(5 * 60),
+            max_bitcoin_height_lag: 2,
+            max_monero_height_lag: 10,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, thiserror::Error)]
+#[non_exhaustive]
+pub enum OutOfSync {
+    #[error("The local clock is off by {offset_secs} seconds compared to NTP servers")]
+    ClockSkew { offset_secs: i64 },
+    #[error("The Electrum server is {lag} blocks behind other Electrum servers")]
+    BitcoinHeightLag { lag: u32 },
+    #[error("The monero daemon is {lag} blocks behind other monero daemons")]
+    MoneroHeightLag { lag: u32 },
+}
+
+/// Checks the local clock and the block heights reported by our wallets
+/// against the reference sources of the network we are on.
+///
+/// Returns everything that exceeds the given thresholds. If no reference
+/// source can be reached for one of the checks, that check is skipped with a
+/// warning.
+pub async fn check(
+    bitcoin_wallet: &bitcoin::Wallet,
+    monero_wallet: &dyn monero::MoneroWallet,
+    env_config: env::Config,
+    thresholds: Thresholds,
+) -> Vec<OutOfSync> {
+    let mut out_of_sync = Vec::new();
+
+    match clock_offset(DEFAULT_NTP_SERVERS).await {
+        Ok(offset) => {
+            let offset_secs = offset.whole_seconds();
+            tracing::debug!(%offset_secs, "Determined offset of local clock");
+
+            if offset.abs() > thresholds.max_clock_skew {
+                out_of_sync.push(OutOfSync::ClockSkew { offset_secs });
+            }
+        }
+        Err(e) => tracing::warn!("Failed to determine clock skew: {:#}", e),
+    }
+
+    let bitcoin_reference_urls = match env_config.bitcoin_network {
+        ::bitcoin::Network::Bitcoin => BITCOIN_REFERENCE_ELECTRUM_RPC_URLS,
+        ::bitcoin::Network::Testnet => BITCOIN_TESTNET_REFERENCE_ELECTRUM_RPC_URLS,
+        _ => &[],
+    };
+    match bitcoin_height_lag(bitcoin_wallet, bitcoin_reference_urls).await {
+        Ok(lag) if lag > thresholds.max_bitcoin_height_lag => {
+            out_of_sync.push(OutOfSync::BitcoinHeightLag { lag })
+        }
+        Ok(_) => {}
+        Err(e) => tracing::warn!("Failed to compare Bitcoin block height: {:#}", e),
+    }
+
+    // Regtest runs on the Monero mainnet network, there are no references for it
+    let monero_reference_daemons = match env_config.monero_network {
+        monero::Network::Mainnet if env_config.bitcoin_network == ::bitcoin::Network::Bitcoin => {
+            MONERO_REFERENCE_DAEMONS
+        }
+        monero::Network::Stagenet => MONERO_STAGENET_REFERENCE_DAEMONS,
+        _ => &[],
+    };
+    match monero_height_lag(monero_wallet, monero_reference_daemons).await {
+        Ok(lag) if lag > thresholds.max_monero_height_lag => {
+            out_of_sync.push(OutOfSync::MoneroHeightLag { lag })
+        }
+        Ok(_) => {}
+        Err(e) => tracing::warn!("Failed to compare Monero block height: {:#}", e),
+    }
+
+    out_of_sync
+}
+
+/// Returns the median offset of the local clock compared to the given NTP
+/// servers. A positive offset means the local clock is behind.
+pub async fn clock_offset(servers: &[&str]) -> Result<time::Duration> {
+    let mut offsets = Vec::new();
+
+    for server in servers {
+        match tokio::time::timeout(SOURCE_TIMEOUT, sntp_offset(server)).await {
+            Ok(Ok(offset)) => offsets.push(offset),
+            Ok(Err(e)) => tracing::debug!(%server, "Failed to query NTP server: {:#}", e),
+            Err(_) => tracing::debug!(%server, "NTP server did not respond in time"),
+        }
+    }
+
+    median(offsets).context("None of the NTP servers responded")
+}
+
+async fn bitcoin_height_lag(
+    bitcoin_wallet: &bitcoin::Wallet,
+    reference_urls: &[&str],
+) -> Result<u32> {
+    let local_height = u32::from(bitcoin_wallet.latest_block_height().await?);
+
+    let mut reference_heights = Vec::new();
+    for url in reference_urls {
+        let height = async {
+            let url = url.to_string();
+            // The Electrum client is blocking
+            tokio::task::spawn_blocking(move || electrum_height(&url)).await?
+        };
+
+        match tokio::time::timeout(SOURCE_TIMEOUT, height).await {
+            Ok(Ok(height)) => reference_heights.push(height),
+            Ok(Err(e)) => {
+                tracing::debug!(%url, "Failed to get block height from Electrum server: {:#}", e)
+            }
+            Err(_) => tracing::debug!(%url, "Electrum server did not respond in time"),
+        }
+    }
+
+    height_lag(local_height, reference_heights)
+}
+
+fn electrum_height(url: &str) -> Result<u32> {
+    let client = bdk::electrum_client::Client::new(url)
+        .with_context(|| format!("Failed to connect to {}", url))?;
+    let latest_block = client
+        .block_headers_subscribe()
+        .context("Failed to subscribe to header notifications")?;
+
+    Ok(u32::from(bitcoin::BlockHeight::try_from(latest_block)?))
+}
+
+async fn monero_height_lag(
+    monero_wallet: &dyn monero::MoneroWallet,
+    reference_daemons: &[&str],
+) -> Result<u32> {
+    let local_height = monero_wallet.block_height().await?.height;
+
+    let mut reference_heights = Vec::new();
+    for daemon in reference_daemons {
+        let height = async {
+            let block_count = monerod::Client::from_daemon_address(daemon)?
+                .get_block_count()
+                .await?;
+
+            Ok::<_, anyhow::Error>(block_count.count)
+        };
+
+        match tokio::time::timeout(SOURCE_TIMEOUT, height).await {
+            Ok(Ok(height)) => reference_heights.push(height),
+            Ok(Err(e)) => {
+                tracing::debug!(%daemon, "Failed to get block height from monero daemon: {:#}", e)
+            }
+            Err(_) => tracing::debug!(%daemon, "Monero daemon did not respond in time"),
+        }
+    }
+
+    height_lag(local_height, reference_heights)
+}
+
+/// The number of blocks the local height is behind the median of the
+/// reference heights. Being ahead of the references is not considered a lag.
+fn height_lag(local_height: u32, reference_heights: Vec<u32>) -> Result<u32> {
+    let reference_height =
+        median(reference_heights).context("None of the reference sources responded")?;
+
+    Ok(reference_height.saturating_sub(local_height))
+}
+
+/// Returns the lower median of the given values.
+fn median<T: Ord + Copy>(mut values: Vec<T>) -> Option<T> {
+    values.sort();
+
+    values.get(values.len().saturating_sub(1) / 2).copied()
+}
+
+async fn sntp_offset(server: &str) -> Result<time::Duration> {
+    let socket = UdpSocket::bind("0.0.0.0:0").await?;
+    socket
+        .connect(server)
+        .await
+        .with_context(|| format!("Failed to connect to {}", server))?;
+
+    // Leap indicator 0, version 3, mode 3 (client), everything else is zero.
+    let mut request = [0u8; 48];
+    request[0] = 0x1b;
+
+    let request_sent = SystemTime::now();
+    socket.send(&request).await?;
+
+    let mut response = [0u8; 48];
+    let received = socket.recv(&mut response).await?;
+    let response_received = SystemTime::now();
+
+    if received < response.len() {
+        bail!("Received truncated NTP response of {} bytes", received)
+    }
+
+    offset_from_response(&response, request_sent, response_received)
+}
+
+/// Computes the clock offset as `((T2 - T1) + (T3 - T4)) / 2` where T1 and T4
+/// are the local times the request was sent and the response received and T2
+/// and T3 the server times the request was received and the response sent.
+fn offset_from_response(
+    response: &[u8; 48],
+    request_sent: SystemTime,
+    response_received: SystemTime,
+) -> Result<time::Duration> {
+    let mode = response[0] & 0x07;
+    if mode != 4 {
+        bail!("Expected NTP server response (mode 4), got mode {}", mode)
+    }
+
+    let stratum = response[1];
+    if stratum == 0 {
+        bail!("NTP server sent a kiss-of-death response")
+    }
+
+    let t1 = unix_nanos(request_sent)?;
+    let t2 = ntp_timestamp_nanos(&response[32..40]);
+    let t3 = ntp_timestamp_nanos(&response[40..48]);
+    let t4 = unix_nanos(response_received)?;
+
+    let offset = ((t2 - t1) + (t3 - t4)) / 2;
+
+    Ok(time::Duration::nanoseconds(i64::try_from(offset)?))
+}
+
+/// Converts a 64 bit NTP timestamp (32 bit seconds, 32 bit fraction) to
+/// nanoseconds since the unix epoch.
+fn ntp_timestamp_nanos(timestamp: &[u8]) -> i128 {
+    let seconds = u32::from_be_bytes([timestamp[0], timestamp[1], timestamp[2], timestamp[3]]);
+    let fraction = u32::from_be_bytes([timestamp[4], timestamp[5], timestamp[6], timestamp[7]]);
+
+    (i128::from(seconds) - NTP_UNIX_EPOCH_OFFSET) * NANOS_PER_SEC
+        + ((i128::from(fraction) * NANOS_PER_SEC) >> 32)
+}
+
+fn unix_nanos(time: SystemTime) -> Result<i128> {
+    let nanos = time
+        .duration_since(UNIX_EPOCH)
+        .context("System time is before the unix epoch")?
+        .as_nanos();
+
+    Ok(i128::try_from(nanos)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ntp_timestamp(unix_millis: u64) -> [u8; 8] {
+        let seconds = u32::try_from(unix_millis / 1000 + 2_208_988_800).unwrap();
+        let fraction = u32::try_from(((unix_millis % 1000) << 32) / 1000).unwrap();
+
+        let mut timestamp = [0u8; 8];
+        timestamp[..4].copy_from_slice(&seconds.to_be_bytes());
+        timestamp[4..].copy_from_slice(&fraction.to_be_bytes());
+        timestamp
+    }
+
+    fn response(server_received_millis: u64, server_sent_millis: u64) -> [u8; 48] {
+        let mut response = [0u8; 48];
+        response[0] = 0x1c;
+        response[1] = 2;
+        response[32..40].copy_from_slice(&ntp_timestamp(server_received_millis));
+        response[40..48].copy_from_slice(&ntp_timestamp(server_sent_millis));
+        response
+    }
+
+    fn local_time(unix_millis: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_millis(unix_millis)
+    }
+
+    #[test]
+    fn given_server_clock_ahead_then_offset_is_positive() {
+        // 100ms network delay each way, server clock 10s ahead
+        let response = response(1_640_000_010_100, 1_640_000_010_100);
+
+        let offset = offset_from_response(
+            &response,
+            local_time(1_640_000_000_000),
+            local_time(1_640_000_000_200),
+        )
+        .unwrap();
+
+        assert!((offset.whole_milliseconds() - 10_000).abs() <= 1);
+    }
+
+    #[test]
+    fn given_server_clock_behind_then_offset_is_negative() {
+        let response = response(1_639_999_700_050, 1_639_999_700_060);
+
+        let offset = offset_from_response(
+            &response,
+            local_time(1_640_000_000_000),
+            local_time(1_640_000_000_110),
+        )
+        .unwrap();
+
+        assert!((offset.whole_milliseconds() + 300_000).abs() <= 1);
+    }
+
+    #[test]
+    fn given_kiss_of_death_response_then_fails() {
+        let mut response = response(1_640_000_000_000, 1_640_000_000_000);
+        response[1] = 0;
+
+        let result = offset_from_response(
+            &response,
+            local_time(1_640_000_000_000),
+            local_time(1_640_000_000_000),
+        );
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn height_lag_is_measured_against_median_reference() {
+        assert_eq!(height_lag(100, vec![103, 1_000_000, 104]).unwrap(), 4);
+        assert_eq!(height_lag(110, vec![103, 104]).unwrap(), 0);
+        assert!(height_lag(100, vec![]).is_err());
+    }
+}
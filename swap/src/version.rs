@@ -0,0 +1,131 @@
+//! What exactly a binary was built from.
+//!
+//! Reported by the `version` command of the CLI and the ASB and logged when
+//! they start to talk to peers, so a bug report or a log file can be traced
+//! back to a commit.
+
+use crate::network::codec;
+use crate::network::cooperative_xmr_redeem_after_punish::CooperativeXmrRedeemAfterPunishProtocol;
+use crate::network::encrypted_signature::EncryptedSignatureProtocol;
+use crate::network::quote::BidQuoteProtocol;
+use crate::network::receipt::ReceiptProtocol;
+use crate::network::swap_setup;
+use crate::network::swap_status::SwapStatusProtocol;
+use crate::network::transfer_proof::TransferProofProtocol;
+use libp2p::core::ProtocolName;
+use libp2p::request_response::ProtocolSupport;
+use serde::Serialize;
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct BuildInfo {
+    pub version: &'static str,
+    pub git_describe: &'static str,
+    pub git_commit: &'static str,
+    pub build_timestamp: &'static str,
+    pub target: &'static str,
+    pub features: Vec<&'static str>,
+    pub protocols: Vec<String>,
+}
+
+impl BuildInfo {
+    pub fn current() -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            git_describe: env!("VERGEN_GIT_SEMVER_LIGHTWEIGHT"),
+            git_commit: env!("VERGEN_GIT_SHA"),
+            build_timestamp: env!("VERGEN_BUILD_TIMESTAMP"),
+            target: env!("SWAP_TARGET"),
+            features: env!("SWAP_FEATURES")
+                .split(',')
+                .filter(|feature| !feature.is_empty())
+                .collect(),
+            protocols: protocols(),
+        }
+    }
+}
+
+impl fmt::Display for BuildInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "version: {}", self.version)?;
+        writeln!(f, "git: {} ({})", self.git_describe, self.git_commit)?;
+        writeln!(f, "built: {}", self.build_timestamp)?;
+        writeln!(f, "target: {}", self.target)?;
+        if self.features.is_empty() {
+            writeln!(f, "features: none")?;
+        } else {
+            writeln!(f, "features: {}", self.features.join(", "))?;
+        }
+        write!(f, "protocols:")?;
+        for protocol in &self.protocols {
+            write!(f, "\n  {}", protocol)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Identifies the binary in logs, in the format the libp2p identify protocol
+/// uses for its `agent_version`, e.g. `asb/0.10.2 (1a2b3c4d)`.
+pub fn agent_version(binary: &str) -> String {
+    let commit = env!("VERGEN_GIT_SHA");
+
+    format!(
+        "{}/{} ({})",
+        binary,
+        env!("CARGO_PKG_VERSION"),
+        commit.get(..8).unwrap_or(commit)
+    )
+}
+
+/// The names of all protocols this build speaks, in the order they are
+/// negotiated.
+fn protocols() -> Vec<String> {
+    let mut names = vec![
+        name(&BidQuoteProtocol),
+        String::from(swap_setup::protocol::PROTOCOL),
+    ];
+    names.extend(versioned(TransferProofProtocol));
+    names.extend(versioned(EncryptedSignatureProtocol));
+    names.extend(versioned(SwapStatusProtocol));
+    names.extend(versioned(CooperativeXmrRedeemAfterPunishProtocol));
+    names.extend(versioned(ReceiptProtocol));
+
+    names
+}
+
+fn versioned<P: ProtocolName + Clone>(protocol: P) -> Vec<String> {
+    codec::versions(protocol, ProtocolSupport::Full)
+        .iter()
+        .map(|(version, _)| name(version))
+        .collect()
+}
+
+fn name(protocol: &impl ProtocolName) -> String {
+    String::from_utf8_lossy(protocol.protocol_name()).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lists_both_versions_of_request_response_protocols() {
+        let protocols = protocols();
+
+        assert!(protocols.contains(&String::from("/comit/xmr/btc/bid-quote/1.0.0")));
+        assert!(protocols.contains(&String::from("/comit/xmr/btc/swap_setup/2.0.0")));
+        assert!(protocols.contains(&String::from("/comit/xmr/btc/transfer_proof/1.0.0")));
+        assert!(protocols.contains(&String::from(
+            "/comit/xmr/btc/transfer_proof/1.0.0/envelope"
+        )));
+    }
+
+    #[test]
+    fn agent_version_names_binary_version_and_commit() {
+        let agent = agent_version("asb");
+
+        assert!(agent.starts_with(&format!("asb/{} (", env!("CARGO_PKG_VERSION"))));
+        assert!(agent.ends_with(')'));
+    }
+}
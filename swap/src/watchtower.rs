@@ -0,0 +1,377 @@
+//! The tower side of the [watchtower protocol](crate::network::watchtower).
+//!
+//! The tower keeps the registered transactions in a JSON file and checks them
+//! periodically. A transaction is published as soon as the transaction it
+//! spends has as many confirmations as its timelock requires. Once all
+//! transactions of a registration were seen on the blockchain, or it is older
+//! than the maximum age, e.g. because the swap was redeemed and the cancel
+//! transaction can never be published, the registration is dropped.
+
+use crate::bitcoin::wallet::ScriptStatus;
+use crate::bitcoin::Wallet;
+use crate::network::watchtower::{self, Request, Response};
+use crate::protocol::presigned::PresignedTransaction;
+use anyhow::{bail, Context, Result};
+use futures::StreamExt;
+use libp2p::request_response::{RequestResponseEvent, RequestResponseMessage};
+use libp2p::swarm::SwarmEvent;
+use libp2p::{PeerId, Swarm};
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DisplayFromStr};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+/// How often the registered transactions are checked. Timelocks are counted
+/// in blocks, checking more often than a block is mined gains nothing.
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Bounds how much a single client can make the tower watch.
+const MAX_REGISTRATIONS_PER_PEER: usize = 100;
+
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Registration {
+    #[serde_as(as = "DisplayFromStr")]
+    pub peer_id: PeerId,
+    pub swap_id: Uuid,
+    /// The Unix timestamp of the registration.
+    pub registered_at: i64,
+    pub transactions: Vec<Watched>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Watched {
+    #[serde(flatten)]
+    pub transaction: PresignedTransaction,
+    /// Whether the transaction was seen on the blockchain, published by the
+    /// tower or anyone else.
+    pub seen: bool,
+}
+
+/// The registrations, stored as JSON in a file that is rewritten on every
+/// change.
+#[derive(Debug)]
+pub struct Registrations {
+    path: PathBuf,
+    registrations: Vec<Registration>,
+}
+
+impl Registrations {
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+
+        let registrations = if path.exists() {
+            let contents = fs::read(&path)
+                .with_context(|| format!("Failed to read registrations from {}", path.display()))?;
+            serde_json::from_slice(&contents)
+                .with_context(|| format!("{} is corrupted", path.display()))?
+        } else {
+            Vec::new()
+        };
+
+        Ok(Self {
+            path,
+            registrations,
+        })
+    }
+
+    pub fn all(&self) -> &[Registration] {
+        &self.registrations
+    }
+
+    /// Adds the registration, replacing an earlier one of the same peer for
+    /// the same swap.
+    pub fn insert(
+        &mut self,
+        peer_id: PeerId,
+        swap_id: Uuid,
+        transactions: Vec<PresignedTransaction>,
+        now: OffsetDateTime,
+    ) -> Result<()> {
+        watchtower::validate(&transactions)?;
+
+        self.registrations.retain(|registration| {
+            (registration.peer_id, registration.swap_id) != (peer_id, swap_id)
+        });
+
+        let of_peer = self
+            .registrations
+            .iter()
+            .filter(|registration| registration.peer_id == peer_id)
+            .count();
+        if of_peer >= MAX_REGISTRATIONS_PER_PEER {
+            bail!(
+                "More than {} swaps are registered already",
+                MAX_REGISTRATIONS_PER_PEER
+            )
+        }
+
+        self.registrations.push(Registration {
+            peer_id,
+            swap_id,
+            registered_at: now.unix_timestamp(),
+            transactions: transactions
+                .into_iter()
+                .map(|transaction| Watched {
+                    transaction,
+                    seen: false,
+                })
+                .collect(),
+        });
+
+        self.save()
+    }
+
+    /// Drops the registrations whose transactions were all seen and the ones
+    /// registered more than `max_age` before `now`.
+    fn prune(&mut self, max_age: Duration, now: OffsetDateTime) {
+        let oldest = (now - max_age).unix_timestamp();
+
+        self.registrations.retain(|registration| {
+            registration.registered_at >= oldest
+                && registration
+                    .transactions
+                    .iter()
+                    .any(|watched| !watched.seen)
+        });
+    }
+
+    fn save(&self) -> Result<()> {
+        let temporary = self.path.with_extension("json.new");
+
+        fs::write(&temporary, serde_json::to_vec_pretty(&self.registrations)?)
+            .with_context(|| format!("Failed to write {}", temporary.display()))?;
+        fs::rename(&temporary, &self.path)
+            .with_context(|| format!("Failed to write {}", self.path.display()))?;
+
+        Ok(())
+    }
+}
+
+pub struct Tower {
+    swarm: Swarm<watchtower::Behaviour>,
+    bitcoin_wallet: Arc<Wallet>,
+    registrations: Registrations,
+    max_age: Duration,
+}
+
+impl Tower {
+    pub fn new(
+        swarm: Swarm<watchtower::Behaviour>,
+        bitcoin_wallet: Arc<Wallet>,
+        registrations: Registrations,
+        max_age: Duration,
+    ) -> Self {
+        Self {
+            swarm,
+            bitcoin_wallet,
+            registrations,
+            max_age,
+        }
+    }
+
+    pub async fn run(mut self) {
+        let mut interval = tokio::time::interval(CHECK_INTERVAL);
+
+        loop {
+            tokio::select! {
+                event = self.swarm.select_next_some() => {
+                    match event {
+                        SwarmEvent::Behaviour(RequestResponseEvent::Message {
+                            peer,
+                            message: RequestResponseMessage::Request { request, channel, .. },
+                        }) => {
+                            let response = self.register(peer, request);
+
+                            if self.swarm.behaviour_mut().send_response(channel, response).is_err() {
+                                tracing::debug!(%peer, "Failed to respond to registration, the client disconnected");
+                            }
+                        }
+                        SwarmEvent::NewListenAddr { address, .. } => {
+                            tracing::info!(%address, "Listening for registrations");
+                        }
+                        _ => {}
+                    }
+                }
+                _ = interval.tick() => {
+                    if let Err(error) = self.check_registrations().await {
+                        tracing::warn!("Failed to check the registered transactions: {:#}", error);
+                    }
+                }
+            }
+        }
+    }
+
+    fn register(&mut self, peer_id: PeerId, request: Request) -> Response {
+        let swap_id = request.swap_id;
+
+        match self.registrations.insert(
+            peer_id,
+            swap_id,
+            request.transactions,
+            OffsetDateTime::now_utc(),
+        ) {
+            Ok(()) => {
+                tracing::info!(%peer_id, %swap_id, "Registered presigned transactions");
+
+                Response::Registered { swap_id }
+            }
+            Err(error) => {
+                tracing::debug!(%peer_id, %swap_id, "Rejected registration: {:#}", error);
+
+                Response::Rejected {
+                    swap_id,
+                    reason: format!("{:#}", error),
+                }
+            }
+        }
+    }
+
+    async fn check_registrations(&mut self) -> Result<()> {
+        for registration in self.registrations.registrations.iter_mut() {
+            let swap_id = registration.swap_id;
+
+            for watched in registration
+                .transactions
+                .iter_mut()
+                .filter(|watched| !watched.seen)
+            {
+                match publish_if_expired(&self.bitcoin_wallet, &watched.transaction).await {
+                    Ok(seen) => watched.seen = seen,
+                    Err(error) => tracing::debug!(
+                        %swap_id,
+                        kind = %watched.transaction.kind,
+                        "Not publishing presigned transaction yet: {:#}",
+                        error
+                    ),
+                }
+            }
+        }
+
+        self.registrations
+            .prune(self.max_age, OffsetDateTime::now_utc());
+        self.registrations.save()
+    }
+}
+
+/// Publishes the transaction if its timelock expired, returns whether the
+/// transaction is on the blockchain.
+async fn publish_if_expired(wallet: &Wallet, presigned: &PresignedTransaction) -> Result<bool> {
+    let transaction = &presigned.transaction;
+    let output = transaction
+        .output
+        .first()
+        .context("Transaction without outputs")?;
+
+    if wallet
+        .status_of_script(&(presigned.txid(), output.script_pubkey.clone()))
+        .await?
+        != ScriptStatus::Unseen
+    {
+        return Ok(true);
+    }
+
+    let spent = transaction
+        .input
+        .first()
+        .context("Transaction without inputs")?
+        .previous_output;
+    let spent_transaction = wallet.get_raw_transaction(spent.txid).await?;
+    let spent_output = spent_transaction
+        .output
+        .get(spent.vout as usize)
+        .context("The spent output does not exist")?;
+
+    if !wallet
+        .status_of_script(&(spent.txid, spent_output.script_pubkey.clone()))
+        .await?
+        .is_confirmed_with(presigned.timelock)
+    {
+        return Ok(false);
+    }
+
+    let (txid, _) = wallet
+        .broadcast(transaction.clone(), &presigned.kind.to_string())
+        .await?;
+    tracing::info!(%txid, kind = %presigned.kind, "Published presigned transaction");
+
+    Ok(true)
+}
+
+/// The file the registrations are stored in.
+pub fn registrations_file(data_dir: &Path) -> PathBuf {
+    data_dir.join("registrations.json")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitcoin::Transaction;
+    use crate::protocol::presigned::Kind;
+    use ::bitcoin::{OutPoint, TxIn};
+    use tempfile::tempdir;
+
+    fn transactions() -> Vec<PresignedTransaction> {
+        let transaction = |kind, previous_output| {
+            PresignedTransaction::new(kind, 0u32, Transaction {
+                version: 2,
+                lock_time: 0,
+                input: vec![TxIn {
+                    previous_output,
+                    ..Default::default()
+                }],
+                output: vec![],
+            })
+        };
+        let cancel = transaction(Kind::Cancel, OutPoint::default());
+        let refund = transaction(Kind::Refund, OutPoint::new(cancel.txid(), 0));
+
+        vec![cancel, refund]
+    }
+
+    #[test]
+    fn registrations_survive_a_restart_and_expire() {
+        let dir = tempdir().unwrap();
+        let path = registrations_file(dir.path());
+        let peer_id = PeerId::random();
+        let swap_id = Uuid::new_v4();
+        let now = OffsetDateTime::now_utc();
+
+        let mut registrations = Registrations::open(&path).unwrap();
+        registrations
+            .insert(peer_id, swap_id, transactions(), now)
+            .unwrap();
+        registrations
+            .insert(peer_id, swap_id, transactions(), now)
+            .unwrap();
+
+        let mut reopened = Registrations::open(&path).unwrap();
+        assert_eq!(reopened.all(), registrations.all());
+        assert_eq!(reopened.all().len(), 1);
+
+        reopened.prune(Duration::from_secs(60), now + Duration::from_secs(61));
+        assert!(reopened.all().is_empty());
+    }
+
+    #[test]
+    fn invalid_registrations_are_rejected() {
+        let dir = tempdir().unwrap();
+        let mut registrations = Registrations::open(registrations_file(dir.path())).unwrap();
+        let mut transactions = transactions();
+        transactions.remove(0);
+
+        assert!(registrations
+            .insert(
+                PeerId::random(),
+                Uuid::new_v4(),
+                transactions,
+                OffsetDateTime::now_utc()
+            )
+            .is_err());
+        assert!(registrations.all().is_empty());
+    }
+}
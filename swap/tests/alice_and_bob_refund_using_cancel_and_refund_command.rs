@@ -1,3 +1,5 @@
+#![cfg(feature = "docker-tests")]
+
 pub mod harness;
 
 use harness::alice_run_until::is_xmr_lock_transaction_sent;
@@ -50,7 +52,8 @@ async fn given_alice_and_bob_manually_refund_after_funds_locked_both_refund() {
 
         // Bob manually cancels
         bob_join_handle.abort();
-        let (_, state) = cli::cancel(bob_swap.id, bob_swap.bitcoin_wallet, bob_swap.db).await?;
+        let (_, state) =
+            cli::cancel(bob_swap.id, bob_swap.bitcoin_wallet, bob_swap.db, false).await?;
         assert!(matches!(state, BobState::BtcCancelled { .. }));
 
         let (bob_swap, bob_join_handle) = ctx
@@ -60,7 +63,8 @@ async fn given_alice_and_bob_manually_refund_after_funds_locked_both_refund() {
 
         // Bob manually refunds
         bob_join_handle.abort();
-        let bob_state = cli::refund(bob_swap.id, bob_swap.bitcoin_wallet, bob_swap.db).await?;
+        let bob_state =
+            cli::refund(bob_swap.id, bob_swap.bitcoin_wallet, bob_swap.db, false).await?;
 
         ctx.assert_bob_refunded(bob_state).await;
 
@@ -1,3 +1,5 @@
+#![cfg(feature = "docker-tests")]
+
 pub mod harness;
 
 use harness::alice_run_until::is_xmr_lock_transaction_sent;
@@ -38,8 +40,22 @@ async fn given_alice_and_bob_manually_cancel_when_timelock_not_expired_errors()
             AliceState::XmrLockTransactionSent { .. }
         ));
 
-        // Bob tries but fails to manually cancel
-        let error = cli::cancel(bob_swap.id, bob_swap.bitcoin_wallet, bob_swap.db)
+        // Bob's manual cancel is refused before anything is published
+        let error = cli::cancel(
+            bob_swap.id,
+            bob_swap.bitcoin_wallet.clone(),
+            bob_swap.db.clone(),
+            false,
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(
+            error.downcast::<cli::cancel::Error>().unwrap(),
+            cli::cancel::Error::CancelTimelockNotExpiredYet
+        );
+
+        // Forcing it publishes the cancel transaction, which is rejected
+        let error = cli::cancel(bob_swap.id, bob_swap.bitcoin_wallet, bob_swap.db, true)
             .await
             .unwrap_err();
         assert_eq!(
@@ -68,8 +84,22 @@ async fn given_alice_and_bob_manually_cancel_when_timelock_not_expired_errors()
             .await;
         assert!(matches!(bob_swap.state, BobState::BtcLocked { .. }));
 
-        // Bob tries but fails to manually refund
-        let error = cli::refund(bob_swap.id, bob_swap.bitcoin_wallet, bob_swap.db)
+        // Bob's manual refund is refused as well
+        let error = cli::refund(
+            bob_swap.id,
+            bob_swap.bitcoin_wallet.clone(),
+            bob_swap.db.clone(),
+            false,
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(
+            error.downcast::<cli::refund::Error>().unwrap(),
+            cli::refund::Error::CancelTimelockNotExpiredYet
+        );
+
+        // Forcing it fails because the cancel transaction is not on chain
+        let error = cli::refund(bob_swap.id, bob_swap.bitcoin_wallet, bob_swap.db, true)
             .await
             .unwrap_err();
         assert_eq!(
@@ -89,7 +119,7 @@ async fn given_alice_and_bob_manually_cancel_when_timelock_not_expired_errors()
             AliceState::XmrLockTransactionSent { .. }
         ));
 
-        // Alice tries but fails manual cancel
+        // Alice tries but fails manual refund
         let result = asb::refund(
             alice_swap.swap_id,
             alice_swap.bitcoin_wallet,
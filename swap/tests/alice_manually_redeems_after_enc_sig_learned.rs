@@ -1,3 +1,5 @@
+#![cfg(feature = "docker-tests")]
+
 pub mod harness;
 
 use harness::alice_run_until::is_encsig_learned;
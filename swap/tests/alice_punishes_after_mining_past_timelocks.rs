@@ -0,0 +1,96 @@
+#![cfg(feature = "docker-tests")]
+
+pub mod harness;
+
+use harness::alice_run_until::{is_btc_cancelled, is_xmr_lock_transaction_sent};
+use harness::bob_run_until::is_btc_locked;
+use harness::SlowPunishConfig;
+use swap::asb::FixedRate;
+use swap::protocol::alice::AliceState;
+use swap::protocol::bob::BobState;
+use swap::protocol::{alice, bob};
+
+/// Bob locks Btc and vanishes. The timelocks are too long to wait for, so the
+/// blocks are mined right away. Alice cancels and punishes on her own, the
+/// punish transaction spends the cancel transaction once the punish timelock
+/// expired.
+#[tokio::test]
+async fn alice_punishes_after_mining_past_timelocks() {
+    harness::setup_test(SlowPunishConfig, |mut ctx| async move {
+        let (bob_swap, bob_join_handle) = ctx.bob_swap().await;
+        let bob_swap_id = bob_swap.id;
+        let bob_swap = tokio::spawn(bob::run_until(bob_swap, is_btc_locked));
+
+        let alice_swap = ctx.alice_next_swap().await;
+        let alice_bitcoin_wallet = alice_swap.bitcoin_wallet.clone();
+        let alice_swap = tokio::spawn(alice::run_until(
+            alice_swap,
+            is_xmr_lock_transaction_sent,
+            FixedRate::default(),
+        ));
+
+        let bob_state = bob_swap.await??;
+        assert!(matches!(bob_state, BobState::BtcLocked { .. }));
+        bob_join_handle.abort();
+
+        let alice_state = alice_swap.await??;
+        assert!(matches!(
+            alice_state,
+            AliceState::XmrLockTransactionSent { .. }
+        ));
+
+        ctx.mine_blocks(SlowPunishConfig::CANCEL_TIMELOCK).await?;
+
+        ctx.restart_alice().await;
+        let alice_swap = ctx.alice_next_swap().await;
+        let alice_state =
+            alice::run_until(alice_swap, is_btc_cancelled, FixedRate::default()).await?;
+
+        let state3 = if let AliceState::BtcCancelled { state3, .. } = &alice_state {
+            state3.clone()
+        } else {
+            panic!("Alice in unexpected state {}", alice_state);
+        };
+
+        // The punish timelock only starts once the cancel transaction is confirmed
+        alice_bitcoin_wallet
+            .subscribe_to(state3.tx_cancel())
+            .await
+            .wait_until_confirmed_with(1)
+            .await?;
+        ctx.mine_blocks(SlowPunishConfig::PUNISH_TIMELOCK).await?;
+
+        ctx.restart_alice().await;
+        let alice_swap = ctx.alice_next_swap().await;
+        let alice_state = alice::run(alice_swap, FixedRate::default()).await?;
+        ctx.assert_alice_punished(alice_state).await;
+
+        let tx_punish = alice_bitcoin_wallet
+            .get_raw_transaction(state3.signed_punish_transaction()?.txid())
+            .await?;
+        assert_eq!(tx_punish.input.len(), 1);
+        assert_eq!(
+            tx_punish.input[0].previous_output,
+            state3.tx_cancel().as_outpoint()
+        );
+        assert_eq!(
+            tx_punish.input[0].sequence,
+            SlowPunishConfig::PUNISH_TIMELOCK
+        );
+        assert_eq!(tx_punish.output.len(), 1);
+
+        // Restart Bob after Alice punished to ensure Bob transitions to
+        // punished and does not run indefinitely
+        let (bob_swap, _) = ctx
+            .stop_and_resume_bob_from_db(bob_join_handle, bob_swap_id)
+            .await;
+        assert!(matches!(bob_swap.state, BobState::BtcLocked { .. }));
+
+        let bob_state = bob::run(bob_swap).await?;
+
+        ctx.assert_bob_punished(bob_state).await;
+
+        Ok(())
+    })
+    .await;
+}
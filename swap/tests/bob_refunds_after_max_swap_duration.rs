@@ -0,0 +1,42 @@
+#![cfg(feature = "docker-tests")]
+
+pub mod harness;
+
+use harness::bob_run_until::is_xmr_locked;
+use harness::FastCancelConfig;
+use std::time::Duration;
+use swap::asb::FixedRate;
+use swap::protocol::{alice, bob};
+
+/// Bob resumes the swap after it exceeded the maximum swap duration. He
+/// withholds the encrypted signature and refunds once the cancel timelock
+/// expired, Alice refunds as well.
+#[tokio::test]
+async fn given_max_swap_duration_passed_after_xmr_locked_then_both_refund() {
+    harness::setup_test(FastCancelConfig, |mut ctx| async move {
+        let (bob_swap, bob_join_handle) = ctx.bob_swap().await;
+        let bob_swap_id = bob_swap.id;
+        let bob_swap = tokio::spawn(bob::run_until(bob_swap, is_xmr_locked));
+
+        let alice_swap = ctx.alice_next_swap().await;
+        let alice_swap = tokio::spawn(alice::run(alice_swap, FixedRate::default()));
+
+        let _ = bob_swap.await??;
+
+        let (mut bob_swap, _) = ctx
+            .stop_and_resume_bob_from_db(bob_join_handle, bob_swap_id)
+            .await;
+        bob_swap.env_config.max_swap_duration = Some(Duration::ZERO);
+        let bob_db = bob_swap.db.clone();
+
+        let bob_state = bob::run(bob_swap).await?;
+        ctx.assert_bob_refunded(bob_state).await;
+        assert!(bob_db.get_wind_down(bob_swap_id).await?.is_some());
+
+        let alice_state = alice_swap.await??;
+        ctx.assert_alice_refunded(alice_state).await;
+
+        Ok(())
+    })
+    .await;
+}
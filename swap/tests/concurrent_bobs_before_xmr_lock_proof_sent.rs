@@ -1,3 +1,5 @@
+#![cfg(feature = "docker-tests")]
+
 pub mod harness;
 
 use harness::bob_run_until::is_btc_locked;
@@ -14,11 +14,12 @@ use std::fmt;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Duration;
-use swap::asb::FixedRate;
+use swap::asb::{FixedRate, TradingControls, TradingParameters};
 use swap::bitcoin::{CancelTimelock, PunishTimelock, TxCancel, TxPunish, TxRedeem, TxRefund};
 use swap::database::SqliteDatabase;
 use swap::env::{Config, GetConfig};
 use swap::fs::ensure_directory_exists;
+use swap::network::maker_key::MakerKey;
 use swap::network::swarm;
 use swap::protocol::alice::{AliceState, Swap};
 use swap::protocol::bob::BobState;
@@ -28,6 +29,7 @@ use swap::{asb, bitcoin, cli, env, monero};
 use tempfile::{tempdir, NamedTempFile};
 use testcontainers::clients::Cli;
 use testcontainers::{Container, Docker, RunArgs};
+use tokio::join;
 use tokio::sync::mpsc;
 use tokio::sync::mpsc::Receiver;
 use tokio::task::JoinHandle;
@@ -52,7 +54,6 @@ where
     let env_config = C::get_config();
 
     let (monero, containers) = init_containers(&cli).await;
-    monero.init_miner().await.unwrap();
 
     let btc_amount = bitcoin::Amount::from_sat(1_000_000);
     let xmr_amount = monero::Amount::from_monero(btc_amount.as_btc() / FixedRate::RATE).unwrap();
@@ -99,7 +100,7 @@ where
 
     let (bob_bitcoin_wallet, bob_monero_wallet) = init_test_wallets(
         MONERO_WALLET_NAME_BOB,
-        containers.bitcoind_url,
+        containers.bitcoind_url.clone(),
         &monero,
         bob_starting_balances.clone(),
         tempdir().unwrap().path(),
@@ -109,20 +110,31 @@ where
     )
     .await;
 
+    let bob_db_path = NamedTempFile::new().unwrap().path().to_path_buf();
+    tokio::fs::File::create(&bob_db_path).await.unwrap();
+    let bob_db = Arc::new(SqliteDatabase::open(&bob_db_path).await.unwrap());
+    let tor_socks5_port = get_port()
+        .expect("We don't care about Tor in the tests so we get a free port to disable it.");
+
     let bob_params = BobParams {
-        seed: Seed::random().unwrap(),
-        db_path: NamedTempFile::new().unwrap().path().to_path_buf(),
-        bitcoin_wallet: bob_bitcoin_wallet.clone(),
-        monero_wallet: bob_monero_wallet.clone(),
+        factory: cli::SwapFactory::new(
+            Seed::random().unwrap().derive_libp2p_identity(),
+            bob_db,
+            bob_bitcoin_wallet.clone(),
+            bob_monero_wallet.clone(),
+            env_config,
+            tor_socks5_port,
+        ),
+        monero_receive_address: bob_monero_wallet.get_main_address(),
         alice_address: alice_listen_address.clone(),
         alice_peer_id: alice_handle.peer_id,
-        env_config,
     };
 
     monero.start_miner().await.unwrap();
 
     let test = TestContext {
         env_config,
+        bitcoind_url: containers.bitcoind_url,
         btc_amount,
         xmr_amount,
         alice_seed,
@@ -142,20 +154,36 @@ where
     testfn(test).await.unwrap()
 }
 
+/// Starts the Bitcoin and the Monero containers concurrently.
 async fn init_containers(cli: &Cli) -> (Monero, Containers<'_>) {
     let prefix = random_prefix();
     let bitcoind_name = format!("{}_{}", prefix, "bitcoind");
-    let (bitcoind, bitcoind_url) =
-        init_bitcoind_container(&cli, prefix.clone(), bitcoind_name.clone(), prefix.clone())
-            .await
-            .expect("could not init bitcoind");
-    let electrs = init_electrs_container(&cli, prefix.clone(), bitcoind_name, prefix)
-        .await
-        .expect("could not init electrs");
-    let (monero, monerod_container, monero_wallet_rpc_containers) =
-        Monero::new(&cli, vec![MONERO_WALLET_NAME_ALICE, MONERO_WALLET_NAME_BOB])
+
+    let bitcoin = async {
+        let (bitcoind, bitcoind_url) =
+            init_bitcoind_container(&cli, prefix.clone(), bitcoind_name.clone(), prefix.clone())
+                .await
+                .expect("could not init bitcoind");
+        let electrs = init_electrs_container(&cli, prefix.clone(), bitcoind_name, prefix.clone())
             .await
-            .unwrap();
+            .expect("could not init electrs");
+
+        (bitcoind, bitcoind_url, electrs)
+    };
+    let monero = async {
+        let (monero, monerod_container, monero_wallet_rpc_containers) =
+            Monero::new(&cli, vec![MONERO_WALLET_NAME_ALICE, MONERO_WALLET_NAME_BOB])
+                .await
+                .unwrap();
+        monero.init_miner().await.unwrap();
+
+        (monero, monerod_container, monero_wallet_rpc_containers)
+    };
+
+    let (
+        (bitcoind, bitcoind_url, electrs),
+        (monero, monerod_container, monero_wallet_rpc_containers),
+    ) = join!(bitcoin, monero);
 
     (monero, Containers {
         bitcoind_url,
@@ -231,32 +259,42 @@ async fn start_alice(
     }
     let db = Arc::new(SqliteDatabase::open(db_path.as_path()).await.unwrap());
 
-    let min_buy = bitcoin::Amount::from_sat(u64::MIN);
-    let max_buy = bitcoin::Amount::from_sat(u64::MAX);
+    let controls = TradingControls::new(TradingParameters {
+        paused: false,
+        ask_spread: Default::default(),
+        min_buy: bitcoin::Amount::from_sat(u64::MIN),
+        max_buy: bitcoin::Amount::from_sat(u64::MAX),
+    });
     let latest_rate = FixedRate::default();
-    let resume_only = false;
 
+    let identity = seed.derive_libp2p_identity();
     let mut swarm = swarm::asb(
-        &seed,
-        min_buy,
-        max_buy,
+        identity.clone(),
+        controls.clone(),
         latest_rate,
-        resume_only,
         env_config,
         None,
+        &[],
     )
     .unwrap();
     swarm.listen_on(listen_address).unwrap();
 
     let (event_loop, swap_handle) = asb::EventLoop::new(
         swarm,
+        identity,
+        MakerKey::new(seed.derive_maker_key()),
         env_config,
         bitcoin_wallet,
         monero_wallet,
         db,
         FixedRate::default(),
-        min_buy,
-        max_buy,
+        controls,
+        Arc::new(asb::policy::AcceptAll),
+        None,
+        None,
+        false,
+        asb::Approvals::default(),
+        asb::DEFAULT_IDENTITY.to_owned(),
     )
     .unwrap();
 
@@ -295,7 +333,8 @@ async fn init_test_wallets(
         env_config,
     )
     .await
-    .unwrap();
+    .unwrap()
+    .with_monerod(monero.monerod().client().clone());
 
     let electrum_rpc_url = {
         let input = format!("tcp://@localhost:{}", electrum_rpc_port);
@@ -303,7 +342,7 @@ async fn init_test_wallets(
     };
 
     let btc_wallet = swap::bitcoin::Wallet::new(
-        electrum_rpc_url,
+        vec![electrum_rpc_url],
         datadir,
         seed.derive_extended_private_key(env_config.bitcoin_network)
             .expect("Could not create extended private key from seed"),
@@ -397,37 +436,16 @@ impl StartingBalances {
 }
 
 struct BobParams {
-    seed: Seed,
-    db_path: PathBuf,
-    bitcoin_wallet: Arc<bitcoin::Wallet>,
-    monero_wallet: Arc<monero::Wallet>,
+    factory: cli::SwapFactory,
+    monero_receive_address: monero::Address,
     alice_address: Multiaddr,
     alice_peer_id: PeerId,
-    env_config: Config,
 }
 
 impl BobParams {
     pub async fn new_swap_from_db(&self, swap_id: Uuid) -> Result<(bob::Swap, cli::EventLoop)> {
         let (event_loop, handle) = self.new_eventloop(swap_id).await?;
-
-        if let Some(parent_dir) = self.db_path.parent() {
-            ensure_directory_exists(parent_dir)?;
-        }
-        if !self.db_path.exists() {
-            tokio::fs::File::create(&self.db_path).await?;
-        }
-        let db = Arc::new(SqliteDatabase::open(&self.db_path).await?);
-
-        let swap = bob::Swap::from_db(
-            db,
-            swap_id,
-            self.bitcoin_wallet.clone(),
-            self.monero_wallet.clone(),
-            self.env_config,
-            handle,
-            self.monero_wallet.get_main_address(),
-        )
-        .await?;
+        let swap = self.factory.resume_swap(swap_id, handle).await?;
 
         Ok((swap, event_loop))
     }
@@ -439,26 +457,18 @@ impl BobParams {
         let swap_id = Uuid::new_v4();
 
         let (event_loop, handle) = self.new_eventloop(swap_id).await?;
-
-        if let Some(parent_dir) = self.db_path.parent() {
-            ensure_directory_exists(parent_dir)?;
-        }
-        if !self.db_path.exists() {
-            tokio::fs::File::create(&self.db_path).await?;
-        }
-        let db = Arc::new(SqliteDatabase::open(&self.db_path).await?);
-
-        let swap = bob::Swap::new(
-            db,
-            swap_id,
-            self.bitcoin_wallet.clone(),
-            self.monero_wallet.clone(),
-            self.env_config,
-            handle,
-            self.monero_wallet.get_main_address(),
-            self.bitcoin_wallet.new_address().await?,
-            btc_amount,
-        );
+        let swap = self
+            .factory
+            .new_swap(
+                swap_id,
+                self.alice_peer_id,
+                handle,
+                self.monero_receive_address,
+                self.factory.bitcoin_wallet().new_address().await?,
+                btc_amount,
+                None,
+            )
+            .await?;
 
         Ok((swap, event_loop))
     }
@@ -467,25 +477,13 @@ impl BobParams {
         &self,
         swap_id: Uuid,
     ) -> Result<(cli::EventLoop, cli::EventLoopHandle)> {
-        let tor_socks5_port = get_port()
-            .expect("We don't care about Tor in the tests so we get a free port to disable it.");
-
-        let behaviour = cli::Behaviour::new(
-            self.alice_peer_id,
-            self.env_config,
-            self.bitcoin_wallet.clone(),
-        );
-        let mut swarm = swarm::cli(
-            self.seed.derive_libp2p_identity(),
-            tor_socks5_port,
-            behaviour,
-        )
-        .await?;
-        swarm
-            .behaviour_mut()
-            .add_address(self.alice_peer_id, self.alice_address.clone());
-
-        cli::EventLoop::new(swap_id, swarm, self.alice_peer_id, self.env_config)
+        self.factory
+            .event_loop(
+                swap_id,
+                self.alice_peer_id,
+                vec![self.alice_address.clone()],
+            )
+            .await
     }
 }
 
@@ -510,6 +508,7 @@ impl AliceApplicationHandle {
 
 pub struct TestContext {
     env_config: Config,
+    bitcoind_url: Url,
 
     btc_amount: bitcoin::Amount,
     xmr_amount: monero::Amount,
@@ -548,6 +547,22 @@ impl TestContext {
         self.alice_swap_handle = alice_swap_handle;
     }
 
+    /// Mines `count` blocks at once, e.g. to move past a timelock without
+    /// waiting for the blocks that are mined every second.
+    pub async fn mine_blocks(&self, count: u32) -> Result<()> {
+        let bitcoind_client = Client::new(self.bitcoind_url.clone());
+
+        let reward_address = bitcoind_client
+            .with_wallet(BITCOIN_TEST_WALLET_NAME)?
+            .getnewaddress(None, None)
+            .await?;
+        bitcoind_client
+            .generatetoaddress(count, reward_address, None)
+            .await?;
+
+        Ok(())
+    }
+
     pub async fn alice_next_swap(&mut self) -> alice::Swap {
         timeout(Duration::from_secs(20), self.alice_swap_handle.recv())
             .await
@@ -622,7 +637,7 @@ impl TestContext {
     }
 
     pub async fn assert_alice_punished(&self, state: AliceState) {
-        assert!(matches!(state, AliceState::BtcPunished));
+        assert!(matches!(state, AliceState::BtcPunished { .. }));
 
         assert_eventual_balance(
             self.alice_bitcoin_wallet.as_ref(),
@@ -968,6 +983,10 @@ pub mod alice_run_until {
     pub fn is_encsig_learned(state: &AliceState) -> bool {
         matches!(state, AliceState::EncSigLearned { .. })
     }
+
+    pub fn is_btc_cancelled(state: &AliceState) -> bool {
+        matches!(state, AliceState::BtcCancelled { .. })
+    }
 }
 
 pub mod bob_run_until {
@@ -1023,3 +1042,22 @@ impl GetConfig for FastPunishConfig {
         }
     }
 }
+
+/// Timelocks that take too long to wait for, tests have to mine past them with
+/// [`TestContext::mine_blocks`].
+pub struct SlowPunishConfig;
+
+impl SlowPunishConfig {
+    pub const CANCEL_TIMELOCK: u32 = 1000;
+    pub const PUNISH_TIMELOCK: u32 = 1000;
+}
+
+impl GetConfig for SlowPunishConfig {
+    fn get_config() -> Config {
+        Config {
+            bitcoin_cancel_timelock: CancelTimelock::new(Self::CANCEL_TIMELOCK),
+            bitcoin_punish_timelock: PunishTimelock::new(Self::PUNISH_TIMELOCK),
+            ..env::Regtest::get_config()
+        }
+    }
+}